@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use persistent_keystore_rs::{Client, Field};
+use persistent_keystore_rs::prelude::*;
+use persistent_keystore_rs::generator::{Generator, CATEGORIES};
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::path::PathBuf;
+
+const SIZES: [u64; 3] = [100, 1_000, 10_000];
+const TABLE: &str = "Bench";
+
+fn db_path(name: &str) -> PathBuf {
+    let mut path = temp_dir();
+    path.push(format!("bench-{}.db", name));
+    if path.exists() {
+        std::fs::remove_file(&path).unwrap();
+    };
+    path
+}
+
+fn populated_client(name: &str, size: u64) -> (Box<dyn DatabaseClient>, PathBuf) {
+    let path = db_path(name);
+    let mut c = Client::new(&path, None).unwrap();
+    c.create_table(Generator::table(TABLE)).unwrap();
+    for entry in Generator::new(size).entries(size) {
+        c.insert(TABLE.to_string(), entry).unwrap();
+    };
+    (c, path)
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || {
+                    let path = db_path("insert");
+                    let mut c = Client::new(&path, None).unwrap();
+                    c.create_table(Generator::table(TABLE)).unwrap();
+                    (c, Generator::new(size).entries(size))
+                },
+                |(mut c, entries)| {
+                    for entry in entries {
+                        c.insert(TABLE.to_string(), entry).unwrap();
+                    };
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    };
+    group.finish();
+}
+
+fn get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for size in SIZES {
+        let (mut client, _) = populated_client("get", size);
+        let key = Generator::key(size / 2);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| client.get(TABLE.to_string(), key.clone()).unwrap())
+        });
+    };
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+    for size in SIZES {
+        let (mut client, _) = populated_client("query", size);
+        let criteria = HashMap::from_iter(vec![("Category".to_string(), Field::String(CATEGORIES[0].to_string()))]);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| client.query(TABLE.to_string(), criteria.clone()).unwrap())
+        });
+    };
+    group.finish();
+}
+
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    for size in SIZES {
+        let (mut client, _) = populated_client("scan", size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| client.scan(TABLE.to_string()).unwrap())
+        });
+    };
+    group.finish();
+}
+
+fn save(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save");
+    for size in SIZES {
+        let (mut client, _) = populated_client("save", size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| client.save().unwrap())
+        });
+    };
+    group.finish();
+}
+
+fn open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("open");
+    for size in SIZES {
+        let (mut client, path) = populated_client("open", size);
+        client.save().unwrap();
+        drop(client);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| Client::open(&path).unwrap())
+        });
+    };
+    group.finish();
+}
+
+criterion_group!(benches, insert, get, query, scan, save, open);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Prints the Tables and Entries added, removed and changed between two database files
+//! ```text
+//! cargo run --example diff -- backup.db live.db
+//! ```
+//! Exits with status 1 if the files differ and 2 if either cannot be read.
+use persistent_keystore_rs::Client;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [a, b] = args.as_slice() else {
+        eprintln!("usage: diff <a.db> <b.db>");
+        std::process::exit(2);
+    };
+
+    let diff = match Client::diff_files(a, b) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("Unable to compare {} and {}: {}", a, b, e);
+            std::process::exit(2);
+        },
+    };
+
+    for table in &diff.tables_added {
+        println!("+ table {}", table);
+    };
+    for table in &diff.tables_removed {
+        println!("- table {}", table);
+    };
+    for table in &diff.tables_changed {
+        println!("~ table {}", table.name);
+        if table.schema_changed {
+            println!("  ~ schema");
+        };
+        for entry in &table.added {
+            println!("  + {}", entry.primary_field);
+        };
+        for entry in &table.removed {
+            println!("  - {}", entry.primary_field);
+        };
+        for change in &table.changed {
+            println!("  ~ {} ({})", change.key, change.fields.join(", "));
+        };
+    };
+
+    if !diff.is_empty() {
+        std::process::exit(1);
+    };
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use tracing::error;
+
+use crate::errors::*;
+use crate::observer::{self, Operation, OperationEvent, OperationObserver};
+use crate::structs::Field;
+
+/// Decision of the authorizer of a Client, registered with ClientBuilder::authorizer, on
+/// whether an operation may be made
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Authorization {
+    Allow,
+    Deny,
+}
+
+impl From<bool> for Authorization {
+    /// Allows the operation if true
+    fn from(allowed: bool) -> Self {
+        if allowed {
+            Authorization::Allow
+        } else {
+            Authorization::Deny
+        }
+    }
+}
+
+/// Closure consulted before each operation made through a Client
+pub(crate) type Authorizer = Arc<dyn Fn(&OperationEvent<'_>) -> Authorization + Send + Sync>;
+
+/// Observers, authorizer and context of a Client, carried by a Snapshot so that reads
+/// through it are observed and authorized as reads through the Client
+#[derive(Clone, Default)]
+pub(crate) struct AccessControl {
+    pub(crate) observers: Vec<Arc<dyn OperationObserver>>,
+    pub(crate) authorizer: Option<Authorizer>,
+    pub(crate) context: Option<Arc<str>>,
+}
+
+impl AccessControl {
+    /// Notifies the observers of an operation about to be attempted, then returns
+    /// DatabaseError::AccessDenied if the authorizer denies the operation
+    pub(crate) fn authorize(&self, operation: Operation, table: &str, key: Option<&Field>) -> Result<(), DatabaseError> {
+        if self.observers.is_empty() && self.authorizer.is_none() {
+            return Ok(())
+        };
+        let event = OperationEvent{
+            operation,
+            table,
+            key,
+            context: self.context.as_deref(),
+        };
+        for observer in self.observers.iter() {
+            observer::notify(observer.as_ref(), &event);
+        };
+
+        if let Some(authorizer) = &self.authorizer {
+            if authorizer(&event) == Authorization::Deny {
+                error!("Access denied to {} of table {}", operation, table);
+                for observer in self.observers.iter() {
+                    observer.on_denied(&event);
+                };
+                return Err(DatabaseError::AccessDenied(format!("{} of table {}", operation, table)))
+            };
+        };
+        Ok(())
+    }
+}
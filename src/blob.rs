@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde_derive::{Serialize, Deserialize};
+use tracing::{debug, error, trace};
+
+use crate::checksum;
+use crate::structs::*;
+use crate::errors::*;
+
+/// Returns the directory holding the externalized values of the named Table of the database
+/// at the supplied path; the table name is hashed so that any name results in a valid name
+pub(crate) fn blob_dir(database: &Path, table: &str) -> PathBuf {
+    let mut name = database.as_os_str().to_owned();
+    name.push(format!(".{:016x}.blobs", checksum(table.as_bytes())));
+    PathBuf::from(name)
+}
+
+/// Removes the directory of externalized values of the named Table of the database at the
+/// supplied path, if any
+pub(crate) fn remove_blob_dir(database: &Path, table: &str) -> Result<(), DatabaseError> {
+    let dir = blob_dir(database, table);
+    match std::fs::remove_dir_all(&dir) {
+        Ok(_) => {
+            debug!("Removed blob directory {:?}", dir);
+            Ok(())
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Side file holding a single externalized value
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct BlobRef {
+    id: u64,
+    len: u64,
+    checksum: u64,
+}
+
+/// Values of a Table configured with TableBuilder::externalize_above, stored in side files
+/// rather than with their Entries.
+///
+/// Each value is written once to its own file, named by a sequence number, and the Entry
+/// keeps a reference to it which is saved in the database file.  Files are never rewritten,
+/// so the database file remains valid after later writes; files no longer referenced are
+/// removed by DatabaseClient::compact once the database has been saved.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct BlobStore {
+    #[serde(serialize_with = "crate::ordered::nested_map")]
+    refs: HashMap<Field, HashMap<String, BlobRef>, EntryHasher>,
+    next_id: u64,
+    #[serde(skip)]
+    dir: Option<PathBuf>,
+}
+
+impl BlobStore {
+    /// Stores values in the supplied directory, creating it if it does not exist
+    pub(crate) fn attach(&mut self, dir: &Path) -> Result<(), DatabaseError> {
+        debug!("Attaching blob directory {:?}", dir);
+        std::fs::create_dir_all(dir)?;
+        self.dir = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Returns the number of externalized values
+    pub(crate) fn len(&self) -> usize {
+        self.refs.values().map(|r| r.len()).sum()
+    }
+
+    fn path(dir: &Path, blob: &BlobRef) -> PathBuf {
+        dir.join(format!("{:016x}", blob.id))
+    }
+
+    fn read(&self, blob: &BlobRef) -> Result<String, DatabaseError> {
+        let dir = match &self.dir {
+            Some(d) => d,
+            None => return Err(DatabaseError::DatabaseIoError(std::io::Error::new(std::io::ErrorKind::NotFound, "blob directory is not attached"))),
+        };
+
+        let mut contents = String::new();
+        File::open(BlobStore::path(dir, blob))?.read_to_string(&mut contents)?;
+        if contents.len() as u64 != blob.len || checksum(contents.as_bytes()) != blob.checksum {
+            return Err(DatabaseError::InvalidFormat(format!("blob {:016x} does not match its checksum", blob.id)))
+        };
+        Ok(contents)
+    }
+
+    /// Moves every String field of the Entry longer than threshold to a side file, replacing
+    /// the references previously held for the Entry.  Values unchanged since they were last
+    /// written keep their existing file.
+    pub(crate) fn externalize(&mut self, entry: &mut Entry, threshold: usize) -> Result<(), DatabaseError> {
+        let dir = match &self.dir {
+            Some(d) => d.clone(),
+            None => return Ok(()),
+        };
+
+        let previous = self.refs.get(&entry.primary_field).cloned().unwrap_or_default();
+        let mut refs = HashMap::new();
+        for (name, field) in &entry.fields {
+            let value = match field {
+                Field::String(v) if v.len() > threshold => v,
+                _ => continue,
+            };
+
+            let hash = checksum(value.as_bytes());
+            let blob = match previous.get(name) {
+                Some(b) if b.len == value.len() as u64 && b.checksum == hash => *b,
+                _ => {
+                    let blob = BlobRef{
+                        id: self.next_id,
+                        len: value.len() as u64,
+                        checksum: hash,
+                    };
+                    let mut f = File::create(BlobStore::path(&dir, &blob))?;
+                    f.write_all(value.as_bytes())?;
+                    f.sync_all()?;
+                    self.next_id += 1;
+                    trace!("Externalized field {} of entry {} to blob {:016x}", name, entry.primary_field, blob.id);
+                    blob
+                },
+            };
+            refs.insert(name.clone(), blob);
+        };
+
+        for name in refs.keys() {
+            entry.fields.remove(name);
+        };
+        match refs.is_empty() {
+            true => self.refs.remove(&entry.primary_field),
+            false => self.refs.insert(entry.primary_field.clone(), refs),
+        };
+        Ok(())
+    }
+
+    /// Returns the Entry with its externalized values read back; values that cannot be read
+    /// are logged and left out, and reported by verify
+    pub(crate) fn resolve(&self, entry: Arc<Entry>) -> Arc<Entry> {
+        let refs = match self.refs.get(&entry.primary_field) {
+            Some(r) => r,
+            None => return entry,
+        };
+
+        let mut resolved = entry.as_ref().clone();
+        for (name, blob) in refs {
+            match self.read(blob) {
+                Ok(v) => {
+                    resolved.fields.insert(name.clone(), Field::String(v));
+                },
+                Err(e) => error!("Unable to read field {} of entry {}: {}", name, entry.primary_field, e),
+            };
+        };
+        Arc::new(resolved)
+    }
+
+    /// Returns the primary Field and externalized value of the supplied field of every Entry
+    pub(crate) fn project(&self, field: &String) -> Vec<(Field, Field)> {
+        let mut results = Vec::new();
+        for (key, refs) in &self.refs {
+            if let Some(blob) = refs.get(field) {
+                match self.read(blob) {
+                    Ok(v) => results.push((key.clone(), Field::String(v))),
+                    Err(e) => error!("Unable to read field {} of entry {}: {}", field, key, e),
+                };
+            };
+        };
+        results
+    }
+
+    /// Removes the references of the Entry; its files are removed by collect
+    pub(crate) fn remove(&mut self, key: &Field) {
+        self.refs.remove(key);
+    }
+
+    /// Copies every referenced file to the supplied directory and attaches it; used when a
+    /// Table is moved to another database
+    pub(crate) fn copy_to(&mut self, dir: &Path) -> Result<(), DatabaseError> {
+        let from = match self.dir.clone() {
+            Some(d) => d,
+            None => return self.attach(dir),
+        };
+
+        std::fs::create_dir_all(dir)?;
+        for blob in self.refs.values().flat_map(|r| r.values()) {
+            std::fs::copy(BlobStore::path(&from, blob), BlobStore::path(dir, blob))?;
+        };
+        self.attach(dir)
+    }
+
+    /// Removes every file of the directory that is not referenced, returning the number
+    /// of files removed.  The database must have been saved since the references changed.
+    pub(crate) fn collect(&self) -> Result<u64, DatabaseError> {
+        let dir = match &self.dir {
+            Some(d) => d,
+            None => return Ok(0),
+        };
+
+        let referenced: HashSet<String> = self.refs.values()
+            .flat_map(|r| r.values())
+            .map(|b| format!("{:016x}", b.id))
+            .collect();
+
+        let mut removed = 0;
+        for file in std::fs::read_dir(dir)? {
+            let file = file?;
+            if referenced.contains(file.file_name().to_string_lossy().as_ref()) {
+                continue
+            };
+            debug!("Removing orphaned blob {:?}", file.path());
+            std::fs::remove_file(file.path())?;
+            removed += 1;
+        };
+        Ok(removed)
+    }
+
+    /// Returns a description of every value that cannot be read, along with its key
+    pub(crate) fn verify(&self) -> Vec<(Option<Field>, String)> {
+        let mut problems = Vec::new();
+        for (key, refs) in &self.refs {
+            for (name, blob) in refs {
+                if let Err(e) = self.read(blob) {
+                    problems.push((Some(key.clone()), format!("unable to read externalized field {}: {}", name, e)));
+                };
+            };
+        };
+        problems
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::structs::*;
+
+/// Smallest number of keys a BloomFilter is sized for
+const MIN_CAPACITY: usize = 1024;
+
+/// Probabilistic set of the primary Fields of a Table configured with
+/// TableBuilder::bloom_filter, answering whether a key may exist without searching the
+/// Table.
+///
+/// Keys cannot be removed, so the filter counts removals and is rebuilt by the Table once
+/// half of the keys added have been removed, or once more keys are added than it was
+/// sized for.
+#[derive(Clone)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    inserted: usize,
+    removed: usize,
+}
+
+impl BloomFilter {
+    /// Creates a filter for twice the supplied number of keys, with the supplied rate of
+    /// false positives once full
+    pub(crate) fn new(keys: usize, false_positive_rate: f64) -> Self {
+        let capacity = (keys * 2).max(MIN_CAPACITY);
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes = ((bits as f64 / capacity as f64) * ln2).round().clamp(1.0, 16.0) as u32;
+
+        BloomFilter{
+            bits: vec![0; bits.div_ceil(64)],
+            hashes,
+            capacity,
+            inserted: 0,
+            removed: 0,
+        }
+    }
+
+    /// Returns the bit positions of the key, using double hashing
+    fn positions(&self, key: &Field) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let h1 = hasher.finish();
+        hasher.write_u64(0x9e3779b97f4a7c15);
+        let h2 = hasher.finish() | 1;
+
+        let len = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+
+    /// Adds a key that is not already held by the Table
+    pub(crate) fn insert(&mut self, key: &Field) {
+        let positions: Vec<usize> = self.positions(key).collect();
+        for p in positions {
+            self.bits[p / 64] |= 1 << (p % 64);
+        };
+        self.inserted += 1;
+    }
+
+    /// Records that a key has been removed from the Table
+    pub(crate) fn remove(&mut self) {
+        self.removed += 1;
+    }
+
+    /// Returns false if the key has certainly not been inserted
+    pub(crate) fn might_contain(&self, key: &Field) -> bool {
+        self.positions(key).all(|p| self.bits[p / 64] & (1 << (p % 64)) != 0)
+    }
+
+    /// Returns true if the filter should be rebuilt to keep its rate of false positives
+    pub(crate) fn is_stale(&self) -> bool {
+        self.inserted > self.capacity || self.removed * 2 > self.inserted
+    }
+}
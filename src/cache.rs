@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, trace};
+
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+/// In-memory cache placed in front of a Table by CachedTable.
+///
+/// The cache is populated with Entries read from or written to the Table, along with the
+/// time remaining until each expires from the Table, if any; the cache should not return
+/// an Entry after its ttl has passed.
+pub trait CacheLayer {
+    /// Returns the cached Entry with the supplied primary Field, if any
+    fn get(&mut self, key: &Field) -> Option<Arc<Entry>>;
+    /// Caches the Entry, replacing any cached Entry with the same primary Field
+    fn put(&mut self, entry: Arc<Entry>, ttl: Option<Duration>);
+    /// Removes the cached Entry with the supplied primary Field, if any
+    fn invalidate(&mut self, key: &Field);
+}
+
+/// Table of a database used as the persistent tier beneath a CacheLayer.
+///
+/// Reads are served from the cache, and populate it from the Table on a miss.  Writes go
+/// to the Table first and then to the cache, and deletes remove the Entry from both.  The
+/// time to live of each cached Entry is taken from the expiration of the Table.  Changes
+/// made to the Table other than through the CachedTable are not seen by the cache.
+/// ```
+/// use persistent_keystore_rs::{CacheLayer, CachedTable, Client, Entry, Field, FieldType, Table};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// # use std::path::Path;
+///
+/// #[derive(Default)]
+/// struct MapCache(HashMap<Field, Arc<Entry>>);
+///
+/// impl CacheLayer for MapCache {
+///     fn get(&mut self, key: &Field) -> Option<Arc<Entry>> {
+///         self.0.get(key).cloned()
+///     }
+///     fn put(&mut self, entry: Arc<Entry>, _ttl: Option<Duration>) {
+///         self.0.insert(entry.primary_field.clone(), entry);
+///     }
+///     fn invalidate(&mut self, key: &Field) {
+///         self.0.remove(key);
+///     }
+/// }
+///
+/// let mut c = Client::new(Path::new("cachedtable.db"), None).unwrap();
+/// # let table = Table::new()
+/// #    .name(String::from("MyTable"))
+/// #    .primary_field(FieldType::String).unwrap()
+/// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+/// #    .build().unwrap();
+/// # c.create_table(table).unwrap();
+/// let mut cached = CachedTable::new(c, "MyTable".to_string(), MapCache::default());
+/// let entry = Entry::new()
+///     .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+///     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+///     .build().unwrap();
+/// cached.insert(entry).unwrap();
+/// assert!(cached.cache().0.contains_key(&Field::String("MyEntry".to_string())));
+/// # std::fs::remove_file("cachedtable.db").unwrap();
+/// ```
+pub struct CachedTable<C: CacheLayer> {
+    client: Box<dyn DatabaseClient>,
+    table: String,
+    cache: C,
+}
+
+impl<C: CacheLayer> CachedTable<C> {
+    /// Places the cache in front of the named Table of the client
+    pub fn new(client: Box<dyn DatabaseClient>, table: String, cache: C) -> Self {
+        CachedTable{
+            client,
+            table,
+            cache,
+        }
+    }
+
+    /// Returns the name of the Table
+    pub fn table(&self) -> &String {
+        &self.table
+    }
+
+    /// Returns the cache
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Returns the client; changes made through it are not seen by the cache
+    pub fn client(&mut self) -> &mut Box<dyn DatabaseClient> {
+        &mut self.client
+    }
+
+    /// Returns the client and cache
+    pub fn into_inner(self) -> (Box<dyn DatabaseClient>, C) {
+        (self.client, self.cache)
+    }
+
+    /// Reads the Entry from the Table and caches it with its time to live
+    fn populate(&mut self, key: Field) -> Result<Arc<Entry>, DatabaseError> {
+        let entry = self.client.get(self.table.clone(), key.clone())?;
+        let ttl = self.client.time_to_live(self.table.clone(), key)?;
+        if ttl == Some(Duration::ZERO) {
+            debug!("Entry {} of table {} has expired; not caching", entry.primary_field, self.table);
+            self.cache.invalidate(&entry.primary_field);
+            return Ok(entry)
+        };
+
+        trace!("Caching entry {} of table {} for {:?}", entry.primary_field, self.table, ttl);
+        self.cache.put(entry.clone(), ttl);
+        Ok(entry)
+    }
+
+    /// Returns the Entry from the cache, or from the Table on a miss; see DatabaseClient::get
+    pub fn get(&mut self, key: Field) -> Result<Arc<Entry>, DatabaseError> {
+        if let Some(entry) = self.cache.get(&key) {
+            trace!("Cache hit for entry {} of table {}", key, self.table);
+            return Ok(entry)
+        };
+        trace!("Cache miss for entry {} of table {}", key, self.table);
+        self.populate(key)
+    }
+
+    /// Inserts the Entry into the Table and the cache; see DatabaseClient::insert
+    pub fn insert(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        let key = entry.primary_field.clone();
+        self.client.insert(self.table.clone(), entry)?;
+        self.populate(key)?;
+        Ok(())
+    }
+
+    /// Inserts or updates the Entry in the Table and the cache; see
+    /// DatabaseClient::insert_or_update
+    pub fn insert_or_update(&mut self, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        let key = entry.primary_field.clone();
+        let outcome = self.client.insert_or_update(self.table.clone(), entry)?;
+        self.populate(key)?;
+        Ok(outcome)
+    }
+
+    /// Updates the Entry in the Table and the cache; see DatabaseClient::update
+    pub fn update(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        let key = entry.primary_field.clone();
+        self.client.update(self.table.clone(), entry)?;
+        self.populate(key)?;
+        Ok(())
+    }
+
+    /// Deletes the Entry from the Table and the cache; see DatabaseClient::delete
+    pub fn delete(&mut self, key: Field) -> Result<(), DatabaseError> {
+        self.cache.invalidate(&key);
+        self.client.delete(self.table.clone(), key)
+    }
+}
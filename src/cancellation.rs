@@ -0,0 +1,69 @@
+use crate::sync::Arc;
+use crate::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::errors::*;
+
+/// Number of entries processed between checks of a CancellationToken
+pub(crate) const CANCELLATION_CHECK_INTERVAL: usize = 256;
+
+/// Token used to abort a long running operation, either explicitly or once a deadline
+/// passes.  Clones share the same cancellation state, so a token can be handed to an
+/// operation and cancelled from another thread.
+/// ```
+/// use persistent_keystore_rs::CancellationToken;
+/// use std::time::Duration;
+///
+/// let token = CancellationToken::new().with_timeout(Duration::from_secs(5));
+/// let handle = token.clone();
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Returns a token that is only cancelled by calling cancel
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token once the supplied Instant has passed
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Cancels the token once the supplied Duration has elapsed from now
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Cancels the token and every clone of it
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token has been cancelled or its deadline has passed
+    pub fn is_cancelled(&self) -> bool {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return true
+        };
+
+        match self.deadline {
+            Some(d) => Instant::now() >= d,
+            None => false,
+        }
+    }
+
+    /// Returns DatabaseError::Cancelled if the token has been cancelled
+    pub(crate) fn check(&self) -> Result<(), DatabaseError> {
+        if self.is_cancelled() {
+            return Err(DatabaseError::Cancelled)
+        };
+        Ok(())
+    }
+}
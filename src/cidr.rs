@@ -0,0 +1,121 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::errors::*;
+
+/// Block of IP addresses sharing a prefix, such as `10.0.0.0/8`; the value of Field::Cidr,
+/// and the subnet of Condition::InSubnet.
+///
+/// The bits of the address after the prefix are cleared, so `10.1.2.3/8` is kept as
+/// `10.0.0.0/8`.  IPv4 and IPv6 blocks never contain addresses of the other family.
+/// ```
+/// use persistent_keystore_rs::Cidr;
+/// use std::net::IpAddr;
+/// let subnet: Cidr = "192.168.1.77/24".parse().unwrap();
+/// assert_eq!(subnet.to_string(), "192.168.1.0/24");
+/// assert!(subnet.contains(&"192.168.1.200".parse::<IpAddr>().unwrap()));
+/// assert!(!subnet.contains(&"192.168.2.1".parse::<IpAddr>().unwrap()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawCidr", into = "RawCidr")]
+pub struct Cidr {
+    address: IpAddr,
+    prefix: u8,
+}
+
+/// Serialized form of a Cidr, checked when read
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct RawCidr {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl TryFrom<RawCidr> for Cidr {
+    type Error = DatabaseError;
+
+    fn try_from(raw: RawCidr) -> Result<Self, Self::Error> {
+        Cidr::new(raw.address, raw.prefix)
+    }
+}
+
+impl From<Cidr> for RawCidr {
+    fn from(c: Cidr) -> Self {
+        RawCidr{
+            address: c.address,
+            prefix: c.prefix,
+        }
+    }
+}
+
+impl Cidr {
+    /// Returns the block of addresses sharing the first prefix bits of the address.  If the
+    /// prefix is longer than the address, DatabaseError::InvalidFormat is returned.
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Cidr, DatabaseError> {
+        let address = match address {
+            IpAddr::V4(a) if prefix <= 32 => IpAddr::V4(Ipv4Addr::from(u32::from(a) & v4_mask(prefix))),
+            IpAddr::V6(a) if prefix <= 128 => IpAddr::V6(Ipv6Addr::from(u128::from(a) & v6_mask(prefix))),
+            _ => return Err(DatabaseError::InvalidFormat(format!("Cidr {}/{}", address, prefix))),
+        };
+        Ok(Cidr{address, prefix})
+    }
+
+    /// Returns the first address of the block
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the number of leading bits shared by the addresses of the block
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Returns true if the address is within the block
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(n), IpAddr::V4(a)) => u32::from(*a) & v4_mask(self.prefix) == u32::from(n),
+            (IpAddr::V6(n), IpAddr::V6(a)) => u128::from(*a) & v6_mask(self.prefix) == u128::from(n),
+            _ => false,
+        }
+    }
+
+    /// Returns true if every address of the other block is within this block
+    pub fn contains_cidr(&self, other: &Cidr) -> bool {
+        other.prefix >= self.prefix && self.contains(&other.address)
+    }
+}
+
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// Parses a block such as `10.0.0.0/8` or `2001:db8::/32`; an address without a prefix is
+/// the block of that address alone
+impl FromStr for Cidr {
+    type Err = DatabaseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || DatabaseError::InvalidFormat(format!("Cidr {}", value));
+        let trimmed = value.trim();
+        let (address, prefix) = match trimmed.split_once('/') {
+            Some((address, prefix)) => (address.parse().map_err(|_| invalid())?, Some(prefix.parse().map_err(|_| invalid())?)),
+            None => (trimmed.parse().map_err(|_| invalid())?, None),
+        };
+        let prefix = prefix.unwrap_or(match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        });
+        Cidr::new(address, prefix)
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant, SystemTime};
+use std::sync::Mutex;
+
+/// Point in time observed by a Clock.
+///
+/// The wall clock time is persisted with entries as their last_timestamp; the monotonic
+/// instant is only meaningful within the current process, but is unaffected by the wall
+/// clock being stepped forwards or backwards (for example by NTP).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    pub wall: SystemTime,
+    pub monotonic: Instant,
+}
+
+impl Timestamp {
+    /// Returns the current Timestamp of the system clocks
+    pub fn now() -> Self {
+        SystemClock.timestamp()
+    }
+}
+
+/// Source of the current time used for entry timestamps and expiration.
+///
+/// Supplied to a Client through ClientBuilder::clock; defaults to SystemClock.
+pub trait Clock: Send + Sync {
+    /// Returns the current wall clock time
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current monotonic time
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Returns both the current wall clock and monotonic time
+    fn timestamp(&self) -> Timestamp {
+        Timestamp{
+            wall: self.now(),
+            monotonic: self.instant(),
+        }
+    }
+}
+
+/// Clock backed by SystemTime::now()
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to; allows expiration to be tested deterministically
+/// ```
+/// use persistent_keystore_rs::{Clock, ManualClock};
+/// use std::time::{Duration, SystemTime};
+///
+/// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Timestamp>,
+}
+
+impl ManualClock {
+    /// Creates a ManualClock frozen at the supplied wall clock time
+    pub fn new(now: SystemTime) -> Self {
+        Self{
+            now: Mutex::new(Timestamp{
+                wall: now,
+                monotonic: Instant::now(),
+            }),
+        }
+    }
+
+    /// Moves both the wall clock and monotonic time forward by the supplied duration
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.wall += duration;
+        now.monotonic += duration;
+    }
+
+    /// Steps the wall clock to the supplied time, which may be in the past, leaving
+    /// monotonic time untouched; simulates the system clock being changed
+    pub fn set(&self, time: SystemTime) {
+        let mut now = self.now.lock().unwrap();
+        now.wall = time;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.now.lock().unwrap().wall
+    }
+
+    fn instant(&self) -> Instant {
+        self.now.lock().unwrap().monotonic
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::{Deref, DerefMut};
+use crate::errors::*;
+use crate::structs::Database;
+use crate::sync::{Condvar, Mutex, RwLockWriteGuard};
+
+/// Sequence numbers of the mutations of a database
+#[derive(Default)]
+struct Sequences {
+    /// Latest mutation made
+    written: u64,
+    /// Latest mutation included in a save
+    durable: u64,
+    /// Number of failed group commits, so that waiting flushes return rather than wait
+    /// for a save that will not come
+    failures: u64,
+}
+
+/// Tracks which mutations of a database have been saved, shared between clones of a
+/// Client so that flush can wait until a mutation is durable
+pub(crate) struct Durability {
+    sequences: Mutex<Sequences>,
+    saved: Condvar,
+}
+
+impl Durability {
+    pub(crate) fn new() -> Self {
+        Durability{
+            sequences: Mutex::new(Sequences::default()),
+            saved: Condvar::new(),
+        }
+    }
+
+    /// Assigns the next sequence number to a mutation; called with the database write
+    /// lock held, so that a save serializing the database includes every mutation numbered
+    /// before it
+    pub(crate) fn record_write(&self) {
+        if let Ok(mut sequences) = self.sequences.lock() {
+            sequences.written += 1;
+        };
+    }
+
+    /// Returns the sequence numbers of the latest mutation and of the latest saved
+    pub(crate) fn sequences(&self) -> Result<(u64, u64), DatabaseError> {
+        match self.sequences.lock() {
+            Ok(sequences) => Ok((sequences.written, sequences.durable)),
+            Err(_) => Err(DatabaseError::UnableToGetLock),
+        }
+    }
+
+    /// Records that every mutation up to the sequence number was saved, waking the
+    /// flushes waiting for them
+    pub(crate) fn saved(&self, sequence: u64) {
+        if let Ok(mut sequences) = self.sequences.lock() {
+            sequences.durable = sequences.durable.max(sequence);
+        };
+        self.saved.notify_all();
+    }
+
+    /// Records that a group commit failed, waking the flushes waiting for it
+    pub(crate) fn failed(&self) {
+        if let Ok(mut sequences) = self.sequences.lock() {
+            sequences.failures += 1;
+        };
+        self.saved.notify_all();
+    }
+
+    /// Waits until the mutation with the sequence number is saved by a group commit;
+    /// returns DatabaseError::DatabaseIoError if a group commit fails in the meantime, and
+    /// DatabaseError::UnknownSequence if no mutation has the sequence number yet, as no
+    /// commit would save it
+    pub(crate) fn wait(&self, sequence: u64) -> Result<(), DatabaseError> {
+        let mut sequences = self.sequences.lock().map_err(|_| DatabaseError::UnableToGetLock)?;
+        if sequence > sequences.written {
+            return Err(DatabaseError::UnknownSequence(sequence))
+        };
+        let failures = sequences.failures;
+        while sequences.durable < sequence {
+            if sequences.failures != failures {
+                return Err(DatabaseError::DatabaseIoError(std::io::Error::other("group commit failed")))
+            };
+            sequences = self.saved.wait(sequences).map_err(|_| DatabaseError::UnableToGetLock)?;
+        };
+        Ok(())
+    }
+}
+
+/// The write lock of the database taken for a mutation; the mutation is assigned its
+/// sequence number by applied, once it has been made, so a mutation that fails leaves
+/// nothing to save or flush
+pub(crate) struct WriteGuard<'a> {
+    database: RwLockWriteGuard<'a, Database>,
+    durability: &'a Durability,
+}
+
+impl<'a> WriteGuard<'a> {
+    pub(crate) fn new(database: RwLockWriteGuard<'a, Database>, durability: &'a Durability) -> Self {
+        WriteGuard{database, durability}
+    }
+
+    /// Assigns the next sequence number to the mutation made under the lock
+    pub(crate) fn applied(&self) {
+        self.durability.record_write();
+    }
+}
+
+impl Deref for WriteGuard<'_> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.database
+    }
+}
+
+impl DerefMut for WriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Database {
+        &mut self.database
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::dictionary;
+use crate::header;
+use crate::lazy;
+use crate::legacy;
+use crate::partial;
+use crate::secret;
+use crate::structs::*;
+
+/// Layout of the serialized database within a database file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    /// The database is serialized as a whole
+    Standard,
+    /// Each Table is serialized on its own; see ClientBuilder::segmented
+    Segmented,
+    /// Written before database files had a header, by version 0.0.8 or earlier; saved in
+    /// the Standard format once opened
+    Legacy,
+}
+
+/// Compression of a database file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileCompression {
+    Lz4,
+    /// Compressed with a trained dictionary; see DatabaseClient::train_dictionary.
+    /// Requires the `zstd` feature.
+    ZstdDictionary,
+}
+
+/// Optional part of the schema of a Table, set through its TableBuilder
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SchemaFeature {
+    /// TableBuilder::add_expiration
+    Expiration,
+    /// TableBuilder::add_expiration_schedule
+    ExpirationSchedule,
+    /// TableBuilder::add_expiration_schedule with ExpirationSchedule::Cron; only pruned
+    /// with the `cron` feature
+    CronSchedule,
+    /// TableBuilder::add_reference
+    References,
+    /// TableBuilder::add_index
+    Indexes,
+    /// TableBuilder::sensitive_field
+    SensitiveFields,
+    /// FieldType::Secret; requires the `zeroize` feature
+    Secrets,
+    /// TableBuilder::append_only
+    AppendOnly,
+    /// TableBuilder::max_entries
+    MaxEntries,
+    /// TableBuilder::spill_after
+    Spill,
+    /// TableBuilder::externalize_above
+    Externalize,
+    /// TableBuilder::deduplicate_above
+    Deduplicate,
+    /// TableBuilder::bloom_filter
+    BloomFilter,
+    /// TableBuilder::prefix_index
+    PrefixIndex,
+    /// TableBuilder::columnar
+    Columnar,
+    /// FieldType::Custom
+    CustomFields,
+}
+
+/// Result of Client::check_compat; whether this build of the crate can open a database
+/// file, and what the file uses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatReport {
+    pub format: FileFormat,
+    pub compression: FileCompression,
+    /// Version of the crate that wrote the file, if the file records it
+    pub written_by: Option<String>,
+    /// SchemaFeatures used by each Table, by name; Tables that could not be read are absent
+    pub tables: BTreeMap<String, Vec<SchemaFeature>>,
+    /// Reasons this build cannot open the file; empty if it can
+    pub problems: Vec<String>,
+}
+
+impl CompatReport {
+    /// Returns true if this build can open the file
+    pub fn is_compatible(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns the SchemaFeatures used by any Table, in order
+    pub fn features(&self) -> BTreeSet<SchemaFeature> {
+        self.tables.values().flatten().copied().collect()
+    }
+}
+
+/// Returns the SchemaFeatures used by the Table, in order
+fn features(table: &Table) -> Vec<SchemaFeature> {
+    let mut features = Vec::new();
+    let mut add = |used: bool, feature: SchemaFeature| if used {
+        features.push(feature);
+    };
+    add(table.expire_after.is_some(), SchemaFeature::Expiration);
+    add(table.expire_schedule.is_some(), SchemaFeature::ExpirationSchedule);
+    add(matches!(table.expire_schedule, Some(crate::ExpirationSchedule::Cron(_))), SchemaFeature::CronSchedule);
+    add(!table.references.is_empty(), SchemaFeature::References);
+    add(!table.indexes.is_empty(), SchemaFeature::Indexes);
+    add(!table.sensitive_fields.is_empty(), SchemaFeature::SensitiveFields);
+    #[cfg(feature = "zeroize")]
+    add(table.primary_field == FieldType::Secret || table.fields.values().any(|f| f.unwrap() == FieldType::Secret), SchemaFeature::Secrets);
+    add(table.history_retention.is_some(), SchemaFeature::AppendOnly);
+    add(table.max_entries.is_some(), SchemaFeature::MaxEntries);
+    add(table.spill_after.is_some(), SchemaFeature::Spill);
+    add(table.externalize_above.is_some(), SchemaFeature::Externalize);
+    add(table.deduplicate_above.is_some(), SchemaFeature::Deduplicate);
+    add(table.bloom_filter.is_some(), SchemaFeature::BloomFilter);
+    add(table.prefix_index, SchemaFeature::PrefixIndex);
+    add(table.layout() == crate::TableLayout::Columnar, SchemaFeature::Columnar);
+    add(table.primary_field == FieldType::Custom || table.fields.values().any(|f| f.unwrap() == FieldType::Custom), SchemaFeature::CustomFields);
+    features
+}
+
+/// Inspects the contents of a database file, reading its Tables one at a time
+pub(crate) fn check(contents: &[u8]) -> CompatReport {
+    let mut report = CompatReport{
+        format: FileFormat::Standard,
+        compression: FileCompression::Lz4,
+        written_by: None,
+        tables: BTreeMap::new(),
+        problems: Vec::new(),
+    };
+    let compressed = match header::split(contents) {
+        Ok((Some(info), compressed)) => {
+            if let Err(e) = info.check_format() {
+                report.problems.push(e.to_string());
+                return report
+            };
+            report.written_by = Some(info.crate_version);
+            compressed
+        },
+        Ok((None, compressed)) => {
+            report.format = FileFormat::Legacy;
+            compressed
+        },
+        Err(e) => {
+            report.problems.push(format!("unable to read header: {}", e));
+            return report
+        },
+    };
+    if dictionary::is_dictionary_compressed(compressed) {
+        report.compression = FileCompression::ZstdDictionary;
+    };
+    let mut uncompressed = match crate::decompress_file(contents) {
+        Ok((uncompressed, _)) => uncompressed,
+        Err(e) => {
+            report.problems.push(format!("unable to decompress: {}", e));
+            return report
+        },
+    };
+
+    if report.format == FileFormat::Legacy {
+        match legacy::deserialize(&uncompressed) {
+            Ok(mut database) => for name in database.list_tables() {
+                if let Ok(t) = database.get_table(&name) {
+                    report.tables.insert(name, features(t));
+                };
+            },
+            Err(e) => report.problems.push(format!("unable to read database: {}", e)),
+        };
+        secret::wipe(&mut uncompressed);
+        return report
+    };
+    if lazy::is_segmented(&uncompressed) {
+        report.format = FileFormat::Segmented;
+    };
+    let mut unreadable = Vec::new();
+    let mut visit = |name: String, table: Result<Table, String>| {
+        match table {
+            Ok(t) => {
+                report.tables.insert(name, features(&t));
+            },
+            Err(e) => unreadable.push((name, e)),
+        };
+        true
+    };
+    let read = match report.format {
+        FileFormat::Segmented => lazy::for_each_table(&uncompressed, &mut visit),
+        FileFormat::Standard | FileFormat::Legacy => partial::for_each_table(&uncompressed, &mut visit).map_err(Into::into),
+    };
+    secret::wipe(&mut uncompressed);
+
+    for (name, e) in unreadable {
+        let later = match report.format {
+            FileFormat::Standard => "; the tables after it were not checked",
+            FileFormat::Segmented | FileFormat::Legacy => "",
+        };
+        report.problems.push(format!("table {} cannot be read by this build, which may lack a feature it uses: {}{}", name, e, later));
+    };
+    if let Err(e) = read {
+        report.problems.push(format!("unable to read database: {}", e));
+    };
+    report
+}
@@ -0,0 +1,199 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::structs::*;
+use crate::format::format_duration;
+use crate::cidr::Cidr;
+use crate::geo::GeoPoint;
+
+/// Name under which criteria and Conditions apply to the primary Field of each Entry
+/// rather than to a named field; starts with RESERVED_PREFIX so no field takes it under
+/// the default NamePolicy.  Equals is answered by a lookup of the Entry, and StartsWith by
+/// the prefix index of Tables built with TableBuilder::prefix_index.
+pub const PRIMARY_FIELD_KEY: &str = "__primary_field";
+
+/// Predicate applied to the value of a single field of an Entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// Field is set and equal to the value
+    Equals(Field),
+    /// Field is not set, or is set to a different value
+    NotEquals(Field),
+    /// Field is set to a value of the same type that is greater than the value
+    GreaterThan(Field),
+    /// Field is set to a value of the same type that is less than the value
+    LessThan(Field),
+    /// Date field is further in the past than the Duration, relative to the time of the query
+    OlderThan(Duration),
+    /// Date field is within the Duration before the time of the query, or in the future
+    NewerThan(Duration),
+    /// IpAddr field is within the block, or Cidr field is a block inside it
+    InSubnet(Cidr),
+    /// GeoPoint field is no further than the supplied number of meters from the center
+    WithinRadius {
+        center: GeoPoint,
+        meters: u64,
+    },
+    /// String field starts with the prefix
+    StartsWith(String),
+}
+
+impl Condition {
+    /// Returns true if the supplied field value, or None if the field is not set, meets
+    /// the Condition as of now
+    /// ```
+    /// use persistent_keystore_rs::{Condition, Field};
+    /// use std::time::{Duration, SystemTime};
+    /// let now = SystemTime::now();
+    /// let value = Field::Date(now - Duration::from_secs(120));
+    /// assert!(Condition::OlderThan(Duration::from_secs(60)).matches(Some(&value), now));
+    /// assert!(Condition::GreaterThan(Field::I64(3)).matches(Some(&Field::I64(4)), now));
+    /// assert!(!Condition::Equals(Field::I64(3)).matches(None, now));
+    /// ```
+    pub fn matches(&self, value: Option<&Field>, now: SystemTime) -> bool {
+        match self {
+            Condition::Equals(f) => value == Some(f),
+            Condition::NotEquals(f) => value != Some(f),
+            Condition::GreaterThan(f) => {
+                match value {
+                    Some(v) => v.compare(f) == Some(Ordering::Greater),
+                    None => false,
+                }
+            },
+            Condition::LessThan(f) => {
+                match value {
+                    Some(v) => v.compare(f) == Some(Ordering::Less),
+                    None => false,
+                }
+            },
+            Condition::OlderThan(d) => {
+                match value {
+                    Some(Field::Date(v)) => {
+                        match now.duration_since(*v) {
+                            Ok(age) => age > *d,
+                            Err(_) => false,
+                        }
+                    },
+                    _ => false,
+                }
+            },
+            Condition::NewerThan(d) => {
+                match value {
+                    Some(Field::Date(v)) => {
+                        match now.duration_since(*v) {
+                            Ok(age) => age <= *d,
+                            Err(_) => true,
+                        }
+                    },
+                    _ => false,
+                }
+            },
+            Condition::InSubnet(subnet) => {
+                match value {
+                    Some(Field::IpAddr(v)) => subnet.contains(v),
+                    Some(Field::Cidr(v)) => subnet.contains_cidr(v),
+                    _ => false,
+                }
+            },
+            Condition::WithinRadius{center, meters} => {
+                match value {
+                    Some(Field::GeoPoint(v)) => center.distance_to(v) <= *meters as f64,
+                    _ => false,
+                }
+            },
+            Condition::StartsWith(prefix) => {
+                match value {
+                    Some(Field::String(v)) => v.starts_with(prefix.as_str()),
+                    _ => false,
+                }
+            },
+        }
+    }
+}
+
+impl Condition {
+    /// Returns a Condition matching Date fields further in the past than the supplied
+    /// duration; negative durations are treated as zero
+    /// ```
+    /// use persistent_keystore_rs::Condition;
+    /// use std::time::Duration;
+    /// let condition = Condition::older_than(Duration::from_secs(60));
+    /// # assert_eq!(condition, Condition::OlderThan(Duration::from_secs(60)));
+    /// ```
+    pub fn older_than<D: AgeDuration>(duration: D) -> Self {
+        Condition::OlderThan(duration.to_age())
+    }
+
+    /// Returns a Condition matching Date fields within the supplied duration before the
+    /// time of the query; negative durations are treated as zero
+    /// ```
+    /// use persistent_keystore_rs::Condition;
+    /// use std::time::Duration;
+    /// let condition = Condition::newer_than(Duration::from_secs(60));
+    /// # assert_eq!(condition, Condition::NewerThan(Duration::from_secs(60)));
+    /// ```
+    pub fn newer_than<D: AgeDuration>(duration: D) -> Self {
+        Condition::NewerThan(duration.to_age())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Equals(v) => write!(f, "= {}", v),
+            Condition::NotEquals(v) => write!(f, "!= {}", v),
+            Condition::GreaterThan(v) => write!(f, "> {}", v),
+            Condition::LessThan(v) => write!(f, "< {}", v),
+            Condition::OlderThan(d) => write!(f, "older than {}", format_duration(*d)),
+            Condition::NewerThan(d) => write!(f, "newer than {}", format_duration(*d)),
+            Condition::InSubnet(c) => write!(f, "in {}", c),
+            Condition::WithinRadius{center, meters} => write!(f, "within {}m of {}", meters, center),
+            Condition::StartsWith(prefix) => write!(f, "starts with {}", prefix),
+        }
+    }
+}
+
+/// Duration types accepted by Condition::older_than and Condition::newer_than.
+///
+/// Implemented for std::time::Duration, and for chrono::Duration and time::Duration when
+/// the `chrono` and `time` features are enabled.
+pub trait AgeDuration {
+    /// Returns the duration as a std::time::Duration, clamping negative values to zero
+    fn to_age(&self) -> Duration;
+}
+
+impl AgeDuration for Duration {
+    fn to_age(&self) -> Duration {
+        *self
+    }
+}
+
+/// Returns the value of the named field of the Entry, or its primary Field if the name is
+/// PRIMARY_FIELD_KEY
+pub(crate) fn field_value<'a>(entry: &'a Entry, name: &str) -> Option<&'a Field> {
+    match name {
+        PRIMARY_FIELD_KEY => Some(&entry.primary_field),
+        _ => entry.fields.get(name),
+    }
+}
+
+/// Returns true if the Entry meets every supplied Condition as of now
+pub(crate) fn matches_all(entry: &Entry, conditions: &HashMap<String, Condition>, now: SystemTime) -> bool {
+    for (k, c) in conditions {
+        if !c.matches(field_value(entry, k), now) {
+            return false
+        };
+    };
+    true
+}
+
+/// Converts equality criteria into the equivalent Conditions
+pub(crate) fn equals_all(criteria: HashMap<String, Field>) -> HashMap<String, Condition> {
+    let mut conditions = HashMap::new();
+    for (k, v) in criteria {
+        conditions.insert(k, Condition::Equals(v));
+    };
+    conditions
+}
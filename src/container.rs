@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+use tracing::{debug, error, info, trace};
+
+use crate::{open_file, Client, ClientBuilder, FileSignature, SaveGate, Worker};
+use crate::prelude::*;
+use crate::errors::*;
+
+/// Marks a container file; followed by the contents of each database it holds, serialized
+/// as a map from name to the compressed database as it would be written to its own file
+const CONTAINER_MAGIC: &[u8; 8] = b"PKSMULTI";
+
+/// Returns the path the database with the supplied name in the container at the supplied
+/// path is known by; the files of its spilled and externalized Tables are stored beside it
+fn root_path(path: &Path, name: &str) -> PathBuf {
+    let mut root = OsString::from(path.as_os_str());
+    root.push(format!(".{}", name));
+    PathBuf::from(root)
+}
+
+/// Returns an error if the name cannot be used for a database in a container; it must be
+/// non-empty and usable as part of a file name
+fn check_name(name: &str) -> Result<(), DatabaseError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        error!("Invalid container database name {:?}", name);
+        return Err(DatabaseError::InvalidFormat(format!("container database name {:?}", name)))
+    };
+    Ok(())
+}
+
+/// Reads and deserializes the container file at the supplied path, returning the compressed
+/// databases it holds by name and the open file
+fn read_roots(path: &Path) -> Result<(BTreeMap<String, Vec<u8>>, File), DatabaseError> {
+    let mut f = open_file(path)?;
+    let mut contents = Vec::new();
+    f.read_to_end(&mut contents)?;
+    match contents.strip_prefix(CONTAINER_MAGIC.as_slice()) {
+        Some(rest) => Ok((bincode::deserialize(rest)?, f)),
+        None => Err(DatabaseError::InvalidFormat(format!("{} is not a container file", path.display()))),
+    }
+}
+
+/// Serializes the compressed databases by name and writes them to the container file at the
+/// supplied path, returning the open file
+fn write_roots(path: &Path, roots: &BTreeMap<String, Vec<u8>>) -> Result<File, DatabaseError> {
+    let mut output = CONTAINER_MAGIC.to_vec();
+    output.extend(bincode::serialize(roots)?);
+    let mut f = OpenOptions::new()
+        .write(true)
+        .read(true)
+        .create(true)
+        .truncate(true)
+        .append(false)
+        .open(path)?;
+    f.write_all(&output)?;
+    f.flush()?;
+    f.sync_all()?;
+    trace!("Wrote container {:?} holding {} databases", path, roots.len());
+    Ok(f)
+}
+
+/// Container file shared by every Client of the databases it holds
+struct ContainerFile {
+    path: PathBuf,
+    roots: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl ContainerFile {
+    /// Replaces the compressed database with the supplied name, or removes it if None, and
+    /// writes the container file
+    fn write(&self, name: &str, compressed: Option<&[u8]>) -> Result<File, DatabaseError> {
+        let mut roots = match self.roots.lock() {
+            Ok(r) => r,
+            Err(_) => {
+                error!("Unable to get container mutex");
+                return Err(DatabaseError::UnableToGetLock)
+            },
+        };
+        match compressed {
+            Some(c) => roots.insert(name.to_string(), c.to_vec()),
+            None => roots.remove(name),
+        };
+        write_roots(&self.path, &roots)
+    }
+}
+
+/// Database held by a container, read and written by the Client of the database in place of
+/// a file of its own
+#[derive(Clone)]
+pub(crate) struct Root {
+    file: Arc<ContainerFile>,
+    name: String,
+}
+
+impl Root {
+    /// Returns the name of the database
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path of the container file
+    pub(crate) fn file(&self) -> &Path {
+        &self.file.path
+    }
+
+    /// Returns true if the container holds the database
+    pub(crate) fn exists(&self) -> bool {
+        self.file.roots.lock().map(|r| r.contains_key(&self.name)).unwrap_or(false)
+    }
+
+    /// Reads the compressed database from the container file, returning it and its signature
+    pub(crate) fn read(&self) -> Result<(Vec<u8>, FileSignature), DatabaseError> {
+        let (mut roots, f) = read_roots(&self.file.path)?;
+        match roots.remove(&self.name) {
+            Some(compressed) => {
+                let signature = FileSignature::new(&f, &compressed);
+                Ok((compressed, signature))
+            },
+            None => Err(DatabaseError::DatabaseDoesNotExist(root_path(&self.file.path, &self.name))),
+        }
+    }
+
+    /// Writes the compressed database to the container file, leaving the other databases it
+    /// holds unchanged, and returns its signature
+    pub(crate) fn write(&self, compressed: &[u8]) -> Result<FileSignature, DatabaseError> {
+        let f = self.file.write(&self.name, Some(compressed))?;
+        Ok(FileSignature::new(&f, compressed))
+    }
+}
+
+/// Single file holding several named databases, each opened and saved independently through
+/// a Client of its own, so that many small logical databases do not each need a file and a
+/// sync thread.
+///
+/// Saving a database rewrites the container file with the last saved contents of every other
+/// database it holds.  Each database is opened at most once per Container; opening it again
+/// returns another handle to the same Client.  Clones of the Container share its databases,
+/// and separate processes sharing the file are not coordinated.
+///
+/// The files of spilled and externalized Tables of a database are stored beside the container
+/// file, named after the container and the database, e.g. `plugins.db.search.<hash>.spill`.
+#[derive(Clone)]
+pub struct Container {
+    file: Arc<ContainerFile>,
+    clients: Arc<Mutex<HashMap<String, Client>>>,
+    workers: Arc<Vec<Worker>>,
+}
+
+impl Container {
+    /// Creates an empty container file at the supplied path
+    /// ```
+    /// use persistent_keystore_rs::Container;
+    /// use std::path::Path;
+    /// let container = Container::new(Path::new("container.db"), None).unwrap();
+    /// assert!(container.list_databases().unwrap().is_empty());
+    /// # std::fs::remove_file("container.db").unwrap();
+    /// ```
+    /// If a sync interval is provided, a single thread prunes and saves every database
+    /// opened through the Container every interval, until the last clone of the Container
+    /// is dropped.  If the path exists, DatabaseError::DatabaseExistsError is returned.
+    pub fn new<P: AsRef<Path>>(path: P, sync_interval: Option<Duration>) -> Result<Container, DatabaseError> {
+        info!("Creating Container at {:?}", path.as_ref());
+        if path.as_ref().exists() {
+            error!("Container exists, cannot create: {:?}", path.as_ref());
+            return Err(DatabaseError::DatabaseExistsError)
+        };
+        write_roots(path.as_ref(), &BTreeMap::new())?;
+        Ok(Container::start(path.as_ref(), BTreeMap::new(), sync_interval))
+    }
+
+    /// Opens the existing container file at the supplied path
+    /// ```
+    /// # use persistent_keystore_rs::Container;
+    /// use std::path::Path;
+    /// # drop(Container::new(Path::new("existingcontainer.db"), None).unwrap());
+    /// let container = Container::open(Path::new("existingcontainer.db"), None).unwrap();
+    /// # std::fs::remove_file("existingcontainer.db").unwrap();
+    /// ```
+    /// If the path does not exist, DatabaseError::DatabaseDoesNotExist is returned.
+    pub fn open<P: AsRef<Path>>(path: P, sync_interval: Option<Duration>) -> Result<Container, DatabaseError> {
+        info!("Opening Container at {:?}", path.as_ref());
+        if !path.as_ref().exists() {
+            error!("Container does not exist, cannot open: {:?}", path.as_ref());
+            return Err(DatabaseError::DatabaseDoesNotExist(path.as_ref().to_path_buf()))
+        };
+        let (roots, _) = read_roots(path.as_ref())?;
+        debug!("Container holds {} databases", roots.len());
+        Ok(Container::start(path.as_ref(), roots, sync_interval))
+    }
+
+    fn start(path: &Path, roots: BTreeMap<String, Vec<u8>>, sync_interval: Option<Duration>) -> Container {
+        let mut container = Container{
+            file: Arc::new(ContainerFile{
+                path: path.to_path_buf(),
+                roots: Mutex::new(roots),
+            }),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            workers: Arc::new(Vec::new()),
+        };
+        if let Some(d) = sync_interval {
+            container.workers = Arc::new(vec![container.start_sync(d)]);
+        };
+        container
+    }
+
+    /// Starts the thread that prunes and saves every open database every duration; the
+    /// thread is stopped when the last clone of the Container is dropped.
+    fn start_sync(&self, duration: Duration) -> Worker {
+        let clients = self.clients.clone();
+        let (tx, rx) = std::sync::mpsc::sync_channel(0);
+        let h = std::thread::spawn( move || loop {
+                if rx.try_recv().is_ok() {
+                    trace!("Breaking");
+                    break
+                };
+
+                trace!("Sleeping for {:?}", duration);
+                sleep(duration);
+
+                let open: Vec<(String, Client)> = match clients.lock() {
+                    Ok(c) => c.iter().map(|(n, c)| (n.clone(), c.clone())).collect(),
+                    Err(_) => continue,
+                };
+                for (name, mut c) in open {
+                    let _permit = c.save_gate.as_ref().map(SaveGate::acquire);
+                    match c.prune().and_then(|_| c.save()) {
+                        Ok(_) => debug!("Database {} saved", name),
+                        Err(e) => error!("Unable to save database {}: {}", name, e),
+                    };
+                };
+            }
+        );
+
+        Worker{
+            handle: Some(h),
+            killer: tx,
+        }
+    }
+
+    /// Returns the path of the container file
+    pub fn path(&self) -> &Path {
+        &self.file.path
+    }
+
+    /// Returns the names of the databases held by the container, as last saved
+    pub fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
+        match self.file.roots.lock() {
+            Ok(roots) => Ok(roots.keys().cloned().collect()),
+            Err(_) => {
+                error!("Unable to get container mutex");
+                Err(DatabaseError::UnableToGetLock)
+            },
+        }
+    }
+
+    /// Returns a ClientBuilder for the named database of the container, allowing its Client
+    /// to be configured before the database is created or opened.  The database is saved by
+    /// the thread of the Container rather than one of its own unless given a sync interval.
+    /// ```
+    /// use persistent_keystore_rs::{Container, CapacityPolicy};
+    /// use std::path::Path;
+    /// let container = Container::new(Path::new("containerbuilder.db"), None).unwrap();
+    /// let c = container.builder("cache").unwrap()
+    ///     .max_memory(1024 * 1024, CapacityPolicy::EvictOldest)
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("containerbuilder.db").unwrap();
+    /// ```
+    pub fn builder(&self, name: &str) -> Result<ClientBuilder, DatabaseError> {
+        check_name(name)?;
+        let mut builder = Client::builder(root_path(&self.file.path, name));
+        builder.container = Some((self.clone(), Root{
+            file: self.file.clone(),
+            name: name.to_string(),
+        }));
+        Ok(builder)
+    }
+
+    /// Creates the named database in the container and returns a Client for it.
+    /// If the database exists, DatabaseError::DatabaseExistsError is returned.
+    /// ```
+    /// use persistent_keystore_rs::{Container, FieldType, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let container = Container::new(Path::new("createdatabase.db"), None).unwrap();
+    /// let mut search = container.create_database("search").unwrap();
+    /// let mut metrics = container.create_database("metrics").unwrap();
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// search.create_table(table).unwrap();
+    /// search.save().unwrap();
+    ///
+    /// assert_eq!(container.list_databases().unwrap(), vec!["metrics".to_string(), "search".to_string()]);
+    /// assert!(metrics.list_tables().unwrap().is_empty());
+    /// # std::fs::remove_file("createdatabase.db").unwrap();
+    /// ```
+    pub fn create_database(&self, name: &str) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
+        self.builder(name)?.build()
+    }
+
+    /// Opens the named database of the container and returns a Client for it, or another
+    /// handle to its Client if already open.
+    /// If the database does not exist, DatabaseError::DatabaseDoesNotExist is returned.
+    /// ```
+    /// # use persistent_keystore_rs::Container;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// # let container = Container::new(Path::new("opendatabase.db"), None).unwrap();
+    /// # drop(container.create_database("search").unwrap());
+    /// # drop(container);
+    /// let container = Container::open(Path::new("opendatabase.db"), None).unwrap();
+    /// let mut search = container.open_database("search").unwrap();
+    /// assert!(container.open_database("metrics").is_err());
+    /// # std::fs::remove_file("opendatabase.db").unwrap();
+    /// ```
+    pub fn open_database(&self, name: &str) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
+        self.builder(name)?.open()
+    }
+
+    /// Removes the named database from the container, returning false if it did not exist.
+    /// Handles of the database that remain open write it back to the container when saved.
+    pub fn drop_database(&self, name: &str) -> Result<bool, DatabaseError> {
+        check_name(name)?;
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.remove(name);
+        };
+        let existed = self.file.roots.lock().map(|r| r.contains_key(name)).unwrap_or(false);
+        if existed {
+            info!("Dropping database {} from container {:?}", name, self.file.path);
+            self.file.write(name, None)?;
+        };
+        Ok(existed)
+    }
+
+    /// Returns the open Client of the named database, if any
+    pub(crate) fn client(&self, name: &str) -> Option<Client> {
+        self.clients.lock().ok().and_then(|c| c.get(name).cloned())
+    }
+
+    /// Records the Client of the named database, so that it is returned when the database is
+    /// opened again and saved by the thread of the Container
+    pub(crate) fn register(&self, name: &str, client: &Client) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(name.to_string(), client.clone());
+        };
+    }
+}
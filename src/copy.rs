@@ -0,0 +1,86 @@
+/// Number of entries copied by DatabaseClient::copy_into between calls of its progress
+/// callback
+pub const COPY_PROGRESS_INTERVAL: u64 = 1000;
+
+/// Action taken by DatabaseClient::copy_into for an entry whose primary field already
+/// exists in the table it is copied into
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CopyConflict {
+    /// The copy stops with DatabaseError::EntryExists
+    #[default]
+    Error,
+    /// The existing entry is kept and the entry is counted as skipped
+    Skip,
+    /// The existing entry is replaced
+    Overwrite,
+}
+
+/// Callback of CopyOptions::progress
+type ProgressCallback = Box<dyn FnMut(&CopyProgress)>;
+
+/// Options of DatabaseClient::copy_into
+/// ```
+/// use persistent_keystore_rs::{CopyConflict, CopyOptions};
+/// let options = CopyOptions::default()
+///     .create_tables(true)
+///     .on_conflict(CopyConflict::Overwrite)
+///     .progress(|p| println!("{}: {} of {} entries", p.table, p.copied + p.skipped, p.entries));
+/// ```
+#[derive(Default)]
+pub struct CopyOptions {
+    pub(crate) create_tables: bool,
+    pub(crate) on_conflict: CopyConflict,
+    pub(crate) progress: Option<ProgressCallback>,
+}
+
+impl CopyOptions {
+    /// Creates the tables missing from the destination with the schema and settings of the
+    /// source tables; otherwise DatabaseError::TableDoesNotExist is returned for them
+    pub fn create_tables(mut self, create: bool) -> Self {
+        self.create_tables = create;
+        self
+    }
+
+    /// Sets the action taken for entries that already exist in the destination; defaults
+    /// to CopyConflict::Error
+    pub fn on_conflict(mut self, on_conflict: CopyConflict) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+
+    /// Sets the callback called every COPY_PROGRESS_INTERVAL entries and once each table
+    /// is copied
+    pub fn progress<F: FnMut(&CopyProgress) + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+/// Progress of DatabaseClient::copy_into, passed to the callback set by
+/// CopyOptions::progress
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CopyProgress {
+    /// Table being copied
+    pub table: String,
+    /// Number of entries of the table, as of when its copy started
+    pub entries: usize,
+    /// Entries of the table copied so far
+    pub copied: u64,
+    /// Entries of the table skipped so far; see CopyConflict::Skip
+    pub skipped: u64,
+    /// Tables copied in full so far
+    pub tables_copied: usize,
+    /// Number of tables to copy
+    pub tables: usize,
+}
+
+/// Outcome of DatabaseClient::copy_into
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopyReport {
+    /// Tables created in the destination; see CopyOptions::create_tables
+    pub created_tables: Vec<String>,
+    /// Entries copied, across all tables
+    pub copied: u64,
+    /// Entries skipped, across all tables; see CopyConflict::Skip
+    pub skipped: u64,
+}
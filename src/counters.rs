@@ -0,0 +1,208 @@
+//! Counters per key, rolled up into buckets per minute, hour and day and stored in a Table of
+//! the database so that they are shared by every clone of a Client and survive restarts.
+//!
+//! Each bucket is incremented under a single write lock, so concurrent increments from any
+//! clone of the Client are never lost.  Buckets older than the retention of their Rollup are
+//! removed by CounterStore::prune_counters, which increments also run once a minute.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+use crate::Client;
+use crate::observer::Operation;
+use crate::structs::*;
+use crate::prelude::*;
+use crate::condition::*;
+use crate::cancellation::*;
+use crate::errors::*;
+
+const KEY: &str = "Key";
+const ROLLUP: &str = "Rollup";
+const BUCKET: &str = "Bucket";
+const COUNT: &str = "Count";
+
+/// Width of the time buckets of a counter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rollup {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Rollup {
+    /// Every Rollup, narrowest first
+    pub const ALL: [Rollup; 3] = [Rollup::Minute, Rollup::Hour, Rollup::Day];
+
+    /// Returns the width of each bucket
+    pub fn width(&self) -> Duration {
+        match self {
+            Rollup::Minute => Duration::from_secs(60),
+            Rollup::Hour => Duration::from_secs(3600),
+            Rollup::Day => Duration::from_secs(86400),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Rollup::Minute => "minute",
+            Rollup::Hour => "hour",
+            Rollup::Day => "day",
+        }
+    }
+
+    /// Returns the start of the bucket containing the time, in seconds since the epoch
+    fn bucket(&self, time: SystemTime) -> u64 {
+        let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        secs - secs % self.width().as_secs()
+    }
+}
+
+/// How long the buckets of each Rollup are kept after they start
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterRetention {
+    pub minutes: Duration,
+    pub hours: Duration,
+    pub days: Duration,
+}
+
+impl CounterRetention {
+    /// Returns the retention of the Rollup
+    pub fn of(&self, rollup: Rollup) -> Duration {
+        match rollup {
+            Rollup::Minute => self.minutes,
+            Rollup::Hour => self.hours,
+            Rollup::Day => self.days,
+        }
+    }
+}
+
+impl Default for CounterRetention {
+    /// Keeps minutes for 2 hours, hours for 2 days and days for 90 days
+    fn default() -> Self {
+        CounterRetention{
+            minutes: Duration::from_secs(2 * 3600),
+            hours: Duration::from_secs(2 * 86400),
+            days: Duration::from_secs(90 * 86400),
+        }
+    }
+}
+
+/// Increments and reads counters rolled up over time, using a Table of the database of a
+/// Client; see DatabaseClient::counter_store
+pub struct CounterStore {
+    client: Client,
+    table: String,
+    retention: CounterRetention,
+    last_pruned: Option<u64>,
+}
+
+impl CounterStore {
+    /// Returns a CounterStore storing its buckets in the named Table of the client, creating
+    /// the Table if it does not exist
+    pub(crate) fn new(client: &Client, table: String, retention: CounterRetention) -> Result<Self, DatabaseError> {
+        let mut client = client.clone();
+        if !client.list_tables()?.contains(&table) {
+            debug!("Creating counter table {}", table);
+            let t = Table::new()
+                .name(table.clone())
+                .primary_field(FieldType::String)?
+                .add_field(KEY.to_string(), FieldType::String)?
+                .add_field(ROLLUP.to_string(), FieldType::String)?
+                .add_field(BUCKET.to_string(), FieldType::Date)?
+                .add_field(COUNT.to_string(), FieldType::I64)?
+                .build()?;
+            client.create_table(t)?;
+        };
+
+        Ok(CounterStore{
+            client,
+            table,
+            retention,
+            last_pruned: None,
+        })
+    }
+
+    /// Returns the retention of the buckets of each Rollup
+    pub fn retention(&self) -> CounterRetention {
+        self.retention
+    }
+
+    /// Adds the amount to the current bucket of every Rollup of the key
+    pub fn increment(&mut self, key: &str, amount: i64) -> Result<(), DatabaseError> {
+        let now = self.client.clock.now();
+        if self.last_pruned != Some(Rollup::Minute.bucket(now)) {
+            self.prune_counters()?;
+            self.last_pruned = Some(Rollup::Minute.bucket(now));
+        };
+
+        for rollup in Rollup::ALL {
+            self.client.read_modify_write(Operation::InsertOrUpdate, &self.table, &bucket_key(key, rollup, now), |current, _| {
+                let count = current.as_deref().map(count).unwrap_or(0).saturating_add(amount);
+                let entry = Entry::new()
+                    .set_primary_field(bucket_key(key, rollup, now))?
+                    .add_field(KEY.to_string(), Field::String(key.to_string()))?
+                    .add_field(ROLLUP.to_string(), Field::String(rollup.name().to_string()))?
+                    .add_field(BUCKET.to_string(), Field::Date(SystemTime::UNIX_EPOCH + Duration::from_secs(rollup.bucket(now))))?
+                    .add_field(COUNT.to_string(), Field::I64(count))?
+                    .build()?;
+                Ok((Some(entry), ()))
+            })?;
+        };
+        Ok(())
+    }
+
+    /// Returns the count of the key in the current bucket of the Rollup
+    pub fn count(&mut self, key: &str, rollup: Rollup) -> Result<i64, DatabaseError> {
+        let now = self.client.clock.now();
+        self.count_at(key, rollup, now)
+    }
+
+    fn count_at(&mut self, key: &str, rollup: Rollup, time: SystemTime) -> Result<i64, DatabaseError> {
+        match self.client.get(self.table.clone(), bucket_key(key, rollup, time)) {
+            Ok(entry) => Ok(count(&entry)),
+            Err(DatabaseError::EntryDoesNotExists) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the start and count of the last buckets of the Rollup for the key, oldest
+    /// first and ending with the current bucket; buckets without increments count 0
+    pub fn series(&mut self, key: &str, rollup: Rollup, buckets: usize) -> Result<Vec<(SystemTime, i64)>, DatabaseError> {
+        let current = SystemTime::UNIX_EPOCH + Duration::from_secs(rollup.bucket(self.client.clock.now()));
+        let mut series = Vec::with_capacity(buckets);
+        for n in (0..buckets as u32).rev() {
+            let start = match current.checked_sub(rollup.width() * n) {
+                Some(s) => s,
+                None => continue,
+            };
+            series.push((start, self.count_at(key, rollup, start)?));
+        };
+        Ok(series)
+    }
+
+    /// Removes the buckets of every key that started longer ago than the retention of their
+    /// Rollup, returning the number removed
+    pub fn prune_counters(&mut self) -> Result<u64, DatabaseError> {
+        let mut removed = 0;
+        for rollup in Rollup::ALL {
+            let mut conditions = HashMap::new();
+            conditions.insert(ROLLUP.to_string(), Condition::Equals(Field::String(rollup.name().to_string())));
+            conditions.insert(BUCKET.to_string(), Condition::OlderThan(self.retention.of(rollup)));
+            removed += self.client.delete_where(self.table.clone(), &conditions, &CancellationToken::new())?;
+        };
+        debug!("Pruned {} counter buckets from table {}", removed, self.table);
+        Ok(removed)
+    }
+}
+
+/// Returns the primary Field of the bucket of the Rollup containing the time for the key
+fn bucket_key(key: &str, rollup: Rollup, time: SystemTime) -> Field {
+    Field::String(format!("{}:{}:{}", rollup.name(), rollup.bucket(time), key))
+}
+
+fn count(entry: &Entry) -> i64 {
+    match entry.fields.get(COUNT) {
+        Some(Field::I64(c)) => *c,
+        _ => 0,
+    }
+}
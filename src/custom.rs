@@ -0,0 +1,108 @@
+//! Custom Field types, for domain values such as IP networks or decimals stored without
+//! adding a variant to Field.  A custom value is stored as Field::Custom, its bytes tagged
+//! with the name of its type, and validated, displayed and parsed by the CustomCodec
+//! registered for the tag.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::errors::*;
+use crate::structs::Field;
+use crate::sync::{RwLock, shared_static};
+
+/// Validates, displays and parses the bytes of the custom Field type registered for a tag
+/// with register_codec.
+///
+/// Values of a type are ordered by their bytes, so codecs should encode values in an
+/// order-preserving form when queries compare them.
+pub trait CustomCodec: Send + Sync {
+    /// Returns a description of why the bytes are not a valid value of the type
+    fn validate(&self, bytes: &[u8]) -> Result<(), String>;
+    /// Returns the textual form of a valid value
+    fn display(&self, bytes: &[u8]) -> String;
+    /// Returns the bytes of a value from its textual form; the reverse of display
+    fn parse(&self, value: &str) -> Result<Vec<u8>, String>;
+}
+
+shared_static! {
+    /// Codecs registered by the process, by tag
+    static CODECS: RwLock<BTreeMap<String, Arc<dyn CustomCodec>>> = RwLock::new(BTreeMap::new());
+}
+
+/// Registers the codec of the custom Field type with the supplied tag for the rest of the
+/// process, replacing any codec registered for the tag.  Field::Custom values with a tag
+/// without a codec are rejected by inserts and updates, but are kept by databases read
+/// from disk and displayed as the hexadecimal form of their bytes.
+/// ```
+/// use persistent_keystore_rs::{CustomCodec, Field, register_codec};
+/// use std::net::Ipv4Addr;
+/// use std::sync::Arc;
+///
+/// struct Ipv4Codec;
+///
+/// impl CustomCodec for Ipv4Codec {
+///     fn validate(&self, bytes: &[u8]) -> Result<(), String> {
+///         match bytes.len() {
+///             4 => Ok(()),
+///             n => Err(format!("expected 4 bytes, found {}", n)),
+///         }
+///     }
+///
+///     fn display(&self, bytes: &[u8]) -> String {
+///         Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()
+///     }
+///
+///     fn parse(&self, value: &str) -> Result<Vec<u8>, String> {
+///         value.parse::<Ipv4Addr>().map(|a| a.octets().to_vec()).map_err(|e| e.to_string())
+///     }
+/// }
+///
+/// register_codec("ipv4", Arc::new(Ipv4Codec));
+/// let address = Field::custom("ipv4", vec![10, 0, 0, 1]).unwrap();
+/// assert_eq!(address.to_string(), "ipv4:10.0.0.1");
+/// assert!(Field::custom("ipv4", vec![10, 0, 0]).is_err());
+/// ```
+pub fn register_codec(tag: &str, codec: Arc<dyn CustomCodec>) {
+    let mut codecs = CODECS.write().unwrap_or_else(|e| e.into_inner());
+    codecs.insert(tag.to_string(), codec);
+}
+
+/// Returns the codec registered for the tag, if any
+fn codec(tag: &str) -> Option<Arc<dyn CustomCodec>> {
+    CODECS.read().unwrap_or_else(|e| e.into_inner()).get(tag).cloned()
+}
+
+/// Returns DatabaseError::InvalidFormat if no codec is registered for the tag, or the
+/// bytes are not a valid value of its type
+pub(crate) fn validate(tag: &str, bytes: &[u8]) -> Result<(), DatabaseError> {
+    match codec(tag) {
+        Some(c) => c.validate(bytes).map_err(|e| DatabaseError::InvalidFormat(format!("custom field {}: {}", tag, e))),
+        None => Err(DatabaseError::InvalidFormat(format!("no codec registered for custom field {}", tag))),
+    }
+}
+
+/// Returns the textual form of a custom value, as `tag:value`; the value is shown by the
+/// codec of the tag, or as hexadecimal if the tag has no codec or the bytes are invalid
+pub(crate) fn display(tag: &str, bytes: &[u8]) -> String {
+    let value = match codec(tag) {
+        Some(c) if c.validate(bytes).is_ok() => c.display(bytes),
+        _ => bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        }),
+    };
+    format!("{}:{}", tag, value)
+}
+
+/// Returns the custom Field of the textual form `tag:value`; the reverse of display
+pub(crate) fn parse(value: &str) -> Result<Field, DatabaseError> {
+    let invalid = |e: String| DatabaseError::InvalidFormat(format!("Custom {}: {}", value, e));
+    let Some((tag, text)) = value.split_once(':') else {
+        return Err(invalid("expected tag:value".to_string()))
+    };
+    let bytes = match codec(tag) {
+        Some(c) => c.parse(text).map_err(invalid)?,
+        None => return Err(invalid(format!("no codec registered for custom field {}", tag))),
+    };
+    Field::custom(tag, bytes)
+}
@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::errors::*;
+
+/// Largest number of digits after the decimal point of a Decimal
+pub const MAX_DECIMAL_SCALE: u32 = 28;
+
+/// Fixed-point decimal number: a signed integer mantissa divided by ten to the power of
+/// its scale.  For amounts such as currency that must not be rounded as floats are, and
+/// for values beyond the range of i64.
+///
+/// Decimals are kept without trailing zeros after the decimal point, so `1.50` and `1.5`
+/// are the same value, equal and hashed alike, and ordered by their numeric value.
+/// ```
+/// use persistent_keystore_rs::Decimal;
+/// let price: Decimal = "12.50".parse().unwrap();
+/// assert_eq!(price, Decimal::new(125, 1).unwrap());
+/// assert_eq!(price.to_string(), "12.5");
+/// assert!(price < "12.51".parse().unwrap());
+/// assert!(price > "-100".parse().unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawDecimal", into = "RawDecimal")]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// Serialized form of a Decimal, checked when read
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct RawDecimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl TryFrom<RawDecimal> for Decimal {
+    type Error = DatabaseError;
+
+    fn try_from(raw: RawDecimal) -> Result<Self, Self::Error> {
+        Decimal::new(raw.mantissa, raw.scale)
+    }
+}
+
+impl From<Decimal> for RawDecimal {
+    fn from(d: Decimal) -> Self {
+        RawDecimal{
+            mantissa: d.mantissa,
+            scale: d.scale,
+        }
+    }
+}
+
+impl Decimal {
+    /// Returns the Decimal mantissa / 10^scale.  If the scale exceeds MAX_DECIMAL_SCALE once
+    /// trailing zeros are removed, DatabaseError::InvalidFormat is returned.
+    pub fn new(mantissa: i128, scale: u32) -> Result<Decimal, DatabaseError> {
+        let (mut mantissa, mut scale) = (mantissa, scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        };
+        if scale > MAX_DECIMAL_SCALE {
+            return Err(DatabaseError::InvalidFormat(format!("Decimal scale {} exceeds {}", scale, MAX_DECIMAL_SCALE)))
+        };
+        Ok(Decimal{mantissa, scale})
+    }
+
+    /// Returns the mantissa, without trailing zeros
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// Returns the number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Returns the mantissa at a larger scale, or None if it overflows
+    fn rescaled(&self, scale: u32) -> Option<i128> {
+        10i128.checked_pow(scale - self.scale).and_then(|f| self.mantissa.checked_mul(f))
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(v: i64) -> Self {
+        Decimal{
+            mantissa: v as i128,
+            scale: 0,
+        }
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (self.rescaled(scale), other.rescaled(scale)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            // Only the side with the smaller scale is rescaled, and if it overflows its
+            // magnitude is the larger
+            (None, _) if self.mantissa < 0 => Ordering::Less,
+            (None, _) => Ordering::Greater,
+            (_, None) if other.mantissa < 0 => Ordering::Greater,
+            (_, None) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits)
+        };
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// Parses a decimal number such as `-12.50`; exponents are not accepted
+impl FromStr for Decimal {
+    type Err = DatabaseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || DatabaseError::InvalidFormat(format!("Decimal {}", value));
+        let trimmed = value.trim();
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(invalid())
+        };
+        let mut mantissa: i128 = 0;
+        for c in integer.chars().chain(fraction.chars()) {
+            let digit = c.to_digit(10).ok_or_else(invalid)? as i128;
+            mantissa = mantissa.checked_mul(10)
+                .and_then(|m| if negative { m.checked_sub(digit) } else { m.checked_add(digit) })
+                .ok_or_else(invalid)?;
+        };
+        Decimal::new(mantissa, fraction.len() as u32)
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+#[cfg(feature = "zstd")]
+use tracing::debug;
+
+#[cfg(feature = "zstd")]
+use crate::structs::*;
+use crate::errors::*;
+
+/// Marks a database file compressed with zstd and a dictionary rather than lz4.  The file
+/// holds the magic, the length of the dictionary as a u32, the dictionary, the length of
+/// the serialized database as a u64 and the zstd frame, with lengths little endian.
+pub(crate) const ZSTD_MAGIC: &[u8; 8] = b"PKSZSTD1";
+
+/// Trained zstd dictionary shared by the clones of a Client
+pub(crate) type Dictionary = Arc<Vec<u8>>;
+
+/// Returns true if the contents of a database file were compressed with a dictionary
+pub(crate) fn is_dictionary_compressed(compressed: &[u8]) -> bool {
+    compressed.starts_with(ZSTD_MAGIC)
+}
+
+/// Returns the error for a database file compressed with a dictionary when the `zstd`
+/// feature is not enabled
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn unsupported() -> DatabaseError {
+    DatabaseError::InvalidFormat("database compressed with a zstd dictionary; enable the zstd feature".to_string())
+}
+
+/// Trains a dictionary of at most max_size bytes from the serialized Entries of every Table
+#[cfg(feature = "zstd")]
+pub(crate) fn train(database: &Database, max_size: usize) -> Result<Vec<u8>, DatabaseError> {
+    let mut samples = Vec::new();
+    for name in database.list_tables() {
+        for entry in database.get_table_ref(&name)?.scan()? {
+            samples.push(bincode::serialize(&*entry)?);
+        };
+    };
+    debug!("Training dictionary from {} entries", samples.len());
+    Ok(zstd::dict::from_samples(&samples, max_size)?)
+}
+
+/// Compresses the serialized database with the dictionary, returning the contents of the
+/// database file
+#[cfg(feature = "zstd")]
+pub(crate) fn compress(output: &[u8], dictionary: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(0, dictionary)?;
+    let frame = compressor.compress(output)?;
+
+    let mut compressed = Vec::with_capacity(ZSTD_MAGIC.len() + 12 + dictionary.len() + frame.len());
+    compressed.extend_from_slice(ZSTD_MAGIC);
+    compressed.extend_from_slice(&(dictionary.len() as u32).to_le_bytes());
+    compressed.extend_from_slice(dictionary);
+    compressed.extend_from_slice(&(output.len() as u64).to_le_bytes());
+    compressed.extend_from_slice(&frame);
+    Ok(compressed)
+}
+
+/// Returns the dictionary and serialized database held by the contents of a database file
+/// compressed with a dictionary
+#[cfg(feature = "zstd")]
+pub(crate) fn decompress(compressed: &[u8]) -> Result<(Dictionary, Vec<u8>), DatabaseError> {
+    let truncated = || DatabaseError::InvalidFormat("truncated zstd database file".to_string());
+    let rest = compressed.strip_prefix(ZSTD_MAGIC.as_slice()).ok_or_else(truncated)?;
+    let (len, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(truncated())
+    };
+    let (dictionary, rest) = rest.split_at(len);
+    let (size, frame) = rest.split_first_chunk::<8>().ok_or_else(truncated)?;
+    let size = u64::from_le_bytes(*size) as usize;
+
+    let mut decompressor = zstd::bulk::Decompressor::with_dictionary(dictionary)?;
+    let output = decompressor.decompress(frame, size)?;
+    Ok((Arc::new(dictionary.to_vec()), output))
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use crate::structs::*;
+
+/// Entry held by both databases of a diff with differing fields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryChange {
+    pub key: Field,
+    /// Entry held by the first database
+    pub before: Arc<Entry>,
+    /// Entry held by the second database
+    pub after: Arc<Entry>,
+    /// Names of the fields added, removed or changed, in order
+    pub fields: Vec<String>,
+}
+
+/// Differences between the Entries of a Table held by both databases of a diff
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableDiff {
+    pub name: String,
+    /// True if the primary field or fields of the Table differ
+    pub schema_changed: bool,
+    /// Entries only held by the second database, in order of their primary fields
+    pub added: Vec<Arc<Entry>>,
+    /// Entries only held by the first database, in order of their primary fields
+    pub removed: Vec<Arc<Entry>>,
+    /// Entries held by both databases with differing fields, in order of their primary
+    /// fields
+    pub changed: Vec<EntryChange>,
+}
+
+impl TableDiff {
+    /// Returns true if the Table is the same in both databases
+    pub fn is_empty(&self) -> bool {
+        !self.schema_changed && self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Result of Client::diff_files; the changes that turn the first database into the second.
+/// Entries are compared by their fields, as by DatabaseClient::merge_from, so Entries
+/// differing only in last_timestamp are the same.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatabaseDiff {
+    /// Tables only held by the second database, in order
+    pub tables_added: Vec<String>,
+    /// Tables only held by the first database, in order
+    pub tables_removed: Vec<String>,
+    /// Tables held by both databases that differ, in order of their names
+    pub tables_changed: Vec<TableDiff>,
+}
+
+impl DatabaseDiff {
+    /// Returns true if both databases hold the same Tables and Entries
+    pub fn is_empty(&self) -> bool {
+        self.tables_added.is_empty() && self.tables_removed.is_empty() && self.tables_changed.is_empty()
+    }
+}
+
+/// Returns the names of the fields added, removed or changed between the Entries, in order
+pub(crate) fn changed_fields(before: &Entry, after: &Entry) -> Vec<String> {
+    let mut fields: Vec<String> = before.fields.iter()
+        .filter(|(k, v)| after.fields.get(*k) != Some(*v))
+        .map(|(k, _)| k.clone())
+        .chain(after.fields.keys().filter(|k| !before.fields.contains_key(*k)).cloned())
+        .collect();
+    fields.sort_unstable();
+    fields
+}
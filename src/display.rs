@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::structs::*;
+use crate::export::PRIMARY_FIELD_COLUMN;
+use crate::redact::REDACTED;
+
+/// Labeled value of a DisplayRow
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayColumn {
+    /// Name of the field; PRIMARY_FIELD_COLUMN for the primary Field
+    pub name: String,
+    /// Value of the field; None if the Entry does not have it
+    pub value: Option<Field>,
+    /// True for the primary Field and required fields
+    pub required: bool,
+    /// True if the Table marks the field sensitive; see TableBuilder::sensitive_field
+    pub sensitive: bool,
+}
+
+impl DisplayColumn {
+    /// Returns the value as shown to a reader: empty if unset, and REDACTED if sensitive
+    pub fn text(&self) -> String {
+        match &self.value {
+            None => String::new(),
+            Some(_) if self.sensitive => REDACTED.to_string(),
+            Some(v) => v.to_string(),
+        }
+    }
+}
+
+/// Fields of an Entry in the order of the schema of its Table: the primary Field, then the
+/// required fields, then the optional fields, each group by name.  Every Entry of a Table
+/// has the same columns, set or not, so rows line up when rendered together.
+/// Returned by Entry::to_display_row.
+///
+/// Written as `name: value` pairs, leaving out unset fields and masking sensitive ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayRow {
+    pub columns: Vec<DisplayColumn>,
+}
+
+impl DisplayRow {
+    /// Returns the names of the columns, in order
+    pub fn labels(&self) -> Vec<&str> {
+        self.columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Returns the text of each column, in order; see DisplayColumn::text
+    pub fn cells(&self) -> Vec<String> {
+        self.columns.iter().map(DisplayColumn::text).collect()
+    }
+}
+
+impl fmt::Display for DisplayRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for column in self.columns.iter().filter(|c| c.value.is_some()) {
+            if !first {
+                write!(f, ", ")?;
+            };
+            write!(f, "{}: {}", column.name, column.text())?;
+            first = false;
+        };
+        Ok(())
+    }
+}
+
+impl Table {
+    /// Returns the names of the fields of the Table in display order: the required fields,
+    /// then the optional fields, each by name.  The order of DisplayRow and of CSV exports.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    /// let table = Table::new()
+    ///     .name(String::from("MyTable"))
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_optional_field(String::from("Alias"), FieldType::String).unwrap()
+    ///     .add_field(String::from("Name"), FieldType::String).unwrap()
+    ///     .add_field(String::from("Age"), FieldType::I64).unwrap()
+    ///     .build().unwrap();
+    /// assert_eq!(table.display_columns(), ["Age", "Name", "Alias"]);
+    /// ```
+    pub fn display_columns(&self) -> Vec<String> {
+        let mut columns: Vec<(bool, &String)> = self.fields.iter()
+            .map(|(name, f)| (matches!(f, FieldRequirement::Optional(_)), name))
+            .collect();
+        columns.sort();
+        columns.into_iter().map(|(_, name)| name.clone()).collect()
+    }
+}
+
+impl Entry {
+    /// Returns the fields of the Entry labeled and ordered by the schema of the Table; see
+    /// DisplayRow.  Fields the Table does not declare are left out.
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field, Table, FieldType};
+    /// use persistent_keystore_rs::REDACTED;
+    /// let table = Table::new()
+    ///     .name(String::from("Users"))
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_optional_field(String::from("Alias"), FieldType::String).unwrap()
+    ///     .add_field(String::from("Name"), FieldType::String).unwrap()
+    ///     .add_optional_field(String::from("Token"), FieldType::String).unwrap()
+    ///     .sensitive_field(String::from("Token")).unwrap()
+    ///     .build().unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("u1".to_string())).unwrap()
+    ///     .add_field("Name".to_string(), Field::String("Ada".to_string())).unwrap()
+    ///     .add_field("Token".to_string(), Field::String("hunter2".to_string())).unwrap()
+    ///     .build().unwrap();
+    /// let row = entry.to_display_row(&table);
+    /// assert_eq!(row.labels(), ["primary_field", "Name", "Alias", "Token"]);
+    /// assert_eq!(row.cells(), ["u1", "Ada", "", REDACTED]);
+    /// assert_eq!(row.to_string(), format!("primary_field: u1, Name: Ada, Token: {}", REDACTED));
+    /// ```
+    pub fn to_display_row(&self, table: &Table) -> DisplayRow {
+        let mut columns = vec![DisplayColumn{
+            name: PRIMARY_FIELD_COLUMN.to_string(),
+            value: Some(self.primary_field.clone()),
+            required: true,
+            sensitive: false,
+        }];
+        for name in table.display_columns() {
+            columns.push(DisplayColumn{
+                value: self.fields.get(&name).cloned(),
+                required: matches!(table.fields.get(&name), Some(FieldRequirement::Required(_))),
+                sensitive: table.sensitive_fields.contains(&name),
+                name,
+            });
+        };
+        DisplayRow{columns}
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+use crate::structs::*;
+
+/// Change that a destructive operation would have made to the database of a Client built
+/// with ClientBuilder::dry_run, recorded in place of making it; see
+/// DatabaseClient::take_dry_run_actions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DryRunAction {
+    /// The table, holding the number of entries, would have been dropped by drop_table
+    DropTable {
+        table: String,
+        entries: usize,
+    },
+    /// The entries of the table would have been deleted by delete_many, truncate or prune.
+    /// Entries of other tables deleted by OnDelete::Cascade references are not included.
+    DeleteEntries {
+        table: String,
+        keys: Vec<Field>,
+    },
+}
+
+impl fmt::Display for DryRunAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DryRunAction::DropTable{table, entries} => write!(f, "drop table {} holding {} entries", table, entries),
+            DryRunAction::DeleteEntries{table, keys} => write!(f, "delete {} entries from table {}", keys.len(), table),
+        }
+    }
+}
@@ -2,6 +2,7 @@
 use std::fmt;
 use crate::structs::Database;
 use std::sync::PoisonError;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum DatabaseError {
@@ -15,7 +16,7 @@ pub enum DatabaseError {
     EntryDoesNotExists,
     DatabaseIoError(std::io::Error),
     DatabaseExistsError,
-    DatabaseDoesNotExist(String),
+    DatabaseDoesNotExist(PathBuf),
     UnsupportedField(String),
     MissingRequiredField(String),
     MismatchedFieldType,
@@ -25,6 +26,116 @@ pub enum DatabaseError {
     InvalidPrimaryKey,
     DatabaseDecompressionError(lz4_flex::block::DecompressError),
     DatabaseCompressionError(lz4_flex::block::CompressError),
+    InvalidFormat(String),
+    CapacityExceeded(String),
+    Cancelled,
+    ReferenceViolation(String),
+    SchemaMismatch(String),
+    ReadOnlyTable(String),
+    EmptyDatabaseFile(PathBuf),
+    AccessDenied(String),
+    WouldBlock,
+    TypeMismatch(String),
+    InvalidName(String),
+    UnsavedChanges(u64),
+    UnknownSequence(u64),
+}
+
+/// Category of a DatabaseError, as returned by DatabaseError::kind, so that callers can
+/// handle every error of a category alike without matching each variant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The table, entry or database does not exist
+    NotFound,
+    /// The operation conflicts with the contents of the database, such as inserting an
+    /// entry that exists or exceeding the capacity of a table
+    Conflict,
+    /// The database file could not be decoded
+    Corruption,
+    /// Reading or writing a file failed
+    Io,
+    /// The table, entry or value supplied is invalid
+    Validation,
+    /// A lock of the Client could not be acquired, or was held by another operation when
+    /// not waiting for it
+    Locking,
+    /// The operation is not permitted, by the authorizer of the Client or because the
+    /// table is read-only
+    Denied,
+    /// The operation was cancelled by its CancellationToken
+    Cancelled,
+}
+
+impl DatabaseError {
+    /// Returns the category of the error
+    /// ```
+    /// use persistent_keystore_rs::errors::{DatabaseError, ErrorKind};
+    /// assert_eq!(DatabaseError::EntryExists.kind(), ErrorKind::Conflict);
+    /// assert_eq!(DatabaseError::TableDoesNotExist("MyTable".to_string()).kind(), ErrorKind::NotFound);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DatabaseError::TableDoesNotExist(_) |
+            DatabaseError::EntryDoesNotExists |
+            DatabaseError::DatabaseDoesNotExist(_) => ErrorKind::NotFound,
+            DatabaseError::TableExists(_) |
+            DatabaseError::EntryExists |
+            DatabaseError::DatabaseExistsError |
+            DatabaseError::ReferenceViolation(_) |
+            DatabaseError::UnsavedChanges(_) |
+            DatabaseError::CapacityExceeded(_) => ErrorKind::Conflict,
+            DatabaseError::DatabaseSerializationError(_) |
+            DatabaseError::DatabaseDecompressionError(_) |
+            DatabaseError::EmptyDatabaseFile(_) => ErrorKind::Corruption,
+            DatabaseError::DatabaseIoError(_) |
+            DatabaseError::DatabaseCompressionError(_) => ErrorKind::Io,
+            DatabaseError::TableMissingPrimaryKey |
+            DatabaseError::TableNameNotSet |
+            DatabaseError::TableMustContainFields |
+            DatabaseError::EntryMustContainFields |
+            DatabaseError::UnsupportedField(_) |
+            DatabaseError::MissingRequiredField(_) |
+            DatabaseError::MismatchedFieldType |
+            DatabaseError::UnsupportedFieldType |
+            DatabaseError::InvalidPrimaryKey |
+            DatabaseError::InvalidFormat(_) |
+            DatabaseError::SchemaMismatch(_) |
+            DatabaseError::TypeMismatch(_) |
+            DatabaseError::InvalidName(_) |
+            DatabaseError::UnknownSequence(_) => ErrorKind::Validation,
+            DatabaseError::UnableToGetLock |
+            DatabaseError::WouldBlock => ErrorKind::Locking,
+            DatabaseError::ReadOnlyTable(_) |
+            DatabaseError::AccessDenied(_) => ErrorKind::Denied,
+            DatabaseError::Cancelled => ErrorKind::Cancelled,
+        }
+    }
+
+    /// Returns true if the operation may succeed if retried unchanged: when a lock could
+    /// not be acquired or was held by another operation, or a file operation was interrupted, timed out or would block.
+    /// Every other error recurs until the request or the database is changed.
+    /// ```
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// use std::io;
+    /// assert!(DatabaseError::UnableToGetLock.is_retryable());
+    /// assert!(DatabaseError::from(io::Error::from(io::ErrorKind::TimedOut)).is_retryable());
+    /// assert!(!DatabaseError::from(io::Error::from(io::ErrorKind::NotFound)).is_retryable());
+    /// assert!(!DatabaseError::EntryExists.is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DatabaseError::UnableToGetLock |
+            DatabaseError::WouldBlock => true,
+            DatabaseError::DatabaseIoError(e) => matches!(e.kind(),
+                std::io::ErrorKind::Interrupted |
+                std::io::ErrorKind::WouldBlock |
+                std::io::ErrorKind::TimedOut |
+                std::io::ErrorKind::ResourceBusy
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for DatabaseError {
@@ -39,7 +150,7 @@ impl fmt::Display for DatabaseError {
             DatabaseError::EntryDoesNotExists => format!("Entry does not exist"),
             DatabaseError::DatabaseExistsError => format!("Database already exists"),
             DatabaseError::DatabaseIoError(e) => format!("Database IO Error: {}", e),
-            DatabaseError::DatabaseDoesNotExist(d) => format!("Database {} is not found", d),
+            DatabaseError::DatabaseDoesNotExist(d) => format!("Database {} is not found", d.display()),
             DatabaseError::UnsupportedField(f) => format!("Field name {} is not supported on table", f),
             DatabaseError::MissingRequiredField(f) => format!("Missing required field {}", f),
             DatabaseError::MismatchedFieldType => format!("Field is not a supported type"),
@@ -50,6 +161,19 @@ impl fmt::Display for DatabaseError {
             DatabaseError::EntryMustContainFields => format!("Entry must contain at least one field"),
             DatabaseError::DatabaseCompressionError(e) => format!("Database compression error {}", e),
             DatabaseError::DatabaseDecompressionError(e) => format!("Database decompression error {}", e),
+            DatabaseError::InvalidFormat(v) => format!("Unable to parse {}", v),
+            DatabaseError::CapacityExceeded(v) => format!("Capacity exceeded: {}", v),
+            DatabaseError::Cancelled => format!("Operation cancelled"),
+            DatabaseError::ReferenceViolation(v) => format!("Reference violation: {}", v),
+            DatabaseError::SchemaMismatch(v) => format!("Schema mismatch: {}", v),
+            DatabaseError::ReadOnlyTable(t) => format!("Table {} is read-only", t),
+            DatabaseError::EmptyDatabaseFile(d) => format!("Database file {} is empty", d.display()),
+            DatabaseError::AccessDenied(v) => format!("Access denied to {}", v),
+            DatabaseError::WouldBlock => format!("Database lock is held by another operation"),
+            DatabaseError::TypeMismatch(v) => format!("Type mismatch: {}", v),
+            DatabaseError::InvalidName(v) => format!("Invalid name: {}", v),
+            DatabaseError::UnsavedChanges(n) => format!("{} mutations of the database are not saved", n),
+            DatabaseError::UnknownSequence(s) => format!("No mutation has sequence number {}", s),
         };
         write!(f, "{}", msg)
     }
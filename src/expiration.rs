@@ -0,0 +1,159 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_derive::{Serialize, Deserialize};
+
+use crate::errors::*;
+
+const DAY: Duration = Duration::from_secs(86400);
+
+/// Calendar based expiration for the Entries of a Table, evaluated by prune.
+///
+/// Unlike a fixed expiration Duration, which is measured from the last update of each
+/// Entry, a schedule expires every Entry last updated before the most recent scheduled
+/// time.  Schedules are evaluated against the wall clock in UTC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpirationSchedule {
+    /// Entries expire at the first midnight UTC after they were last updated
+    EndOfDay,
+    /// Entries expire at the first occurrence of the supplied time of day, as an offset
+    /// from midnight UTC, after they were last updated
+    Daily(Duration),
+    /// Entries expire at the first multiple of the supplied interval since the Unix epoch
+    /// after they were last updated, e.g. every hour on the hour
+    Every(Duration),
+    /// Entries expire at the first time matching the supplied cron expression after they
+    /// were last updated, e.g. `0 0 0 * * Sun *`; evaluated only with the `cron` feature.
+    /// Without it the schedule fails validate and has no boundaries, so tables using it
+    /// can still be read but are not pruned by it.
+    Cron(String),
+}
+
+impl ExpirationSchedule {
+    /// Returns an error if the schedule can never be evaluated.
+    /// Intervals must be non-zero and daily offsets less than a day, and cron expressions
+    /// must parse, which requires the `cron` feature; invalid schedules return
+    /// DatabaseError::InvalidFormat.
+    /// ```
+    /// use persistent_keystore_rs::ExpirationSchedule;
+    /// use std::time::Duration;
+    /// assert!(ExpirationSchedule::Daily(Duration::from_secs(7200)).validate().is_ok());
+    /// assert!(ExpirationSchedule::Every(Duration::ZERO).validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), DatabaseError> {
+        match self {
+            ExpirationSchedule::EndOfDay => Ok(()),
+            ExpirationSchedule::Daily(offset) => {
+                if *offset >= DAY {
+                    return Err(DatabaseError::InvalidFormat(format!("daily expiration offset {:?}", offset)))
+                };
+                Ok(())
+            },
+            ExpirationSchedule::Every(interval) => {
+                if interval.is_zero() {
+                    return Err(DatabaseError::InvalidFormat(format!("expiration interval {:?}", interval)))
+                };
+                Ok(())
+            },
+            ExpirationSchedule::Cron(expression) => {
+                parse_cron(expression)?;
+                Ok(())
+            },
+        }
+    }
+
+    /// Returns the most recent scheduled time at or before now.  Entries last updated
+    /// before this time have expired.  None is returned if no scheduled time has passed,
+    /// or the schedule cannot be evaluated.
+    /// ```
+    /// use persistent_keystore_rs::ExpirationSchedule;
+    /// use persistent_keystore_rs::format::{format_date, parse_date};
+    /// let now = parse_date("2018-02-14T13:28:07Z").unwrap();
+    /// let boundary = ExpirationSchedule::EndOfDay.last_boundary(now).unwrap();
+    /// assert_eq!(format_date(boundary), "2018-02-14T00:00:00Z".to_string());
+    /// ```
+    pub fn last_boundary(&self, now: SystemTime) -> Option<SystemTime> {
+        match self {
+            ExpirationSchedule::EndOfDay => aligned_boundary(now, DAY, Duration::ZERO),
+            ExpirationSchedule::Daily(offset) => aligned_boundary(now, DAY, *offset),
+            ExpirationSchedule::Every(interval) => aligned_boundary(now, *interval, Duration::ZERO),
+            #[cfg(feature = "cron")]
+            ExpirationSchedule::Cron(expression) => {
+                let schedule = parse_cron(expression).ok()?;
+                let now = chrono::DateTime::<chrono::Utc>::from(now);
+                let whole_seconds = now - chrono::Duration::nanoseconds(now.timestamp_subsec_nanos() as i64);
+                let previous = schedule.after(&(whole_seconds + chrono::Duration::seconds(1))).next_back()?;
+                Some(SystemTime::from(previous))
+            },
+            #[cfg(not(feature = "cron"))]
+            ExpirationSchedule::Cron(_) => None,
+        }
+    }
+}
+
+impl ExpirationSchedule {
+    /// Returns the first scheduled time after the supplied time; an Entry last updated at
+    /// that time expires once it has passed.  None is returned if the schedule cannot be
+    /// evaluated.
+    /// ```
+    /// use persistent_keystore_rs::ExpirationSchedule;
+    /// use persistent_keystore_rs::format::{format_date, parse_date};
+    /// let updated = parse_date("2018-02-14T13:28:07Z").unwrap();
+    /// let boundary = ExpirationSchedule::EndOfDay.next_boundary(updated).unwrap();
+    /// assert_eq!(format_date(boundary), "2018-02-15T00:00:00Z".to_string());
+    /// ```
+    pub fn next_boundary(&self, after: SystemTime) -> Option<SystemTime> {
+        match self {
+            ExpirationSchedule::EndOfDay => next_aligned_boundary(after, DAY, Duration::ZERO),
+            ExpirationSchedule::Daily(offset) => next_aligned_boundary(after, DAY, *offset),
+            ExpirationSchedule::Every(interval) => next_aligned_boundary(after, *interval, Duration::ZERO),
+            #[cfg(feature = "cron")]
+            ExpirationSchedule::Cron(expression) => {
+                let schedule = parse_cron(expression).ok()?;
+                let next = schedule.after(&chrono::DateTime::<chrono::Utc>::from(after)).next()?;
+                Some(SystemTime::from(next))
+            },
+            #[cfg(not(feature = "cron"))]
+            ExpirationSchedule::Cron(_) => None,
+        }
+    }
+}
+
+/// Returns the first time after the supplied time that is offset past a multiple of
+/// interval since the Unix epoch
+fn next_aligned_boundary(after: SystemTime, interval: Duration, offset: Duration) -> Option<SystemTime> {
+    if interval.is_zero() {
+        return None
+    };
+
+    match aligned_boundary(after, interval, offset) {
+        Some(b) => b.checked_add(interval),
+        None => UNIX_EPOCH.checked_add(offset),
+    }
+}
+
+/// Returns the latest time at or before now that is offset past a multiple of interval
+/// since the Unix epoch
+fn aligned_boundary(now: SystemTime, interval: Duration, offset: Duration) -> Option<SystemTime> {
+    let interval = interval.as_nanos();
+    if interval == 0 {
+        return None
+    };
+
+    let since = now.duration_since(UNIX_EPOCH).ok()?.as_nanos().checked_sub(offset.as_nanos())?;
+    let boundary = since - since % interval + offset.as_nanos();
+    let secs = (boundary / 1_000_000_000) as u64;
+    let nanos = (boundary % 1_000_000_000) as u32;
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+#[cfg(feature = "cron")]
+fn parse_cron(expression: &str) -> Result<cron::Schedule, DatabaseError> {
+    match expression.parse::<cron::Schedule>() {
+        Ok(s) => Ok(s),
+        Err(e) => Err(DatabaseError::InvalidFormat(format!("cron expression {}: {}", expression, e))),
+    }
+}
+
+#[cfg(not(feature = "cron"))]
+fn parse_cron(expression: &str) -> Result<(), DatabaseError> {
+    Err(DatabaseError::InvalidFormat(format!("cron expression {}: requires the cron feature", expression)))
+}
@@ -0,0 +1,135 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::structs::*;
+use crate::format::format_date;
+use crate::errors::*;
+
+/// Name of the column holding the primary Field of each Entry written by
+/// DatabaseClient::query_to_writer
+pub const PRIMARY_FIELD_COLUMN: &str = "primary_field";
+
+/// Output format of DatabaseClient::query_to_writer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line, mapping field name to natural scalar value as Entry::flat
+    Ndjson,
+    /// Comma separated values with a header row, in the order of Table::display_columns;
+    /// unset fields are empty
+    Csv,
+}
+
+/// Writes Entries of a Table to a sink in an ExportFormat, one Entry at a time
+pub(crate) struct ExportWriter<'a> {
+    format: ExportFormat,
+    columns: Vec<String>,
+    writer: &'a mut dyn Write,
+    line: String,
+}
+
+impl<'a> ExportWriter<'a> {
+    /// Returns a writer for the Entries of the Table, writing the CSV header if required
+    pub(crate) fn new(format: ExportFormat, table: &Table, writer: &'a mut dyn Write) -> Result<Self, DatabaseError> {
+        let columns = table.display_columns();
+        let mut export = ExportWriter{
+            format,
+            columns,
+            writer,
+            line: String::new(),
+        };
+        if format == ExportFormat::Csv {
+            push_csv(&mut export.line, PRIMARY_FIELD_COLUMN);
+            for column in &export.columns {
+                export.line.push(',');
+                push_csv(&mut export.line, column);
+            };
+            export.finish_line()?;
+        };
+        Ok(export)
+    }
+
+    fn finish_line(&mut self) -> Result<(), DatabaseError> {
+        self.line.push('\n');
+        self.writer.write_all(self.line.as_bytes())?;
+        self.line.clear();
+        Ok(())
+    }
+
+    pub(crate) fn write(&mut self, entry: &Entry) -> Result<(), DatabaseError> {
+        match self.format {
+            ExportFormat::Ndjson => {
+                self.line.push('{');
+                push_json_string(&mut self.line, PRIMARY_FIELD_COLUMN);
+                self.line.push(':');
+                push_json(&mut self.line, &entry.primary_field);
+                for column in &self.columns {
+                    if let Some(value) = entry.fields.get(column) {
+                        self.line.push(',');
+                        push_json_string(&mut self.line, column);
+                        self.line.push(':');
+                        push_json(&mut self.line, value);
+                    };
+                };
+                self.line.push('}');
+            },
+            ExportFormat::Csv => {
+                push_csv(&mut self.line, &entry.primary_field.to_string());
+                for column in &self.columns {
+                    self.line.push(',');
+                    if let Some(value) = entry.fields.get(column) {
+                        push_csv(&mut self.line, &value.to_string());
+                    };
+                };
+            },
+        };
+        self.finish_line()
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), DatabaseError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Appends a CSV cell, quoted if it contains a separator, quote or line break
+fn push_csv(line: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        line.push('"');
+        line.push_str(&value.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(value);
+    };
+}
+
+/// Appends a Field as its natural JSON scalar value
+fn push_json(line: &mut String, field: &Field) {
+    match field {
+        Field::String(v) | Field::Enum(v) => push_json_string(line, v),
+        Field::Date(v) => push_json_string(line, &format_date(*v)),
+        Field::Null => line.push_str("null"),
+        Field::Custom{..} | Field::Decimal(_) | Field::IpAddr(_) | Field::Cidr(_) | Field::GeoPoint(_) => push_json_string(line, &field.to_string()),
+        f => {
+            let _ = write!(line, "{}", f);
+        },
+    };
+}
+
+/// Appends a JSON string literal, escaping quotes, backslashes and control characters
+fn push_json_string(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            },
+            c => line.push(c),
+        };
+    };
+    line.push('"');
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Not;
+use std::time::SystemTime;
+
+use crate::structs::*;
+use crate::condition::{Condition, field_value};
+
+/// Predicate over the fields of an Entry, combining Conditions with AND, OR and NOT;
+/// queried with DatabaseClient::query_filter.
+///
+/// The Conditions required by every match, those of the Filter itself and of the Filters
+/// of a top level And, are used to choose an index as by query_where; the rest of the
+/// Filter is evaluated against each candidate.
+/// ```
+/// use persistent_keystore_rs::{Field, Filter};
+/// use std::time::SystemTime;
+/// # use persistent_keystore_rs::Entry;
+/// let filter = Filter::equals("Status", Field::String("active".to_string()))
+///     .and(Filter::equals("Tier", Field::String("gold".to_string()))
+///         .or(Filter::equals("Tier", Field::String("silver".to_string()))));
+/// assert_eq!(filter.to_string(), "Status = active AND (Tier = gold OR Tier = silver)");
+/// # let entry = Entry::new()
+/// #    .set_primary_field(Field::String("u1".to_string())).unwrap()
+/// #    .add_field("Status".to_string(), Field::String("active".to_string())).unwrap()
+/// #    .add_field("Tier".to_string(), Field::String("silver".to_string())).unwrap()
+/// #    .build().unwrap();
+/// assert!(filter.matches(&entry, SystemTime::now()));
+/// assert!(!(!filter).matches(&entry, SystemTime::now()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// The named field, or the primary Field if named PRIMARY_FIELD_KEY, meets the Condition
+    Field(String, Condition),
+    /// Every Filter matches; an empty And matches every Entry
+    And(Vec<Filter>),
+    /// Any Filter matches; an empty Or matches no Entry
+    Or(Vec<Filter>),
+    /// The Filter does not match
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Returns a Filter matching Entries whose named field meets the Condition
+    pub fn field(name: &str, condition: Condition) -> Self {
+        Filter::Field(name.to_string(), condition)
+    }
+
+    /// Returns a Filter matching Entries whose named field is set to the value
+    pub fn equals(name: &str, value: Field) -> Self {
+        Filter::field(name, Condition::Equals(value))
+    }
+
+    /// Returns a Filter matching Entries that match both Filters
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            },
+            f => Filter::And(vec![f, other]),
+        }
+    }
+
+    /// Returns a Filter matching Entries that match either Filter
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            },
+            f => Filter::Or(vec![f, other]),
+        }
+    }
+
+    /// Returns true if the Entry matches the Filter, with time based Conditions evaluated
+    /// relative to now
+    pub fn matches(&self, entry: &Entry, now: SystemTime) -> bool {
+        match self {
+            Filter::Field(name, c) => c.matches(field_value(entry, name), now),
+            Filter::And(filters) => filters.iter().all(|f| f.matches(entry, now)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(entry, now)),
+            Filter::Not(f) => !f.matches(entry, now),
+        }
+    }
+
+    /// Returns Conditions met by every Entry matching the Filter, at most one per field,
+    /// for choosing an index
+    pub(crate) fn required(&self) -> HashMap<String, Condition> {
+        let mut conditions = HashMap::new();
+        self.collect_required(&mut conditions);
+        conditions
+    }
+
+    fn collect_required(&self, conditions: &mut HashMap<String, Condition>) {
+        match self {
+            Filter::Field(name, c) => {
+                conditions.entry(name.clone()).or_insert_with(|| c.clone());
+            },
+            Filter::And(filters) => {
+                for f in filters {
+                    f.collect_required(conditions);
+                };
+            },
+            Filter::Or(_) | Filter::Not(_) => {},
+        }
+    }
+}
+
+/// Matches Entries that do not match the Filter
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        match self {
+            Filter::Not(f) => *f,
+            f => Filter::Not(Box::new(f)),
+        }
+    }
+}
+
+/// Every Condition must be met, as the conditions of query_where
+impl From<HashMap<String, Condition>> for Filter {
+    fn from(conditions: HashMap<String, Condition>) -> Self {
+        let mut conditions: Vec<(String, Condition)> = conditions.into_iter().collect();
+        conditions.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut filters: Vec<Filter> = conditions.into_iter()
+            .map(|(name, c)| Filter::Field(name, c))
+            .collect();
+        match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::And(filters),
+        }
+    }
+}
+
+/// Writes the Filters separated by the operator, parenthesizing nested Ands and Ors
+fn write_joined(f: &mut fmt::Formatter, filters: &[Filter], operator: &str, empty: &str) -> fmt::Result {
+    if filters.is_empty() {
+        return write!(f, "{}", empty)
+    };
+    for (i, filter) in filters.iter().enumerate() {
+        if i > 0 {
+            write!(f, " {} ", operator)?;
+        };
+        match filter {
+            Filter::And(_) | Filter::Or(_) => write!(f, "({})", filter)?,
+            _ => write!(f, "{}", filter)?,
+        };
+    };
+    Ok(())
+}
+
+/// Writes the Filter as an expression such as `Status = active AND (Tier = gold OR Tier = silver)`
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Field(name, c) => write!(f, "{} {}", name, c),
+            Filter::And(filters) => write_joined(f, filters, "AND", "TRUE"),
+            Filter::Or(filters) => write_joined(f, filters, "OR", "FALSE"),
+            Filter::Not(filter) => match filter.as_ref() {
+                Filter::Field(..) | Filter::Not(_) => write!(f, "NOT {}", filter),
+                _ => write!(f, "NOT ({})", filter),
+            },
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Feature flags, stored in a Table of the database so that they are shared by every clone
+//! of a Client and survive restarts.
+//!
+//! Percentage rollouts assign each context, such as a user id, to a stable bucket per flag,
+//! so a context keeps its answer as the percentage grows.
+use crate::sync::{Arc, Mutex};
+use crate::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, error};
+
+use crate::Client;
+use crate::observer::Operation;
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+const KIND: &str = "Kind";
+const ENABLED: &str = "Enabled";
+const PERCENTAGE: &str = "Percentage";
+const VARIANT: &str = "Variant";
+
+/// Value of a feature flag held by a FlagStore
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Flag {
+    /// Enabled or disabled for every context
+    Bool(bool),
+    /// Enabled for the supplied percentage of contexts, from 0 to 100
+    Percentage(u8),
+    /// Enabled for every context, selecting the named variant
+    Variant(String),
+}
+
+impl Flag {
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match (entry.fields.get(KIND), entry.fields.get(ENABLED), entry.fields.get(PERCENTAGE), entry.fields.get(VARIANT)) {
+            (Some(Field::String(k)), Some(Field::Bool(b)), _, _) if k == "bool" => Some(Flag::Bool(*b)),
+            (Some(Field::String(k)), _, Some(Field::U32(p)), _) if k == "percentage" => Some(Flag::Percentage((*p).min(100) as u8)),
+            (Some(Field::String(k)), _, _, Some(Field::String(v))) if k == "variant" => Some(Flag::Variant(v.clone())),
+            _ => None,
+        }
+    }
+
+    fn to_entry(&self, name: &str) -> Result<Entry, DatabaseError> {
+        let entry = Entry::new()
+            .set_primary_field(Field::String(name.to_string()))?;
+        let entry = match self {
+            Flag::Bool(b) => entry
+                .add_field(KIND.to_string(), Field::String("bool".to_string()))?
+                .add_field(ENABLED.to_string(), Field::Bool(*b))?,
+            Flag::Percentage(p) => entry
+                .add_field(KIND.to_string(), Field::String("percentage".to_string()))?
+                .add_field(PERCENTAGE.to_string(), Field::U32((*p).min(100) as u32))?,
+            Flag::Variant(v) => entry
+                .add_field(KIND.to_string(), Field::String("variant".to_string()))?
+                .add_field(VARIANT.to_string(), Field::String(v.clone()))?,
+        };
+        entry.build()
+    }
+}
+
+/// Change to a feature flag, sent to the subscribers of a FlagStore
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlagChange {
+    /// Name of the flag
+    pub name: String,
+    /// New value of the flag, or None if it was removed
+    pub flag: Option<Flag>,
+}
+
+/// Sets and evaluates feature flags, using a Table of the database of a Client; see
+/// DatabaseClient::flag_store.
+///
+/// Clones share their subscribers, so a change made through any clone is sent to every
+/// subscriber.  Changes made through other FlagStores, or by other processes, are not sent.
+#[derive(Clone)]
+pub struct FlagStore {
+    client: Client,
+    table: String,
+    subscribers: Arc<Mutex<Vec<Sender<FlagChange>>>>,
+}
+
+impl FlagStore {
+    /// Returns a FlagStore storing its flags in the named Table of the client, creating the
+    /// Table if it does not exist
+    pub(crate) fn new(client: &Client, table: String) -> Result<Self, DatabaseError> {
+        let mut client = client.clone();
+        if !client.list_tables()?.contains(&table) {
+            debug!("Creating flag table {}", table);
+            let t = Table::new()
+                .name(table.clone())
+                .primary_field(FieldType::String)?
+                .add_field(KIND.to_string(), FieldType::String)?
+                .add_optional_field(ENABLED.to_string(), FieldType::Bool)?
+                .add_optional_field(PERCENTAGE.to_string(), FieldType::U32)?
+                .add_optional_field(VARIANT.to_string(), FieldType::String)?
+                .build()?;
+            client.create_table(t)?;
+        };
+
+        Ok(FlagStore{
+            client,
+            table,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Sets the named flag, notifying subscribers if its value changed
+    pub fn set_flag(&mut self, name: &str, flag: Flag) -> Result<(), DatabaseError> {
+        let entry = flag.to_entry(name)?;
+        let changed = self.client.read_modify_write(Operation::InsertOrUpdate, &self.table, &entry.primary_field.clone(), |current, _| {
+            let changed = current.as_deref().and_then(Flag::from_entry).as_ref() != Some(&flag);
+            Ok((Some(entry), changed))
+        })?;
+        if changed {
+            self.notify(FlagChange{
+                name: name.to_string(),
+                flag: Some(flag),
+            });
+        };
+        Ok(())
+    }
+
+    /// Removes the named flag, returning true and notifying subscribers if it existed
+    pub fn remove_flag(&mut self, name: &str) -> Result<bool, DatabaseError> {
+        match self.client.delete(self.table.clone(), Field::String(name.to_string())) {
+            Ok(_) => {
+                self.notify(FlagChange{
+                    name: name.to_string(),
+                    flag: None,
+                });
+                Ok(true)
+            },
+            Err(DatabaseError::EntryDoesNotExists) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the value of the named flag, or None if it is not set
+    pub fn get_flag(&mut self, name: &str) -> Result<Option<Flag>, DatabaseError> {
+        match self.client.get(self.table.clone(), Field::String(name.to_string())) {
+            Ok(entry) => Ok(Flag::from_entry(&entry)),
+            Err(DatabaseError::EntryDoesNotExists) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the name and value of every flag, sorted by name
+    pub fn list_flags(&mut self) -> Result<Vec<(String, Flag)>, DatabaseError> {
+        let mut flags: Vec<(String, Flag)> = self.client.scan(self.table.clone())?
+            .iter()
+            .filter_map(|e| match (&e.primary_field, Flag::from_entry(e)) {
+                (Field::String(name), Some(flag)) => Some((name.clone(), flag)),
+                _ => None,
+            })
+            .collect();
+        flags.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(flags)
+    }
+
+    /// Returns true if the named flag is enabled for the context, such as a user id.  A flag
+    /// that is not set is disabled.
+    pub fn is_enabled(&mut self, name: &str, context: &str) -> Result<bool, DatabaseError> {
+        Ok(match self.get_flag(name)? {
+            Some(Flag::Bool(b)) => b,
+            Some(Flag::Percentage(p)) => bucket(name, context) < p as u64,
+            Some(Flag::Variant(_)) => true,
+            None => false,
+        })
+    }
+
+    /// Returns the variant selected by the named flag, or None if it is not a Variant flag
+    pub fn variant(&mut self, name: &str) -> Result<Option<String>, DatabaseError> {
+        match self.get_flag(name)? {
+            Some(Flag::Variant(v)) => Ok(Some(v)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns a Receiver of every subsequent change made through this FlagStore or its clones
+    pub fn subscribe(&self) -> Receiver<FlagChange> {
+        let (tx, rx) = channel();
+        match self.subscribers.lock() {
+            Ok(mut subscribers) => subscribers.push(tx),
+            Err(_) => error!("Unable to get subscriber lock"),
+        };
+        rx
+    }
+
+    /// Sends the change to every subscriber, forgetting those that have been dropped
+    fn notify(&self, change: FlagChange) {
+        match self.subscribers.lock() {
+            Ok(mut subscribers) => subscribers.retain(|s| s.send(change.clone()).is_ok()),
+            Err(_) => error!("Unable to get subscriber lock"),
+        };
+    }
+}
+
+/// Returns the bucket from 0 to 99 of the context for the named flag, using FNV-1a so that
+/// buckets are stable across processes and versions of Rust
+fn bucket(name: &str, context: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in name.bytes().chain(std::iter::once(0)).chain(context.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    };
+    hash % 100
+}
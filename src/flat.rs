@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fmt;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor};
+
+use crate::structs::*;
+use crate::format::{format_date, parse_date};
+
+/// Serializes a Field as its natural scalar value, e.g. `"x"` or `42` rather than
+/// `{"String": "x"}`; dates are written as RFC3339 strings, decimals as strings so no
+/// precision is lost, addresses, address blocks and points as strings, custom values as
+/// `tag:value`, Field::Null as null, and Field::Secret as REDACTED.
+/// Returned by Field::flat.
+pub struct FlatField<'a>(&'a Field);
+
+impl Serialize for FlatField<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Field::String(v) => serializer.serialize_str(v),
+            Field::I64(v) => serializer.serialize_i64(*v),
+            Field::I32(v) => serializer.serialize_i32(*v),
+            Field::U64(v) => serializer.serialize_u64(*v),
+            Field::U32(v) => serializer.serialize_u32(*v),
+            Field::Date(v) => serializer.serialize_str(&format_date(*v)),
+            Field::Bool(v) => serializer.serialize_bool(*v),
+            #[cfg(feature = "zeroize")]
+            Field::Secret(_) => serializer.serialize_str(crate::redact::REDACTED),
+            Field::Custom{..} => serializer.serialize_str(&self.0.to_string()),
+            Field::I128(v) => serializer.serialize_i128(*v),
+            Field::U128(v) => serializer.serialize_u128(*v),
+            Field::Decimal(v) => serializer.serialize_str(&v.to_string()),
+            Field::IpAddr(v) => serializer.serialize_str(&v.to_string()),
+            Field::Cidr(v) => serializer.serialize_str(&v.to_string()),
+            Field::GeoPoint(v) => serializer.serialize_str(&v.to_string()),
+            Field::Enum(v) => serializer.serialize_str(v),
+            Field::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+/// Serializes an Entry as a flat map of field name to natural scalar value, with the
+/// primary Field under the supplied key.  Returned by Entry::flat.
+pub struct FlatEntry<'a> {
+    entry: &'a Entry,
+    primary_key: &'a str,
+}
+
+impl Serialize for FlatEntry<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut names: Vec<&String> = self.entry.fields.keys().collect();
+        names.sort();
+
+        let mut map = serializer.serialize_map(Some(names.len() + 1))?;
+        map.serialize_entry(self.primary_key, &FlatField(&self.entry.primary_field))?;
+        for name in names {
+            map.serialize_entry(name, &FlatField(&self.entry.fields[name]))?;
+        };
+        map.end()
+    }
+}
+
+impl Field {
+    /// Returns the Field in a form serialized as its natural scalar value
+    /// ```
+    /// use persistent_keystore_rs::Field;
+    /// let json = serde_json::to_string(&Field::I64(42).flat()).unwrap();
+    /// assert_eq!(json, "42");
+    /// ```
+    pub fn flat(&self) -> FlatField<'_> {
+        FlatField(self)
+    }
+}
+
+impl Entry {
+    /// Returns the Entry in a form serialized as a flat map of field name to natural
+    /// scalar value, with the primary Field under the supplied key; see Table::flat_entry
+    /// for the reverse
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///     .build().unwrap();
+    /// let json = serde_json::to_string(&entry.flat("id")).unwrap();
+    /// assert_eq!(json, r#"{"id":"MyEntry","Count":1}"#);
+    /// ```
+    pub fn flat<'a>(&'a self, primary_key: &'a str) -> FlatEntry<'a> {
+        FlatEntry{
+            entry: self,
+            primary_key,
+        }
+    }
+}
+
+/// Deserializes a Field of the supplied FieldType from its natural scalar value
+struct FlatFieldSeed(FieldType);
+
+impl<'de> DeserializeSeed<'de> for FlatFieldSeed {
+    type Value = Field;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Field, D::Error> {
+        match self.0 {
+            FieldType::String | FieldType::Date | FieldType::Custom | FieldType::Decimal | FieldType::IpAddr | FieldType::Cidr | FieldType::GeoPoint | FieldType::Enum(_) => deserializer.deserialize_str(FlatFieldVisitor(self.0)),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => deserializer.deserialize_string(FlatFieldVisitor(self.0)),
+            FieldType::I64 => deserializer.deserialize_i64(FlatFieldVisitor(self.0)),
+            FieldType::I32 => deserializer.deserialize_i32(FlatFieldVisitor(self.0)),
+            FieldType::U64 => deserializer.deserialize_u64(FlatFieldVisitor(self.0)),
+            FieldType::U32 => deserializer.deserialize_u32(FlatFieldVisitor(self.0)),
+            FieldType::I128 => deserializer.deserialize_i128(FlatFieldVisitor(self.0)),
+            FieldType::U128 => deserializer.deserialize_u128(FlatFieldVisitor(self.0)),
+            FieldType::Bool => deserializer.deserialize_bool(FlatFieldVisitor(self.0)),
+            FieldType::Null => deserializer.deserialize_unit(FlatFieldVisitor(self.0)),
+        }
+    }
+}
+
+struct FlatFieldVisitor(FieldType);
+
+impl FlatFieldVisitor {
+    fn integer<E: de::Error>(&self, v: i128) -> Result<Field, E> {
+        let out_of_range = || E::custom(format!("{} out of range for {:?}", v, self.0));
+        let f = match self.0 {
+            FieldType::I64 => Field::I64(i64::try_from(v).map_err(|_| out_of_range())?),
+            FieldType::I32 => Field::I32(i32::try_from(v).map_err(|_| out_of_range())?),
+            FieldType::U64 => Field::U64(u64::try_from(v).map_err(|_| out_of_range())?),
+            FieldType::U32 => Field::U32(u32::try_from(v).map_err(|_| out_of_range())?),
+            FieldType::I128 => Field::I128(v),
+            FieldType::U128 => Field::U128(u128::try_from(v).map_err(|_| out_of_range())?),
+            _ => return Err(E::custom(format!("expected {:?}, found integer {}", self.0, v))),
+        };
+        Ok(f)
+    }
+}
+
+impl<'de> Visitor<'de> for FlatFieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a {:?} value", self.0)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Field, E> {
+        self.integer(v as i128)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Field, E> {
+        self.integer(v as i128)
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Field, E> {
+        self.integer(v)
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Field, E> {
+        match (&self.0, i128::try_from(v)) {
+            (FieldType::U128, _) => Ok(Field::U128(v)),
+            (_, Ok(v)) => self.integer(v),
+            (_, Err(_)) => Err(E::custom(format!("{} out of range for {:?}", v, self.0))),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Field, E> {
+        match self.0 {
+            FieldType::Null => Ok(Field::Null),
+            _ => Err(E::custom(format!("expected {:?}, found null", self.0))),
+        }
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Field, E> {
+        match self.0 {
+            FieldType::Bool => Ok(Field::Bool(v)),
+            _ => Err(E::custom(format!("expected {:?}, found bool {}", self.0, v))),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+        match self.0 {
+            FieldType::String => Ok(Field::String(v.to_string())),
+            FieldType::Date => parse_date(v).map(Field::Date).map_err(E::custom),
+            FieldType::Custom => Field::parse(FieldType::Custom, v).map_err(E::custom),
+            FieldType::Decimal => v.parse().map(Field::Decimal).map_err(E::custom),
+            FieldType::IpAddr | FieldType::Cidr | FieldType::GeoPoint | FieldType::Enum(_) => Field::parse(self.0, v).map_err(E::custom),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => Ok(Field::Secret(crate::Secret::new(v.to_string()))),
+            _ => Err(E::custom(format!("expected {:?}, found string {}", self.0, v))),
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Field, E> {
+        match self.0 {
+            FieldType::Secret => Ok(Field::Secret(crate::Secret::new(v))),
+            _ => self.visit_str(&v),
+        }
+    }
+}
+
+/// Deserializes an Entry from a flat map of field name to natural scalar value, typed by
+/// the schema of a Table; the reverse of Entry::flat.  Returned by Table::flat_entry.
+///
+/// Fields that are not part of the Table, and values of the wrong type, are errors;
+/// optional fields may be missing or null.
+pub struct FlatEntrySeed<'a> {
+    table: &'a Table,
+    primary_key: &'a str,
+}
+
+impl<'de> DeserializeSeed<'de> for FlatEntrySeed<'_> {
+    type Value = Entry;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Entry, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+/// Deserializes an optional Field, treating null as missing
+struct OptionalFieldSeed(FieldType);
+
+impl<'de> DeserializeSeed<'de> for OptionalFieldSeed {
+    type Value = Option<Field>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<Field>, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for OptionalFieldSeed {
+    type Value = Option<Field>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a {:?} value or null", self.0)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<Field>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<Field>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<Field>, D::Error> {
+        FlatFieldSeed(self.0).deserialize(deserializer).map(Some)
+    }
+}
+
+impl<'de> Visitor<'de> for FlatEntrySeed<'_> {
+    type Value = Entry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of the fields of table {}", self.table.name)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Entry, A::Error> {
+        let mut primary_field = None;
+        let mut fields = HashMap::new();
+        while let Some(name) = map.next_key::<String>()? {
+            if name == self.primary_key {
+                primary_field = Some(map.next_value_seed(FlatFieldSeed(self.table.primary_field.clone()))?);
+                continue
+            };
+
+            let value = match self.table.fields.get(&name) {
+                Some(FieldRequirement::Required(t)) => Some(map.next_value_seed(FlatFieldSeed(t.clone()))?),
+                Some(FieldRequirement::Optional(t)) => map.next_value_seed(OptionalFieldSeed(t.clone()))?,
+                None => return Err(de::Error::custom(format!("Field name {} is not supported on table", name))),
+            };
+            if let Some(v) = value {
+                fields.insert(name, v);
+            };
+        };
+
+        let primary_field = match primary_field {
+            Some(f) => f,
+            None => return Err(de::Error::missing_field("primary key")),
+        };
+
+        for (name, requirement) in &self.table.fields {
+            if let FieldRequirement::Required(_) = requirement {
+                if !fields.contains_key(name) {
+                    return Err(de::Error::custom(format!("Missing required field {}", name)))
+                };
+            };
+        };
+
+        Ok(Entry{
+            primary_field,
+            fields,
+            last_timestamp: None,
+        })
+    }
+}
+
+impl Table {
+    /// Returns a DeserializeSeed reading an Entry of the Table from a flat map of field
+    /// name to natural scalar value, with the primary Field under the supplied key; the
+    /// reverse of Entry::flat
+    /// ```
+    /// use persistent_keystore_rs::{Field, FieldType, Table};
+    /// use serde::de::DeserializeSeed;
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::U32).unwrap()
+    ///     .add_optional_field("Updated".to_string(), FieldType::Date).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// let mut json = serde_json::Deserializer::from_str(r#"{"id": "MyEntry", "Count": 3, "Updated": null}"#);
+    /// let entry = table.flat_entry("id").deserialize(&mut json).unwrap();
+    /// assert_eq!(entry.primary_field, Field::String("MyEntry".to_string()));
+    /// assert_eq!(entry.fields["Count"], Field::U32(3));
+    /// ```
+    pub fn flat_entry<'a>(&'a self, primary_key: &'a str) -> FlatEntrySeed<'a> {
+        FlatEntrySeed{
+            table: self,
+            primary_key,
+        }
+    }
+}
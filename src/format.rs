@@ -0,0 +1,103 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::*;
+
+/// Last representable second of the year 9999; later dates cannot be written as RFC3339
+const MAX_RFC3339_SECONDS: u64 = 253_402_300_799;
+
+/// Formats a SystemTime as an RFC3339 UTC timestamp, e.g. `2018-02-14T00:28:07Z`.
+///
+/// Fractional seconds are written with nanosecond precision only when present.  Times
+/// before the Unix epoch or after the year 9999 cannot be represented in RFC3339 and are
+/// written as signed seconds since the Unix epoch instead, e.g. `-86400.5`, which
+/// parse_date reads back.
+/// ```
+/// use persistent_keystore_rs::format::format_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let date = UNIX_EPOCH + Duration::from_secs(1518568087);
+/// assert_eq!(format_date(date), "2018-02-14T00:28:07Z".to_string());
+/// assert_eq!(format_date(UNIX_EPOCH - Duration::from_millis(86400500)), "-86400.500000000".to_string());
+/// ```
+pub fn format_date(date: SystemTime) -> String {
+    let (sign, since) = match date.duration_since(UNIX_EPOCH) {
+        Ok(d) if d.as_secs() <= MAX_RFC3339_SECONDS => return format!("{}", humantime::format_rfc3339(date)),
+        Ok(d) => ("", d),
+        Err(e) => ("-", e.duration()),
+    };
+    match since.subsec_nanos() {
+        0 => format!("{}{}", sign, since.as_secs()),
+        n => format!("{}{}.{:09}", sign, since.as_secs(), n),
+    }
+}
+
+/// Parses an RFC3339 UTC timestamp, e.g. `2018-02-14T00:28:07Z`, or signed seconds since
+/// the Unix epoch as written by format_date for dates RFC3339 cannot represent.
+/// If the value is not a valid timestamp, DatabaseError::InvalidFormat is returned.
+/// ```
+/// use persistent_keystore_rs::format::{format_date, parse_date};
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let date = parse_date("2018-02-14T00:28:07.5Z").unwrap();
+/// assert_eq!(format_date(date), "2018-02-14T00:28:07.500000000Z".to_string());
+/// assert_eq!(parse_date("-86400.5").unwrap(), UNIX_EPOCH - Duration::from_millis(86400500));
+/// ```
+pub fn parse_date(value: &str) -> Result<SystemTime, DatabaseError> {
+    let trimmed = value.trim();
+    if let Some(date) = parse_epoch_seconds(trimmed) {
+        return date.ok_or_else(|| DatabaseError::InvalidFormat(format!("date {}: out of range", value)))
+    };
+    match humantime::parse_rfc3339(trimmed) {
+        Ok(d) => Ok(d),
+        Err(e) => Err(DatabaseError::InvalidFormat(format!("date {}: {}", value, e))),
+    }
+}
+
+/// Parses signed seconds since the Unix epoch with up to nine fractional digits; returns
+/// None if the value is not of that form, and Some(None) if it is but the time cannot be
+/// represented
+fn parse_epoch_seconds(value: &str) -> Option<Option<SystemTime>> {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value),
+    };
+    let (secs, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(secs) || (unsigned.contains('.') && (!digits(fraction) || fraction.len() > 9)) {
+        return None
+    };
+    let nanos = match fraction.is_empty() {
+        true => 0,
+        false => fraction.parse::<u32>().ok()? * 10u32.pow(9 - fraction.len() as u32),
+    };
+    let since = match secs.parse::<u64>() {
+        Ok(s) => Duration::new(s, nanos),
+        Err(_) => return Some(None),
+    };
+    Some(match negative {
+        true => UNIX_EPOCH.checked_sub(since),
+        false => UNIX_EPOCH.checked_add(since),
+    })
+}
+
+/// Formats a Duration as a humanized string, e.g. `1h 30m 5s`
+/// ```
+/// use persistent_keystore_rs::format::format_duration;
+/// use std::time::Duration;
+/// assert_eq!(format_duration(Duration::from_secs(5405)), "1h 30m 5s".to_string());
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}", humantime::format_duration(duration))
+}
+
+/// Parses a humanized duration, e.g. `1h 30m 5s` or `250ms`.
+/// If the value is not a valid duration, DatabaseError::InvalidFormat is returned.
+/// ```
+/// use persistent_keystore_rs::format::parse_duration;
+/// use std::time::Duration;
+/// assert_eq!(parse_duration("1h 30m 5s").unwrap(), Duration::from_secs(5405));
+/// ```
+pub fn parse_duration(value: &str) -> Result<Duration, DatabaseError> {
+    match humantime::parse_duration(value.trim()) {
+        Ok(d) => Ok(d),
+        Err(e) => Err(DatabaseError::InvalidFormat(format!("duration {}: {}", value, e))),
+    }
+}
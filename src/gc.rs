@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+use crate::structs::*;
+use crate::spill::segment_path;
+use crate::blob::blob_dir;
+use crate::errors::*;
+
+/// Returns true if the file name, relative to the name of the database file, is that of a
+/// spill segment or blob directory of some Table: `<database>.<16 hex digits>.spill` or
+/// `.blobs`
+fn is_table_file(rest: &str) -> bool {
+    match rest.split_once('.') {
+        Some((hash, ext)) => hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) && (ext == "spill" || ext == "blobs"),
+        None => false,
+    }
+}
+
+/// Removes the spill segments and blob directories beside the database file at the supplied
+/// path that belong to no Table of the Database, if last modified longer ago than the
+/// retention by the wall clock of the system; returning the paths removed.
+///
+/// Spill segments and blob directories of Tables that do not exist are left behind when a
+/// process stops after creating a Table but before saving the database.  No other file is
+/// removed, as other files sharing the name of the database, such as its `.bak` backup
+/// or the databases of a Container named after it, are not the crate's to remove.
+pub(crate) fn gc_files(path: &Path, database: &Database, retention: Duration) -> Result<Vec<PathBuf>, DatabaseError> {
+    let mut name = match path.file_name() {
+        Some(n) => n.as_encoded_bytes().to_vec(),
+        None => return Ok(Vec::new()),
+    };
+    name.push(b'.');
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut live = HashSet::new();
+    for table in database.list_tables() {
+        live.insert(segment_path(path, &table));
+        live.insert(blob_dir(path, &table));
+    };
+
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for item in std::fs::read_dir(&dir)? {
+        let item = item?;
+        let file_name = item.file_name();
+        let rest = match file_name.as_encoded_bytes().strip_prefix(name.as_slice()).and_then(|r| std::str::from_utf8(r).ok()) {
+            Some(r) => r,
+            None => continue,
+        };
+        let candidate = path.with_file_name(&file_name);
+        if !is_table_file(rest) || live.contains(&candidate) {
+            continue
+        };
+
+        let metadata = item.metadata()?;
+        let age = metadata.modified().ok().and_then(|m| now.duration_since(m).ok()).unwrap_or_default();
+        if age < retention {
+            debug!("Keeping {:?}, modified {:?} ago", candidate, age);
+            continue
+        };
+
+        match metadata.is_dir() {
+            true => std::fs::remove_dir_all(&candidate)?,
+            false => std::fs::remove_file(&candidate)?,
+        };
+        info!("Removed orphaned file {:?}", candidate);
+        removed.push(candidate);
+    };
+    removed.sort();
+    Ok(removed)
+}
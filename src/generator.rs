@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::structs::*;
+
+/// Names of the values used for the "Category" field; queries against a single category
+/// select roughly one in eight entries.
+pub const CATEGORIES: [&str; 8] = ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel"];
+
+/// Tag of the custom values returned by Generator::field for FieldType::Custom
+pub const GENERATED_TAG: &str = "generated";
+
+/// Deterministic generator of synthetic Tables and Entries, intended for benchmarks and
+/// load tests.  The same seed always produces the same sequence of entries.
+///
+/// Entries may also be generated for an arbitrary Table with entry_for, or rejected by it
+/// with invalid_entry_for, to fuzz code handling user-defined schemas.
+///
+/// Generated tables have the following schema:
+/// - primary field: FieldType::String, "key-{id}"
+/// - "Count": required FieldType::I64
+/// - "Category": required FieldType::String, one of CATEGORIES
+/// - "Created": required FieldType::Date
+/// - "Active": optional FieldType::Bool
+/// - "Notes": optional FieldType::String
+pub struct Generator {
+    state: u64,
+    notes_len: usize,
+}
+
+impl Generator {
+    /// Creates a Generator from the supplied seed
+    /// ```
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let mut generator = Generator::new(42);
+    /// ```
+    pub fn new(seed: u64) -> Self {
+        Self{
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+            notes_len: 64,
+        }
+    }
+
+    /// Sets the length of the generated "Notes" field, to control the size of each Entry
+    /// ```
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let mut generator = Generator::new(42).notes_len(1024);
+    /// ```
+    pub fn notes_len(mut self, notes_len: usize) -> Self {
+        self.notes_len = notes_len;
+        self
+    }
+
+    /// Returns a Table with the generator schema and the supplied name
+    /// ```
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let table = Generator::table("MyTable");
+    /// # assert_eq!(table.name, "MyTable".to_string());
+    /// ```
+    pub fn table(name: &str) -> Table {
+        Table::new()
+            .name(name.to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_field("Count".to_string(), FieldType::I64).unwrap()
+            .add_field("Category".to_string(), FieldType::String).unwrap()
+            .add_field("Created".to_string(), FieldType::Date).unwrap()
+            .add_optional_field("Active".to_string(), FieldType::Bool).unwrap()
+            .add_optional_field("Notes".to_string(), FieldType::String).unwrap()
+            .build().unwrap()
+    }
+
+    /// Returns the primary Field used for the Entry with the supplied id
+    /// ```
+    /// use persistent_keystore_rs::Field;
+    /// use persistent_keystore_rs::generator::Generator;
+    /// assert_eq!(Generator::key(7), Field::String("key-7".to_string()));
+    /// ```
+    pub fn key(id: u64) -> Field {
+        Field::String(format!("key-{}", id))
+    }
+
+    /// Returns an Entry with the supplied id matching the generator schema
+    /// ```
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let mut generator = Generator::new(42);
+    /// let mut table = Generator::table("MyTable");
+    /// let entry = generator.entry(0);
+    /// table.insert(entry).unwrap();
+    /// ```
+    pub fn entry(&mut self, id: u64) -> Entry {
+        let count = self.next() as i64;
+        let category = CATEGORIES[(self.next() % CATEGORIES.len() as u64) as usize];
+        let created = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000 + self.next() % 100_000_000);
+
+        let mut builder = Entry::new()
+            .set_primary_field(Generator::key(id)).unwrap()
+            .add_field("Count".to_string(), Field::I64(count)).unwrap()
+            .add_field("Category".to_string(), Field::String(category.to_string())).unwrap()
+            .add_field("Created".to_string(), Field::Date(created)).unwrap();
+
+        if self.next() & 1 == 0 {
+            builder = builder.add_field("Active".to_string(), Field::Bool(self.next() & 1 == 0)).unwrap();
+        };
+
+        if self.notes_len > 0 {
+            let mut notes = String::with_capacity(self.notes_len);
+            while notes.len() < self.notes_len {
+                notes.push((b'a' + (self.next() % 26) as u8) as char);
+            };
+            builder = builder.add_field("Notes".to_string(), Field::String(notes)).unwrap();
+        };
+
+        builder.build().unwrap()
+    }
+
+    /// Returns count Entries with ids 0..count
+    /// ```
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let entries = Generator::new(42).entries(100);
+    /// # assert_eq!(entries.len(), 100);
+    /// ```
+    pub fn entries(&mut self, count: u64) -> Vec<Entry> {
+        let mut results = Vec::new();
+        for id in 0..count {
+            results.push(self.entry(id));
+        };
+        results
+    }
+
+    /// Returns a random Field of the supplied FieldType.  Custom values are 8 random bytes
+    /// tagged GENERATED_TAG, so can only be inserted once a CustomCodec accepting them is
+    /// registered for the tag.  Enum values are one of the declared variants, of which
+    /// there must be at least one.
+    /// ```
+    /// use persistent_keystore_rs::FieldType;
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let field = Generator::new(42).field(FieldType::U32);
+    /// assert_eq!(field.get_type(), FieldType::U32);
+    /// ```
+    pub fn field(&mut self, field_type: FieldType) -> Field {
+        match field_type {
+            FieldType::String => {
+                let len = 1 + (self.next() % 16) as usize;
+                let mut value = String::with_capacity(len);
+                while value.len() < len {
+                    value.push((b'a' + (self.next() % 26) as u8) as char);
+                };
+                Field::String(value)
+            },
+            FieldType::I64 => Field::I64(self.next() as i64),
+            FieldType::I32 => Field::I32(self.next() as i32),
+            FieldType::U64 => Field::U64(self.next()),
+            FieldType::U32 => Field::U32(self.next() as u32),
+            FieldType::Date => Field::Date(SystemTime::UNIX_EPOCH + Duration::from_secs(self.next() % 4_000_000_000)),
+            FieldType::Bool => Field::Bool(self.next() & 1 == 0),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => match self.field(FieldType::String) {
+                Field::String(value) => Field::Secret(crate::Secret::new(value)),
+                _ => unreachable!("FieldType::String generates Field::String"),
+            },
+            FieldType::Custom => Field::Custom{
+                tag: GENERATED_TAG.to_string(),
+                bytes: self.next().to_be_bytes().to_vec(),
+            },
+            FieldType::I128 => Field::I128(((self.next() as u128) << 64 | self.next() as u128) as i128),
+            FieldType::U128 => Field::U128((self.next() as u128) << 64 | self.next() as u128),
+            FieldType::Decimal => match crate::Decimal::new(self.next() as i64 as i128, 2) {
+                Ok(d) => Field::Decimal(d),
+                Err(_) => unreachable!("a scale of 2 is within MAX_DECIMAL_SCALE"),
+            },
+            FieldType::IpAddr => Field::IpAddr(std::net::Ipv4Addr::from(self.next() as u32).into()),
+            FieldType::Cidr => match crate::Cidr::new(std::net::Ipv4Addr::from(self.next() as u32).into(), 8 + (self.next() % 25) as u8) {
+                Ok(c) => Field::Cidr(c),
+                Err(_) => unreachable!("prefixes of 8 to 32 bits fit an IPv4 address"),
+            },
+            FieldType::GeoPoint => {
+                let lat = (self.next() % 1_800_001) as f64 / 10_000.0 - 90.0;
+                let lon = (self.next() % 3_600_001) as f64 / 10_000.0 - 180.0;
+                match crate::GeoPoint::new(lat, lon) {
+                    Ok(p) => Field::GeoPoint(p),
+                    Err(_) => unreachable!("coordinates are generated within range"),
+                }
+            },
+            FieldType::Enum(variants) => Field::Enum(variants[(self.next() % variants.len() as u64) as usize].clone()),
+            FieldType::Null => Field::Null,
+        }
+    }
+
+    /// Returns a random Entry valid for the schema of the supplied Table; every required
+    /// field is set, and each optional field is set half of the time.
+    ///
+    /// References and other constraints on the values of the Table are not considered,
+    /// and primary Fields are random, so they may repeat.
+    /// ```
+    /// use persistent_keystore_rs::{FieldType, Table};
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let mut table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::U64).unwrap()
+    ///     .add_field("Name".to_string(), FieldType::String).unwrap()
+    ///     .add_optional_field("Updated".to_string(), FieldType::Date).unwrap()
+    ///     .build().unwrap();
+    /// let entry = Generator::new(42).entry_for(&table);
+    /// table.insert(entry).unwrap();
+    /// ```
+    pub fn entry_for(&mut self, table: &Table) -> Entry {
+        let mut names: Vec<&String> = table.fields.keys().collect();
+        names.sort();
+
+        let mut fields = HashMap::new();
+        for name in names {
+            let (field_type, required) = match &table.fields[name] {
+                FieldRequirement::Required(t) => (t.clone(), true),
+                FieldRequirement::Optional(t) => (t.clone(), false),
+            };
+            if !required && self.next() & 1 == 0 {
+                continue
+            };
+            fields.insert(name.clone(), self.field(field_type));
+        };
+
+        Entry{
+            primary_field: self.field(table.primary_field.clone()),
+            fields,
+            last_timestamp: None,
+        }
+    }
+
+    /// Returns a random Entry that the supplied Table rejects, with one of: a field not
+    /// in the schema, a field or primary Field of the wrong type, or a missing required
+    /// field
+    /// ```
+    /// use persistent_keystore_rs::{FieldType, Table};
+    /// use persistent_keystore_rs::generator::Generator;
+    /// let mut table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::U64).unwrap()
+    ///     .add_field("Name".to_string(), FieldType::String).unwrap()
+    ///     .build().unwrap();
+    /// let entry = Generator::new(42).invalid_entry_for(&table);
+    /// assert!(table.insert(entry).is_err());
+    /// ```
+    pub fn invalid_entry_for(&mut self, table: &Table) -> Entry {
+        let mut entry = self.entry_for(table);
+
+        let mut required: Vec<&String> = table.fields.iter()
+            .filter(|(_, r)| matches!(r, FieldRequirement::Required(_)))
+            .map(|(name, _)| name)
+            .collect();
+        required.sort();
+
+        match self.next() % 4 {
+            0 if !required.is_empty() => {
+                let name = required[(self.next() % required.len() as u64) as usize];
+                entry.fields.remove(name);
+            },
+            1 if !entry.fields.is_empty() => {
+                let mut names: Vec<String> = entry.fields.keys().cloned().collect();
+                names.sort();
+                let name = names.swap_remove((self.next() % names.len() as u64) as usize);
+                let field = self.mismatched_field(entry.fields[&name].get_type());
+                entry.fields.insert(name, field);
+            },
+            2 => {
+                entry.primary_field = self.mismatched_field(table.primary_field.clone());
+            },
+            _ => {
+                let mut name = format!("Unsupported{}", self.next() % 1000);
+                while table.fields.contains_key(&name) {
+                    name.push('_');
+                };
+                entry.fields.insert(name, Field::Bool(true));
+            },
+        };
+        entry
+    }
+
+    /// Returns a random Field of a type other than the supplied FieldType
+    fn mismatched_field(&mut self, field_type: FieldType) -> Field {
+        let other = match field_type {
+            FieldType::String => FieldType::I64,
+            _ => FieldType::String,
+        };
+        self.field(other)
+    }
+
+    /// xorshift64*
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Entry {
+    /// Returns a random Entry valid for the schema of the supplied Table, generated from
+    /// the seed; see Generator::entry_for
+    /// ```
+    /// use persistent_keystore_rs::{Entry, FieldType, Table};
+    /// let mut table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I32).unwrap()
+    ///     .build().unwrap();
+    /// table.insert(Entry::arbitrary_for(&table, 42)).unwrap();
+    /// ```
+    pub fn arbitrary_for(table: &Table, seed: u64) -> Entry {
+        Generator::new(seed).entry_for(table)
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::str::FromStr;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::errors::*;
+
+/// Mean radius of the Earth in meters, used for distances between GeoPoints
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Units of a degree in which GeoPoints are stored
+const UNITS_PER_DEGREE: f64 = 10_000_000.0;
+
+/// Latitude and longitude in degrees; the value of Field::GeoPoint, and the center of
+/// Condition::WithinRadius.
+///
+/// Coordinates are stored to the nearest ten-millionth of a degree, about a centimeter, so
+/// points are compared and hashed exactly.  Points are ordered by latitude, then longitude.
+/// ```
+/// use persistent_keystore_rs::GeoPoint;
+/// let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+/// let paris: GeoPoint = "48.8566,2.3522".parse().unwrap();
+/// assert_eq!(london.to_string(), "51.5074,-0.1278");
+/// assert!((london.distance_to(&paris) - 343_556.0).abs() < 1_000.0);
+/// assert!(GeoPoint::new(91.0, 0.0).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawGeoPoint", into = "RawGeoPoint")]
+pub struct GeoPoint {
+    lat: i32,
+    lon: i32,
+}
+
+/// Serialized form of a GeoPoint, checked when read
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct RawGeoPoint {
+    lat: i32,
+    lon: i32,
+}
+
+impl TryFrom<RawGeoPoint> for GeoPoint {
+    type Error = DatabaseError;
+
+    fn try_from(raw: RawGeoPoint) -> Result<Self, Self::Error> {
+        GeoPoint::new(raw.lat as f64 / UNITS_PER_DEGREE, raw.lon as f64 / UNITS_PER_DEGREE)
+    }
+}
+
+impl From<GeoPoint> for RawGeoPoint {
+    fn from(p: GeoPoint) -> Self {
+        RawGeoPoint{
+            lat: p.lat,
+            lon: p.lon,
+        }
+    }
+}
+
+impl GeoPoint {
+    /// Returns the point at the latitude and longitude in degrees.  If the latitude is
+    /// outside -90 to 90, or the longitude outside -180 to 180, DatabaseError::InvalidFormat
+    /// is returned.
+    pub fn new(lat: f64, lon: f64) -> Result<GeoPoint, DatabaseError> {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(DatabaseError::InvalidFormat(format!("GeoPoint {},{}", lat, lon)))
+        };
+        Ok(GeoPoint{
+            lat: (lat * UNITS_PER_DEGREE).round() as i32,
+            lon: (lon * UNITS_PER_DEGREE).round() as i32,
+        })
+    }
+
+    /// Returns the latitude in degrees
+    pub fn lat(&self) -> f64 {
+        self.lat as f64 / UNITS_PER_DEGREE
+    }
+
+    /// Returns the longitude in degrees
+    pub fn lon(&self) -> f64 {
+        self.lon as f64 / UNITS_PER_DEGREE
+    }
+
+    /// Returns the great-circle distance to the other point in meters, by the haversine
+    /// formula over a sphere of EARTH_RADIUS_METERS
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat().to_radians(), other.lat().to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon() - self.lon()).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// Writes a coordinate in degrees without trailing zeros
+fn write_degrees(f: &mut fmt::Formatter, units: i32) -> fmt::Result {
+    let sign = if units < 0 { "-" } else { "" };
+    let units = units.unsigned_abs();
+    let fraction = format!("{:07}", units % UNITS_PER_DEGREE as u32);
+    let fraction = fraction.trim_end_matches('0');
+    match fraction.is_empty() {
+        true => write!(f, "{}{}", sign, units / UNITS_PER_DEGREE as u32),
+        false => write!(f, "{}{}.{}", sign, units / UNITS_PER_DEGREE as u32, fraction),
+    }
+}
+
+/// Written as `lat,lon` in degrees, such as `51.5074,-0.1278`
+impl fmt::Display for GeoPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_degrees(f, self.lat)?;
+        write!(f, ",")?;
+        write_degrees(f, self.lon)
+    }
+}
+
+/// Parses a point written as `lat,lon` in degrees
+impl FromStr for GeoPoint {
+    type Err = DatabaseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || DatabaseError::InvalidFormat(format!("GeoPoint {}", value));
+        let (lat, lon) = value.split_once(',').ok_or_else(invalid)?;
+        let lat: f64 = lat.trim().parse().map_err(|_| invalid())?;
+        let lon: f64 = lon.trim().parse().map_err(|_| invalid())?;
+        GeoPoint::new(lat, lon)
+    }
+}
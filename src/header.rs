@@ -0,0 +1,114 @@
+use std::io::{self, Read};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::errors::*;
+
+/// Marks the header written ahead of the compressed database in a database file.  The
+/// header holds the magic, the length of the FileInfo as a u32 little endian and the
+/// serialized FileInfo; files written by version 0.0.8 and earlier have no header and start
+/// with the compressed database, which is read by the legacy module.
+pub(crate) const HEADER_MAGIC: &[u8; 8] = b"PKSHEAD1";
+
+/// Version of the format of the database behind the header written by this build; raised
+/// whenever the serialized layout of Database, Table or Entry changes.  Files of a later
+/// version are refused rather than misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// What wrote a database file, recorded in its header each time it is saved; see
+/// Client::file_info
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileInfo {
+    /// Version of the crate that wrote the file
+    pub crate_version: String,
+    /// Name of the host that wrote the file, if it could be determined
+    pub host: Option<String>,
+    /// Time the file was written, by the Clock of the Client that wrote it
+    pub written_at: SystemTime,
+    /// Version of the format of the database behind the header; see FORMAT_VERSION
+    pub format_version: u32,
+}
+
+impl FileInfo {
+    /// Returns the FileInfo of a file written now by this process
+    pub(crate) fn new(written_at: SystemTime) -> Self {
+        FileInfo{
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            host: host().clone(),
+            written_at,
+            format_version: FORMAT_VERSION,
+        }
+    }
+
+    /// Returns an error if the database behind the header is of a format later than this
+    /// build reads
+    pub(crate) fn check_format(&self) -> Result<(), DatabaseError> {
+        match self.format_version > FORMAT_VERSION {
+            true => Err(DatabaseError::InvalidFormat(format!("database file format {} written by version {} is newer than format {} read by this build", self.format_version, self.crate_version, FORMAT_VERSION))),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Returns the name of this host from the environment or, failing that, the files the
+/// operating system keeps it in; looked up once per process
+fn host() -> &'static Option<String> {
+    static HOST: OnceLock<Option<String>> = OnceLock::new();
+    HOST.get_or_init(|| {
+        ["HOSTNAME", "COMPUTERNAME"].iter()
+            .filter_map(|v| std::env::var(v).ok())
+            .chain(["/proc/sys/kernel/hostname", "/etc/hostname"].iter().filter_map(|p| std::fs::read_to_string(p).ok()))
+            .map(|h| h.trim().to_string())
+            .find(|h| !h.is_empty())
+    })
+}
+
+/// Returns the contents of a database file holding the compressed database behind a
+/// header recording the FileInfo
+pub(crate) fn prepend(info: &FileInfo, compressed: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+    let header = bincode::serialize(info)?;
+    let mut contents = Vec::with_capacity(HEADER_MAGIC.len() + 4 + header.len() + compressed.len());
+    contents.extend_from_slice(HEADER_MAGIC);
+    contents.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    contents.extend_from_slice(&header);
+    contents.extend_from_slice(compressed);
+    Ok(contents)
+}
+
+/// Splits the contents of a database file into the FileInfo of its header, if it has one,
+/// and the compressed database
+pub(crate) fn split(contents: &[u8]) -> Result<(Option<FileInfo>, &[u8]), DatabaseError> {
+    let Some(rest) = contents.strip_prefix(HEADER_MAGIC.as_slice()) else {
+        return Ok((None, contents))
+    };
+    let truncated = || DatabaseError::InvalidFormat("truncated file header".to_string());
+    let (length, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+    let length = u32::from_le_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(truncated())
+    };
+    let (header, compressed) = rest.split_at(length);
+    Ok((Some(bincode::deserialize(header)?), compressed))
+}
+
+/// Reads the FileInfo from the header at the start of the reader, without reading the
+/// compressed database; None is returned if the file has no header
+pub(crate) fn read(reader: &mut dyn Read) -> Result<Option<FileInfo>, DatabaseError> {
+    let mut prefix = [0u8; HEADER_MAGIC.len() + 4];
+    match reader.read_exact(&mut prefix) {
+        Ok(()) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if !prefix.starts_with(HEADER_MAGIC) {
+        return Ok(None)
+    };
+    let mut header = Vec::new();
+    let length = u32::from_le_bytes([prefix[8], prefix[9], prefix[10], prefix[11]]) as u64;
+    reader.take(length).read_to_end(&mut header)?;
+    if header.len() as u64 != length {
+        return Err(DatabaseError::InvalidFormat("truncated file header".to_string()))
+    };
+    Ok(Some(bincode::deserialize(&header)?))
+}
@@ -0,0 +1,49 @@
+use std::collections::{HashSet, VecDeque};
+use serde_derive::{Serialize, Deserialize};
+
+/// Number of idempotency keys a Client remembers by default; see
+/// ClientBuilder::idempotency_capacity
+pub const DEFAULT_IDEMPOTENCY_CAPACITY: usize = 10_000;
+
+/// Idempotency keys of the most recent writes, oldest first; saved with the Database so
+/// that writes retried after a restart are still recognized
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "VecDeque<String>", into = "VecDeque<String>")]
+pub(crate) struct IdempotencyLog {
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl From<VecDeque<String>> for IdempotencyLog {
+    fn from(order: VecDeque<String>) -> Self {
+        IdempotencyLog{
+            seen: order.iter().cloned().collect(),
+            order,
+        }
+    }
+}
+
+impl From<IdempotencyLog> for VecDeque<String> {
+    fn from(log: IdempotencyLog) -> Self {
+        log.order
+    }
+}
+
+impl IdempotencyLog {
+    /// Returns true if a write with the key was recorded and not since forgotten
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.seen.contains(key)
+    }
+
+    /// Records a write with the key, forgetting the oldest keys beyond capacity
+    pub(crate) fn record(&mut self, key: String, capacity: usize) {
+        if self.seen.insert(key.clone()) {
+            self.order.push_back(key);
+        };
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            };
+        };
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Write as _;
+use std::hash::{BuildHasher, Hasher};
+use std::time::SystemTime;
+
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::shared_static;
+
+/// Source of the identifiers a Client generates as the primary fields of the entries it
+/// creates, such as the ids of Sessions; set with ClientBuilder::id_generator
+pub trait IdGenerator: Send + Sync {
+    /// Returns a new identifier, distinct from every other returned by the generator
+    fn next_id(&self) -> String;
+}
+
+/// Generates unguessable identifiers of 128 bits from keyed hashes, whose keys are seeded
+/// from the randomness of the operating system, rendered as 32 hex digits; the default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn next_id(&self) -> String {
+        shared_static! {
+            static COUNTER: AtomicU64 = AtomicU64::new(0);
+        }
+        let state = RandomState::new();
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos();
+
+        let mut id = String::with_capacity(32);
+        for part in 0..2u8 {
+            let mut hasher = state.build_hasher();
+            hasher.write_u8(part);
+            hasher.write_u64(count);
+            hasher.write_u128(nanos);
+            let _ = write!(id, "{:016x}", hasher.finish());
+        };
+        id
+    }
+}
+
+/// Generates identifiers counting up from a starting value, rendered as 32 hex digits like
+/// those of RandomIdGenerator; so that tests creating entries with generated identifiers
+/// produce the same databases on every run.  The identifiers are guessable, so are not
+/// suitable for Sessions outside of tests.
+/// ```
+/// use persistent_keystore_rs::{IdGenerator, SequentialIdGenerator};
+/// let ids = SequentialIdGenerator::new(1);
+/// assert_eq!(ids.next_id(), "00000000000000000000000000000001");
+/// assert_eq!(ids.next_id(), "00000000000000000000000000000002");
+/// ```
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new(start: u64) -> Self {
+        SequentialIdGenerator{
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> String {
+        format!("{:032x}", self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::structs::*;
+use crate::condition::*;
+
+/// Secondary index over a tuple of fields of a Table, declared with TableBuilder::add_index.
+///
+/// Entries are indexed by the values of every leading run of the fields, so that criteria
+/// covering only the first fields of the index can use it as well.  An Entry is only indexed
+/// under the runs of fields it has set.
+#[derive(Clone)]
+pub(crate) struct CompoundIndex {
+    fields: Vec<String>,
+    levels: Vec<HashMap<Vec<Field>, HashSet<Field, EntryHasher>>>,
+}
+
+impl CompoundIndex {
+    pub(crate) fn new(fields: Vec<String>) -> Self {
+        CompoundIndex{
+            levels: vec![HashMap::new(); fields.len()],
+            fields,
+        }
+    }
+
+    pub(crate) fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Returns the values of each leading run of the fields set on the Entry, shortest first
+    fn runs(&self, entry: &Entry) -> Vec<Vec<Field>> {
+        let mut runs = Vec::new();
+        let mut values = Vec::new();
+        for name in &self.fields {
+            match entry.fields.get(name) {
+                Some(v) => values.push(v.clone()),
+                None => break,
+            };
+            runs.push(values.clone());
+        };
+        runs
+    }
+
+    pub(crate) fn insert(&mut self, entry: &Entry) {
+        for (level, values) in self.runs(entry).into_iter().enumerate() {
+            self.levels[level].entry(values).or_default().insert(entry.primary_field.clone());
+        };
+    }
+
+    pub(crate) fn remove(&mut self, entry: &Entry) {
+        for (level, values) in self.runs(entry).into_iter().enumerate() {
+            if let Some(keys) = self.levels[level].get_mut(&values) {
+                keys.remove(&entry.primary_field);
+                if keys.is_empty() {
+                    self.levels[level].remove(&values);
+                };
+            };
+        };
+    }
+
+    /// Returns the values of the longest leading run of the fields with an Equals Condition,
+    /// if any
+    pub(crate) fn covered(&self, conditions: &HashMap<String, Condition>) -> Vec<Field> {
+        let mut values = Vec::new();
+        for name in &self.fields {
+            match conditions.get(name) {
+                Some(Condition::Equals(v)) => values.push(v.clone()),
+                _ => break,
+            };
+        };
+        values
+    }
+
+    /// Returns the primary Fields of the Entries whose leading fields equal the values
+    pub(crate) fn get(&self, values: &[Field]) -> Vec<Field> {
+        match values.len().checked_sub(1).and_then(|level| self.levels[level].get(values)) {
+            Some(keys) => keys.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns true if the Entry is indexed under every leading run of fields it has set
+    pub(crate) fn contains(&self, entry: &Entry) -> bool {
+        self.runs(entry).iter().enumerate()
+            .all(|(level, values)| self.levels[level].get(values).is_some_and(|k| k.contains(&entry.primary_field)))
+    }
+}
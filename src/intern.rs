@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde_derive::{Serialize, Deserialize};
+use tracing::trace;
+
+use crate::structs::*;
+
+/// String value shared by one or more fields
+#[derive(Clone, Serialize, Deserialize)]
+struct InternedValue {
+    value: Arc<str>,
+    count: usize,
+}
+
+/// String values of a Table configured with TableBuilder::deduplicate_above, stored once
+/// however many Entries hold them.
+///
+/// The Entry keeps the id of each shared value in place of the value itself; values are
+/// saved once in the database file and removed when the last field holding them is.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct StringPool {
+    #[serde(serialize_with = "crate::ordered::map")]
+    values: HashMap<u64, InternedValue>,
+    #[serde(serialize_with = "crate::ordered::nested_map")]
+    refs: HashMap<Field, HashMap<String, u64>, EntryHasher>,
+    next_id: u64,
+    #[serde(skip)]
+    ids: HashMap<Arc<str>, u64>,
+}
+
+impl StringPool {
+    /// Returns the number of distinct values held
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Rebuilds the lookup of values to their id; used after the pool is loaded from disk
+    pub(crate) fn rebuild(&mut self) {
+        self.ids = self.values.iter()
+            .map(|(id, v)| (v.value.clone(), *id))
+            .collect();
+    }
+
+    /// Returns the id of the value, adding it to the pool if it is not held
+    fn acquire(&mut self, value: &str) -> u64 {
+        if let Some(id) = self.ids.get(value) {
+            if let Some(v) = self.values.get_mut(id) {
+                v.count += 1;
+                return *id
+            };
+        };
+
+        let id = self.next_id;
+        let value: Arc<str> = Arc::from(value);
+        self.next_id += 1;
+        self.ids.insert(value.clone(), id);
+        self.values.insert(id, InternedValue{
+            value,
+            count: 1,
+        });
+        id
+    }
+
+    /// Drops a field holding the value, removing the value once no field holds it
+    fn release(&mut self, id: u64) {
+        if let Some(v) = self.values.get_mut(&id) {
+            v.count -= 1;
+            if v.count == 0 {
+                if let Some(v) = self.values.remove(&id) {
+                    self.ids.remove(&v.value);
+                };
+            };
+        };
+    }
+
+    /// Moves every String field of the Entry longer than min_len to the pool, replacing the
+    /// references previously held for the Entry
+    pub(crate) fn intern(&mut self, entry: &mut Entry, min_len: usize) {
+        let mut refs = HashMap::new();
+        for (name, field) in &entry.fields {
+            if let Field::String(v) = field {
+                if v.len() > min_len {
+                    refs.insert(name.clone(), self.acquire(v));
+                };
+            };
+        };
+
+        for name in refs.keys() {
+            entry.fields.remove(name);
+        };
+        self.remove(&entry.primary_field);
+        if !refs.is_empty() {
+            trace!("Deduplicated {} fields of entry {}", refs.len(), entry.primary_field);
+            self.refs.insert(entry.primary_field.clone(), refs);
+        };
+    }
+
+    /// Returns the Entry with its shared values restored
+    pub(crate) fn resolve(&self, entry: Arc<Entry>) -> Arc<Entry> {
+        let refs = match self.refs.get(&entry.primary_field) {
+            Some(r) => r,
+            None => return entry,
+        };
+
+        let mut resolved = entry.as_ref().clone();
+        for (name, id) in refs {
+            if let Some(v) = self.values.get(id) {
+                resolved.fields.insert(name.clone(), Field::String(v.value.to_string()));
+            };
+        };
+        Arc::new(resolved)
+    }
+
+    /// Returns the primary Field and shared value of the supplied field of every Entry
+    pub(crate) fn project(&self, field: &String) -> Vec<(Field, Field)> {
+        let mut results = Vec::new();
+        for (key, refs) in &self.refs {
+            if let Some(v) = refs.get(field).and_then(|id| self.values.get(id)) {
+                results.push((key.clone(), Field::String(v.value.to_string())));
+            };
+        };
+        results
+    }
+
+    /// Removes the references of the Entry, dropping values no longer held by any field
+    pub(crate) fn remove(&mut self, key: &Field) {
+        if let Some(refs) = self.refs.remove(key) {
+            for id in refs.into_values() {
+                self.release(id);
+            };
+        };
+    }
+
+    /// Returns a description of every reference to a missing value, and of every value
+    /// whose count does not match its references, along with its key
+    pub(crate) fn verify(&self) -> Vec<(Option<Field>, String)> {
+        let mut problems = Vec::new();
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for (key, refs) in &self.refs {
+            for (name, id) in refs {
+                match self.values.contains_key(id) {
+                    true => *counts.entry(*id).or_default() += 1,
+                    false => problems.push((Some(key.clone()), format!("deduplicated field {} references missing value {}", name, id))),
+                };
+            };
+        };
+
+        for (id, v) in &self.values {
+            let count = counts.get(id).copied().unwrap_or(0);
+            if count != v.count {
+                problems.push((None, format!("deduplicated value {} is held by {} fields but counted {}", id, count, v.count)));
+            };
+        };
+        problems
+    }
+}
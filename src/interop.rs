@@ -0,0 +1,88 @@
+//! Conversions between Field::Date and the date types of the chrono and time crates,
+//! enabled by the `chrono` and `time` features respectively.
+use std::time::{Duration, SystemTime};
+
+use crate::structs::*;
+use crate::errors::*;
+use crate::condition::AgeDuration;
+
+/// ```
+/// use persistent_keystore_rs::Field;
+/// use chrono::{DateTime, TimeZone, Utc};
+/// let date = Utc.timestamp_opt(1518568087, 0).unwrap();
+/// let field = Field::from(date);
+/// assert_eq!(DateTime::<Utc>::try_from(field).unwrap(), date);
+/// ```
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Field {
+    fn from(date: chrono::DateTime<chrono::Utc>) -> Self {
+        Field::Date(SystemTime::from(date))
+    }
+}
+
+/// If the Field is not a Field::Date, DatabaseError::MismatchedFieldType is returned
+#[cfg(feature = "chrono")]
+impl TryFrom<Field> for chrono::DateTime<chrono::Utc> {
+    type Error = DatabaseError;
+
+    fn try_from(field: Field) -> Result<Self, Self::Error> {
+        match field {
+            Field::Date(d) => Ok(chrono::DateTime::<chrono::Utc>::from(d)),
+            _ => Err(DatabaseError::MismatchedFieldType),
+        }
+    }
+}
+
+/// ```
+/// use persistent_keystore_rs::Condition;
+/// use std::time::Duration;
+/// let condition = Condition::older_than(chrono::Duration::hours(1));
+/// assert_eq!(condition, Condition::OlderThan(Duration::from_secs(3600)));
+/// ```
+#[cfg(feature = "chrono")]
+impl AgeDuration for chrono::Duration {
+    fn to_age(&self) -> Duration {
+        self.to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+/// ```
+/// use persistent_keystore_rs::Field;
+/// use time::OffsetDateTime;
+/// let date = OffsetDateTime::from_unix_timestamp(1518568087).unwrap();
+/// let field = Field::from(date);
+/// assert_eq!(OffsetDateTime::try_from(field).unwrap(), date);
+/// ```
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Field {
+    fn from(date: time::OffsetDateTime) -> Self {
+        Field::Date(SystemTime::from(date))
+    }
+}
+
+/// The returned OffsetDateTime is in UTC.
+/// If the Field is not a Field::Date, DatabaseError::MismatchedFieldType is returned
+#[cfg(feature = "time")]
+impl TryFrom<Field> for time::OffsetDateTime {
+    type Error = DatabaseError;
+
+    fn try_from(field: Field) -> Result<Self, Self::Error> {
+        match field {
+            Field::Date(d) => Ok(time::OffsetDateTime::from(d)),
+            _ => Err(DatabaseError::MismatchedFieldType),
+        }
+    }
+}
+
+/// ```
+/// use persistent_keystore_rs::Condition;
+/// use std::time::Duration;
+/// let condition = Condition::newer_than(time::Duration::minutes(5));
+/// assert_eq!(condition, Condition::NewerThan(Duration::from_secs(300)));
+/// ```
+#[cfg(feature = "time")]
+impl AgeDuration for time::Duration {
+    fn to_age(&self) -> Duration {
+        Duration::try_from(*self).unwrap_or(Duration::ZERO)
+    }
+}
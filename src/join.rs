@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::structs::*;
+use crate::errors::*;
+use crate::condition::*;
+
+/// Which Entries of the left Table are returned by DatabaseClient::lookup_join
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Every matching left Entry is returned, with right set to None if nothing matched
+    Left,
+    /// Only left Entries with a matching right Entry are returned
+    Inner,
+}
+
+/// Options controlling DatabaseClient::lookup_join
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinOptions {
+    /// Which left Entries are returned; defaults to JoinKind::Left
+    pub kind: JoinKind,
+    /// Conditions the left Entries must meet; defaults to none, joining every Entry
+    pub conditions: HashMap<String, Condition>,
+}
+
+impl Default for JoinOptions {
+    fn default() -> Self {
+        Self{
+            kind: JoinKind::Left,
+            conditions: HashMap::new(),
+        }
+    }
+}
+
+/// Entry of the left Table along with the Entry of the right Table whose primary Field
+/// matches the value of the join field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinedEntry {
+    pub left: Arc<Entry>,
+    pub right: Option<Arc<Entry>>,
+}
+
+/// Joins the Entries of left meeting the options to the Entry of right whose primary Field
+/// equals the value of left_field.
+/// If left_field is not part of left, DatabaseError::UnsupportedField is returned; if its
+/// type differs from the primary field of right, DatabaseError::MismatchedFieldType is returned.
+pub(crate) fn lookup_join(left: &Table, right: &Table, left_field: &String, options: &JoinOptions, now: SystemTime) -> Result<Vec<JoinedEntry>, DatabaseError> {
+    match left.fields.get(left_field) {
+        Some(requirement) => {
+            if requirement.unwrap() != right.primary_field {
+                return Err(DatabaseError::MismatchedFieldType)
+            };
+        },
+        None => return Err(DatabaseError::UnsupportedField(left_field.clone())),
+    };
+
+    let mut results = Vec::new();
+    for entry in left.filter(&options.conditions, now)? {
+        let matched = match entry.fields.get(left_field) {
+            Some(key) => right.get(key).ok(),
+            None => None,
+        };
+
+        if matched.is_none() && options.kind == JoinKind::Inner {
+            continue
+        };
+
+        results.push(JoinedEntry{
+            left: entry,
+            right: matched,
+        });
+    };
+    Ok(results)
+}
@@ -0,0 +1,322 @@
+//! Segmented format of the serialized database, in which each Table is serialized on its
+//! own; see ClientBuilder::segmented.  A Database read from the segmented format holds each
+//! Table as its serialized segment until the Table is first accessed, so opening the
+//! database costs in proportion to the Tables used rather than to every Table it holds.
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use serde::{Deserializer, Serializer};
+use serde::ser::Error;
+use serde_derive::{Serialize, Deserialize};
+use tracing::{debug, error};
+
+use crate::clock::Timestamp;
+use crate::errors::*;
+use crate::idempotency::IdempotencyLog;
+use crate::secret;
+use crate::structs::{FieldType, Reference, Table};
+use crate::system::SchemaChange;
+
+/// Marks the segmented format at the start of the serialized database; never the start of
+/// the standard format, which begins with the tag of an Option
+pub(crate) const SEGMENTED_MAGIC: &[u8; 8] = b"PKSSEGM1";
+
+/// Returns true if the serialized database is in the segmented format
+pub(crate) fn is_segmented(uncompressed: &[u8]) -> bool {
+    uncompressed.starts_with(SEGMENTED_MAGIC)
+}
+
+/// Serialized Table, with the parts of its schema needed by the Tables referencing it
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Segment {
+    primary_field: FieldType,
+    #[serde(serialize_with = "crate::ordered::map")]
+    references: HashMap<String, Reference>,
+    entries: usize,
+    bytes: Vec<u8>,
+}
+
+impl Segment {
+    fn new(table: &Table) -> Result<Self, DatabaseError> {
+        Ok(Segment{
+            primary_field: table.primary_field.clone(),
+            references: table.references.clone(),
+            entries: table.len(),
+            bytes: bincode::serialize(table)?,
+        })
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        secret::wipe(&mut self.bytes);
+    }
+}
+
+/// Table of a Database, held as its Segment until first accessed if the Database was read
+/// from the segmented format.  The Table is shared by clones of the Database, such as
+/// Snapshots, and copied by the first of them to modify it.
+#[derive(Clone)]
+pub(crate) struct Slot {
+    table: OnceLock<Arc<Table>>,
+    /// Segment the Table was read from; kept until the Table is modified, so that saving
+    /// does not serialize the Table again
+    segment: Option<Arc<Segment>>,
+    /// Time to rebase the Table to once read; see Database::rebase
+    rebase: Option<Timestamp>,
+    /// Database file to attach the Table to once read; see Database::attach_files
+    files: Option<PathBuf>,
+}
+
+impl Slot {
+    fn pending(segment: Segment) -> Self {
+        Slot{
+            table: OnceLock::new(),
+            segment: Some(Arc::new(segment)),
+            rebase: None,
+            files: None,
+        }
+    }
+
+    /// Returns true if the Table has been read from its Segment
+    pub(crate) fn is_loaded(&self) -> bool {
+        self.table.get().is_some()
+    }
+
+    /// Returns true if the Table is shared with other, as by clones of a Database neither
+    /// of which has modified or replaced it
+    pub(crate) fn shares(&self, other: &Slot) -> bool {
+        let tables = match (self.table.get(), other.table.get()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+        let segments = match (&self.segment, &other.segment) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+        tables || segments
+    }
+
+    /// Returns the Table if it has been read from its Segment
+    pub(crate) fn loaded(&self) -> Option<&Table> {
+        self.table.get().map(|t| &**t)
+    }
+
+    /// Returns the Table if it has been read from its Segment, for modification
+    pub(crate) fn loaded_mut(&mut self) -> Option<&mut Table> {
+        let table = self.table.get_mut()?;
+        self.segment = None;
+        Some(Arc::make_mut(table))
+    }
+
+    /// Returns the Table, reading it from its Segment on first access
+    pub(crate) fn get(&self) -> Result<&Table, DatabaseError> {
+        if let Some(table) = self.table.get() {
+            return Ok(table)
+        };
+        let table = self.load()?;
+        Ok(self.table.get_or_init(|| Arc::new(table)))
+    }
+
+    /// Returns the Table for modification, reading it from its Segment on first access and
+    /// copying it if it is shared with a clone of the Database
+    pub(crate) fn get_mut(&mut self) -> Result<&mut Table, DatabaseError> {
+        if self.table.get().is_none() {
+            self.table = OnceLock::from(Arc::new(self.load()?));
+        };
+        self.segment = None;
+        match self.table.get_mut() {
+            Some(table) => Ok(Arc::make_mut(table)),
+            None => unreachable!("table read above"),
+        }
+    }
+
+    /// Returns the Table, reading it from its Segment if it has not been accessed
+    pub(crate) fn into_table(mut self) -> Result<Table, DatabaseError> {
+        match self.table.take() {
+            Some(table) => Ok(Arc::unwrap_or_clone(table)),
+            None => self.load(),
+        }
+    }
+
+    fn load(&self) -> Result<Table, DatabaseError> {
+        let Some(segment) = &self.segment else {
+            error!("Table has neither been read nor a segment to read it from");
+            return Err(DatabaseError::InvalidFormat("table segment missing".to_string()))
+        };
+        let mut table: Table = bincode::deserialize(&segment.bytes)?;
+        if let Some(now) = self.rebase {
+            table.rebase(now);
+        };
+        if let Some(path) = &self.files {
+            table.attach_files(path)?;
+        };
+        debug!("Loaded table {} of {} entries from its segment", table.name, segment.entries);
+        Ok(table)
+    }
+
+    /// Returns the primary field of the Table, without reading it
+    pub(crate) fn primary_field(&self) -> FieldType {
+        match (self.table.get(), &self.segment) {
+            (Some(table), _) => table.primary_field.clone(),
+            (None, Some(segment)) => segment.primary_field.clone(),
+            (None, None) => unreachable!("slot holds a table or a segment"),
+        }
+    }
+
+    /// Returns the references of the Table, without reading it
+    pub(crate) fn references(&self) -> &HashMap<String, Reference> {
+        match (self.table.get(), &self.segment) {
+            (Some(table), _) => &table.references,
+            (None, Some(segment)) => &segment.references,
+            (None, None) => unreachable!("slot holds a table or a segment"),
+        }
+    }
+
+    /// Returns the number of Entries of the Table, without reading it
+    pub(crate) fn len(&self) -> usize {
+        match (self.table.get(), &self.segment) {
+            (Some(table), _) => table.len(),
+            (None, Some(segment)) => segment.entries,
+            (None, None) => 0,
+        }
+    }
+
+    /// Returns the approximate in-memory size of the Entries of the Table, or of its Segment
+    /// if it has not been read
+    pub(crate) fn approx_size(&self) -> usize {
+        match (self.table.get(), &self.segment) {
+            (Some(table), _) => table.approx_size(),
+            (None, Some(segment)) => segment.bytes.len(),
+            (None, None) => 0,
+        }
+    }
+
+    /// Rebases the Table, or records the time to rebase it to once read
+    pub(crate) fn rebase(&mut self, now: Timestamp) {
+        match self.table.get_mut() {
+            Some(table) => Arc::make_mut(table).rebase(now),
+            None => self.rebase = Some(now),
+        };
+    }
+
+    /// Attaches the Table to its files, or records the path to attach it to once read
+    pub(crate) fn attach_files(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        match self.table.get_mut() {
+            Some(table) => Arc::make_mut(table).attach_files(path),
+            None => {
+                self.files = Some(path.to_path_buf());
+                Ok(())
+            },
+        }
+    }
+
+    /// Returns the Segment of the Table, serializing it unless it is unchanged since read
+    fn segment(&self) -> Result<Arc<Segment>, DatabaseError> {
+        match (&self.segment, self.table.get()) {
+            (Some(segment), _) => Ok(segment.clone()),
+            (None, Some(table)) => Ok(Arc::new(Segment::new(table)?)),
+            (None, None) => unreachable!("slot holds a table or a segment"),
+        }
+    }
+}
+
+impl From<Table> for Slot {
+    fn from(table: Table) -> Self {
+        Slot{
+            table: OnceLock::from(Arc::new(table)),
+            segment: None,
+            rebase: None,
+            files: None,
+        }
+    }
+}
+
+/// Serialized as the Table, reading it from its Segment if it has not been accessed; for
+/// the standard format
+impl serde::Serialize for Slot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(self.get().map_err(S::Error::custom)?, serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Slot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <Table as serde::Deserialize>::deserialize(deserializer).map(Slot::from)
+    }
+}
+
+/// Database in the segmented format, as serialized
+#[derive(Serialize)]
+struct SegmentedRef<'a> {
+    sync_interval: Option<Duration>,
+    meta: &'a BTreeMap<String, String>,
+    idempotency: &'a IdempotencyLog,
+    tables: BTreeMap<&'a str, Arc<Segment>>,
+    schema_history: &'a [SchemaChange],
+}
+
+/// Database in the segmented format, as deserialized
+#[derive(Deserialize)]
+pub(crate) struct Segmented {
+    pub(crate) sync_interval: Option<Duration>,
+    pub(crate) meta: BTreeMap<String, String>,
+    pub(crate) idempotency: IdempotencyLog,
+    tables: BTreeMap<String, Segment>,
+    pub(crate) schema_history: Vec<SchemaChange>,
+}
+
+impl Segmented {
+    /// Returns the Slot of every Table, none of them read from its Segment
+    pub(crate) fn take_tables(&mut self) -> HashMap<String, Slot> {
+        std::mem::take(&mut self.tables).into_iter()
+            .map(|(name, segment)| (name, Slot::pending(segment)))
+            .collect()
+    }
+}
+
+/// Serializes the parts of a Database in the segmented format
+pub(crate) fn serialize(
+    sync_interval: Option<Duration>,
+    meta: &BTreeMap<String, String>,
+    idempotency: &IdempotencyLog,
+    tables: &HashMap<String, Slot>,
+    schema_history: &[SchemaChange],
+) -> Result<Vec<u8>, DatabaseError> {
+    let mut segments = BTreeMap::new();
+    for (name, slot) in tables {
+        segments.insert(name.as_str(), slot.segment()?);
+    };
+    let mut output = SEGMENTED_MAGIC.to_vec();
+    bincode::serialize_into(&mut output, &SegmentedRef{
+        sync_interval,
+        meta,
+        idempotency,
+        tables: segments,
+        schema_history,
+    })?;
+    Ok(output)
+}
+
+/// Calls f with the name of each Table of a Database serialized in the segmented format and
+/// the Table, or the error deserializing it, in turn until f returns false; as
+/// partial::for_each_table, but a Table that cannot be deserialized does not end the Tables
+/// read
+pub(crate) fn for_each_table(uncompressed: &[u8], f: &mut dyn FnMut(String, Result<Table, String>) -> bool) -> Result<(), DatabaseError> {
+    for (name, segment) in std::mem::take(&mut deserialize(uncompressed)?.tables) {
+        let table = bincode::deserialize(&segment.bytes).map_err(|e| e.to_string());
+        if !f(name, table) {
+            break
+        };
+    };
+    Ok(())
+}
+
+/// Deserializes a Database serialized in the segmented format, without reading its Tables
+pub(crate) fn deserialize(uncompressed: &[u8]) -> Result<Segmented, DatabaseError> {
+    match uncompressed.strip_prefix(SEGMENTED_MAGIC.as_slice()) {
+        Some(rest) => Ok(bincode::deserialize(rest)?),
+        None => Err(DatabaseError::InvalidFormat("database is not in the segmented format".to_string())),
+    }
+}
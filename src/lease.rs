@@ -0,0 +1,218 @@
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, trace};
+
+use crate::Client;
+use crate::observer::Operation;
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+use crate::names::NamePolicy;
+
+/// Name of the system table holding the state of every Lease of a database
+pub const LEASE_TABLE: &str = "__leases";
+
+const TOKEN: &str = "Token";
+const EXPIRES: &str = "Expires";
+
+/// Exclusive, expiring claim on a name, returned by DatabaseClient::acquire_lease.
+///
+/// At most one unexpired Lease on a name exists at a time.  Each Lease on a name has a
+/// greater token than the last, so resources guarded by the name can reject requests made
+/// with the token of an older Lease, whose holder may not yet know it has expired.
+///
+/// Leases are atomic across every clone of a Client and across separate processes sharing
+/// the database file.  Each acquire, renew and release holds a lock on the file
+/// `<database>.leases.lock`, taken through the operating system, while it reads the Leases
+/// of the database file, makes its change and saves the database.  Once the lock file
+/// exists, every save of the database also holds the lock and keeps the Leases of the
+/// file, so that a process saving its other changes does not overwrite Leases taken by
+/// another.
+pub struct Lease {
+    client: Client,
+    name: String,
+    token: u64,
+    expires: SystemTime,
+}
+
+impl std::fmt::Debug for Lease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lease")
+            .field("name", &self.name)
+            .field("token", &self.token)
+            .field("expires", &self.expires)
+            .finish()
+    }
+}
+
+/// Returns the token and expiry of the Lease held in the Entry
+fn state(entry: &Entry) -> (u64, SystemTime) {
+    let token = match entry.fields.get(TOKEN) {
+        Some(Field::U64(t)) => *t,
+        _ => 0,
+    };
+    let expires = match entry.fields.get(EXPIRES) {
+        Some(Field::Date(d)) => *d,
+        _ => SystemTime::UNIX_EPOCH,
+    };
+    (token, expires)
+}
+
+/// Returns the time ttl after now, or an error if it cannot be represented
+fn expiry(now: SystemTime, ttl: Duration) -> Result<SystemTime, DatabaseError> {
+    match now.checked_add(ttl) {
+        Some(expires) => Ok(expires),
+        None => {
+            error!("Lease ttl {:?} is out of range", ttl);
+            Err(DatabaseError::InvalidFormat(format!("lease ttl {:?}: the expiry is out of range", ttl)))
+        },
+    }
+}
+
+/// Returns the path of the lock file of the Leases of the database file at the supplied path
+pub(crate) fn lock_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".leases.lock");
+    PathBuf::from(name)
+}
+
+/// Exclusive lock on the lock file of the Leases of a database file, taken through the
+/// operating system so that it excludes other processes; released when dropped
+pub(crate) struct LeaseLock(File);
+
+impl LeaseLock {
+    /// Waits for the lock on the lock file of the database file at the supplied path,
+    /// creating the lock file if create; returns None if it does not exist and is not
+    /// created
+    pub(crate) fn acquire(path: &Path, create: bool) -> Result<Option<LeaseLock>, DatabaseError> {
+        let file = match OpenOptions::new().read(true).write(true).create(create).truncate(false).open(lock_path(path)) {
+            Ok(f) => f,
+            Err(e) if !create && e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        trace!("Waiting for lease lock of {:?}", path);
+        file.lock()?;
+        Ok(Some(LeaseLock(file)))
+    }
+}
+
+impl Drop for LeaseLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+fn lease_entry(name: &str, token: u64, expires: SystemTime) -> Result<Entry, DatabaseError> {
+    Entry::new()
+        .set_primary_field(Field::String(name.to_string()))?
+        .add_field(TOKEN.to_string(), Field::U64(token))?
+        .add_field(EXPIRES.to_string(), Field::Date(expires))?
+        .build()
+}
+
+impl Lease {
+    /// Acquires the named Lease for ttl if it is not held, creating the lease table if it
+    /// does not exist
+    pub(crate) fn acquire(client: &Client, name: String, ttl: Duration) -> Result<Option<Lease>, DatabaseError> {
+        let mut client = client.clone();
+        let acquired = client.with_leases(|client| {
+            if !client.list_tables()?.iter().any(|t| t == LEASE_TABLE) {
+                debug!("Creating lease table {}", LEASE_TABLE);
+                let table = Table::new()
+                    .name(LEASE_TABLE.to_string())
+                    .name_policy(NamePolicy::unrestricted())
+                    .primary_field(FieldType::String)?
+                    .add_field(TOKEN.to_string(), FieldType::U64)?
+                    .add_field(EXPIRES.to_string(), FieldType::Date)?
+                    .build()?;
+                match client.create_table(table) {
+                    Ok(_) | Err(DatabaseError::TableExists(_)) => (),
+                    Err(e) => return Err(e),
+                };
+            };
+
+            client.read_modify_write(Operation::InsertOrUpdate, &LEASE_TABLE.to_string(), &Field::String(name.clone()), |current, now| {
+                let (token, expires) = current.as_deref().map(state).unwrap_or((0, SystemTime::UNIX_EPOCH));
+                if expires > now {
+                    trace!("Lease {} is held until {:?}", name, expires);
+                    return Ok((None, None))
+                };
+                let expires = expiry(now, ttl)?;
+                Ok((Some(lease_entry(&name, token + 1, expires)?), Some((token + 1, expires))))
+            })
+        })?;
+
+        Ok(acquired.map(|(token, expires)| {
+            debug!("Acquired lease {} with token {}", name, token);
+            Lease{
+                client,
+                name,
+                token,
+                expires,
+            }
+        }))
+    }
+
+    /// Returns the name of the Lease
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the token of the Lease, greater than that of every earlier Lease on the name
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Returns the time the Lease expires unless renewed
+    pub fn expires(&self) -> SystemTime {
+        self.expires
+    }
+
+    /// Extends the Lease to expire ttl from now, returning false if it has already expired
+    /// or been released, in which case it is no longer held
+    pub fn renew(&mut self, ttl: Duration) -> Result<bool, DatabaseError> {
+        let (name, token) = (self.name.clone(), self.token);
+        let renewed = self.client.with_leases(|client| {
+            client.read_modify_write(Operation::Update, &LEASE_TABLE.to_string(), &Field::String(name.clone()), |current, now| {
+                match current.as_deref().map(state) {
+                    Some((t, expires)) if t == token && expires > now => {
+                        let expires = expiry(now, ttl)?;
+                        Ok((Some(lease_entry(&name, token, expires)?), Some(expires)))
+                    },
+                    _ => Ok((None, None)),
+                }
+            })
+        })?;
+
+        match renewed {
+            Some(expires) => {
+                trace!("Renewed lease {} until {:?}", self.name, expires);
+                self.expires = expires;
+                Ok(true)
+            },
+            None => {
+                debug!("Lease {} with token {} is no longer held", self.name, self.token);
+                Ok(false)
+            },
+        }
+    }
+
+    /// Releases the Lease so that it may be acquired immediately, returning false if it had
+    /// already expired or been released
+    pub fn release(mut self) -> Result<bool, DatabaseError> {
+        let (name, token) = (self.name.clone(), self.token);
+        self.client.with_leases(|client| {
+            client.read_modify_write(Operation::Update, &LEASE_TABLE.to_string(), &Field::String(name.clone()), |current, now| {
+                match current.as_deref().map(state) {
+                    Some((t, expires)) if t == token && expires > now => {
+                        debug!("Releasing lease {} with token {}", name, token);
+                        Ok((Some(lease_entry(&name, token, now)?), true))
+                    },
+                    _ => Ok((None, false)),
+                }
+            })
+        })
+    }
+}
@@ -0,0 +1,160 @@
+//! Decoding of database files written by version 0.0.8 and earlier, before database files
+//! had a header.  Those files hold the Database as derived serialization numbered the
+//! variants of Field and FieldType and laid out the fields of Table and Entry, so they are
+//! read into the types below and converted; once opened, a database is saved in the
+//! current format.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use serde_derive::Deserialize;
+
+use crate::clock::Timestamp;
+use crate::errors::*;
+use crate::names::NamePolicy;
+use crate::structs::{Database, Entry, Field, FieldType, Table};
+
+#[derive(Deserialize, Hash, PartialEq, Eq)]
+enum LegacyField {
+    String(String),
+    I64(i64),
+    I32(i32),
+    U64(u64),
+    U32(u32),
+    Date(SystemTime),
+    Bool(bool),
+    NotImplemented,
+}
+
+#[derive(Deserialize)]
+enum LegacyFieldType {
+    String,
+    I64,
+    I32,
+    U64,
+    U32,
+    Date,
+    Bool,
+    None,
+}
+
+#[derive(Deserialize)]
+enum LegacyFieldRequirement {
+    Required(LegacyFieldType),
+    Optional(LegacyFieldType),
+}
+
+#[derive(Deserialize)]
+struct LegacyEntry {
+    primary_field: LegacyField,
+    fields: HashMap<String, LegacyField>,
+    last_timestamp: Option<SystemTime>,
+}
+
+#[derive(Deserialize)]
+struct LegacyTable {
+    name: String,
+    primary_field: LegacyFieldType,
+    fields: HashMap<String, LegacyFieldRequirement>,
+    entries: HashMap<LegacyField, LegacyEntry>,
+    expire_after: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct LegacyDatabase {
+    sync_interval: Option<Duration>,
+    tables: HashMap<String, LegacyTable>,
+}
+
+impl TryFrom<LegacyField> for Field {
+    type Error = DatabaseError;
+
+    fn try_from(field: LegacyField) -> Result<Self, Self::Error> {
+        Ok(match field {
+            LegacyField::String(v) => Field::String(v),
+            LegacyField::I64(v) => Field::I64(v),
+            LegacyField::I32(v) => Field::I32(v),
+            LegacyField::U64(v) => Field::U64(v),
+            LegacyField::U32(v) => Field::U32(v),
+            LegacyField::Date(v) => Field::Date(v),
+            LegacyField::Bool(v) => Field::Bool(v),
+            LegacyField::NotImplemented => return Err(DatabaseError::UnsupportedFieldType),
+        })
+    }
+}
+
+impl TryFrom<LegacyFieldType> for FieldType {
+    type Error = DatabaseError;
+
+    fn try_from(field_type: LegacyFieldType) -> Result<Self, Self::Error> {
+        Ok(match field_type {
+            LegacyFieldType::String => FieldType::String,
+            LegacyFieldType::I64 => FieldType::I64,
+            LegacyFieldType::I32 => FieldType::I32,
+            LegacyFieldType::U64 => FieldType::U64,
+            LegacyFieldType::U32 => FieldType::U32,
+            LegacyFieldType::Date => FieldType::Date,
+            LegacyFieldType::Bool => FieldType::Bool,
+            LegacyFieldType::None => return Err(DatabaseError::UnsupportedFieldType),
+        })
+    }
+}
+
+impl LegacyTable {
+    /// Returns the Table with the schema and Entries of the legacy Table; each Entry keeps
+    /// the time it was last updated
+    fn convert(self) -> Result<Table, DatabaseError> {
+        let mut builder = Table::new()
+            .name(self.name)
+            .name_policy(NamePolicy::unrestricted())
+            .primary_field(self.primary_field.try_into()?)?;
+        let mut fields: Vec<(String, LegacyFieldRequirement)> = self.fields.into_iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, requirement) in fields {
+            builder = match requirement {
+                LegacyFieldRequirement::Required(t) => builder.add_field(name, t.try_into()?)?,
+                LegacyFieldRequirement::Optional(t) => builder.add_optional_field(name, t.try_into()?)?,
+            };
+        };
+        if let Some(d) = self.expire_after {
+            builder = builder.add_expiration(d);
+        };
+        let mut table = builder.build()?;
+
+        let now = Timestamp::now();
+        for entry in self.entries.into_values() {
+            let mut fields = HashMap::with_capacity(entry.fields.len());
+            for (name, field) in entry.fields {
+                fields.insert(name, field.try_into()?);
+            };
+            let wall = entry.last_timestamp.unwrap_or(now.wall);
+            let age = now.wall.duration_since(wall).unwrap_or_default();
+            let entry = Entry{
+                primary_field: entry.primary_field.try_into()?,
+                fields,
+                last_timestamp: None,
+            };
+            table.insert_at(entry, Timestamp{
+                wall,
+                monotonic: now.monotonic.checked_sub(age).unwrap_or(now.monotonic),
+            })?;
+        };
+        Ok(table)
+    }
+}
+
+/// Deserializes a Database written by version 0.0.8 or earlier from the decompressed
+/// contents of its file
+pub(crate) fn deserialize(uncompressed: &[u8]) -> Result<Database, DatabaseError> {
+    let legacy: LegacyDatabase = bincode::deserialize(uncompressed)?;
+    let mut database = Database::default();
+    database.sync_interval = legacy.sync_interval;
+    let mut tables: Vec<LegacyTable> = legacy.tables.into_values().collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    for table in tables {
+        let name = table.name.clone();
+        database.create_table(table.convert()?)?;
+        let table = database.get_table(&name)?;
+        table.created_at = None;
+        table.last_modified_at = None;
+    };
+    Ok(database)
+}
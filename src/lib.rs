@@ -1,45 +1,205 @@
+// The crate is written in an explicit style these lints object to: `return` at the end of
+// functions, `format!` for constant error messages and `self: &mut Self` in DatabaseClient.
+// Client::new returns a boxed DatabaseClient, Table::new and Entry::new return their
+// builders and DatabaseClient::drop_table takes &String; changing them would break the
+// public API.
+#![allow(clippy::needless_return, clippy::useless_format, clippy::needless_arbitrary_self_type, clippy::new_ret_no_self, clippy::ptr_arg)]
 use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::SeekFrom;
-use std::time::{SystemTime, Duration};
+use std::time::{Duration, Instant};
 use std::collections::HashMap;
 use std::io::prelude::*;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use sync::mpsc::Receiver;
 use std::thread::sleep;
 use std::fs::OpenOptions;
 use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use tracing::{debug, error, info, trace};
 
 mod structs;
+mod storage;
+mod clock;
+mod condition;
+mod filter;
+mod expiration;
+mod stats;
+mod cancellation;
+mod plan;
+mod join;
+mod verify;
+mod sync;
+mod pool;
+mod merge;
+mod diff;
+mod partial;
+mod lazy;
+mod compat;
+mod header;
+mod legacy;
+mod names;
+mod custom;
+mod variants;
+mod decimal;
+mod cidr;
+mod geo;
+mod cache;
+mod flat;
+mod spill;
+mod blob;
+mod intern;
+mod bloom;
+mod prefix;
+mod index;
+mod export;
+mod display;
+mod lease;
+mod system;
+mod gc;
+mod dictionary;
+mod container;
+mod snapshot;
+mod schedule;
+mod dryrun;
+mod logging;
+mod idempotency;
+mod commit;
+mod typed;
+mod copy;
+mod progress;
+mod ids;
+mod ordered;
+mod observer;
+mod lifecycle;
+mod access;
+mod redact;
+mod secret;
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod interop;
+#[cfg(feature = "web")]
+mod web;
 pub mod errors;
 pub mod prelude;
+pub mod format;
+pub mod ratelimit;
+pub mod sessions;
+pub mod flags;
+pub mod counters;
+#[cfg(feature = "generator")]
+pub mod generator;
 pub use structs::*;
+pub use storage::TableLayout;
+pub use clock::{Clock, SystemClock, ManualClock, Timestamp};
+pub use ids::{IdGenerator, RandomIdGenerator, SequentialIdGenerator};
+pub use condition::{Condition, AgeDuration, PRIMARY_FIELD_KEY};
+pub use filter::Filter;
+pub use expiration::ExpirationSchedule;
+pub use stats::{DatabaseStats, ENTRY_SIZE_BUCKETS, EntrySizeStats, LARGEST_ENTRIES, LOCK_WAIT_BUCKETS, LockWaitStats, SaveStats, TableStats, WriteStats};
+pub use cancellation::CancellationToken;
+pub use plan::{AccessPath, QueryPlan};
+pub use join::{JoinKind, JoinOptions, JoinedEntry};
+pub use verify::{VerifyProblem, VerifyReport};
+pub use pool::ClientPool;
+pub use container::Container;
+pub use snapshot::Snapshot;
+pub use schedule::SaveGate;
+pub use dryrun::DryRunAction;
+pub use logging::{Subsystem, log_level, set_log_level};
+pub use idempotency::DEFAULT_IDEMPOTENCY_CAPACITY;
+pub use observer::{Operation, OperationEvent, OperationObserver};
+pub use lifecycle::{LifecycleEvent, LifecycleEvents};
+pub use custom::{CustomCodec, register_codec};
+pub use decimal::{Decimal, MAX_DECIMAL_SCALE};
+pub use cidr::Cidr;
+pub use geo::{GeoPoint, EARTH_RADIUS_METERS};
+pub use access::Authorization;
+pub use redact::{REDACTED, is_sensitive};
+#[cfg(feature = "zeroize")]
+pub use secret::{Secret, exclude_from_core_dumps};
+pub use merge::{MergeConflict, MergeReport, Resolution, last_writer_wins};
+pub use diff::{DatabaseDiff, EntryChange, TableDiff};
+pub use compat::{CompatReport, FileCompression, FileFormat, SchemaFeature};
+pub use header::{FileInfo, FORMAT_VERSION};
+pub use names::{DEFAULT_MAX_NAME_LEN, NameCharset, NamePolicy, RESERVED_PREFIX};
+pub use cache::{CacheLayer, CachedTable};
+pub use typed::{Key, TypedTable};
+pub use copy::{COPY_PROGRESS_INTERVAL, CopyConflict, CopyOptions, CopyProgress, CopyReport};
+pub use progress::{PROGRESS_BYTES, PROGRESS_ENTRIES, Progress, ProgressOperation, ProgressPhase};
+pub use flat::{FlatEntry, FlatEntrySeed, FlatField};
+pub use export::{ExportFormat, PRIMARY_FIELD_COLUMN};
+pub use display::{DisplayColumn, DisplayRow};
+pub use lease::{Lease, LEASE_TABLE};
+pub use system::{SchemaChange, SchemaChangeKind, SCHEMA_HISTORY_TABLE, STATS_TABLE, SYSTEM_TABLES, TABLES_TABLE, is_system_table};
+#[cfg(feature = "web")]
+pub use web::{NamedTable, SharedClient, TableHandle};
 #[cfg(feature = "mocks")]
 pub use prelude::MockDatabaseClient;
 use errors::*;
 use prelude::*;
+use condition::*;
+use export::ExportWriter;
+use ratelimit::{RateLimit, RateLimiter};
+use sessions::SessionStore;
+use flags::FlagStore;
+use counters::{CounterRetention, CounterStore};
+use schedule::jitter;
+use lease::LeaseLock;
+use logging::log_event;
 use std::thread::JoinHandle;
+use sync::{Mutex, RwLock};
 
-struct Saver {
+/// Maximum number of entries of a table evaluated by prune at a time
+pub const PRUNE_CHUNK_SIZE: usize = 1000;
+
+/// Background thread of a Client, stopped when dropped
+struct Worker {
     handle: Option<JoinHandle<()>>,
     killer: std::sync::mpsc::SyncSender<()>,
 }
 
-impl Drop for Saver {
+impl Drop for Worker {
     fn drop(&mut self) {
-        self.killer.send(()).unwrap();
+        // A thread that already stopped, such as by panicking, has dropped its receiver
+        let _ = self.killer.send(());
         if let Some(h) = self.handle.take() {
-            h.join().unwrap();
+            let _ = h.join();
         }
     }
 }
 
 /// Thread-safe, optionally persistent client for interacting with a keystore database
+/// 
+/// Read operations (get, scan, query, list_tables) share a read lock on the database and
+/// proceed concurrently with each other and with a save in progress; only the write lock
+/// taken by mutations, and briefly by prune to swap in the pruned database, blocks them.
 #[derive(Clone)]
 pub struct Client {
-    database: Arc<Mutex<Database>>,
-    raw_file: Arc<Mutex<PathBuf>>,
-    handle: Arc<Option<Saver>>,
+    database: sync::Arc<RwLock<Database>>,
+    raw_file: sync::Arc<Mutex<PathBuf>>,
+    signature: sync::Arc<Mutex<Option<FileSignature>>>,
+    workers: sync::Arc<Vec<Worker>>,
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGenerator>,
+    max_memory: Option<(usize, CapacityPolicy)>,
+    size_budget: Option<u64>,
+    verify_interval: Option<Duration>,
+    expiry_warning: Option<Duration>,
+    compact_interval: Option<(Duration, Duration)>,
+    save_gate: Option<SaveGate>,
+    dry_run: bool,
+    dry_runs: sync::Arc<Mutex<Vec<DryRunAction>>>,
+    idempotency_capacity: usize,
+    durability: sync::Arc<commit::Durability>,
+    group_commit: bool,
+    wait_for_locks: bool,
+    access: access::AccessControl,
+    progress: Option<progress::Reporter>,
+    lifecycle: LifecycleEvents,
+    writes: sync::Arc<Mutex<WriteStats>>,
+    lock_waits: sync::Arc<Mutex<HashMap<&'static str, LockWaitStats>>>,
+    #[cfg(feature = "zstd")]
+    dictionary: sync::Arc<Mutex<Option<dictionary::Dictionary>>>,
+    root: Option<container::Root>,
 }
 
 fn open_file<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P) -> Result<File, std::io::Error> {
@@ -53,6 +213,119 @@ fn open_file<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P) -> Result<File,
         .open(path)
 }
 
+/// Modification time, length and checksum of a database file, as of when it was last
+/// read or written by the Client; used by reload to detect changes by other processes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FileSignature {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+    checksum: u64,
+}
+
+impl FileSignature {
+    fn new(f: &File, contents: &[u8]) -> Self {
+        FileSignature{
+            modified: f.metadata().and_then(|m| m.modified()).ok(),
+            len: contents.len() as u64,
+            checksum: checksum(contents),
+        }
+    }
+}
+
+/// Returns the FNV-1a hash of the supplied bytes; stable across processes and platforms
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    };
+    hash
+}
+
+/// Reads the database file at the supplied path, returning its contents and signature
+fn read_file<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P) -> Result<(Vec<u8>, FileSignature), DatabaseError> {
+    read_file_with(path, &progress::Tracker::none())
+}
+
+/// Reads the database file at the supplied path as read_file, reporting its progress
+fn read_file_with<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P, tracker: &progress::Tracker) -> Result<(Vec<u8>, FileSignature), DatabaseError> {
+    let mut f = open_file(path)?;
+    let mut compressed: Vec<u8> = Vec::new();
+    let total = f.metadata().ok().map(|m| m.len());
+    tracker.read_to_end(&mut f, &mut compressed, total)?;
+    let signature = FileSignature::new(&f, &compressed);
+    Ok((compressed, signature))
+}
+
+/// Decompresses and deserializes the contents of a database file
+fn decode_database(compressed: &[u8]) -> Result<Database, DatabaseError> {
+    Ok(decode_file(compressed)?.0)
+}
+
+/// Decompresses and deserializes the contents of a database file, returning the database
+/// and the dictionary it was compressed with, if any; see DatabaseClient::train_dictionary
+fn decode_file(compressed: &[u8]) -> Result<(Database, Option<dictionary::Dictionary>), DatabaseError> {
+    let (info, _) = header::split(compressed)?;
+    let (mut uncompressed, dictionary) = decompress_file(compressed)?;
+    let database = deserialize_database(info.as_ref(), &uncompressed);
+    secret::wipe(&mut uncompressed);
+    Ok((database?, dictionary))
+}
+
+/// Deserializes the decompressed database of a file with the supplied header, or of a file
+/// written before headers, by version 0.0.8 or earlier, if it has none
+fn deserialize_database(info: Option<&FileInfo>, uncompressed: &[u8]) -> Result<Database, DatabaseError> {
+    match info {
+        Some(info) => {
+            info.check_format()?;
+            Database::deserialize(uncompressed)
+        },
+        None => legacy::deserialize(uncompressed),
+    }
+}
+
+/// Decompresses the contents of a database file, after its header if it has one, returning
+/// the serialized database and the dictionary it was compressed with, if any
+fn decompress_file(contents: &[u8]) -> Result<(Vec<u8>, Option<dictionary::Dictionary>), DatabaseError> {
+    let (_, compressed) = header::split(contents)?;
+    match dictionary::is_dictionary_compressed(compressed) {
+        #[cfg(feature = "zstd")]
+        true => {
+            let (dictionary, uncompressed) = dictionary::decompress(compressed)?;
+            Ok((uncompressed, Some(dictionary)))
+        },
+        #[cfg(not(feature = "zstd"))]
+        true => Err(dictionary::unsupported()),
+        false => Ok((decompress_size_prepended(compressed)?, None)),
+    }
+}
+
+/// Reads the named Table of the database file at the supplied path, without keeping its
+/// other Tables in memory; see partial::read_table
+fn read_table<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P, name: &str) -> Result<Option<Table>, DatabaseError> {
+    let (compressed, _) = read_file(path)?;
+    decode_table(&compressed, name)
+}
+
+/// Decompresses the contents of a database file and deserializes its named Table, as
+/// read_table
+fn decode_table(compressed: &[u8], name: &str) -> Result<Option<Table>, DatabaseError> {
+    let (info, _) = header::split(compressed)?;
+    let (mut uncompressed, _) = decompress_file(compressed)?;
+    let table = match (&info, lazy::is_segmented(&uncompressed)) {
+        (Some(info), false) => info.check_format().and_then(|_| partial::read_table(&uncompressed, name)),
+        (info, _) => deserialize_database(info.as_ref(), &uncompressed).and_then(|mut database| database.take_table(name)),
+    };
+    secret::wipe(&mut uncompressed);
+    table
+}
+
+/// Reads, decompresses and deserializes the database file at the supplied path
+fn read_database<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P) -> Result<Database, DatabaseError> {
+    let (compressed, _) = read_file(path)?;
+    decode_database(&compressed)
+}
+
 impl Client {
     /// Creates a database at the supplied path
     /// ```
@@ -82,55 +355,11 @@ impl Client {
     /// This thread will prune (remove stale entries) and save the database
     /// every duration
     pub fn new<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P, sync_interval: Option<Duration>) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
-        info!("Creating Client with database at {:?}", path);
-        if path.as_ref().exists() {
-            error!("Database exists, cannot create: {:?}", path);
-            return Err(DatabaseError::DatabaseExistsError)
-        };
-
-        let mut database = Database::default();
-        
-        if let Some(d) = sync_interval {
-            debug!("Setting sync interval to {:?}", d);
-            database.set_sync_duration(d);
-        };
-
-        let mut client = Self{
-            database: Arc::new(Mutex::new(database)),
-            raw_file: Arc::new(Mutex::new(PathBuf::from(path.as_ref()))),
-            handle: Arc::new(None),
-        };
-
+        let mut builder = Client::builder(path);
         if let Some(d) = sync_interval {
-            let mut c = client.clone();
-            let (tx, rx) = std::sync::mpsc::sync_channel(0);
-            let h = std::thread::spawn( move || loop {
-                    if let Ok(_) = rx.try_recv() {
-                        trace!("Breaking");
-                        break
-                    };
-
-                    trace!("Sleeping for {:?}", d);
-                    sleep(d);
-
-                    trace!("Pruning database");
-                    c.prune().unwrap();
-                    debug!("Database pruned");
-                    
-                    trace!("Saving database");
-                    c.save().unwrap();
-                    debug!("Database saved");
-                }
-            );
-            client.handle = Arc::new(Some(Saver{
-                handle: Some(h),
-                killer: tx,
-            }));
+            builder = builder.sync_interval(d);
         };
-
-        client.save()?;
-        trace!("Returning Client");
-        Ok(Box::new(client))
+        builder.build()
     }
 
     /// Opens an existing database at the supplied path
@@ -145,1063 +374,9661 @@ impl Client {
     /// This database will resume the sync settings that were provided when
     /// the database was created.
     pub fn open<P: AsRef<Path> + Clone + std::fmt::Debug>(path: P) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
-        info!("Opening Client with database at {:?}", path);
-        if !path.as_ref().exists() {
-            error!("Database does not exist exists, cannot open: {:?}", path);
-            return Err(DatabaseError::DatabaseDoesNotExist(path.as_ref().to_str().unwrap().to_string()))
-        } ;
-
-        let mut f = open_file(&path)?;
-        let mut compressed: Vec<u8> = Vec::new();
-        f.read_to_end(&mut compressed)?;
-        let uncompressed = decompress_size_prepended(&compressed)?;
-        let database: Database = bincode::deserialize(&uncompressed)?;
-        let sync_interval = database.sync_interval.clone();
-
-        let mut client = Self{
-            database: Arc::new(Mutex::new(database)),
-            raw_file: Arc::new(Mutex::new(PathBuf::from(path.as_ref()))),
-            handle: Arc::new(None),
-        };
-
-        if let Some(duration) = sync_interval {
-            let mut c = client.clone();
-            let (tx, rx) = std::sync::mpsc::sync_channel(0);
-            let h = std::thread::spawn( move || loop {
-                    if let Ok(_) = rx.try_recv() {
-                        trace!("Breaking");
-                        break
-                    };
-    
-                    trace!("Sleeping for {:?}", duration);
-                    sleep(duration);
-                    
-                    trace!("Pruning database");
-                    c.prune().unwrap();
-                    debug!("Database pruned");
-                    
-                    trace!("Saving database");
-                    c.save().unwrap();
-                    debug!("Database saved");
-                }
-            );
-    
-            client.handle = Arc::new(Some(Saver{
-                handle: Some(h),
-                killer: tx,
-            }));
-        };
-        
-
-        trace!("Returning Client");
-
-        Ok(Box::new(client))
+        Client::builder(path).open()
     }
-}
 
-impl DatabaseClient for Client {
-    /// Removes stale entries as defined by the expiration value per table
-    /// and saves the database to disk; using lz4 compression
+    /// Returns a ClientBuilder for the database at the supplied path, allowing the
+    /// Client to be configured before the database is created or opened
     /// ```
-    /// # use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::Client;
     /// use std::path::Path;
-    /// let c = Client::new(Path::new("saved.db"), None);
-    /// # std::fs::remove_file("saved.db").unwrap();
-    /// ```
-    /// This database will not sync on its own and will need to be saved with 
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("builder.db"))
+    ///     .sync_interval(Duration::from_millis(30))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("builder.db").unwrap();
     /// ```
-    /// # use persistent_keystore_rs::Client;
-    /// # let mut c = Client::new(std::path::Path::new("saved2.db"), None).unwrap();
-    /// c.save();
-    /// # std::fs::remove_file("saved2.db").unwrap();
-    fn save(&mut self) -> Result<(), DatabaseError> {
-        trace!("Saving database");
-        if let Ok(database) = self.database.lock() {
-            if let Ok(raw_file) = self.raw_file.lock() {
-                debug!("Saving database {:?}", raw_file);
-                let mut f = OpenOptions::new()
-                    .write(true)
-                    .read(true)
-                    .create(true)
-                    .truncate(true)
-                    .append(false)
-                    .open(raw_file.as_path())?;
-                let output = bincode::serialize(&database.clone())?;
-                let compressed = compress_prepend_size(&output);
-                f.seek(SeekFrom::Start(0))?;
-                f.write_all(&compressed)?;
-                f.flush()?;
-                f.sync_all()?;
-                drop(f);
-                return Ok(())
-
-            } else {
-                error!("Unable to get file mutex");
-            };
-        };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+    pub fn builder<P: AsRef<Path>>(path: P) -> ClientBuilder {
+        ClientBuilder{
+            path: PathBuf::from(path.as_ref()),
+            sync_interval: None,
+            sync_jitter: Duration::ZERO,
+            save_gate: None,
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIdGenerator),
+            max_memory: None,
+            size_budget: None,
+            verify_interval: None,
+            expiry_warning: None,
+            compact_interval: None,
+            reload_interval: None,
+            gc_retention: None,
+            empty_file: EmptyFilePolicy::default(),
+            dry_run: false,
+            idempotency_capacity: DEFAULT_IDEMPOTENCY_CAPACITY,
+            group_commit: None,
+            observers: Vec::new(),
+            authorizer: None,
+            progress: None,
+            lifecycle: LifecycleEvents::new(),
+            segmented: false,
+            case_insensitive_tables: false,
+            container: None,
+        }
     }
 
-    /// Creates a table within the database of the associated client
+    /// Returns the Tables and Entries added, removed and changed between the database
+    /// files a and b, without opening either with a Client; for verifying backups and
+    /// finding the drift between replicas.  See examples/diff.rs for use from the command
+    /// line.
     /// ```
-    /// use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{Client, FieldType, Table};
     /// use persistent_keystore_rs::prelude::*;
-    /// use std::time::Duration;
-    /// # use std::path::Path;
-    /// let mut c = Client::new(Path::new("createtable.db"), None).unwrap();
+    /// use std::path::Path;
+    /// let mut c = Client::new(Path::new("diff_a.db"), None).unwrap();
+    /// c.save().unwrap();
+    /// std::fs::copy("diff_a.db", "diff_b.db").unwrap();
+    /// assert!(Client::diff_files("diff_a.db", "diff_b.db").unwrap().is_empty());
+    ///
     /// let table = Table::new()
-    ///     .name(String::from("MyTable"))
-    ///     .primary_field(FieldType::String).unwrap()
-    ///     .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    ///     .add_expiration(Duration::from_secs(2592000))
-    ///     .build().unwrap();
+    ///    .name("MyTable".to_string())
+    ///    .primary_field(FieldType::String).unwrap()
+    ///    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///    .build().unwrap();
     /// c.create_table(table).unwrap();
-    /// # std::fs::remove_file("createtable.db").unwrap();
+    /// c.save().unwrap();
+    /// let diff = Client::diff_files("diff_b.db", "diff_a.db").unwrap();
+    /// assert_eq!(diff.tables_added, vec!["MyTable".to_string()]);
+    /// # std::fs::remove_file("diff_a.db").unwrap();
+    /// # std::fs::remove_file("diff_b.db").unwrap();
     /// ```
-    fn create_table(&mut self, table: Table) -> Result<(), DatabaseError> {
-        trace!("Creating table {}", table.name);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table.name.clone()) {
-                Ok(_) => {
-                    error!("Table {} exists", table.name);
-                    return Err(DatabaseError::TableExists(table.name))
-                },
-                Err(_) => {
-                    debug!("Creating table {}", table.name);
-                    return database.create_table(table)
-                },
-            };
-        };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+    pub fn diff_files<P: AsRef<Path>>(a: P, b: P) -> Result<DatabaseDiff, DatabaseError> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        log_event!(Subsystem::Save, INFO, "Comparing databases {:?} and {:?}", a, b);
+        let mut before = read_database(a)?;
+        before.attach_files(a)?;
+        let mut after = read_database(b)?;
+        after.attach_files(b)?;
+        before.diff(&after)
     }
 
-    /// Lists tables within the database of the associated client
+    /// Inspects the database file at the supplied path without opening it, reporting its
+    /// format, the version of the crate that wrote it and the SchemaFeatures each table
+    /// uses, and whether this build of the crate can open it; so that deploy pipelines can
+    /// check a new build against the files it will open before rolling it out.  Tables are
+    /// read one at a time, so the database is never held in memory as a whole.
     /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// let mut c = Client::new(Path::new("listtable.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// let tables = c.list_tables().unwrap();
-    /// assert_eq!(tables.len(), 1);
-    /// assert_eq!(tables[0], String::from("MyTable"));
-    /// # std::fs::remove_file("listtable.db").unwrap();
+    /// use persistent_keystore_rs::{Client, FieldType, SchemaFeature, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let mut c = Client::new(Path::new("checkcompat.db"), None).unwrap();
+    /// let table = Table::new()
+    ///    .name("MyTable".to_string())
+    ///    .primary_field(FieldType::String).unwrap()
+    ///    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///    .add_expiration(Duration::from_secs(60))
+    ///    .build().unwrap();
+    /// c.create_table(table).unwrap();
+    /// c.save().unwrap();
+    ///
+    /// let report = Client::check_compat("checkcompat.db").unwrap();
+    /// assert!(report.is_compatible());
+    /// assert_eq!(report.tables["MyTable"], vec![SchemaFeature::Expiration]);
+    /// # std::fs::remove_file("checkcompat.db").unwrap();
     /// ```
-    fn list_tables(&mut self) -> Result<Vec<String>, DatabaseError> {
-        trace!("Listing Tables");
-        if let Ok(mut database) = self.database.lock() {
-            let tables = database.list_tables();
-            debug!("Listed {} tables", tables.len());
-            return Ok(tables)
+    pub fn check_compat<P: AsRef<Path>>(path: P) -> Result<CompatReport, DatabaseError> {
+        let path = path.as_ref();
+        log_event!(Subsystem::Save, INFO, "Checking compatibility of database {:?}", path);
+        let (compressed, _) = read_file(path)?;
+        if compressed.is_empty() {
+            error!("Database file is empty, cannot check: {:?}", path);
+            return Err(DatabaseError::EmptyDatabaseFile(path.to_path_buf()))
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        let report = compat::check(&compressed);
+        log_event!(Subsystem::Save, DEBUG, "Database {:?} has {} tables and {} compatibility problems", path, report.tables.len(), report.problems.len());
+        Ok(report)
     }
 
-    /// Drops the specified table from within the database of the associated client
+    /// Returns the version of the crate, host and time recorded in the header of the
+    /// database file at the supplied path when it was last saved, reading only the header;
+    /// None is returned for files written before headers were recorded.
     /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// let mut c = Client::new(Path::new("droptable.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// let tables = c.list_tables().unwrap();
-    /// assert_eq!(tables.len(), 1);
-    /// assert_eq!(tables[0], String::from("MyTable"));
-    /// c.drop_table(&String::from("MyTable")).unwrap();
-    /// let current_tables = c.list_tables().unwrap();
-    /// assert_eq!(current_tables.len(), 0);
-    /// # std::fs::remove_file("droptable.db").unwrap();
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::new(Path::new("fileinfo.db"), None).unwrap();
+    /// c.save().unwrap();
+    ///
+    /// let info = Client::file_info("fileinfo.db").unwrap().unwrap();
+    /// assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+    /// # std::fs::remove_file("fileinfo.db").unwrap();
     /// ```
-    fn drop_table(&mut self, table: &String) -> Result<(), DatabaseError> {
-        trace!("Dropping table {}", table);
-        if let Ok(mut database) = self.database.lock() {
-            debug!("Dropping table {}", table);
-            return database.drop_table(table)
+    pub fn file_info<P: AsRef<Path>>(path: P) -> Result<Option<FileInfo>, DatabaseError> {
+        let path = path.as_ref();
+        log_event!(Subsystem::Save, TRACE, "Reading header of database {:?}", path);
+        let mut f = File::open(path)?;
+        let info = header::read(&mut f)?;
+        if let Some(info) = &info {
+            log_event!(Subsystem::Save, DEBUG, "Database {:?} was written by version {} on {:?}", path, info.crate_version, info.host);
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        Ok(info)
     }
 
-    /// Inserts the provided entry into the specified table within the database of the associated client.
-    /// If an entry with the same primary key exists, an DatabaseError::EntryExists is returned
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType};
-    /// use persistent_keystore_rs::{Entry, Field};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("insertentry.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// let entry = Entry::new()
-    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    ///     .build().unwrap();
-    /// c.insert("MyTable".to_string(), entry).unwrap();
-    /// # std::fs::remove_file("insertentry.db").unwrap();
-    /// ```
-    fn insert(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
-        trace!("Inserting entry into table {}: {}", table, entry);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Inserting entry into table {}", table);
-                    return t.insert(entry)
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                }
+    /// Starts the thread that prunes and saves the database every duration, plus up to
+    /// max_jitter; the thread is stopped when the last clone of the Client is dropped.
+    fn start_sync(&mut self, duration: Duration, max_jitter: Duration) -> Worker {
+        let mut c = self.clone();
+        let (tx, rx) = std::sync::mpsc::sync_channel(0);
+        let mut last_verified = Instant::now();
+        let next_compaction = |(interval, max): (Duration, Duration)| Instant::now() + interval + jitter(max);
+        let mut compact_at = c.compact_interval.map(next_compaction);
+        let h = std::thread::spawn( move || loop {
+                if rx.try_recv().is_ok() {
+                    log_event!(Subsystem::Save, TRACE, "Breaking");
+                    break
+                };
+
+                let delay = duration + jitter(max_jitter);
+                log_event!(Subsystem::Save, TRACE, "Sleeping for {:?}", delay);
+                sleep(delay);
+
+                if let Some(within) = c.expiry_warning {
+                    log_event!(Subsystem::Prune, TRACE, "Checking for entries expiring within {:?}", within);
+                    if let Err(e) = c.warn_expiring(within) {
+                        error!("Unable to check for expiring entries: {}", e);
+                    };
+                };
+
+                log_event!(Subsystem::Save, TRACE, "Pruning database");
+                match c.prune() {
+                    Ok(()) => log_event!(Subsystem::Save, DEBUG, "Database pruned"),
+                    Err(e) => error!("Unable to prune database: {}", e),
+                };
+
+                let permit = c.save_gate.as_ref().map(SaveGate::acquire);
+                match (compact_at, c.compact_interval) {
+                    (Some(at), Some(interval)) if Instant::now() >= at => {
+                        log_event!(Subsystem::Save, TRACE, "Compacting database");
+                        match c.compact() {
+                            Ok(removed) => log_event!(Subsystem::Save, DEBUG, "Database compacted; {} orphaned files removed", removed),
+                            Err(e) => error!("Unable to compact database: {}", e),
+                        };
+                        compact_at = Some(next_compaction(interval));
+                    },
+                    _ => {
+                        log_event!(Subsystem::Save, TRACE, "Saving database");
+                        match c.save() {
+                            Ok(()) => log_event!(Subsystem::Save, DEBUG, "Database saved"),
+                            Err(e) => error!("Unable to save database: {}", e),
+                        };
+                    },
+                };
+                drop(permit);
+
+                if let Some(interval) = c.verify_interval {
+                    if last_verified.elapsed() >= interval {
+                        log_event!(Subsystem::Save, TRACE, "Verifying database");
+                        match c.verify() {
+                            Ok(report) => {
+                                for problem in &report.problems {
+                                    error!("Database verification failed: {}", problem);
+                                };
+                                log_event!(Subsystem::Save, DEBUG, "Database verified; {} problems found", report.problems.len());
+                            },
+                            Err(e) => error!("Unable to verify database: {}", e),
+                        };
+                        last_verified = Instant::now();
+                    };
+                };
             }
-        };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        );
+
+        Worker{
+            handle: Some(h),
+            killer: tx,
+        }
     }
 
-    /// Inserts the provided entry into the specified table within the database of the associated client.
-    /// If an entry with the same primary key exists, the entry is updated.
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType};
-    /// use persistent_keystore_rs::{Entry, Field};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("insertorupdateentry.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// let entry = Entry::new()
-    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    ///     .build().unwrap();
-    /// c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// c.insert_or_update("MyTable".to_string(), entry).unwrap();
-    /// # std::fs::remove_file("insertorupdateentry.db").unwrap();
-    /// ```
-    fn insert_or_update(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
-        trace!("Inserting or updating entry into table {}: {}", table, entry);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Inserting entry into table {}", table);
-                    return t.insert_or_update(entry)
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                },
+    /// Starts the thread that reloads the database every duration if its file was changed
+    /// by another process; the thread is stopped when the last clone of the Client is
+    /// dropped.
+    fn start_reload(&mut self, duration: Duration) -> Worker {
+        let mut c = self.clone();
+        let (tx, rx) = std::sync::mpsc::sync_channel(0);
+        let h = std::thread::spawn( move || loop {
+                if rx.try_recv().is_ok() {
+                    log_event!(Subsystem::Save, TRACE, "Breaking");
+                    break
+                };
+
+                log_event!(Subsystem::Save, TRACE, "Sleeping for {:?}", duration);
+                sleep(duration);
+
+                match c.reload() {
+                    Ok(true) => log_event!(Subsystem::Save, DEBUG, "Database reloaded"),
+                    Ok(false) => log_event!(Subsystem::Save, TRACE, "Database unchanged"),
+                    Err(e) => error!("Unable to reload database: {}", e),
+                };
             }
+        );
+
+        Worker{
+            handle: Some(h),
+            killer: tx,
+        }
+    }
+
+    /// Starts the thread that saves the database every interval if it was changed since
+    /// its last save, grouping the mutations made in between into one save, and once more
+    /// when stopped; the thread is stopped when the last clone of the Client is dropped.
+    fn start_group_commit(&mut self, interval: Duration) -> Worker {
+        let mut c = self.clone();
+        let (tx, rx) = std::sync::mpsc::sync_channel(0);
+        let h = std::thread::spawn( move || loop {
+                let stopped = !matches!(rx.recv_timeout(interval), Err(std::sync::mpsc::RecvTimeoutError::Timeout));
+
+                match c.durability.sequences() {
+                    Ok((written, durable)) if written > durable => {
+                        log_event!(Subsystem::Save, TRACE, "Committing mutations {} to {}", durable + 1, written);
+                        match c.save() {
+                            Ok(()) => log_event!(Subsystem::Save, DEBUG, "Committed mutations up to {}", written),
+                            Err(e) => {
+                                error!("Unable to commit database: {}", e);
+                                c.durability.failed();
+                            },
+                        };
+                    },
+                    Ok(_) => log_event!(Subsystem::Save, TRACE, "No mutations to commit"),
+                    Err(e) => error!("Unable to commit database: {}", e),
+                };
+
+                if stopped {
+                    log_event!(Subsystem::Save, TRACE, "Breaking");
+                    break
+                };
+            }
+        );
+
+        Worker{
+            handle: Some(h),
+            killer: tx,
+        }
+    }
+
+    /// Takes the read lock of the database, recording the time waited for it against the
+    /// method; see DatabaseStats::lock_waits.  A Client returned by non_blocking does not
+    /// wait, returning DatabaseError::WouldBlock if the lock is held.
+    fn read_lock(&self, method: &'static str) -> Result<sync::RwLockReadGuard<'_, Database>, DatabaseError> {
+        let started = Instant::now();
+        let database = match self.wait_for_locks {
+            true => self.database.read().map_err(|_| DatabaseError::UnableToGetLock),
+            false => self.database.try_read().map_err(|e| lock_not_taken(e, method)),
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        match database {
+            Ok(database) => {
+                self.record_lock_wait(method, started.elapsed());
+                Ok(database)
+            },
+            Err(DatabaseError::WouldBlock) => Err(DatabaseError::WouldBlock),
+            Err(e) => {
+                error!("Unable to get database lock");
+                Err(e)
+            },
+        }
     }
 
-    /// Updates an existing entry in the specified table within the database of the associated client.
-    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType};
-    /// use persistent_keystore_rs::{Entry, Field};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("updateentry.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// let entry = Entry::new()
-    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    ///     .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// c.update("MyTable".to_string(), entry).unwrap();
-    /// # std::fs::remove_file("updateentry.db").unwrap();
-    /// ```
-    fn update(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
-        trace!("Updating entry into table {}: {}", table, entry);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Updating entry {} in table {}", entry.primary_field, table);
-                    return t.update(entry)
+    /// Takes the write lock of the database, as read_lock
+    fn write_lock(&self, method: &'static str) -> Result<sync::RwLockWriteGuard<'_, Database>, DatabaseError> {
+        let started = Instant::now();
+        let database = match self.wait_for_locks {
+            true => self.database.write().map_err(|_| DatabaseError::UnableToGetLock),
+            false => self.database.try_write().map_err(|e| lock_not_taken(e, method)),
+        };
+        match database {
+            Ok(database) => {
+                self.record_lock_wait(method, started.elapsed());
+                Ok(database)
+            },
+            Err(DatabaseError::WouldBlock) => Err(DatabaseError::WouldBlock),
+            Err(e) => {
+                error!("Unable to get database lock");
+                Err(e)
+            },
+        }
+    }
+
+    /// Returns a clone of the Client whose methods return DatabaseError::WouldBlock rather
+    /// than wait for the database lock
+    fn without_waiting(&self) -> Client {
+        let mut c = self.clone();
+        c.wait_for_locks = false;
+        c
+    }
+
+    /// Takes the write lock of the database for a mutation, which calls applied on the
+    /// guard once made to assign the mutation its sequence number; see DatabaseClient::flush
+    fn write_database(&self, method: &'static str) -> Result<commit::WriteGuard<'_>, DatabaseError> {
+        Ok(commit::WriteGuard::new(self.write_lock(method)?, &self.durability))
+    }
+
+    /// Records the time a method waited for the database lock
+    fn record_lock_wait(&self, method: &'static str, wait: Duration) {
+        if let Ok(mut lock_waits) = self.lock_waits.lock() {
+            lock_waits.entry(method).or_default().record(wait);
+        };
+    }
+
+    /// Sends LifecycleEvent::Expiring for each table with entries expiring within the
+    /// supplied duration; tables not yet read from their segment are skipped, as by prune
+    fn warn_expiring(&self, within: Duration) -> Result<(), DatabaseError> {
+        let mut events = Vec::new();
+        match self.read_lock("warn_expiring") {
+            Ok(database) => {
+                let now = self.clock.timestamp();
+                for t in database.list_tables() {
+                    if !database.is_loaded(&t) {
+                        continue
+                    };
+                    let Ok(table) = database.get_table_ref(&t) else {
+                        continue
+                    };
+                    let entries: Vec<(Field, Duration)> = table.expiring(within, now)?.into_iter()
+                        .map(|(e, ttl)| (e.primary_field.clone(), ttl))
+                        .collect();
+                    if !entries.is_empty() {
+                        log_event!(Subsystem::Prune, DEBUG, "{} entries of table {} expire within {:?}", entries.len(), t, within);
+                        events.push(LifecycleEvent::Expiring{table: t, entries});
+                    };
+                };
+            },
+            Err(e) => return Err(e),
+        };
+        for event in events {
+            self.lifecycle.emit(event);
+        };
+        Ok(())
+    }
+
+    /// Returns the result of the named operation, sending LifecycleEvent::Error to the
+    /// subscribers of the lifecycle events of the Client if it failed
+    fn report<T>(&self, operation: &'static str, result: Result<T, DatabaseError>) -> Result<T, DatabaseError> {
+        if let Err(e) = &result {
+            self.lifecycle.emit(LifecycleEvent::Error{operation, error: e.to_string()});
+        };
+        result
+    }
+
+    /// Serializes and writes the database to its file, keeping the Leases held in the file
+    /// if Leases were ever taken on it; returns the number of bytes written
+    fn save_file(&self) -> Result<u64, DatabaseError> {
+        let _leases = self.lock_leases(false)?;
+        self.write_database_file()
+    }
+
+    /// Takes the LeaseLock of the database file, creating its lock file if create, then
+    /// replaces the table of Leases with that of the file, if it has one; so that the
+    /// Leases acquired, renewed and released by other processes are seen and not
+    /// overwritten.  Returns None, taking no lock, if the lock file does not exist and is
+    /// not created, as no Lease was ever taken on the file.
+    fn lock_leases(&self, create: bool) -> Result<Option<LeaseLock>, DatabaseError> {
+        let path = self.path()?;
+        let lock = match LeaseLock::acquire(&path, create)? {
+            Some(l) => l,
+            None => return Ok(None),
+        };
+        let compressed = match self.read_contents(&path) {
+            Ok((c, _)) => c,
+            Err(DatabaseError::DatabaseIoError(e)) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Some(lock)),
+            Err(e) => return Err(e),
+        };
+        if compressed.is_empty() {
+            return Ok(Some(lock))
+        };
+        if let Some(mut table) = decode_table(&compressed, LEASE_TABLE)? {
+            log_event!(Subsystem::Save, TRACE, "Reading leases of database file {:?}", path);
+            table.rebase(self.clock.timestamp());
+            self.write_lock("lock_leases")?.replace_table(table);
+        };
+        Ok(Some(lock))
+    }
+
+    /// Makes the changes to the Leases of the database made by f holding the LeaseLock of
+    /// the database file, reading the Leases of the file before and saving the database
+    /// after; so that Leases are atomic across separate processes sharing the file
+    pub(crate) fn with_leases<R, F>(&mut self, f: F) -> Result<R, DatabaseError>
+    where
+        F: FnOnce(&mut Client) -> Result<R, DatabaseError>,
+    {
+        let _leases = self.lock_leases(true)?;
+        let result = f(self)?;
+        self.write_database_file()?;
+        Ok(result)
+    }
+
+    /// Serializes and writes the database to its file, as save_file, without taking the
+    /// LeaseLock
+    fn write_database_file(&self) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Saving database");
+        if let Ok(raw_file) = self.raw_file.lock() {
+            let tracker = progress::Tracker::new(&self.progress, &self.clock, ProgressOperation::Save);
+            let (mut output, sequence, entries) = match self.read_lock("save") {
+                Ok(database) => {
+                    database.sync_spill()?;
+                    tracker.bytes(ProgressPhase::Serializing, 0, None);
+                    (database.serialize()?, self.durability.sequences()?.0, database.entry_count())
                 },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                }
-            }
+                Err(e) => return Err(e),
+            };
+            tracker.bytes(ProgressPhase::Serializing, output.len() as u64, Some(output.len() as u64));
+
+            log_event!(Subsystem::Save, DEBUG, "Saving database {:?}", raw_file);
+            let written = self.write_file(raw_file.as_path(), &output, &tracker);
+            secret::wipe(&mut output);
+            let written = written?;
+            self.durability.saved(sequence);
+            tracker.done(written, entries as u64);
+            return Ok(written)
         };
-        error!("Unable to get database lock");
+        error!("Unable to get file mutex");
         Err(DatabaseError::UnableToGetLock)
     }
 
-    /// Get an existing entry from the specified table within the database of the associated client.
-    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
-    /// use persistent_keystore_rs::Field;
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("getentry.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// # let entry = Entry::new()
-    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// let e = c.get("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
-    /// # std::fs::remove_file("getentry.db").unwrap();
-    /// ```
-    fn get(&mut self, table: String, primary_field: Field) -> Result<Entry, DatabaseError> {
-        trace!("Getting entry {} from table {}", primary_field, table);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Getting entry {} from table {}", primary_field, table);
-                    let item = t.get(&primary_field)?;
-                    return Ok(item.clone());
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                }
-            }
+    /// Compacts and saves the database; returns the number of bytes written and of orphaned
+    /// files removed
+    fn compact_file(&self) -> Result<(u64, u64), DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Compacting database");
+        let _leases = self.lock_leases(false)?;
+        if let Ok(raw_file) = self.raw_file.lock() {
+            self.write_lock("compact")?.shrink_to_fit();
+            let tracker = progress::Tracker::new(&self.progress, &self.clock, ProgressOperation::Compact);
+            let database = self.read_lock("compact")?;
+            database.sync_spill()?;
+            tracker.bytes(ProgressPhase::Serializing, 0, None);
+            let mut output = database.serialize()?;
+            tracker.bytes(ProgressPhase::Serializing, output.len() as u64, Some(output.len() as u64));
+            let sequence = self.durability.sequences()?.0;
+            log_event!(Subsystem::Save, DEBUG, "Saving database {:?}", raw_file);
+            let written = self.write_file(raw_file.as_path(), &output, &tracker);
+            secret::wipe(&mut output);
+            let written = written?;
+            self.durability.saved(sequence);
+
+            let removed = database.collect_blobs()?;
+            log_event!(Subsystem::Save, DEBUG, "Removed {} orphaned files", removed);
+            tracker.done(written, database.entry_count() as u64);
+            return Ok((written, removed))
         };
-        error!("Unable to get database lock");
+        error!("Unable to get file mutex");
         Err(DatabaseError::UnableToGetLock)
     }
 
-    /// Delete an existing entry from the specified table within the database of the associated client.
-    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
-    /// use persistent_keystore_rs::Field;
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("delete.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// # let entry = Entry::new()
-    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// c.delete("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
-    /// # std::fs::remove_file("delete.db").unwrap();
-    /// ```
-    fn delete(&mut self, table: String, primary_field: Field) -> Result<(), DatabaseError> {
-        trace!("Deleting entry {} from table {}", primary_field, table);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Deleting entry {} from table {}", primary_field, table);
-                    return t.delete(primary_field)
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                },
+    /// Removes the expired entries of every table; returns the number of entries removed.
+    /// The tables are pruned in a copy of the database taken under the read lock, sharing
+    /// every table until pruning copies it, which is then swapped in under the write lock,
+    /// so that readers are neither blocked by the prune nor see it half done.  If the
+    /// database was written in the meantime the copy is discarded and the database pruned
+    /// in place under the write lock instead.  If nothing was pruned the write lock is not
+    /// taken, and no write is recorded.
+    /// Tables whose expiration schedule cannot be evaluated are skipped, and the first such
+    /// error is returned once the others are pruned.
+    /// Expired entries are deleted as by delete, applying the OnDelete behavior of every
+    /// reference to them; entries whose delete a reference restricts are kept.
+    fn prune_entries(&mut self) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Prune, TRACE, "Pruning database");
+        let (base, written) = match self.read_lock("prune") {
+            Ok(database) => (database.clone(), self.durability.sequences()?.0),
+            Err(e) => return Err(e),
+        };
+        let current_time = self.clock.timestamp();
+
+        if self.dry_run {
+            let mut unevaluated = None;
+            for t in base.list_tables() {
+                let Some(until) = Self::prune_until(&base, &t, &mut unevaluated) else {
+                    continue
+                };
+                let table = base.get_table_ref(&t)?;
+                let mut expired = Vec::new();
+                let mut cursor = Some(0);
+                while let Some(after) = cursor {
+                    let (chunk, next) = table.keys_updated_between(after, until, PRUNE_CHUNK_SIZE);
+                    expired.extend(base.deletable_keys(&t, table.expired_keys(&chunk, current_time))?);
+                    cursor = next;
+                };
+                if !expired.is_empty() {
+                    self.record_dry_run(DryRunAction::DeleteEntries{table: t, keys: expired})?;
+                };
+            };
+            return match unevaluated {
+                Some(e) => Err(e),
+                None => Ok(0),
             }
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+
+        let mut copy = base.clone();
+        let (mut pruned, mut changed, mut unevaluated) = Self::prune_database(&mut copy, current_time)?;
+        if changed {
+            let mut database = self.write_lock("prune")?;
+            if self.durability.sequences()?.0 == written && database.shares_tables(&base) {
+                *database = copy;
+            } else {
+                log_event!(Subsystem::Prune, DEBUG, "Database written while being pruned; pruning it in place");
+                drop((base, copy));
+                (pruned, changed, unevaluated) = Self::prune_database(&mut database, current_time)?;
+            };
+            if changed {
+                self.durability.record_write();
+            };
+        } else {
+            log_event!(Subsystem::Prune, DEBUG, "Nothing to prune");
+        };
+
+        match unevaluated {
+            Some(e) => Err(e),
+            None => Ok(pruned),
+        }
     }
 
-    /// Delete all entries matching the supplied criteria.
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
-    /// use persistent_keystore_rs::Field;
-    /// use std::collections::HashMap;
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("deletemany.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// # let entry = Entry::new()
-    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// # let entry2 = Entry::new()
-    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
-    /// # let entry3 = Entry::new()
-    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
-    /// let mut criteria: HashMap<String, Field> = HashMap::new();
-    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
-    /// c.delete_many("MyTable".to_string(), criteria).unwrap();
-    /// # assert_eq!(c.scan("MyTable".to_string()).unwrap().len(), 1);
-    /// # std::fs::remove_file("deletemany.db").unwrap();
-    /// ```
-    fn delete_many(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<u64, DatabaseError> {
-        trace!("Deleting many from table {}", table);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    let items = t.scan()?;
-                    let mut deleted = 0;
-                    'L:
-                    for i in items {
-                        for (k, v) in &criteria {
-                            match &i.fields.get_key_value(k) {
-                                Some((_, value)) => {
-                                    if v != *value {
-                                        trace!("{} does not meet criteria", i.primary_field);
-                                        continue 'L;
-                                    }
-                                },
-                                None => {
-                                    trace!("{} does not meet criteria", i.primary_field);
-                                    continue 'L
-                                },
-                            };
-                            
-                        };
-                        debug!("Deleting entry {} from table {}", i.primary_field, table);
-                        t.delete(i.primary_field)?;
-                        deleted+=1;
+    /// Returns the update sequence of the entry last updated in the table, up to which it
+    /// is pruned, or None if it is not pruned: the table has no expiration setting, has not
+    /// been read from its segment, or its expiration schedule cannot be evaluated, the
+    /// first such error being kept in unevaluated
+    fn prune_until(database: &Database, t: &String, unevaluated: &mut Option<DatabaseError>) -> Option<u64> {
+        if !database.is_loaded(t) {
+            log_event!(Subsystem::Prune, DEBUG, "Table {} has not been read from its segment", t);
+            return None
+        };
+        let table = database.get_table_ref(t).ok()?;
+        let history_max_age = table.history_retention.and_then(|r| r.max_age);
+        if table.expire_after.is_none() && table.expire_schedule.is_none() && history_max_age.is_none() {
+            log_event!(Subsystem::Prune, DEBUG, "No expiration setting for table {}", t);
+            return None
+        };
+        if let Some(Err(e)) = table.expire_schedule.as_ref().map(|s| s.validate()) {
+            log_event!(Subsystem::Prune, WARN, "Unable to evaluate the expiration schedule of table {}: {}", t, e);
+            unevaluated.get_or_insert(e);
+            return None
+        };
+        Some(table.update_sequence())
+    }
+
+    /// Removes the expired entries of every table of the database, returning the number
+    /// removed, whether the database was changed, counting previous versions removed from
+    /// histories, and the first expiration schedule that could not be evaluated.  Each
+    /// table is visited in chunks through a cursor over the update sequence of its entries,
+    /// so that the keys of the table are not copied.
+    fn prune_database(database: &mut Database, now: Timestamp) -> Result<(u64, bool, Option<DatabaseError>), DatabaseError> {
+        let mut pruned = 0;
+        let mut histories = false;
+        let mut unevaluated = None;
+        for t in database.list_tables() {
+            let Some(until) = Self::prune_until(database, &t, &mut unevaluated) else {
+                continue
+            };
+
+            let mut removed = 0;
+            let mut cursor = Some(0);
+            while let Some(after) = cursor {
+                let table = database.get_table_ref(&t)?;
+                let (chunk, next) = table.keys_updated_between(after, until, PRUNE_CHUNK_SIZE);
+                let expired = table.expired_keys(&chunk, now);
+                if table.history_retention.and_then(|r| r.max_age).is_some() {
+                    histories |= database.get_table(&t)?.prune_histories(&chunk, now);
+                };
+                removed += database.delete_unrestricted(&t, expired, now)?;
+                cursor = next;
+            };
+            log_event!(Subsystem::Prune, DEBUG, "Pruned {} entries from table {}", removed, t);
+            pruned += removed;
+        };
+        Ok((pruned, pruned > 0 || histories, unevaluated))
+    }
+
+    /// Replaces the database with its file if the file was changed; returns true if it was
+    fn reload_file(&mut self) -> Result<bool, DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Checking database file for changes");
+        if let Ok(raw_file) = self.raw_file.lock() {
+            if let Ok(mut signature) = self.signature.lock() {
+                let metadata = std::fs::metadata(self.root.as_ref().map_or(raw_file.as_path(), |r| r.file()))?;
+                if let Some(last) = *signature {
+                    if last.modified.is_some() && last.modified == metadata.modified().ok() && last.len == metadata.len() {
+                        log_event!(Subsystem::Save, TRACE, "Database file {:?} is unchanged", raw_file);
+                        return Ok(false)
                     };
-                    return Ok(deleted)
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                },
+                };
+
+                let (compressed, current) = self.read_contents(raw_file.as_path())?;
+                if signature.is_some_and(|last| last.len == current.len && last.checksum == current.checksum) {
+                    log_event!(Subsystem::Save, DEBUG, "Database file {:?} was rewritten with the same contents", raw_file);
+                    *signature = Some(current);
+                    return Ok(false)
+                };
+
+                let (mut reloaded, _dictionary) = decode_file(&compressed)?;
+                reloaded.rebase(self.clock.timestamp());
+                reloaded.attach_files(raw_file.as_path())?;
+                match self.write_lock("reload") {
+                    Ok(mut database) => {
+                        let (written, durable) = self.durability.sequences()?;
+                        if written > durable {
+                            log_event!(Subsystem::Save, WARN, "Not reloading database {:?}: {} mutations are not saved", raw_file, written - durable);
+                            return Err(DatabaseError::UnsavedChanges(written - durable))
+                        };
+                        reloaded.set_case_insensitive(database.is_case_insensitive());
+                        *database = reloaded
+                    },
+                    Err(e) => return Err(e),
+                };
+                #[cfg(feature = "zstd")]
+                if let Ok(mut dictionary) = self.dictionary.lock() {
+                    *dictionary = _dictionary;
+                };
+                *signature = Some(current);
+                log_event!(Subsystem::Save, INFO, "Reloaded database {:?}", raw_file);
+                return Ok(true)
             };
         };
-        error!("Unable to get database lock");
+        error!("Unable to get file mutex");
         Err(DatabaseError::UnableToGetLock)
     }
 
-    /// Returns all entries from the specified table within the database of the associated client.
-    /// If no entries exist, will return an empty vec
+    /// Compresses the serialized database and writes it behind its header to the database
+    /// file, or the container holding the database, recording its signature; returns the
+    /// number of bytes written
+    fn write_file(&self, path: &Path, output: &[u8], tracker: &progress::Tracker) -> Result<u64, DatabaseError> {
+        let started = self.clock.instant();
+        tracker.bytes(ProgressPhase::Compressing, 0, Some(output.len() as u64));
+        #[cfg(feature = "zstd")]
+        let compressed = match self.dictionary.lock().ok().and_then(|d| d.clone()) {
+            Some(d) => dictionary::compress(output, &d)?,
+            None => compress_prepend_size(output),
+        };
+        #[cfg(not(feature = "zstd"))]
+        let compressed = compress_prepend_size(output);
+        tracker.bytes(ProgressPhase::Compressing, output.len() as u64, Some(output.len() as u64));
+        let contents = header::prepend(&FileInfo::new(self.clock.now()), &compressed)?;
+        let written = match &self.root {
+            Some(root) => root.write(&contents)?,
+            None => {
+                let mut f = OpenOptions::new()
+                    .write(true)
+                    .read(true)
+                    .create(true)
+                    .truncate(true)
+                    .append(false)
+                    .open(path)?;
+                f.seek(SeekFrom::Start(0))?;
+                tracker.write_all(&mut f, &contents)?;
+                f.flush()?;
+                f.sync_all()?;
+                FileSignature::new(&f, &contents)
+            },
+        };
+        if let Ok(mut signature) = self.signature.lock() {
+            *signature = Some(written);
+        };
+
+        let save = SaveStats{
+            serialized_bytes: output.len() as u64,
+            compressed_bytes: compressed.len() as u64,
+            written_bytes: contents.len() as u64,
+            duration: self.clock.instant().saturating_duration_since(started),
+        };
+        log_event!(Subsystem::Save, DEBUG, "Saved {} bytes, compressed to {}, in {:?}", save.serialized_bytes, save.compressed_bytes, save.duration);
+        if let Ok(mut writes) = self.writes.lock() {
+            writes.record(save);
+        };
+        if let Some(budget) = self.size_budget.filter(|b| save.written_bytes > *b) {
+            log_event!(Subsystem::Save, WARN, "Database file {:?} of {} bytes exceeds its size budget of {} bytes", path, save.written_bytes, budget);
+            self.lifecycle.emit(LifecycleEvent::SizeBudgetExceeded{bytes: save.written_bytes, budget});
+        };
+        Ok(contents.len() as u64)
+    }
+
+    /// Reads the database file at the supplied path, or the database from the container
+    /// holding it, returning its contents and signature
+    fn read_contents(&self, path: &Path) -> Result<(Vec<u8>, FileSignature), DatabaseError> {
+        match &self.root {
+            Some(root) => root.read(),
+            None => read_file(path),
+        }
+    }
+
+    /// Returns the path of the database file
+    fn path(&self) -> Result<PathBuf, DatabaseError> {
+        match self.raw_file.lock() {
+            Ok(raw_file) => Ok(raw_file.clone()),
+            Err(_) => {
+                error!("Unable to get file mutex");
+                Err(DatabaseError::UnableToGetLock)
+            },
+        }
+    }
+
+    /// Reads the entry with the supplied primary field, treating an expired entry as
+    /// missing, and writes the entry returned by f, if any, under a single write lock; so
+    /// that the read and write are atomic with respect to every clone of the Client.  The
+    /// operation is authorized first, as the supplied Operation.
+    pub(crate) fn read_modify_write<R, F>(&self, operation: Operation, table: &String, primary_field: &Field, f: F) -> Result<R, DatabaseError>
+    where
+        F: FnOnce(Option<Arc<Entry>>, std::time::SystemTime) -> Result<(Option<Entry>, R), DatabaseError>,
+    {
+        self.authorize(operation, table, Some(primary_field))?;
+        log_event!(Subsystem::Write, TRACE, "Modifying entry {} of table {}", primary_field, table);
+        let mut database = self.write_database("read_modify_write")?;
+        let now = self.clock.timestamp();
+        let current = match database.get_table_ref(table) {
+            Ok(t) => match t.time_to_live(primary_field, now) {
+                Ok(Some(Duration::ZERO)) | Err(_) => None,
+                Ok(_) => t.lookup(primary_field),
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table.clone()))
+            },
+        };
+
+        let (entry, result) = f(current, now.wall)?;
+        if let Some(entry) = entry {
+            self.check_write(&mut database, table, &entry, WriteKind::Update)?;
+            database.get_table(table)?.update_at(entry, now)?;
+            database.applied();
+        };
+        return Ok(result)
+    }
+
+    /// Deletes all entries of the table meeting every Condition under a single write lock,
+    /// returning the number deleted
+    pub(crate) fn delete_where(&self, table: String, conditions: &HashMap<String, Condition>, token: &CancellationToken) -> Result<u64, DatabaseError> {
+        self.delete_where_limit(table, conditions, usize::MAX, token).map(|b| b.deleted)
+    }
+
+    /// Deletes up to limit entries of the table meeting every Condition under a single
+    /// write lock, returning the number deleted and whether more remain
+    fn delete_where_limit(&self, table: String, conditions: &HashMap<String, Condition>, limit: usize, token: &CancellationToken) -> Result<DeleteBatch, DatabaseError> {
+        if is_system_table(&table) {
+            error!("Table {} is read-only", table);
+            return Err(DatabaseError::ReadOnlyTable(table))
+        };
+        let mut database = self.write_database("delete_where")?;
+        let (items, more) = match database.get_table_ref(&table) {
+            Ok(t) => t.filter_limit(conditions, self.clock.now(), limit, token)?,
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+
+        let keys: Vec<Field> = items.iter().map(|i| i.primary_field.clone()).collect();
+        if self.dry_run {
+            let deleted = keys.len() as u64;
+            self.record_dry_run(DryRunAction::DeleteEntries{table, keys})?;
+            return Ok(DeleteBatch{deleted, more})
+        };
+        log_event!(Subsystem::Write, DEBUG, "Deleting {} entries from table {}", items.len(), table);
+        let deleted = database.delete_entries(&table, keys, self.clock.timestamp())?;
+        if deleted > 0 {
+            database.applied();
+        };
+        Ok(DeleteBatch{deleted, more})
+    }
+
+    /// Notifies the observers of the Client of an operation about to be attempted, then
+    /// returns DatabaseError::AccessDenied if its authorizer denies the operation
+    fn authorize(&self, operation: Operation, table: &str, key: Option<&Field>) -> Result<(), DatabaseError> {
+        self.access.authorize(operation, table, key)
+    }
+
+    /// Makes the write to the table unless a write with the idempotency key was already
+    /// made, recording the key, under a single write lock; returns true if the write was
+    /// made
+    fn write_once(&self, table: String, entry: Entry, idempotency_key: Option<String>, kind: WriteKind) -> Result<bool, DatabaseError> {
+        let mut database = self.write_database("write_once")?;
+        if let Some(key) = idempotency_key.as_ref().filter(|k| database.idempotency().contains(k)) {
+            log_event!(Subsystem::Write, DEBUG, "Skipping write to table {} with idempotency key {}", table, key);
+            return Ok(false)
+        };
+        self.check_write(&mut database, &table, &entry, kind)?;
+        match database.get_table(&table) {
+            Ok(t) => {
+                log_event!(Subsystem::Write, DEBUG, "Writing entry {} to table {}", entry.primary_field, table);
+                t.write_at(entry, kind, self.clock.timestamp())?;
+            },
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            },
+        };
+        if let Some(key) = idempotency_key {
+            database.idempotency().record(key, self.idempotency_capacity);
+        };
+        database.applied();
+        return Ok(true)
+    }
+
+    /// Records a change not made because the Client is a dry run
+    fn record_dry_run(&self, action: DryRunAction) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Write, INFO, "Dry run; would {}", action);
+        match self.dry_runs.lock() {
+            Ok(mut actions) => {
+                actions.push(action);
+                Ok(())
+            },
+            Err(_) => {
+                error!("Unable to get dry run mutex");
+                Err(DatabaseError::UnableToGetLock)
+            },
+        }
+    }
+
+    /// Validates the entry and its references for a write of the supplied kind, then makes
+    /// room for it within max_entries of its table and the memory limit of the client, if
+    /// any; nothing is evicted for a write that would be refused.  Entries are evicted as by
+    /// delete, so entries whose delete a reference restricts are kept.
+    fn check_write(&self, database: &mut Database, table: &String, entry: &Entry, kind: WriteKind) -> Result<(), DatabaseError> {
+        if let Err(e) = database.get_table_ref(table).and_then(|t| t.check_write(entry, kind)) {
+            if !matches!(e, DatabaseError::TableDoesNotExist(_)) {
+                error!("Unable to write entry {} to table {}: {}", entry.primary_field, table, e);
+                return Err(e)
+            };
+        };
+        if let Err(e) = database.check_references(table, entry) {
+            error!("Unable to write entry {} to table {}: {}", entry.primary_field, table, e);
+            return Err(e)
+        };
+
+        let now = self.clock.timestamp();
+        if let Err(e) = database.make_room(table, &entry.primary_field, now) {
+            error!("Unable to write entry {} to table {}: {}", entry.primary_field, table, e);
+            return Err(e)
+        };
+        if let Some((max_bytes, policy)) = self.max_memory {
+            if let Err(e) = database.reserve(table, entry, max_bytes, policy, now) {
+                error!("Unable to write entry {} to table {}: {}", entry.primary_field, table, e);
+                return Err(e)
+            };
+        };
+        Ok(())
+    }
+}
+
+/// Action taken when opening a database file that is empty, as left by a first save that
+/// did not complete
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyFilePolicy {
+    /// Opening fails with DatabaseError::EmptyDatabaseFile
+    #[default]
+    Error,
+    /// The database is opened with no tables, replacing the file when next saved
+    TreatAsNew,
+    /// The database is read from its backup, the file at the same path with `.bak`
+    /// appended; opening fails with DatabaseError::EmptyDatabaseFile if the backup is
+    /// missing or also empty
+    UseBackup,
+}
+
+/// Maps a failure to take a lock without waiting to DatabaseError::WouldBlock if the lock
+/// is held, or DatabaseError::UnableToGetLock if it is poisoned
+fn lock_not_taken<T>(e: std::sync::TryLockError<T>, method: &str) -> DatabaseError {
+    match e {
+        std::sync::TryLockError::WouldBlock => {
+            debug!("Database lock is held; not waiting for it in {}", method);
+            DatabaseError::WouldBlock
+        },
+        std::sync::TryLockError::Poisoned(_) => DatabaseError::UnableToGetLock,
+    }
+}
+
+/// Returns the path of the backup of the database file at the supplied path
+pub(crate) fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Builder Pattern for configuring a Client before creating or opening its database
+pub struct ClientBuilder {
+    path: PathBuf,
+    sync_interval: Option<Duration>,
+    sync_jitter: Duration,
+    save_gate: Option<SaveGate>,
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGenerator>,
+    max_memory: Option<(usize, CapacityPolicy)>,
+    size_budget: Option<u64>,
+    verify_interval: Option<Duration>,
+    expiry_warning: Option<Duration>,
+    compact_interval: Option<(Duration, Duration)>,
+    reload_interval: Option<Duration>,
+    gc_retention: Option<Duration>,
+    empty_file: EmptyFilePolicy,
+    dry_run: bool,
+    idempotency_capacity: usize,
+    group_commit: Option<Duration>,
+    observers: Vec<Arc<dyn OperationObserver>>,
+    authorizer: Option<access::Authorizer>,
+    progress: Option<progress::Reporter>,
+    lifecycle: LifecycleEvents,
+    segmented: bool,
+    case_insensitive_tables: bool,
+    container: Option<(Container, container::Root)>,
+}
+
+impl ClientBuilder {
+    /// Sets the interval at which a background thread prunes and saves the database.
+    /// When opening an existing database this replaces the persisted interval.
+    pub fn sync_interval(mut self, duration: Duration) -> Self {
+        self.sync_interval = Some(duration);
+        self
+    }
+
+    /// Delays each background sync by a random duration of up to max_jitter beyond the
+    /// sync interval, chosen afresh for every sync, so that Clients created together do
+    /// not save together.  The jitter is not persisted with the database.
     /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("scan.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// # let entry = Entry::new()
-    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// # let entry2 = Entry::new()
-    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("syncjitter.db"))
+    ///     .sync_interval(Duration::from_millis(30))
+    ///     .sync_jitter(Duration::from_millis(10))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("syncjitter.db").unwrap();
+    /// ```
+    pub fn sync_jitter(mut self, max_jitter: Duration) -> Self {
+        self.sync_jitter = max_jitter;
+        self
+    }
+
+    /// Limits the background saves and compactions of the Client, and of every other
+    /// Client given a clone of the gate, to the number the SaveGate allows at once
+    pub fn save_gate(mut self, gate: SaveGate) -> Self {
+        self.save_gate = Some(gate);
+        self
+    }
+
+    /// Sets the Clock used for entry timestamps and expiration; defaults to SystemClock
+    /// ```
+    /// use persistent_keystore_rs::{Client, ManualClock};
+    /// use std::path::Path;
+    /// use std::sync::Arc;
+    /// use std::time::SystemTime;
+    /// let clock = Arc::new(ManualClock::new(SystemTime::UNIX_EPOCH));
+    /// let c = Client::builder(Path::new("clock.db"))
+    ///     .clock(clock.clone())
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("clock.db").unwrap();
+    /// ```
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the IdGenerator of the identifiers the Client generates, such as the ids of the
+    /// Sessions of DatabaseClient::session_store; defaults to RandomIdGenerator.  Together
+    /// with a ManualClock, from which entry timestamps are taken, a SequentialIdGenerator
+    /// makes tests that create such entries produce the same databases on every run.
+    /// ```
+    /// use persistent_keystore_rs::{Client, ManualClock, SequentialIdGenerator};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// use std::sync::Arc;
+    /// use std::time::{Duration, SystemTime};
+    /// let mut c = Client::builder(Path::new("idgenerator.db"))
+    ///     .clock(Arc::new(ManualClock::new(SystemTime::UNIX_EPOCH)))
+    ///     .id_generator(Arc::new(SequentialIdGenerator::new(1)))
+    ///     .build().unwrap();
+    /// let mut sessions = c.session_store("Sessions".to_string(), Duration::from_secs(60)).unwrap();
+    /// let session = sessions.create_session("alice", String::new()).unwrap();
+    /// assert_eq!(session.id, "00000000000000000000000000000001");
+    /// assert_eq!(session.created, SystemTime::UNIX_EPOCH);
+    /// # std::fs::remove_file("idgenerator.db").unwrap();
+    /// ```
+    pub fn id_generator(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Limits the approximate in-memory size of all entries in the database.  Writes that
+    /// would exceed the limit either fail with DatabaseError::CapacityExceeded or evict the
+    /// least recently updated entries of the table being written, depending on the
+    /// CapacityPolicy; entries are evicted as by delete, skipping those whose delete a
+    /// reference restricts.  The limit is not persisted with the database.
+    /// ```
+    /// use persistent_keystore_rs::{Client, CapacityPolicy};
+    /// use std::path::Path;
+    /// let c = Client::builder(Path::new("maxmemory.db"))
+    ///     .max_memory(64 * 1024 * 1024, CapacityPolicy::Reject)
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("maxmemory.db").unwrap();
+    /// ```
+    pub fn max_memory(mut self, max_bytes: usize, policy: CapacityPolicy) -> Self {
+        self.max_memory = Some((max_bytes, policy));
+        self
+    }
+
+    /// Sets the size of the database file in bytes above which saves, and compactions, log a
+    /// warning and send LifecycleEvent::SizeBudgetExceeded, so that growth is noticed before
+    /// the disk fills.  DatabaseStats::over_size_budget reports whether the most recent save
+    /// exceeded it.  Saves are not prevented, and the budget is not persisted with the
+    /// database.
+    /// ```
+    /// use persistent_keystore_rs::{Client, LifecycleEvent, LifecycleEvents};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let events = LifecycleEvents::new();
+    /// let rx = events.subscribe();
+    /// let mut c = Client::builder(Path::new("sizebudget.db"))
+    ///     .size_budget(1)
+    ///     .lifecycle_events(events)
+    ///     .build().unwrap();
+    /// assert!(matches!(rx.try_recv().unwrap(), LifecycleEvent::SizeBudgetExceeded{budget: 1, ..}));
+    /// assert!(c.stats().unwrap().over_size_budget);
+    /// # drop(c);
+    /// # std::fs::remove_file("sizebudget.db").unwrap();
+    /// ```
+    pub fn size_budget(mut self, max_bytes: u64) -> Self {
+        self.size_budget = Some(max_bytes);
+        self
+    }
+
+    /// Runs verify on the background sync thread at most once per interval, logging any
+    /// problems found.  Has no effect unless a sync interval is set.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("verifyinterval.db"))
+    ///     .sync_interval(Duration::from_millis(30))
+    ///     .verify_interval(Duration::from_secs(3600))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("verifyinterval.db").unwrap();
+    /// ```
+    pub fn verify_interval(mut self, interval: Duration) -> Self {
+        self.verify_interval = Some(interval);
+        self
+    }
+
+    /// Runs compact on the background sync thread in place of a save, at most once per
+    /// interval plus a random delay of up to jitter, chosen afresh after each compaction so
+    /// that Clients sharing a host do not compact together.  Has no effect unless a sync
+    /// interval is set.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("compactinterval.db"))
+    ///     .sync_interval(Duration::from_millis(30))
+    ///     .compact_interval(Duration::from_secs(3600), Duration::from_secs(600))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("compactinterval.db").unwrap();
+    /// ```
+    pub fn compact_interval(mut self, interval: Duration, jitter: Duration) -> Self {
+        self.compact_interval = Some((interval, jitter));
+        self
+    }
+
+    /// Sends LifecycleEvent::Expiring from the background sync thread, before each prune,
+    /// for every table with entries that will expire within the supplied duration; for
+    /// refreshing entries such as credentials before they are pruned.  Entries are sent at
+    /// each sync until refreshed or pruned.  Has no effect unless a sync interval is set;
+    /// see DatabaseClient::expiring_within to check on demand.
+    /// ```
+    /// use persistent_keystore_rs::{Client, LifecycleEvent, LifecycleEvents};
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let events = LifecycleEvents::new();
+    /// let rx = events.subscribe();
+    /// let c = Client::builder(Path::new("expirywarning.db"))
+    ///     .sync_interval(Duration::from_millis(30))
+    ///     .expiry_warning(Duration::from_secs(300))
+    ///     .lifecycle_events(events)
+    ///     .build();
+    /// for event in rx.try_iter() {
+    ///     if let LifecycleEvent::Expiring{table, entries} = event {
+    ///         println!("{} entries of {} expire within 5 minutes", entries.len(), table);
+    ///     };
+    /// };
+    /// # drop(c);
+    /// # std::fs::remove_file("expirywarning.db").unwrap();
+    /// ```
+    pub fn expiry_warning(mut self, within: Duration) -> Self {
+        self.expiry_warning = Some(within);
+        self
+    }
+
+    /// Removes orphaned files beside the database file when the Client is built or opened,
+    /// if last modified longer ago than the retention; see DatabaseClient::gc_files
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("gcfilesonopen.db"))
+    ///     .gc_files_on_open(Duration::from_secs(3600))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("gcfilesonopen.db").unwrap();
+    /// ```
+    pub fn gc_files_on_open(mut self, retention: Duration) -> Self {
+        self.gc_retention = Some(retention);
+        self
+    }
+
+    /// Sets the action taken by open if the database file is empty; by default opening
+    /// fails with DatabaseError::EmptyDatabaseFile
+    /// ```
+    /// use persistent_keystore_rs::{Client, EmptyFilePolicy};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// std::fs::write("emptyfile.db", b"").unwrap();
+    /// assert!(Client::open(Path::new("emptyfile.db")).is_err());
+    ///
+    /// let mut c = Client::builder(Path::new("emptyfile.db"))
+    ///     .on_empty_file(EmptyFilePolicy::TreatAsNew)
+    ///     .open().unwrap();
+    /// assert!(c.list_tables().unwrap().is_empty());
+    /// # std::fs::remove_file("emptyfile.db").unwrap();
+    /// ```
+    pub fn on_empty_file(mut self, policy: EmptyFilePolicy) -> Self {
+        self.empty_file = policy;
+        self
+    }
+
+    /// Makes drop_table, delete_many, truncate and prune record the changes they would make
+    /// as DryRunAction rather than making them, for checking what an operator tool will do
+    /// before running it for real; see DatabaseClient::take_dry_run_actions.  Their results
+    /// are those they would return, and other writes are unaffected.
+    /// ```
+    /// use persistent_keystore_rs::{Client, DryRunAction, FieldType, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// # let mut c = Client::new(Path::new("dryrun.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
     /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
-    /// # let entry3 = Entry::new()
-    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
+    /// # c.create_table(table).unwrap();
+    /// # c.save().unwrap();
+    /// # drop(c);
+    /// let mut c = Client::builder(Path::new("dryrun.db"))
+    ///     .dry_run(true)
+    ///     .open().unwrap();
+    /// c.drop_table(&"MyTable".to_string()).unwrap();
+    /// assert_eq!(c.list_tables().unwrap(), vec!["MyTable".to_string()]);
+    /// assert_eq!(c.take_dry_run_actions().unwrap(), vec![DryRunAction::DropTable{
+    ///     table: "MyTable".to_string(),
+    ///     entries: 0,
+    /// }]);
+    /// # std::fs::remove_file("dryrun.db").unwrap();
+    /// ```
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Sets the number of idempotency keys remembered, across all tables, by the
+    /// idempotent writes of the Client; older keys are forgotten, after which their writes
+    /// would be applied again.  Defaults to DEFAULT_IDEMPOTENCY_CAPACITY.  The keys are
+    /// saved with the database, but the capacity is not.
+    pub fn idempotency_capacity(mut self, capacity: usize) -> Self {
+        self.idempotency_capacity = capacity;
+        self
+    }
+
+    /// Saves the database every interval, on a background thread, if it was changed since
+    /// its last save, so that the mutations made in between are made durable together by
+    /// one save.  Mutations return once made in memory; DatabaseClient::flush waits for the
+    /// next group commit when a caller needs a mutation to be durable.  Pending mutations
+    /// are also saved when the last clone of the Client is dropped.  The interval is not
+    /// persisted with the database.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// let c = Client::builder(Path::new("groupcommit.db"))
+    ///     .group_commit(Duration::from_millis(5))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("groupcommit.db").unwrap();
+    /// ```
+    pub fn group_commit(mut self, interval: Duration) -> Self {
+        self.group_commit = Some(interval);
+        self
+    }
+
+    /// Registers an OperationObserver notified of the operations made through the Client
+    /// and its clones; may be called more than once to register several
+    /// ```
+    /// use persistent_keystore_rs::{Client, OperationEvent, OperationObserver};
+    /// use std::path::Path;
+    /// use std::sync::Arc;
+    ///
+    /// struct AccessLog;
+    ///
+    /// impl OperationObserver for AccessLog {
+    ///     fn on_get(&self, event: &OperationEvent<'_>) {
+    ///         println!("{:?} read {:?} from {}", event.context, event.key, event.table);
+    ///     }
+    /// }
+    ///
+    /// let c = Client::builder(Path::new("observer.db"))
+    ///     .observer(Arc::new(AccessLog))
+    ///     .build();
+    /// # drop(c);
+    /// # std::fs::remove_file("observer.db").unwrap();
+    /// ```
+    pub fn observer(mut self, observer: Arc<dyn OperationObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Sets the closure consulted before each operation on a table made through the Client
+    /// and its clones; operations it denies fail with DatabaseError::AccessDenied without
+    /// being made.  The context set by DatabaseClient::with_context identifies the caller,
+    /// so that one shared Client can confine each of its users to their own tables.
+    ///
+    /// The operations checked are those of Operation, including reads through a Snapshot
+    /// and the reads and writes of the stores returned by methods such as
+    /// DatabaseClient::session_store; those of the background threads of the Client, such
+    /// as prune, are not.
+    /// ```
+    /// use persistent_keystore_rs::{Authorization, Client, FieldType, Table};
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::builder(Path::new("authorizer.db"))
+    ///     .authorizer(|event| match event.context {
+    ///         Some(plugin) => Authorization::from(event.table.starts_with(plugin)),
+    ///         None => Authorization::Allow,
+    ///     })
+    ///     .build().unwrap();
+    /// for name in ["PluginA.Settings", "PluginB.Settings"] {
+    ///     let table = Table::new()
+    ///         .name(name.to_string())
+    ///         .primary_field(FieldType::String).unwrap()
+    ///         .add_field("Value".to_string(), FieldType::String).unwrap()
+    ///         .build().unwrap();
+    ///     c.create_table(table).unwrap();
+    /// };
+    ///
+    /// let mut plugin_a = c.with_context("PluginA".to_string());
+    /// assert!(plugin_a.scan("PluginA.Settings".to_string()).is_ok());
+    /// assert!(matches!(plugin_a.scan("PluginB.Settings".to_string()), Err(DatabaseError::AccessDenied(_))));
+    /// # std::fs::remove_file("authorizer.db").unwrap();
+    /// ```
+    pub fn authorizer<F>(mut self, authorizer: F) -> Self
+    where
+        F: Fn(&OperationEvent<'_>) -> Authorization + Send + Sync + 'static,
+    {
+        self.authorizer = Some(Arc::new(authorizer));
+        self
+    }
+
+    /// Sets the closure to which the Client and its clones report the progress of opening,
+    /// saving and compacting the database, exporting with DatabaseClient::query_to_writer
+    /// and copying with DatabaseClient::copy_into; for example to show progress bars, or
+    /// detect stalls, on large databases.  Files are read and written in chunks of
+    /// PROGRESS_BYTES, and entries reported every PROGRESS_ENTRIES, each operation
+    /// finishing with ProgressPhase::Done.  The closure is called on the thread making the
+    /// operation, so should return promptly.
+    /// ```
+    /// use persistent_keystore_rs::{Client, ProgressOperation, ProgressPhase};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::builder(Path::new("progress.db"))
+    ///     .progress(|p| match (p.operation, p.phase) {
+    ///         (ProgressOperation::Save, ProgressPhase::Writing) => println!("{} of {:?} bytes written", p.bytes, p.total_bytes),
+    ///         (operation, ProgressPhase::Done) => println!("{:?} completed in {:?}", operation, p.elapsed),
+    ///         _ => {},
+    ///     })
+    ///     .build().unwrap();
+    /// c.save().unwrap();
+    /// # std::fs::remove_file("progress.db").unwrap();
+    /// ```
+    pub fn progress<F>(mut self, reporter: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(reporter));
+        self
+    }
+
+    /// Reloads the database every interval if its file was changed by another process;
+    /// see DatabaseClient::reload.  Intended for read-only Clients of a file maintained
+    /// elsewhere, as changes made by the Client are lost when the file is reloaded.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// # drop(Client::new(Path::new("reloadinterval.db"), None));
+    /// let c = Client::builder(Path::new("reloadinterval.db"))
+    ///     .reload_interval(Duration::from_secs(60))
+    ///     .open();
+    /// # drop(c);
+    /// # std::fs::remove_file("reloadinterval.db").unwrap();
+    /// ```
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = Some(interval);
+        self
+    }
+
+    /// Saves the database in the segmented format, in which each table is serialized on
+    /// its own.  When a database in the segmented format is opened, each table is only
+    /// deserialized when first accessed, so applications using a few of many tables pay
+    /// an open cost in proportion to the tables they use.  Saves reuse the serialized form
+    /// of tables read but not modified since opening.
+    ///
+    /// A database opened from the segmented format stays in it, whether or not this is
+    /// set.  Tables not yet accessed are skipped by DatabaseClient::prune, and the files of
+    /// their externalized values by DatabaseClient::compact.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::builder(Path::new("segmented.db"))
+    ///     .segmented(true)
+    ///     .build().unwrap();
+    /// c.save().unwrap();
+    /// # drop(c);
+    /// let c = Client::open(Path::new("segmented.db")).unwrap();
+    /// # drop(c);
+    /// # std::fs::remove_file("segmented.db").unwrap();
+    /// ```
+    pub fn segmented(mut self, segmented: bool) -> Self {
+        self.segmented = segmented;
+        self
+    }
+
+    /// Sends the lifecycle events of the Client and its clones, such as saves and failures
+    /// of its background threads, to the subscribers of the supplied LifecycleEvents; for
+    /// subscribing before the database is opened, to receive LifecycleEvent::Opened.  See
+    /// DatabaseClient::subscribe_lifecycle to subscribe after.
+    pub fn lifecycle_events(mut self, events: LifecycleEvents) -> Self {
+        self.lifecycle = events;
+        self
+    }
+
+    /// Finds tables by names differing from theirs only in case, so that `mytable` finds
+    /// the table created as `MyTable`.  Tables keep the name they were created with, as
+    /// returned by DatabaseClient::list_tables, and creating a table whose name differs
+    /// from an existing one only in case returns DatabaseError::TableExists.  Of tables
+    /// created before this was set whose names differ only in case, a name matching none
+    /// exactly finds the first in order.
+    ///
+    /// The setting is not saved with the database; set it each time the database is opened.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::builder(Path::new("case_insensitive.db"))
+    ///     .case_insensitive_tables(true)
+    ///     .build().unwrap();
+    /// # let table = Table::new()
+    /// #     .name("MyTable".to_string())
+    /// #     .primary_field(FieldType::String).unwrap()
+    /// #     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #     .build().unwrap();
+    /// c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #     .set_primary_field(Field::String("MyKey".to_string())).unwrap()
+    /// #     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #     .build().unwrap();
+    /// c.insert("mytable".to_string(), entry).unwrap();
+    /// assert!(c.get("MYTABLE".to_string(), Field::String("MyKey".to_string())).is_ok());
+    /// assert_eq!(c.list_tables().unwrap(), vec!["MyTable".to_string()]);
+    /// # drop(c);
+    /// # std::fs::remove_file("case_insensitive.db").unwrap();
+    /// ```
+    pub fn case_insensitive_tables(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive_tables = case_insensitive;
+        self
+    }
+
+    /// Creates the database at the configured path and returns a Client for it.
+    /// If the path exists, DatabaseError::DatabaseExistsError is returned.
+    pub fn build(self) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
+        Ok(Box::new(self.build_client()?))
+    }
+
+    /// Opens the existing database at the configured path and returns a Client for it.
+    /// If the path does not exist, DatabaseError::DatabaseDoesNotExist is returned.
+    pub fn open(self) -> Result<Box<dyn DatabaseClient>, DatabaseError> {
+        Ok(Box::new(self.open_client()?))
+    }
+
+    fn build_client(self) -> Result<Client, DatabaseError> {
+        info!("Creating Client with database at {:?}", self.path);
+        let exists = match &self.container {
+            Some((_, root)) => root.exists(),
+            None => self.path.exists(),
+        };
+        if exists {
+            error!("Database exists, cannot create: {:?}", self.path);
+            return Err(DatabaseError::DatabaseExistsError)
+        };
+
+        let mut database = Database::default();
+        database.set_segmented(self.segmented);
+        database.set_case_insensitive(self.case_insensitive_tables);
+
+        if let Some(d) = self.sync_interval {
+            debug!("Setting sync interval to {:?}", d);
+            database.set_sync_duration(d);
+        };
+
+        let mut client = Client{
+            database: sync::Arc::new(RwLock::new(database)),
+            raw_file: sync::Arc::new(Mutex::new(self.path)),
+            signature: sync::Arc::new(Mutex::new(None)),
+            workers: sync::Arc::new(Vec::new()),
+            clock: self.clock,
+            ids: self.ids,
+            max_memory: self.max_memory,
+            size_budget: self.size_budget,
+            verify_interval: self.verify_interval,
+            expiry_warning: self.expiry_warning,
+            compact_interval: self.compact_interval,
+            save_gate: self.save_gate.clone(),
+            dry_run: self.dry_run,
+            dry_runs: sync::Arc::new(Mutex::new(Vec::new())),
+            idempotency_capacity: self.idempotency_capacity,
+            durability: sync::Arc::new(commit::Durability::new()),
+            group_commit: self.group_commit.is_some(),
+            wait_for_locks: true,
+            access: access::AccessControl{
+                observers: self.observers.clone(),
+                authorizer: self.authorizer.clone(),
+                context: None,
+            },
+            progress: self.progress.clone(),
+            lifecycle: self.lifecycle.clone(),
+            writes: sync::Arc::new(Mutex::new(WriteStats::default())),
+            lock_waits: sync::Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "zstd")]
+            dictionary: sync::Arc::new(Mutex::new(None)),
+            root: self.container.as_ref().map(|(_, root)| root.clone()),
+        };
+
+        let mut workers = Vec::new();
+        if let Some(d) = self.sync_interval {
+            workers.push(client.start_sync(d, self.sync_jitter));
+        };
+        if let Some(d) = self.reload_interval {
+            workers.push(client.start_reload(d));
+        };
+        if let Some(d) = self.group_commit {
+            workers.push(client.start_group_commit(d));
+        };
+        client.workers = sync::Arc::new(workers);
+
+        client.save()?;
+        if let Some(retention) = self.gc_retention {
+            client.gc_files(retention)?;
+        };
+        if let Some((container, root)) = &self.container {
+            container.register(root.name(), &client);
+        };
+        client.lifecycle.emit(LifecycleEvent::Opened{path: client.path()?, created: true});
+        trace!("Returning Client");
+        Ok(client)
+    }
+
+    fn open_client(self) -> Result<Client, DatabaseError> {
+        info!("Opening Client with database at {:?}", self.path);
+        let exists = match &self.container {
+            Some((container, root)) => {
+                if let Some(client) = container.client(root.name()) {
+                    debug!("Database {} of container {:?} is open", root.name(), root.file());
+                    return Ok(client)
+                };
+                root.exists()
+            },
+            None => self.path.exists(),
+        };
+        if !exists {
+            error!("Database does not exist exists, cannot open: {:?}", self.path);
+            return Err(DatabaseError::DatabaseDoesNotExist(self.path))
+        } ;
+
+        let tracker = progress::Tracker::new(&self.progress, &self.clock, ProgressOperation::Open);
+        let (compressed, signature) = match &self.container {
+            Some((_, root)) => root.read()?,
+            None => read_file_with(&self.path, &tracker)?,
+        };
+        let (mut database, _dictionary) = match (compressed.is_empty(), self.empty_file) {
+            (false, _) => {
+                let total = Some(compressed.len() as u64);
+                tracker.bytes(ProgressPhase::Decoding, 0, total);
+                let decoded = decode_file(&compressed)?;
+                tracker.bytes(ProgressPhase::Decoding, compressed.len() as u64, total);
+                decoded
+            },
+            (true, EmptyFilePolicy::Error) => {
+                error!("Database file is empty, cannot open: {:?}", self.path);
+                return Err(DatabaseError::EmptyDatabaseFile(self.path))
+            },
+            (true, EmptyFilePolicy::TreatAsNew) => {
+                info!("Database file {:?} is empty, opening as a new database", self.path);
+                (Database::default(), None)
+            },
+            (true, EmptyFilePolicy::UseBackup) => {
+                let backup = backup_path(&self.path);
+                info!("Database file {:?} is empty, opening backup {:?}", self.path, backup);
+                let compressed = match backup.exists() {
+                    true => read_file(&backup)?.0,
+                    false => Vec::new(),
+                };
+                if compressed.is_empty() {
+                    error!("Database file and backup are missing or empty, cannot open: {:?}", self.path);
+                    return Err(DatabaseError::EmptyDatabaseFile(self.path))
+                };
+                decode_file(&compressed)?
+            },
+        };
+        database.rebase(self.clock.timestamp());
+        database.attach_files(&self.path)?;
+        if self.segmented {
+            database.set_segmented(true);
+        };
+        database.set_case_insensitive(self.case_insensitive_tables);
+        if let Some(retention) = self.gc_retention {
+            gc::gc_files(&self.path, &database, retention)?;
+        };
+        tracker.done(compressed.len() as u64, database.entry_count() as u64);
+
+        if let Some(d) = self.sync_interval {
+            debug!("Setting sync interval to {:?}", d);
+            database.set_sync_duration(d);
+        };
+        let sync_interval = database.sync_interval;
+
+        let mut client = Client{
+            database: sync::Arc::new(RwLock::new(database)),
+            raw_file: sync::Arc::new(Mutex::new(self.path)),
+            signature: sync::Arc::new(Mutex::new(None)),
+            workers: sync::Arc::new(Vec::new()),
+            clock: self.clock,
+            ids: self.ids,
+            max_memory: self.max_memory,
+            size_budget: self.size_budget,
+            verify_interval: self.verify_interval,
+            expiry_warning: self.expiry_warning,
+            compact_interval: self.compact_interval,
+            save_gate: self.save_gate.clone(),
+            dry_run: self.dry_run,
+            dry_runs: sync::Arc::new(Mutex::new(Vec::new())),
+            idempotency_capacity: self.idempotency_capacity,
+            durability: sync::Arc::new(commit::Durability::new()),
+            group_commit: self.group_commit.is_some(),
+            wait_for_locks: true,
+            access: access::AccessControl{
+                observers: self.observers.clone(),
+                authorizer: self.authorizer.clone(),
+                context: None,
+            },
+            progress: self.progress.clone(),
+            lifecycle: self.lifecycle.clone(),
+            writes: sync::Arc::new(Mutex::new(WriteStats::default())),
+            lock_waits: sync::Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "zstd")]
+            dictionary: sync::Arc::new(Mutex::new(_dictionary)),
+            root: self.container.as_ref().map(|(_, root)| root.clone()),
+        };
+
+        *client.signature.lock().unwrap() = Some(signature);
+        let mut workers = Vec::new();
+        if let Some(duration) = sync_interval {
+            workers.push(client.start_sync(duration, self.sync_jitter));
+        };
+        if let Some(d) = self.reload_interval {
+            workers.push(client.start_reload(d));
+        };
+        if let Some(d) = self.group_commit {
+            workers.push(client.start_group_commit(d));
+        };
+        client.workers = sync::Arc::new(workers);
+        if let Some((container, root)) = &self.container {
+            container.register(root.name(), &client);
+        };
+        client.lifecycle.emit(LifecycleEvent::Opened{path: client.path()?, created: false});
+
+        trace!("Returning Client");
+
+        Ok(client)
+    }
+}
+
+impl DatabaseClient for Client {
+    /// Removes stale entries as defined by the expiration value per table
+    /// and saves the database to disk; using lz4 compression
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// use std::path::Path;
+    /// let c = Client::new(Path::new("saved.db"), None);
+    /// # std::fs::remove_file("saved.db").unwrap();
+    /// ```
+    /// This database will not sync on its own and will need to be saved with 
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// # let mut c = Client::new(std::path::Path::new("saved2.db"), None).unwrap();
+    /// c.save();
+    /// # std::fs::remove_file("saved2.db").unwrap();
+    /// ```
+    /// The database is serialized under a shared read lock, so reads are not blocked
+    /// while a save is in progress; compression and disk IO happen after it is released.
+    ///
+    /// Tables, entries and their fields are serialized in the order of their names and
+    /// primary fields, so databases holding the same entries, with the same timestamps,
+    /// are serialized as the same bytes whatever order they were written in.  Columnar
+    /// tables, and interned and externalized values, are laid out in the order they were
+    /// written.  The header of the file records the version of the crate, host and time it
+    /// was written by; see Client::file_info.
+    fn save(&mut self) -> Result<(), DatabaseError> {
+        let started = self.clock.instant();
+        let result = self.save_file();
+        let bytes = self.report("save", result)?;
+        self.lifecycle.emit(LifecycleEvent::Saved{bytes, duration: self.clock.instant().saturating_duration_since(started)});
+        Ok(())
+    }
+
+    /// Releases memory left unused by removed entries, saves the database and removes the
+    /// files of externalized values that are no longer referenced, returning the number of
+    /// files removed; see TableBuilder::externalize_above.  Writes wait for the compaction
+    /// to complete.  See ClientBuilder::compact_interval to compact in the background.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("compact.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Document"), FieldType::String).unwrap()
+    /// #    .externalize_above(16)
     /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
-    /// let results = c.scan("MyTable".to_string()).unwrap();
-    /// # assert_eq!(c.scan("MyTable".to_string()).unwrap().len(), 3);
-    /// # std::fs::remove_file("scan.db").unwrap();
+    /// # c.create_table(table).unwrap();
+    /// for document in ["first version of the document", "second version of the document"] {
+    ///     let entry = Entry::new()
+    ///         .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    ///         .add_field("Document".to_string(), Field::String(document.to_string())).unwrap()
+    ///         .build().unwrap();
+    ///     c.insert_or_update("MyTable".to_string(), entry).unwrap();
+    /// };
+    /// assert_eq!(c.compact().unwrap(), 1);
+    /// # c.drop_table(&"MyTable".to_string()).unwrap();
+    /// # std::fs::remove_file("compact.db").unwrap();
     /// ```
-    fn scan(&mut self, table: String) -> Result<Vec<Entry>, DatabaseError> {
-        trace!("Scanning table {}", table);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    debug!("Scanning table {}", table);
-                    return t.scan()
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                },
+    fn compact(&mut self) -> Result<u64, DatabaseError> {
+        let result = self.compact_file();
+        let (bytes, removed) = self.report("compact", result)?;
+        self.lifecycle.emit(LifecycleEvent::Compacted{bytes, removed});
+        Ok(removed)
+    }
+
+    /// Creates a table within the database of the associated client
+    /// ```
+    /// use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("createtable.db"), None).unwrap();
+    /// let table = Table::new()
+    ///     .name(String::from("MyTable"))
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    ///     .add_expiration(Duration::from_secs(2592000))
+    ///     .build().unwrap();
+    /// c.create_table(table).unwrap();
+    /// # std::fs::remove_file("createtable.db").unwrap();
+    /// ```
+    fn create_table(&mut self, mut table: Table) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Schema, TRACE, "Creating table {}", table.name);
+        self.authorize(Operation::CreateTable, &table.name, None)?;
+        let path = self.path()?;
+        let mut database = self.write_database("create_table")?;
+        match database.get_table(&table.name.clone()) {
+            Ok(_) => {
+                error!("Table {} exists", table.name);
+                return Err(DatabaseError::TableExists(table.name))
+            },
+            Err(_) => {
+                log_event!(Subsystem::Schema, DEBUG, "Creating table {}", table.name);
+                let name = table.name.clone();
+                let now = self.clock.now();
+                table.created_at = Some(now);
+                database.create_table(table)?;
+                database.record_schema_change(name, SchemaChangeKind::Created, now);
+                database.applied();
+                return database.attach_files(&path)
+            },
+        };
+    }
+
+    /// Lists tables within the database of the associated client
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("listtable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let tables = c.list_tables().unwrap();
+    /// assert_eq!(tables.len(), 1);
+    /// assert_eq!(tables[0], String::from("MyTable"));
+    /// # std::fs::remove_file("listtable.db").unwrap();
+    /// ```
+    fn list_tables(&mut self) -> Result<Vec<String>, DatabaseError> {
+        log_event!(Subsystem::Schema, TRACE, "Listing Tables");
+        let database = self.read_lock("list_tables")?;
+        let tables = database.list_tables();
+        log_event!(Subsystem::Schema, DEBUG, "Listed {} tables", tables.len());
+        return Ok(tables)
+    }
+
+    /// Drops the specified table from within the database of the associated client
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("droptable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let tables = c.list_tables().unwrap();
+    /// assert_eq!(tables.len(), 1);
+    /// assert_eq!(tables[0], String::from("MyTable"));
+    /// c.drop_table(&String::from("MyTable")).unwrap();
+    /// let current_tables = c.list_tables().unwrap();
+    /// assert_eq!(current_tables.len(), 0);
+    /// # std::fs::remove_file("droptable.db").unwrap();
+    /// ```
+    fn drop_table(&mut self, table: &String) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Schema, TRACE, "Dropping table {}", table);
+        self.authorize(Operation::DropTable, table, None)?;
+        let path = self.path()?;
+        if self.dry_run {
+            let entries = match self.read_lock("drop_table") {
+                Ok(database) => database.check_drop_table(table)?.len(),
+                Err(e) => return Err(e),
+            };
+            return self.record_dry_run(DryRunAction::DropTable{table: table.clone(), entries})
+        };
+        let mut database = self.write_database("drop_table")?;
+        let table = &database.table_name(table).into_owned();
+        log_event!(Subsystem::Schema, DEBUG, "Dropping table {}", table);
+        database.drop_table(table)?;
+        database.record_schema_change(table.clone(), SchemaChangeKind::Dropped, self.clock.now());
+        database.applied();
+        spill::remove_segment(&path, table)?;
+        return blob::remove_blob_dir(&path, table)
+    }
+
+    /// Inserts the provided entry into the specified table within the database of the associated client.
+    /// If an entry with the same primary key exists, an DatabaseError::EntryExists is returned
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("insertentry.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    ///     .build().unwrap();
+    /// c.insert("MyTable".to_string(), entry).unwrap();
+    /// # std::fs::remove_file("insertentry.db").unwrap();
+    /// ```
+    fn insert(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Inserting entry into table {}: {}", table, entry);
+        self.authorize(Operation::Insert, &table, Some(&entry.primary_field))?;
+        let mut database = self.write_database("insert")?;
+        self.check_write(&mut database, &table, &entry, WriteKind::Insert)?;
+        match database.get_table(&table) {
+            Ok(t) => {
+                log_event!(Subsystem::Write, DEBUG, "Inserting entry into table {}", table);
+                t.insert_at(entry, self.clock.timestamp())?;
+                database.applied();
+                return Ok(())
+            },
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            }
+        }
+    }
+
+    /// Inserts the provided entry into the specified table within the database of the associated client.
+    /// If an entry with the same primary key exists, the entry is updated, and returned as the
+    /// previous version in UpsertOutcome::Updated; expired entries count as inserted.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, UpsertOutcome};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("insertorupdateentry.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    ///     .build().unwrap();
+    /// let outcome = c.insert_or_update("MyTable".to_string(), entry.clone()).unwrap();
+    /// assert_eq!(outcome, UpsertOutcome::Inserted);
+    /// match c.insert_or_update("MyTable".to_string(), entry).unwrap() {
+    ///     UpsertOutcome::Updated{previous} => println!("Refreshed {}", previous.primary_field),
+    ///     UpsertOutcome::Inserted => unreachable!(),
+    /// };
+    /// # std::fs::remove_file("insertorupdateentry.db").unwrap();
+    /// ```
+    fn insert_or_update(&mut self, table: String, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Inserting or updating entry into table {}: {}", table, entry);
+        self.authorize(Operation::InsertOrUpdate, &table, Some(&entry.primary_field))?;
+        let mut database = self.write_database("insert_or_update")?;
+        self.check_write(&mut database, &table, &entry, WriteKind::Update)?;
+        match database.get_table(&table) {
+            Ok(t) => {
+                let now = self.clock.timestamp();
+                let previous = match t.time_to_live(&entry.primary_field, now) {
+                    Ok(Some(Duration::ZERO)) | Err(_) => None,
+                    Ok(_) => t.lookup(&entry.primary_field),
+                };
+                t.update_at(entry, now)?;
+                database.applied();
+                return Ok(match previous {
+                    Some(previous) => {
+                        log_event!(Subsystem::Write, DEBUG, "Updated entry in table {}", table);
+                        UpsertOutcome::Updated{previous}
+                    },
+                    None => {
+                        log_event!(Subsystem::Write, DEBUG, "Inserted entry into table {}", table);
+                        UpsertOutcome::Inserted
+                    },
+                })
+            },
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            },
+        }
+    }
+
+    /// Updates an existing entry in the specified table within the database of the associated client.
+    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("updateentry.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    ///     .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// c.update("MyTable".to_string(), entry).unwrap();
+    /// # std::fs::remove_file("updateentry.db").unwrap();
+    /// ```
+    fn update(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Updating entry into table {}: {}", table, entry);
+        self.authorize(Operation::Update, &table, Some(&entry.primary_field))?;
+        let mut database = self.write_database("update")?;
+        self.check_write(&mut database, &table, &entry, WriteKind::Update)?;
+        match database.get_table(&table) {
+            Ok(t) => {
+                log_event!(Subsystem::Write, DEBUG, "Updating entry {} in table {}", entry.primary_field, table);
+                t.update_at(entry, self.clock.timestamp())?;
+                database.applied();
+                return Ok(())
+            },
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            }
+        }
+    }
+
+    /// Get an existing entry from the specified table within the database of the associated client.
+    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
+    /// 
+    /// The entry is returned as a shared handle rather than a copy; later updates replace the
+    /// stored entry and do not affect handles that have already been returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("getentry.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// let e = c.get("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
+    /// # std::fs::remove_file("getentry.db").unwrap();
+    /// ```
+    fn get(&mut self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Getting entry {} from table {}", primary_field, table);
+        self.authorize(Operation::Get, &table, Some(&primary_field))?;
+        let database = self.read_lock("get")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Getting entry {} from table {}", primary_field, table);
+                return t.get(&primary_field)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            }
+        }
+    }
+
+    /// Returns true if an entry exists within the specified table of the database of the
+    /// associated client.  Tables configured with TableBuilder::bloom_filter usually answer
+    /// for missing entries without searching the table.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("exists.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .bloom_filter(0.01)
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// assert!(c.exists("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap());
+    /// assert!(!c.exists("MyTable".to_string(), Field::String("Missing".to_string())).unwrap());
+    /// # std::fs::remove_file("exists.db").unwrap();
+    /// ```
+    fn exists(&mut self, table: String, primary_field: Field) -> Result<bool, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Checking for entry {} in table {}", primary_field, table);
+        self.authorize(Operation::Get, &table, Some(&primary_field))?;
+        let database = self.read_lock("exists")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Checking for entry {} in table {}", primary_field, table);
+                return Ok(t.exists(&primary_field))
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            }
+        }
+    }
+
+    /// Returns how long until an existing entry from the specified table expires, or None if
+    /// the table does not expire entries; see Table::time_to_live.
+    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("timetolive.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_expiration(Duration::from_secs(60))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let ttl = c.time_to_live("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
+    /// assert!(ttl.unwrap() <= Duration::from_secs(60));
+    /// # std::fs::remove_file("timetolive.db").unwrap();
+    /// ```
+    fn time_to_live(&mut self, table: String, primary_field: Field) -> Result<Option<Duration>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Getting time to live of entry {} from table {}", primary_field, table);
+        self.authorize(Operation::Get, &table, Some(&primary_field))?;
+        let database = self.read_lock("time_to_live")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Getting time to live of entry {} from table {}", primary_field, table);
+                return t.time_to_live(&primary_field, self.clock.timestamp())
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            }
+        }
+    }
+
+    /// Returns every version of an existing entry from the specified table, oldest first and
+    /// ending with the current version.  Only append-only tables keep previous versions.
+    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::{Field, HistoryRetention};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("history.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .append_only(HistoryRetention::default())
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for count in 0..2 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #        .build().unwrap();
+    /// #     c.insert_or_update("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// let versions = c.history("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
+    /// assert_eq!(versions.len(), 2);
+    /// # std::fs::remove_file("history.db").unwrap();
+    /// ```
+    fn history(&mut self, table: String, primary_field: Field) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Getting history of entry {} from table {}", primary_field, table);
+        self.authorize(Operation::Get, &table, Some(&primary_field))?;
+        let database = self.read_lock("history")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Getting history of entry {} from table {}", primary_field, table);
+                return t.history(&primary_field)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            }
+        }
+    }
+
+    /// Delete an existing entry from the specified table within the database of the associated client.
+    /// If an entry does not exist, DatabaseError::EntryDoesNotExists is returned
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("delete.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// c.delete("MyTable".to_string(), Field::String("MyFirstEntry".to_string())).unwrap();
+    /// # std::fs::remove_file("delete.db").unwrap();
+    /// ```
+    fn delete(&mut self, table: String, primary_field: Field) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Deleting entry {} from table {}", primary_field, table);
+        self.authorize(Operation::Delete, &table, Some(&primary_field))?;
+        let mut database = self.write_database("delete")?;
+        log_event!(Subsystem::Write, DEBUG, "Deleting entry {} from table {}", primary_field, table);
+        match database.delete_entries(&table, vec![primary_field], self.clock.timestamp()) {
+            Ok(0) => return Err(DatabaseError::EntryDoesNotExists),
+            Ok(_) => {
+                database.applied();
+                return Ok(())
+            },
+            Err(DatabaseError::TableDoesNotExist(_)) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    /// Delete all entries matching the supplied criteria.  Criteria under PRIMARY_FIELD_KEY
+    /// match the primary field of each entry.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// use std::collections::HashMap;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("deletemany.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// # let entry2 = Entry::new()
+    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
+    /// # let entry3 = Entry::new()
+    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
+    /// let mut criteria: HashMap<String, Field> = HashMap::new();
+    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
+    /// c.delete_many("MyTable".to_string(), criteria).unwrap();
+    /// # assert_eq!(c.scan("MyTable".to_string()).unwrap().len(), 1);
+    /// # std::fs::remove_file("deletemany.db").unwrap();
+    /// ```
+    fn delete_many(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<u64, DatabaseError> {
+        self.delete_many_cancellable(table, criteria, &CancellationToken::new())
+    }
+
+    /// Delete all entries matching the supplied criteria, as delete_many.
+    /// If the token is cancelled while matching entries are being found,
+    /// DatabaseError::Cancelled is returned and no entries are deleted.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{CancellationToken, Field};
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("deletemanycancellable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let mut criteria: HashMap<String, Field> = HashMap::new();
+    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
+    /// let token = CancellationToken::new().with_timeout(Duration::from_secs(5));
+    /// c.delete_many_cancellable("MyTable".to_string(), criteria, &token).unwrap();
+    /// # std::fs::remove_file("deletemanycancellable.db").unwrap();
+    /// ```
+    fn delete_many_cancellable(&mut self, table: String, criteria: HashMap<String, Field>, token: &CancellationToken) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Deleting many from table {}", table);
+        self.authorize(Operation::Delete, &table, None)?;
+        self.delete_where(table, &equals_all(criteria), token)
+    }
+
+    /// Deletes every entry from the specified table, keeping the table, and returns the
+    /// number deleted
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("truncate.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// assert_eq!(c.truncate("MyTable".to_string()).unwrap(), 1);
+    /// assert!(c.scan("MyTable".to_string()).unwrap().is_empty());
+    /// # std::fs::remove_file("truncate.db").unwrap();
+    /// ```
+    fn truncate(&mut self, table: String) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Truncating table {}", table);
+        self.authorize(Operation::Delete, &table, None)?;
+        self.delete_where(table, &HashMap::new(), &CancellationToken::new())
+    }
+
+    /// Returns all entries from the specified table within the database of the associated client.
+    /// If no entries exist, will return an empty vec
+    /// 
+    /// Entries are returned as shared handles rather than copies.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("scan.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// # let entry2 = Entry::new()
+    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
+    /// # let entry3 = Entry::new()
+    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
+    /// let results = c.scan("MyTable".to_string()).unwrap();
+    /// # assert_eq!(c.scan("MyTable".to_string()).unwrap().len(), 3);
+    /// # std::fs::remove_file("scan.db").unwrap();
+    /// ```
+    fn scan(&mut self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {}", table);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("scan")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Scanning table {}", table);
+                return t.scan()
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Returns the entries from the specified table whose String primary field starts with
+    /// prefix, ordered by primary field; see Table::scan_prefix.
+    /// If the primary field is not FieldType::String, DatabaseError::InvalidPrimaryKey is
+    /// returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("scanprefix.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .prefix_index()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for url in ["https://example.com/a", "https://example.com/b", "https://example.org/"] {
+    /// #     let entry = Entry::new()
+    /// #         .set_primary_field(Field::String(url.to_string())).unwrap()
+    /// #         .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #         .build().unwrap();
+    /// #     c.insert("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// let results = c.scan_prefix("MyTable".to_string(), "https://example.com/".to_string()).unwrap();
+    /// # assert_eq!(results.len(), 2);
+    /// # std::fs::remove_file("scanprefix.db").unwrap();
+    /// ```
+    fn scan_prefix(&mut self, table: String, prefix: String) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {} for prefix {}", table, prefix);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("scan_prefix")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Scanning table {} for prefix {}", table, prefix);
+                return t.scan_prefix(&prefix)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Returns all entries from the specified table, as scan.
+    /// If the token is cancelled before the scan completes, DatabaseError::Cancelled is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::CancellationToken;
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("scancancellable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let token = CancellationToken::new();
+    /// token.cancel();
+    /// let result = c.scan_cancellable("MyTable".to_string(), &token);
+    /// assert!(matches!(result, Err(DatabaseError::Cancelled)));
+    /// # std::fs::remove_file("scancancellable.db").unwrap();
+    /// ```
+    fn scan_cancellable(&mut self, table: String, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {}", table);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("scan_cancellable")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Scanning table {}", table);
+                return t.filter_cancellable(&HashMap::new(), self.clock.now(), token)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Query for entries within a specified table meeting the supplied criteria.  Criteria
+    /// under PRIMARY_FIELD_KEY match the primary field of each entry.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// use std::collections::HashMap;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("query.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
+    /// #    .add_expiration(Duration::from_secs(2592000))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
+    /// # let entry2 = Entry::new()
+    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
+    /// # let entry3 = Entry::new()
+    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
+    /// let mut criteria: HashMap<String, Field> = HashMap::new();
+    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
+    /// let results = c.query("MyTable".to_string(), criteria).unwrap();
+    /// # assert_eq!(results.len(), 2);
+    /// # std::fs::remove_file("query.db").unwrap();
+    /// ```
+    fn query(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where_cancellable(table, equals_all(criteria), &CancellationToken::new())
+    }
+
+    /// Query for entries within a specified table meeting the supplied criteria, as query.
+    /// If the token is cancelled before the query completes, DatabaseError::Cancelled is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{CancellationToken, Field};
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("querycancellable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let mut criteria: HashMap<String, Field> = HashMap::new();
+    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
+    /// let token = CancellationToken::new().with_timeout(Duration::from_secs(5));
+    /// let results = c.query_cancellable("MyTable".to_string(), criteria, &token).unwrap();
+    /// # assert_eq!(results.len(), 0);
+    /// # std::fs::remove_file("querycancellable.db").unwrap();
+    /// ```
+    fn query_cancellable(&mut self, table: String, criteria: HashMap<String, Field>, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where_cancellable(table, equals_all(criteria), token)
+    }
+
+    /// Query for entries within a specified table meeting every supplied Condition.
+    /// Time based Conditions are evaluated relative to the clock of the client, and
+    /// Conditions under PRIMARY_FIELD_KEY apply to the primary field of each entry, such as
+    /// a range of keys, or with Condition::StartsWith, a key prefix.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::{Condition, Field};
+    /// use std::collections::HashMap;
+    /// use std::time::{Duration, SystemTime};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("querywhere.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now() - Duration::from_secs(3600))).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(5)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// # let entry2 = Entry::new()
+    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(5)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry2).unwrap();
+    /// let mut conditions = HashMap::new();
+    /// conditions.insert("TimeStamp".to_string(), Condition::OlderThan(Duration::from_secs(60)));
+    /// conditions.insert("Count".to_string(), Condition::GreaterThan(Field::I64(1)));
+    /// let results = c.query_where("MyTable".to_string(), conditions).unwrap();
+    /// # assert_eq!(results.len(), 1);
+    /// # std::fs::remove_file("querywhere.db").unwrap();
+    /// ```
+    fn query_where(&mut self, table: String, conditions: HashMap<String, Condition>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where_cancellable(table, conditions, &CancellationToken::new())
+    }
+
+    /// Query for entries within a specified table meeting every supplied Condition, as
+    /// query_where.
+    /// If the token is cancelled before the query completes, DatabaseError::Cancelled is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{CancellationToken, Condition, Field};
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("querywherecancellable.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let mut conditions = HashMap::new();
+    /// conditions.insert("Count".to_string(), Condition::GreaterThan(Field::I64(1)));
+    /// let token = CancellationToken::new().with_timeout(Duration::from_secs(5));
+    /// let results = c.query_where_cancellable("MyTable".to_string(), conditions, &token).unwrap();
+    /// # assert_eq!(results.len(), 0);
+    /// # std::fs::remove_file("querywherecancellable.db").unwrap();
+    /// ```
+    fn query_where_cancellable(&mut self, table: String, conditions: HashMap<String, Condition>, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Querying table {}", table);
+        self.authorize(Operation::Query, &table, None)?;
+        let database = self.read_lock("query_where_cancellable")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Querying table {}", table);
+                return t.filter_cancellable(&conditions, self.clock.now(), token)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Writes the entries within a specified table meeting the supplied criteria to writer
+    /// in the supplied ExportFormat as they are found, rather than collecting them first,
+    /// returning the number of entries written.  The primary field of each entry is written
+    /// under PRIMARY_FIELD_COLUMN.
+    ///
+    /// The database is read locked until every entry has been written, so writes to the
+    /// database wait on slow writers; buffer the writer if it is not already.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::{ExportFormat, Field};
+    /// use std::collections::HashMap;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("querytowriter.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(5)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let mut output = Vec::new();
+    /// let written = c.query_to_writer("MyTable".to_string(), HashMap::new(), ExportFormat::Ndjson, &mut output).unwrap();
+    /// assert_eq!(written, 1);
+    /// assert_eq!(String::from_utf8(output).unwrap(), "{\"primary_field\":\"MyFirstEntry\",\"Count\":5}\n");
+    /// # std::fs::remove_file("querytowriter.db").unwrap();
+    /// ```
+    fn query_to_writer(&mut self, table: String, criteria: HashMap<String, Field>, format: ExportFormat, writer: &mut dyn Write) -> Result<usize, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Exporting table {}", table);
+        self.authorize(Operation::Query, &table, None)?;
+        let database = self.read_lock("query_to_writer")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Exporting table {} as {:?}", table, format);
+                let tracker = progress::Tracker::new(&self.progress, &self.clock, ProgressOperation::Export);
+                let mut export = ExportWriter::new(format, &t, writer)?;
+                let mut written = 0;
+                t.for_each_match(&equals_all(criteria), self.clock.now(), &CancellationToken::new(), |entry| {
+                    written += 1;
+                    if (written as u64).is_multiple_of(PROGRESS_ENTRIES) {
+                        tracker.entries(ProgressPhase::Entries, written as u64, None);
+                    };
+                    export.write(&entry)
+                })?;
+                export.flush()?;
+                tracker.done(0, written as u64);
+                return Ok(written)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Returns the entries of the left table, each with the entry of the right table whose
+    /// primary field equals the value of left_field, under a single read lock.  The options
+    /// select which left entries are joined and whether unmatched ones are returned.
+    /// If either table does not exist, DatabaseError::TableDoesNotExist is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::{Field, JoinOptions};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("lookupjoin.db"), None).unwrap();
+    /// # let orders = Table::new()
+    /// #    .name(String::from("Orders"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Customer"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(orders).unwrap();
+    /// # let customers = Table::new()
+    /// #    .name(String::from("Customers"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Name"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(customers).unwrap();
+    /// # let order = Entry::new()
+    /// #    .set_primary_field(Field::String("Order1".to_string())).unwrap()
+    /// #    .add_field("Customer".to_string(), Field::String("Customer1".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("Orders".to_string(), order).unwrap();
+    /// # let customer = Entry::new()
+    /// #    .set_primary_field(Field::String("Customer1".to_string())).unwrap()
+    /// #    .add_field("Name".to_string(), Field::String("Alice".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("Customers".to_string(), customer).unwrap();
+    /// let results = c.lookup_join("Orders".to_string(), "Customers".to_string(), "Customer".to_string(), JoinOptions::default()).unwrap();
+    /// let customer = results[0].right.as_ref().unwrap();
+    /// assert_eq!(customer.fields["Name"], Field::String("Alice".to_string()));
+    /// # std::fs::remove_file("lookupjoin.db").unwrap();
+    /// ```
+    fn lookup_join(&mut self, left_table: String, right_table: String, left_field: String, options: JoinOptions) -> Result<Vec<JoinedEntry>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Joining table {} to table {} on {}", left_table, right_table, left_field);
+        self.authorize(Operation::Query, &left_table, None)?;
+        self.authorize(Operation::Query, &right_table, None)?;
+        let database = self.read_lock("lookup_join")?;
+        let left = match database.read_table(&left_table, self.clock.timestamp()) {
+            Ok(t) => t,
+            Err(_) => {
+                error!("Table {} does not exist", left_table);
+                return Err(DatabaseError::TableDoesNotExist(left_table))
+            },
+        };
+
+        let right = match database.read_table(&right_table, self.clock.timestamp()) {
+            Ok(t) => t,
+            Err(_) => {
+                error!("Table {} does not exist", right_table);
+                return Err(DatabaseError::TableDoesNotExist(right_table))
+            },
+        };
+
+        log_event!(Subsystem::Query, DEBUG, "Joining table {} to table {} on {}", left_table, right_table, left_field);
+        return join::lookup_join(&left, &right, &left_field, &options, self.clock.now())
+    }
+
+    /// Describes how a query_where against the specified table with the supplied conditions
+    /// would be executed; which access path is used, which conditions are pushed down and
+    /// how many entries are expected to be scanned.  The query itself is not run.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType};
+    /// use persistent_keystore_rs::{Condition, Field};
+    /// use std::collections::HashMap;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("explain.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let mut conditions = HashMap::new();
+    /// conditions.insert("Count".to_string(), Condition::GreaterThan(Field::I64(1)));
+    /// let plan = c.explain("MyTable".to_string(), conditions).unwrap();
+    /// assert_eq!(format!("{}", plan), "Full scan of table MyTable; estimated 0 rows scanned\n  filter: Count > 1");
+    /// # std::fs::remove_file("explain.db").unwrap();
+    /// ```
+    fn explain(&mut self, table: String, conditions: HashMap<String, Condition>) -> Result<QueryPlan, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Explaining query of table {}", table);
+        let database = self.read_lock("explain")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Explaining query of table {}", table);
+                return Ok(t.explain(&conditions))
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Returns the primary field and value of the supplied field for every entry within the
+    /// specified table that has the field set.  Tables created with a columnar layout answer
+    /// this from a single column without materializing entries.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("project.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .columnar()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(3)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let counts = c.project("MyTable".to_string(), "Count".to_string()).unwrap();
+    /// # assert_eq!(counts.len(), 1);
+    /// # std::fs::remove_file("project.db").unwrap();
+    /// ```
+    fn project(&mut self, table: String, field: String) -> Result<Vec<(Field, Field)>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Projecting field {} from table {}", field, table);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("project")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Projecting field {} from table {}", field, table);
+                return t.project(&field)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Removes entries that have expired by the specified TTL field in the table.
+    /// This is done automatically before saves if a sync_interval is provided.
+    /// 
+    /// Tables are pruned in a copy of the database that is swapped in once done, so that
+    /// reads proceed during the prune and see the database either before or after it.  The
+    /// copy shares every table until pruning copies it, so a table with expired entries is
+    /// held twice in memory while it is pruned.  If the database is written during the
+    /// prune, the copy is discarded and the database pruned under the write lock instead.
+    /// 
+    /// Tables with an ExpirationSchedule::Cron are pruned only with the `cron` feature;
+    /// without it the other tables are pruned and DatabaseError::InvalidFormat returned.
+    /// 
+    /// ```
+    /// use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// # use std::thread::sleep;
+    /// use persistent_keystore_rs::Field;
+    /// use std::collections::HashMap;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use std::time::SystemTime;
+    /// let mut c = Client::new(Path::new("prune.db"), None).unwrap();
+    /// let table = Table::new()
+    ///     .name(String::from("MyTable"))
+    ///     .add_expiration(Duration::from_secs(1))
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("FirstKey".to_string(), FieldType::I64).unwrap()
+    ///     .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// c.create_table(table).unwrap();
+    ///
+    /// let entry_first = Entry::new()
+    ///     .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+    ///     .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+    ///     .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// let result = c.insert("MyTable".to_string(), entry_first);
+    ///
+    /// if let Err(e) = result {
+    ///     panic!("No error expected, received {}", e)
+    /// } 
+    ///
+    /// let output = c.scan("MyTable".to_string()).unwrap();
+    ///
+    /// if output.len() != 1 {
+    ///     panic!("Expected scan results to be 1")
+    /// };
+    ///
+    /// sleep(Duration::from_secs(2));
+    /// c.prune().unwrap();
+    ///
+    /// let output = c.scan("MyTable".to_string()).unwrap();
+    /// if output.len() != 0 {
+    ///     panic!("Expected scan results to be 0")
+    /// };
+    /// # std::fs::remove_file("prune.db").unwrap();
+    /// ```
+    fn prune(&mut self) -> Result<(), DatabaseError> {
+        let result = self.prune_entries();
+        let removed = self.report("prune", result)?;
+        self.lifecycle.emit(LifecycleEvent::Pruned{removed});
+        Ok(())
+    }
+
+    /// Replaces the contents of the database with its file if the file was changed by
+    /// another process since it was last read or written by the Client, returning true if
+    /// the database was reloaded.
+    ///
+    /// The file is considered unchanged if its modification time and length, or its
+    /// checksum, are those last seen by the Client.  The new contents are read and decoded
+    /// before the database lock is taken, so readers see either the old or new contents; if
+    /// the file cannot be decoded an error is returned and the database is left unchanged.
+    /// Mutations of this Client that are not yet saved would be lost by the reload, so
+    /// while there are any DatabaseError::UnsavedChanges is returned instead; save or
+    /// flush them first.  Mutations that failed made no change, so are not counted.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, FieldType, Table};
+    /// # use std::path::Path;
+    /// let mut reader = Client::new(Path::new("reload.db"), None).unwrap();
+    /// assert!(!reader.reload().unwrap());
+    ///
+    /// let mut writer = Client::open(Path::new("reload.db")).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// writer.create_table(table).unwrap();
+    /// writer.save().unwrap();
+    ///
+    /// assert!(reader.reload().unwrap());
+    /// assert_eq!(reader.list_tables().unwrap(), vec!["MyTable".to_string()]);
+    /// # std::fs::remove_file("reload.db").unwrap();
+    /// ```
+    fn reload(&mut self) -> Result<bool, DatabaseError> {
+        let result = self.reload_file();
+        self.report("reload", result)
+    }
+
+    /// Merges the database file at the supplied path into this database using
+    /// last-writer-wins on the last_timestamp of each Entry, returning a report of the
+    /// Entries held by both with differing fields; see Database::merge
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use std::path::Path;
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let mut device = Client::new(Path::new("mergedevice.db"), None).unwrap();
+    /// # device.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # device.insert("MyTable".to_string(), entry).unwrap();
+    /// device.save().unwrap();
+    ///
+    /// let mut central = Client::new(Path::new("mergecentral.db"), None).unwrap();
+    /// let report = central.merge_from(Path::new("mergedevice.db")).unwrap();
+    /// assert!(report.conflicts.is_empty());
+    /// assert_eq!(central.scan("MyTable".to_string()).unwrap().len(), 1);
+    /// # std::fs::remove_file("mergedevice.db").unwrap();
+    /// # std::fs::remove_file("mergecentral.db").unwrap();
+    /// ```
+    fn merge_from(&mut self, path: &Path) -> Result<MergeReport, DatabaseError> {
+        self.merge_from_with(path, &mut last_writer_wins)
+    }
+
+    /// Merges the database file at the supplied path into this database, calling resolver
+    /// with the local and remote Entry for each primary Field held by both with differing
+    /// fields; see Database::merge_with
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::Resolution;
+    /// # use std::path::Path;
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let mut device = Client::new(Path::new("resolvedevice.db"), None).unwrap();
+    /// # let mut central = Client::new(Path::new("resolvecentral.db"), None).unwrap();
+    /// # device.create_table(table.clone()).unwrap();
+    /// # central.create_table(table).unwrap();
+    /// # for (c, count) in [(&mut device, 1), (&mut central, 2)] {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #        .build().unwrap();
+    /// #     c.insert("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// # device.save().unwrap();
+    /// let report = central.merge_from_with(Path::new("resolvedevice.db"), &mut |_local, _remote| {
+    ///     Resolution::KeepRemote
+    /// }).unwrap();
+    /// assert_eq!(report.conflicts[0].resolution, Resolution::KeepRemote);
+    /// # std::fs::remove_file("resolvedevice.db").unwrap();
+    /// # std::fs::remove_file("resolvecentral.db").unwrap();
+    /// ```
+    fn merge_from_with(&mut self, path: &Path, resolver: &mut dyn FnMut(&Entry, &Entry) -> Resolution) -> Result<MergeReport, DatabaseError> {
+        log_event!(Subsystem::Save, INFO, "Merging database {:?}", path);
+        let local = self.path()?;
+        let mut remote = read_database(path)?;
+        let existing = self.read_lock("merge_from_with")?.list_tables();
+        for table in remote.list_tables().iter().filter(|t| !t.starts_with(RESERVED_PREFIX)) {
+            if !existing.contains(table) {
+                self.authorize(Operation::CreateTable, table, None)?;
+            };
+            self.authorize(Operation::InsertOrUpdate, table, None)?;
+        };
+        remote.attach_files(path)?;
+        let mut database = self.write_database("merge_from_with")?;
+        let now = self.clock.timestamp();
+        let result = database.plan_merge(&remote, resolver).and_then(|plan| {
+            database.apply_merge(plan, now, Some(&local), |database, table, entry| match self.max_memory {
+                Some((max_bytes, policy)) => database.reserve(table, entry, max_bytes, policy, now),
+                None => Ok(()),
+            })
+        });
+        match result {
+            Ok(report) => {
+                log_event!(Subsystem::Save, DEBUG, "Merged database {:?}; {} inserted, {} conflicts", path, report.inserted, report.conflicts.len());
+                database.applied();
+                return Ok(report)
+            },
+            Err(e) => {
+                error!("Unable to merge database {:?}: {}", path, e);
+                return Err(e)
+            },
+        };
+    }
+
+    /// Checks the consistency of the database; that every stored entry agrees with the schema
+    /// of its table, that the structures used to locate and account for entries agree with
+    /// the entries, that references point to existing entries, and that the database file
+    /// can be read back.  Problems are returned in the report rather than as errors.
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("verify.db"), None).unwrap();
+    /// let report = c.verify().unwrap();
+    /// assert!(report.is_ok());
+    /// # std::fs::remove_file("verify.db").unwrap();
+    /// ```
+    fn verify(&mut self) -> Result<VerifyReport, DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Verifying database");
+        let mut report = match self.read_lock("verify") {
+            Ok(database) => database.verify(),
+            Err(e) => return Err(e),
+        };
+
+        if let Ok(raw_file) = self.raw_file.lock() {
+            log_event!(Subsystem::Save, DEBUG, "Verifying database file {:?}", raw_file);
+            if let Err(e) = self.read_contents(raw_file.as_path()).and_then(|(compressed, _)| decode_database(&compressed)) {
+                report.problems.push(VerifyProblem{
+                    table: None,
+                    key: None,
+                    message: format!("database file {:?} cannot be read: {}", raw_file, e),
+                });
+            };
+            return Ok(report)
+        };
+        error!("Unable to get file mutex");
+        Err(DatabaseError::UnableToGetLock)
+    }
+
+    /// Returns the current usage and configured limits of the database and each table
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field, CapacityPolicy};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("stats.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .max_entries(100, CapacityPolicy::Reject)
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let stats = c.stats().unwrap();
+    /// let table = &stats.tables["MyTable"];
+    /// assert_eq!(table.entries, 1);
+    /// assert_eq!(table.max_entries, Some(100));
+    /// # std::fs::remove_file("stats.db").unwrap();
+    /// ```
+    fn stats(&mut self) -> Result<DatabaseStats, DatabaseError> {
+        trace!("Collecting database stats");
+        let database = self.read_lock("stats")?;
+        let mut stats = DatabaseStats{
+            max_memory: self.max_memory.map(|(m, _)| m),
+            size_budget: self.size_budget,
+            ..Default::default()
+        };
+        if let Ok(writes) = self.writes.lock() {
+            stats.writes = *writes;
+        };
+        stats.over_size_budget = match (stats.size_budget, stats.writes.last_save) {
+            (Some(budget), Some(save)) => save.written_bytes > budget,
+            _ => false,
+        };
+        if let Ok(lock_waits) = self.lock_waits.lock() {
+            stats.lock_waits = lock_waits.clone();
+        };
+        for t in database.list_tables() {
+            if let Ok(table) = database.get_table_ref(&t) {
+                let table_stats = table.stats();
+                stats.approx_bytes += table_stats.approx_bytes;
+                stats.tables.insert(t, table_stats);
+            };
+        };
+        return Ok(stats)
+    }
+
+    /// Returns a RateLimiter sharing the database of the Client, storing its state in the
+    /// named Table; the Table is created if it does not exist.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use persistent_keystore_rs::ratelimit::RateLimit;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("ratelimiter.db"), None).unwrap();
+    /// let limit = RateLimit::TokenBucket{ capacity: 2, refill_every: Duration::from_secs(60) };
+    /// let mut limiter = c.rate_limiter("Logins".to_string(), limit).unwrap();
+    ///
+    /// assert!(limiter.check("alice").unwrap().allowed);
+    /// assert!(limiter.check("alice").unwrap().allowed);
+    /// assert!(!limiter.check("alice").unwrap().allowed);
+    /// assert!(limiter.check("bob").unwrap().allowed);
+    /// # std::fs::remove_file("ratelimiter.db").unwrap();
+    /// ```
+    fn rate_limiter(&mut self, table: String, limit: RateLimit) -> Result<RateLimiter, DatabaseError> {
+        trace!("Creating RateLimiter on table {}", table);
+        RateLimiter::new(self, table, limit)
+    }
+
+    /// Returns a SessionStore sharing the database of the Client, storing its Sessions in the
+    /// named Table; the Table is created if it does not exist.  Sessions expire once they
+    /// have not been created or touched for the idle timeout.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("sessionstore.db"), None).unwrap();
+    /// let mut sessions = c.session_store("Sessions".to_string(), Duration::from_secs(1800)).unwrap();
+    ///
+    /// let session = sessions.create_session("alice", "{\"theme\":\"dark\"}".to_string()).unwrap();
+    /// assert_eq!(sessions.get_session(&session.id).unwrap().unwrap().user, "alice");
+    /// assert!(sessions.touch(&session.id).unwrap().is_some());
+    ///
+    /// assert_eq!(sessions.revoke_all_for_user("alice").unwrap(), 1);
+    /// assert!(sessions.get_session(&session.id).unwrap().is_none());
+    /// # std::fs::remove_file("sessionstore.db").unwrap();
+    /// ```
+    fn session_store(&mut self, table: String, idle_timeout: Duration) -> Result<SessionStore, DatabaseError> {
+        trace!("Creating SessionStore on table {}", table);
+        SessionStore::new(self, table, idle_timeout)
+    }
+
+    /// Returns a FlagStore sharing the database of the Client, storing its feature flags in
+    /// the named Table; the Table is created if it does not exist.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use persistent_keystore_rs::flags::Flag;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("flagstore.db"), None).unwrap();
+    /// let mut flags = c.flag_store("Flags".to_string()).unwrap();
+    ///
+    /// flags.set_flag("new-editor", Flag::Bool(true)).unwrap();
+    /// flags.set_flag("checkout", Flag::Variant("one-page".to_string())).unwrap();
+    /// flags.set_flag("beta", Flag::Percentage(0)).unwrap();
+    ///
+    /// assert!(flags.is_enabled("new-editor", "alice").unwrap());
+    /// assert!(!flags.is_enabled("beta", "alice").unwrap());
+    /// assert!(!flags.is_enabled("unknown", "alice").unwrap());
+    /// assert_eq!(flags.variant("checkout").unwrap(), Some("one-page".to_string()));
+    /// # std::fs::remove_file("flagstore.db").unwrap();
+    /// ```
+    fn flag_store(&mut self, table: String) -> Result<FlagStore, DatabaseError> {
+        trace!("Creating FlagStore on table {}", table);
+        FlagStore::new(self, table)
+    }
+
+    /// Returns a CounterStore sharing the database of the Client, storing the buckets of its
+    /// counters in the named Table; the Table is created if it does not exist.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use persistent_keystore_rs::counters::{CounterRetention, Rollup};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("counterstore.db"), None).unwrap();
+    /// let mut counters = c.counter_store("PageViews".to_string(), CounterRetention::default()).unwrap();
+    ///
+    /// counters.increment("/index.html", 1).unwrap();
+    /// counters.increment("/index.html", 2).unwrap();
+    /// assert_eq!(counters.count("/index.html", Rollup::Day).unwrap(), 3);
+    /// assert_eq!(counters.series("/index.html", Rollup::Hour, 24).unwrap().len(), 24);
+    /// # std::fs::remove_file("counterstore.db").unwrap();
+    /// ```
+    fn counter_store(&mut self, table: String, retention: CounterRetention) -> Result<CounterStore, DatabaseError> {
+        trace!("Creating CounterStore on table {}", table);
+        CounterStore::new(self, table, retention)
+    }
+
+    /// Acquires the named Lease for ttl, returning None if an unexpired Lease on the name is
+    /// held.  Leases are stored in the system table LEASE_TABLE, created if it does not
+    /// exist, and coordinate every clone of the Client and other processes sharing the
+    /// database file; see Lease.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("acquirelease.db"), None).unwrap();
+    /// let mut leader = c.acquire_lease("leader".to_string(), Duration::from_secs(30)).unwrap().unwrap();
+    /// assert!(c.acquire_lease("leader".to_string(), Duration::from_secs(30)).unwrap().is_none());
+    ///
+    /// assert!(leader.renew(Duration::from_secs(30)).unwrap());
+    /// let token = leader.token();
+    /// assert!(leader.release().unwrap());
+    ///
+    /// let next = c.acquire_lease("leader".to_string(), Duration::from_secs(30)).unwrap().unwrap();
+    /// assert!(next.token() > token);
+    /// # std::fs::remove_file("acquirelease.db").unwrap();
+    /// # std::fs::remove_file("acquirelease.db.leases.lock").unwrap();
+    /// ```
+    fn acquire_lease(&mut self, name: String, ttl: Duration) -> Result<Option<Lease>, DatabaseError> {
+        trace!("Acquiring lease {}", name);
+        Lease::acquire(self, name, ttl)
+    }
+
+    /// Removes the spill segments and blob directories beside the database file that belong
+    /// to no table of the database, such as those left by a process that stopped before
+    /// saving; returning the paths removed.  Only files last modified longer ago than the
+    /// retention are removed, so that files being written by another process are kept.
+    /// Other files, such as the `.bak` backup read by EmptyFilePolicy::UseBackup, are never
+    /// removed.
+    /// ```
+    /// use persistent_keystore_rs::Client;
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::time::Duration;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("gcfiles.db"), None).unwrap();
+    /// std::fs::write("gcfiles.db.bak", b"backup").unwrap();
+    ///
+    /// assert!(c.gc_files(Duration::ZERO).unwrap().is_empty());
+    /// assert!(Path::new("gcfiles.db.bak").exists());
+    /// # std::fs::remove_file("gcfiles.db").unwrap();
+    /// # std::fs::remove_file("gcfiles.db.bak").unwrap();
+    /// ```
+    fn gc_files(&mut self, retention: Duration) -> Result<Vec<PathBuf>, DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Collecting orphaned files");
+        let path = self.path()?;
+        let database = self.read_lock("gc_files")?;
+        let removed = gc::gc_files(&path, &database, retention)?;
+        log_event!(Subsystem::Save, DEBUG, "Removed {} orphaned files", removed.len());
+        return Ok(removed)
+    }
+
+    /// Trains a zstd dictionary of at most max_size bytes from the entries of every table,
+    /// returning its size; enabled by the `zstd` feature.  Subsequent saves compress the
+    /// database with the dictionary, which is stored in the database file and used again
+    /// when it is opened, substantially reducing the size of files holding many small,
+    /// similar entries.  Files compressed with a dictionary require the `zstd` feature to
+    /// be opened.
+    ///
+    /// Training fails with DatabaseError::DatabaseIoError if there are too few entries to
+    /// train a dictionary of the requested size.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("traindictionary.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("Events"))
+    /// #    .primary_field(FieldType::U64).unwrap()
+    /// #    .add_field(String::from("Kind"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for i in 0..2000 {
+    /// #     let entry = Entry::new()
+    /// #         .set_primary_field(Field::U64(i)).unwrap()
+    /// #         .add_field("Kind".to_string(), Field::String(format!("page-view:{}", i % 7))).unwrap()
+    /// #         .build().unwrap();
+    /// #     c.insert("Events".to_string(), entry).unwrap();
+    /// # };
+    /// let size = c.train_dictionary(4096).unwrap();
+    /// assert!(size <= 4096);
+    /// c.save().unwrap();
+    /// # std::fs::remove_file("traindictionary.db").unwrap();
+    /// ```
+    #[cfg(feature = "zstd")]
+    fn train_dictionary(&mut self, max_size: usize) -> Result<usize, DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Training dictionary");
+        let trained = match self.read_lock("train_dictionary") {
+            Ok(database) => dictionary::train(&database, max_size)?,
+            Err(e) => return Err(e),
+        };
+
+        let size = trained.len();
+        if let Ok(mut dictionary) = self.dictionary.lock() {
+            log_event!(Subsystem::Save, DEBUG, "Trained dictionary of {} bytes", size);
+            *dictionary = Some(Arc::new(trained));
+            return Ok(size)
+        };
+        error!("Unable to get dictionary lock");
+        Err(DatabaseError::UnableToGetLock)
+    }
+
+    /// Returns an immutable, point-in-time view of the database which is read without
+    /// locking it; see Snapshot.  Taking a Snapshot shares every table with the database
+    /// rather than copying it; a table is copied only when it is next modified while a
+    /// Snapshot holding it is alive.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("snapshot.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = |count| Entry::new()
+    /// #    .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #    .build().unwrap();
+    /// c.insert("MyTable".to_string(), entry(1)).unwrap();
+    /// let snapshot = c.snapshot().unwrap();
+    /// c.update("MyTable".to_string(), entry(2)).unwrap();
+    ///
+    /// let before = snapshot.get("MyTable".to_string(), Field::String("MyEntry".to_string())).unwrap();
+    /// assert_eq!(before.fields["Count"], Field::I64(1));
+    /// # std::fs::remove_file("snapshot.db").unwrap();
+    /// ```
+    fn snapshot(&mut self) -> Result<Snapshot, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Taking snapshot");
+        let database = self.read_lock("snapshot")?;
+        let snapshot = Snapshot::new(database.clone(), self.clock.timestamp(), self.access.clone());
+        log_event!(Subsystem::Query, DEBUG, "Took snapshot of {} tables", snapshot.list_tables().len());
+        return Ok(snapshot)
+    }
+
+    /// Returns the changes recorded in place of being made since last called, oldest first,
+    /// by a Client built with ClientBuilder::dry_run and its clones
+    fn take_dry_run_actions(&mut self) -> Result<Vec<DryRunAction>, DatabaseError> {
+        match self.dry_runs.lock() {
+            Ok(mut actions) => Ok(std::mem::take(&mut *actions)),
+            Err(_) => {
+                error!("Unable to get dry run mutex");
+                Err(DatabaseError::UnableToGetLock)
+            },
+        }
+    }
+
+    /// Returns a clone of the Client whose operations are reported to its OperationObservers
+    /// and authorizer with the supplied context, such as the user on whose behalf they are
+    /// made.  The clone shares the database of the Client; the context of the Client is
+    /// unchanged.
+    /// ```
+    /// use persistent_keystore_rs::{Client, Field, FieldType, OperationEvent, OperationObserver, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Default)]
+    /// struct AccessLog(Mutex<Vec<String>>);
+    ///
+    /// impl OperationObserver for AccessLog {
+    ///     fn on_get(&self, event: &OperationEvent<'_>) {
+    ///         let line = format!("{} read {} from {}", event.context.unwrap_or("unknown"), event.key.unwrap(), event.table);
+    ///         self.0.lock().unwrap().push(line);
+    ///     }
+    /// }
+    ///
+    /// let log = Arc::new(AccessLog::default());
+    /// let mut c = Client::builder(Path::new("withcontext.db"))
+    ///     .observer(log.clone())
+    ///     .build().unwrap();
+    /// # let table = Table::new()
+    /// #    .name("Secrets".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Value".to_string(), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let mut alice = c.with_context("alice".to_string());
+    /// let _ = alice.get("Secrets".to_string(), Field::String("ApiKey".to_string()));
+    /// assert_eq!(*log.0.lock().unwrap(), vec!["alice read ApiKey from Secrets".to_string()]);
+    /// # std::fs::remove_file("withcontext.db").unwrap();
+    /// ```
+    fn with_context(&mut self, context: String) -> Box<dyn DatabaseClient> {
+        let mut c = self.clone();
+        c.access.context = Some(Arc::from(context));
+        Box::new(c)
+    }
+
+    /// Sets a metadata value saved with the database, such as the version of the
+    /// application or a migration cursor, returning the previous value of the key; for
+    /// a handful of small values that do not warrant a table.  Metadata of other files is
+    /// not copied by merge_from.
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("setmeta.db"), None).unwrap();
+    /// c.set_meta("Environment".to_string(), "staging".to_string()).unwrap();
+    /// c.set_meta("MigrationCursor".to_string(), "0007".to_string()).unwrap();
+    /// c.save().unwrap();
+    /// # drop(c);
+    ///
+    /// let mut c = Client::open(Path::new("setmeta.db")).unwrap();
+    /// assert_eq!(c.get_meta("MigrationCursor".to_string()).unwrap(), Some("0007".to_string()));
+    /// assert_eq!(c.remove_meta("Environment".to_string()).unwrap(), Some("staging".to_string()));
+    /// assert_eq!(c.get_meta("Environment".to_string()).unwrap(), None);
+    /// # std::fs::remove_file("setmeta.db").unwrap();
+    /// ```
+    fn set_meta(&mut self, key: String, value: String) -> Result<Option<String>, DatabaseError> {
+        trace!("Setting metadata {}", key);
+        let mut database = self.write_database("set_meta")?;
+        let previous = database.set_meta(key, value);
+        database.applied();
+        return Ok(previous)
+    }
+
+    /// Returns a metadata value saved with the database; see DatabaseClient::set_meta
+    fn get_meta(&mut self, key: String) -> Result<Option<String>, DatabaseError> {
+        trace!("Getting metadata {}", key);
+        let database = self.read_lock("get_meta")?;
+        return Ok(database.get_meta(&key).cloned())
+    }
+
+    /// Removes a metadata value saved with the database, returning it; see
+    /// DatabaseClient::set_meta
+    fn remove_meta(&mut self, key: String) -> Result<Option<String>, DatabaseError> {
+        trace!("Removing metadata {}", key);
+        let mut database = self.write_database("remove_meta")?;
+        let previous = database.remove_meta(&key);
+        if previous.is_some() {
+            database.applied();
+        };
+        return Ok(previous)
+    }
+
+    /// Inserts the entry as insert, unless an earlier idempotent write was made with the
+    /// same idempotency key, so that a retried message is applied once.  Returns true if
+    /// the entry was inserted, and false if the write was skipped as a retry; without a
+    /// key the entry is always inserted.
+    ///
+    /// Keys are shared by all tables and are recorded only by writes that succeed; see
+    /// ClientBuilder::idempotency_capacity for how many are remembered.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("insertidempotent.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("Payments".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Amount".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let payment = Entry::new()
+    ///     .set_primary_field(Field::String("Payment1".to_string())).unwrap()
+    ///     .add_field("Amount".to_string(), Field::I64(100)).unwrap()
+    ///     .build().unwrap();
+    /// let message_id = Some("message-42".to_string());
+    /// assert!(c.insert_idempotent("Payments".to_string(), payment.clone(), message_id.clone()).unwrap());
+    /// // The retried message is skipped rather than failing with EntryExists
+    /// assert!(!c.insert_idempotent("Payments".to_string(), payment, message_id).unwrap());
+    /// # std::fs::remove_file("insertidempotent.db").unwrap();
+    /// ```
+    fn insert_idempotent(&mut self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Inserting entry into table {}: {}", table, entry);
+        self.authorize(Operation::Insert, &table, Some(&entry.primary_field))?;
+        self.write_once(table, entry, idempotency_key, WriteKind::Insert)
+    }
+
+    /// Inserts or updates the entry as insert_or_update, unless an earlier idempotent
+    /// write was made with the same idempotency key; see DatabaseClient::insert_idempotent
+    fn insert_or_update_idempotent(&mut self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Inserting or updating entry into table {}: {}", table, entry);
+        self.authorize(Operation::InsertOrUpdate, &table, Some(&entry.primary_field))?;
+        self.write_once(table, entry, idempotency_key, WriteKind::Update)
+    }
+
+    /// Updates the entry as update, unless an earlier idempotent write was made with the
+    /// same idempotency key, so that a retried increment is not applied twice; see
+    /// DatabaseClient::insert_idempotent
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("updateidempotent.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("Counters".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let counter = |count| Entry::new()
+    /// #    .set_primary_field(Field::String("Visits".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("Counters".to_string(), counter(0)).unwrap();
+    /// for _delivery in 0..2 {
+    ///     let current = c.get("Counters".to_string(), Field::String("Visits".to_string())).unwrap();
+    ///     let Field::I64(count) = current.fields["Count"] else { unreachable!() };
+    ///     c.update_idempotent("Counters".to_string(), counter(count + 1), Some("message-7".to_string())).unwrap();
+    /// };
+    /// let current = c.get("Counters".to_string(), Field::String("Visits".to_string())).unwrap();
+    /// assert_eq!(current.fields["Count"], Field::I64(1));
+    /// # std::fs::remove_file("updateidempotent.db").unwrap();
+    /// ```
+    fn update_idempotent(&mut self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Updating entry into table {}: {}", table, entry);
+        self.authorize(Operation::Update, &table, Some(&entry.primary_field))?;
+        self.write_once(table, entry, idempotency_key, WriteKind::Update)
+    }
+
+    /// Returns the sequence number of the latest mutation made to the database through the
+    /// Client or any of its clones; pass it to flush_until to wait until that mutation, and
+    /// every mutation before it, is durable
+    fn write_sequence(&mut self) -> Result<u64, DatabaseError> {
+        Ok(self.durability.sequences()?.0)
+    }
+
+    /// Waits until every mutation made to the database so far is durable; see flush_until
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use std::time::Duration;
+    /// let mut c = Client::builder(Path::new("flush.db"))
+    ///     .group_commit(Duration::from_millis(5))
+    ///     .build().unwrap();
+    /// # let table = Table::new()
+    /// #    .name("Orders".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Status".to_string(), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let order = |status: &str| Entry::new()
+    /// #    .set_primary_field(Field::String("Order1".to_string())).unwrap()
+    /// #    .add_field("Status".to_string(), Field::String(status.to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// // Progress updates are acknowledged from memory and saved by the next group commit
+    /// c.insert("Orders".to_string(), order("Packing")).unwrap();
+    /// c.update("Orders".to_string(), order("Packed")).unwrap();
+    ///
+    /// // The payment is not acknowledged until it is durable
+    /// c.update("Orders".to_string(), order("Paid")).unwrap();
+    /// c.flush().unwrap();
+    /// # drop(c);
+    /// # std::fs::remove_file("flush.db").unwrap();
+    /// ```
+    fn flush(&mut self) -> Result<(), DatabaseError> {
+        let sequence = self.write_sequence()?;
+        self.flush_until(sequence)
+    }
+
+    /// Waits until the mutation with the sequence number returned by write_sequence, and
+    /// every mutation before it, is durable.  With ClientBuilder::group_commit this waits
+    /// for the next group commit, shared by every flush waiting for it, and returns
+    /// DatabaseError::DatabaseIoError if that commit fails; otherwise the database is
+    /// saved unless a save since the mutation already included it.  A sequence number
+    /// greater than write_sequence returns DatabaseError::UnknownSequence.
+    fn flush_until(&mut self, sequence: u64) -> Result<(), DatabaseError> {
+        log_event!(Subsystem::Save, TRACE, "Flushing mutations up to {}", sequence);
+        let (written, durable) = self.durability.sequences()?;
+        if sequence > written {
+            return Err(DatabaseError::UnknownSequence(sequence))
+        };
+        if durable >= sequence {
+            log_event!(Subsystem::Save, TRACE, "Mutations up to {} are durable", durable);
+            return Ok(())
+        };
+        match self.group_commit {
+            true => self.durability.wait(sequence),
+            false => self.save(),
+        }
+    }
+
+    /// Returns all entries from the specified table, as scan, with the write sequence of
+    /// the database as of the scan; pass it to has_changed_since to learn whether the
+    /// entries may be stale without scanning again.  The sequence is read under the same
+    /// lock as the entries, so it counts every mutation they include and none they do not.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("scanversioned.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// let (cached, sequence) = c.scan_versioned("MyTable".to_string()).unwrap();
+    /// assert!(cached.is_empty());
+    /// assert!(!c.has_changed_since(sequence).unwrap());
+    ///
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///     .build().unwrap();
+    /// c.insert("MyTable".to_string(), entry).unwrap();
+    /// assert!(c.has_changed_since(sequence).unwrap());
+    /// # std::fs::remove_file("scanversioned.db").unwrap();
+    /// ```
+    fn scan_versioned(&mut self, table: String) -> Result<(Vec<Arc<Entry>>, u64), DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {}", table);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("scan_versioned")?;
+        let sequence = self.durability.sequences()?.0;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Scanning table {} at sequence {}", table, sequence);
+                return Ok((t.scan()?, sequence))
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+    }
+
+    /// Returns true if the database may have been changed, through the Client or any of
+    /// its clones, since the write sequence returned by scan_versioned or write_sequence.
+    /// Every mutation of any table counts; those that fail do not.  Entries expiring are not
+    /// counted until removed by prune.
+    fn has_changed_since(&mut self, sequence: u64) -> Result<bool, DatabaseError> {
+        Ok(self.durability.sequences()?.0 > sequence)
+    }
+
+    /// Returns a clone of the Client whose methods return DatabaseError::WouldBlock at once,
+    /// rather than wait, when the database lock is held by another operation; such as a
+    /// write waiting for a save, or a read waiting for a write.  For callers that would
+    /// rather serve stale data than wait.  Locks held only briefly, such as that of the
+    /// file of a save, are still waited for.  See also try_get and the other try_ methods.
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// let mut c = Client::new(Path::new("nonblocking.db"), None).unwrap();
+    /// let mut non_blocking = c.non_blocking();
+    /// match non_blocking.list_tables() {
+    ///     Ok(tables) => assert!(tables.is_empty()),
+    ///     Err(DatabaseError::WouldBlock) => println!("Serving the cached list of tables"),
+    ///     Err(e) => panic!("{}", e),
+    /// };
+    /// # std::fs::remove_file("nonblocking.db").unwrap();
+    /// ```
+    fn non_blocking(&mut self) -> Box<dyn DatabaseClient> {
+        Box::new(self.without_waiting())
+    }
+
+    /// Returns the entry as get, or DatabaseError::WouldBlock without waiting if the
+    /// database lock is held; see non_blocking
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// # use std::collections::HashMap;
+    /// # use std::sync::Arc;
+    /// let mut c = Client::new(Path::new("tryget.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("Prices".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Cents".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("Widget".to_string())).unwrap()
+    /// #    .add_field("Cents".to_string(), Field::I64(250)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("Prices".to_string(), entry).unwrap();
+    /// let mut stale: HashMap<Field, Arc<Entry>> = HashMap::new();
+    /// let key = Field::String("Widget".to_string());
+    /// let price = match c.try_get("Prices".to_string(), key.clone()) {
+    ///     Ok(entry) => {
+    ///         stale.insert(key, entry.clone());
+    ///         entry
+    ///     },
+    ///     Err(DatabaseError::WouldBlock) => stale[&key].clone(),
+    ///     Err(e) => panic!("{}", e),
+    /// };
+    /// assert_eq!(price.fields["Cents"], Field::I64(250));
+    /// # std::fs::remove_file("tryget.db").unwrap();
+    /// ```
+    fn try_get(&mut self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError> {
+        self.without_waiting().get(table, primary_field)
+    }
+
+    /// Returns the entries of the table as scan, or DatabaseError::WouldBlock without
+    /// waiting if the database lock is held; see non_blocking
+    fn try_scan(&mut self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.without_waiting().scan(table)
+    }
+
+    /// Returns the entries matching the criteria as query, or DatabaseError::WouldBlock
+    /// without waiting if the database lock is held; see non_blocking
+    fn try_query(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.without_waiting().query(table, criteria)
+    }
+
+    /// Inserts the entry as insert, or returns DatabaseError::WouldBlock without waiting
+    /// if the database lock is held; see non_blocking
+    fn try_insert(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        self.without_waiting().insert(table, entry)
+    }
+
+    /// Inserts or updates the entry as insert_or_update, or returns
+    /// DatabaseError::WouldBlock without waiting if the database lock is held; see
+    /// non_blocking
+    fn try_insert_or_update(&mut self, table: String, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        self.without_waiting().insert_or_update(table, entry)
+    }
+
+    /// Updates the entry as update, or returns DatabaseError::WouldBlock without waiting
+    /// if the database lock is held; see non_blocking
+    fn try_update(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        self.without_waiting().update(table, entry)
+    }
+
+    /// Deletes the entry as delete, or returns DatabaseError::WouldBlock without waiting
+    /// if the database lock is held; see non_blocking
+    fn try_delete(&mut self, table: String, primary_field: Field) -> Result<(), DatabaseError> {
+        self.without_waiting().delete(table, primary_field)
+    }
+
+    /// Copies the entries of the tables, or of every table if none are named, into another
+    /// client, such as one opened on a file with a different format, one table at a time.
+    /// Entries are cloned from the source one at a time as they are inserted, rather than
+    /// the whole table at once, and are written as new, so their expirations restart.
+    /// ```
+    /// use persistent_keystore_rs::{Client, CopyConflict, CopyOptions, Entry, Field, FieldType, Table};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut old = Client::new(Path::new("copyfrom.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # old.create_table(table).unwrap();
+    /// # for i in 0..3 {
+    /// #     let entry = Entry::new()
+    /// #         .set_primary_field(Field::String(format!("Key{}", i))).unwrap()
+    /// #         .add_field("Count".to_string(), Field::I64(i)).unwrap()
+    /// #         .build().unwrap();
+    /// #     old.insert("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// let mut new = Client::new(Path::new("copyinto.db"), None).unwrap();
+    /// let options = CopyOptions::default()
+    ///     .create_tables(true)
+    ///     .on_conflict(CopyConflict::Skip)
+    ///     .progress(|p| println!("{}: {} of {} entries", p.table, p.copied + p.skipped, p.entries));
+    /// let report = old.copy_into(&mut *new, &["MyTable"], options).unwrap();
+    /// assert_eq!(report.created_tables, vec!["MyTable".to_string()]);
+    /// assert_eq!(report.copied, 3);
+    /// assert_eq!(new.scan("MyTable".to_string()).unwrap().len(), 3);
+    /// # std::fs::remove_file("copyfrom.db").unwrap();
+    /// # std::fs::remove_file("copyinto.db").unwrap();
+    /// ```
+    fn copy_into(&mut self, other: &mut dyn DatabaseClient, tables: &[&str], mut options: CopyOptions) -> Result<CopyReport, DatabaseError> {
+        let tables = match tables.is_empty() {
+            true => self.list_tables()?,
+            false => tables.iter().map(|table| table.to_string()).collect(),
+        };
+        log_event!(Subsystem::Write, INFO, "Copying {} tables", tables.len());
+        let tracker = progress::Tracker::new(&self.progress, &self.clock, ProgressOperation::Copy);
+        let existing = other.list_tables()?;
+        let mut report = CopyReport::default();
+        for (tables_copied, table) in tables.iter().enumerate() {
+            let entries = self.scan(table.clone())?;
+            if !existing.contains(table) {
+                if !options.create_tables {
+                    error!("Table {} does not exist in the destination", table);
+                    return Err(DatabaseError::TableDoesNotExist(table.clone()))
+                };
+                let schema = self.read_lock("copy_into")?.get_table_ref(table)?.empty_copy()?;
+                other.create_table(schema)?;
+                report.created_tables.push(table.clone());
+            };
+
+            log_event!(Subsystem::Write, DEBUG, "Copying {} entries of table {}", entries.len(), table);
+            let mut progress = CopyProgress{
+                table: table.clone(),
+                entries: entries.len(),
+                copied: 0,
+                skipped: 0,
+                tables_copied,
+                tables: tables.len(),
+            };
+            for entry in entries {
+                let entry = Entry::clone(&entry);
+                let result = match options.on_conflict {
+                    CopyConflict::Overwrite => other.insert_or_update(table.clone(), entry).map(|_| ()),
+                    _ => other.insert(table.clone(), entry),
+                };
+                match result {
+                    Ok(_) => progress.copied += 1,
+                    Err(DatabaseError::EntryExists) if options.on_conflict == CopyConflict::Skip => progress.skipped += 1,
+                    Err(e) => {
+                        error!("Unable to copy an entry of table {}: {}", table, e);
+                        return Err(e)
+                    },
+                };
+                if (progress.copied + progress.skipped).is_multiple_of(COPY_PROGRESS_INTERVAL) {
+                    if let Some(callback) = options.progress.as_mut() {
+                        callback(&progress);
+                    };
+                };
+                let entries = report.copied + report.skipped + progress.copied + progress.skipped;
+                if entries.is_multiple_of(PROGRESS_ENTRIES) {
+                    tracker.entries(ProgressPhase::Entries, entries, None);
+                };
+            };
+
+            report.copied += progress.copied;
+            report.skipped += progress.skipped;
+            progress.tables_copied += 1;
+            if let Some(callback) = options.progress.as_mut() {
+                callback(&progress);
+            };
+        };
+        log_event!(Subsystem::Write, DEBUG, "Copied {} entries, skipped {}", report.copied, report.skipped);
+        tracker.done(0, report.copied + report.skipped);
+        Ok(report)
+    }
+
+    /// Creates a Table named new_name holding the Entries of the named Table of the database
+    /// file at the supplied path, returning the number of Entries; for sharing reference
+    /// datasets between services.  The other Tables of that database are not kept in memory.
+    /// The Table keeps the schema and settings of the original, and its Entries are inserted
+    /// as by DatabaseClient::insert.  If an Entry cannot be inserted the Table is dropped.
+    /// The new name is checked against NamePolicy::default.
+    /// ```
+    /// use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut shared = Client::new(Path::new("attachfrom.db"), None).unwrap();
+    /// let table = Table::new()
+    ///    .name("Countries".to_string())
+    ///    .primary_field(FieldType::String).unwrap()
+    ///    .add_field("Name".to_string(), FieldType::String).unwrap()
+    ///    .build().unwrap();
+    /// shared.create_table(table).unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("NZ".to_string())).unwrap()
+    ///     .add_field("Name".to_string(), Field::String("New Zealand".to_string())).unwrap()
+    ///     .build().unwrap();
+    /// shared.insert("Countries".to_string(), entry).unwrap();
+    /// shared.save().unwrap();
+    ///
+    /// let mut c = Client::new(Path::new("attachto.db"), None).unwrap();
+    /// let attached = c.attach_table_from(Path::new("attachfrom.db"), "Countries", "RefCountries").unwrap();
+    /// assert_eq!(attached, 1);
+    /// assert!(c.exists("RefCountries".to_string(), Field::String("NZ".to_string())).unwrap());
+    /// # std::fs::remove_file("attachfrom.db").unwrap();
+    /// # std::fs::remove_file("attachto.db").unwrap();
+    /// ```
+    fn attach_table_from(&mut self, path: &Path, table: &str, new_name: &str) -> Result<usize, DatabaseError> {
+        log_event!(Subsystem::Write, INFO, "Attaching table {} of database {:?} as {}", table, path, new_name);
+        NamePolicy::default().check_table_name(new_name)?;
+        let Some(mut source) = read_table(path, table)? else {
+            error!("Table {} does not exist in database {:?}", table, path);
+            return Err(DatabaseError::TableDoesNotExist(table.to_string()))
+        };
+        source.attach_files(path)?;
+        let mut schema = source.empty_copy()?;
+        schema.name = new_name.to_string();
+        self.create_table(schema)?;
+
+        let mut attached = 0;
+        for entry in source.iter_entries() {
+            if let Err(e) = self.insert(new_name.to_string(), Entry::clone(&entry)) {
+                error!("Unable to attach an entry of table {} as {}: {}", table, new_name, e);
+                self.drop_table(&new_name.to_string())?;
+                return Err(e)
+            };
+            attached += 1;
+        };
+        log_event!(Subsystem::Write, DEBUG, "Attached {} entries of table {} as {}", attached, table, new_name);
+        Ok(attached)
+    }
+
+    /// Returns the entries of the specified table last updated longer ago than older_than,
+    /// oldest first, whether or not the table expires entries; for jobs refreshing
+    /// long-lived records before they go stale.  See Table::stale.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::path::Path;
+    /// use std::time::Duration;
+    /// let mut c = Client::new(Path::new("scanstale.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// for entry in c.scan_stale("MyTable".to_string(), Duration::from_secs(3600)).unwrap() {
+    ///     println!("refreshing {}", entry.primary_field);
+    /// };
+    /// # std::fs::remove_file("scanstale.db").unwrap();
+    /// ```
+    fn scan_stale(&mut self, table: String, older_than: Duration) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {} for entries older than {:?}", table, older_than);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("scan_stale")?;
+        let now = self.clock.timestamp();
+        match database.read_table(&table, now) {
+            Ok(t) => {
+                let stale = t.stale(older_than, now)?;
+                log_event!(Subsystem::Query, DEBUG, "Found {} entries of table {} older than {:?}", stale.len(), table, older_than);
+                Ok(stale)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                Err(DatabaseError::TableDoesNotExist(table))
+            },
+        }
+    }
+
+    /// Refreshes the last_timestamp of each entry of the specified table with a primary
+    /// field in keys, under a single lock, returning the number refreshed; keys without an
+    /// entry are skipped.  The fields of the entries are unchanged; see Table::touch_at.
+    /// For keepalive workloads refreshing many entries at once, such as sessions kept
+    /// from expiring by heartbeats.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("touchmany.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let keys = vec![Field::String("MyFirstEntry".to_string()), Field::String("Missing".to_string())];
+    /// assert_eq!(c.touch_many("MyTable".to_string(), &keys).unwrap(), 1);
+    /// # std::fs::remove_file("touchmany.db").unwrap();
+    /// ```
+    fn touch_many(&mut self, table: String, keys: &[Field]) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Touching {} entries of table {}", keys.len(), table);
+        self.authorize(Operation::Update, &table, None)?;
+        let mut database = self.write_database("touch_many")?;
+        let now = self.clock.timestamp();
+        let t = match database.get_table(&table) {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            },
+        };
+        let mut touched = 0;
+        for key in keys {
+            if t.touch_at(key, now)? {
+                touched += 1;
+            };
+        };
+        log_event!(Subsystem::Write, DEBUG, "Touched {} of {} entries of table {}", touched, keys.len(), table);
+        if touched > 0 {
+            database.applied();
+        };
+        Ok(touched)
+    }
+
+    /// Returns a Receiver of the subsequent lifecycle events of the database, sent by the
+    /// Client, its clones and their background threads; see LifecycleEvents.  To receive
+    /// LifecycleEvent::Opened, subscribe before opening with ClientBuilder::lifecycle_events.
+    /// ```
+    /// use persistent_keystore_rs::{Client, LifecycleEvent};
+    /// use persistent_keystore_rs::prelude::*;
+    /// use std::path::Path;
+    /// let mut c = Client::new(Path::new("subscribelifecycle.db"), None).unwrap();
+    /// let rx = c.subscribe_lifecycle();
+    /// c.prune().unwrap();
+    /// assert_eq!(rx.try_recv().unwrap(), LifecycleEvent::Pruned{removed: 0});
+    /// # std::fs::remove_file("subscribelifecycle.db").unwrap();
+    /// ```
+    fn subscribe_lifecycle(&mut self) -> Receiver<LifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    /// Returns the entries of the specified table that will expire within the supplied
+    /// duration, with the time left until each expires, soonest first; for refreshing
+    /// entries such as credentials before prune removes them.  See Table::expiring, and
+    /// ClientBuilder::expiry_warning to be notified from the background sync thread.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry, Field};
+    /// # use std::path::Path;
+    /// use std::time::Duration;
+    /// let mut c = Client::new(Path::new("expiringwithin.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_expiration(Duration::from_secs(3600))
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// for (entry, ttl) in c.expiring_within("MyTable".to_string(), Duration::from_secs(7200)).unwrap() {
+    ///     println!("refreshing {}, which expires in {:?}", entry.primary_field, ttl);
+    /// };
+    /// # std::fs::remove_file("expiringwithin.db").unwrap();
+    /// ```
+    fn expiring_within(&mut self, table: String, within: Duration) -> Result<Vec<(Arc<Entry>, Duration)>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Scanning table {} for entries expiring within {:?}", table, within);
+        self.authorize(Operation::Scan, &table, None)?;
+        let database = self.read_lock("expiring_within")?;
+        let now = self.clock.timestamp();
+        match database.read_table(&table, now) {
+            Ok(t) => {
+                let expiring = t.expiring(within, now)?;
+                log_event!(Subsystem::Query, DEBUG, "Found {} entries of table {} expiring within {:?}", expiring.len(), table, within);
+                Ok(expiring)
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                Err(DatabaseError::TableDoesNotExist(table))
+            },
+        }
+    }
+
+    /// Sets the supplied fields of an existing entry within the specified table, keeping its
+    /// other fields, and returns the patched entry; unlike update, the whole entry need not
+    /// be sent.  Optional fields patched with Field::Null are cleared, so they can be told
+    /// apart from fields that were never set.
+    /// If the entry does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use std::collections::HashMap;
+    /// let mut c = Client::new(Path::new("patchentry.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_optional_field(String::from("Notes"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .add_field("Notes".to_string(), Field::String("draft".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let fields = HashMap::from([
+    ///     ("Count".to_string(), Field::I64(2)),
+    ///     ("Notes".to_string(), Field::Null),
+    /// ]);
+    /// let entry = c.patch("MyTable".to_string(), Field::String("MyFirstEntry".to_string()), fields).unwrap();
+    /// assert_eq!(entry.get_field("Notes".to_string()), Some(Field::Null));
+    /// # std::fs::remove_file("patchentry.db").unwrap();
+    /// ```
+    fn patch(&mut self, table: String, primary_field: Field, fields: HashMap<String, Field>) -> Result<Arc<Entry>, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Patching entry {} in table {}", primary_field, table);
+        self.authorize(Operation::Update, &table, Some(&primary_field))?;
+        let mut database = self.write_database("patch")?;
+        let mut entry = match database.get_table(&table) {
+            Ok(t) => t.patched(&primary_field, fields)?,
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            },
+        };
+        self.check_write(&mut database, &table, &entry, WriteKind::Update)?;
+        let timestamp = self.clock.timestamp();
+        log_event!(Subsystem::Write, DEBUG, "Patching entry {} in table {}", primary_field, table);
+        database.get_table(&table)?.update_at(entry.clone(), timestamp)?;
+        database.applied();
+        entry.last_timestamp = Some(timestamp.wall);
+        Ok(Arc::new(entry))
+    }
+
+    /// Removes the named optional fields from an existing entry within the specified table,
+    /// keeping its other fields, and returns the updated entry.
+    /// Removing a required field returns DatabaseError::MissingRequiredField, and naming a
+    /// field the table does not declare returns DatabaseError::UnsupportedField; in either
+    /// case the entry is unchanged.
+    /// If the entry does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("unsetfields.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_optional_field(String::from("Notes"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .add_field("Notes".to_string(), Field::String("draft".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let entry = c.unset_fields("MyTable".to_string(), Field::String("MyFirstEntry".to_string()), &["Notes".to_string()]).unwrap();
+    /// assert_eq!(entry.get_field("Notes".to_string()), None);
+    /// assert!(c.unset_fields("MyTable".to_string(), Field::String("MyFirstEntry".to_string()), &["Count".to_string()]).is_err());
+    /// # std::fs::remove_file("unsetfields.db").unwrap();
+    /// ```
+    fn unset_fields(&mut self, table: String, primary_field: Field, fields: &[String]) -> Result<Arc<Entry>, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Unsetting fields {:?} of entry {} in table {}", fields, primary_field, table);
+        self.authorize(Operation::Update, &table, Some(&primary_field))?;
+        let mut database = self.write_database("unset_fields")?;
+        let mut entry = match database.get_table(&table) {
+            Ok(t) => t.unset(&primary_field, fields)?,
+            Err(e) => {
+                error!("Unable to write to table {}: {}", table, e);
+                return Err(e)
+            },
+        };
+        self.check_write(&mut database, &table, &entry, WriteKind::Update)?;
+        let timestamp = self.clock.timestamp();
+        log_event!(Subsystem::Write, DEBUG, "Unsetting fields {:?} of entry {} in table {}", fields, primary_field, table);
+        database.get_table(&table)?.update_at(entry.clone(), timestamp)?;
+        database.applied();
+        entry.last_timestamp = Some(timestamp.wall);
+        Ok(Arc::new(entry))
+    }
+
+    /// Query for entries within a specified table matching the Filter, which combines
+    /// Conditions with AND, OR and NOT; see Filter.  An index is used as by query_where
+    /// when the Conditions joined by AND at the top of the Filter cover one.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::{Field, Filter};
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("queryfilter.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("Users"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Status"), FieldType::String).unwrap()
+    /// #    .add_field(String::from("Tier"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for (key, status, tier) in [("u1", "active", "gold"), ("u2", "active", "bronze"), ("u3", "closed", "silver"), ("u4", "active", "silver")] {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String(key.to_string())).unwrap()
+    /// #        .add_field("Status".to_string(), Field::String(status.to_string())).unwrap()
+    /// #        .add_field("Tier".to_string(), Field::String(tier.to_string())).unwrap()
+    /// #        .build().unwrap();
+    /// #     c.insert("Users".to_string(), entry).unwrap();
+    /// # };
+    /// let filter = Filter::equals("Status", Field::String("active".to_string()))
+    ///     .and(Filter::equals("Tier", Field::String("gold".to_string()))
+    ///         .or(Filter::equals("Tier", Field::String("silver".to_string()))));
+    /// let results = c.query_filter("Users".to_string(), filter).unwrap();
+    /// assert_eq!(results.len(), 2);
+    /// # std::fs::remove_file("queryfilter.db").unwrap();
+    /// ```
+    fn query_filter(&mut self, table: String, filter: Filter) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        log_event!(Subsystem::Query, TRACE, "Querying table {} for {}", table, filter);
+        self.authorize(Operation::Query, &table, None)?;
+        let database = self.read_lock("query_filter")?;
+        match database.read_table(&table, self.clock.timestamp()) {
+            Ok(t) => {
+                log_event!(Subsystem::Query, DEBUG, "Querying table {} for {}", table, filter);
+                t.query_filter(&filter, self.clock.now(), &CancellationToken::new())
+            },
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                Err(DatabaseError::TableDoesNotExist(table))
+            },
+        }
+    }
+
+    /// Deletes up to max entries matching the supplied criteria, as delete_many, and
+    /// returns the number deleted and whether matching entries remain.  The search stops
+    /// once max entries are found, so large cleanups can be spread over several calls
+    /// without holding the write lock for the whole of the table.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use std::collections::HashMap;
+    /// let mut c = Client::new(Path::new("deletemanylimit.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Status"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for i in 0..25 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+    /// #        .add_field("Status".to_string(), Field::String("stale".to_string())).unwrap()
+    /// #        .build().unwrap();
+    /// #     c.insert("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// let criteria = HashMap::from([("Status".to_string(), Field::String("stale".to_string()))]);
+    /// let mut deleted = 0;
+    /// loop {
+    ///     let batch = c.delete_many_limit("MyTable".to_string(), criteria.clone(), 10).unwrap();
+    ///     deleted += batch.deleted;
+    ///     if !batch.more {
+    ///         break
+    ///     };
+    /// };
+    /// assert_eq!(deleted, 25);
+    /// # std::fs::remove_file("deletemanylimit.db").unwrap();
+    /// ```
+    fn delete_many_limit(&mut self, table: String, criteria: HashMap<String, Field>, max: u64) -> Result<DeleteBatch, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Deleting up to {} from table {}", max, table);
+        self.authorize(Operation::Delete, &table, None)?;
+        let limit = usize::try_from(max).unwrap_or(usize::MAX);
+        self.delete_where_limit(table, &equals_all(criteria), limit, &CancellationToken::new())
+    }
+
+    /// Applies update to a copy of every entry matching the supplied criteria and writes
+    /// the entries it changed under a single write lock, returning the number written.
+    /// Every changed entry is validated before any is written, so if one is invalid the
+    /// error is returned and no entry is changed.  If update changes the primary field of
+    /// an entry, DatabaseError::InvalidPrimaryKey is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// use std::collections::HashMap;
+    /// let mut c = Client::new(Path::new("updatemany.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Status"), FieldType::String).unwrap()
+    /// #    .add_field(String::from("Retries"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # for i in 0..3 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String(format!("Job{}", i))).unwrap()
+    /// #        .add_field("Status".to_string(), Field::String("failed".to_string())).unwrap()
+    /// #        .add_field("Retries".to_string(), Field::I64(i)).unwrap()
+    /// #        .build().unwrap();
+    /// #     c.insert("MyTable".to_string(), entry).unwrap();
+    /// # };
+    /// let criteria = HashMap::from([("Status".to_string(), Field::String("failed".to_string()))]);
+    /// let updated = c.update_many("MyTable".to_string(), criteria, &mut |entry| {
+    ///     let retries = entry.get_i64("Retries").unwrap_or(0);
+    ///     entry.fields.insert("Retries".to_string(), Field::I64(retries + 1));
+    ///     entry.fields.insert("Status".to_string(), Field::String("queued".to_string()));
+    /// }).unwrap();
+    /// assert_eq!(updated, 3);
+    /// # std::fs::remove_file("updatemany.db").unwrap();
+    /// ```
+    fn update_many(&mut self, table: String, criteria: HashMap<String, Field>, update: &mut dyn FnMut(&mut Entry)) -> Result<u64, DatabaseError> {
+        log_event!(Subsystem::Write, TRACE, "Updating many in table {}", table);
+        self.authorize(Operation::Update, &table, None)?;
+        let mut database = self.write_database("update_many")?;
+        let now = self.clock.timestamp();
+        let matches = match database.get_table_ref(&table) {
+            Ok(t) => t.filter_cancellable(&equals_all(criteria), now.wall, &CancellationToken::new())?,
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+        };
+
+        let mut updated = Vec::new();
+        for current in matches {
+            let mut entry = Entry::clone(&current);
+            update(&mut entry);
+            if entry.primary_field != current.primary_field {
+                error!("Unable to update entry {} of table {}: the primary field was changed", current.primary_field, table);
+                return Err(DatabaseError::InvalidPrimaryKey)
+            };
+            if entry.fields == current.fields {
+                continue
+            };
+            if let Err(e) = database.get_table_ref(&table)?.validate_entry(&entry) {
+                error!("Unable to update entry {} of table {}: {}", entry.primary_field, table, e);
+                return Err(e)
+            };
+            self.check_write(&mut database, &table, &entry, WriteKind::Update)?;
+            updated.push(entry);
+        };
+
+        log_event!(Subsystem::Write, DEBUG, "Updating {} entries in table {}", updated.len(), table);
+        let t = database.get_table(&table)?;
+        let count = updated.len() as u64;
+        for entry in updated {
+            t.update_at(entry, now)?;
+        };
+        if count > 0 {
+            database.applied();
+        };
+        Ok(count)
+    }
+
+    /// Applies modify to the entry with the supplied primary field and writes the result,
+    /// refreshing its last_timestamp; the read, modification and write are made under a
+    /// single write lock, so no other write to the entry can come between them.  The
+    /// modified entry is validated as by update.
+    /// If the entry does not exist or has expired, DatabaseError::EntryDoesNotExists is
+    /// returned, and if modify changes its primary field, DatabaseError::InvalidPrimaryKey.
+    /// ```
+    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
+    /// use persistent_keystore_rs::Field;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("modify.db"), None).unwrap();
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # c.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # c.insert("MyTable".to_string(), entry).unwrap();
+    /// let key = Field::String("MyFirstEntry".to_string());
+    /// c.modify("MyTable".to_string(), key.clone(), &mut |entry| {
+    ///     let count = entry.get_i64("Count").unwrap_or(0);
+    ///     entry.fields.insert("Count".to_string(), Field::I64(count + 1));
+    /// }).unwrap();
+    /// assert_eq!(c.get("MyTable".to_string(), key).unwrap().get_i64("Count"), Some(2));
+    /// # std::fs::remove_file("modify.db").unwrap();
+    /// ```
+    fn modify(&mut self, table: String, primary_field: Field, modify: &mut dyn FnMut(&mut Entry)) -> Result<(), DatabaseError> {
+        self.read_modify_write(Operation::Update, &table, &primary_field, |current, _| {
+            let mut entry = match current {
+                Some(e) => Entry::clone(&e),
+                None => return Err(DatabaseError::EntryDoesNotExists),
+            };
+            modify(&mut entry);
+            if entry.primary_field != primary_field {
+                error!("Unable to modify entry {} of table {}: the primary field was changed", primary_field, table);
+                return Err(DatabaseError::InvalidPrimaryKey)
+            };
+            Ok((Some(entry), ()))
+        })
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn create_loom_client(name: &str) -> Client {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push(format!("{}.db", name));
+
+        let mut c = Client{
+            database: sync::Arc::new(RwLock::new(Database::default())),
+            raw_file: sync::Arc::new(Mutex::new(temp_dir_path)),
+            signature: sync::Arc::new(Mutex::new(None)),
+            workers: sync::Arc::new(Vec::new()),
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIdGenerator),
+            max_memory: None,
+            size_budget: None,
+            verify_interval: None,
+            expiry_warning: None,
+            compact_interval: None,
+            save_gate: None,
+            dry_run: false,
+            dry_runs: sync::Arc::new(Mutex::new(Vec::new())),
+            idempotency_capacity: DEFAULT_IDEMPOTENCY_CAPACITY,
+            durability: sync::Arc::new(commit::Durability::new()),
+            group_commit: false,
+            wait_for_locks: true,
+            access: access::AccessControl::default(),
+            progress: None,
+            lifecycle: LifecycleEvents::new(),
+            writes: sync::Arc::new(Mutex::new(WriteStats::default())),
+            lock_waits: sync::Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "zstd")]
+            dictionary: sync::Arc::new(Mutex::new(None)),
+            root: None,
+        };
+
+        let table = structs::Table::new()
+            .name(name.to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c
+    }
+
+    fn loom_entry(key: &str) -> Entry {
+        structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap()
+    }
+
+    fn saved_entries(c: &Client, table: &str) -> usize {
+        let path = c.raw_file.lock().unwrap().clone();
+        let mut database = read_database(&path).unwrap();
+        database.get_table(&table.to_string()).unwrap().scan().unwrap().len()
+    }
+
+    #[test]
+    fn loom_clone_across_threads() {
+        loom::model(|| {
+            let mut c = create_loom_client("LoomCloneAcrossThreads");
+            let mut other = c.clone();
+
+            let h = loom::thread::spawn(move || {
+                other.insert("LoomCloneAcrossThreads".to_string(), loom_entry("Other")).unwrap();
+            });
+            c.insert("LoomCloneAcrossThreads".to_string(), loom_entry("Main")).unwrap();
+            h.join().unwrap();
+
+            assert_eq!(c.scan("LoomCloneAcrossThreads".to_string()).unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn loom_save_vs_insert() {
+        loom::model(|| {
+            let mut c = create_loom_client("LoomSaveVsInsert");
+            let mut other = c.clone();
+
+            let h = loom::thread::spawn(move || {
+                other.save().unwrap();
+            });
+            c.insert("LoomSaveVsInsert".to_string(), loom_entry("Main")).unwrap();
+            h.join().unwrap();
+
+            assert!(saved_entries(&c, "LoomSaveVsInsert") <= 1);
+            c.save().unwrap();
+            assert_eq!(saved_entries(&c, "LoomSaveVsInsert"), 1);
+        });
+    }
+
+    #[test]
+    fn loom_prune_vs_insert() {
+        loom::model(|| {
+            let mut c = create_loom_client("LoomPruneVsInsert");
+            let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+            c.clock = clock.clone();
+            let table = structs::Table::new()
+                .name("LoomPruneExpiring".to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                .add_expiration(Duration::from_secs(60))
+                .build().unwrap();
+            c.create_table(table).unwrap();
+            c.insert("LoomPruneExpiring".to_string(), loom_entry("Expired")).unwrap();
+            clock.advance(Duration::from_secs(61));
+            let mut other = c.clone();
+
+            let h = loom::thread::spawn(move || {
+                other.insert("LoomPruneExpiring".to_string(), loom_entry("Fresh")).unwrap();
+            });
+            c.prune().unwrap();
+            h.join().unwrap();
+
+            let keys: Vec<Field> = c.scan("LoomPruneExpiring".to_string()).unwrap().into_iter().map(|e| e.primary_field.clone()).collect();
+            assert_eq!(keys, vec![Field::String("Fresh".to_string())]);
+        });
+    }
+
+    #[test]
+    fn loom_drop_while_saving() {
+        loom::model(|| {
+            let mut c = create_loom_client("LoomDropWhileSaving");
+            c.insert("LoomDropWhileSaving".to_string(), loom_entry("Main")).unwrap();
+            let path = c.raw_file.lock().unwrap().clone();
+            let mut other = c.clone();
+
+            let h = loom::thread::spawn(move || {
+                other.save().unwrap();
+            });
+            drop(c);
+            h.join().unwrap();
+
+            let mut database = read_database(&path).unwrap();
+            assert_eq!(database.get_table(&"LoomDropWhileSaving".to_string()).unwrap().scan().unwrap().len(), 1);
+        });
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn create_client_table(name: String) -> (Box<dyn DatabaseClient>, TableBuilder) {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push(format!("{}.db", name));
+        if temp_dir_path.exists() {
+            std::fs::remove_file(temp_dir_path.clone().to_str().unwrap()).unwrap();
+        };
+
+        let c = Client::new(temp_dir_path, None).unwrap();
+        
+        let table = structs::Table::new()
+            .name(name);
+
+        (c, table)
+    }
+
+    #[test]
+    fn entry_missing_fields() {
+        let missing_fields_entry = structs::Entry::new()
+            .set_primary_field(Field::String("This entry should fail".to_string())).unwrap()
+            .build();
+        
+        if let Err(e) = missing_fields_entry {
+            match e {
+                DatabaseError::EntryMustContainFields => {},
+                _ => panic!("Expected EntryMustContainFields but not {}", e),
+            };
+        } else {
+            panic!("Expected error and did not receive one");
+        };
+    }
+
+    #[test]
+    fn entry_mismatched_primary_field_type() {
+        let (mut c, table_builder) = create_client_table("MismatchedPrimaryFieldType".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let mismatched_primary_field_type = structs::Entry::new()
+            .set_primary_field(Field::I64(123123)).unwrap()
+            .add_field("FirstKey".to_string(), Field::String("testing".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("MismatchedPrimaryFieldType".to_string(), mismatched_primary_field_type);
+
+        if let Err(e) = result {
+            match e {
+                DatabaseError::MismatchedFieldType => {},
+                _ => panic!("Expected MismatchedField type, not {}", e),
+            };
+        } else {
+            panic!("Expected MismatchedFieldType error but received none");
+        };
+    }
+
+    #[test]
+    fn entry_mismatched_secondary_field_type() {
+        let (mut c, table_builder) = create_client_table("MismatchedSecondaryFieldType".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let mismatched_optional_field = structs::Entry::new()
+            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::String("Should fail".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("MismatchedSecondaryFieldType".to_string(), mismatched_optional_field);
+
+        if let Err(e) = result {
+            match e {
+                DatabaseError::MismatchedFieldType => {},
+                _ => panic!("Expected MismatchedField type, not {}", e),
+            };
+        } else {
+            panic!("Expected MismatchedFieldType error but received none");
+        };
+    }
+
+    #[test]
+    fn entry_extra_undefined_field() {
+        let (mut c, table_builder) = create_client_table("EntryExtraUndefiniedField".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let extra_field = structs::Entry::new()
+            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
+            .add_field("ExtraField".to_string(), Field::String("ExtraField".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("EntryExtraUndefiniedField".to_string(), extra_field);
+
+        if let Err(e) = result {
+            match e {
+                DatabaseError::UnsupportedField(_) => {},
+                _ => panic!("Expected UnsupportedField, got {}", e),
+            };
+        } else {
+            panic!("Expected UnsupportedField, got none")
+        }
+    }
+
+    #[test]
+    fn entry_table_does_not_exist() {
+        let (mut c, table_builder) = create_client_table("EntryTableDoesNotExist".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let extra_field = structs::Entry::new()
+            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("EntryTableDoesNotExist2".to_string(), extra_field);
+
+        if let Err(e) = result {
+            match e {
+                DatabaseError::TableDoesNotExist(_) =>  {},
+                _ => panic!("Expected TableDoesNotExist, got {}", e),
+            };
+        } else {
+            panic!("Expected TableDoesNotExist, got none")
+        }
+    }
+
+    #[test]
+    fn entry_success_without_optional() {
+        let (mut c, table_builder) = create_client_table("EntrySuccessWithoutOptional".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let extra_field = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("EntrySuccessWithoutOptional".to_string(), extra_field);
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+    }
+
+    #[test]
+    fn entry_success_with_optional() {
+        let (mut c, table_builder) = create_client_table("EntrySuccessWithOptional".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let extra_field = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("EntrySuccessWithOptional".to_string(), extra_field);
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+    }
+
+    #[test]
+    fn entry_update_success() {
+        let (mut c, table_builder) = create_client_table("EntryUpdateSuccess".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let entry_first = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("EntryUpdateSuccess".to_string(), entry_first);
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+
+        let entry_second = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.update("EntryUpdateSuccess".to_string(), entry_second.clone());
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        }
+
+        let curent_entry = c.get("EntryUpdateSuccess".to_string(), Field::String("This should Succeed".to_string())).unwrap();
+        assert!(curent_entry.primary_field==entry_second.primary_field);
+        assert!(curent_entry.fields==entry_second.fields);
+    }
+
+    #[test]
+    fn entry_failure_second_insert() {
+        let (mut c, table_builder) = create_client_table("SecondInsertFailure".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let entry_first = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("SecondInsertFailure".to_string(), entry_first);
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+
+        let entry_second = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("SecondInsertFailure".to_string(), entry_second);
+
+        if let Err(e) = result {
+            match e {
+                DatabaseError::EntryExists => {},
+                _ => panic!("Expected EntryExists, got {}", e),
+            };
+        } else {
+            panic!("Expected EntryExists, got none")
+        }
+    }
+
+    #[test]
+    fn database_open_close() {
+        let (mut c, table_builder) = create_client_table("OpenSaveCloseOpen".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let entry_first = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert_or_update("OpenSaveCloseOpen".to_string(), entry_first.clone());
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+
+        c.save().unwrap();
+        drop(c);
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("OpenSaveCloseOpen.db");
+
+        let mut c = Client::open(temp_dir_path).unwrap();
+        let curent_entry = c.get("OpenSaveCloseOpen".to_string(), Field::String("This should Succeed".to_string())).unwrap();
+        assert!(curent_entry.primary_field==entry_first.primary_field);
+        assert!(curent_entry.fields==entry_first.fields);
+    }
+
+    #[test]
+    fn query_item() {
+        let (mut c, table_builder) = create_client_table("QueryItems".to_string());
+        
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let entry_first = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("QueryItems".to_string(), entry_first.clone());
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        } 
+
+        let entry_second = structs::Entry::new()
+            .set_primary_field(Field::String("This should Succeed too".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(12312312)).unwrap()
+            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
+            .build().unwrap();
+
+        let result = c.insert("QueryItems".to_string(), entry_second.clone());
+
+        if let Err(e) = result {
+            panic!("No error expected, received {}", e)
+        }
+
+        let first_query = c.query("QueryItems".to_string(), HashMap::from_iter(vec![("OptionalKey".to_string(), Field::String("My first entry".to_string()))])).unwrap();
+        let second_query = c.query("QueryItems".to_string(), HashMap::from_iter(vec![("FirstKey".to_string(),Field::I64(12312312))])).unwrap();
+        
+        assert!(first_query[0].primary_field==entry_first.primary_field);
+        assert!(first_query[0].fields==entry_first.fields);
+        assert!(first_query.len() == 1);
+
+        assert!(second_query[0].primary_field==entry_second.primary_field);
+        assert!(second_query[0].fields==entry_second.fields);
+        assert!(second_query.len() == 1);
+    }
+
+    #[test]
+    fn prune_multiple_chunks() {
+        let (mut c, table_builder) = create_client_table("PruneMultipleChunks".to_string());
+
+        let table = table_builder.primary_field(structs::FieldType::I64).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_expiration(Duration::from_millis(1))
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let total = PRUNE_CHUNK_SIZE * 2 + 10;
+        for i in 0..total {
+            let entry = structs::Entry::new()
+                .set_primary_field(Field::I64(i as i64)).unwrap()
+                .add_field("FirstKey".to_string(), Field::I64(i as i64)).unwrap()
+                .build().unwrap();
+            c.insert("PruneMultipleChunks".to_string(), entry).unwrap();
+        };
+        assert_eq!(c.scan("PruneMultipleChunks".to_string()).unwrap().len(), total);
+
+        sleep(Duration::from_millis(10));
+        c.prune().unwrap();
+
+        assert_eq!(c.scan("PruneMultipleChunks".to_string()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn columnar_table_round_trip() {
+        let (mut c, table_builder) = create_client_table("ColumnarRoundTrip".to_string());
+
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+            .columnar()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        for i in 0..5 {
+            let mut builder = structs::Entry::new()
+                .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+                .add_field("FirstKey".to_string(), Field::I64(i)).unwrap();
+            if i % 2 == 0 {
+                builder = builder.add_field("OptionalKey".to_string(), Field::String(format!("Optional{}", i))).unwrap();
+            };
+            c.insert("ColumnarRoundTrip".to_string(), builder.build().unwrap()).unwrap();
+        };
+
+        c.delete("ColumnarRoundTrip".to_string(), Field::String("Entry1".to_string())).unwrap();
+        c.update("ColumnarRoundTrip".to_string(), structs::Entry::new()
+            .set_primary_field(Field::String("Entry4".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(40)).unwrap()
+            .build().unwrap()).unwrap();
+
+        let entry = c.get("ColumnarRoundTrip".to_string(), Field::String("Entry4".to_string())).unwrap();
+        assert_eq!(entry.get_field("FirstKey".to_string()), Some(Field::I64(40)));
+        assert_eq!(entry.get_field("OptionalKey".to_string()), None);
+
+        let entry = c.get("ColumnarRoundTrip".to_string(), Field::String("Entry2".to_string())).unwrap();
+        assert_eq!(entry.get_field("OptionalKey".to_string()), Some(Field::String("Optional2".to_string())));
+
+        assert_eq!(c.scan("ColumnarRoundTrip".to_string()).unwrap().len(), 4);
+        assert_eq!(c.project("ColumnarRoundTrip".to_string(), "OptionalKey".to_string()).unwrap().len(), 2);
+
+        c.save().unwrap();
+        drop(c);
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("ColumnarRoundTrip.db");
+        let mut c = Client::open(temp_dir_path).unwrap();
+        let results = c.query("ColumnarRoundTrip".to_string(), HashMap::from_iter(vec![("FirstKey".to_string(), Field::I64(3))])).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].primary_field, Field::String("Entry3".to_string()));
+    }
+
+    #[test]
+    fn get_handle_unchanged_by_update() {
+        let (mut c, table_builder) = create_client_table("GetHandleUnchanged".to_string());
+
+        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+
+        c.create_table(table).unwrap();
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("GetHandleUnchanged".to_string(), entry).unwrap();
+
+        let first = c.get("GetHandleUnchanged".to_string(), Field::String("MyEntry".to_string())).unwrap();
+        let second = c.get("GetHandleUnchanged".to_string(), Field::String("MyEntry".to_string())).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(2)).unwrap()
+            .build().unwrap();
+        c.update("GetHandleUnchanged".to_string(), entry).unwrap();
+
+        let updated = c.get("GetHandleUnchanged".to_string(), Field::String("MyEntry".to_string())).unwrap();
+        assert_eq!(first.get_field("FirstKey".to_string()), Some(Field::I64(1)));
+        assert_eq!(updated.get_field("FirstKey".to_string()), Some(Field::I64(2)));
+    }
+
+    #[test]
+    fn prune_with_manual_clock() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("PruneWithManualClock.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("PruneWithManualClock".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("PruneWithManualClock".to_string(), entry).unwrap();
+
+        let stored = c.get("PruneWithManualClock".to_string(), Field::String("MyEntry".to_string())).unwrap();
+        assert_eq!(stored.last_timestamp, Some(std::time::SystemTime::UNIX_EPOCH));
+
+        clock.advance(Duration::from_secs(59));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithManualClock".to_string()).unwrap().len(), 1);
+
+        clock.advance(Duration::from_secs(2));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithManualClock".to_string()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn prune_ignores_wall_clock_steps() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("PruneIgnoresWallClockSteps.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("PruneIgnoresWallClockSteps".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("PruneIgnoresWallClockSteps".to_string(), entry).unwrap();
+
+        clock.set(start + Duration::from_secs(3600));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneIgnoresWallClockSteps".to_string()).unwrap().len(), 1);
+
+        clock.set(start - Duration::from_secs(3600));
+        clock.advance(Duration::from_secs(61));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneIgnoresWallClockSteps".to_string()).unwrap().len(), 0);
+    }
+
+
+    #[test]
+    fn query_where_with_manual_clock() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("QueryWhereWithManualClock.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("QueryWhere".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Created".to_string(), structs::FieldType::Date).unwrap()
+            .add_optional_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("Old".to_string())).unwrap()
+            .add_field("Created".to_string(), Field::Date(start - Duration::from_secs(120))).unwrap()
+            .add_field("Count".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("QueryWhere".to_string(), entry).unwrap();
+
+        let entry2 = structs::Entry::new()
+            .set_primary_field(Field::String("New".to_string())).unwrap()
+            .add_field("Created".to_string(), Field::Date(start)).unwrap()
+            .build().unwrap();
+        c.insert("QueryWhere".to_string(), entry2).unwrap();
+
+        let mut older = HashMap::new();
+        older.insert("Created".to_string(), Condition::OlderThan(Duration::from_secs(60)));
+        let results = c.query_where("QueryWhere".to_string(), older.clone()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].primary_field, Field::String("Old".to_string()));
+
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(c.query_where("QueryWhere".to_string(), older).unwrap().len(), 2);
+
+        let mut not_equals = HashMap::new();
+        not_equals.insert("Count".to_string(), Condition::NotEquals(Field::I64(1)));
+        let results = c.query_where("QueryWhere".to_string(), not_equals).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].primary_field, Field::String("New".to_string()));
+
+        let mut mismatched = HashMap::new();
+        mismatched.insert("Count".to_string(), Condition::GreaterThan(Field::U64(0)));
+        assert_eq!(c.query_where("QueryWhere".to_string(), mismatched).unwrap().len(), 0);
+    }
+
+
+    fn create_schedule_client(name: &str, clock: Arc<ManualClock>, schedule: ExpirationSchedule) -> Box<dyn DatabaseClient> {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push(format!("{}.db", name));
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock)
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name(name.to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_expiration_schedule(schedule).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert(name.to_string(), entry).unwrap();
+        c
+    }
+
+    #[test]
+    fn prune_with_expiration_schedule() {
+        let clock = Arc::new(ManualClock::new(crate::format::parse_date("2018-02-14T23:58:00Z").unwrap()));
+        let mut c = create_schedule_client("PruneWithExpirationSchedule", clock.clone(), ExpirationSchedule::EndOfDay);
+
+        clock.advance(Duration::from_secs(119));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithExpirationSchedule".to_string()).unwrap().len(), 1);
+
+        clock.advance(Duration::from_secs(1));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithExpirationSchedule".to_string()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn invalid_expiration_schedule() {
+        let result = structs::Table::new()
+            .name("InvalidExpirationSchedule".to_string())
+            .add_expiration_schedule(ExpirationSchedule::Daily(Duration::from_secs(86400)));
+        assert!(matches!(result, Err(DatabaseError::InvalidFormat(_))));
+    }
+
+    #[cfg(feature = "cron")]
+    #[test]
+    fn prune_with_cron_schedule() {
+        let clock = Arc::new(ManualClock::new(crate::format::parse_date("2018-02-14T01:59:30Z").unwrap()));
+        let schedule = ExpirationSchedule::Cron("0 0 2 * * * *".to_string());
+        let mut c = create_schedule_client("PruneWithCronSchedule", clock.clone(), schedule);
+
+        clock.advance(Duration::from_secs(29));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithCronSchedule".to_string()).unwrap().len(), 1);
+
+        clock.advance(Duration::from_secs(1));
+        c.prune().unwrap();
+        assert_eq!(c.scan("PruneWithCronSchedule".to_string()).unwrap().len(), 0);
+    }
+
+    #[cfg(not(feature = "cron"))]
+    #[test]
+    fn cron_schedule_without_feature() {
+        let schedule = ExpirationSchedule::Cron("0 0 2 * * * *".to_string());
+        let result = structs::Table::new()
+            .name("CronWithoutFeature".to_string())
+            .add_expiration_schedule(schedule.clone());
+        assert!(matches!(result, Err(DatabaseError::InvalidFormat(_))));
+        let now = std::time::SystemTime::now();
+        assert!(schedule.last_boundary(now).is_none() && schedule.next_boundary(now).is_none());
+
+        // A table with a cron schedule, as read from a file written with the feature, is
+        // left unpruned while the other tables are pruned
+        let clock = Arc::new(ManualClock::new(crate::format::parse_date("2018-02-14T01:59:30Z").unwrap()));
+        let mut c = create_schedule_client("CronWithoutFeature", clock.clone(), ExpirationSchedule::EndOfDay);
+        let mut table = structs::Table::new()
+            .name("CronTable".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        table.expire_schedule = Some(schedule);
+        c.create_table(table).unwrap();
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("CronTable".to_string(), entry).unwrap();
+
+        clock.advance(Duration::from_secs(86400));
+        assert!(matches!(c.prune(), Err(DatabaseError::InvalidFormat(_))));
+        assert_eq!(c.scan("CronWithoutFeature".to_string()).unwrap().len(), 0);
+        assert_eq!(c.scan("CronTable".to_string()).unwrap().len(), 1);
+
+        // The background thread logs the failed prune and keeps saving
+        let path = temp_dir().join("CronWithoutFeatureSync.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::builder(&path)
+            .sync_interval(Duration::from_millis(10))
+            .build().unwrap();
+        let mut table = structs::Table::new()
+            .name("CronTable".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        table.expire_schedule = Some(ExpirationSchedule::Cron("0 0 2 * * * *".to_string()));
+        c.create_table(table).unwrap();
+        sleep(Duration::from_millis(50));
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        c.insert("CronTable".to_string(), entry).unwrap();
+        sleep(Duration::from_millis(100));
+        let mut saved = read_database(&path).unwrap();
+        assert_eq!(saved.get_table(&"CronTable".to_string()).unwrap().len(), 1);
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    fn capacity_entry(key: &str, notes: &str) -> Entry {
+        structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Notes".to_string(), Field::String(notes.to_string())).unwrap()
+            .build().unwrap()
+    }
+
+    #[test]
+    fn max_entries_reject() {
+        let (mut c, table) = create_client_table("MaxEntriesReject".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .max_entries(2, CapacityPolicy::Reject)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        c.insert("MaxEntriesReject".to_string(), capacity_entry("First", "a")).unwrap();
+        c.insert("MaxEntriesReject".to_string(), capacity_entry("Second", "b")).unwrap();
+        match c.insert("MaxEntriesReject".to_string(), capacity_entry("Third", "c")) {
+            Err(DatabaseError::CapacityExceeded(_)) => {},
+            r => panic!("Expected CapacityExceeded but received {:?}", r),
+        };
+
+        c.update("MaxEntriesReject".to_string(), capacity_entry("First", "d")).unwrap();
+        assert_eq!(c.scan("MaxEntriesReject".to_string()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn max_entries_evict_oldest() {
+        let (mut c, table) = create_client_table("MaxEntriesEvict".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .max_entries(2, CapacityPolicy::EvictOldest)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        c.insert("MaxEntriesEvict".to_string(), capacity_entry("First", "a")).unwrap();
+        c.insert("MaxEntriesEvict".to_string(), capacity_entry("Second", "b")).unwrap();
+        c.update("MaxEntriesEvict".to_string(), capacity_entry("First", "c")).unwrap();
+        c.insert("MaxEntriesEvict".to_string(), capacity_entry("Third", "d")).unwrap();
+
+        assert!(c.get("MaxEntriesEvict".to_string(), Field::String("First".to_string())).is_ok());
+        assert!(c.get("MaxEntriesEvict".to_string(), Field::String("Second".to_string())).is_err());
+        assert!(c.get("MaxEntriesEvict".to_string(), Field::String("Third".to_string())).is_ok());
+        assert_eq!(c.stats().unwrap().tables["MaxEntriesEvict"].evicted, 1);
+    }
+
+    #[test]
+    fn max_memory() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("MaxMemory.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let entry_size = capacity_entry("Key0", &"x".repeat(100)).approx_size();
+        let mut c = Client::builder(&temp_dir_path)
+            .max_memory(entry_size * 3, CapacityPolicy::EvictOldest)
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("MaxMemory".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        for i in 0..5 {
+            c.insert("MaxMemory".to_string(), capacity_entry(&format!("Key{}", i), &"x".repeat(100))).unwrap();
+        };
+
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.tables["MaxMemory"].entries, 3);
+        assert_eq!(stats.approx_bytes, entry_size * 3);
+        assert_eq!(stats.max_memory, Some(entry_size * 3));
+        assert!(c.get("MaxMemory".to_string(), Field::String("Key1".to_string())).is_err());
+        assert!(c.get("MaxMemory".to_string(), Field::String("Key2".to_string())).is_ok());
+
+        match c.insert("MaxMemory".to_string(), capacity_entry("Huge", &"x".repeat(entry_size * 4))) {
+            Err(DatabaseError::CapacityExceeded(_)) => {},
+            r => panic!("Expected CapacityExceeded but received {:?}", r),
+        };
+
+        c.save().unwrap();
+        drop(c);
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        assert_eq!(c.stats().unwrap().approx_bytes, entry_size * 3);
+    }
+
+
+    #[test]
+    fn cancelled_operations() {
+        let (mut c, table) = create_client_table("CancelledOperations".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        for i in 0..1000 {
+            c.insert("CancelledOperations".to_string(), capacity_entry(&format!("Key{}", i), "a")).unwrap();
+        };
+
+        let expired = CancellationToken::new().with_deadline(std::time::Instant::now());
+        match c.scan_cancellable("CancelledOperations".to_string(), &expired) {
+            Err(DatabaseError::Cancelled) => {},
+            r => panic!("Expected Cancelled but received {:?}", r.map(|v| v.len())),
+        };
+
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), Field::String("a".to_string()));
+        match c.delete_many_cancellable("CancelledOperations".to_string(), criteria.clone(), &expired) {
+            Err(DatabaseError::Cancelled) => {},
+            r => panic!("Expected Cancelled but received {:?}", r),
+        };
+        assert_eq!(c.scan("CancelledOperations".to_string()).unwrap().len(), 1000);
+
+        let token = CancellationToken::new().with_timeout(Duration::from_secs(60));
+        assert_eq!(c.query_cancellable("CancelledOperations".to_string(), criteria, &token).unwrap().len(), 1000);
+    }
+
+
+    #[test]
+    fn lookup_join_kinds() {
+        let (mut c, table) = create_client_table("JoinOrders".to_string());
+        let orders = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_optional_field("Customer".to_string(), structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(orders).unwrap();
+
+        let customers = structs::Table::new()
+            .name("JoinCustomers".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Name".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(customers).unwrap();
+
+        let customer = structs::Entry::new()
+            .set_primary_field(Field::String("Customer1".to_string())).unwrap()
+            .add_field("Name".to_string(), Field::String("Alice".to_string())).unwrap()
+            .build().unwrap();
+        c.insert("JoinCustomers".to_string(), customer).unwrap();
+
+        for (key, customer) in [("Order1", Some("Customer1")), ("Order2", Some("Missing")), ("Order3", None)] {
+            let mut entry = structs::Entry::new()
+                .set_primary_field(Field::String(key.to_string())).unwrap()
+                .add_field("Count".to_string(), Field::I64(1)).unwrap();
+            if let Some(v) = customer {
+                entry = entry.add_field("Customer".to_string(), Field::String(v.to_string())).unwrap();
+            };
+            c.insert("JoinOrders".to_string(), entry.build().unwrap()).unwrap();
+        };
+
+        let left = c.lookup_join("JoinOrders".to_string(), "JoinCustomers".to_string(), "Customer".to_string(), JoinOptions::default()).unwrap();
+        assert_eq!(left.len(), 3);
+        assert_eq!(left.iter().filter(|j| j.right.is_some()).count(), 1);
+
+        let options = JoinOptions{
+            kind: JoinKind::Inner,
+            ..Default::default()
+        };
+        let inner = c.lookup_join("JoinOrders".to_string(), "JoinCustomers".to_string(), "Customer".to_string(), options).unwrap();
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].left.primary_field, Field::String("Order1".to_string()));
+
+        match c.lookup_join("JoinOrders".to_string(), "JoinCustomers".to_string(), "Count".to_string(), JoinOptions::default()) {
+            Err(DatabaseError::MismatchedFieldType) => {},
+            r => panic!("Expected MismatchedFieldType but received {:?}", r),
+        };
+    }
+
+
+    fn create_reference_tables(name: &str, on_delete: OnDelete) -> Box<dyn DatabaseClient> {
+        let (mut c, table) = create_client_table(format!("{}Customers", name));
+        let customers = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Name".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(customers).unwrap();
+
+        let orders = structs::Table::new()
+            .name(format!("{}Orders", name))
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_optional_field("Customer".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Customer".to_string(), format!("{}Customers", name), on_delete).unwrap()
+            .build().unwrap();
+        c.create_table(orders).unwrap();
+
+        let customer = structs::Entry::new()
+            .set_primary_field(Field::String("Customer1".to_string())).unwrap()
+            .add_field("Name".to_string(), Field::String("Alice".to_string())).unwrap()
+            .build().unwrap();
+        c.insert(format!("{}Customers", name), customer).unwrap();
+
+        let order = structs::Entry::new()
+            .set_primary_field(Field::String("Order1".to_string())).unwrap()
+            .add_field("Customer".to_string(), Field::String("Customer1".to_string())).unwrap()
+            .build().unwrap();
+        c.insert(format!("{}Orders", name), order).unwrap();
+        c
+    }
+
+    #[test]
+    fn reference_restrict() {
+        let mut c = create_reference_tables("Restrict", OnDelete::Restrict);
+
+        let missing = structs::Entry::new()
+            .set_primary_field(Field::String("Order2".to_string())).unwrap()
+            .add_field("Customer".to_string(), Field::String("Missing".to_string())).unwrap()
+            .build().unwrap();
+        match c.insert("RestrictOrders".to_string(), missing) {
+            Err(DatabaseError::ReferenceViolation(_)) => {},
+            r => panic!("Expected ReferenceViolation but received {:?}", r),
+        };
+
+        match c.delete("RestrictCustomers".to_string(), Field::String("Customer1".to_string())) {
+            Err(DatabaseError::ReferenceViolation(_)) => {},
+            r => panic!("Expected ReferenceViolation but received {:?}", r),
+        };
+        match c.drop_table(&"RestrictCustomers".to_string()) {
+            Err(DatabaseError::ReferenceViolation(_)) => {},
+            r => panic!("Expected ReferenceViolation but received {:?}", r),
+        };
+
+        c.delete("RestrictOrders".to_string(), Field::String("Order1".to_string())).unwrap();
+        c.delete("RestrictCustomers".to_string(), Field::String("Customer1".to_string())).unwrap();
+    }
+
+    #[test]
+    fn reference_cascade() {
+        let mut c = create_reference_tables("Cascade", OnDelete::Cascade);
+        c.delete("CascadeCustomers".to_string(), Field::String("Customer1".to_string())).unwrap();
+        assert_eq!(c.scan("CascadeOrders".to_string()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn reference_set_missing() {
+        let mut c = create_reference_tables("SetMissing", OnDelete::SetMissing);
+        let mut criteria = HashMap::new();
+        criteria.insert("Name".to_string(), Field::String("Alice".to_string()));
+        assert_eq!(c.delete_many("SetMissingCustomers".to_string(), criteria).unwrap(), 1);
+
+        let order = c.get("SetMissingOrders".to_string(), Field::String("Order1".to_string())).unwrap();
+        assert!(!order.fields.contains_key("Customer"));
+    }
+
+
+    #[test]
+    fn append_only_history_retention() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("AppendOnlyHistory.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("AppendOnlyHistory".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .append_only(HistoryRetention{
+                max_versions: Some(3),
+                max_age: Some(Duration::from_secs(60)),
+            })
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let key = Field::String("MyEntry".to_string());
+        for count in 0..5 {
+            let entry = structs::Entry::new()
+                .set_primary_field(key.clone()).unwrap()
+                .add_field("Count".to_string(), Field::I64(count)).unwrap()
+                .build().unwrap();
+            c.insert_or_update("AppendOnlyHistory".to_string(), entry).unwrap();
+            clock.advance(Duration::from_secs(30));
+        };
+
+        let versions = c.history("AppendOnlyHistory".to_string(), key.clone()).unwrap();
+        let counts: Vec<Field> = versions.iter().map(|v| v.fields["Count"].clone()).collect();
+        assert_eq!(counts, vec![Field::I64(1), Field::I64(2), Field::I64(3), Field::I64(4)]);
+        assert_eq!(c.get("AppendOnlyHistory".to_string(), key.clone()).unwrap().fields["Count"], Field::I64(4));
+
+        c.prune().unwrap();
+        let versions = c.history("AppendOnlyHistory".to_string(), key.clone()).unwrap();
+        assert_eq!(versions.len(), 2);
+
+        c.save().unwrap();
+        drop(c);
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        assert_eq!(c.history("AppendOnlyHistory".to_string(), key.clone()).unwrap().len(), 2);
+
+        c.delete("AppendOnlyHistory".to_string(), key.clone()).unwrap();
+        assert_eq!(c.stats().unwrap().approx_bytes, 0);
+        assert!(c.history("AppendOnlyHistory".to_string(), key).is_err());
+    }
+
+    #[test]
+    fn verify_database() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("VerifyDatabase.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::new(&temp_dir_path, None).unwrap();
+        let table = structs::Table::new()
+            .name("VerifyDatabase".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        for count in 0..10 {
+            let entry = structs::Entry::new()
+                .set_primary_field(Field::String(format!("Entry{}", count))).unwrap()
+                .add_field("Count".to_string(), Field::I64(count)).unwrap()
+                .build().unwrap();
+            c.insert("VerifyDatabase".to_string(), entry).unwrap();
+        };
+        c.save().unwrap();
+
+        let report = c.verify().unwrap();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.tables_checked, 1);
+        assert_eq!(report.entries_checked, 10);
+
+        std::fs::write(&temp_dir_path, b"not a database").unwrap();
+        let report = c.verify().unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].table.is_none());
+
+        c.save().unwrap();
+        assert!(c.verify().unwrap().is_ok());
+    }
+
+
+    #[test]
+    fn client_pool_threads() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("ClientPoolThreads.db");
+        for n in 0..4 {
+            let shard = temp_dir().join(format!("ClientPoolThreads.db.{}", n));
+            if shard.exists() {
+                std::fs::remove_file(shard).unwrap();
+            };
+        };
+
+        let mut pool = ClientPool::new(&temp_dir_path, 4, None).unwrap();
+        let table = structs::Table::new()
+            .name("ClientPoolThreads".to_string())
+            .primary_field(structs::FieldType::I64).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        pool.create_table(table).unwrap();
+
+        let mut handles = Vec::new();
+        for t in 0..4 {
+            let mut p = pool.clone();
+            handles.push(std::thread::spawn(move || {
+                for n in 0..250 {
+                    let entry = structs::Entry::new()
+                        .set_primary_field(Field::I64(t * 250 + n)).unwrap()
+                        .add_field("Count".to_string(), Field::I64(n & 1)).unwrap()
+                        .build().unwrap();
+                    p.insert("ClientPoolThreads".to_string(), entry).unwrap();
+                };
+            }));
+        };
+        for h in handles {
+            h.join().unwrap();
+        };
+
+        let stats = pool.stats().unwrap();
+        assert_eq!(stats.tables["ClientPoolThreads"].entries, 1000);
+
+        let mut criteria = HashMap::new();
+        criteria.insert("Count".to_string(), Field::I64(1));
+        assert_eq!(pool.query("ClientPoolThreads".to_string(), criteria.clone()).unwrap().len(), 500);
+        assert_eq!(pool.delete_many("ClientPoolThreads".to_string(), criteria).unwrap(), 500);
+
+        pool.save().unwrap();
+        drop(pool);
+        let mut pool = ClientPool::open(&temp_dir_path).unwrap();
+        assert_eq!(pool.shards(), 4);
+        assert_eq!(pool.scan("ClientPoolThreads".to_string()).unwrap().len(), 500);
+        assert_eq!(pool.get("ClientPoolThreads".to_string(), Field::I64(998)).unwrap().fields["Count"], Field::I64(0));
+        assert!(pool.get("ClientPoolThreads".to_string(), Field::I64(999)).is_err());
+    }
+
+
+    fn create_merge_client(name: &str, clock: Arc<ManualClock>) -> (Box<dyn DatabaseClient>, PathBuf) {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push(format!("{}.db", name));
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock)
+            .build().unwrap();
+
+        let table = structs::Table::new()
+            .name("Readings".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Value".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        (c, temp_dir_path)
+    }
+
+    fn merge_entry(key: &str, value: i64) -> Entry {
+        structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Value".to_string(), Field::I64(value)).unwrap()
+            .build().unwrap()
+    }
+
+    #[test]
+    fn merge_last_writer_wins() {
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let (mut central, _) = create_merge_client("MergeCentral", clock.clone());
+        let (mut first, first_path) = create_merge_client("MergeFirstDevice", clock.clone());
+        let (mut second, second_path) = create_merge_client("MergeSecondDevice", clock.clone());
+
+        first.insert("Readings".to_string(), merge_entry("Shared", 1)).unwrap();
+        first.insert("Readings".to_string(), merge_entry("First", 1)).unwrap();
+        clock.advance(Duration::from_secs(10));
+        second.insert("Readings".to_string(), merge_entry("Shared", 2)).unwrap();
+        second.insert("Readings".to_string(), merge_entry("Second", 2)).unwrap();
+        first.save().unwrap();
+        second.save().unwrap();
+
+        let report = central.merge_from(&second_path).unwrap();
+        assert_eq!(report.inserted, 2);
+        assert!(report.conflicts.is_empty());
+
+        let report = central.merge_from(&first_path).unwrap();
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].key, Field::String("Shared".to_string()));
+        assert_eq!(report.conflicts[0].resolution, Resolution::KeepLocal);
+
+        let shared = central.get("Readings".to_string(), Field::String("Shared".to_string())).unwrap();
+        assert_eq!(shared.fields["Value"], Field::I64(2));
+        assert_eq!(shared.last_timestamp, Some(clock.now()));
+        assert_eq!(central.scan("Readings".to_string()).unwrap().len(), 3);
+
+        let report = central.merge_from(&second_path).unwrap();
+        assert_eq!(report.unchanged, 2);
+        assert!(report.conflicts.is_empty());
+
+        let mismatched = structs::Table::new()
+            .name("Readings".to_string())
+            .primary_field(structs::FieldType::I64).unwrap()
+            .add_field("Value".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        let (mut other, other_path) = create_merge_client("MergeMismatched", clock.clone());
+        other.drop_table(&"Readings".to_string()).unwrap();
+        other.create_table(mismatched).unwrap();
+        other.save().unwrap();
+        match central.merge_from(&other_path) {
+            Err(DatabaseError::SchemaMismatch(_)) => {},
+            _ => panic!("Expected SchemaMismatch"),
+        };
+    }
+
+
+    #[test]
+    fn merge_with_resolver() {
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let (mut central, _) = create_merge_client("ResolverCentral", clock.clone());
+        let (mut device, device_path) = create_merge_client("ResolverDevice", clock.clone());
+
+        central.insert("Readings".to_string(), merge_entry("Shared", 5)).unwrap();
+        device.insert("Readings".to_string(), merge_entry("Shared", 7)).unwrap();
+        device.save().unwrap();
+
+        let mut calls = 0;
+        let report = central.merge_from_with(&device_path, &mut |local, remote| {
+            calls += 1;
+            let (Field::I64(l), Field::I64(r)) = (&local.fields["Value"], &remote.fields["Value"]) else {
+                return Resolution::KeepLocal
+            };
+            Resolution::Merged(merge_entry("Shared", std::cmp::max(*l, *r)))
+        }).unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(report.conflicts[0].resolution, Resolution::Merged(merge_entry("Shared", 7)));
+
+        let shared = central.get("Readings".to_string(), Field::String("Shared".to_string())).unwrap();
+        assert_eq!(shared.fields["Value"], Field::I64(7));
+        assert_eq!(shared.last_timestamp, Some(clock.now()));
+
+        device.insert_or_update("Readings".to_string(), merge_entry("Shared", 9)).unwrap();
+        device.save().unwrap();
+        match central.merge_from_with(&device_path, &mut |_, _| Resolution::Merged(merge_entry("Other", 1))) {
+            Err(DatabaseError::InvalidPrimaryKey) => {},
+            _ => panic!("Expected InvalidPrimaryKey"),
+        };
+        assert!(central.get("Readings".to_string(), Field::String("Other".to_string())).is_err());
+    }
+
+
+    #[test]
+    fn reload_changed_file() {
+        let (mut writer, table) = create_client_table("ReloadChangedFile".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        writer.create_table(table).unwrap();
+        writer.save().unwrap();
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("ReloadChangedFile.db");
+        let mut reader = Client::builder(&temp_dir_path)
+            .reload_interval(Duration::from_millis(10))
+            .open().unwrap();
+        let mut polled = Client::open(&temp_dir_path).unwrap();
+        assert!(!polled.reload().unwrap());
+
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        writer.insert("ReloadChangedFile".to_string(), entry).unwrap();
+        writer.save().unwrap();
+
+        assert!(polled.reload().unwrap());
+        assert!(!polled.reload().unwrap());
+        assert_eq!(polled.scan("ReloadChangedFile".to_string()).unwrap().len(), 1);
+
+        let mut reloaded = false;
+        for _ in 0..100 {
+            if reader.scan("ReloadChangedFile".to_string()).unwrap().len() == 1 {
+                reloaded = true;
+                break
+            };
+            sleep(Duration::from_millis(10));
+        };
+        assert!(reloaded);
+        drop(reader);
+
+        std::fs::write(&temp_dir_path, b"\x40\x00\x00\x00partially written").unwrap();
+        assert!(polled.reload().is_err());
+        assert_eq!(polled.scan("ReloadChangedFile".to_string()).unwrap().len(), 1);
+
+        let entry = |key: &str| structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I64(2)).unwrap()
+            .build().unwrap();
+        writer.insert("ReloadChangedFile".to_string(), entry("Written")).unwrap();
+        writer.save().unwrap();
+        polled.insert("ReloadChangedFile".to_string(), entry("Unsaved")).unwrap();
+        assert!(matches!(polled.reload(), Err(DatabaseError::UnsavedChanges(1))));
+        assert!(polled.get("ReloadChangedFile".to_string(), Field::String("Unsaved".to_string())).is_ok());
+        polled.flush().unwrap();
+        assert!(polled.reload().is_ok());
+        assert!(polled.get("ReloadChangedFile".to_string(), Field::String("Unsaved".to_string())).is_ok());
+
+        // A mutation that fails leaves nothing unsaved
+        writer.insert("ReloadChangedFile".to_string(), entry("Later")).unwrap();
+        writer.save().unwrap();
+        assert!(matches!(polled.insert("ReloadChangedFile".to_string(), entry("Unsaved")), Err(DatabaseError::EntryExists)));
+        assert!(polled.reload().unwrap());
+        assert!(polled.get("ReloadChangedFile".to_string(), Field::String("Later".to_string())).is_ok());
+    }
+
+
+    #[derive(Default)]
+    struct RecordingCache {
+        entries: HashMap<Field, (Arc<Entry>, Option<Duration>)>,
+        invalidated: usize,
+    }
+
+    impl CacheLayer for RecordingCache {
+        fn get(&mut self, key: &Field) -> Option<Arc<Entry>> {
+            self.entries.get(key).map(|(e, _)| e.clone())
+        }
+
+        fn put(&mut self, entry: Arc<Entry>, ttl: Option<Duration>) {
+            self.entries.insert(entry.primary_field.clone(), (entry, ttl));
+        }
+
+        fn invalidate(&mut self, key: &Field) {
+            self.invalidated += 1;
+            self.entries.remove(key);
+        }
+    }
+
+    #[test]
+    fn cached_table_write_through() {
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("CachedTableWriteThrough.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+        for (name, expiration) in [("CachedExpiring", true), ("CachedScheduled", false)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap();
+            table = match expiration {
+                true => table.add_expiration(Duration::from_secs(60)),
+                false => table.add_expiration_schedule(ExpirationSchedule::Every(Duration::from_secs(3600))).unwrap(),
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+        };
+
+        let entry = |count: i64| structs::Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I64(count)).unwrap()
+            .build().unwrap();
+        let key = Field::String("MyEntry".to_string());
+
+        c.insert("CachedExpiring".to_string(), entry(0)).unwrap();
+        let mut cached = CachedTable::new(c, "CachedExpiring".to_string(), RecordingCache::default());
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(cached.get(key.clone()).unwrap().fields["Count"], Field::I64(0));
+        assert_eq!(cached.cache().entries[&key].1, Some(Duration::from_secs(40)));
+
+        cached.insert_or_update(entry(1)).unwrap();
+        assert_eq!(cached.cache().entries[&key].1, Some(Duration::from_secs(60)));
+
+        cached.client().delete("CachedExpiring".to_string(), key.clone()).unwrap();
+        assert_eq!(cached.get(key.clone()).unwrap().fields["Count"], Field::I64(1));
+
+        cached.update(entry(2)).unwrap();
+        assert_eq!(cached.get(key.clone()).unwrap().fields["Count"], Field::I64(2));
+        cached.delete(key.clone()).unwrap();
+        assert!(cached.get(key.clone()).is_err());
+        assert!(!cached.cache().entries.contains_key(&key));
+
+        let (c, _) = cached.into_inner();
+        let mut cached = CachedTable::new(c, "CachedScheduled".to_string(), RecordingCache::default());
+        cached.insert(entry(0)).unwrap();
+        assert_eq!(cached.cache().entries[&key].1, Some(Duration::from_secs(780)));
+    }
+
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn web_extractor_and_responses() {
+        use axum_core::extract::FromRequestParts;
+        use axum_core::response::IntoResponse;
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        struct WebUsers;
+
+        impl NamedTable for WebUsers {
+            const NAME: &'static str = "WebUsers";
+        }
+
+        struct MissingTable;
+
+        impl NamedTable for MissingTable {
+            const NAME: &'static str = "MissingTable";
+        }
+
+        fn extract<T: NamedTable>(state: &SharedClient) -> Result<TableHandle<T>, DatabaseError> {
+            let (mut parts, _) = http::Request::new(()).into_parts();
+            let mut future = std::pin::pin!(TableHandle::<T>::from_request_parts(&mut parts, state));
+            match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+                Poll::Ready(result) => result,
+                Poll::Pending => panic!("Expected extraction to complete"),
+            }
+        }
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("WebExtractor.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut state = SharedClient::build(Client::builder(&temp_dir_path)).unwrap();
+        let table = structs::Table::new()
+            .name("WebUsers".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Name".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        state.create_table(table).unwrap();
+
+        let mut users = extract::<WebUsers>(&state).unwrap();
+        let entry = structs::Entry::new()
+            .set_primary_field(Field::String("user1".to_string())).unwrap()
+            .add_field("Name".to_string(), Field::String("Alice".to_string())).unwrap()
+            .build().unwrap();
+        users.insert(entry.clone()).unwrap();
+        assert_eq!(state.scan("WebUsers".to_string()).unwrap().len(), 1);
+
+        let response = users.insert(entry).unwrap_err().into_response();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+        let response = users.get(Field::String("user2".to_string())).unwrap_err().into_response();
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+
+        match extract::<MissingTable>(&state) {
+            Err(e) => assert_eq!(e.into_response().status(), http::StatusCode::NOT_FOUND),
+            Ok(_) => panic!("Expected TableDoesNotExist"),
+        };
+    }
+
+    #[test]
+    fn flat_entry_round_trip() {
+        use serde::de::DeserializeSeed;
+
+        let table = Table::new()
+            .name("FlatTable".to_string())
+            .primary_field(FieldType::U64).unwrap()
+            .add_field("Name".to_string(), FieldType::String).unwrap()
+            .add_field("Count".to_string(), FieldType::I32).unwrap()
+            .add_field("Active".to_string(), FieldType::Bool).unwrap()
+            .add_optional_field("Seen".to_string(), FieldType::Date).unwrap()
+            .build().unwrap();
+
+        let seen = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let entry = Entry::new()
+            .set_primary_field(Field::U64(7)).unwrap()
+            .add_field("Name".to_string(), Field::String("seven".to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I32(-3)).unwrap()
+            .add_field("Active".to_string(), Field::Bool(true)).unwrap()
+            .add_field("Seen".to_string(), Field::Date(seen)).unwrap()
+            .build().unwrap();
+
+        let json = serde_json::to_string(&entry.flat("id")).unwrap();
+        assert_eq!(json, r#"{"id":7,"Active":true,"Count":-3,"Name":"seven","Seen":"2023-11-14T22:13:20Z"}"#);
+
+        let mut de = serde_json::Deserializer::from_str(&json);
+        let parsed = table.flat_entry("id").deserialize(&mut de).unwrap();
+        assert_eq!(parsed.primary_field, entry.primary_field);
+        assert_eq!(parsed.fields, entry.fields);
+
+        let invalid = [
+            r#"{"Name":"seven","Count":1,"Active":true}"#,
+            r#"{"id":7,"Count":1,"Active":true}"#,
+            r#"{"id":7,"Name":"seven","Count":"1","Active":true}"#,
+            r#"{"id":7,"Name":"seven","Count":3000000000,"Active":true}"#,
+            r#"{"id":7,"Name":"seven","Count":1,"Active":true,"Other":1}"#,
+            r#"{"id":7,"Name":"seven","Count":1,"Active":true,"Seen":"yesterday"}"#,
+        ];
+        for json in invalid {
+            let mut de = serde_json::Deserializer::from_str(json);
+            assert!(table.flat_entry("id").deserialize(&mut de).is_err(), "{}", json);
+        };
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn generator_entries_for_schema() {
+        use crate::generator::Generator;
+
+        let mut table = Table::new()
+            .name("Fuzzed".to_string())
+            .primary_field(FieldType::U64).unwrap()
+            .add_field("Name".to_string(), FieldType::String).unwrap()
+            .add_field("Count".to_string(), FieldType::I32).unwrap()
+            .add_field("Active".to_string(), FieldType::Bool).unwrap()
+            .add_optional_field("Seen".to_string(), FieldType::Date).unwrap()
+            .add_optional_field("Total".to_string(), FieldType::U32).unwrap()
+            .build().unwrap();
+
+        assert_eq!(Generator::new(7).entry_for(&table), Generator::new(7).entry_for(&table));
+
+        let mut generator = Generator::new(7);
+        for _ in 0..100 {
+            let entry = generator.entry_for(&table);
+            table.insert_or_update(entry).unwrap();
+        };
+
+        for _ in 0..100 {
+            let entry = generator.invalid_entry_for(&table);
+            assert!(table.insert_or_update(entry).is_err());
+        };
+    }
+
+    #[test]
+    fn spill_to_disk() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Spill.db");
+        let segment = spill::segment_path(&temp_dir_path, "Spill");
+        for path in [&temp_dir_path, &segment] {
+            if path.exists() {
+                std::fs::remove_file(path).unwrap();
+            };
+        };
+
+        let entry_size = capacity_entry("Key0", &"x".repeat(100)).approx_size();
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        let table = structs::Table::new()
+            .name("Spill".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .spill_after(entry_size * 3)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        for i in 0..10 {
+            c.insert("Spill".to_string(), capacity_entry(&format!("Key{}", i), &"x".repeat(100))).unwrap();
+        };
+
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.tables["Spill"].entries, 10);
+        assert_eq!(stats.tables["Spill"].spilled, 7);
+        assert_eq!(stats.approx_bytes, entry_size * 3);
+        assert!(segment.exists());
+
+        let entry = c.get("Spill".to_string(), Field::String("Key0".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String("x".repeat(100)));
+        assert_eq!(c.scan("Spill".to_string()).unwrap().len(), 10);
+
+        c.update("Spill".to_string(), capacity_entry("Key0", &"y".repeat(100))).unwrap();
+        c.delete("Spill".to_string(), Field::String("Key1".to_string())).unwrap();
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.tables["Spill"].entries, 9);
+        assert_eq!(stats.tables["Spill"].spilled, 6);
+        assert!(stats.tables["Spill"].spill_garbage_bytes > 0);
+        assert!(c.verify().unwrap().is_ok());
+
+        c.save().unwrap();
+        drop(c);
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        assert_eq!(c.stats().unwrap().tables["Spill"].spilled, 6);
+        let entry = c.get("Spill".to_string(), Field::String("Key2".to_string())).unwrap();
+        assert_eq!(entry.primary_field, Field::String("Key2".to_string()));
+        let entry = c.get("Spill".to_string(), Field::String("Key0".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String("y".repeat(100)));
+        assert!(c.get("Spill".to_string(), Field::String("Key1".to_string())).is_err());
+        assert!(c.verify().unwrap().is_ok());
+
+        c.drop_table(&"Spill".to_string()).unwrap();
+        assert!(!segment.exists());
+    }
+
+    #[test]
+    fn externalize_large_values() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Externalize.db");
+        let dir = blob::blob_dir(&temp_dir_path, "Externalize");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+
+        let document = |seed: u64| -> String {
+            let mut state = seed;
+            (0..4096).map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (b'a' + (state >> 59) as u8 % 26) as char
+            }).collect()
+        };
+
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        let table = structs::Table::new()
+            .name("Externalize".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .externalize_above(1024)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        c.insert("Externalize".to_string(), capacity_entry("Large", &document(1))).unwrap();
+        c.insert("Externalize".to_string(), capacity_entry("Small", "small")).unwrap();
+        c.save().unwrap();
+        assert!(std::fs::metadata(&temp_dir_path).unwrap().len() < 1024);
+        assert_eq!(c.stats().unwrap().tables["Externalize"].externalized, 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let entry = c.get("Externalize".to_string(), Field::String("Large".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String(document(1)));
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), Field::String(document(1)));
+        assert_eq!(c.query("Externalize".to_string(), criteria).unwrap().len(), 1);
+        assert_eq!(c.project("Externalize".to_string(), "Notes".to_string()).unwrap().len(), 2);
+
+        c.update("Externalize".to_string(), capacity_entry("Large", &document(1))).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        c.update("Externalize".to_string(), capacity_entry("Large", &document(2))).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(c.compact().unwrap(), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        drop(c);
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        let entry = c.get("Externalize".to_string(), Field::String("Large".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String(document(2)));
+        assert!(c.verify().unwrap().is_ok());
+
+        c.delete("Externalize".to_string(), Field::String("Large".to_string())).unwrap();
+        assert_eq!(c.compact().unwrap(), 1);
+        c.drop_table(&"Externalize".to_string()).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn deduplicate_repeated_values() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Deduplicate.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let agents = ["Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_7) AppleWebKit/605.1.15 Safari/605.1.15"];
+
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        let table = structs::Table::new()
+            .name("Deduplicate".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .deduplicate_above(16)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        for i in 0..20 {
+            c.insert("Deduplicate".to_string(), capacity_entry(&format!("Entry{}", i), agents[i % 2])).unwrap();
+        };
+        c.insert("Deduplicate".to_string(), capacity_entry("Short", "short")).unwrap();
+        assert_eq!(c.stats().unwrap().tables["Deduplicate"].deduplicated, 2);
+        assert_eq!(c.project("Deduplicate".to_string(), "Notes".to_string()).unwrap().len(), 21);
+        c.save().unwrap();
+
+        drop(c);
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        let entry = c.get("Deduplicate".to_string(), Field::String("Entry3".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String(agents[1].to_string()));
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), Field::String(agents[0].to_string()));
+        assert_eq!(c.query("Deduplicate".to_string(), criteria).unwrap().len(), 10);
+
+        c.insert("Deduplicate".to_string(), capacity_entry("Entry20", agents[0])).unwrap();
+        assert_eq!(c.stats().unwrap().tables["Deduplicate"].deduplicated, 2);
+        for i in (1..20).step_by(2) {
+            c.update("Deduplicate".to_string(), capacity_entry(&format!("Entry{}", i), agents[0])).unwrap();
+        };
+        assert_eq!(c.stats().unwrap().tables["Deduplicate"].deduplicated, 1);
+        c.delete("Deduplicate".to_string(), Field::String("Entry0".to_string())).unwrap();
+        assert!(c.verify().unwrap().is_ok());
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn bloom_filter_missing_keys() {
+        let mut table = structs::Table::new()
+            .name("Bloom".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .bloom_filter(0.01)
+            .build().unwrap();
+
+        for i in 0..5000 {
+            table.insert(capacity_entry(&format!("Entry{}", i), "notes")).unwrap();
+        };
+        for i in 0..5000 {
+            assert!(table.exists(&Field::String(format!("Entry{}", i))));
+        };
+        let false_positives = (5000..15000)
+            .filter(|i| table.might_contain(&Field::String(format!("Entry{}", i))))
+            .count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+
+        for i in 0..4000 {
+            table.delete(Field::String(format!("Entry{}", i))).unwrap();
+        };
+        table.update(capacity_entry("Entry0", "notes")).unwrap();
+        assert!(table.exists(&Field::String("Entry0".to_string())));
+        assert!(!table.exists(&Field::String("Entry1".to_string())));
+        for i in 4000..5000 {
+            assert!(table.get(&Field::String(format!("Entry{}", i))).is_ok());
+        };
+        let false_positives = (5000..15000)
+            .filter(|i| table.might_contain(&Field::String(format!("Entry{}", i))))
+            .count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn prefix_index_scans() {
+        let mut indexed = structs::Table::new()
+            .name("Prefix".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .prefix_index()
+            .build().unwrap();
+        let mut plain = indexed.clone();
+        plain.prefix_index = false;
+        plain.rebase(crate::clock::Timestamp::now());
+
+        let mut keys = Vec::new();
+        for host in ["a.example", "b.example", "ab.example"] {
+            for path in ["", "/", "/index", "/index.html", "/images/x.png", "/é"] {
+                keys.push(format!("https://{}{}", host, path));
+            };
+        };
+        for key in &keys {
+            indexed.insert(capacity_entry(key, "notes")).unwrap();
+            plain.insert(capacity_entry(key, "notes")).unwrap();
+        };
+
+        let primary = |entries: Vec<Arc<Entry>>| -> Vec<Field> {
+            entries.into_iter().map(|e| e.primary_field.clone()).collect()
+        };
+        for prefix in ["", "https://", "https://a", "https://a.example/", "https://a.example/index", "https://ab.example/i", "https://c", "https://a.example/\u{e9}"] {
+            assert_eq!(primary(indexed.scan_prefix(prefix).unwrap()), primary(plain.scan_prefix(prefix).unwrap()), "{}", prefix);
+        };
+        assert_eq!(indexed.scan_prefix("https://a.example/index").unwrap().len(), 2);
+
+        for key in keys.iter().step_by(2) {
+            indexed.delete(Field::String(key.clone())).unwrap();
+            plain.delete(Field::String(key.clone())).unwrap();
+        };
+        for prefix in ["", "https://a", "https://b.example/in", "https://ab.example/images/x.png"] {
+            assert_eq!(primary(indexed.scan_prefix(prefix).unwrap()), primary(plain.scan_prefix(prefix).unwrap()), "{}", prefix);
+        };
+        let mut report = VerifyReport::default();
+        indexed.verify(&mut report);
+        assert!(report.is_ok());
+
+        let numeric = structs::Table::new()
+            .name("Numeric".to_string())
+            .primary_field(structs::FieldType::I64).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .prefix_index()
+            .build();
+        assert!(matches!(numeric, Err(DatabaseError::InvalidPrimaryKey)));
+    }
+
+    #[test]
+    fn compound_index_queries() {
+        let mut indexed = structs::Table::new()
+            .name("Tickets".to_string())
+            .primary_field(structs::FieldType::U64).unwrap()
+            .add_field("Tenant".to_string(), structs::FieldType::String).unwrap()
+            .add_optional_field("Status".to_string(), structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .add_index(vec!["Tenant".to_string(), "Status".to_string()]).unwrap()
+            .build().unwrap();
+        let mut plain = indexed.clone();
+        plain.indexes.clear();
+        plain.rebase(crate::clock::Timestamp::now());
+
+        let ticket = |id: u64, status: Option<&str>| -> Entry {
+            let mut entry = Entry::new()
+                .set_primary_field(Field::U64(id)).unwrap()
+                .add_field("Tenant".to_string(), Field::String(format!("Tenant{}", id % 3))).unwrap()
+                .add_field("Count".to_string(), Field::I64(id as i64 % 7)).unwrap();
+            if let Some(s) = status {
+                entry = entry.add_field("Status".to_string(), Field::String(s.to_string())).unwrap();
+            };
+            entry.build().unwrap()
+        };
+        let statuses = [Some("Open"), Some("Closed"), None];
+        for id in 0..300 {
+            indexed.insert(ticket(id, statuses[(id / 3 % 3) as usize])).unwrap();
+            plain.insert(ticket(id, statuses[(id / 3 % 3) as usize])).unwrap();
+        };
+
+        let condition = |pairs: &[(&str, Condition)]| -> HashMap<String, Condition> {
+            pairs.iter().map(|(k, c)| (k.to_string(), c.clone())).collect()
+        };
+        let keys = |entries: Vec<Arc<Entry>>| -> std::collections::HashSet<Field> {
+            entries.into_iter().map(|e| e.primary_field.clone()).collect()
+        };
+        let tenant = Condition::Equals(Field::String("Tenant1".to_string()));
+        let open = Condition::Equals(Field::String("Open".to_string()));
+        let queries = [
+            (condition(&[("Tenant", tenant.clone()), ("Status", open.clone())]), AccessPath::Index(vec!["Tenant".to_string(), "Status".to_string()])),
+            (condition(&[("Tenant", tenant.clone()), ("Count", Condition::GreaterThan(Field::I64(3)))]), AccessPath::Index(vec!["Tenant".to_string()])),
+            (condition(&[("Tenant", tenant.clone()), ("Status", Condition::NotEquals(Field::String("Open".to_string())))]), AccessPath::Index(vec!["Tenant".to_string()])),
+            (condition(&[("Status", open.clone())]), AccessPath::FullScan),
+        ];
+        let now = std::time::SystemTime::now();
+        for (conditions, access) in &queries {
+            assert_eq!(&indexed.explain(conditions).access, access);
+            assert_eq!(keys(indexed.filter(conditions, now).unwrap()), keys(plain.filter(conditions, now).unwrap()));
+        };
+        let plan = indexed.explain(&queries[0].0);
+        assert_eq!(plan.pushed_down.len(), 2);
+        assert!(plan.residual.is_empty());
+        assert_eq!(plan.estimated_rows_scanned, indexed.filter(&queries[0].0, now).unwrap().len());
+
+        for id in (0..300).step_by(5) {
+            indexed.update(ticket(id, Some("Open"))).unwrap();
+            plain.update(ticket(id, Some("Open"))).unwrap();
+        };
+        for id in (0..300).step_by(7) {
+            indexed.delete(Field::U64(id)).unwrap();
+            plain.delete(Field::U64(id)).unwrap();
+        };
+        for (conditions, _) in &queries {
+            assert_eq!(keys(indexed.filter(conditions, now).unwrap()), keys(plain.filter(conditions, now).unwrap()));
+        };
+        let mut report = VerifyReport::default();
+        indexed.verify(&mut report);
+        assert!(report.is_ok());
+
+        let missing = structs::Table::new()
+            .name("Tickets".to_string())
+            .primary_field(structs::FieldType::U64).unwrap()
+            .add_field("Tenant".to_string(), structs::FieldType::String).unwrap()
+            .add_index(vec!["Tenant".to_string(), "Status".to_string()]);
+        assert!(matches!(missing, Err(DatabaseError::UnsupportedField(f)) if f == "Status"));
+    }
+
+    #[test]
+    fn query_to_writer_formats() {
+        let (mut c, table) = create_client_table("QueryToWriter".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_optional_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .add_optional_field("Updated".to_string(), structs::FieldType::Date).unwrap()
+            .build().unwrap();
+        c.create_table(table.clone()).unwrap();
+
+        let notes = ["plain", "comma, separated", "\"quoted\"", "line\nbreak\ttab\u{1}", "unicodé ✓"];
+        for (i, n) in notes.iter().enumerate() {
+            let mut entry = Entry::new()
+                .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+                .add_field("Notes".to_string(), Field::String(n.to_string())).unwrap();
+            if i % 2 == 0 {
+                entry = entry.add_field("Count".to_string(), Field::I64(-(i as i64))).unwrap()
+                    .add_field("Updated".to_string(), Field::Date(std::time::UNIX_EPOCH + Duration::from_secs(1518568087))).unwrap();
+            };
+            c.insert("QueryToWriter".to_string(), entry.build().unwrap()).unwrap();
+        };
+
+        let mut output = Vec::new();
+        let written = c.query_to_writer("QueryToWriter".to_string(), HashMap::new(), ExportFormat::Ndjson, &mut output).unwrap();
+        assert_eq!(written, notes.len());
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), notes.len());
+        for line in output.lines() {
+            use serde::de::DeserializeSeed;
+            let mut json = serde_json::Deserializer::from_str(line);
+            let entry = table.flat_entry(PRIMARY_FIELD_COLUMN).deserialize(&mut json).unwrap();
+            let stored = c.get("QueryToWriter".to_string(), entry.primary_field.clone()).unwrap();
+            assert_eq!(entry.fields, stored.fields);
+        };
+
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), Field::String(notes[3].to_string()));
+        let mut output = Vec::new();
+        assert_eq!(c.query_to_writer("QueryToWriter".to_string(), criteria, ExportFormat::Csv, &mut output).unwrap(), 1);
+        assert_eq!(String::from_utf8(output).unwrap(), "primary_field,Notes,Count,Updated\nEntry3,\"line\nbreak\ttab\u{1}\",,\n");
+
+        let mut output = Vec::new();
+        c.query_to_writer("QueryToWriter".to_string(), HashMap::new(), ExportFormat::Csv, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Entry1,\"comma, separated\",,\n"));
+        assert!(output.contains("Entry2,\"\"\"quoted\"\"\",-2,2018-02-14T00:28:07Z\n"));
+
+        assert!(matches!(c.query_to_writer("Missing".to_string(), HashMap::new(), ExportFormat::Csv, &mut Vec::new()), Err(DatabaseError::TableDoesNotExist(_))));
+    }
+
+    #[test]
+    fn rate_limiter() {
+        use crate::ratelimit::{RateLimit, RateLimiter};
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("RateLimiter.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(86400)));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build_client().unwrap();
+
+        let bucket = RateLimit::TokenBucket{ capacity: 3, refill_every: Duration::from_secs(10) };
+        let mut limiter = RateLimiter::new(&c, "Buckets".to_string(), bucket).unwrap();
+        let mut shared = RateLimiter::new(&c, "Buckets".to_string(), bucket).unwrap();
+        assert_eq!(limiter.check("alice").unwrap().remaining, 2);
+        assert_eq!(shared.check("alice").unwrap().remaining, 1);
+        assert!(limiter.check("alice").unwrap().allowed);
+        let denied = limiter.check_n("alice", 2).unwrap();
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Some(Duration::from_secs(20)));
+        assert_eq!(limiter.check_n("alice", 4).unwrap().retry_after, None);
+        assert!(limiter.check("bob").unwrap().allowed);
+
+        clock.advance(Duration::from_secs(15));
+        let decision = limiter.check("alice").unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(limiter.check("alice").unwrap().retry_after, Some(Duration::from_secs(5)));
+        clock.advance(Duration::from_secs(5));
+        assert!(limiter.check("alice").unwrap().allowed);
+
+        clock.advance(Duration::from_secs(31));
+        c.prune().unwrap();
+        assert_eq!(c.stats().unwrap().tables["Buckets"].entries, 0);
+        assert_eq!(limiter.check("alice").unwrap().remaining, 2);
+        limiter.reset("alice").unwrap();
+        limiter.reset("alice").unwrap();
+
+        let window = RateLimit::SlidingWindow{ limit: 10, window: Duration::from_secs(60) };
+        let mut limiter = RateLimiter::new(&c, "Windows".to_string(), window).unwrap();
+        assert_eq!(limiter.check_n("alice", 8).unwrap().remaining, 2);
+        assert!(!limiter.check_n("alice", 3).unwrap().allowed);
+        assert!(limiter.check_n("alice", 2).unwrap().allowed);
+        assert_eq!(limiter.check("alice").unwrap().retry_after, Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(90));
+        let denied = limiter.check_n("alice", 6).unwrap();
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 5);
+        assert_eq!(denied.retry_after, Some(Duration::from_secs(6)));
+        clock.advance(Duration::from_secs(6));
+        assert!(limiter.check_n("alice", 6).unwrap().allowed);
+        assert_eq!(limiter.check_n("alice", 11).unwrap().retry_after, None);
+
+        clock.advance(Duration::from_secs(180));
+        assert_eq!(limiter.check_n("alice", 10).unwrap().remaining, 0);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn session_store() {
+        use crate::sessions::SessionStore;
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("SessionStore.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build_client().unwrap();
+
+        let mut sessions = SessionStore::new(&c, "Sessions".to_string(), Duration::from_secs(60)).unwrap();
+        let first = sessions.create_session("alice", "first".to_string()).unwrap();
+        let second = sessions.create_session("alice", String::new()).unwrap();
+        let other = sessions.create_session("bob", String::new()).unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.id.len(), 32);
+        assert_eq!(first.created, start);
+        assert_eq!(sessions.get_session(&first.id).unwrap(), Some(first.clone()));
+        assert_eq!(sessions.get_session("missing").unwrap(), None);
+
+        clock.advance(Duration::from_secs(45));
+        let touched = sessions.touch(&first.id).unwrap().unwrap();
+        assert_eq!(touched.created, start);
+        assert_eq!(touched.last_seen, start + Duration::from_secs(45));
+        assert_eq!(sessions.set_data(&other.id, "updated".to_string()).unwrap().unwrap().data, "updated");
+
+        clock.advance(Duration::from_secs(30));
+        assert!(sessions.get_session(&first.id).unwrap().is_some());
+        assert!(sessions.get_session(&second.id).unwrap().is_none());
+        assert!(sessions.touch(&second.id).unwrap().is_none());
+        c.prune().unwrap();
+        assert_eq!(c.stats().unwrap().tables["Sessions"].entries, 2);
+
+        let mut shared = SessionStore::new(&c, "Sessions".to_string(), Duration::from_secs(60)).unwrap();
+        assert!(shared.get_session(&other.id).unwrap().is_some());
+        assert!(shared.revoke(&other.id).unwrap());
+        assert!(!sessions.revoke(&other.id).unwrap());
+        assert_eq!(sessions.revoke_all_for_user("alice").unwrap(), 1);
+        assert_eq!(c.stats().unwrap().tables["Sessions"].entries, 0);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn flag_store() {
+        use crate::flags::{Flag, FlagChange, FlagStore};
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("FlagStore.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let c = Client::builder(&temp_dir_path).build_client().unwrap();
+        let mut flags = FlagStore::new(&c, "Flags".to_string()).unwrap();
+        let changes = flags.subscribe();
+        let mut shared = flags.clone();
+
+        flags.set_flag("rollout", Flag::Percentage(30)).unwrap();
+        let enabled = (0..1000).filter(|i| flags.is_enabled("rollout", &format!("user-{}", i)).unwrap()).count();
+        assert!((200..400).contains(&enabled), "{} of 1000 enabled", enabled);
+        let before: Vec<bool> = (0..100).map(|i| flags.is_enabled("rollout", &format!("user-{}", i)).unwrap()).collect();
+        shared.set_flag("rollout", Flag::Percentage(60)).unwrap();
+        for (i, was_enabled) in before.iter().enumerate() {
+            if *was_enabled {
+                assert!(flags.is_enabled("rollout", &format!("user-{}", i)).unwrap());
+            };
+        };
+        shared.set_flag("rollout", Flag::Percentage(100)).unwrap();
+        assert!(flags.is_enabled("rollout", "anyone").unwrap());
+
+        flags.set_flag("dark-mode", Flag::Bool(false)).unwrap();
+        flags.set_flag("dark-mode", Flag::Bool(false)).unwrap();
+        assert!(!flags.is_enabled("dark-mode", "alice").unwrap());
+        assert_eq!(flags.variant("dark-mode").unwrap(), None);
+        assert_eq!(flags.get_flag("dark-mode").unwrap(), Some(Flag::Bool(false)));
+        assert_eq!(flags.list_flags().unwrap().iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>(), vec!["dark-mode", "rollout"]);
+
+        assert!(flags.remove_flag("dark-mode").unwrap());
+        assert!(!flags.remove_flag("dark-mode").unwrap());
+        assert_eq!(flags.get_flag("dark-mode").unwrap(), None);
+
+        let received: Vec<FlagChange> = changes.try_iter().collect();
+        assert_eq!(received.len(), 5);
+        assert_eq!(received[1], FlagChange{ name: "rollout".to_string(), flag: Some(Flag::Percentage(60)) });
+        assert_eq!(received[4], FlagChange{ name: "dark-mode".to_string(), flag: None });
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn counter_store() {
+        use crate::counters::{CounterRetention, CounterStore, Rollup};
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("CounterStore.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(10 * 86400);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build_client().unwrap();
+        let retention = CounterRetention{
+            minutes: Duration::from_secs(300),
+            hours: Duration::from_secs(7200),
+            days: Duration::from_secs(10 * 86400),
+        };
+        let mut counters = CounterStore::new(&c, "Counters".to_string(), retention).unwrap();
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let mut counters = CounterStore::new(&c, "Counters".to_string(), retention).unwrap();
+            std::thread::spawn(move || {
+                for _ in 0..25 {
+                    counters.increment("hits", 1).unwrap();
+                };
+            })
+        }).collect();
+        for t in threads {
+            t.join().unwrap();
+        };
+        assert_eq!(counters.count("hits", Rollup::Minute).unwrap(), 100);
+
+        clock.advance(Duration::from_secs(90));
+        counters.increment("hits", 5).unwrap();
+        counters.increment("misses", -1).unwrap();
+        assert_eq!(counters.count("hits", Rollup::Minute).unwrap(), 5);
+        assert_eq!(counters.count("hits", Rollup::Hour).unwrap(), 105);
+        assert_eq!(counters.count("misses", Rollup::Day).unwrap(), -1);
+        assert_eq!(counters.series("hits", Rollup::Minute, 3).unwrap(), vec![
+            (start - Duration::from_secs(60), 0),
+            (start, 100),
+            (start + Duration::from_secs(60), 5),
+        ]);
+
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(counters.prune_counters().unwrap(), 3);
+        assert_eq!(counters.series("hits", Rollup::Minute, 12).unwrap().iter().map(|(_, c)| c).sum::<i64>(), 0);
+        assert_eq!(counters.count("hits", Rollup::Hour).unwrap(), 105);
+
+        clock.advance(Duration::from_secs(3 * 86400));
+        counters.increment("misses", 1).unwrap();
+        assert_eq!(c.stats().unwrap().tables["Counters"].entries, 5);
+        assert_eq!(counters.count("hits", Rollup::Day).unwrap(), 0);
+        assert_eq!(counters.series("hits", Rollup::Day, 4).unwrap()[0].1, 105);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn acquire_lease() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("AcquireLease.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build_client().unwrap();
+
+        let mut first = c.acquire_lease("leader".to_string(), Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(first.token(), 1);
+        assert_eq!(first.expires(), start + Duration::from_secs(10));
+        assert!(c.acquire_lease("leader".to_string(), Duration::from_secs(10)).unwrap().is_none());
+        let other = c.acquire_lease("follower".to_string(), Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(other.token(), 1);
+
+        clock.advance(Duration::from_secs(8));
+        assert!(first.renew(Duration::from_secs(10)).unwrap());
+        clock.advance(Duration::from_secs(8));
+        assert!(c.acquire_lease("leader".to_string(), Duration::from_secs(10)).unwrap().is_none());
+
+        clock.advance(Duration::from_secs(3));
+        let mut second = c.acquire_lease("leader".to_string(), Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(second.token(), 2);
+        assert!(!first.renew(Duration::from_secs(10)).unwrap());
+        assert!(!first.release().unwrap());
+        assert!(second.renew(Duration::from_secs(10)).unwrap());
+        assert!(second.release().unwrap());
+        assert_eq!(c.acquire_lease("leader".to_string(), Duration::from_secs(10)).unwrap().unwrap().token(), 3);
+
+        let threads: Vec<_> = (0..8).map(|_| {
+            let mut c = c.clone();
+            std::thread::spawn(move || c.acquire_lease("race".to_string(), Duration::from_secs(10)).unwrap().is_some())
+        }).collect();
+        assert_eq!(threads.into_iter().map(|t| t.join().unwrap()).filter(|a| *a).count(), 1);
+        assert!(matches!(c.acquire_lease("overflow".to_string(), Duration::MAX), Err(DatabaseError::InvalidFormat(_))));
+
+        // Another Client of the file, as in another process, sees the Leases in the file
+        let mut other = Client::builder(&temp_dir_path).clock(clock.clone()).open().unwrap();
+        assert!(other.acquire_lease("race".to_string(), Duration::from_secs(10)).unwrap().is_none());
+        assert_eq!(other.acquire_lease("elsewhere".to_string(), Duration::from_secs(10)).unwrap().unwrap().token(), 1);
+        assert!(c.acquire_lease("elsewhere".to_string(), Duration::from_secs(10)).unwrap().is_none());
+        c.save().unwrap();
+        let mut reopened = Client::builder(&temp_dir_path).clock(clock.clone()).open().unwrap();
+        assert!(reopened.acquire_lease("elsewhere".to_string(), Duration::from_secs(10)).unwrap().is_none());
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+        std::fs::remove_file(lease::lock_path(&temp_dir_path)).unwrap();
+    }
+
+    #[test]
+    fn system_tables() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("SystemTables.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let start = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+        for name in ["Users", "Scratch"] {
+            let table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_optional_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+                .max_entries(10, CapacityPolicy::Reject)
+                .build().unwrap();
+            c.create_table(table).unwrap();
+        };
+        c.insert("Users".to_string(), capacity_entry("alice", "first")).unwrap();
+        clock.advance(Duration::from_secs(5));
+        c.drop_table(&"Scratch".to_string()).unwrap();
+
+        let tables = c.scan(TABLES_TABLE.to_string()).unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].fields["Fields"], Field::String("Count:I64?,Notes:String".to_string()));
+        assert_eq!(tables[0].fields["MaxEntries"], Field::U64(10));
+
+        let stats = c.get(STATS_TABLE.to_string(), Field::String("Users".to_string())).unwrap();
+        assert_eq!(stats.fields["Entries"], Field::U64(1));
+
+        let mut criteria = HashMap::new();
+        criteria.insert("Change".to_string(), Field::String("dropped".to_string()));
+        let dropped = c.query(SCHEMA_HISTORY_TABLE.to_string(), criteria).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].primary_field, Field::U64(2));
+        assert_eq!(dropped[0].fields["Table"], Field::String("Scratch".to_string()));
+        assert_eq!(dropped[0].fields["At"], Field::Date(start + Duration::from_secs(5)));
+
+        assert!(matches!(c.insert(STATS_TABLE.to_string(), capacity_entry("bob", "")), Err(DatabaseError::ReadOnlyTable(_))));
+        assert!(matches!(c.drop_table(&TABLES_TABLE.to_string()), Err(DatabaseError::ReadOnlyTable(_))));
+        assert!(matches!(c.delete(TABLES_TABLE.to_string(), Field::String("Users".to_string())), Err(DatabaseError::ReadOnlyTable(_))));
+        let table = structs::Table::new()
+            .name(STATS_TABLE.to_string())
+            .name_policy(NamePolicy::unrestricted())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        assert!(matches!(c.create_table(table), Err(DatabaseError::TableExists(_))));
+        assert_eq!(c.list_tables().unwrap(), vec!["Users".to_string()]);
+
+        c.save().unwrap();
+        let mut reopened = Client::open(&temp_dir_path).unwrap();
+        assert_eq!(reopened.scan(SCHEMA_HISTORY_TABLE.to_string()).unwrap().len(), 3);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    #[test]
+    fn gc_orphaned_files() {
+        let mut dir = temp_dir();
+        dir.push("GcOrphanedFiles");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("Gc.db");
+
+        let mut c = Client::builder(&path).build().unwrap();
+        for name in ["Kept", "Orphaned"] {
+            let table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+                .spill_after(1)
+                .build().unwrap();
+            c.create_table(table).unwrap();
+            if name == "Kept" {
+                c.save().unwrap();
+            };
+        };
+        drop(c);
+
+        let kept = spill::segment_path(&path, "Kept");
+        let orphaned = spill::segment_path(&path, "Orphaned");
+        assert!(kept.exists() && orphaned.exists());
+        for name in ["Gc.db.bak", "Gc.db.tmp.1", "Gc.db.0.tmp", "Gc.db.notes", "Other.db.tmp"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        };
+
+        let mut c = Client::builder(&path)
+            .gc_files_on_open(Duration::from_secs(3600))
+            .open().unwrap();
+        assert!(orphaned.exists());
+        assert_eq!(c.gc_files(Duration::ZERO).unwrap(), vec![orphaned.clone()]);
+        assert!(c.gc_files(Duration::ZERO).unwrap().is_empty());
+        assert!(kept.exists());
+        for name in ["Gc.db.bak", "Gc.db.tmp.1", "Gc.db.0.tmp", "Gc.db.notes", "Other.db.tmp"] {
+            assert!(dir.join(name).exists());
+        };
+        drop(c);
+
+        // The database named "tmp" of a Container at Gc.db keeps its files beside Gc.db.tmp
+        let container_path = dir.join("Gc.db.tmp");
+        let mut inner = Client::builder(&container_path).build().unwrap();
+        let table = structs::Table::new()
+            .name("Inner".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .spill_after(1)
+            .build().unwrap();
+        inner.create_table(table).unwrap();
+        inner.save().unwrap();
+        drop(inner);
+        Client::builder(&path)
+            .gc_files_on_open(Duration::ZERO)
+            .open().unwrap();
+        assert!(container_path.exists() && spill::segment_path(&container_path, "Inner").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_missing_database_reports_path() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Missing Directory");
+        temp_dir_path.push("Missing.db");
+        match Client::open(&temp_dir_path) {
+            Err(DatabaseError::DatabaseDoesNotExist(p)) => assert_eq!(p, temp_dir_path),
+            _ => panic!("expected DatabaseDoesNotExist"),
+        };
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let mut dir = temp_dir();
+        dir.push(OsStr::from_bytes(b"NonUtf8\xff"));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join(OsStr::from_bytes(b"Keystore\xfe.db"));
+
+        match Client::open(&path) {
+            Err(e @ DatabaseError::DatabaseDoesNotExist(_)) => {
+                assert!(e.to_string().contains("Keystore"));
+                assert!(matches!(e, DatabaseError::DatabaseDoesNotExist(p) if p == path));
+            },
+            _ => panic!("expected DatabaseDoesNotExist"),
+        };
+
+        let mut c = Client::builder(&path)
+            .gc_files_on_open(Duration::ZERO)
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Spilled".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .spill_after(1)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("Spilled".to_string(), capacity_entry("Key", "value")).unwrap();
+        c.save().unwrap();
+        std::fs::write(spill::segment_path(&path, "Orphaned"), b"").unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        assert_eq!(c.get("Spilled".to_string(), Field::String("Key".to_string())).unwrap().fields["Notes"], Field::String("value".to_string()));
+        assert_eq!(c.gc_files(Duration::ZERO).unwrap().len(), 1);
+        drop(c);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths() {
+        use std::ffi::OsString;
+        use std::os::windows::ffi::OsStringExt;
+        let mut dir = temp_dir();
+        dir.push(OsString::from_wide(&[0x004b, 0xd800, 0x0056]));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("Keystore.db");
+
+        match Client::open(&path) {
+            Err(DatabaseError::DatabaseDoesNotExist(p)) => assert_eq!(p, path),
+            _ => panic!("expected DatabaseDoesNotExist"),
+        };
+
+        let mut c = Client::builder(&path).build().unwrap();
+        c.save().unwrap();
+        drop(c);
+        assert!(Client::open(&path).is_ok());
+
+        let mut verbatim = OsString::from(r"\\?\");
+        verbatim.push(path.as_os_str());
+        assert!(Client::open(PathBuf::from(verbatim)).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_empty_file() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("OpenEmptyFile.db");
+        let backup = backup_path(&temp_dir_path);
+        for path in [&temp_dir_path, &backup] {
+            if path.exists() {
+                std::fs::remove_file(path).unwrap();
+            };
+        };
+
+        std::fs::write(&temp_dir_path, b"").unwrap();
+        assert!(matches!(Client::open(&temp_dir_path), Err(DatabaseError::EmptyDatabaseFile(p)) if p == temp_dir_path));
+        assert!(matches!(Client::builder(&temp_dir_path).on_empty_file(EmptyFilePolicy::UseBackup).open(), Err(DatabaseError::EmptyDatabaseFile(_))));
+
+        let mut c = Client::builder(&temp_dir_path)
+            .on_empty_file(EmptyFilePolicy::TreatAsNew)
+            .open().unwrap();
+        let table = structs::Table::new()
+            .name("Recovered".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.save().unwrap();
+        drop(c);
+
+        std::fs::copy(&temp_dir_path, &backup).unwrap();
+        std::fs::write(&temp_dir_path, b"").unwrap();
+        let mut c = Client::builder(&temp_dir_path)
+            .on_empty_file(EmptyFilePolicy::UseBackup)
+            .open().unwrap();
+        assert_eq!(c.list_tables().unwrap(), vec!["Recovered".to_string()]);
+        c.save().unwrap();
+        assert_eq!(Client::open(&temp_dir_path).unwrap().list_tables().unwrap().len(), 1);
+
+        for path in [&temp_dir_path, &backup] {
+            std::fs::remove_file(path).unwrap();
+        };
+    }
+
+    #[test]
+    fn write_telemetry() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("WriteTelemetry.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        let created = c.stats().unwrap().writes;
+        assert_eq!(created.saves, 1);
+        assert_eq!(created.last_save, Some(created.total));
+
+        let table = structs::Table::new()
+            .name("Telemetry".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..100 {
+            c.insert("Telemetry".to_string(), capacity_entry(&format!("Key{}", i), &"repeated ".repeat(20))).unwrap();
+        };
+        c.save().unwrap();
+        c.compact().unwrap();
+
+        let writes = c.stats().unwrap().writes;
+        let last = writes.last_save.unwrap();
+        assert_eq!(writes.saves, 3);
+        assert!(last.serialized_bytes > 100 * 180);
+        assert!(last.compressed_bytes < last.serialized_bytes);
+        assert_eq!(last.written_bytes, std::fs::metadata(&temp_dir_path).unwrap().len());
+        assert_eq!(writes.total.written_bytes, created.total.written_bytes + 2 * last.written_bytes);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn train_dictionary() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("TrainDictionary.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        let table = structs::Table::new()
+            .name("Dictionary".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..2000 {
+            c.insert("Dictionary".to_string(), capacity_entry(&format!("Key{}", i), &format!("user {} signed in from host-{}.example.com", i * 7, i % 13))).unwrap();
+        };
+
+        let size = c.train_dictionary(4096).unwrap();
+        assert!(size > 0 && size <= 4096);
+        c.save().unwrap();
+        assert!(dictionary::is_dictionary_compressed(header::split(&std::fs::read(&temp_dir_path).unwrap()).unwrap().1));
+
+        let mut reopened = Client::open(&temp_dir_path).unwrap();
+        assert_eq!(reopened.scan("Dictionary".to_string()).unwrap().len(), 2000);
+        let entry = reopened.get("Dictionary".to_string(), structs::Field::String("Key42".to_string())).unwrap();
+        assert_eq!(entry.fields.get("Notes"), Some(&structs::Field::String("user 294 signed in from host-3.example.com".to_string())));
+        reopened.save().unwrap();
+        assert!(dictionary::is_dictionary_compressed(header::split(&std::fs::read(&temp_dir_path).unwrap()).unwrap().1));
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn container_databases() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Container.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let container = Container::new(&temp_dir_path, None).unwrap();
+        assert!(Container::new(&temp_dir_path, None).is_err());
+        let table = structs::Table::new()
+            .name("Plugin".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        let mut first = container.create_database("first").unwrap();
+        let mut second = container.create_database("second").unwrap();
+        assert!(container.create_database("first").is_err());
+        assert!(container.create_database("../escape").is_err());
+        for (c, notes) in [(&mut first, "first"), (&mut second, "second")] {
+            c.create_table(table.clone()).unwrap();
+            c.insert("Plugin".to_string(), capacity_entry("Key", notes)).unwrap();
+        };
+        first.save().unwrap();
+
+        let mut again = container.open_database("first").unwrap();
+        assert_eq!(again.scan("Plugin".to_string()).unwrap().len(), 1);
+        drop((first, second, again, container));
+
+        let container = Container::open(&temp_dir_path, None).unwrap();
+        assert_eq!(container.list_databases().unwrap(), vec!["first".to_string(), "second".to_string()]);
+        let mut first = container.open_database("first").unwrap();
+        let entry = first.get("Plugin".to_string(), structs::Field::String("Key".to_string())).unwrap();
+        assert_eq!(entry.fields.get("Notes"), Some(&structs::Field::String("first".to_string())));
+        assert!(first.verify().unwrap().is_ok());
+        let mut second = container.open_database("second").unwrap();
+        assert!(second.list_tables().unwrap().is_empty());
+
+        assert!(container.drop_database("second").unwrap());
+        assert!(!container.drop_database("second").unwrap());
+        assert!(container.open_database("second").is_err());
+        assert_eq!(container.list_databases().unwrap(), vec!["first".to_string()]);
+        assert!(!temp_dir_path.with_file_name("Container.db.first").exists());
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn snapshot_isolation() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Snapshot.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let mut c = Client::builder(&temp_dir_path).clock(clock.clone()).build_client().unwrap();
+        let table = structs::Table::new()
+            .name("Snapshot".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..10 {
+            c.insert("Snapshot".to_string(), capacity_entry(&format!("Key{}", i), "before")).unwrap();
+        };
+
+        let snapshot = c.snapshot().unwrap();
+        assert_eq!(snapshot.taken_at(), clock.now());
+        let mut writer = c.clone();
+        std::thread::spawn(move || {
+            for i in 0..10 {
+                writer.insert_or_update("Snapshot".to_string(), capacity_entry(&format!("Key{}", i), "after")).unwrap();
+            };
+            writer.insert("Snapshot".to_string(), capacity_entry("Key10", "after")).unwrap();
+            writer.drop_table(&"Snapshot".to_string()).unwrap();
+        }).join().unwrap();
+
+        assert!(c.scan("Snapshot".to_string()).is_err());
+        let entries = snapshot.scan("Snapshot".to_string()).unwrap();
+        assert_eq!(entries.len(), 10);
+        assert!(entries.iter().all(|e| e.fields.get("Notes") == Some(&structs::Field::String("before".to_string()))));
+        assert!(!snapshot.exists("Snapshot".to_string(), structs::Field::String("Key10".to_string())).unwrap());
+
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), structs::Field::String("before".to_string()));
+        assert_eq!(snapshot.query("Snapshot".to_string(), criteria).unwrap().len(), 10);
+        let mut output = Vec::new();
+        assert_eq!(snapshot.query_to_writer("Snapshot".to_string(), HashMap::new(), ExportFormat::Ndjson, &mut output).unwrap(), 10);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn background_compaction() {
+        let max = Duration::from_millis(50);
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+        assert!((0..100).all(|_| jitter(max) <= max));
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("BackgroundCompaction.db");
+        let dir = blob::blob_dir(&temp_dir_path, "Compaction");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .sync_interval(Duration::from_millis(10))
+            .compact_interval(Duration::from_millis(40), Duration::from_millis(20))
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Compaction".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .externalize_above(64)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("Compaction".to_string(), capacity_entry("Large", &"first ".repeat(32))).unwrap();
+        c.update("Compaction".to_string(), capacity_entry("Large", &"second ".repeat(32))).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        let started = Instant::now();
+        while std::fs::read_dir(&dir).unwrap().count() > 1 {
+            assert!(started.elapsed() < Duration::from_secs(5), "background compaction did not run");
+            sleep(Duration::from_millis(10));
+        };
+        let entry = c.get("Compaction".to_string(), Field::String("Large".to_string())).unwrap();
+        assert_eq!(entry.fields["Notes"], Field::String("second ".repeat(32)));
+
+        c.drop_table(&"Compaction".to_string()).unwrap();
+        drop(c);
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn save_gate_limits_concurrency() {
+        let gate = SaveGate::new(2);
+        assert_eq!(SaveGate::new(0).max_concurrent(), 1);
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let handles: Vec<_> = (0..8).map(|_| {
+            let (gate, peak) = (gate.clone(), peak.clone());
+            std::thread::spawn(move || {
+                let _permit = gate.acquire();
+                peak.fetch_max(gate.active(), std::sync::atomic::Ordering::SeqCst);
+                sleep(Duration::from_millis(5));
+            })
+        }).collect();
+        for h in handles {
+            h.join().unwrap();
+        };
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(gate.active(), 0);
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("SaveGate.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let mut c = Client::builder(&temp_dir_path)
+            .sync_interval(Duration::from_millis(5))
+            .sync_jitter(Duration::from_millis(5))
+            .save_gate(gate.clone())
+            .build_client().unwrap();
+        let held = gate.acquire();
+        let _second = gate.acquire();
+        let saves = c.stats().unwrap().writes.saves;
+        sleep(Duration::from_millis(50));
+        assert_eq!(c.stats().unwrap().writes.saves, saves);
+        drop(held);
+
+        let started = Instant::now();
+        while c.stats().unwrap().writes.saves == saves {
+            assert!(started.elapsed() < Duration::from_secs(5), "background save did not run");
+            sleep(Duration::from_millis(5));
+        };
+        c.save().unwrap();
+        drop(c);
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn dry_run_destructive_operations() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("DryRun.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .dry_run(true)
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("DryRun".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("DryRun".to_string(), capacity_entry("Old", "keep")).unwrap();
+        clock.advance(Duration::from_secs(61));
+        c.insert("DryRun".to_string(), capacity_entry("New", "drop")).unwrap();
+
+        c.prune().unwrap();
+        let mut criteria = HashMap::new();
+        criteria.insert("Notes".to_string(), Field::String("drop".to_string()));
+        assert_eq!(c.delete_many("DryRun".to_string(), criteria).unwrap(), 1);
+        assert_eq!(c.truncate("DryRun".to_string()).unwrap(), 2);
+        c.drop_table(&"DryRun".to_string()).unwrap();
+        assert!(matches!(c.drop_table(&"Missing".to_string()), Err(DatabaseError::TableDoesNotExist(_))));
+        assert!(matches!(c.truncate(TABLES_TABLE.to_string()), Err(DatabaseError::ReadOnlyTable(_))));
+        assert_eq!(c.scan("DryRun".to_string()).unwrap().len(), 2);
+
+        let mut actions = c.take_dry_run_actions().unwrap();
+        if let DryRunAction::DeleteEntries{keys, ..} = &mut actions[2] {
+            keys.sort_by_key(|k| k.to_string());
+        };
+        assert_eq!(actions, vec![
+            DryRunAction::DeleteEntries{table: "DryRun".to_string(), keys: vec![Field::String("Old".to_string())]},
+            DryRunAction::DeleteEntries{table: "DryRun".to_string(), keys: vec![Field::String("New".to_string())]},
+            DryRunAction::DeleteEntries{table: "DryRun".to_string(), keys: vec![Field::String("New".to_string()), Field::String("Old".to_string())]},
+            DryRunAction::DropTable{table: "DryRun".to_string(), entries: 2},
+        ]);
+        assert!(c.take_dry_run_actions().unwrap().is_empty());
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+    type ObservedEvent = (Operation, String, Option<Field>, Option<String>);
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<ObservedEvent>>,
+    }
+
+    impl OperationObserver for RecordingObserver {
+        fn on_insert(&self, event: &OperationEvent<'_>) {
+            self.record(event);
+        }
+
+        fn on_get(&self, event: &OperationEvent<'_>) {
+            self.record(event);
+        }
+
+        fn on_delete(&self, event: &OperationEvent<'_>) {
+            self.record(event);
+        }
+
+        fn on_drop_table(&self, event: &OperationEvent<'_>) {
+            self.record(event);
+        }
+    }
+
+    impl RecordingObserver {
+        fn record(&self, event: &OperationEvent<'_>) {
+            self.events.lock().unwrap().push((
+                event.operation,
+                event.table.to_string(),
+                event.key.cloned(),
+                event.context.map(str::to_string),
+            ));
+        }
+    }
+
+    #[test]
+    fn operation_observer() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("OperationObserver.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let observer = Arc::new(RecordingObserver::default());
+        let mut c = Client::builder(&temp_dir_path)
+            .observer(observer.clone())
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Observed".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("Observed".to_string(), capacity_entry("Secret", "hidden")).unwrap();
+
+        let mut alice = c.with_context("alice".to_string());
+        alice.get("Observed".to_string(), Field::String("Secret".to_string())).unwrap();
+        assert!(alice.get("Observed".to_string(), Field::String("Missing".to_string())).is_err());
+        alice.scan("Observed".to_string()).unwrap();
+        assert_eq!(alice.truncate("Observed".to_string()).unwrap(), 1);
+        c.drop_table(&"Observed".to_string()).unwrap();
+
+        let secret = Some(Field::String("Secret".to_string()));
+        let alice = Some("alice".to_string());
+        assert_eq!(*observer.events.lock().unwrap(), vec![
+            (Operation::Insert, "Observed".to_string(), secret.clone(), None),
+            (Operation::Get, "Observed".to_string(), secret, alice.clone()),
+            (Operation::Get, "Observed".to_string(), Some(Field::String("Missing".to_string())), alice.clone()),
+            (Operation::Delete, "Observed".to_string(), None, alice),
+            (Operation::DropTable, "Observed".to_string(), None, None),
+        ]);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn authorizer_denies_operations() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("Authorizer.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        #[derive(Default)]
+        struct DeniedObserver(Mutex<Vec<ObservedEvent>>);
+
+        impl OperationObserver for DeniedObserver {
+            fn on_denied(&self, event: &OperationEvent<'_>) {
+                self.0.lock().unwrap().push((event.operation, event.table.to_string(), event.key.cloned(), event.context.map(str::to_string)));
+            }
+        }
+
+        let denied = Arc::new(DeniedObserver::default());
+        let mut c = Client::builder(&temp_dir_path)
+            .observer(denied.clone())
+            .authorizer(|event| match (event.context, event.operation) {
+                (None, _) => Authorization::Allow,
+                (Some("reader"), Operation::Get | Operation::Scan | Operation::Query) => Authorization::Allow,
+                (Some(_), _) => Authorization::Deny,
+            })
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Authorized".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("Authorized".to_string(), capacity_entry("Key", "value")).unwrap();
+
+        let mut reader = c.with_context("reader".to_string());
+        assert!(reader.get("Authorized".to_string(), Field::String("Key".to_string())).is_ok());
+        assert_eq!(reader.query("Authorized".to_string(), HashMap::new()).unwrap().len(), 1);
+        assert!(matches!(reader.insert("Authorized".to_string(), capacity_entry("Other", "value")), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(reader.drop_table(&"Authorized".to_string()), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(reader.truncate("Authorized".to_string()), Err(DatabaseError::AccessDenied(_))));
+        assert_eq!(c.scan("Authorized".to_string()).unwrap().len(), 1);
+
+        let reader = Some("reader".to_string());
+        assert_eq!(*denied.0.lock().unwrap(), vec![
+            (Operation::Insert, "Authorized".to_string(), Some(Field::String("Other".to_string())), reader.clone()),
+            (Operation::DropTable, "Authorized".to_string(), None, reader.clone()),
+            (Operation::Delete, "Authorized".to_string(), None, reader),
+        ]);
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn secret_fields() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("SecretFields.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path).build().unwrap();
+        for (name, columnar) in [("Secrets", false), ("ColumnarSecrets", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Password".to_string(), structs::FieldType::Secret).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            let entry = structs::Entry::new()
+                .set_primary_field(Field::String("admin".to_string())).unwrap()
+                .add_field("Password".to_string(), Field::Secret(Secret::new("hunter2".to_string()))).unwrap()
+                .build().unwrap();
+            assert!(!format!("{:?}", entry).contains("hunter2"));
+            c.insert(name.to_string(), entry).unwrap();
+        };
+        c.save().unwrap();
+        drop(c);
+
+        let mut reopened = Client::open(&temp_dir_path).unwrap();
+        for name in ["Secrets", "ColumnarSecrets"] {
+            let entry = reopened.get(name.to_string(), Field::String("admin".to_string())).unwrap();
+            match &entry.fields["Password"] {
+                Field::Secret(s) => assert_eq!(s.expose(), "hunter2"),
+                f => panic!("Expected Secret, received {}", f),
+            };
+            assert_eq!(format!("{}", entry.fields["Password"]), REDACTED);
+        };
+        assert_eq!(Field::parse(FieldType::Secret, "hunter2").unwrap(), Field::Secret(Secret::new("hunter2".to_string())));
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn subsystem_log_levels() {
+        use crate::logging::enabled;
+        use tracing::Level;
+
+        set_log_level(Subsystem::Schema, Some(Level::INFO));
+        assert_eq!(log_level(Subsystem::Schema), Some(Level::INFO));
+        assert!(enabled(Subsystem::Schema, Level::ERROR));
+        assert!(enabled(Subsystem::Schema, Level::INFO));
+        assert!(!enabled(Subsystem::Schema, Level::DEBUG));
+
+        set_log_level(Subsystem::Schema, None);
+        assert_eq!(log_level(Subsystem::Schema), None);
+        assert!(!enabled(Subsystem::Schema, Level::ERROR));
+        let (mut c, table) = create_client_table("SilencedSchemaLogging".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.drop_table(&"SilencedSchemaLogging".to_string()).unwrap();
+
+        set_log_level(Subsystem::Schema, Some(Level::TRACE));
+        assert!(Subsystem::ALL.iter().all(|s| log_level(*s) == Some(Level::TRACE)));
+        assert_eq!(Subsystem::Query.target(), "persistent_keystore_rs::query");
+    }
+
+
+    #[test]
+    fn database_metadata() {
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let (mut local, local_path) = create_merge_client("MetadataLocal", clock.clone());
+        let (mut remote, remote_path) = create_merge_client("MetadataRemote", clock);
+
+        assert_eq!(local.set_meta("AppVersion".to_string(), "1.0.0".to_string()).unwrap(), None);
+        assert_eq!(local.set_meta("AppVersion".to_string(), "1.1.0".to_string()).unwrap(), Some("1.0.0".to_string()));
+        remote.set_meta("AppVersion".to_string(), "2.0.0".to_string()).unwrap();
+        remote.set_meta("Environment".to_string(), "remote".to_string()).unwrap();
+        remote.save().unwrap();
+
+        local.merge_from(&remote_path).unwrap();
+        local.compact().unwrap();
+        drop(local);
+
+        let mut reopened = Client::open(&local_path).unwrap();
+        assert_eq!(reopened.get_meta("AppVersion".to_string()).unwrap(), Some("1.1.0".to_string()));
+        assert_eq!(reopened.get_meta("Environment".to_string()).unwrap(), None);
+
+        std::fs::remove_file(&local_path).unwrap();
+        std::fs::remove_file(&remote_path).unwrap();
+    }
+
+
+    #[test]
+    fn idempotent_writes() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("IdempotentWrites.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .idempotency_capacity(2)
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Idempotent".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        let key = |k: &str| Some(k.to_string());
+        assert!(c.insert_idempotent("Idempotent".to_string(), capacity_entry("A", "first"), key("m1")).unwrap());
+        assert!(!c.insert_idempotent("Idempotent".to_string(), capacity_entry("A", "first"), key("m1")).unwrap());
+        assert!(!c.update_idempotent("Idempotent".to_string(), capacity_entry("A", "retried"), key("m1")).unwrap());
+        assert!(matches!(c.insert_idempotent("Idempotent".to_string(), capacity_entry("A", "other"), key("m2")), Err(DatabaseError::EntryExists)));
+        assert!(c.update_idempotent("Idempotent".to_string(), capacity_entry("A", "second"), key("m2")).unwrap());
+        assert!(c.insert_or_update_idempotent("Idempotent".to_string(), capacity_entry("B", "first"), None).unwrap());
+        assert!(c.insert_or_update_idempotent("Idempotent".to_string(), capacity_entry("B", "second"), None).unwrap());
+        c.save().unwrap();
+        drop(c);
+
+        let mut reopened = Client::builder(&temp_dir_path)
+            .idempotency_capacity(2)
+            .open().unwrap();
+        assert!(!reopened.update_idempotent("Idempotent".to_string(), capacity_entry("A", "replayed"), key("m2")).unwrap());
+        let a = reopened.get("Idempotent".to_string(), Field::String("A".to_string())).unwrap();
+        assert_eq!(a.fields["Notes"], Field::String("second".to_string()));
+
+        // m1 is forgotten once two newer keys are recorded
+        assert!(reopened.insert_idempotent("Idempotent".to_string(), capacity_entry("C", "first"), key("m3")).unwrap());
+        assert!(reopened.update_idempotent("Idempotent".to_string(), capacity_entry("A", "third"), key("m1")).unwrap());
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn group_commit_flush() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("GroupCommitFlush.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let saved = |key: &str| read_database(&temp_dir_path).unwrap()
+            .get_table_ref(&"Commits".to_string()).unwrap()
+            .lookup(&Field::String(key.to_string()))
+            .is_some();
+
+        let mut c = Client::builder(&temp_dir_path)
+            .group_commit(Duration::from_millis(5))
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Commits".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        let before = c.write_sequence().unwrap();
+        c.insert("Commits".to_string(), capacity_entry("A", "first")).unwrap();
+        let sequence = c.write_sequence().unwrap();
+        assert!(sequence > before);
+        c.flush_until(sequence).unwrap();
+        assert!(saved("A"));
+        c.flush_until(before).unwrap();
+        assert!(matches!(c.flush_until(sequence + 1), Err(DatabaseError::UnknownSequence(_))));
+
+        // Mutations that fail are not assigned a sequence number
+        assert!(matches!(c.insert("Commits".to_string(), capacity_entry("A", "again")), Err(DatabaseError::EntryExists)));
+        assert!(c.insert("Missing".to_string(), capacity_entry("A", "first")).is_err());
+        assert!(c.delete("Commits".to_string(), Field::String("Z".to_string())).is_err());
+        assert_eq!(c.write_sequence().unwrap(), sequence);
+        assert!(matches!(c.flush_until(sequence + 1), Err(DatabaseError::UnknownSequence(_))));
+
+        // Nor is a prune that removed nothing
+        c.prune().unwrap();
+        assert_eq!(c.write_sequence().unwrap(), sequence);
+
+        // Pending mutations are committed when the Client is dropped
+        let mut c = Client::builder(&temp_dir_path)
+            .group_commit(Duration::from_secs(3600))
+            .open().unwrap();
+        c.insert("Commits".to_string(), capacity_entry("B", "first")).unwrap();
+        assert!(!saved("B"));
+        drop(c);
+        assert!(saved("B"));
+
+        // Without group commit, flush saves the database
+        let mut c = Client::open(&temp_dir_path).unwrap();
+        c.insert("Commits".to_string(), capacity_entry("C", "first")).unwrap();
+        assert!(!saved("C"));
+        c.flush().unwrap();
+        assert!(saved("C"));
+        let sequence = c.write_sequence().unwrap();
+        assert!(matches!(c.flush_until(sequence + 1), Err(DatabaseError::UnknownSequence(_))));
+
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn lock_wait_stats() {
+        let (mut c, table) = create_client_table("LockWaitStats".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("LockWaitStats".to_string(), capacity_entry("A", "first")).unwrap();
+        c.insert("LockWaitStats".to_string(), capacity_entry("B", "first")).unwrap();
+        c.get("LockWaitStats".to_string(), Field::String("A".to_string())).unwrap();
+
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.lock_waits["insert"].acquisitions, 2);
+        assert_eq!(stats.lock_waits["get"].acquisitions, 1);
+        assert_eq!(stats.lock_waits["create_table"].acquisitions, 1);
+        for waits in stats.lock_waits.values() {
+            assert_eq!(waits.buckets.iter().sum::<u64>(), waits.acquisitions);
+            assert!(waits.max_wait <= waits.total_wait);
+        };
+        let worst = stats.worst_lock_waits(2);
+        assert_eq!(worst.len(), 2);
+        assert!(worst[0].1.total_wait >= worst[1].1.total_wait);
+
+        let mut waits = LockWaitStats::default();
+        waits.record(Duration::from_micros(5));
+        waits.record(Duration::from_millis(5));
+        waits.record(Duration::from_secs(5));
+        assert_eq!(waits.buckets, [1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(waits.max_wait, Duration::from_secs(5));
+        assert_eq!(waits.mean_wait(), Duration::from_micros(1_668_335));
+
+        std::fs::remove_file(temp_dir().join("LockWaitStats.db")).unwrap();
+    }
+
+
+    #[test]
+    fn scan_versioned() {
+        let (mut c, table) = create_client_table("ScanVersioned".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(3600))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("ScanVersioned".to_string(), capacity_entry("A", "first")).unwrap();
+
+        let (entries, sequence) = c.scan_versioned("ScanVersioned".to_string()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(sequence, c.write_sequence().unwrap());
+        c.get("ScanVersioned".to_string(), Field::String("A".to_string())).unwrap();
+        assert!(!c.has_changed_since(sequence).unwrap());
+
+        let mut clone = c.with_context("Other".to_string());
+        clone.update("ScanVersioned".to_string(), capacity_entry("A", "second")).unwrap();
+        assert!(c.has_changed_since(sequence).unwrap());
+        let (entries, sequence) = c.scan_versioned("ScanVersioned".to_string()).unwrap();
+        assert_eq!(entries[0].fields["Notes"], Field::String("second".to_string()));
+        assert!(!c.has_changed_since(sequence).unwrap());
+
+        assert!(matches!(c.insert("ScanVersioned".to_string(), capacity_entry("A", "third")), Err(DatabaseError::EntryExists)));
+        c.prune().unwrap();
+        assert!(!c.has_changed_since(sequence).unwrap());
+
+        assert!(matches!(c.scan_versioned("Missing".to_string()), Err(DatabaseError::TableDoesNotExist(_))));
+        std::fs::remove_file(temp_dir().join("ScanVersioned.db")).unwrap();
+    }
+
+
+    #[test]
+    fn try_methods_do_not_wait() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("TryMethods.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path).build_client().unwrap();
+        let table = structs::Table::new()
+            .name("TryMethods".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.try_insert("TryMethods".to_string(), capacity_entry("A", "first")).unwrap();
+
+        // Reads share the lock with a save in progress, but writes would wait for it
+        let shared = c.database.clone();
+        let database = shared.read().unwrap();
+        let key = Field::String("A".to_string());
+        assert_eq!(c.try_get("TryMethods".to_string(), key.clone()).unwrap().fields["Notes"], Field::String("first".to_string()));
+        assert_eq!(c.try_scan("TryMethods".to_string()).unwrap().len(), 1);
+        assert!(matches!(c.try_insert("TryMethods".to_string(), capacity_entry("B", "first")), Err(DatabaseError::WouldBlock)));
+        assert!(matches!(c.try_update("TryMethods".to_string(), capacity_entry("A", "second")), Err(DatabaseError::WouldBlock)));
+        assert!(matches!(c.try_delete("TryMethods".to_string(), key.clone()), Err(DatabaseError::WouldBlock)));
+        drop(database);
+
+        let database = shared.write().unwrap();
+        assert!(matches!(c.try_get("TryMethods".to_string(), key.clone()), Err(DatabaseError::WouldBlock)));
+        assert!(matches!(c.try_query("TryMethods".to_string(), HashMap::new()), Err(DatabaseError::WouldBlock)));
+        let error = c.non_blocking().list_tables().unwrap_err();
+        assert_eq!(error.kind(), errors::ErrorKind::Locking);
+        assert!(error.is_retryable());
+        drop(database);
+
+        c.try_insert_or_update("TryMethods".to_string(), capacity_entry("A", "second")).unwrap();
+        assert_eq!(c.non_blocking().get("TryMethods".to_string(), key).unwrap().fields["Notes"], Field::String("second".to_string()));
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn typed_table() {
+        let (mut c, table) = create_client_table("TypedTable".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("TypedTable".to_string(), capacity_entry("A", "first")).unwrap();
+        c.save().unwrap();
+
+        let open = || Client::open(temp_dir().join("TypedTable.db")).unwrap();
+        assert!(matches!(TypedTable::<u64>::new(open(), "TypedTable".to_string()), Err(DatabaseError::SchemaMismatch(_))));
+        assert!(matches!(TypedTable::<String>::new(open(), "Missing".to_string()), Err(DatabaseError::TableDoesNotExist(_))));
+
+        let mut typed = TypedTable::<String>::new(c, "TypedTable".to_string()).unwrap();
+        assert_eq!(typed.get("A".to_string()).unwrap().fields["Notes"], Field::String("first".to_string()));
+        assert!(!typed.exists("B".to_string()).unwrap());
+        typed.insert_or_update(capacity_entry("B", "first")).unwrap();
+        typed.delete("A".to_string()).unwrap();
+        assert_eq!(typed.scan().unwrap().len(), 1);
+        assert_eq!(Field::from(7u32), Field::U32(7));
+
+        std::fs::remove_file(temp_dir().join("TypedTable.db")).unwrap();
+    }
+
+
+    #[test]
+    fn copy_into() {
+        let (mut c, table) = create_client_table("CopyFrom".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .max_entries(10, structs::CapacityPolicy::Reject)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for key in ["A", "B", "C"] {
+            c.insert("CopyFrom".to_string(), capacity_entry(key, "source")).unwrap();
+        };
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("CopyInto.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let mut other = Client::builder(&temp_dir_path).build_client().unwrap();
+        assert!(matches!(c.copy_into(&mut other, &[], CopyOptions::default()), Err(DatabaseError::TableDoesNotExist(_))));
+
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let options = CopyOptions::default()
+            .create_tables(true)
+            .progress(move |p| seen.lock().unwrap().push(p.clone()));
+        let report = c.copy_into(&mut other, &[], options).unwrap();
+        assert_eq!(report.created_tables, vec!["CopyFrom".to_string()]);
+        assert_eq!(report.copied, 3);
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 1);
+        assert_eq!((calls[0].entries, calls[0].copied, calls[0].tables_copied, calls[0].tables), (3, 3, 1, 1));
+        assert_eq!(other.read_lock("test").unwrap().get_table_ref(&"CopyFrom".to_string()).unwrap().max_entries, Some(10));
+
+        c.update("CopyFrom".to_string(), capacity_entry("A", "changed")).unwrap();
+        assert!(matches!(c.copy_into(&mut other, &["CopyFrom"], CopyOptions::default()), Err(DatabaseError::EntryExists)));
+        let report = c.copy_into(&mut other, &["CopyFrom"], CopyOptions::default().on_conflict(CopyConflict::Skip)).unwrap();
+        assert_eq!((report.copied, report.skipped), (0, 3));
+        assert_eq!(other.get("CopyFrom".to_string(), Field::String("A".to_string())).unwrap().fields["Notes"], Field::String("source".to_string()));
+        let report = c.copy_into(&mut other, &["CopyFrom"], CopyOptions::default().on_conflict(CopyConflict::Overwrite)).unwrap();
+        assert_eq!((report.copied, report.skipped), (3, 0));
+        assert_eq!(other.get("CopyFrom".to_string(), Field::String("A".to_string())).unwrap().fields["Notes"], Field::String("changed".to_string()));
+
+        std::fs::remove_file(temp_dir().join("CopyFrom.db")).unwrap();
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn progress_reports() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("ProgressReports.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let mut c = Client::builder(&temp_dir_path)
+            .progress(move |p| seen.lock().unwrap().push(p.clone()))
+            .build_client().unwrap();
+        let table = structs::Table::new()
+            .name("ProgressReports".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..PROGRESS_ENTRIES {
+            c.insert("ProgressReports".to_string(), capacity_entry(&format!("Entry{}", i), "notes")).unwrap();
+        };
+
+        reports.lock().unwrap().clear();
+        c.save().unwrap();
+        let saved = reports.lock().unwrap().drain(..).collect::<Vec<_>>();
+        assert!(saved.iter().all(|p| p.operation == ProgressOperation::Save));
+        let phases = saved.iter().map(|p| p.phase).collect::<Vec<_>>();
+        assert_eq!(phases, vec![
+            ProgressPhase::Serializing, ProgressPhase::Serializing,
+            ProgressPhase::Compressing, ProgressPhase::Compressing,
+            ProgressPhase::Writing, ProgressPhase::Writing,
+            ProgressPhase::Done,
+        ]);
+        let done = saved.last().unwrap();
+        assert_eq!(done.entries, PROGRESS_ENTRIES);
+        assert_eq!(done.bytes, std::fs::metadata(&temp_dir_path).unwrap().len());
+        assert_eq!(saved[5].bytes, done.bytes);
+
+        c.compact().unwrap();
+        let compacted = reports.lock().unwrap().drain(..).collect::<Vec<_>>();
+        assert!(compacted.iter().all(|p| p.operation == ProgressOperation::Compact));
+        assert_eq!(compacted.last().unwrap().phase, ProgressPhase::Done);
+
+        let mut output = Vec::new();
+        c.query_to_writer("ProgressReports".to_string(), HashMap::new(), ExportFormat::Ndjson, &mut output).unwrap();
+        let exported = reports.lock().unwrap().drain(..).map(|p| (p.operation, p.phase, p.entries)).collect::<Vec<_>>();
+        assert_eq!(exported, vec![
+            (ProgressOperation::Export, ProgressPhase::Entries, PROGRESS_ENTRIES),
+            (ProgressOperation::Export, ProgressPhase::Done, PROGRESS_ENTRIES),
+        ]);
+        drop(c);
+
+        let seen = reports.clone();
+        let c = Client::builder(&temp_dir_path)
+            .progress(move |p| seen.lock().unwrap().push(p.clone()))
+            .open_client().unwrap();
+        let opened = reports.lock().unwrap().drain(..).collect::<Vec<_>>();
+        let phases = opened.iter().map(|p| (p.operation, p.phase)).collect::<Vec<_>>();
+        assert_eq!(phases, vec![
+            (ProgressOperation::Open, ProgressPhase::Reading),
+            (ProgressOperation::Open, ProgressPhase::Reading),
+            (ProgressOperation::Open, ProgressPhase::Decoding),
+            (ProgressOperation::Open, ProgressPhase::Decoding),
+            (ProgressOperation::Open, ProgressPhase::Done),
+        ]);
+        assert_eq!(opened[1].bytes, opened[1].total_bytes.unwrap());
+        assert_eq!(opened[4].entries, PROGRESS_ENTRIES);
+        drop(c);
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn insert_or_update_outcome() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("UpsertOutcome.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build_client().unwrap();
+        let table = structs::Table::new()
+            .name("UpsertOutcome".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        assert!(c.insert_or_update("UpsertOutcome".to_string(), capacity_entry("A", "first")).unwrap().is_inserted());
+        match c.insert_or_update("UpsertOutcome".to_string(), capacity_entry("A", "second")).unwrap() {
+            UpsertOutcome::Updated{previous} => assert_eq!(previous.fields["Notes"], Field::String("first".to_string())),
+            UpsertOutcome::Inserted => panic!("Expected the entry to be updated"),
+        };
+
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(c.insert_or_update("UpsertOutcome".to_string(), capacity_entry("A", "third")).unwrap(), UpsertOutcome::Inserted);
+        assert!(c.insert_or_update("Missing".to_string(), capacity_entry("A", "first")).is_err());
+        std::fs::remove_file(&temp_dir_path).unwrap();
+    }
+
+
+    #[test]
+    fn id_generator_sessions() {
+        let run = |name: &str| {
+            let mut temp_dir_path = temp_dir();
+            temp_dir_path.push(format!("{}.db", name));
+            if temp_dir_path.exists() {
+                std::fs::remove_file(&temp_dir_path).unwrap();
+            };
+            let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+            let mut c = Client::builder(&temp_dir_path)
+                .clock(clock.clone())
+                .id_generator(Arc::new(SequentialIdGenerator::new(7)))
+                .build_client().unwrap();
+            let mut sessions = c.session_store("Sessions".to_string(), Duration::from_secs(60)).unwrap();
+            let mut created = Vec::new();
+            for user in ["alice", "bob"] {
+                clock.advance(Duration::from_secs(1));
+                created.push(sessions.create_session(user, String::new()).unwrap());
+            };
+            let mut stored = c.scan("Sessions".to_string()).unwrap().iter().map(|e| Entry::clone(e)).collect::<Vec<_>>();
+            stored.sort_by_key(|e| e.primary_field.to_string());
+            std::fs::remove_file(&temp_dir_path).unwrap();
+            (created, stored)
+        };
+
+        let (created, stored) = run("IdGeneratorFirst");
+        assert_eq!(created[0].id, format!("{:032x}", 7));
+        assert_eq!(created[1].id, format!("{:032x}", 8));
+        assert_eq!(stored[1].last_timestamp, Some(std::time::UNIX_EPOCH + Duration::from_secs(2)));
+        assert_eq!(run("IdGeneratorSecond"), (created, stored));
+    }
+
+
+    #[test]
+    fn deterministic_serialization() {
+        let build = |name: &str, keys: &[&str]| {
+            let mut temp_dir_path = temp_dir();
+            temp_dir_path.push(format!("{}.db", name));
+            if temp_dir_path.exists() {
+                std::fs::remove_file(&temp_dir_path).unwrap();
+            };
+            let mut c = Client::builder(&temp_dir_path)
+                .clock(Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH)))
+                .build_client().unwrap();
+            for table in ["First", "Second", "Third"] {
+                let table = structs::Table::new()
+                    .name(table.to_string())
+                    .primary_field(structs::FieldType::String).unwrap()
+                    .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+                    .add_optional_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                    .add_optional_field("Flag".to_string(), structs::FieldType::Bool).unwrap()
+                    .sensitive_field("Notes".to_string()).unwrap()
+                    .build().unwrap();
+                c.create_table(table).unwrap();
+            };
+            for key in keys {
+                for table in ["Third", "First", "Second"] {
+                    let entry = Entry::new()
+                        .set_primary_field(Field::String(key.to_string())).unwrap()
+                        .add_field("Notes".to_string(), Field::String(format!("{} notes", key))).unwrap()
+                        .add_field("Count".to_string(), Field::I64(key.len() as i64)).unwrap()
+                        .add_field("Flag".to_string(), Field::Bool(true)).unwrap()
+                        .build().unwrap();
+                    c.insert(table.to_string(), entry).unwrap();
+                };
+            };
+            c.save().unwrap();
+            let bytes = std::fs::read(&temp_dir_path).unwrap();
+            (temp_dir_path, bytes)
+        };
+
+        let keys = (0..50).map(|i| format!("Key{}", i)).collect::<Vec<_>>();
+        let mut keys = keys.iter().map(|k| k.as_str()).collect::<Vec<_>>();
+        let (first_path, first) = build("DeterministicFirst", &keys);
+        keys.reverse();
+        let (second_path, second) = build("DeterministicSecond", &keys);
+        assert_eq!(first, second);
+
+        let mut c = Client::builder(&first_path)
+            .clock(Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH)))
+            .open_client().unwrap();
+        c.save().unwrap();
+        drop(c);
+        assert_eq!(std::fs::read(&first_path).unwrap(), second);
+        std::fs::remove_file(&first_path).unwrap();
+        std::fs::remove_file(&second_path).unwrap();
+    }
+
+
+
+    #[test]
+    fn diff_files() {
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let (mut first, first_path) = create_merge_client("DiffFirst", clock.clone());
+        let (mut second, second_path) = create_merge_client("DiffSecond", clock.clone());
+
+        first.insert("Readings".to_string(), merge_entry("Same", 1)).unwrap();
+        first.insert("Readings".to_string(), merge_entry("Changed", 1)).unwrap();
+        first.insert("Readings".to_string(), merge_entry("Removed", 1)).unwrap();
+        clock.advance(Duration::from_secs(10));
+        second.insert("Readings".to_string(), merge_entry("Same", 1)).unwrap();
+        second.insert("Readings".to_string(), merge_entry("Changed", 2)).unwrap();
+        second.insert("Readings".to_string(), merge_entry("Added", 2)).unwrap();
+        let table = structs::Table::new()
+            .name("Extra".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Value".to_string(), structs::FieldType::I64).unwrap()
+            .build().unwrap();
+        second.create_table(table).unwrap();
+        first.save().unwrap();
+        second.save().unwrap();
+
+        assert!(Client::diff_files(&first_path, &first_path).unwrap().is_empty());
+
+        let diff = Client::diff_files(&first_path, &second_path).unwrap();
+        assert_eq!(diff.tables_added, vec!["Extra".to_string()]);
+        assert!(diff.tables_removed.is_empty());
+        assert_eq!(diff.tables_changed.len(), 1);
+        let readings = &diff.tables_changed[0];
+        assert_eq!(readings.name, "Readings");
+        assert!(!readings.schema_changed);
+        assert_eq!(readings.added.iter().map(|e| e.primary_field.clone()).collect::<Vec<_>>(), vec![Field::String("Added".to_string())]);
+        assert_eq!(readings.removed.iter().map(|e| e.primary_field.clone()).collect::<Vec<_>>(), vec![Field::String("Removed".to_string())]);
+        assert_eq!(readings.changed.len(), 1);
+        assert_eq!(readings.changed[0].key, Field::String("Changed".to_string()));
+        assert_eq!(readings.changed[0].fields, vec!["Value".to_string()]);
+        assert_eq!(readings.changed[0].before.fields["Value"], Field::I64(1));
+        assert_eq!(readings.changed[0].after.fields["Value"], Field::I64(2));
+
+        let reversed = Client::diff_files(&second_path, &first_path).unwrap();
+        assert_eq!(reversed.tables_removed, vec!["Extra".to_string()]);
+        assert_eq!(reversed.tables_changed[0].added[0].primary_field, Field::String("Removed".to_string()));
+
+        match Client::diff_files(&first_path, &temp_dir().join("DiffMissing.db")) {
+            Err(_) => {},
+            Ok(_) => panic!("Expected an error for a missing file"),
+        };
+    }
+
+
+
+    #[test]
+    fn attach_table_from() {
+        let (mut shared, table) = create_client_table("AttachShared".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .externalize_above(64)
+            .build().unwrap();
+        shared.create_table(table).unwrap();
+        let other = structs::Table::new()
+            .name("Other".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        shared.create_table(other).unwrap();
+        shared.insert("AttachShared".to_string(), capacity_entry("Short", "short")).unwrap();
+        shared.insert("AttachShared".to_string(), capacity_entry("Long", &"long".repeat(100))).unwrap();
+        shared.insert("Other".to_string(), capacity_entry("Other", "other")).unwrap();
+        shared.save().unwrap();
+        let shared_path = temp_dir().join("AttachShared.db");
+
+        let (mut c, _) = create_client_table("AttachLocal".to_string());
+        assert_eq!(c.attach_table_from(&shared_path, "AttachShared", "Reference").unwrap(), 2);
+        assert_eq!(c.list_tables().unwrap(), vec!["Reference".to_string()]);
+        let long = c.get("Reference".to_string(), Field::String("Long".to_string())).unwrap();
+        assert_eq!(long.fields["Notes"], Field::String("long".repeat(100)));
+        c.save().unwrap();
+        assert_eq!(c.stats().unwrap().tables["Reference"].entries, 2);
+
+        match c.attach_table_from(&shared_path, "Other", "Reference") {
+            Err(DatabaseError::TableExists(_)) => {},
+            _ => panic!("Expected TableExists"),
+        };
+        match c.attach_table_from(&shared_path, "Missing", "Missing") {
+            Err(DatabaseError::TableDoesNotExist(_)) => {},
+            _ => panic!("Expected TableDoesNotExist"),
+        };
+        assert_eq!(c.attach_table_from(&shared_path, "Other", "OtherReference").unwrap(), 1);
+        assert!(c.exists("OtherReference".to_string(), Field::String("Other".to_string())).unwrap());
+    }
+
+
+
+    #[test]
+    fn segmented_tables_load_on_access() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("SegmentedTables.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path).segmented(true).build_client().unwrap();
+        let users = structs::Table::new()
+            .name("Users".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .externalize_above(64)
+            .build().unwrap();
+        let posts = structs::Table::new()
+            .name("Posts".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_field("Author".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Author".to_string(), "Users".to_string(), OnDelete::Cascade).unwrap()
+            .build().unwrap();
+        let other = structs::Table::new()
+            .name("Other".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(users).unwrap();
+        c.create_table(posts).unwrap();
+        c.create_table(other).unwrap();
+        c.insert("Users".to_string(), capacity_entry("alice", &"long".repeat(100))).unwrap();
+        let post = structs::Entry::new()
+            .set_primary_field(Field::String("first".to_string())).unwrap()
+            .add_field("Notes".to_string(), Field::String("hello".to_string())).unwrap()
+            .add_field("Author".to_string(), Field::String("alice".to_string())).unwrap()
+            .build().unwrap();
+        c.insert("Posts".to_string(), post).unwrap();
+        c.insert("Other".to_string(), capacity_entry("other", "other")).unwrap();
+        c.save().unwrap();
+        drop(c);
+
+        let (compressed, _) = read_file(&temp_dir_path).unwrap();
+        assert!(lazy::is_segmented(&decompress_file(&compressed).unwrap().0));
+
+        let mut c = Client::builder(&temp_dir_path).open_client().unwrap();
+        let loaded = |c: &Client| {
+            let database = c.database.read().unwrap();
+            let mut loaded: Vec<String> = database.list_tables().into_iter().filter(|t| database.is_loaded(t)).collect();
+            loaded.sort();
+            loaded
+        };
+        assert!(loaded(&c).is_empty());
+        assert_eq!(c.list_tables().unwrap().len(), 3);
+        c.prune().unwrap();
+        assert!(loaded(&c).is_empty());
+
+        let alice = c.get("Users".to_string(), Field::String("alice".to_string())).unwrap();
+        assert_eq!(alice.fields["Notes"], Field::String("long".repeat(100)));
+        assert_eq!(loaded(&c), vec!["Users".to_string()]);
+
+        c.insert("Users".to_string(), capacity_entry("bob", "short")).unwrap();
+        c.save().unwrap();
+        assert_eq!(loaded(&c), vec!["Users".to_string()]);
+        drop(c);
+
+        let mut c = Client::builder(&temp_dir_path).open_client().unwrap();
+        assert_eq!(c.scan("Users".to_string()).unwrap().len(), 2);
+        assert_eq!(c.get("Other".to_string(), Field::String("other".to_string())).unwrap().fields["Notes"], Field::String("other".to_string()));
+        c.delete("Users".to_string(), Field::String("alice".to_string())).unwrap();
+        assert!(c.scan("Posts".to_string()).unwrap().is_empty());
+        assert!(c.verify().unwrap().is_ok());
+
+        c.save().unwrap();
+        assert!(Client::diff_files(&temp_dir_path, &temp_dir_path).unwrap().is_empty());
+        let (mut other, _) = create_client_table("SegmentedAttach".to_string());
+        assert_eq!(other.attach_table_from(&temp_dir_path, "Other", "Other").unwrap(), 1);
+    }
+
+
+
+    #[test]
+    fn check_compat() {
+        let (mut c, table) = create_client_table("CheckCompat".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .add_index(vec!["Notes".to_string()]).unwrap()
+            .bloom_filter(0.01)
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        let plain = structs::Table::new()
+            .name("Plain".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .columnar()
+            .build().unwrap();
+        c.create_table(plain).unwrap();
+        c.insert("CheckCompat".to_string(), capacity_entry("first", "notes")).unwrap();
+        c.save().unwrap();
+        let path = temp_dir().join("CheckCompat.db");
+
+        let report = Client::check_compat(&path).unwrap();
+        assert!(report.is_compatible());
+        assert_eq!(report.format, FileFormat::Standard);
+        assert_eq!(report.compression, FileCompression::Lz4);
+        assert_eq!(report.written_by.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(report.tables["CheckCompat"], vec![SchemaFeature::Expiration, SchemaFeature::Indexes, SchemaFeature::BloomFilter]);
+        assert_eq!(report.tables["Plain"], vec![SchemaFeature::Columnar]);
+        assert_eq!(report.features().len(), 4);
+
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("CheckCompatSegmented.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let mut segmented = Client::builder(&temp_dir_path).segmented(true).build_client().unwrap();
+        segmented.attach_table_from(&path, "CheckCompat", "Copied").unwrap();
+        segmented.save().unwrap();
+        let segmented_report = Client::check_compat(&temp_dir_path).unwrap();
+        assert!(segmented_report.is_compatible());
+        assert_eq!(segmented_report.format, FileFormat::Segmented);
+        assert_eq!(segmented_report.tables["Copied"], report.tables["CheckCompat"]);
+
+        std::fs::write(&temp_dir_path, b"not a database").unwrap();
+        assert!(!Client::check_compat(&temp_dir_path).unwrap().is_compatible());
+        std::fs::write(&temp_dir_path, b"").unwrap();
+        match Client::check_compat(&temp_dir_path) {
+            Err(DatabaseError::EmptyDatabaseFile(_)) => {},
+            _ => panic!("Expected EmptyDatabaseFile"),
+        };
+    }
+
+
+    #[test]
+    fn file_info() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("FileInfo.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let written_at = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(Arc::new(ManualClock::new(written_at)))
+            .build_client().unwrap();
+        let table = structs::Table::new()
+            .name("FileInfo".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("FileInfo".to_string(), capacity_entry("first", "notes")).unwrap();
+        c.save().unwrap();
+
+        let info = Client::file_info(&temp_dir_path).unwrap().unwrap();
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.written_at, written_at);
+        drop(c);
+
+        let contents = std::fs::read(&temp_dir_path).unwrap();
+        let (header_info, _) = header::split(&contents).unwrap();
+        assert_eq!(header_info, Some(info));
+        std::fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/baseline-0.0.8.db"), &temp_dir_path).unwrap();
+        assert_eq!(Client::file_info(&temp_dir_path).unwrap(), None);
+        let mut legacy = Client::builder(&temp_dir_path).open_client().unwrap();
+        assert!(legacy.get("Users".to_string(), structs::Field::String("alice".to_string())).is_ok());
+        legacy.save().unwrap();
+        assert!(Client::file_info(&temp_dir_path).unwrap().is_some());
+
+        std::fs::write(&temp_dir_path, &contents[..14]).unwrap();
+        assert!(Client::file_info(&temp_dir_path).is_err());
+    }
+
+
+    #[test]
+    fn entry_size_stats() {
+        let (mut c, table) = create_client_table("EntrySizeStats".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..10 {
+            c.insert("EntrySizeStats".to_string(), capacity_entry(&format!("small{}", i), "notes")).unwrap();
+        };
+        let large = capacity_entry("large", &"x".repeat(10_000));
+        let large_size = large.approx_size();
+        c.insert("EntrySizeStats".to_string(), large).unwrap();
+
+        let stats = c.stats().unwrap();
+        let sizes = &stats.tables["EntrySizeStats"].entry_sizes;
+        assert_eq!(sizes.count(), 11);
+        assert_eq!(sizes.buckets[0] + sizes.buckets[1], 10);
+        assert_eq!(sizes.buckets[4], 1);
+        assert_eq!(sizes.largest.len(), LARGEST_ENTRIES);
+        assert_eq!(sizes.largest[0], (structs::Field::String("large".to_string()), large_size));
+        assert!(sizes.largest.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        c.delete("EntrySizeStats".to_string(), structs::Field::String("large".to_string())).unwrap();
+        let sizes = c.stats().unwrap().tables["EntrySizeStats"].entry_sizes.clone();
+        assert_eq!(sizes.count(), 10);
+        assert!(sizes.largest.iter().all(|(k, _)| *k != structs::Field::String("large".to_string())));
+    }
+
+
+    #[test]
+    fn scan_stale() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("ScanStale.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("ScanStale".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(3600))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+
+        c.insert("ScanStale".to_string(), capacity_entry("B", "oldest")).unwrap();
+        c.insert("ScanStale".to_string(), capacity_entry("A", "oldest")).unwrap();
+        clock.advance(Duration::from_secs(60));
+        c.insert("ScanStale".to_string(), capacity_entry("C", "older")).unwrap();
+        clock.advance(Duration::from_secs(60));
+        c.insert("ScanStale".to_string(), capacity_entry("D", "fresh")).unwrap();
+        clock.advance(Duration::from_secs(30));
+
+        let keys = |entries: Vec<Arc<Entry>>| entries.iter().map(|e| e.primary_field.clone()).collect::<Vec<Field>>();
+        let key = |k: &str| Field::String(k.to_string());
+        assert_eq!(keys(c.scan_stale("ScanStale".to_string(), Duration::from_secs(60)).unwrap()), vec![key("A"), key("B"), key("C")]);
+        assert_eq!(keys(c.scan_stale("ScanStale".to_string(), Duration::from_secs(120)).unwrap()), vec![key("A"), key("B")]);
+        assert!(c.scan_stale("ScanStale".to_string(), Duration::from_secs(600)).unwrap().is_empty());
+
+        c.update("ScanStale".to_string(), capacity_entry("A", "refreshed")).unwrap();
+        assert_eq!(keys(c.scan_stale("ScanStale".to_string(), Duration::from_secs(60)).unwrap()), vec![key("B"), key("C")]);
+        assert!(matches!(c.scan_stale("Missing".to_string(), Duration::ZERO), Err(DatabaseError::TableDoesNotExist(_))));
+    }
+
+
+    #[test]
+    fn touch_many() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("TouchMany.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
+            .build().unwrap();
+        let names = ["TouchMany", "TouchManyColumnar", "TouchManySpilled"];
+        for name in names {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+                .add_expiration(Duration::from_secs(60))
+                .append_only(HistoryRetention::default());
+            match name {
+                "TouchManyColumnar" => table = table.columnar(),
+                "TouchManySpilled" => table = table.spill_after(1),
+                _ => {},
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            c.insert(name.to_string(), capacity_entry("A", "kept")).unwrap();
+            c.insert(name.to_string(), capacity_entry("B", "expired")).unwrap();
+        };
+
+        clock.advance(Duration::from_secs(50));
+        let keys = vec![Field::String("A".to_string()), Field::String("Missing".to_string())];
+        for name in names {
+            assert_eq!(c.touch_many(name.to_string(), &keys).unwrap(), 1);
+        };
+        clock.advance(Duration::from_secs(20));
+        c.prune().unwrap();
+
+        for name in names {
+            let entries = c.scan(name.to_string()).unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].primary_field, Field::String("A".to_string()));
+            assert_eq!(entries[0].fields["Notes"], Field::String("kept".to_string()));
+            assert_eq!(entries[0].last_timestamp, Some(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(50)));
+            assert_eq!(c.history(name.to_string(), Field::String("A".to_string())).unwrap().len(), 1);
+        };
+        assert!(matches!(c.touch_many("Missing".to_string(), &keys), Err(DatabaseError::TableDoesNotExist(_))));
+    }
+
+
+    #[test]
+    fn name_policy() {
+        let builder = || structs::Table::new()
+            .primary_field(structs::FieldType::String).unwrap();
+        let invalid = |result: Result<structs::Table, DatabaseError>| matches!(result, Err(DatabaseError::InvalidName(_)));
+
+        assert!(invalid(builder().name("__mine".to_string()).add_field("Notes".to_string(), structs::FieldType::String).unwrap().build()));
+        assert!(invalid(builder().name("x".repeat(DEFAULT_MAX_NAME_LEN + 1)).add_field("Notes".to_string(), structs::FieldType::String).unwrap().build()));
+        assert!(invalid(builder().name("Line\nBreak".to_string()).add_field("Notes".to_string(), structs::FieldType::String).unwrap().build()));
+        assert!(invalid(builder().name("Names".to_string()).add_field(String::new(), structs::FieldType::String).unwrap().build()));
+        assert!(invalid(builder().name("Names".to_string()).add_field("__Notes".to_string(), structs::FieldType::String).unwrap().build()));
+        match builder().add_field("Notes".to_string(), structs::FieldType::String).unwrap().build() {
+            Err(DatabaseError::TableNameNotSet) => {},
+            _ => panic!("Expected TableNameNotSet"),
+        };
+        let table = builder().name("Names with spaces".to_string())
+            .add_field("Last Seen".to_string(), structs::FieldType::Date).unwrap()
+            .build().unwrap();
+        assert_eq!(table.name, "Names with spaces");
+
+        let identifiers = NamePolicy{
+            charset: NameCharset::Identifier,
+            ..Default::default()
+        };
+        assert!(invalid(builder().name("Names with spaces".to_string()).add_field("Notes".to_string(), structs::FieldType::String).unwrap().name_policy(identifiers.clone()).build()));
+        assert!(builder().name("names.v2".to_string()).add_field("last_seen".to_string(), structs::FieldType::Date).unwrap().name_policy(identifiers).build().is_ok());
+        assert!(builder().name("__mine".to_string()).add_field("Notes".to_string(), structs::FieldType::String).unwrap().name_policy(NamePolicy::unrestricted()).build().is_ok());
+
+        let (mut c, table) = create_client_table("NamePolicy".to_string());
+        c.create_table(table.primary_field(structs::FieldType::String).unwrap().add_field("Notes".to_string(), structs::FieldType::String).unwrap().build().unwrap()).unwrap();
+        c.save().unwrap();
+        let path = temp_dir().join("NamePolicy.db");
+        assert!(matches!(c.attach_table_from(&path, "NamePolicy", "__copy"), Err(DatabaseError::InvalidName(_))));
+        assert!(c.acquire_lease("name-policy".to_string(), Duration::from_secs(60)).unwrap().is_some());
+        assert_eq!(DatabaseError::InvalidName(String::new()).kind(), ErrorKind::Validation);
+    }
+
+
+    #[test]
+    fn case_insensitive_tables() {
+        let path = temp_dir().join("case_insensitive_tables.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let table = || Table::new()
+            .name("MyTable".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap();
+        let mut c = Client::builder(&path).case_insensitive_tables(true).build().unwrap();
+        c.create_table(table().build().unwrap()).unwrap();
+        c.insert("mytable".to_string(), capacity_entry("a", "one")).unwrap();
+        c.insert("MYTABLE".to_string(), capacity_entry("b", "two")).unwrap();
+        assert_eq!(c.get("myTable".to_string(), Field::String("a".to_string())).unwrap().get_field("Notes".to_string()).unwrap(), Field::String("one".to_string()));
+        assert_eq!(c.scan("MyTable".to_string()).unwrap().len(), 2);
+        assert_eq!(c.list_tables().unwrap(), vec!["MyTable".to_string()]);
+        match c.create_table(table().name("MYTABLE".to_string()).build().unwrap()) {
+            Err(DatabaseError::TableExists(_)) => {},
+            other => panic!("expected TableExists, got {:?}", other),
+        };
+
+        let referencing = Table::new()
+            .name("Notes".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Table".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Table".to_string(), "mytable".to_string(), OnDelete::Restrict).unwrap()
+            .build().unwrap();
+        c.create_table(referencing).unwrap();
+        c.save().unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        match c.get("mytable".to_string(), Field::String("a".to_string())) {
+            Err(DatabaseError::TableDoesNotExist(_)) => {},
+            other => panic!("expected TableDoesNotExist, got {:?}", other),
+        };
+        drop(c);
+
+        let mut c = Client::builder(&path).case_insensitive_tables(true).open().unwrap();
+        assert!(c.exists("MYTABLE".to_string(), Field::String("b".to_string())).unwrap());
+        assert!(c.drop_table(&"mytable".to_string()).is_err());
+        c.drop_table(&"notes".to_string()).unwrap();
+        c.drop_table(&"mytable".to_string()).unwrap();
+        assert!(c.list_tables().unwrap().is_empty());
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn lifecycle_events() {
+        let dir = temp_dir().join("LifecycleEvents");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        };
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("LifecycleEvents.db");
+
+        let events = LifecycleEvents::new();
+        let rx = events.subscribe();
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&path)
+            .clock(clock.clone())
+            .lifecycle_events(events)
+            .build().unwrap();
+        assert!(matches!(rx.try_recv().unwrap(), LifecycleEvent::Saved{..}));
+        assert_eq!(rx.try_recv().unwrap(), LifecycleEvent::Opened{path: path.clone(), created: true});
+
+        let table = structs::Table::new()
+            .name("LifecycleEvents".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("LifecycleEvents".to_string(), capacity_entry("a", "one")).unwrap();
+        c.insert("LifecycleEvents".to_string(), capacity_entry("b", "two")).unwrap();
+        clock.advance(Duration::from_secs(61));
+        c.prune().unwrap();
+        assert_eq!(rx.try_recv().unwrap(), LifecycleEvent::Pruned{removed: 2});
+
+        c.save().unwrap();
+        let bytes = std::fs::metadata(&path).unwrap().len();
+        match rx.try_recv().unwrap() {
+            LifecycleEvent::Saved{bytes: saved, ..} => assert_eq!(saved, bytes),
+            other => panic!("expected Saved, got {:?}", other),
+        };
+        let late = c.subscribe_lifecycle();
+        assert_eq!(c.compact().unwrap(), 0);
+        assert!(matches!(rx.try_recv().unwrap(), LifecycleEvent::Compacted{removed: 0, ..}));
+        assert!(matches!(late.try_recv().unwrap(), LifecycleEvent::Compacted{removed: 0, ..}));
+        drop(c);
+
+        let rx = {
+            let events = LifecycleEvents::new();
+            let rx = events.subscribe();
+            let mut c = Client::builder(&path).lifecycle_events(events).open().unwrap();
+            assert_eq!(rx.try_recv().unwrap(), LifecycleEvent::Opened{path: path.clone(), created: false});
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert!(c.save().is_err());
+            rx
+        };
+        match rx.try_recv().unwrap() {
+            LifecycleEvent::Error{operation, ..} => assert_eq!(operation, "save"),
+            other => panic!("expected Error, got {:?}", other),
+        };
+        assert!(rx.try_recv().is_err());
+    }
+
+
+    #[test]
+    fn size_budget() {
+        let path = temp_dir().join("SizeBudget.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let events = LifecycleEvents::new();
+        let rx = events.subscribe();
+        let mut c = Client::builder(&path)
+            .size_budget(1024)
+            .lifecycle_events(events)
+            .build_client().unwrap();
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.size_budget, Some(1024));
+        assert!(!stats.over_size_budget);
+        assert!(rx.try_iter().all(|e| !matches!(e, LifecycleEvent::SizeBudgetExceeded{..})));
+
+        let table = structs::Table::new()
+            .name("SizeBudget".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        for i in 0..64 {
+            c.insert("SizeBudget".to_string(), capacity_entry(&format!("key{}", i), &format!("{:x}", (i as u64).wrapping_mul(0x9e3779b97f4a7c15)))).unwrap();
+        };
+        c.save().unwrap();
+        let bytes = std::fs::metadata(&path).unwrap().len();
+        assert!(bytes > 1024);
+        assert!(rx.try_iter().any(|e| e == LifecycleEvent::SizeBudgetExceeded{bytes, budget: 1024}));
+        assert!(c.stats().unwrap().over_size_budget);
+
+        c.truncate("SizeBudget".to_string()).unwrap();
+        c.save().unwrap();
+        assert!(!c.stats().unwrap().over_size_budget);
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn expiring_within() {
+        let path = temp_dir().join("ExpiringWithin.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let events = LifecycleEvents::new();
+        let rx = events.subscribe();
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&path)
+            .clock(clock.clone())
+            .sync_interval(Duration::from_millis(10))
+            .expiry_warning(Duration::from_secs(45))
+            .lifecycle_events(events)
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("ExpiringWithin".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("ExpiringWithin".to_string(), capacity_entry("B", "first")).unwrap();
+        c.insert("ExpiringWithin".to_string(), capacity_entry("A", "first")).unwrap();
+        clock.advance(Duration::from_secs(20));
+        c.insert("ExpiringWithin".to_string(), capacity_entry("C", "second")).unwrap();
+        clock.advance(Duration::from_secs(10));
+
+        assert!(c.expiring_within("ExpiringWithin".to_string(), Duration::from_secs(10)).unwrap().is_empty());
+        let expiring: Vec<(Field, Duration)> = c.expiring_within("ExpiringWithin".to_string(), Duration::from_secs(45)).unwrap()
+            .into_iter()
+            .map(|(e, ttl)| (e.primary_field.clone(), ttl))
+            .collect();
+        assert_eq!(expiring, vec![
+            (Field::String("A".to_string()), Duration::from_secs(30)),
+            (Field::String("B".to_string()), Duration::from_secs(30)),
+        ]);
+        assert_eq!(c.expiring_within("ExpiringWithin".to_string(), Duration::from_secs(60)).unwrap().len(), 3);
+        assert!(matches!(c.expiring_within("Missing".to_string(), Duration::from_secs(60)), Err(DatabaseError::TableDoesNotExist(_))));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let entries = loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).unwrap() {
+                LifecycleEvent::Expiring{table, entries} => {
+                    assert_eq!(table, "ExpiringWithin");
+                    break entries
+                },
+                _ => continue,
+            };
+        };
+        assert_eq!(entries, expiring);
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    struct PortCodec;
+
+    impl CustomCodec for PortCodec {
+        fn validate(&self, bytes: &[u8]) -> Result<(), String> {
+            match bytes.len() {
+                2 => Ok(()),
+                n => Err(format!("expected 2 bytes, found {}", n)),
+            }
+        }
+
+        fn display(&self, bytes: &[u8]) -> String {
+            u16::from_be_bytes([bytes[0], bytes[1]]).to_string()
+        }
+
+        fn parse(&self, value: &str) -> Result<Vec<u8>, String> {
+            value.parse::<u16>().map(|p| p.to_be_bytes().to_vec()).map_err(|e| e.to_string())
+        }
+    }
+
+    #[test]
+    fn custom_fields() {
+        register_codec("port", Arc::new(PortCodec));
+        let port = |p: u16| Field::custom("port", p.to_be_bytes().to_vec()).unwrap();
+        assert_eq!(port(8080).to_string(), "port:8080");
+        assert_eq!(Field::parse(FieldType::Custom, "port:443").unwrap(), port(443));
+        assert!(Field::parse(FieldType::Custom, "port:http").is_err());
+        assert!(Field::parse(FieldType::Custom, "unregistered:1").is_err());
+        assert!(matches!(Field::custom("port", vec![1]), Err(DatabaseError::InvalidFormat(_))));
+        assert_eq!(port(80).compare(&port(443)), Some(std::cmp::Ordering::Less));
+        let unregistered = Field::Custom{tag: "unregistered".to_string(), bytes: vec![0xab, 0x01]};
+        assert_eq!(unregistered.to_string(), "unregistered:ab01");
+
+        let path = temp_dir().join("CustomFields.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        for (name, columnar) in [("CustomFieldsRow", false), ("CustomFieldsColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Port".to_string(), structs::FieldType::Custom).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+
+            let entry = |key: &str, value: Field| structs::Entry::new()
+                .set_primary_field(Field::String(key.to_string())).unwrap()
+                .add_field("Port".to_string(), value).unwrap()
+                .build().unwrap();
+            c.insert(name.to_string(), entry("web", port(443))).unwrap();
+            c.insert(name.to_string(), entry("ssh", port(22))).unwrap();
+            let invalid = Field::Custom{tag: "port".to_string(), bytes: vec![1, 2, 3]};
+            assert!(matches!(c.insert(name.to_string(), entry("bad", invalid)), Err(DatabaseError::InvalidFormat(_))));
+            assert!(matches!(c.insert(name.to_string(), entry("bad", unregistered.clone())), Err(DatabaseError::InvalidFormat(_))));
+            assert!(matches!(c.insert(name.to_string(), entry("bad", Field::U32(1))), Err(DatabaseError::MismatchedFieldType)));
+        };
+        c.save().unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        for name in ["CustomFieldsRow", "CustomFieldsColumnar"] {
+            let web = c.get(name.to_string(), Field::String("web".to_string())).unwrap();
+            assert_eq!(web.get_field("Port".to_string()), Some(port(443)));
+            let json = serde_json::to_string(&web.flat("id")).unwrap();
+            assert_eq!(json, r#"{"id":"web","Port":"port:443"}"#);
+            let ssh = c.query(name.to_string(), HashMap::from([("Port".to_string(), port(22))])).unwrap();
+            assert_eq!(ssh.len(), 1);
+        };
+        let report = Client::check_compat(&path).unwrap();
+        assert!(report.features().contains(&SchemaFeature::CustomFields));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn wide_numeric_fields() {
+        let path = temp_dir().join("WideNumericFields.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let d = |v: &str| Field::Decimal(v.parse().unwrap());
+        for (name, columnar) in [("LedgerRow", false), ("LedgerColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::I128).unwrap()
+                .add_field("Balance".to_string(), structs::FieldType::Decimal).unwrap()
+                .add_field("Total".to_string(), structs::FieldType::U128).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+
+            for (account, balance, total) in [(i128::MIN, "-10.5", 1u128), (1, "0.25", u128::MAX), (2, "100", 1 << 100)] {
+                let entry = structs::Entry::new()
+                    .set_primary_field(Field::I128(account)).unwrap()
+                    .add_field("Balance".to_string(), d(balance)).unwrap()
+                    .add_field("Total".to_string(), Field::U128(total)).unwrap()
+                    .build().unwrap();
+                c.insert(name.to_string(), entry).unwrap();
+            };
+        };
+        c.save().unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        for name in ["LedgerRow", "LedgerColumnar"] {
+            let entry = c.get(name.to_string(), Field::I128(i128::MIN)).unwrap();
+            assert_eq!(entry.get_field("Balance".to_string()), Some(d("-10.50")));
+
+            let positive = c.query_where(name.to_string(), HashMap::from([("Balance".to_string(), Condition::GreaterThan(d("0")))])).unwrap();
+            let mut accounts: Vec<Field> = positive.iter().map(|e| e.primary_field.clone()).collect();
+            accounts.sort();
+            assert_eq!(accounts, vec![Field::I128(1), Field::I128(2)]);
+
+            let large = c.query_where(name.to_string(), HashMap::from([("Total".to_string(), Condition::GreaterThan(Field::U128(u64::MAX as u128)))])).unwrap();
+            assert_eq!(large.len(), 2);
+            let exact = c.query(name.to_string(), HashMap::from([("Balance".to_string(), d("0.250"))])).unwrap();
+            assert_eq!(exact.len(), 1);
+        };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn in_subnet_queries() {
+        let path = temp_dir().join("InSubnetQueries.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let ip = |v: &str| Field::IpAddr(v.parse().unwrap());
+        let cidr = |v: &str| Field::Cidr(v.parse().unwrap());
+        let subnet = |v: &str| Condition::InSubnet(v.parse().unwrap());
+        for (name, columnar) in [("RequestsRow", false), ("RequestsColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Address".to_string(), structs::FieldType::IpAddr).unwrap()
+                .add_field("Requests".to_string(), structs::FieldType::U64).unwrap()
+                .add_optional_field("Network".to_string(), structs::FieldType::Cidr).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+
+            for (client, address, requests, network) in [
+                ("a", "10.0.0.1", 5, Some("10.0.0.0/24")),
+                ("b", "10.0.7.9", 50, Some("10.0.7.0/24")),
+                ("c", "10.1.0.1", 70, None),
+                ("d", "2001:db8::1", 9, Some("2001:db8::/48")),
+            ] {
+                let mut entry = structs::Entry::new()
+                    .set_primary_field(Field::String(client.to_string())).unwrap()
+                    .add_field("Address".to_string(), ip(address)).unwrap()
+                    .add_field("Requests".to_string(), Field::U64(requests)).unwrap();
+                if let Some(n) = network {
+                    entry = entry.add_field("Network".to_string(), cidr(n)).unwrap();
+                };
+                c.insert(name.to_string(), entry.build().unwrap()).unwrap();
             };
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        c.save().unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        for name in ["RequestsRow", "RequestsColumnar"] {
+            let busy = c.query_where(name.to_string(), HashMap::from([
+                ("Address".to_string(), subnet("10.0.0.0/16")),
+                ("Requests".to_string(), Condition::GreaterThan(Field::U64(10))),
+            ])).unwrap();
+            assert_eq!(busy.len(), 1);
+            assert_eq!(busy[0].primary_field, Field::String("b".to_string()));
+
+            let mut private = c.query_where(name.to_string(), HashMap::from([("Address".to_string(), subnet("10.0.0.0/8"))])).unwrap();
+            private.sort_by(|a, b| a.primary_field.cmp(&b.primary_field));
+            let clients: Vec<String> = private.iter().map(|e| e.primary_field.to_string()).collect();
+            assert_eq!(clients, ["a", "b", "c"]);
+
+            let networks = c.query_where(name.to_string(), HashMap::from([("Network".to_string(), subnet("2001:db8::/32"))])).unwrap();
+            assert_eq!(networks.len(), 1);
+            let entry = c.get(name.to_string(), Field::String("a".to_string())).unwrap();
+            assert_eq!(entry.get_field("Network".to_string()), Some(cidr("10.0.0.0/24")));
+        };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
-    /// Query for entries within a specified table meeting the supplied criteria.
-    /// ```
-    /// # use persistent_keystore_rs::{Client, Table, FieldType, Entry};
-    /// use persistent_keystore_rs::Field;
-    /// use std::collections::HashMap;
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("query.db"), None).unwrap();
-    /// # let table = Table::new()
-    /// #    .name(String::from("MyTable"))
-    /// #    .primary_field(FieldType::String).unwrap()
-    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
-    /// #    .add_field(String::from("OptionalField"), FieldType::String).unwrap()
-    /// #    .add_expiration(Duration::from_secs(2592000))
-    /// #    .build().unwrap();
-    /// # c.create_table(table).unwrap();
-    /// # let entry = Entry::new()
-    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry.clone()).unwrap();
-    /// # let entry2 = Entry::new()
-    /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry2.clone()).unwrap();
-    /// # let entry3 = Entry::new()
-    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
-    /// #    .add_field("OptionalField".to_string(), Field::String("MyOtherTestingField".to_string())).unwrap()
-    /// #    .build().unwrap();
-    /// # c.insert("MyTable".to_string(), entry3.clone()).unwrap();
-    /// let mut criteria: HashMap<String, Field> = HashMap::new();
-    /// criteria.insert("OptionalField".to_string(), Field::String("MyTestingField".to_string()));
-    /// let results = c.query("MyTable".to_string(), criteria).unwrap();
-    /// # assert_eq!(results.len(), 2);
-    /// # std::fs::remove_file("query.db").unwrap();
-    /// ```
-    fn query(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Entry>, DatabaseError> {
-        trace!("Querying table {}", table);
-        if let Ok(mut database) = self.database.lock() {
-            match database.get_table(&table) {
-                Ok(t) => {
-                    let items = t.scan()?;
-                    let mut results = Vec::new();
-                    'L:
-                    for i in items {
-                        for (k, v) in &criteria {
-                            match &i.fields.get_key_value(k) {
-                                Some((_, value)) => {
-                                    if v != *value {
-                                        trace!("{} does not meet criteria", i.primary_field);
-                                        continue 'L;
-                                    }
-                                },
-                                None => {
-                                    trace!("{} does not meet criteria", i.primary_field);
-                                    continue 'L;
-                                },
-                            };
-                            
-                        };
-                        results.push(i.clone());
-                    };
-                    return Ok(results)
-                },
-                Err(_) => {
-                    error!("Table {} does not exist", table);
-                    return Err(DatabaseError::TableDoesNotExist(table))
-                },
+
+    #[test]
+    fn within_radius_queries() {
+        let path = temp_dir().join("WithinRadiusQueries.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let point = |v: &str| v.parse::<GeoPoint>().unwrap();
+        for (name, columnar) in [("AssetsRow", false), ("AssetsColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Location".to_string(), structs::FieldType::GeoPoint).unwrap()
+                .add_field("Kind".to_string(), structs::FieldType::String).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+
+            for (asset, location, kind) in [
+                ("truck1", "52.5200,13.4050", "truck"),
+                ("truck2", "52.5300,13.3800", "truck"),
+                ("van1", "52.5100,13.4200", "van"),
+                ("truck3", "48.1351,11.5820", "truck"),
+            ] {
+                let entry = structs::Entry::new()
+                    .set_primary_field(Field::String(asset.to_string())).unwrap()
+                    .add_field("Location".to_string(), Field::GeoPoint(point(location))).unwrap()
+                    .add_field("Kind".to_string(), Field::String(kind.to_string())).unwrap()
+                    .build().unwrap();
+                c.insert(name.to_string(), entry).unwrap();
             };
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
-    }
+        c.save().unwrap();
+        drop(c);
 
-    /// Removes entries that have expired by the specified TTL field in the table.
-    /// This is done automatically before saves if a sync_interval is provided.
-    /// 
-    /// ```
-    /// use persistent_keystore_rs::{Client, Table, FieldType, Entry};
-    /// # use std::thread::sleep;
-    /// use persistent_keystore_rs::Field;
-    /// use std::collections::HashMap;
-    /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use std::time::SystemTime;
-    /// let mut c = Client::new(Path::new("prune.db"), None).unwrap();
-    /// let table = Table::new()
-    ///     .name(String::from("MyTable"))
-    ///     .add_expiration(Duration::from_secs(1))
-    ///     .primary_field(FieldType::String).unwrap()
-    ///     .add_field("FirstKey".to_string(), FieldType::I64).unwrap()
-    ///     .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-    ///     .build().unwrap();
-    ///
-    /// c.create_table(table).unwrap();
-    ///
-    /// let entry_first = Entry::new()
-    ///     .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-    ///     .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-    ///     .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
-    ///     .build().unwrap();
-    ///
-    /// let result = c.insert("MyTable".to_string(), entry_first);
-    ///
-    /// if let Err(e) = result {
-    ///     panic!("No error expected, received {}", e)
-    /// } 
-    ///
-    /// let output = c.scan("MyTable".to_string()).unwrap();
-    ///
-    /// if output.len() != 1 {
-    ///     panic!("Expected scan results to be 1")
-    /// };
-    ///
-    /// sleep(Duration::from_secs(2));
-    /// c.prune().unwrap();
-    ///
-    /// let output = c.scan("MyTable".to_string()).unwrap();
-    /// if output.len() != 0 {
-    ///     panic!("Expected scan results to be 0")
-    /// };
-    /// # std::fs::remove_file("prune.db").unwrap();
-    /// ```
-    fn prune(&mut self) -> Result<(), DatabaseError> {
-        trace!("Pruning database");
-        if let Ok(mut database) = self.database.lock() {
-            let current_time = SystemTime::now();
-            for t in database.list_tables() {
-                if let Ok(table) = database.get_table(&t) {
-                    if let Some(expire_after) = table.expire_after {
-                        let items = table.scan()?;
-                        for item in items {
-                            if let Some(n) = item.last_timestamp {
-                                if let Ok(last_time) = current_time.duration_since(n) {
-                                    if last_time > expire_after {
-                                        debug!("Pruning item {}", item.clone());
-                                        table.delete(item.primary_field)?;
-                                    } else {
-                                        trace!("Not yet expired {}", item.clone());
-                                    }
-                                } 
-                            }
-                        }
-                    } else {
-                        debug!("No expire after setting for table {}", t);
-                    }
-                }
+        let mut c = Client::open(&path).unwrap();
+        let berlin = point("52.5163,13.3777");
+        for name in ["AssetsRow", "AssetsColumnar"] {
+            let near = |c: &mut Box<dyn DatabaseClient>, meters: u64| {
+                let mut conditions = HashMap::from([("Location".to_string(), Condition::WithinRadius{center: berlin, meters})]);
+                conditions.insert("Kind".to_string(), Condition::Equals(Field::String("truck".to_string())));
+                let mut assets: Vec<String> = c.query_where(name.to_string(), conditions).unwrap()
+                    .iter().map(|e| e.primary_field.to_string()).collect();
+                assets.sort();
+                assets
             };
-            return Ok(())
+            assert_eq!(near(&mut c, 10_000), ["truck1", "truck2"]);
+            assert_eq!(near(&mut c, 1_000_000), ["truck1", "truck2", "truck3"]);
+            assert!(near(&mut c, 100).is_empty());
+
+            let plan = c.explain(name.to_string(), HashMap::from([("Location".to_string(), Condition::WithinRadius{center: berlin, meters: 100})])).unwrap();
+            assert_eq!(plan.access, AccessPath::FullScan);
         };
-        error!("Unable to get database lock");
-        Err(DatabaseError::UnableToGetLock)
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env::temp_dir;
 
-    fn create_client_table(name: String) -> (Box<dyn DatabaseClient>, TableBuilder) {
-        let mut temp_dir_path = temp_dir();
-        temp_dir_path.push(format!("{}.db", name));
-        if temp_dir_path.exists() {
-            std::fs::remove_file(temp_dir_path.clone().to_str().unwrap()).unwrap();
+    #[test]
+    fn enum_fields() {
+        let path = temp_dir().join("EnumFields.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
         };
+        let mut c = Client::new(&path, None).unwrap();
+        let status = structs::FieldType::Enum(vec!["pending".to_string(), "shipped".to_string(), "delivered".to_string()]);
+        let entry = |key: &str, status: &str| structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Status".to_string(), Field::Enum(status.to_string())).unwrap()
+            .build().unwrap();
+        for (name, columnar) in [("OrdersRow", false), ("OrdersColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Status".to_string(), status.clone()).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
 
-        let c = Client::new(temp_dir_path, None).unwrap();
-        
-        let table = structs::Table::new()
-            .name(name);
+            c.insert(name.to_string(), entry("o1", "pending")).unwrap();
+            c.insert(name.to_string(), entry("o2", "shipped")).unwrap();
+            c.insert(name.to_string(), entry("o3", "shipped")).unwrap();
+            match c.insert(name.to_string(), entry("o4", "shiped")) {
+                Err(DatabaseError::InvalidFormat(e)) => assert_eq!(e, "Enum shiped is not one of pending, shipped, delivered"),
+                _ => panic!("expected InvalidFormat"),
+            };
+            let string = structs::Entry::new()
+                .set_primary_field(Field::String("o4".to_string())).unwrap()
+                .add_field("Status".to_string(), Field::String("shipped".to_string())).unwrap()
+                .build().unwrap();
+            assert!(matches!(c.insert(name.to_string(), string), Err(DatabaseError::MismatchedFieldType)));
+            c.update(name.to_string(), entry("o1", "delivered")).unwrap();
+            assert!(c.update(name.to_string(), entry("o2", "lost")).is_err());
+        };
+        c.save().unwrap();
+        drop(c);
 
-        (c, table)
+        let mut c = Client::open(&path).unwrap();
+        for name in ["OrdersRow", "OrdersColumnar"] {
+            let shipped = c.query(name.to_string(), HashMap::from([("Status".to_string(), Field::Enum("shipped".to_string()))])).unwrap();
+            assert_eq!(shipped.len(), 2);
+            let o1 = c.get(name.to_string(), Field::String("o1".to_string())).unwrap();
+            assert_eq!(o1.get_field("Status".to_string()), Some(Field::Enum("delivered".to_string())));
+            assert_eq!(serde_json::to_string(&o1.flat("id")).unwrap(), r#"{"id":"o1","Status":"delivered"}"#);
+        };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_missing_fields() {
-        let missing_fields_entry = structs::Entry::new()
-            .set_primary_field(Field::String("This entry should fail".to_string())).unwrap()
-            .build();
-        
-        if let Err(e) = missing_fields_entry {
-            match e {
-                DatabaseError::EntryMustContainFields => {},
-                _ => panic!("Expected EntryMustContainFields but not {}", e),
+    fn null_fields() {
+        let path = temp_dir().join("NullFields.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let entry = |key: &str, notes: Option<&str>| {
+            let mut entry = structs::Entry::new()
+                .set_primary_field(Field::String(key.to_string())).unwrap()
+                .add_field("Count".to_string(), Field::I64(1)).unwrap();
+            if let Some(notes) = notes {
+                entry = entry.add_field("Notes".to_string(), Field::String(notes.to_string())).unwrap();
             };
-        } else {
-            panic!("Expected error and did not receive one");
+            entry.build().unwrap()
+        };
+        let cleared = || HashMap::from([("Notes".to_string(), Field::Null)]);
+        for (name, columnar) in [("NotesRow", false), ("NotesColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                .add_optional_field("Notes".to_string(), structs::FieldType::String).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+
+            for (key, notes) in [("a", Some("first")), ("b", None), ("c", Some("third")), ("d", Some("fourth"))] {
+                c.insert(name.to_string(), entry(key, notes)).unwrap();
+            };
+            let patched = c.patch(name.to_string(), Field::String("a".to_string()), cleared()).unwrap();
+            assert_eq!(patched.get_field("Notes".to_string()), Some(Field::Null));
+            assert_eq!(patched.get_field("Count".to_string()), Some(Field::I64(1)));
+            c.patch(name.to_string(), Field::String("c".to_string()), cleared()).unwrap();
+            assert_eq!(c.get(name.to_string(), Field::String("b".to_string())).unwrap().get_field("Notes".to_string()), None);
+
+            let required = HashMap::from([("Count".to_string(), Field::Null)]);
+            match c.patch(name.to_string(), Field::String("d".to_string()), required) {
+                Err(DatabaseError::MissingRequiredField(f)) => assert_eq!(f, "Count"),
+                _ => panic!("expected MissingRequiredField"),
+            };
+            assert!(matches!(c.patch(name.to_string(), Field::String("z".to_string()), cleared()), Err(DatabaseError::EntryDoesNotExists)));
+
+            // Moving the last row into the place of a deleted one keeps its null
+            c.delete(name.to_string(), Field::String("a".to_string())).unwrap();
+        };
+        c.save().unwrap();
+        drop(c);
+
+        let mut c = Client::open(&path).unwrap();
+        for name in ["NotesRow", "NotesColumnar"] {
+            let nulls = c.query(name.to_string(), cleared()).unwrap();
+            assert_eq!(nulls.len(), 1);
+            assert_eq!(nulls[0].primary_field, Field::String("c".to_string()));
+            assert_eq!(c.get(name.to_string(), Field::String("b".to_string())).unwrap().get_field("Notes".to_string()), None);
+            let d = c.get(name.to_string(), Field::String("d".to_string())).unwrap();
+            assert_eq!(d.get_field("Notes".to_string()), Some(Field::String("fourth".to_string())));
+            assert_eq!(serde_json::to_string(&c.get(name.to_string(), Field::String("c".to_string())).unwrap().flat("id")).unwrap(), r#"{"id":"c","Count":1,"Notes":null}"#);
         };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_mismatched_primary_field_type() {
-        let (mut c, table_builder) = create_client_table("MismatchedPrimaryFieldType".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+    fn unset_fields() {
+        let path = temp_dir().join("UnsetFields.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let entry = |key: &str| structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Count".to_string(), Field::I64(1)).unwrap()
+            .add_field("Notes".to_string(), Field::String("draft".to_string())).unwrap()
+            .add_field("Owner".to_string(), Field::String("ops".to_string())).unwrap()
             .build().unwrap();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        for (name, columnar) in [("UnsetRow", false), ("UnsetColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                .add_optional_field("Notes".to_string(), structs::FieldType::String).unwrap()
+                .add_optional_field("Owner".to_string(), structs::FieldType::String).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            c.insert(name.to_string(), entry("a")).unwrap();
+            c.insert(name.to_string(), entry("b")).unwrap();
 
-        c.create_table(table).unwrap();
+            let unset = c.unset_fields(name.to_string(), Field::String("a".to_string()), &names(&["Notes", "Owner"])).unwrap();
+            assert_eq!(unset.get_field("Notes".to_string()), None);
+            assert_eq!(unset.get_field("Owner".to_string()), None);
+            assert_eq!(unset.get_field("Count".to_string()), Some(Field::I64(1)));
 
-        let mismatched_primary_field_type = structs::Entry::new()
-            .set_primary_field(Field::I64(123123)).unwrap()
-            .add_field("FirstKey".to_string(), Field::String("testing".to_string())).unwrap()
-            .build().unwrap();
+            match c.unset_fields(name.to_string(), Field::String("b".to_string()), &names(&["Notes", "Count"])) {
+                Err(DatabaseError::MissingRequiredField(f)) => assert_eq!(f, "Count"),
+                _ => panic!("expected MissingRequiredField"),
+            };
+            match c.unset_fields(name.to_string(), Field::String("b".to_string()), &names(&["Notes", "Colour"])) {
+                Err(DatabaseError::UnsupportedField(f)) => assert_eq!(f, "Colour"),
+                _ => panic!("expected UnsupportedField"),
+            };
+            assert!(matches!(c.unset_fields(name.to_string(), Field::String("z".to_string()), &names(&["Notes"])), Err(DatabaseError::EntryDoesNotExists)));
+            let b = c.get(name.to_string(), Field::String("b".to_string())).unwrap();
+            assert_eq!(b.get_field("Notes".to_string()), Some(Field::String("draft".to_string())));
+        };
+        c.save().unwrap();
+        drop(c);
 
-        let result = c.insert("MismatchedPrimaryFieldType".to_string(), mismatched_primary_field_type);
+        let mut c = Client::open(&path).unwrap();
+        for name in ["UnsetRow", "UnsetColumnar"] {
+            let a = c.get(name.to_string(), Field::String("a".to_string())).unwrap();
+            assert_eq!(a.fields.len(), 1);
+            assert_eq!(c.get(name.to_string(), Field::String("b".to_string())).unwrap().fields.len(), 3);
+        };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        if let Err(e) = result {
-            match e {
-                DatabaseError::MismatchedFieldType => {},
-                _ => panic!("Expected MismatchedField type, not {}", e),
+    #[test]
+    fn query_filter_trees() {
+        let path = temp_dir().join("QueryFilter.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let users = [("u1", "active", "gold"), ("u2", "active", "bronze"), ("u3", "closed", "silver"), ("u4", "active", "silver"), ("u5", "closed", "gold")];
+        let filter = Filter::equals("Status", Field::String("active".to_string()))
+            .and(Filter::equals("Tier", Field::String("gold".to_string()))
+                .or(Filter::equals("Tier", Field::String("silver".to_string()))));
+        for (name, columnar, indexed) in [("UsersRow", false, false), ("UsersIndexed", false, true), ("UsersColumnar", true, false)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Status".to_string(), structs::FieldType::String).unwrap()
+                .add_field("Tier".to_string(), structs::FieldType::String).unwrap();
+            if indexed {
+                table = table.add_index(vec!["Status".to_string()]).unwrap();
             };
-        } else {
-            panic!("Expected MismatchedFieldType error but received none");
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            for (key, status, tier) in users {
+                let entry = structs::Entry::new()
+                    .set_primary_field(Field::String(key.to_string())).unwrap()
+                    .add_field("Status".to_string(), Field::String(status.to_string())).unwrap()
+                    .add_field("Tier".to_string(), Field::String(tier.to_string())).unwrap()
+                    .build().unwrap();
+                c.insert(name.to_string(), entry).unwrap();
+            };
+
+            let keys = |results: Vec<Arc<Entry>>| {
+                let mut keys: Vec<String> = results.iter().map(|e| e.primary_field.to_string()).collect();
+                keys.sort();
+                keys
+            };
+            assert_eq!(keys(c.query_filter(name.to_string(), filter.clone()).unwrap()), ["u1", "u4"]);
+            assert_eq!(keys(c.query_filter(name.to_string(), !filter.clone()).unwrap()), ["u2", "u3", "u5"]);
+            let gold_or_closed = Filter::equals("Tier", Field::String("gold".to_string()))
+                .or(Filter::equals("Status", Field::String("closed".to_string())));
+            assert_eq!(keys(c.query_filter(name.to_string(), gold_or_closed).unwrap()), ["u1", "u3", "u5"]);
+            let conditions = HashMap::from([("Status".to_string(), Condition::Equals(Field::String("closed".to_string())))]);
+            assert_eq!(keys(c.query_filter(name.to_string(), Filter::from(conditions.clone())).unwrap()), keys(c.query_where(name.to_string(), conditions).unwrap()));
         };
+        assert!(matches!(c.query_filter("Missing".to_string(), filter), Err(DatabaseError::TableDoesNotExist(_))));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_mismatched_secondary_field_type() {
-        let (mut c, table_builder) = create_client_table("MismatchedSecondaryFieldType".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
+    fn primary_field_criteria() {
+        let path = temp_dir().join("PrimaryFieldCriteria.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let paths = ["/etc/hosts", "/usr/bin/ls", "/usr/bin/cat", "/usr/lib/libc.so", "/var/log/syslog"];
+        let key = |k: &str| Field::String(k.to_string());
+        let keys = |results: Vec<Arc<Entry>>| {
+            let mut keys: Vec<String> = results.iter().map(|e| e.primary_field.to_string()).collect();
+            keys.sort();
+            keys
+        };
+        for (name, prefix_index, columnar) in [("PathsRow", false, false), ("PathsPrefix", true, false), ("PathsColumnar", false, true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Size".to_string(), structs::FieldType::I64).unwrap();
+            if prefix_index {
+                table = table.prefix_index();
+            };
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            for (i, p) in paths.iter().enumerate() {
+                let entry = structs::Entry::new()
+                    .set_primary_field(key(p)).unwrap()
+                    .add_field("Size".to_string(), Field::I64(i as i64 * 10)).unwrap()
+                    .build().unwrap();
+                c.insert(name.to_string(), entry).unwrap();
+            };
 
-        c.create_table(table).unwrap();
+            let criteria = HashMap::from([(PRIMARY_FIELD_KEY.to_string(), key("/usr/bin/ls"))]);
+            assert_eq!(keys(c.query(name.to_string(), criteria.clone()).unwrap()), ["/usr/bin/ls"]);
+            let plan = c.explain(name.to_string(), equals_all(criteria)).unwrap();
+            assert_eq!(plan.access, AccessPath::PrimaryKey);
+            assert_eq!(plan.estimated_rows_scanned, 1);
+            assert!(plan.residual.is_empty());
 
-        let mismatched_optional_field = structs::Entry::new()
-            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::String("Should fail".to_string())).unwrap()
-            .build().unwrap();
+            let usr = HashMap::from([
+                (PRIMARY_FIELD_KEY.to_string(), Condition::StartsWith("/usr/".to_string())),
+                ("Size".to_string(), Condition::GreaterThan(Field::I64(10))),
+            ]);
+            assert_eq!(keys(c.query_where(name.to_string(), usr.clone()).unwrap()), ["/usr/bin/cat", "/usr/lib/libc.so"]);
+            let plan = c.explain(name.to_string(), usr).unwrap();
+            match prefix_index {
+                true => {
+                    assert_eq!(plan.access, AccessPath::PrimaryKeyPrefix("/usr/".to_string()));
+                    assert_eq!(plan.estimated_rows_scanned, 3);
+                    assert_eq!(plan.residual.len(), 1);
+                },
+                false => assert_eq!(plan.access, AccessPath::FullScan),
+            };
 
-        let result = c.insert("MismatchedSecondaryFieldType".to_string(), mismatched_optional_field);
+            let range = Filter::field(PRIMARY_FIELD_KEY, Condition::GreaterThan(key("/usr/")))
+                .and(Filter::field(PRIMARY_FIELD_KEY, Condition::LessThan(key("/usr/bin/m"))));
+            assert_eq!(keys(c.query_filter(name.to_string(), range).unwrap()), ["/usr/bin/cat", "/usr/bin/ls"]);
 
-        if let Err(e) = result {
-            match e {
-                DatabaseError::MismatchedFieldType => {},
-                _ => panic!("Expected MismatchedField type, not {}", e),
-            };
-        } else {
-            panic!("Expected MismatchedFieldType error but received none");
+            let var = HashMap::from([(PRIMARY_FIELD_KEY.to_string(), key("/var/log/syslog"))]);
+            assert_eq!(c.delete_many(name.to_string(), var).unwrap(), 1);
+            let missing = HashMap::from([(PRIMARY_FIELD_KEY.to_string(), key("/missing"))]);
+            assert_eq!(c.delete_many(name.to_string(), missing).unwrap(), 0);
+            assert_eq!(c.query(name.to_string(), HashMap::new()).unwrap().len(), paths.len() - 1);
         };
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_extra_undefined_field() {
-        let (mut c, table_builder) = create_client_table("EntryExtraUndefiniedField".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
+    fn delete_many_limit_batches() {
+        let path = temp_dir().join("DeleteManyLimit.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let stale = || HashMap::from([("Status".to_string(), Field::String("stale".to_string()))]);
+        for (name, columnar) in [("CleanupRow", false), ("CleanupColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Status".to_string(), structs::FieldType::String).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            for i in 0..25 {
+                let status = if i % 5 == 0 { "live" } else { "stale" };
+                let entry = structs::Entry::new()
+                    .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+                    .add_field("Status".to_string(), Field::String(status.to_string())).unwrap()
+                    .build().unwrap();
+                c.insert(name.to_string(), entry).unwrap();
+            };
 
-        c.create_table(table).unwrap();
+            assert_eq!(c.delete_many_limit(name.to_string(), stale(), 0).unwrap(), DeleteBatch{deleted: 0, more: true});
+            assert_eq!(c.delete_many_limit(name.to_string(), stale(), 8).unwrap(), DeleteBatch{deleted: 8, more: true});
+            assert_eq!(c.delete_many_limit(name.to_string(), stale(), 8).unwrap(), DeleteBatch{deleted: 8, more: true});
+            assert_eq!(c.delete_many_limit(name.to_string(), stale(), 4).unwrap(), DeleteBatch{deleted: 4, more: false});
+            assert_eq!(c.delete_many_limit(name.to_string(), stale(), 4).unwrap(), DeleteBatch{deleted: 0, more: false});
+            assert_eq!(c.query(name.to_string(), HashMap::new()).unwrap().len(), 5);
+            assert!(c.query(name.to_string(), stale()).unwrap().is_empty());
+        };
+        assert!(matches!(c.delete_many_limit("Missing".to_string(), stale(), 1), Err(DatabaseError::TableDoesNotExist(_))));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        let extra_field = structs::Entry::new()
-            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
-            .add_field("ExtraField".to_string(), Field::String("ExtraField".to_string())).unwrap()
-            .build().unwrap();
+    #[test]
+    fn update_many_entries() {
+        let path = temp_dir().join("UpdateMany.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::new(&path, None).unwrap();
+        let status = |s: &str| HashMap::from([("Status".to_string(), Field::String(s.to_string()))]);
+        for (name, columnar) in [("JobsRow", false), ("JobsColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Status".to_string(), structs::FieldType::String).unwrap()
+                .add_field("Retries".to_string(), structs::FieldType::I64).unwrap()
+                .add_optional_field("Error".to_string(), structs::FieldType::String).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            for i in 0..10 {
+                let mut entry = structs::Entry::new()
+                    .set_primary_field(Field::String(format!("Job{}", i))).unwrap()
+                    .add_field("Retries".to_string(), Field::I64(i % 3)).unwrap();
+                entry = match i % 2 {
+                    0 => entry.add_field("Status".to_string(), Field::String("failed".to_string())).unwrap()
+                        .add_field("Error".to_string(), Field::String("timeout".to_string())).unwrap(),
+                    _ => entry.add_field("Status".to_string(), Field::String("done".to_string())).unwrap(),
+                };
+                c.insert(name.to_string(), entry.build().unwrap()).unwrap();
+            };
 
-        let result = c.insert("EntryExtraUndefiniedField".to_string(), extra_field);
+            let requeue = &mut |entry: &mut Entry| {
+                let retries = entry.get_i64("Retries").unwrap();
+                entry.fields.insert("Retries".to_string(), Field::I64(retries + 1));
+                entry.fields.insert("Status".to_string(), Field::String("queued".to_string()));
+                entry.fields.remove("Error");
+            };
+            assert_eq!(c.update_many(name.to_string(), status("failed"), requeue).unwrap(), 5);
+            assert!(c.query(name.to_string(), status("failed")).unwrap().is_empty());
+            let queued = c.query(name.to_string(), status("queued")).unwrap();
+            assert_eq!(queued.len(), 5);
+            for entry in &queued {
+                assert_eq!(entry.get_field("Error".to_string()), None);
+                assert!(entry.get_i64("Retries").unwrap() >= 1);
+            };
+            assert_eq!(c.get(name.to_string(), Field::String("Job4".to_string())).unwrap().get_i64("Retries"), Some(2));
 
-        if let Err(e) = result {
-            match e {
-                DatabaseError::UnsupportedField(_) => {},
-                _ => panic!("Expected UnsupportedField, got {}", e),
+            // Unchanged entries are not written
+            assert_eq!(c.update_many(name.to_string(), status("done"), &mut |_| {}).unwrap(), 0);
+
+            // An invalid entry leaves every entry unchanged
+            let mut n = 0;
+            let invalid = &mut |entry: &mut Entry| {
+                n += 1;
+                let value = if n == 3 { Field::I64(0) } else { Field::String("cancelled".to_string()) };
+                entry.fields.insert("Status".to_string(), value);
             };
-        } else {
-            panic!("Expected UnsupportedField, got none")
-        }
+            assert!(matches!(c.update_many(name.to_string(), status("queued"), invalid), Err(DatabaseError::MismatchedFieldType)));
+            assert_eq!(c.query(name.to_string(), status("queued")).unwrap().len(), 5);
+
+            let rekey = &mut |entry: &mut Entry| entry.primary_field = Field::String("Other".to_string());
+            assert!(matches!(c.update_many(name.to_string(), status("done"), rekey), Err(DatabaseError::InvalidPrimaryKey)));
+        };
+        assert!(matches!(c.update_many("Missing".to_string(), HashMap::new(), &mut |_| {}), Err(DatabaseError::TableDoesNotExist(_))));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_table_does_not_exist() {
-        let (mut c, table_builder) = create_client_table("EntryTableDoesNotExist".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
-
-        c.create_table(table).unwrap();
-
-        let extra_field = structs::Entry::new()
-            .set_primary_field(Field::String("This should also fail".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
-            .build().unwrap();
+    fn modify_entries() {
+        let path = temp_dir().join("ModifyEntries.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let clock = Arc::new(ManualClock::new(std::time::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let mut c = Client::builder(&path).clock(clock.clone()).build().unwrap();
+        let key = || Field::String("Counter".to_string());
+        for (name, columnar) in [("CountersRow", false), ("CountersColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap()
+                .add_expiration(Duration::from_secs(60));
+            if columnar {
+                table = table.columnar();
+            };
+            c.create_table(table.build().unwrap()).unwrap();
+            let entry = structs::Entry::new()
+                .set_primary_field(key()).unwrap()
+                .add_field("Count".to_string(), Field::I64(0)).unwrap()
+                .build().unwrap();
+            c.insert(name.to_string(), entry).unwrap();
 
-        let result = c.insert("EntryTableDoesNotExist2".to_string(), extra_field);
+            let increment = &mut |entry: &mut Entry| {
+                let count = entry.get_i64("Count").unwrap();
+                entry.fields.insert("Count".to_string(), Field::I64(count + 1));
+            };
+            for _ in 0..3 {
+                clock.advance(Duration::from_secs(30));
+                c.modify(name.to_string(), key(), increment).unwrap();
+            };
+            let entry = c.get(name.to_string(), key()).unwrap();
+            assert_eq!(entry.get_i64("Count"), Some(3));
+            assert_eq!(entry.last_timestamp, Some(clock.now()));
 
-        if let Err(e) = result {
-            match e {
-                DatabaseError::TableDoesNotExist(_) =>  {},
-                _ => panic!("Expected TableDoesNotExist, got {}", e),
+            let invalid = &mut |entry: &mut Entry| {
+                entry.fields.insert("Count".to_string(), Field::String("many".to_string()));
             };
-        } else {
-            panic!("Expected TableDoesNotExist, got none")
-        }
+            assert!(matches!(c.modify(name.to_string(), key(), invalid), Err(DatabaseError::MismatchedFieldType)));
+            let rekey = &mut |entry: &mut Entry| entry.primary_field = Field::String("Other".to_string());
+            assert!(matches!(c.modify(name.to_string(), key(), rekey), Err(DatabaseError::InvalidPrimaryKey)));
+            assert_eq!(c.get(name.to_string(), key()).unwrap().get_i64("Count"), Some(3));
+            assert!(matches!(c.modify(name.to_string(), Field::String("Missing".to_string()), increment), Err(DatabaseError::EntryDoesNotExists)));
+
+            clock.advance(Duration::from_secs(120));
+            assert!(matches!(c.modify(name.to_string(), key(), increment), Err(DatabaseError::EntryDoesNotExists)));
+        };
+        assert!(matches!(c.modify("Missing".to_string(), key(), &mut |_| {}), Err(DatabaseError::TableDoesNotExist(_))));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
+
     #[test]
-    fn entry_success_without_optional() {
-        let (mut c, table_builder) = create_client_table("EntrySuccessWithoutOptional".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
+    fn table_timestamps() {
+        let path = temp_dir().join("TableTimestamps.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let clock = Arc::new(ManualClock::new(std::time::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let mut c = Client::builder(&path).clock(clock.clone()).build().unwrap();
+        let key = || Field::String("Entry1".to_string());
+        for (name, columnar) in [("TimestampsRow", false), ("TimestampsColumnar", true)] {
+            let mut table = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Count".to_string(), structs::FieldType::I64).unwrap();
+            if columnar {
+                table = table.columnar();
+            };
+            let created = clock.now();
+            c.create_table(table.build().unwrap()).unwrap();
+            let stats = &c.stats().unwrap().tables[name];
+            assert_eq!(stats.created_at, Some(created));
+            assert_eq!(stats.last_modified_at, None);
+            let described = c.get(TABLES_TABLE.to_string(), Field::String(name.to_string())).unwrap();
+            assert_eq!(described.fields.get("CreatedAt"), Some(&Field::Date(created)));
+            assert_eq!(described.fields.get("LastModifiedAt"), None);
 
-        c.create_table(table).unwrap();
+            clock.advance(Duration::from_secs(60));
+            let entry = structs::Entry::new()
+                .set_primary_field(key()).unwrap()
+                .add_field("Count".to_string(), Field::I64(1)).unwrap()
+                .build().unwrap();
+            c.insert(name.to_string(), entry.clone()).unwrap();
+            assert_eq!(c.stats().unwrap().tables[name].last_modified_at, Some(clock.now()));
 
-        let extra_field = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
+            clock.advance(Duration::from_secs(60));
+            c.update(name.to_string(), entry).unwrap();
+            assert_eq!(c.stats().unwrap().tables[name].last_modified_at, Some(clock.now()));
+
+            clock.advance(Duration::from_secs(60));
+            let _ = c.get(name.to_string(), key()).unwrap();
+            assert_eq!(c.stats().unwrap().tables[name].last_modified_at, Some(clock.now() - Duration::from_secs(60)));
+
+            c.delete(name.to_string(), key()).unwrap();
+            let stats = &c.stats().unwrap().tables[name];
+            assert_eq!(stats.created_at, Some(created));
+            assert_eq!(stats.last_modified_at, Some(clock.now()));
+            let described = c.get(TABLES_TABLE.to_string(), Field::String(name.to_string())).unwrap();
+            assert_eq!(described.fields.get("LastModifiedAt"), Some(&Field::Date(clock.now())));
+        };
+
+        let expiring = || structs::Table::new()
+            .name("TimestampsExpiring".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .max_entries(1, CapacityPolicy::EvictOldest)
             .build().unwrap();
+        c.create_table(expiring()).unwrap();
+        c.insert("TimestampsExpiring".to_string(), capacity_entry("Evicted", "a")).unwrap();
+        clock.advance(Duration::from_secs(30));
+        c.insert("TimestampsExpiring".to_string(), capacity_entry("Expired", "b")).unwrap();
+        let stats = &c.stats().unwrap().tables["TimestampsExpiring"];
+        assert_eq!(stats.evicted, 1);
+        assert_eq!(stats.last_modified_at, Some(clock.now()));
+        clock.advance(Duration::from_secs(61));
+        c.prune().unwrap();
+        let stats = &c.stats().unwrap().tables["TimestampsExpiring"];
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.last_modified_at, Some(clock.now()));
 
-        let result = c.insert("EntrySuccessWithoutOptional".to_string(), extra_field);
+        let mut table = expiring();
+        table.insert_at(capacity_entry("Evicted", "a"), clock.timestamp()).unwrap();
+        clock.advance(Duration::from_secs(30));
+        table.insert_at(capacity_entry("Expired", "b"), clock.timestamp()).unwrap();
+        assert_eq!(table.last_modified_at, Some(clock.now()));
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(table.prune_keys(&table.keys(), clock.timestamp()), 1);
+        assert_eq!(table.last_modified_at, Some(clock.now()));
+        c.save().unwrap();
+        drop(c);
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
+        let mut c = Client::open(&path).unwrap();
+        let stats = c.stats().unwrap();
+        let created = std::time::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(stats.tables["TimestampsRow"].created_at, Some(created));
+        assert_eq!(stats.tables["TimestampsRow"].last_modified_at, Some(created + Duration::from_secs(180)));
+        assert_eq!(stats.tables["TimestampsColumnar"].created_at, Some(created + Duration::from_secs(180)));
+        assert_eq!(stats.tables["TimestampsColumnar"].last_modified_at, Some(created + Duration::from_secs(360)));
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
+
     #[test]
-    fn entry_success_with_optional() {
-        let (mut c, table_builder) = create_client_table("EntrySuccessWithOptional".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
+    fn open_baseline_file() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/baseline-0.0.8.db");
+        let path = temp_dir().join("OpenBaselineFile.db");
+        std::fs::copy(&fixture, &path).unwrap();
 
-        c.create_table(table).unwrap();
+        let report = Client::check_compat(&path).unwrap();
+        assert!(report.is_compatible(), "{:?}", report.problems);
+        assert_eq!(report.format, FileFormat::Legacy);
+        assert_eq!(report.written_by, None);
+        assert_eq!(report.tables["Sessions"], [SchemaFeature::Expiration]);
 
-        let extra_field = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second field".to_string())).unwrap()
-            .build().unwrap();
+        let mut c = Client::open(&path).unwrap();
+        let mut tables = c.list_tables().unwrap();
+        tables.sort();
+        assert_eq!(tables, ["Sessions", "Users"]);
+        let users = c.scan("Users".to_string()).unwrap();
+        assert_eq!(users.len(), 3);
+        let alice = c.get("Users".to_string(), Field::String("alice".to_string())).unwrap();
+        assert_eq!(alice.get_string("Name"), Some("ALICE"));
+        assert_eq!(alice.get_i64("Age"), Some(30));
+        assert_eq!(alice.fields["Joined"], Field::Date(std::time::UNIX_EPOCH + Duration::from_secs(1_600_000_000)));
+        assert_eq!(alice.fields["Score"], Field::I32(0));
+        assert_eq!(alice.fields["Visits"], Field::U64(0));
+        assert_eq!(alice.fields["Level"], Field::U32(0));
+        assert_eq!(alice.get_bool("Active"), Some(true));
+        assert!(alice.last_timestamp.is_some_and(|t| t < std::time::SystemTime::now()));
+        let bob = c.get("Users".to_string(), Field::String("bob".to_string())).unwrap();
+        assert!(!bob.fields.contains_key("Score"));
+        assert!(matches!(c.insert("Users".to_string(), (*bob).clone()), Err(DatabaseError::EntryExists)));
 
-        let result = c.insert("EntrySuccessWithOptional".to_string(), extra_field);
+        // The session expired a minute after the fixture was written
+        assert!(c.get("Sessions".to_string(), Field::U64(1)).is_ok());
+        c.prune().unwrap();
+        assert!(matches!(c.get("Sessions".to_string(), Field::U64(1)), Err(DatabaseError::EntryDoesNotExists)));
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
+        c.save().unwrap();
+        drop(c);
+        assert_eq!(Client::file_info(&path).unwrap().map(|i| i.format_version), Some(FORMAT_VERSION));
+        let mut c = Client::open(&path).unwrap();
+        assert_eq!(c.scan("Users".to_string()).unwrap().len(), 3);
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn entry_update_success() {
-        let (mut c, table_builder) = create_client_table("EntryUpdateSuccess".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
-            .build().unwrap();
+    fn refuse_newer_format() {
+        let path = temp_dir().join("RefuseNewerFormat.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let mut c = Client::builder(&path).build().unwrap();
+        c.save().unwrap();
+        drop(c);
+
+        let contents = std::fs::read(&path).unwrap();
+        let (info, compressed) = header::split(&contents).unwrap();
+        let mut info = info.unwrap();
+        info.format_version = FORMAT_VERSION + 1;
+        std::fs::write(&path, header::prepend(&info, compressed).unwrap()).unwrap();
+        assert!(matches!(Client::open(&path), Err(DatabaseError::InvalidFormat(_))));
+        assert!(!Client::check_compat(&path).unwrap().is_compatible());
+        std::fs::remove_file(&path).unwrap();
+    }
+
 
+    #[test]
+    fn max_memory_rejected_write_evicts_nothing() {
+        let path = temp_dir().join("MaxMemoryRejectedWrite.db");
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        };
+        let entry_size = capacity_entry("k1", &"x".repeat(100)).approx_size();
+        let mut c = Client::builder(&path)
+            .max_memory(entry_size * 3, CapacityPolicy::EvictOldest)
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Parents".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .build().unwrap();
+        c.create_table(table).unwrap();
+        let table = structs::Table::new()
+            .name("Children".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_optional_field("Parent".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Parent".to_string(), "Parents".to_string(), OnDelete::Restrict).unwrap()
+            .build().unwrap();
         c.create_table(table).unwrap();
+        for key in ["k1", "k2", "k3"] {
+            c.insert("Parents".to_string(), capacity_entry(key, &"x".repeat(100))).unwrap();
+        };
+        let keys = |c: &mut Box<dyn DatabaseClient>| {
+            let mut keys: Vec<String> = c.scan("Parents".to_string()).unwrap().iter().map(|e| e.primary_field.to_string()).collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&mut c), ["k1", "k2", "k3"]);
 
-        let entry_first = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+        assert!(matches!(c.insert("Parents".to_string(), capacity_entry("k3", &"y".repeat(100))), Err(DatabaseError::EntryExists)));
+        let invalid = structs::Entry::new()
+            .set_primary_field(Field::String("k4".to_string())).unwrap()
+            .add_field("Notes".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+        assert!(matches!(c.insert("Parents".to_string(), invalid.clone()), Err(DatabaseError::MismatchedFieldType)));
+        assert!(matches!(c.update("Parents".to_string(), invalid), Err(DatabaseError::MismatchedFieldType)));
+        let undeclared = structs::Entry::new()
+            .set_primary_field(Field::String("k4".to_string())).unwrap()
+            .add_field("Other".to_string(), Field::String("x".repeat(100))).unwrap()
+            .build().unwrap();
+        assert!(matches!(c.insert_or_update("Parents".to_string(), undeclared), Err(DatabaseError::UnsupportedField(_))));
+        let orphan = structs::Entry::new()
+            .set_primary_field(Field::String("c1".to_string())).unwrap()
+            .add_field("Notes".to_string(), Field::String("x".repeat(100))).unwrap()
+            .add_field("Parent".to_string(), Field::String("k9".to_string())).unwrap()
             .build().unwrap();
+        assert!(matches!(c.insert("Children".to_string(), orphan), Err(DatabaseError::ReferenceViolation(_))));
+        assert_eq!(keys(&mut c), ["k1", "k2", "k3"]);
+        assert_eq!(c.stats().unwrap().tables["Parents"].evicted, 0);
 
-        let result = c.insert("EntryUpdateSuccess".to_string(), entry_first);
+        c.insert("Parents".to_string(), capacity_entry("k4", &"x".repeat(100))).unwrap();
+        assert_eq!(keys(&mut c), ["k2", "k3", "k4"]);
+        drop(c);
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
 
-        let entry_second = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
+    #[test]
+    fn authorizer_guards_snapshots_and_stores() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("AuthorizerSnapshot.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let mut c = Client::builder(&temp_dir_path)
+            .authorizer(|event| Authorization::from(event.context.is_none() || !event.table.starts_with("Private")))
+            .build().unwrap();
+        let table = structs::Table::new()
+            .name("Private".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
             .build().unwrap();
+        c.create_table(table).unwrap();
+        c.insert("Private".to_string(), capacity_entry("Key", "value")).unwrap();
+        let session = c.session_store("PrivateSessions".to_string(), Duration::from_secs(60)).unwrap()
+            .create_session("alice", String::new()).unwrap();
 
-        let result = c.update("EntryUpdateSuccess".to_string(), entry_second.clone());
+        let mut guest = c.with_context("guest".to_string());
+        let snapshot = guest.snapshot().unwrap();
+        assert!(matches!(snapshot.get("Private".to_string(), Field::String("Key".to_string())), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(snapshot.scan("Private".to_string()), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(snapshot.query("Private".to_string(), HashMap::new()), Err(DatabaseError::AccessDenied(_))));
+        assert_eq!(c.snapshot().unwrap().scan("Private".to_string()).unwrap().len(), 1);
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        }
+        let mut sessions = guest.session_store("PrivateSessions".to_string(), Duration::from_secs(60)).unwrap();
+        assert!(matches!(sessions.get_session(&session.id), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(sessions.touch(&session.id), Err(DatabaseError::AccessDenied(_))));
+        assert!(matches!(guest.modify("Private".to_string(), Field::String("Key".to_string()), &mut |_| {}), Err(DatabaseError::AccessDenied(_))));
 
-        let curent_entry = c.get("EntryUpdateSuccess".to_string(), Field::String("This should Succeed".to_string())).unwrap();
-        assert!(curent_entry.primary_field==entry_second.primary_field);
-        assert!(curent_entry.fields==entry_second.fields);
+        std::fs::remove_file(&temp_dir_path).unwrap();
     }
 
+
     #[test]
-    fn entry_failure_second_insert() {
-        let (mut c, table_builder) = create_client_table("SecondInsertFailure".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+    fn merge_checks_writes() {
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+        let (mut central, central_path) = create_merge_client("CheckedCentral", clock.clone());
+        let (mut device, device_path) = create_merge_client("CheckedDevice", clock.clone());
+        for (c, max_entries) in [(&mut central, 1), (&mut device, 10)] {
+            let orders = structs::Table::new()
+                .name("Orders".to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Reading".to_string(), structs::FieldType::String).unwrap()
+                .add_reference("Reading".to_string(), "Readings".to_string(), OnDelete::Restrict).unwrap()
+                .max_entries(max_entries, CapacityPolicy::Reject)
+                .build().unwrap();
+            c.create_table(orders).unwrap();
+        };
+        let order = |key: &str| structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Reading".to_string(), Field::String("First".to_string())).unwrap()
             .build().unwrap();
 
-        c.create_table(table).unwrap();
+        // Orders are merged before the Readings they refer to
+        device.insert("Readings".to_string(), merge_entry("First", 1)).unwrap();
+        device.insert("Orders".to_string(), order("Order1")).unwrap();
+        assert!(device.acquire_lease("leader".to_string(), Duration::from_secs(60)).unwrap().is_some());
+        device.save().unwrap();
+        let report = central.merge_from(&device_path).unwrap();
+        assert_eq!(report.inserted, 2);
+        assert!(!central.list_tables().unwrap().contains(&LEASE_TABLE.to_string()));
 
-        let entry_first = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
-            .build().unwrap();
+        // Order2 exceeds the capacity of the central Orders, so nothing is merged
+        device.insert("Readings".to_string(), merge_entry("Second", 2)).unwrap();
+        device.insert("Orders".to_string(), order("Order2")).unwrap();
+        device.save().unwrap();
+        assert!(matches!(central.merge_from(&device_path), Err(DatabaseError::CapacityExceeded(_))));
+        assert!(central.get("Readings".to_string(), Field::String("Second".to_string())).is_err());
+        assert_eq!(central.scan("Orders".to_string()).unwrap().len(), 1);
 
-        let result = c.insert("SecondInsertFailure".to_string(), entry_first);
+        std::fs::remove_file(&central_path).unwrap();
+        std::fs::remove_file(&device_path).unwrap();
+        std::fs::remove_file(lease::lock_path(&device_path)).unwrap();
+    }
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
 
-        let entry_second = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
+    #[test]
+    fn prune_applies_references() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("PruneReferences.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
+
+        let clock = Arc::new(ManualClock::new(std::time::SystemTime::UNIX_EPOCH));
+        let mut c = Client::builder(&temp_dir_path)
+            .clock(clock.clone())
             .build().unwrap();
 
-        let result = c.insert("SecondInsertFailure".to_string(), entry_second);
+        let customers = structs::Table::new()
+            .name("PruneCustomers".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .add_expiration(Duration::from_secs(60))
+            .build().unwrap();
+        c.create_table(customers).unwrap();
+        for (name, on_delete) in [("PruneRestricted", OnDelete::Restrict), ("PruneCascaded", OnDelete::Cascade)] {
+            let orders = structs::Table::new()
+                .name(name.to_string())
+                .primary_field(structs::FieldType::String).unwrap()
+                .add_field("Customer".to_string(), structs::FieldType::String).unwrap()
+                .add_reference("Customer".to_string(), "PruneCustomers".to_string(), on_delete).unwrap()
+                .build().unwrap();
+            c.create_table(orders).unwrap();
+        };
 
-        if let Err(e) = result {
-            match e {
-                DatabaseError::EntryExists => {},
-                _ => panic!("Expected EntryExists, got {}", e),
-            };
-        } else {
-            panic!("Expected EntryExists, got none")
-        }
+        for key in ["Kept", "Cascaded", "Unreferenced"] {
+            c.insert("PruneCustomers".to_string(), capacity_entry(key, "a")).unwrap();
+        };
+        let order = |customer: &str| structs::Entry::new()
+            .set_primary_field(Field::String("Order1".to_string())).unwrap()
+            .add_field("Customer".to_string(), Field::String(customer.to_string())).unwrap()
+            .build().unwrap();
+        c.insert("PruneRestricted".to_string(), order("Kept")).unwrap();
+        c.insert("PruneCascaded".to_string(), order("Cascaded")).unwrap();
+
+        clock.advance(Duration::from_secs(61));
+        c.prune().unwrap();
+        let remaining: Vec<Field> = c.scan("PruneCustomers".to_string()).unwrap().into_iter().map(|e| e.primary_field.clone()).collect();
+        assert_eq!(remaining, vec![Field::String("Kept".to_string())]);
+        assert_eq!(c.scan("PruneRestricted".to_string()).unwrap().len(), 1);
+        assert_eq!(c.scan("PruneCascaded".to_string()).unwrap().len(), 0);
     }
 
     #[test]
-    fn database_open_close() {
-        let (mut c, table_builder) = create_client_table("OpenSaveCloseOpen".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+    fn max_entries_evict_skips_restricted() {
+        let (mut c, table) = create_client_table("EvictRestricted".to_string());
+        let table = table
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Notes".to_string(), structs::FieldType::String).unwrap()
+            .max_entries(2, CapacityPolicy::EvictOldest)
             .build().unwrap();
-
         c.create_table(table).unwrap();
-
-        let entry_first = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+        let orders = structs::Table::new()
+            .name("EvictRestrictedOrders".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Customer".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Customer".to_string(), "EvictRestricted".to_string(), OnDelete::Restrict).unwrap()
+            .build().unwrap();
+        c.create_table(orders).unwrap();
+        let order = |key: &str, customer: &str| structs::Entry::new()
+            .set_primary_field(Field::String(key.to_string())).unwrap()
+            .add_field("Customer".to_string(), Field::String(customer.to_string())).unwrap()
             .build().unwrap();
 
-        let result = c.insert_or_update("OpenSaveCloseOpen".to_string(), entry_first.clone());
-
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
-
-        c.save().unwrap();
-        drop(c);
-
-        let mut temp_dir_path = temp_dir();
-        temp_dir_path.push("OpenSaveCloseOpen.db");
+        c.insert("EvictRestricted".to_string(), capacity_entry("First", "a")).unwrap();
+        c.insert("EvictRestricted".to_string(), capacity_entry("Second", "b")).unwrap();
+        c.insert("EvictRestrictedOrders".to_string(), order("Order1", "First")).unwrap();
+        c.insert("EvictRestricted".to_string(), capacity_entry("Third", "c")).unwrap();
+        assert!(c.get("EvictRestricted".to_string(), Field::String("First".to_string())).is_ok());
+        assert!(c.get("EvictRestricted".to_string(), Field::String("Second".to_string())).is_err());
+        assert_eq!(c.stats().unwrap().tables["EvictRestricted"].evicted, 1);
 
-        let mut c = Client::open(temp_dir_path).unwrap();
-        let curent_entry = c.get("OpenSaveCloseOpen".to_string(), Field::String("This should Succeed".to_string())).unwrap();
-        assert!(curent_entry.primary_field==entry_first.primary_field);
-        assert!(curent_entry.fields==entry_first.fields);
+        c.insert("EvictRestrictedOrders".to_string(), order("Order2", "Third")).unwrap();
+        match c.insert("EvictRestricted".to_string(), capacity_entry("Fourth", "d")) {
+            Err(DatabaseError::CapacityExceeded(_)) => {},
+            r => panic!("Expected CapacityExceeded but received {:?}", r),
+        };
+        assert_eq!(c.scan("EvictRestricted".to_string()).unwrap().len(), 2);
+        assert_eq!(c.stats().unwrap().tables["EvictRestricted"].evicted, 1);
     }
 
     #[test]
-    fn query_item() {
-        let (mut c, table_builder) = create_client_table("QueryItems".to_string());
-        
-        let table = table_builder.primary_field(structs::FieldType::String).unwrap()
-            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
-            .add_optional_field("OptionalKey".to_string(), FieldType::String).unwrap()
+    fn reference_delete_checked_before_applying() {
+        let mut c = create_reference_tables("Unapplied", OnDelete::SetMissing);
+        let invoices = structs::Table::new()
+            .name("UnappliedInvoices".to_string())
+            .primary_field(structs::FieldType::String).unwrap()
+            .add_field("Customer".to_string(), structs::FieldType::String).unwrap()
+            .add_reference("Customer".to_string(), "UnappliedCustomers".to_string(), OnDelete::Restrict).unwrap()
             .build().unwrap();
-
-        c.create_table(table).unwrap();
-
-        let entry_first = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(123123)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My first entry".to_string())).unwrap()
+        c.create_table(invoices).unwrap();
+        let invoice = structs::Entry::new()
+            .set_primary_field(Field::String("Invoice1".to_string())).unwrap()
+            .add_field("Customer".to_string(), Field::String("Customer1".to_string())).unwrap()
             .build().unwrap();
+        c.insert("UnappliedInvoices".to_string(), invoice).unwrap();
 
-        let result = c.insert("QueryItems".to_string(), entry_first.clone());
-
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        } 
+        match c.delete("UnappliedCustomers".to_string(), Field::String("Customer1".to_string())) {
+            Err(DatabaseError::ReferenceViolation(_)) => {},
+            r => panic!("Expected ReferenceViolation but received {:?}", r),
+        };
+        assert!(c.get("UnappliedCustomers".to_string(), Field::String("Customer1".to_string())).is_ok());
+        let order = c.get("UnappliedOrders".to_string(), Field::String("Order1".to_string())).unwrap();
+        assert_eq!(order.fields.get("Customer"), Some(&Field::String("Customer1".to_string())));
+    }
 
-        let entry_second = structs::Entry::new()
-            .set_primary_field(Field::String("This should Succeed too".to_string())).unwrap()
-            .add_field("FirstKey".to_string(), Field::I64(12312312)).unwrap()
-            .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
-            .build().unwrap();
 
-        let result = c.insert("QueryItems".to_string(), entry_second.clone());
+    #[test]
+    fn prune_readers_see_whole_prune() {
+        let mut temp_dir_path = temp_dir();
+        temp_dir_path.push("PruneWholeForReaders.db");
+        if temp_dir_path.exists() {
+            std::fs::remove_file(&temp_dir_path).unwrap();
+        };
 
-        if let Err(e) = result {
-            panic!("No error expected, received {}", e)
-        }
+        let mut c = Client::builder(&temp_dir_path).build_client().unwrap();
+        let table = structs::Table::new()
+            .name("PruneWholeForReaders".to_string())
+            .primary_field(structs::FieldType::I64).unwrap()
+            .add_field("FirstKey".to_string(), structs::FieldType::I64).unwrap()
+            .add_expiration(Duration::from_millis(1))
+            .build().unwrap();
+        c.create_table(table).unwrap();
 
-        let first_query = c.query("QueryItems".to_string(), HashMap::from_iter(vec![("OptionalKey".to_string(), Field::String("My first entry".to_string()))])).unwrap();
-        let second_query = c.query("QueryItems".to_string(), HashMap::from_iter(vec![("FirstKey".to_string(),Field::I64(12312312))])).unwrap();
-        
-        assert!(first_query[0].primary_field==entry_first.primary_field);
-        assert!(first_query[0].fields==entry_first.fields);
-        assert!(first_query.len() == 1);
+        let total = PRUNE_CHUNK_SIZE * 2 + 10;
+        for i in 0..total {
+            let entry = structs::Entry::new()
+                .set_primary_field(Field::I64(i as i64)).unwrap()
+                .add_field("FirstKey".to_string(), Field::I64(i as i64)).unwrap()
+                .build().unwrap();
+            c.insert("PruneWholeForReaders".to_string(), entry).unwrap();
+        };
+        sleep(Duration::from_millis(10));
 
-        assert!(second_query[0].primary_field==entry_second.primary_field);
-        assert!(second_query[0].fields==entry_second.fields);
-        assert!(second_query.len() == 1);
+        let mut reader = c.clone();
+        let h = std::thread::spawn(move || {
+            loop {
+                let seen = reader.scan("PruneWholeForReaders".to_string()).unwrap().len();
+                assert!(seen == total || seen == 0, "Reader saw {} of {} entries", seen, total);
+                if seen == 0 {
+                    break
+                };
+            };
+        });
+        c.prune().unwrap();
+        h.join().unwrap();
     }
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+use crate::sync::{Arc, Mutex};
+use crate::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+use tracing::error;
+
+use crate::structs::Field;
+
+/// Event in the life of the database of a Client, sent to the subscribers of its
+/// LifecycleEvents; for sidecar tooling such as health checks and save monitors.  Unlike
+/// OperationObservers, events are sent for the database as a whole rather than for each
+/// operation on its tables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// The database was opened, or created if created is true
+    Opened {
+        path: PathBuf,
+        created: bool,
+    },
+    /// The database was saved, writing bytes to its file
+    Saved {
+        bytes: u64,
+        duration: Duration,
+    },
+    /// Expired entries were pruned, removing the supplied number of entries
+    Pruned {
+        removed: u64,
+    },
+    /// The database was compacted, writing bytes to its file and removing the supplied
+    /// number of orphaned files
+    Compacted {
+        bytes: u64,
+        removed: u64,
+    },
+    /// Entries of the table will expire within the duration set by
+    /// ClientBuilder::expiry_warning; each primary Field with the time left until it expires
+    Expiring {
+        table: String,
+        entries: Vec<(Field, Duration)>,
+    },
+    /// A save or compaction wrote more bytes than the size budget of the Client; see
+    /// ClientBuilder::size_budget
+    SizeBudgetExceeded {
+        bytes: u64,
+        budget: u64,
+    },
+    /// Saving, pruning, compacting or reloading the database failed; operation is the name
+    /// of the method, such as "save"
+    Error {
+        operation: &'static str,
+        error: String,
+    },
+}
+
+/// Broadcasts LifecycleEvents to its subscribers; see ClientBuilder::lifecycle_events and
+/// DatabaseClient::subscribe_lifecycle.
+///
+/// Clones share their subscribers.  Events are sent on the thread of the operation,
+/// including the background threads of the Client; a subscriber that is dropped is
+/// forgotten at the next event.
+/// ```
+/// use persistent_keystore_rs::{Client, LifecycleEvent, LifecycleEvents};
+/// use persistent_keystore_rs::prelude::*;
+/// use std::path::Path;
+/// let events = LifecycleEvents::new();
+/// let rx = events.subscribe();
+/// let mut c = Client::builder(Path::new("lifecycle.db"))
+///     .lifecycle_events(events)
+///     .build().unwrap();
+/// assert!(matches!(rx.try_recv().unwrap(), LifecycleEvent::Saved{..}));
+/// assert!(matches!(rx.try_recv().unwrap(), LifecycleEvent::Opened{created: true, ..}));
+/// # drop(c);
+/// # std::fs::remove_file("lifecycle.db").unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct LifecycleEvents {
+    subscribers: Arc<Mutex<Vec<Sender<LifecycleEvent>>>>,
+}
+
+impl LifecycleEvents {
+    pub fn new() -> Self {
+        LifecycleEvents::default()
+    }
+
+    /// Returns a Receiver of every subsequent event
+    pub fn subscribe(&self) -> Receiver<LifecycleEvent> {
+        let (tx, rx) = channel();
+        match self.subscribers.lock() {
+            Ok(mut subscribers) => subscribers.push(tx),
+            Err(_) => error!("Unable to get subscriber lock"),
+        };
+        rx
+    }
+
+    /// Sends the event to every subscriber, forgetting those that have been dropped
+    pub(crate) fn emit(&self, event: LifecycleEvent) {
+        match self.subscribers.lock() {
+            Ok(mut subscribers) => subscribers.retain(|s| s.send(event.clone()).is_ok()),
+            Err(_) => error!("Unable to get subscriber lock"),
+        };
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use tracing::Level;
+
+/// Part of the crate emitting tracing events of its own target, such as
+/// `persistent_keystore_rs::query`, so that subscribers can route each separately, and
+/// with its own maximum level set at runtime by set_log_level
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    /// Saving, compacting, verifying and reloading the database file, and the background
+    /// sync thread
+    Save,
+    /// Removing expired entries
+    Prune,
+    /// Reads of entries: get, exists, scan, query and their variants
+    Query,
+    /// Writes of entries: insert, update, delete and their variants
+    Write,
+    /// Creating and dropping tables
+    Schema,
+}
+
+impl Subsystem {
+    /// Every Subsystem
+    pub const ALL: [Subsystem; 5] = [Subsystem::Save, Subsystem::Prune, Subsystem::Query, Subsystem::Write, Subsystem::Schema];
+
+    /// Returns the tracing target of the events of the Subsystem
+    pub const fn target(self) -> &'static str {
+        match self {
+            Subsystem::Save => "persistent_keystore_rs::save",
+            Subsystem::Prune => "persistent_keystore_rs::prune",
+            Subsystem::Query => "persistent_keystore_rs::query",
+            Subsystem::Write => "persistent_keystore_rs::write",
+            Subsystem::Schema => "persistent_keystore_rs::schema",
+        }
+    }
+}
+
+/// Level of every event emitted, including trace
+const ALL_LEVELS: u8 = 5;
+
+/// Maximum level of each Subsystem, indexed by its discriminant; 0 silences it
+static LEVELS: [AtomicU8; Subsystem::ALL.len()] = [const { AtomicU8::new(ALL_LEVELS) }; Subsystem::ALL.len()];
+
+fn rank(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => ALL_LEVELS,
+    }
+}
+
+/// Sets the most verbose level of the events the Subsystem emits, for every Client of the
+/// process; None silences it.  Events above the level are skipped before their messages
+/// are formatted, unlike those filtered out by a subscriber.  Every level is emitted by
+/// default.
+/// ```
+/// use persistent_keystore_rs::{Subsystem, log_level, set_log_level};
+/// use tracing::Level;
+///
+/// // Skip the per-entry events of a bulk load, keeping errors
+/// set_log_level(Subsystem::Write, Some(Level::ERROR));
+/// assert_eq!(log_level(Subsystem::Write), Some(Level::ERROR));
+/// set_log_level(Subsystem::Write, Some(Level::TRACE));
+/// ```
+pub fn set_log_level(subsystem: Subsystem, level: Option<Level>) {
+    LEVELS[subsystem as usize].store(level.map(rank).unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Returns the most verbose level of the events the Subsystem emits, or None if it is
+/// silenced; see set_log_level
+pub fn log_level(subsystem: Subsystem) -> Option<Level> {
+    match LEVELS[subsystem as usize].load(Ordering::Relaxed) {
+        0 => None,
+        1 => Some(Level::ERROR),
+        2 => Some(Level::WARN),
+        3 => Some(Level::INFO),
+        4 => Some(Level::DEBUG),
+        _ => Some(Level::TRACE),
+    }
+}
+
+/// Returns true if the Subsystem emits events of the level
+#[inline]
+pub(crate) fn enabled(subsystem: Subsystem, level: Level) -> bool {
+    rank(level) <= LEVELS[subsystem as usize].load(Ordering::Relaxed)
+}
+
+/// Emits a tracing event with the target of the Subsystem, if its level is enabled;
+/// `log_event!(Subsystem::Query, TRACE, "Scanning table {}", table)`
+macro_rules! log_event {
+    ($subsystem:expr, $level:ident, $($arg:tt)+) => {
+        if $crate::logging::enabled($subsystem, tracing::Level::$level) {
+            tracing::event!(target: $subsystem.target(), tracing::Level::$level, $($arg)+)
+        }
+    };
+}
+
+pub(crate) use log_event;
@@ -0,0 +1,88 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::structs::*;
+
+/// Outcome of a primary Field held by both databases of a merge with differing fields;
+/// returned by the resolver supplied to DatabaseClient::merge_from_with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// The local Entry is kept
+    KeepLocal,
+    /// The local Entry is replaced by the remote Entry
+    KeepRemote,
+    /// The local Entry is replaced by the supplied Entry, which must have the same primary
+    /// Field.  Its last_timestamp is kept if set, otherwise the time of the merge is used.
+    Merged(Entry),
+}
+
+/// Resolver used by DatabaseClient::merge_from; keeps the Entry with the later
+/// last_timestamp.  Of Entries with the same last_timestamp, the one whose serialized form
+/// is greater is kept, so that databases merged into each other settle on the same Entry.
+/// ```
+/// use persistent_keystore_rs::{Entry, Field, Resolution, last_writer_wins};
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let mut local = Entry::new()
+///     .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+///     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+///     .build().unwrap();
+/// let mut remote = local.clone();
+/// remote.fields.insert("Count".to_string(), Field::I64(2));
+/// local.last_timestamp = Some(UNIX_EPOCH);
+/// remote.last_timestamp = Some(UNIX_EPOCH + Duration::from_secs(1));
+/// assert_eq!(last_writer_wins(&local, &remote), Resolution::KeepRemote);
+///
+/// local.last_timestamp = remote.last_timestamp;
+/// assert_ne!(last_writer_wins(&local, &remote), last_writer_wins(&remote, &local));
+/// ```
+pub fn last_writer_wins(local: &Entry, remote: &Entry) -> Resolution {
+    let later = match remote.last_timestamp.cmp(&local.last_timestamp) {
+        Ordering::Equal => serialized(remote) > serialized(local),
+        ordering => ordering == Ordering::Greater,
+    };
+    match later {
+        true => Resolution::KeepRemote,
+        false => Resolution::KeepLocal,
+    }
+}
+
+/// Returns the serialized form of the Entry, whose fields are serialized in the order of
+/// their names; used to order Entries written at the same time
+fn serialized(entry: &Entry) -> Vec<u8> {
+    bincode::serialize(entry).unwrap_or_default()
+}
+
+/// Primary Field held by both databases of a merge with differing fields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub table: String,
+    pub key: Field,
+    /// Entry held by this database before the merge
+    pub local: Arc<Entry>,
+    /// Entry held by the merged database
+    pub remote: Arc<Entry>,
+    pub resolution: Resolution,
+}
+
+/// Result of DatabaseClient::merge_from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Tables that only existed in the merged database, and were copied in full
+    pub tables_created: Vec<String>,
+    /// Entries that only existed in the merged database
+    pub inserted: u64,
+    /// Entries held by both databases with the same fields
+    pub unchanged: u64,
+    /// Entries held by both databases with differing fields, and how each was resolved
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Changes to a Database planned by a merge, before any is made; see Database::plan_merge
+#[derive(Default)]
+pub(crate) struct MergePlan {
+    pub(crate) report: MergeReport,
+    /// Tables only held by the merged database, without their Entries
+    pub(crate) tables: Vec<Table>,
+    /// Entries to write, with the name of their Table
+    pub(crate) writes: Vec<(String, Entry)>,
+}
@@ -0,0 +1,104 @@
+use crate::errors::*;
+
+/// Prefix of the names reserved for Tables and fields defined by the crate, such as
+/// SYSTEM_TABLES and LEASE_TABLE
+pub const RESERVED_PREFIX: &str = "__";
+
+/// Default maximum length of a name in bytes; see NamePolicy::max_len
+pub const DEFAULT_MAX_NAME_LEN: usize = 128;
+
+/// Characters allowed in names by a NamePolicy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NameCharset {
+    /// Any character
+    Any,
+    /// Any character other than control characters, such as newlines and NUL
+    #[default]
+    Printable,
+    /// ASCII letters and digits, `_`, `-` and `.`
+    Identifier,
+}
+
+impl NameCharset {
+    fn allows(&self, c: char) -> bool {
+        match self {
+            NameCharset::Any => true,
+            NameCharset::Printable => !c.is_control(),
+            NameCharset::Identifier => c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'),
+        }
+    }
+}
+
+/// Rules the names of a Table and its fields must follow, checked by TableBuilder::build; see
+/// TableBuilder::name_policy.  Names breaking the policy are rejected with
+/// DatabaseError::InvalidName.  By default names must be non-empty, at most
+/// DEFAULT_MAX_NAME_LEN bytes, free of control characters and not start with
+/// RESERVED_PREFIX.
+/// ```
+/// use persistent_keystore_rs::{NameCharset, NamePolicy};
+/// let policy = NamePolicy{
+///     max_len: 32,
+///     reserved_prefixes: vec!["__".to_string(), "tmp_".to_string()],
+///     charset: NameCharset::Identifier,
+/// };
+/// assert!(policy.check_table_name("users").is_ok());
+/// assert!(policy.check_table_name("tmp_users").is_err());
+/// assert!(policy.check_field_name("last seen").is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NamePolicy {
+    /// Maximum length of a name in bytes
+    pub max_len: usize,
+    /// Prefixes names must not start with
+    pub reserved_prefixes: Vec<String>,
+    /// Characters allowed in names
+    pub charset: NameCharset,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        NamePolicy{
+            max_len: DEFAULT_MAX_NAME_LEN,
+            reserved_prefixes: vec![RESERVED_PREFIX.to_string()],
+            charset: NameCharset::default(),
+        }
+    }
+}
+
+impl NamePolicy {
+    /// Returns a policy allowing any non-empty name; for Tables whose names were chosen
+    /// before names were checked, or by the crate itself
+    pub fn unrestricted() -> Self {
+        NamePolicy{
+            max_len: usize::MAX,
+            reserved_prefixes: Vec::new(),
+            charset: NameCharset::Any,
+        }
+    }
+
+    /// Returns DatabaseError::InvalidName if the name of a Table breaks the policy
+    pub fn check_table_name(&self, name: &str) -> Result<(), DatabaseError> {
+        self.check("table", name)
+    }
+
+    /// Returns DatabaseError::InvalidName if the name of a field breaks the policy
+    pub fn check_field_name(&self, name: &str) -> Result<(), DatabaseError> {
+        self.check("field", name)
+    }
+
+    fn check(&self, kind: &str, name: &str) -> Result<(), DatabaseError> {
+        if name.is_empty() {
+            return Err(DatabaseError::InvalidName(format!("{} name must not be empty", kind)))
+        };
+        if name.len() > self.max_len {
+            return Err(DatabaseError::InvalidName(format!("{} name {:?} is longer than {} bytes", kind, name, self.max_len)))
+        };
+        if let Some(prefix) = self.reserved_prefixes.iter().find(|p| name.starts_with(p.as_str())) {
+            return Err(DatabaseError::InvalidName(format!("{} name {:?} starts with the reserved prefix {:?}", kind, name, prefix)))
+        };
+        if let Some(c) = name.chars().find(|c| !self.charset.allows(*c)) {
+            return Err(DatabaseError::InvalidName(format!("{} name {:?} contains {:?}, not allowed by {:?}", kind, name, c, self.charset)))
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+use crate::structs::*;
+
+/// Kind of operation on a table made through a Client, reported to its OperationObservers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// create_table
+    CreateTable,
+    /// drop_table
+    DropTable,
+    /// insert
+    Insert,
+    /// insert_or_update
+    InsertOrUpdate,
+    /// update
+    Update,
+    /// get, exists, time_to_live and history of a single entry
+    Get,
+    /// scan, scan_prefix, scan_cancellable, scan_versioned and project of a whole table
+    Scan,
+    /// query, query_where and their variants, query_to_writer and lookup_join
+    Query,
+    /// delete of a single entry, and delete_many and truncate of many
+    Delete,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::CreateTable => "create_table",
+            Operation::DropTable => "drop_table",
+            Operation::Insert => "insert",
+            Operation::InsertOrUpdate => "insert_or_update",
+            Operation::Update => "update",
+            Operation::Get => "get",
+            Operation::Scan => "scan",
+            Operation::Query => "query",
+            Operation::Delete => "delete",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Operation about to be attempted through a Client, passed to its OperationObservers
+#[derive(Clone, Copy, Debug)]
+pub struct OperationEvent<'a> {
+    pub operation: Operation,
+    pub table: &'a str,
+    /// Primary field of the entry operated on; None for operations on many entries
+    pub key: Option<&'a Field>,
+    /// Context of the Client, set by DatabaseClient::with_context, such as the caller
+    /// on whose behalf the operation is made
+    pub context: Option<&'a str>,
+}
+
+/// Hook notified of the operations made through a Client, registered with
+/// ClientBuilder::observer; for example to keep an access log of who read which entry.
+///
+/// Observers are notified before each operation is attempted, whether or not it then
+/// succeeds, on the thread making it, so should return promptly.  Every method defaults to
+/// doing nothing.  Reads through a Snapshot of the Client are observed; operations of the
+/// background threads of the Client, such as prune, are not.
+pub trait OperationObserver: Send + Sync {
+    fn on_create_table(&self, _event: &OperationEvent<'_>) {}
+    fn on_drop_table(&self, _event: &OperationEvent<'_>) {}
+    /// Called for Operation::Insert and Operation::InsertOrUpdate
+    fn on_insert(&self, _event: &OperationEvent<'_>) {}
+    fn on_update(&self, _event: &OperationEvent<'_>) {}
+    fn on_get(&self, _event: &OperationEvent<'_>) {}
+    fn on_scan(&self, _event: &OperationEvent<'_>) {}
+    fn on_query(&self, _event: &OperationEvent<'_>) {}
+    fn on_delete(&self, _event: &OperationEvent<'_>) {}
+    /// Called, after the method for the operation, when the authorizer of the Client
+    /// denies it; see ClientBuilder::authorizer
+    fn on_denied(&self, _event: &OperationEvent<'_>) {}
+}
+
+/// Calls the method of the observer for the operation of the event
+pub(crate) fn notify(observer: &dyn OperationObserver, event: &OperationEvent<'_>) {
+    match event.operation {
+        Operation::CreateTable => observer.on_create_table(event),
+        Operation::DropTable => observer.on_drop_table(event),
+        Operation::Insert | Operation::InsertOrUpdate => observer.on_insert(event),
+        Operation::Update => observer.on_update(event),
+        Operation::Get => observer.on_get(event),
+        Operation::Scan => observer.on_scan(event),
+        Operation::Query => observer.on_query(event),
+        Operation::Delete => observer.on_delete(event),
+    }
+}
@@ -0,0 +1,57 @@
+//! Serialization of the hash maps and sets of the Database in the order of their keys, for
+//! use with `#[serde(serialize_with)]`; so that Databases holding the same Tables and
+//! Entries serialize to the same bytes, rather than in the iteration order of each map,
+//! which differs between processes.  Deserialization is unchanged, as the encoding of a
+//! map does not depend on the order of its entries.
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use serde::{Serialize, Serializer};
+
+/// Map serialized in the order of its keys
+struct Sorted<'a, K, V, H>(&'a HashMap<K, V, H>);
+
+impl<K: Serialize + Ord, V: Serialize, H: BuildHasher> Serialize for Sorted<'_, K, V, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(&K, &V)> = self.0.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_map(entries)
+    }
+}
+
+/// Serializes the map in the order of its keys
+pub(crate) fn map<S, K, V, H>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Serialize + Ord,
+    V: Serialize,
+    H: BuildHasher,
+{
+    Sorted(map).serialize(serializer)
+}
+
+/// Serializes the map, and each map it holds, in the order of their keys
+pub(crate) fn nested_map<S, K, K2, V, H, H2>(map: &HashMap<K, HashMap<K2, V, H2>, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Serialize + Ord,
+    K2: Serialize + Ord,
+    V: Serialize,
+    H: BuildHasher,
+    H2: BuildHasher,
+{
+    let mut entries: Vec<(&K, Sorted<'_, K2, V, H2>)> = map.iter().map(|(k, v)| (k, Sorted(v))).collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_map(entries)
+}
+
+/// Serializes the set in the order of its values
+pub(crate) fn set<S, T, H>(set: &HashSet<T, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize + Ord,
+    H: BuildHasher,
+{
+    let mut values: Vec<&T> = set.iter().collect();
+    values.sort_unstable();
+    serializer.collect_seq(values)
+}
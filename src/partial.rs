@@ -0,0 +1,106 @@
+//! Deserialization of the Tables of the serialized contents of a database file one at a
+//! time, for DatabaseClient::attach_table_from and Client::check_compat.  Each Table is
+//! dropped before the next is read, and reading stops once the Table sought is found, so
+//! the Tables of the database are never held in memory together.  Follows the order of the
+//! fields of Database, which bincode serializes as a sequence.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use bincode::Options;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::errors::*;
+use crate::structs::Table;
+use crate::idempotency::IdempotencyLog;
+
+/// Names of the fields of Database, in order
+const DATABASE_FIELDS: &[&str] = &["sync_interval", "meta", "idempotency", "tables", "schema_history"];
+
+/// Callback of for_each_table
+type TableCallback<'a> = &'a mut dyn FnMut(String, Result<Table, String>) -> bool;
+
+/// Deserializes the Database, passing each of its Tables to the callback
+struct DatabaseSeed<'a>(TableCallback<'a>);
+
+impl<'de> DeserializeSeed<'de> for DatabaseSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Database", DATABASE_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for DatabaseSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Database")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        seq.next_element::<Option<Duration>>()?;
+        seq.next_element::<BTreeMap<String, String>>()?;
+        seq.next_element::<IdempotencyLog>()?;
+        seq.next_element_seed(TablesSeed(self.0))?;
+        Ok(())
+    }
+}
+
+/// Deserializes the Tables of a Database, passing each to the callback
+struct TablesSeed<'a>(TableCallback<'a>);
+
+impl<'de> DeserializeSeed<'de> for TablesSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TablesSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of Tables")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            match map.next_value::<Table>() {
+                Ok(table) => if !(self.0)(name, Ok(table)) {
+                    return Ok(())
+                },
+                Err(e) => {
+                    (self.0)(name, Err(e.to_string()));
+                    return Ok(())
+                },
+            };
+        };
+        Ok(())
+    }
+}
+
+/// Calls f with the name of each Table of the serialized Database and the Table, or the
+/// error deserializing it, in turn until f returns false.  Each Table is dropped before the
+/// next is read.  A Table that cannot be deserialized ends the Tables read, as those after
+/// it cannot be located.
+pub(crate) fn for_each_table(uncompressed: &[u8], f: TableCallback<'_>) -> Result<(), bincode::Error> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize_seed(DatabaseSeed(f), uncompressed)
+}
+
+/// Returns the named Table of the serialized Database, or None if it holds no such Table
+pub(crate) fn read_table(uncompressed: &[u8], name: &str) -> Result<Option<Table>, DatabaseError> {
+    let mut found = Ok(None);
+    for_each_table(uncompressed, &mut |table, result| {
+        match result {
+            Ok(t) if table == name => found = Ok(Some(t)),
+            Ok(_) => return true,
+            Err(e) => found = Err(DatabaseError::InvalidFormat(format!("table {}: {}", table, e))),
+        };
+        false
+    })?;
+    found
+}
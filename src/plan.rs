@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::condition::*;
+
+/// How a query locates the Entries it evaluates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccessPath {
+    /// Every Entry of the Table is read and evaluated
+    FullScan,
+    /// Only the Entries found in the compound index on the fields are read and evaluated;
+    /// see TableBuilder::add_index
+    Index(Vec<String>),
+    /// Only the Entry with the primary Field equal to the value of a PRIMARY_FIELD_KEY
+    /// Condition is read and evaluated
+    PrimaryKey,
+    /// Only the Entries found in the prefix index under the prefix of a PRIMARY_FIELD_KEY
+    /// Condition are read and evaluated; see TableBuilder::prefix_index
+    PrimaryKeyPrefix(String),
+}
+
+/// Description of how a query against a Table will be executed, as returned by
+/// DatabaseClient::explain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPlan {
+    /// Name of the Table being queried
+    pub table: String,
+    /// How the Entries to evaluate are located
+    pub access: AccessPath,
+    /// Conditions answered by the access path without reading Entries, by field name
+    pub pushed_down: Vec<(String, Condition)>,
+    /// Conditions evaluated against each Entry read, by field name
+    pub residual: Vec<(String, Condition)>,
+    /// Estimated number of Entries read to answer the query
+    pub estimated_rows_scanned: usize,
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.access {
+            AccessPath::FullScan => write!(f, "Full scan of table {}", self.table)?,
+            AccessPath::Index(fields) => write!(f, "Index scan of table {} on ({})", self.table, fields.join(", "))?,
+            AccessPath::PrimaryKey => write!(f, "Primary key lookup of table {}", self.table)?,
+            AccessPath::PrimaryKeyPrefix(prefix) => write!(f, "Prefix scan of table {} on {}", self.table, prefix)?,
+        };
+        write!(f, "; estimated {} rows scanned", self.estimated_rows_scanned)?;
+
+        for (k, c) in &self.pushed_down {
+            write!(f, "\n  pushed down: {} {}", k, c)?;
+        };
+        for (k, c) in &self.residual {
+            write!(f, "\n  filter: {} {}", k, c)?;
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, trace};
+
+use crate::{checksum, Client};
+use crate::structs::*;
+use crate::condition::*;
+use crate::stats::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+/// Logical database spread across a fixed number of shards, each an independent database
+/// with its own lock and file, to scale writes beyond a single lock.
+///
+/// Entries are assigned to a shard by a stable hash of their primary field, so writes to
+/// different shards proceed in parallel.  Tables are created in every shard; scans and
+/// queries visit every shard and merge the results.  Clones of the pool share its shards
+/// and may be moved to other threads.
+///
+/// Shard `n` is stored at the supplied path with `.n` appended, e.g. `ingest.db.0`.
+/// Limits such as max_entries apply to each shard, and tables with references are not
+/// supported as the referenced Entry may be held by another shard.
+#[derive(Clone)]
+pub struct ClientPool {
+    shards: Vec<Client>,
+}
+
+/// Returns the path of shard n of the pool at the supplied path
+fn shard_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Returns the shard of the supplied primary field; a checksum of its serialized form, which
+/// is stable across processes so that reopened pools find their entries
+fn shard_index(key: &Field, shards: usize) -> Result<usize, DatabaseError> {
+    let hash = checksum(&bincode::serialize(key)?);
+    Ok((hash % shards as u64) as usize)
+}
+
+impl ClientPool {
+    /// Creates a pool of the supplied number of shards at the supplied path
+    /// ```
+    /// use persistent_keystore_rs::ClientPool;
+    /// use std::path::Path;
+    /// let pool = ClientPool::new(Path::new("pool.db"), 4, None).unwrap();
+    /// assert_eq!(pool.shards(), 4);
+    /// # for n in 0..4 { std::fs::remove_file(format!("pool.db.{}", n)).unwrap(); };
+    /// ```
+    /// If a sync interval is provided, each shard prunes and saves itself every interval.
+    /// If any shard exists, DatabaseError::DatabaseExistsError is returned.
+    pub fn new<P: AsRef<Path>>(path: P, shards: usize, sync_interval: Option<Duration>) -> Result<ClientPool, DatabaseError> {
+        info!("Creating ClientPool of {} shards at {:?}", shards, path.as_ref());
+        if shards == 0 {
+            error!("ClientPool must contain at least one shard");
+            return Err(DatabaseError::InvalidFormat(format!("pool of {} shards", shards)))
+        };
+
+        for n in 0..shards {
+            if shard_path(path.as_ref(), n).exists() {
+                error!("Shard {} exists, cannot create pool: {:?}", n, path.as_ref());
+                return Err(DatabaseError::DatabaseExistsError)
+            };
+        };
+
+        let mut clients = Vec::with_capacity(shards);
+        for n in 0..shards {
+            let mut builder = Client::builder(shard_path(path.as_ref(), n));
+            if let Some(d) = sync_interval {
+                builder = builder.sync_interval(d);
+            };
+            clients.push(builder.build_client()?);
+        };
+        Ok(ClientPool{
+            shards: clients,
+        })
+    }
+
+    /// Opens the existing pool at the supplied path, with as many shards as were created
+    /// ```
+    /// # use persistent_keystore_rs::ClientPool;
+    /// use std::path::Path;
+    /// # let pool = ClientPool::new(Path::new("existingpool.db"), 2, None).unwrap();
+    /// # drop(pool);
+    /// let pool = ClientPool::open(Path::new("existingpool.db")).unwrap();
+    /// assert_eq!(pool.shards(), 2);
+    /// # for n in 0..2 { std::fs::remove_file(format!("existingpool.db.{}", n)).unwrap(); };
+    /// ```
+    /// If no shard exists, DatabaseError::DatabaseDoesNotExist is returned.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ClientPool, DatabaseError> {
+        info!("Opening ClientPool at {:?}", path.as_ref());
+        let mut clients = Vec::new();
+        loop {
+            let shard = shard_path(path.as_ref(), clients.len());
+            if !shard.exists() {
+                break
+            };
+            clients.push(Client::builder(shard).open_client()?);
+        };
+
+        if clients.is_empty() {
+            let shard = shard_path(path.as_ref(), 0);
+            error!("Pool does not exist, cannot open: {:?}", shard);
+            return Err(DatabaseError::DatabaseDoesNotExist(shard))
+        };
+        debug!("Opened {} shards", clients.len());
+        Ok(ClientPool{
+            shards: clients,
+        })
+    }
+
+    /// Returns the number of shards in the pool
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the Client of the shard holding the supplied primary field
+    fn shard(&mut self, primary_field: &Field) -> Result<&mut Client, DatabaseError> {
+        let n = shard_index(primary_field, self.shards.len())?;
+        trace!("Entry {} is held by shard {}", primary_field, n);
+        Ok(&mut self.shards[n])
+    }
+
+    /// Saves every shard
+    pub fn save(&mut self) -> Result<(), DatabaseError> {
+        for shard in self.shards.iter_mut() {
+            shard.save()?;
+        };
+        Ok(())
+    }
+
+    /// Prunes every shard
+    pub fn prune(&mut self) -> Result<(), DatabaseError> {
+        for shard in self.shards.iter_mut() {
+            shard.prune()?;
+        };
+        Ok(())
+    }
+
+    /// Creates the table in every shard.  If the table references other tables,
+    /// DatabaseError::ReferenceViolation is returned.
+    /// ```
+    /// use persistent_keystore_rs::{ClientPool, Entry, Field, FieldType, Table};
+    /// # use std::path::Path;
+    /// let mut pool = ClientPool::new(Path::new("pooltable.db"), 4, None).unwrap();
+    /// let table = Table::new()
+    ///     .name(String::from("MyTable"))
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field(String::from("Count"), FieldType::I64).unwrap()
+    ///     .build().unwrap();
+    /// pool.create_table(table).unwrap();
+    ///
+    /// for n in 0..16 {
+    ///     let entry = Entry::new()
+    ///         .set_primary_field(Field::String(format!("Entry{}", n))).unwrap()
+    ///         .add_field(String::from("Count"), Field::I64(n)).unwrap()
+    ///         .build().unwrap();
+    ///     pool.insert(String::from("MyTable"), entry).unwrap();
+    /// };
+    /// assert_eq!(pool.scan(String::from("MyTable")).unwrap().len(), 16);
+    /// # for n in 0..4 { std::fs::remove_file(format!("pooltable.db.{}", n)).unwrap(); };
+    /// ```
+    pub fn create_table(&mut self, table: Table) -> Result<(), DatabaseError> {
+        if !table.references.is_empty() {
+            error!("Unable to create table {} in pool", table.name);
+            return Err(DatabaseError::ReferenceViolation(format!("table {} has references, which are not supported by ClientPool", table.name)))
+        };
+
+        for shard in self.shards.iter_mut() {
+            shard.create_table(table.clone())?;
+        };
+        Ok(())
+    }
+
+    /// Returns the names of the tables in the pool
+    pub fn list_tables(&mut self) -> Result<Vec<String>, DatabaseError> {
+        self.shards[0].list_tables()
+    }
+
+    /// Drops the table from every shard
+    pub fn drop_table(&mut self, table: &String) -> Result<(), DatabaseError> {
+        for shard in self.shards.iter_mut() {
+            shard.drop_table(table)?;
+        };
+        Ok(())
+    }
+
+    /// Inserts the entry into its shard; see DatabaseClient::insert
+    pub fn insert(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        self.shard(&entry.primary_field)?.insert(table, entry)
+    }
+
+    /// Inserts or updates the entry in its shard; see DatabaseClient::insert_or_update
+    pub fn insert_or_update(&mut self, table: String, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        self.shard(&entry.primary_field)?.insert_or_update(table, entry)
+    }
+
+    /// Updates the entry in its shard; see DatabaseClient::update
+    pub fn update(&mut self, table: String, entry: Entry) -> Result<(), DatabaseError> {
+        self.shard(&entry.primary_field)?.update(table, entry)
+    }
+
+    /// Returns the entry from its shard; see DatabaseClient::get
+    pub fn get(&mut self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError> {
+        self.shard(&primary_field)?.get(table, primary_field)
+    }
+
+    /// Deletes the entry from its shard; see DatabaseClient::delete
+    pub fn delete(&mut self, table: String, primary_field: Field) -> Result<(), DatabaseError> {
+        self.shard(&primary_field)?.delete(table, primary_field)
+    }
+
+    /// Deletes the entries matching the criteria from every shard, returning the number
+    /// of entries deleted
+    pub fn delete_many(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<u64, DatabaseError> {
+        let mut deleted = 0;
+        for shard in self.shards.iter_mut() {
+            deleted += shard.delete_many(table.clone(), criteria.clone())?;
+        };
+        Ok(deleted)
+    }
+
+    /// Returns every entry of the table across all shards
+    pub fn scan(&mut self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let mut entries = Vec::new();
+        for shard in self.shards.iter_mut() {
+            entries.extend(shard.scan(table.clone())?);
+        };
+        Ok(entries)
+    }
+
+    /// Returns the entries matching the criteria across all shards
+    pub fn query(&mut self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where(table, equals_all(criteria))
+    }
+
+    /// Returns the entries meeting every condition across all shards
+    pub fn query_where(&mut self, table: String, conditions: HashMap<String, Condition>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let mut entries = Vec::new();
+        for shard in self.shards.iter_mut() {
+            entries.extend(shard.query_where(table.clone(), conditions.clone())?);
+        };
+        Ok(entries)
+    }
+
+    /// Returns the combined usage of every shard; table limits are summed across shards, and
+    /// the pool is over its size budget if any shard is
+    pub fn stats(&mut self) -> Result<DatabaseStats, DatabaseError> {
+        let mut stats = DatabaseStats::default();
+        for shard in self.shards.iter_mut() {
+            let shard_stats = shard.stats()?;
+            stats.approx_bytes += shard_stats.approx_bytes;
+            stats.over_size_budget |= shard_stats.over_size_budget;
+            stats.writes.saves += shard_stats.writes.saves;
+            stats.writes.total += shard_stats.writes.total;
+            for (name, t) in shard_stats.tables {
+                let table_stats = stats.tables.entry(name).or_insert(TableStats{
+                    max_entries: Some(0),
+                    ..Default::default()
+                });
+                table_stats.entries += t.entries;
+                table_stats.approx_bytes += t.approx_bytes;
+                table_stats.evicted += t.evicted;
+                table_stats.spilled += t.spilled;
+                table_stats.spill_garbage_bytes += t.spill_garbage_bytes;
+                table_stats.externalized += t.externalized;
+                table_stats.deduplicated += t.deduplicated;
+                table_stats.prefix_index_bytes += t.prefix_index_bytes;
+                table_stats.entry_sizes.merge(&t.entry_sizes);
+                table_stats.created_at = table_stats.created_at.into_iter().chain(t.created_at).min();
+                table_stats.last_modified_at = table_stats.last_modified_at.into_iter().chain(t.last_modified_at).max();
+                table_stats.max_entries = match (table_stats.max_entries, t.max_entries) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    _ => None,
+                };
+            };
+        };
+        Ok(stats)
+    }
+}
@@ -0,0 +1,164 @@
+/// Node of a PrefixIndex, holding the bytes shared by every key below it
+#[derive(Clone, Default)]
+struct PrefixNode {
+    label: Vec<u8>,
+    terminal: bool,
+    children: Vec<PrefixNode>,
+}
+
+impl PrefixNode {
+    /// Returns the position of the child whose label starts with the supplied byte
+    fn child(&self, byte: u8) -> Result<usize, usize> {
+        self.children.binary_search_by_key(&byte, |c| c.label[0])
+    }
+
+    fn insert(&mut self, key: &[u8]) -> bool {
+        if key.is_empty() {
+            let added = !self.terminal;
+            self.terminal = true;
+            return added
+        };
+
+        let position = match self.child(key[0]) {
+            Ok(p) => p,
+            Err(p) => {
+                self.children.insert(p, PrefixNode{
+                    label: key.to_vec(),
+                    terminal: true,
+                    children: Vec::new(),
+                });
+                return true
+            },
+        };
+
+        let child = &mut self.children[position];
+        let shared = child.label.iter().zip(key).take_while(|(a, b)| a == b).count();
+        if shared < child.label.len() {
+            let tail = PrefixNode{
+                label: child.label.split_off(shared),
+                terminal: child.terminal,
+                children: std::mem::take(&mut child.children),
+            };
+            child.terminal = false;
+            child.children.push(tail);
+        };
+        child.insert(&key[shared..])
+    }
+
+    fn remove(&mut self, key: &[u8]) -> bool {
+        if key.is_empty() {
+            let removed = self.terminal;
+            self.terminal = false;
+            return removed
+        };
+
+        let position = match self.child(key[0]) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        let child = &mut self.children[position];
+        if !key.starts_with(&child.label) {
+            return false
+        };
+        let label_len = child.label.len();
+        if !child.remove(&key[label_len..]) {
+            return false
+        };
+
+        // Drop emptied nodes and merge a node with its only child to keep the tree compact
+        if !child.terminal && child.children.is_empty() {
+            self.children.remove(position);
+        } else if !child.terminal && child.children.len() == 1 {
+            let mut only = child.children.remove(0);
+            child.label.append(&mut only.label);
+            child.terminal = only.terminal;
+            child.children = only.children;
+        };
+        true
+    }
+
+    /// Adds every key below this node to keys, in sorted order
+    fn collect(&self, prefix: &mut Vec<u8>, keys: &mut Vec<String>) {
+        prefix.extend_from_slice(&self.label);
+        if self.terminal {
+            keys.push(String::from_utf8_lossy(prefix).into_owned());
+        };
+        for child in &self.children {
+            child.collect(prefix, keys);
+        };
+        prefix.truncate(prefix.len() - self.label.len());
+    }
+
+    /// Returns the approximate size of the node and its children in bytes
+    fn approx_size(&self) -> usize {
+        std::mem::size_of::<PrefixNode>() + self.label.len() +
+            self.children.iter().map(|c| c.approx_size()).sum::<usize>()
+    }
+}
+
+/// Radix tree of the String primary Fields of a Table configured with
+/// TableBuilder::prefix_index; bytes shared by several keys are stored once, and the keys
+/// starting with a prefix are found without visiting the rest of the Table.
+#[derive(Clone, Default)]
+pub(crate) struct PrefixIndex {
+    root: PrefixNode,
+    len: usize,
+}
+
+impl PrefixIndex {
+    /// Adds the key, returning true if it was not already held
+    pub(crate) fn insert(&mut self, key: &str) -> bool {
+        let added = self.root.insert(key.as_bytes());
+        if added {
+            self.len += 1;
+        };
+        added
+    }
+
+    /// Removes the key, returning true if it was held
+    pub(crate) fn remove(&mut self, key: &str) -> bool {
+        let removed = self.root.remove(key.as_bytes());
+        if removed {
+            self.len -= 1;
+        };
+        removed
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns every key starting with the supplied prefix, in sorted order
+    pub(crate) fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        let mut node = &self.root;
+        let mut path = Vec::new();
+        let mut rest = prefix.as_bytes();
+
+        while !rest.is_empty() {
+            let child = match node.child(rest[0]) {
+                Ok(p) => &node.children[p],
+                Err(_) => return keys,
+            };
+            let shared = child.label.iter().zip(rest).take_while(|(a, b)| a == b).count();
+            if shared < rest.len() && shared < child.label.len() {
+                return keys
+            };
+            if shared == rest.len() {
+                child.collect(&mut path, &mut keys);
+                return keys
+            };
+            path.extend_from_slice(&child.label);
+            rest = &rest[shared..];
+            node = child;
+        };
+
+        node.collect(&mut path, &mut keys);
+        keys
+    }
+
+    /// Returns the approximate in-memory size of the index in bytes
+    pub(crate) fn approx_size(&self) -> usize {
+        self.root.approx_size()
+    }
+}
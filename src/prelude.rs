@@ -1,23 +1,118 @@
 use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::sync::mpsc::Receiver;
+use std::time::Duration;
 #[cfg(feature = "mocks")]
 use mockall::automock;
 
 use crate::structs::*;
+use crate::condition::*;
+use crate::filter::Filter;
+use crate::stats::*;
+use crate::cancellation::*;
+use crate::plan::*;
+use crate::join::*;
+use crate::verify::*;
+use crate::merge::*;
+use crate::export::*;
+use crate::ratelimit::*;
+use crate::sessions::*;
+use crate::flags::*;
+use crate::counters::*;
+use crate::lease::*;
+use crate::snapshot::*;
+use crate::dryrun::*;
+use crate::copy::*;
+use crate::lifecycle::*;
 use crate::errors::*;
 
+/// Resolves a conflict between the local and remote versions of an Entry; see
+/// DatabaseClient::merge_from_with
+pub type Resolver<'a> = dyn FnMut(&Entry, &Entry) -> Resolution + 'a;
+
+/// Changes an Entry in place; see DatabaseClient::update_many and DatabaseClient::modify
+pub type EntryUpdate<'a> = dyn FnMut(&mut Entry) + 'a;
+
+// Closures are passed through the Resolver and EntryUpdate aliases, and the lifetimes of
+// borrowed arguments are named, as mockall cannot generate MockDatabaseClient otherwise
 #[cfg_attr(feature = "mocks", automock)]
 pub trait DatabaseClient {
     fn save(self: &mut Self) -> Result<(), DatabaseError>;
+    fn compact(self: &mut Self) -> Result<u64, DatabaseError>;
     fn create_table(self: &mut Self, table: Table) -> Result<(), DatabaseError>;
     fn list_tables(self: &mut Self) -> Result<Vec<String>, DatabaseError>;
     fn drop_table(self: &mut Self, table: &String) -> Result<(), DatabaseError>;
     fn insert(self: &mut Self, table: String, entry: Entry) -> Result<(), DatabaseError>;
-    fn insert_or_update(self: &mut Self, table: String, entry: Entry) -> Result<(), DatabaseError>;
+    fn insert_or_update(self: &mut Self, table: String, entry: Entry) -> Result<UpsertOutcome, DatabaseError>;
     fn update(self: &mut Self, table: String, entry: Entry) -> Result<(), DatabaseError>;
-    fn get(self: &mut Self, table: String, primary_field: Field) -> Result<Entry, DatabaseError>;
+    fn get(self: &mut Self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError>;
+    fn exists(self: &mut Self, table: String, primary_field: Field) -> Result<bool, DatabaseError>;
+    fn time_to_live(self: &mut Self, table: String, primary_field: Field) -> Result<Option<Duration>, DatabaseError>;
+    fn history(self: &mut Self, table: String, primary_field: Field) -> Result<Vec<Arc<Entry>>, DatabaseError>;
     fn delete(self: &mut Self, table: String, primary_field: Field) -> Result<(), DatabaseError>;
     fn delete_many(self: &mut Self, table: String, criteria: HashMap<String, Field>) -> Result<u64, DatabaseError>;
-    fn scan(self: &mut Self, table: String) -> Result<Vec<Entry>, DatabaseError>;
-    fn query(self: &mut Self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Entry>, DatabaseError>;
+    fn delete_many_cancellable(self: &mut Self, table: String, criteria: HashMap<String, Field>, token: &CancellationToken) -> Result<u64, DatabaseError>;
+    fn truncate(self: &mut Self, table: String) -> Result<u64, DatabaseError>;
+    fn scan(self: &mut Self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn scan_prefix(self: &mut Self, table: String, prefix: String) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn scan_cancellable(self: &mut Self, table: String, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn query(self: &mut Self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn query_cancellable(self: &mut Self, table: String, criteria: HashMap<String, Field>, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn query_where(self: &mut Self, table: String, conditions: HashMap<String, Condition>) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn query_where_cancellable(self: &mut Self, table: String, conditions: HashMap<String, Condition>, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn query_to_writer(self: &mut Self, table: String, criteria: HashMap<String, Field>, format: ExportFormat, writer: &mut dyn Write) -> Result<usize, DatabaseError>;
+    fn lookup_join(self: &mut Self, left_table: String, right_table: String, left_field: String, options: JoinOptions) -> Result<Vec<JoinedEntry>, DatabaseError>;
+    fn explain(self: &mut Self, table: String, conditions: HashMap<String, Condition>) -> Result<QueryPlan, DatabaseError>;
+    fn project(self: &mut Self, table: String, field: String) -> Result<Vec<(Field, Field)>, DatabaseError>;
     fn prune(self: &mut Self) -> Result<(), DatabaseError>;
-}
\ No newline at end of file
+    fn reload(self: &mut Self) -> Result<bool, DatabaseError>;
+    fn merge_from(self: &mut Self, path: &Path) -> Result<MergeReport, DatabaseError>;
+    fn merge_from_with<'a>(self: &mut Self, path: &Path, resolver: &'a mut Resolver<'a>) -> Result<MergeReport, DatabaseError>;
+    fn verify(self: &mut Self) -> Result<VerifyReport, DatabaseError>;
+    fn stats(self: &mut Self) -> Result<DatabaseStats, DatabaseError>;
+    fn rate_limiter(self: &mut Self, table: String, limit: RateLimit) -> Result<RateLimiter, DatabaseError>;
+    fn session_store(self: &mut Self, table: String, idle_timeout: Duration) -> Result<SessionStore, DatabaseError>;
+    fn flag_store(self: &mut Self, table: String) -> Result<FlagStore, DatabaseError>;
+    fn counter_store(self: &mut Self, table: String, retention: CounterRetention) -> Result<CounterStore, DatabaseError>;
+    fn acquire_lease(self: &mut Self, name: String, ttl: Duration) -> Result<Option<Lease>, DatabaseError>;
+    fn gc_files(self: &mut Self, retention: Duration) -> Result<Vec<PathBuf>, DatabaseError>;
+    #[cfg(feature = "zstd")]
+    fn train_dictionary(self: &mut Self, max_size: usize) -> Result<usize, DatabaseError>;
+    fn snapshot(self: &mut Self) -> Result<Snapshot, DatabaseError>;
+    fn take_dry_run_actions(self: &mut Self) -> Result<Vec<DryRunAction>, DatabaseError>;
+    fn with_context(self: &mut Self, context: String) -> Box<dyn DatabaseClient>;
+    fn set_meta(self: &mut Self, key: String, value: String) -> Result<Option<String>, DatabaseError>;
+    fn get_meta(self: &mut Self, key: String) -> Result<Option<String>, DatabaseError>;
+    fn remove_meta(self: &mut Self, key: String) -> Result<Option<String>, DatabaseError>;
+    fn insert_idempotent(self: &mut Self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError>;
+    fn insert_or_update_idempotent(self: &mut Self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError>;
+    fn update_idempotent(self: &mut Self, table: String, entry: Entry, idempotency_key: Option<String>) -> Result<bool, DatabaseError>;
+    fn write_sequence(self: &mut Self) -> Result<u64, DatabaseError>;
+    fn flush(self: &mut Self) -> Result<(), DatabaseError>;
+    fn flush_until(self: &mut Self, sequence: u64) -> Result<(), DatabaseError>;
+    fn scan_versioned(self: &mut Self, table: String) -> Result<(Vec<Arc<Entry>>, u64), DatabaseError>;
+    fn has_changed_since(self: &mut Self, sequence: u64) -> Result<bool, DatabaseError>;
+    fn non_blocking(self: &mut Self) -> Box<dyn DatabaseClient>;
+    fn try_get(self: &mut Self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError>;
+    fn try_scan(self: &mut Self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn try_query(self: &mut Self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn try_insert(self: &mut Self, table: String, entry: Entry) -> Result<(), DatabaseError>;
+    fn try_insert_or_update(self: &mut Self, table: String, entry: Entry) -> Result<UpsertOutcome, DatabaseError>;
+    fn try_update(self: &mut Self, table: String, entry: Entry) -> Result<(), DatabaseError>;
+    fn try_delete(self: &mut Self, table: String, primary_field: Field) -> Result<(), DatabaseError>;
+    #[allow(clippy::needless_lifetimes)]
+    fn copy_into<'a>(self: &mut Self, other: &mut dyn DatabaseClient, tables: &[&'a str], options: CopyOptions) -> Result<CopyReport, DatabaseError>;
+    fn attach_table_from(self: &mut Self, path: &Path, table: &str, new_name: &str) -> Result<usize, DatabaseError>;
+    fn scan_stale(self: &mut Self, table: String, older_than: Duration) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn touch_many(self: &mut Self, table: String, keys: &[Field]) -> Result<u64, DatabaseError>;
+    fn subscribe_lifecycle(self: &mut Self) -> Receiver<LifecycleEvent>;
+    fn expiring_within(self: &mut Self, table: String, within: Duration) -> Result<Vec<(Arc<Entry>, Duration)>, DatabaseError>;
+    fn patch(self: &mut Self, table: String, primary_field: Field, fields: HashMap<String, Field>) -> Result<Arc<Entry>, DatabaseError>;
+    fn unset_fields(self: &mut Self, table: String, primary_field: Field, fields: &[String]) -> Result<Arc<Entry>, DatabaseError>;
+    fn query_filter(self: &mut Self, table: String, filter: Filter) -> Result<Vec<Arc<Entry>>, DatabaseError>;
+    fn delete_many_limit(self: &mut Self, table: String, criteria: HashMap<String, Field>, max: u64) -> Result<DeleteBatch, DatabaseError>;
+    fn update_many<'a>(self: &mut Self, table: String, criteria: HashMap<String, Field>, update: &'a mut EntryUpdate<'a>) -> Result<u64, DatabaseError>;
+    fn modify<'a>(self: &mut Self, table: String, primary_field: Field, modify: &'a mut EntryUpdate<'a>) -> Result<(), DatabaseError>;
+}
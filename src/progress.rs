@@ -0,0 +1,153 @@
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// Number of bytes read or written between reports while reading or writing the database
+/// file
+pub const PROGRESS_BYTES: usize = 1 << 20;
+
+/// Number of entries exported or copied between reports
+pub const PROGRESS_ENTRIES: u64 = 1000;
+
+/// Operation of a Client whose progress is reported to the closure set by
+/// ClientBuilder::progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProgressOperation {
+    /// ClientBuilder::open
+    Open,
+    /// DatabaseClient::save, including the saves of the background threads of the Client
+    Save,
+    /// DatabaseClient::compact
+    Compact,
+    /// DatabaseClient::query_to_writer
+    Export,
+    /// DatabaseClient::copy_into
+    Copy,
+}
+
+/// Step of a ProgressOperation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProgressPhase {
+    /// Reading the database file
+    Reading,
+    /// Decompressing and deserializing the contents of the database file
+    Decoding,
+    /// Serializing the database
+    Serializing,
+    /// Compressing the serialized database
+    Compressing,
+    /// Writing the database file
+    Writing,
+    /// Exporting or copying entries
+    Entries,
+    /// The operation completed; bytes and entries are its totals
+    Done,
+}
+
+/// Progress of a ProgressOperation, passed to the closure set by ClientBuilder::progress
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub operation: ProgressOperation,
+    pub phase: ProgressPhase,
+    /// Bytes processed so far by the phase
+    pub bytes: u64,
+    /// Bytes the phase will process, if known
+    pub total_bytes: Option<u64>,
+    /// Entries processed so far by the phase
+    pub entries: u64,
+    /// Entries the phase will process, if known
+    pub total_entries: Option<u64>,
+    /// Time since the operation started
+    pub elapsed: Duration,
+}
+
+/// Closure to which a Client reports the progress of its long operations
+pub(crate) type Reporter = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Reports the progress of one operation to the reporter of a Client, if it has one
+pub(crate) struct Tracker {
+    operation: ProgressOperation,
+    reporter: Option<(Reporter, Arc<dyn Clock>, Instant)>,
+}
+
+impl Tracker {
+    pub(crate) fn new(reporter: &Option<Reporter>, clock: &Arc<dyn Clock>, operation: ProgressOperation) -> Self {
+        Tracker{
+            operation,
+            reporter: reporter.as_ref().map(|r| (r.clone(), clock.clone(), clock.instant())),
+        }
+    }
+
+    /// Returns a Tracker reporting to nothing
+    pub(crate) fn none() -> Self {
+        Tracker{
+            operation: ProgressOperation::Open,
+            reporter: None,
+        }
+    }
+
+    fn report(&self, phase: ProgressPhase, bytes: (u64, Option<u64>), entries: (u64, Option<u64>)) {
+        if let Some((reporter, clock, started)) = &self.reporter {
+            reporter(&Progress{
+                operation: self.operation,
+                phase,
+                bytes: bytes.0,
+                total_bytes: bytes.1,
+                entries: entries.0,
+                total_entries: entries.1,
+                elapsed: clock.instant().saturating_duration_since(*started),
+            });
+        };
+    }
+
+    /// Reports the bytes processed by the phase
+    pub(crate) fn bytes(&self, phase: ProgressPhase, bytes: u64, total: Option<u64>) {
+        self.report(phase, (bytes, total), (0, None));
+    }
+
+    /// Reports the entries processed by the phase
+    pub(crate) fn entries(&self, phase: ProgressPhase, entries: u64, total: Option<u64>) {
+        self.report(phase, (0, None), (entries, total));
+    }
+
+    /// Reports the completion of the operation
+    pub(crate) fn done(&self, bytes: u64, entries: u64) {
+        self.report(ProgressPhase::Done, (bytes, Some(bytes)), (entries, Some(entries)));
+    }
+
+    /// Writes the buffer, reporting ProgressPhase::Writing every PROGRESS_BYTES
+    pub(crate) fn write_all(&self, writer: &mut dyn Write, buffer: &[u8]) -> io::Result<()> {
+        if self.reporter.is_none() {
+            return writer.write_all(buffer)
+        };
+        let total = Some(buffer.len() as u64);
+        let mut written = 0;
+        self.bytes(ProgressPhase::Writing, 0, total);
+        for chunk in buffer.chunks(PROGRESS_BYTES) {
+            writer.write_all(chunk)?;
+            written += chunk.len() as u64;
+            self.bytes(ProgressPhase::Writing, written, total);
+        };
+        Ok(())
+    }
+
+    /// Reads the reader to its end, reporting ProgressPhase::Reading every PROGRESS_BYTES
+    pub(crate) fn read_to_end(&self, reader: &mut dyn Read, buffer: &mut Vec<u8>, total: Option<u64>) -> io::Result<()> {
+        if self.reporter.is_none() {
+            return reader.read_to_end(buffer).map(|_| ())
+        };
+        if let Some(total) = total {
+            buffer.reserve(total as usize);
+        };
+        self.bytes(ProgressPhase::Reading, 0, total);
+        loop {
+            let read = (&mut *reader).take(PROGRESS_BYTES as u64).read_to_end(buffer)?;
+            if read == 0 {
+                return Ok(())
+            };
+            self.bytes(ProgressPhase::Reading, buffer.len() as u64, total);
+        };
+    }
+}
@@ -0,0 +1,252 @@
+//! Rate limiting per key, stored in a Table of the database so that limits are shared by
+//! every clone of a Client and survive restarts.
+//!
+//! Each key is checked and updated under a single write lock, and the Table expires keys
+//! once they would be back at their full allowance, so idle keys are removed by
+//! DatabaseClient::prune rather than accumulating.
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+use crate::Client;
+use crate::observer::Operation;
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+const TOKENS: &str = "Tokens";
+const UPDATED: &str = "Updated";
+const WINDOW: &str = "Window";
+const CURRENT: &str = "Current";
+const PREVIOUS: &str = "Previous";
+
+/// Algorithm and limit applied by a RateLimiter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimit {
+    /// Each key holds up to capacity tokens, regaining one every refill_every; requests
+    /// consume tokens and are denied when too few remain.  Allows bursts of up to capacity.
+    TokenBucket {
+        capacity: u64,
+        refill_every: Duration,
+    },
+    /// Each key may make up to limit requests in any window, estimated from the counts of
+    /// the current and previous fixed windows weighted by their overlap with the window
+    /// ending now.
+    SlidingWindow {
+        limit: u64,
+        window: Duration,
+    },
+}
+
+impl RateLimit {
+    /// Returns the Table storing the state of each key under this RateLimit
+    fn table(&self, name: String) -> Result<Table, DatabaseError> {
+        let builder = Table::new()
+            .name(name)
+            .primary_field(FieldType::String)?;
+        let builder = match self {
+            RateLimit::TokenBucket{ capacity, refill_every } => builder
+                .add_field(TOKENS.to_string(), FieldType::U64)?
+                .add_field(UPDATED.to_string(), FieldType::Date)?
+                .add_expiration(refill_every.saturating_mul((*capacity).min(u32::MAX as u64) as u32)),
+            RateLimit::SlidingWindow{ window, .. } => builder
+                .add_field(WINDOW.to_string(), FieldType::Date)?
+                .add_field(CURRENT.to_string(), FieldType::U64)?
+                .add_field(PREVIOUS.to_string(), FieldType::U64)?
+                .add_expiration(window.saturating_mul(2)),
+        };
+        builder.build()
+    }
+}
+
+/// Outcome of RateLimiter::check
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateDecision {
+    /// True if the request is within the limit and has been counted
+    pub allowed: bool,
+    /// Number of further requests of cost 1 allowed now
+    pub remaining: u64,
+    /// If denied, the earliest time after which the request may be allowed; None if the
+    /// request can never be allowed because its cost exceeds the limit
+    pub retry_after: Option<Duration>,
+}
+
+/// Limits the rate of requests per key, using a Table of the database of a Client; see
+/// DatabaseClient::rate_limiter
+pub struct RateLimiter {
+    client: Client,
+    table: String,
+    limit: RateLimit,
+}
+
+impl RateLimiter {
+    /// Returns a RateLimiter storing its state in the named Table of the client, creating
+    /// the Table if it does not exist
+    pub(crate) fn new(client: &Client, table: String, limit: RateLimit) -> Result<Self, DatabaseError> {
+        let mut client = client.clone();
+        if !client.list_tables()?.contains(&table) {
+            debug!("Creating rate limit table {}", table);
+            client.create_table(limit.table(table.clone())?)?;
+        };
+
+        Ok(RateLimiter{
+            client,
+            table,
+            limit,
+        })
+    }
+
+    /// Returns the RateLimit applied
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Counts a request of cost 1 for the key if it is within the limit
+    pub fn check(&mut self, key: &str) -> Result<RateDecision, DatabaseError> {
+        self.check_n(key, 1)
+    }
+
+    /// Counts a request of the supplied cost for the key if it is within the limit; a
+    /// denied request is not counted
+    pub fn check_n(&mut self, key: &str, cost: u64) -> Result<RateDecision, DatabaseError> {
+        let limit = self.limit;
+        let primary_field = Field::String(key.to_string());
+        self.client.read_modify_write(Operation::InsertOrUpdate, &self.table, &primary_field, |current, now| {
+            let (fields, decision) = match limit {
+                RateLimit::TokenBucket{ capacity, refill_every } => token_bucket(current.as_deref(), now, capacity, refill_every, cost),
+                RateLimit::SlidingWindow{ limit, window } => sliding_window(current.as_deref(), now, limit, window, cost),
+            };
+
+            let mut entry = Entry::new().set_primary_field(primary_field.clone())?;
+            for (name, value) in fields {
+                entry = entry.add_field(name.to_string(), value)?;
+            };
+            Ok((Some(entry.build()?), decision))
+        })
+    }
+
+    /// Forgets the requests counted for the key
+    pub fn reset(&mut self, key: &str) -> Result<(), DatabaseError> {
+        match self.client.delete(self.table.clone(), Field::String(key.to_string())) {
+            Ok(_) | Err(DatabaseError::EntryDoesNotExists) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Returns the value of a U64 field of the Entry, or 0
+fn count(entry: &Entry, name: &str) -> u64 {
+    match entry.fields.get(name) {
+        Some(Field::U64(v)) => *v,
+        _ => 0,
+    }
+}
+
+/// Returns the value of a Date field of the Entry, or now
+fn date(entry: &Entry, name: &str, now: SystemTime) -> SystemTime {
+    match entry.fields.get(name) {
+        Some(Field::Date(v)) => *v,
+        _ => now,
+    }
+}
+
+fn token_bucket(current: Option<&Entry>, now: SystemTime, capacity: u64, refill_every: Duration, cost: u64) -> (Vec<(&'static str, Field)>, RateDecision) {
+    let (mut tokens, mut updated) = match current {
+        Some(e) => (count(e, TOKENS), date(e, UPDATED, now)),
+        None => (capacity, now),
+    };
+
+    // Add the tokens regained since the last update, carrying over the time towards the
+    // next token so that frequent checks do not lose partial refills
+    let elapsed = now.duration_since(updated).unwrap_or_default();
+    let refills = match refill_every.is_zero() {
+        true => capacity as u128,
+        false => elapsed.as_nanos() / refill_every.as_nanos(),
+    };
+    tokens = (tokens as u128 + refills).min(capacity as u128) as u64;
+    if tokens == capacity || refill_every.is_zero() {
+        updated = now;
+    } else {
+        updated += refill_every.saturating_mul(refills.min(u32::MAX as u128) as u32);
+    };
+
+    let decision = match tokens >= cost {
+        true => {
+            tokens -= cost;
+            RateDecision{
+                allowed: true,
+                remaining: tokens,
+                retry_after: None,
+            }
+        },
+        false => {
+            let since_refill = now.duration_since(updated).unwrap_or_default();
+            let retry_after = match cost <= capacity {
+                true => Some(refill_every.saturating_mul((cost - tokens).min(u32::MAX as u64) as u32).saturating_sub(since_refill)),
+                false => None,
+            };
+            RateDecision{
+                allowed: false,
+                remaining: tokens,
+                retry_after,
+            }
+        },
+    };
+
+    (vec![(TOKENS, Field::U64(tokens)), (UPDATED, Field::Date(updated))], decision)
+}
+
+fn sliding_window(current: Option<&Entry>, now: SystemTime, limit: u64, window: Duration, cost: u64) -> (Vec<(&'static str, Field)>, RateDecision) {
+    let (mut start, mut counted, mut previous) = match current {
+        Some(e) => (date(e, WINDOW, now), count(e, CURRENT), count(e, PREVIOUS)),
+        None => (now, 0, 0),
+    };
+
+    let mut elapsed = now.duration_since(start).unwrap_or_default();
+    if window.is_zero() || elapsed >= window.saturating_mul(2) {
+        start = now;
+        elapsed = Duration::ZERO;
+        counted = 0;
+        previous = 0;
+    } else if elapsed >= window {
+        start += window;
+        elapsed -= window;
+        previous = counted;
+        counted = 0;
+    };
+
+    let overlap = match window.is_zero() {
+        true => 0.0,
+        false => 1.0 - elapsed.as_secs_f64() / window.as_secs_f64(),
+    };
+    let estimate = (previous as f64 * overlap).ceil() as u64 + counted;
+
+    let decision = match estimate.saturating_add(cost) <= limit {
+        true => {
+            counted += cost;
+            RateDecision{
+                allowed: true,
+                remaining: limit - estimate - cost,
+                retry_after: None,
+            }
+        },
+        false => {
+            // The previous window's share decays linearly; wait until it has decayed
+            // enough, or for the next window if the current one alone is over the limit
+            let retry_after = match (cost <= limit, previous > 0 && counted + cost <= limit) {
+                (false, _) => None,
+                (true, true) => {
+                    let decayed = 1.0 - (limit - counted - cost) as f64 / previous as f64;
+                    Some(window.mul_f64(decayed.clamp(0.0, 1.0)).saturating_sub(elapsed))
+                },
+                (true, false) => Some(window - elapsed),
+            };
+            RateDecision{
+                allowed: false,
+                remaining: limit.saturating_sub(estimate),
+                retry_after,
+            }
+        },
+    };
+
+    (vec![(WINDOW, Field::Date(start)), (CURRENT, Field::U64(counted)), (PREVIOUS, Field::U64(previous))], decision)
+}
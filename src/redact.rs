@@ -0,0 +1,51 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::structs::Field;
+use crate::sync::{RwLock, shared_static};
+
+/// Text shown in place of the value of a sensitive field
+pub const REDACTED: &str = "<redacted>";
+
+shared_static! {
+    /// Names of the fields marked sensitive by any Table built or loaded by the process; an
+    /// Entry does not know its Table, so is masked by field name alone
+    static SENSITIVE_FIELDS: RwLock<BTreeSet<String>> = RwLock::new(BTreeSet::new());
+}
+
+/// Records the fields as sensitive for the rest of the process
+pub(crate) fn register<'a, I: IntoIterator<Item = &'a String>>(fields: I) {
+    let mut fields = fields.into_iter().peekable();
+    if fields.peek().is_none() {
+        return
+    };
+    let mut sensitive = SENSITIVE_FIELDS.write().unwrap_or_else(|e| e.into_inner());
+    for field in fields {
+        if !sensitive.contains(field) {
+            sensitive.insert(field.clone());
+        };
+    };
+}
+
+/// Returns true if a Table built or loaded by the process marks the field as sensitive;
+/// see TableBuilder::sensitive_field
+pub fn is_sensitive(field: &str) -> bool {
+    SENSITIVE_FIELDS.read().map(|s| s.contains(field)).unwrap_or(true)
+}
+
+/// Formats the fields of an Entry with the values of sensitive fields masked
+pub(crate) struct RedactedFields<'a>(pub &'a HashMap<String, Field>);
+
+impl fmt::Debug for RedactedFields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0 {
+            if is_sensitive(name) {
+                map.entry(name, &format_args!("{}", REDACTED));
+            } else {
+                map.entry(name, value);
+            };
+        };
+        map.finish()
+    }
+}
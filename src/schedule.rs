@@ -0,0 +1,93 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use crate::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+/// Returns a random Duration of at most max, used to spread out background work; seeded
+/// by the per-process randomness of the standard library
+pub(crate) fn jitter(max: Duration) -> Duration {
+    let nanos = max.as_nanos().min(u64::MAX as u128) as u64;
+    if nanos == 0 {
+        return Duration::ZERO
+    };
+    let random = RandomState::new().hash_one(Instant::now());
+    Duration::from_nanos(random % (nanos + 1))
+}
+
+/// Limit on the number of background saves running at once, shared by the Clients given it
+/// through ClientBuilder::save_gate; so that many databases on a host do not all write
+/// their files together.
+///
+/// Saves of a Client waiting on the gate are delayed rather than skipped; explicit calls
+/// to DatabaseClient::save are not limited.  Clones of a SaveGate share its limit.
+/// ```
+/// use persistent_keystore_rs::{Client, SaveGate};
+/// use std::path::Path;
+/// use std::time::Duration;
+/// let gate = SaveGate::new(2);
+/// let clients: Vec<_> = (0..4).map(|n| {
+///     Client::builder(Path::new(&format!("savegate{}.db", n)))
+///         .sync_interval(Duration::from_millis(30))
+///         .sync_jitter(Duration::from_millis(10))
+///         .save_gate(gate.clone())
+///         .build().unwrap()
+/// }).collect();
+/// assert!(gate.active() <= 2);
+/// # drop(clients);
+/// # for n in 0..4 { std::fs::remove_file(format!("savegate{}.db", n)).unwrap(); };
+/// ```
+#[derive(Clone, Debug)]
+pub struct SaveGate {
+    state: Arc<(Mutex<usize>, Condvar)>,
+    max_concurrent: usize,
+}
+
+/// Permit to run a background save, returned to its SaveGate when dropped
+pub(crate) struct SavePermit {
+    gate: SaveGate,
+}
+
+impl Drop for SavePermit {
+    fn drop(&mut self) {
+        let (active, released) = &*self.gate.state;
+        *active.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        released.notify_one();
+    }
+}
+
+impl SaveGate {
+    /// Returns a SaveGate allowing at most max_concurrent background saves at once; at
+    /// least one save is always allowed
+    pub fn new(max_concurrent: usize) -> Self {
+        SaveGate{
+            state: Arc::new((Mutex::new(0), Condvar::new())),
+            max_concurrent: max_concurrent.max(1),
+        }
+    }
+
+    /// Returns the number of background saves allowed at once
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Returns the number of background saves running
+    pub fn active(&self) -> usize {
+        self.state.0.lock().map(|a| *a).unwrap_or(0)
+    }
+
+    /// Waits until fewer than max_concurrent saves are running and returns a permit to run
+    /// another
+    pub(crate) fn acquire(&self) -> SavePermit {
+        let (active, released) = &*self.state;
+        let mut running = active.lock().unwrap_or_else(|e| e.into_inner());
+        while *running >= self.max_concurrent {
+            trace!("Waiting for one of {} background saves to complete", *running);
+            running = released.wait(running).unwrap_or_else(|e| e.into_inner());
+        };
+        *running += 1;
+        SavePermit{
+            gate: self.clone(),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+#[cfg(feature = "zeroize")]
+use std::fmt;
+#[cfg(feature = "zeroize")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+#[cfg(feature = "zeroize")]
+use crate::errors::*;
+#[cfg(feature = "zeroize")]
+use crate::redact::REDACTED;
+
+/// Zeroizes a buffer that held the serialized database once it is no longer needed, when
+/// the `zeroize` feature is enabled
+pub(crate) fn wipe(_buffer: &mut Vec<u8>) {
+    #[cfg(feature = "zeroize")]
+    _buffer.zeroize();
+}
+
+/// Sensitive string value, such as a password or API key, stored as Field::Secret; its
+/// buffer is zeroized when dropped rather than left in freed memory.  Requires the
+/// `zeroize` feature.
+///
+/// Debug and Display show REDACTED in place of the value, which is read with expose.
+/// Copies of the value made outside the Secret, such as by expose().to_string(), are not
+/// zeroized.  See exclude_from_core_dumps to keep values out of core dumps.
+/// ```
+/// use persistent_keystore_rs::{Field, Secret};
+///
+/// let password = Field::Secret(Secret::new("hunter2".to_string()));
+/// assert_eq!(format!("{}", password), "<redacted>");
+/// if let Field::Secret(s) = &password {
+///     assert_eq!(s.expose(), "hunter2");
+/// };
+/// ```
+#[cfg(feature = "zeroize")]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Secret(String);
+
+#[cfg(feature = "zeroize")]
+impl Secret {
+    /// Takes ownership of the value, without copying it
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    /// Returns the value of the Secret
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Clone for Secret {
+    /// Copies the value into a new buffer of exactly its length, so that no spare
+    /// capacity is left to be reallocated without being zeroized
+    fn clone(&self) -> Self {
+        let mut value = String::with_capacity(self.0.len());
+        value.push_str(&self.0);
+        Secret(value)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", REDACTED)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Excludes the memory of the whole process from core dumps, and stops other processes
+/// of the same user from attaching to it, so that Secrets held in memory are not written
+/// out when it crashes.  Individual values cannot be excluded without a dedicated
+/// allocator, so this applies to the process as a whole and cannot be undone.
+///
+/// Core dumps are disabled with prctl(PR_SET_DUMPABLE) on Linux and by setting the core
+/// file size limit to zero on other Unix systems.  On other platforms, or if the call
+/// fails, DatabaseError::DatabaseIoError is returned.  Requires the `zeroize` feature.
+#[cfg(feature = "zeroize")]
+pub fn exclude_from_core_dumps() -> Result<(), DatabaseError> {
+    Ok(disable_core_dumps()?)
+}
+
+#[cfg(all(feature = "zeroize", target_os = "linux"))]
+fn disable_core_dumps() -> std::io::Result<()> {
+    // SAFETY: prctl with PR_SET_DUMPABLE takes a single integer argument and touches no memory
+    match unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(all(feature = "zeroize", unix, not(target_os = "linux")))]
+fn disable_core_dumps() -> std::io::Result<()> {
+    let limit = libc::rlimit{rlim_cur: 0, rlim_max: 0};
+    // SAFETY: the limit is a valid rlimit that outlives the call
+    match unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(all(feature = "zeroize", not(unix)))]
+fn disable_core_dumps() -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "core dumps cannot be disabled on this platform"))
+}
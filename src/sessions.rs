@@ -0,0 +1,179 @@
+//! Sessions of users, stored in a Table of the database so that they are shared by every
+//! clone of a Client and survive restarts.
+//!
+//! A session expires once it has not been created or touched for the idle timeout of its
+//! SessionStore, and is then removed by DatabaseClient::prune.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+use crate::Client;
+use crate::observer::Operation;
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+const USER: &str = "User";
+const DATA: &str = "Data";
+const CREATED: &str = "Created";
+const LAST_SEEN: &str = "LastSeen";
+
+/// Session of a user held by a SessionStore
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    /// Identifier of the session, as handed to the client of the user; unguessable unless
+    /// the Client was given another IdGenerator, see ClientBuilder::id_generator
+    pub id: String,
+    /// Identifier of the user the session belongs to
+    pub user: String,
+    /// Application data attached to the session
+    pub data: String,
+    /// Time the session was created
+    pub created: SystemTime,
+    /// Time the session was created or last touched
+    pub last_seen: SystemTime,
+}
+
+impl Session {
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match (&entry.primary_field, entry.fields.get(USER), entry.fields.get(CREATED), entry.fields.get(LAST_SEEN)) {
+            (Field::String(id), Some(Field::String(user)), Some(Field::Date(created)), Some(Field::Date(last_seen))) => {
+                let data = match entry.fields.get(DATA) {
+                    Some(Field::String(d)) => d.clone(),
+                    _ => String::new(),
+                };
+                Some(Session{
+                    id: id.clone(),
+                    user: user.clone(),
+                    data,
+                    created: *created,
+                    last_seen: *last_seen,
+                })
+            },
+            _ => None,
+        }
+    }
+
+    fn to_entry(&self) -> Result<Entry, DatabaseError> {
+        Entry::new()
+            .set_primary_field(Field::String(self.id.clone()))?
+            .add_field(USER.to_string(), Field::String(self.user.clone()))?
+            .add_field(DATA.to_string(), Field::String(self.data.clone()))?
+            .add_field(CREATED.to_string(), Field::Date(self.created))?
+            .add_field(LAST_SEEN.to_string(), Field::Date(self.last_seen))?
+            .build()
+    }
+}
+
+/// Creates, looks up and revokes Sessions, using a Table of the database of a Client; see
+/// DatabaseClient::session_store
+pub struct SessionStore {
+    client: Client,
+    table: String,
+    idle_timeout: Duration,
+}
+
+impl SessionStore {
+    /// Returns a SessionStore storing its Sessions in the named Table of the client, creating
+    /// the Table if it does not exist
+    pub(crate) fn new(client: &Client, table: String, idle_timeout: Duration) -> Result<Self, DatabaseError> {
+        let mut client = client.clone();
+        if !client.list_tables()?.contains(&table) {
+            debug!("Creating session table {}", table);
+            let t = Table::new()
+                .name(table.clone())
+                .primary_field(FieldType::String)?
+                .add_field(USER.to_string(), FieldType::String)?
+                .add_field(DATA.to_string(), FieldType::String)?
+                .add_field(CREATED.to_string(), FieldType::Date)?
+                .add_field(LAST_SEEN.to_string(), FieldType::Date)?
+                .add_index(vec![USER.to_string()])?
+                .add_expiration(idle_timeout)
+                .build()?;
+            client.create_table(t)?;
+        };
+
+        Ok(SessionStore{
+            client,
+            table,
+            idle_timeout,
+        })
+    }
+
+    /// Returns the time after which an untouched Session expires
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Creates and returns a new Session for the user, holding the supplied data
+    pub fn create_session(&mut self, user: &str, data: String) -> Result<Session, DatabaseError> {
+        let id = self.client.ids.next_id();
+        let user = user.to_string();
+        self.client.read_modify_write(Operation::Insert, &self.table, &Field::String(id.clone()), |current, now| {
+            if current.is_some() {
+                return Err(DatabaseError::EntryExists)
+            };
+            let session = Session{
+                id,
+                user,
+                data,
+                created: now,
+                last_seen: now,
+            };
+            Ok((Some(session.to_entry()?), session))
+        })
+    }
+
+    /// Returns the Session with the supplied id, or None if it does not exist or has
+    /// expired.  The Session is not touched.
+    pub fn get_session(&mut self, id: &str) -> Result<Option<Session>, DatabaseError> {
+        self.client.read_modify_write(Operation::Get, &self.table, &Field::String(id.to_string()), |current, _| {
+            Ok((None, current.as_deref().and_then(Session::from_entry)))
+        })
+    }
+
+    /// Extends the Session with the supplied id by the idle timeout, returning it; or None
+    /// if it does not exist or has expired
+    pub fn touch(&mut self, id: &str) -> Result<Option<Session>, DatabaseError> {
+        self.client.read_modify_write(Operation::Update, &self.table, &Field::String(id.to_string()), |current, now| {
+            match current.as_deref().and_then(Session::from_entry) {
+                Some(mut session) => {
+                    session.last_seen = now;
+                    Ok((Some(session.to_entry()?), Some(session)))
+                },
+                None => Ok((None, None)),
+            }
+        })
+    }
+
+    /// Replaces the data of the Session with the supplied id and touches it, returning the
+    /// Session; or None if it does not exist or has expired
+    pub fn set_data(&mut self, id: &str, data: String) -> Result<Option<Session>, DatabaseError> {
+        self.client.read_modify_write(Operation::Update, &self.table, &Field::String(id.to_string()), |current, now| {
+            match current.as_deref().and_then(Session::from_entry) {
+                Some(mut session) => {
+                    session.data = data;
+                    session.last_seen = now;
+                    Ok((Some(session.to_entry()?), Some(session)))
+                },
+                None => Ok((None, None)),
+            }
+        })
+    }
+
+    /// Revokes the Session with the supplied id, returning true if it existed
+    pub fn revoke(&mut self, id: &str) -> Result<bool, DatabaseError> {
+        match self.client.delete(self.table.clone(), Field::String(id.to_string())) {
+            Ok(_) => Ok(true),
+            Err(DatabaseError::EntryDoesNotExists) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Revokes every Session of the user, returning the number revoked
+    pub fn revoke_all_for_user(&mut self, user: &str) -> Result<u64, DatabaseError> {
+        let mut criteria = HashMap::new();
+        criteria.insert(USER.to_string(), Field::String(user.to_string()));
+        self.client.delete_many(self.table.clone(), criteria)
+    }
+}
@@ -0,0 +1,125 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::error;
+
+use crate::structs::*;
+use crate::access::AccessControl;
+use crate::clock::Timestamp;
+use crate::condition::*;
+use crate::cancellation::*;
+use crate::export::{ExportFormat, ExportWriter};
+use crate::errors::*;
+use crate::logging::{Subsystem, log_event};
+use crate::observer::Operation;
+
+/// Immutable, point-in-time view of the database of a Client, returned by
+/// DatabaseClient::snapshot.
+///
+/// Reads from a Snapshot take no lock on the database, so writers to the Client proceed
+/// while long-running reads, such as exports, see the database as it was when the Snapshot
+/// was taken.  Reads are reported to the OperationObservers and authorizer of the Client,
+/// with its context, as reads through the Client are.  Conditions relative to the current time, such as Condition::OlderThan, are
+/// evaluated as of when the Snapshot was taken.  Clones of a Snapshot share its contents.
+///
+/// Files of externalized values removed by DatabaseClient::compact after the Snapshot was
+/// taken cannot be read through it.
+#[derive(Clone)]
+pub struct Snapshot {
+    database: Arc<Database>,
+    at: Timestamp,
+    access: AccessControl,
+}
+
+impl Snapshot {
+    pub(crate) fn new(database: Database, at: Timestamp, access: AccessControl) -> Self {
+        Snapshot{
+            database: Arc::new(database),
+            at,
+            access,
+        }
+    }
+
+    /// Returns the named Table as of when the Snapshot was taken
+    fn table(&self, table: &String) -> Result<Cow<'_, Table>, DatabaseError> {
+        match self.database.read_table(table, self.at) {
+            Ok(t) => Ok(t),
+            Err(_) => {
+                error!("Table {} does not exist", table);
+                Err(DatabaseError::TableDoesNotExist(table.clone()))
+            },
+        }
+    }
+
+    /// Returns the time the Snapshot was taken, by the Clock of its Client
+    pub fn taken_at(&self) -> SystemTime {
+        self.at.wall
+    }
+
+    /// Lists the tables of the Snapshot
+    pub fn list_tables(&self) -> Vec<String> {
+        self.database.list_tables()
+    }
+
+    /// Returns an entry from the specified table; see DatabaseClient::get
+    pub fn get(&self, table: String, primary_field: Field) -> Result<Arc<Entry>, DatabaseError> {
+        self.access.authorize(Operation::Get, &table, Some(&primary_field))?;
+        log_event!(Subsystem::Query, TRACE, "Getting entry {} from snapshot of table {}", primary_field, table);
+        self.table(&table)?.get(&primary_field)
+    }
+
+    /// Returns true if an entry exists within the specified table; see DatabaseClient::exists
+    pub fn exists(&self, table: String, primary_field: Field) -> Result<bool, DatabaseError> {
+        self.access.authorize(Operation::Get, &table, Some(&primary_field))?;
+        log_event!(Subsystem::Query, TRACE, "Checking for entry {} in snapshot of table {}", primary_field, table);
+        Ok(self.table(&table)?.exists(&primary_field))
+    }
+
+    /// Returns all entries of the specified table; see DatabaseClient::scan
+    pub fn scan(&self, table: String) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.access.authorize(Operation::Scan, &table, None)?;
+        log_event!(Subsystem::Query, DEBUG, "Scanning snapshot of table {}", table);
+        self.table(&table)?.scan()
+    }
+
+    /// Query for entries within a specified table meeting the supplied criteria; see
+    /// DatabaseClient::query
+    pub fn query(&self, table: String, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where(table, equals_all(criteria))
+    }
+
+    /// Query for entries within a specified table meeting every supplied Condition; see
+    /// DatabaseClient::query_where
+    pub fn query_where(&self, table: String, conditions: HashMap<String, Condition>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.query_where_cancellable(table, conditions, &CancellationToken::new())
+    }
+
+    /// Query for entries within a specified table meeting every supplied Condition, as
+    /// query_where.
+    /// If the token is cancelled before the query completes, DatabaseError::Cancelled is returned.
+    pub fn query_where_cancellable(&self, table: String, conditions: HashMap<String, Condition>, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.access.authorize(Operation::Query, &table, None)?;
+        log_event!(Subsystem::Query, DEBUG, "Querying snapshot of table {}", table);
+        self.table(&table)?.filter_cancellable(&conditions, self.at.wall, token)
+    }
+
+    /// Writes the entries within a specified table meeting the supplied criteria to writer
+    /// in the supplied ExportFormat, returning the number of entries written; see
+    /// DatabaseClient::query_to_writer.  Unlike the Client, the database is not locked
+    /// while writing.
+    pub fn query_to_writer(&self, table: String, criteria: HashMap<String, Field>, format: ExportFormat, writer: &mut dyn Write) -> Result<usize, DatabaseError> {
+        self.access.authorize(Operation::Query, &table, None)?;
+        let t = self.table(&table)?;
+        log_event!(Subsystem::Query, DEBUG, "Exporting snapshot of table {} as {:?}", table, format);
+        let mut export = ExportWriter::new(format, &t, writer)?;
+        let mut written = 0;
+        t.for_each_match(&equals_all(criteria), self.at.wall, &CancellationToken::new(), |entry| {
+            written += 1;
+            export.write(&entry)
+        })?;
+        export.flush()?;
+        Ok(written)
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+use serde_derive::{Serialize, Deserialize};
+use tracing::{debug, error, trace};
+
+use crate::checksum;
+use crate::sync;
+use crate::structs::*;
+use crate::errors::*;
+
+/// Returns the path of the spill segment of the named Table of the database at the supplied
+/// path; the table name is hashed so that any name results in a valid file name
+pub(crate) fn segment_path(database: &Path, table: &str) -> PathBuf {
+    let mut name = database.as_os_str().to_owned();
+    name.push(format!(".{:016x}.spill", checksum(table.as_bytes())));
+    PathBuf::from(name)
+}
+
+/// Returns the error for an Entry spilled to a segment that has not been attached
+fn detached(key: &Field) -> DatabaseError {
+    let message = format!("spill segment is not attached for entry {}", key);
+    DatabaseError::DatabaseIoError(std::io::Error::new(std::io::ErrorKind::NotFound, message))
+}
+
+/// Location of a spilled Entry within the segment file
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct SpillRecord {
+    offset: u64,
+    len: u64,
+    last_timestamp: Option<SystemTime>,
+    /// Bookkeeping of the Table for the Entry; rebuilt by Table::rebase after loading
+    #[serde(skip)]
+    state: EntryState,
+}
+
+/// Open segment file, shared by clones of the Table
+struct SegmentFile {
+    path: PathBuf,
+    file: File,
+    end: u64,
+}
+
+/// On-disk overflow of a Table configured with TableBuilder::spill_after.
+///
+/// Spilled Entries are appended to the segment file as compressed records, and the location
+/// of each is kept with the Table and saved in the database file.  Records are never
+/// rewritten, so a database file remains valid for the segment after later writes; records
+/// superseded by an update or delete are counted as garbage.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct SpillSegment {
+    #[serde(serialize_with = "crate::ordered::map")]
+    records: HashMap<Field, SpillRecord, EntryHasher>,
+    garbage: u64,
+    #[serde(skip)]
+    file: Option<sync::Arc<sync::Mutex<SegmentFile>>>,
+}
+
+impl SpillSegment {
+    /// Opens the segment file at the supplied path, creating it if it does not exist
+    pub(crate) fn attach(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        debug!("Attaching spill segment {:?}", path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let end = file.metadata()?.len();
+        self.file = Some(sync::Arc::new(sync::Mutex::new(SegmentFile{
+            path: path.to_path_buf(),
+            file,
+            end,
+        })));
+        Ok(())
+    }
+
+    /// Returns the path of the attached segment file, if any
+    pub(crate) fn path(&self) -> Option<PathBuf> {
+        let file = self.file.as_ref()?.lock().ok()?;
+        Some(file.path.clone())
+    }
+
+    pub(crate) fn is_attached(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub(crate) fn contains_key(&self, key: &Field) -> bool {
+        self.records.contains_key(key)
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &Field> {
+        self.records.keys()
+    }
+
+    pub(crate) fn last_timestamp(&self, key: &Field) -> Option<SystemTime> {
+        self.records.get(key)?.last_timestamp
+    }
+
+    /// Returns the bookkeeping of the Table for the spilled Entry
+    pub(crate) fn state(&self, key: &Field) -> Option<EntryState> {
+        self.records.get(key).map(|r| r.state)
+    }
+
+    /// Returns the bookkeeping of the Table for the spilled Entry for modification
+    pub(crate) fn state_mut(&mut self, key: &Field) -> Option<&mut EntryState> {
+        self.records.get_mut(key).map(|r| &mut r.state)
+    }
+
+    /// Returns the number of bytes of the segment file held by superseded records
+    pub(crate) fn garbage(&self) -> u64 {
+        self.garbage
+    }
+
+    /// Reads the spilled Entry with the supplied primary Field from the segment file
+    fn read(&self, key: &Field) -> Result<Option<Entry>, DatabaseError> {
+        let record = match self.records.get(key) {
+            Some(r) => *r,
+            None => return Ok(None),
+        };
+        let file = match &self.file {
+            Some(f) => f,
+            None => return Err(detached(key)),
+        };
+
+        let mut buffer = vec![0; record.len as usize];
+        match file.lock() {
+            Ok(mut segment) => {
+                segment.file.seek(SeekFrom::Start(record.offset))?;
+                segment.file.read_exact(&mut buffer)?;
+            },
+            Err(_) => return Err(DatabaseError::UnableToGetLock),
+        };
+
+        let entry: Entry = bincode::deserialize(&decompress_size_prepended(&buffer)?)?;
+        if entry.primary_field != *key {
+            return Err(DatabaseError::InvalidFormat(format!("spill record for entry {} holds entry {}", key, entry.primary_field)))
+        };
+        Ok(Some(entry))
+    }
+
+    /// Returns the spilled Entry with the supplied primary Field; an Entry that cannot be
+    /// read is logged and treated as missing, and reported by verify
+    pub(crate) fn get(&self, key: &Field) -> Option<Arc<Entry>> {
+        match self.read(key) {
+            Ok(e) => e.map(Arc::new),
+            Err(e) => {
+                error!("Unable to read spilled entry {}: {}", key, e);
+                None
+            },
+        }
+    }
+
+    /// Appends the Entry to the segment file along with its bookkeeping, replacing any
+    /// record of the same Entry
+    pub(crate) fn write(&mut self, entry: &Entry, state: EntryState) -> Result<(), DatabaseError> {
+        let file = match &self.file {
+            Some(f) => f,
+            None => return Err(detached(&entry.primary_field)),
+        };
+
+        let compressed = compress_prepend_size(&bincode::serialize(entry)?);
+        let offset = match file.lock() {
+            Ok(mut segment) => {
+                let offset = segment.end;
+                segment.file.seek(SeekFrom::Start(offset))?;
+                segment.file.write_all(&compressed)?;
+                segment.end += compressed.len() as u64;
+                offset
+            },
+            Err(_) => return Err(DatabaseError::UnableToGetLock),
+        };
+
+        trace!("Spilled entry {} at offset {}", entry.primary_field, offset);
+        self.remove(&entry.primary_field);
+        self.records.insert(entry.primary_field.clone(), SpillRecord{
+            offset,
+            len: compressed.len() as u64,
+            last_timestamp: entry.last_timestamp,
+            state,
+        });
+        Ok(())
+    }
+
+    /// Removes the record of the Entry, returning true if it was spilled
+    pub(crate) fn remove(&mut self, key: &Field) -> bool {
+        match self.records.remove(key) {
+            Some(record) => {
+                self.garbage += record.len;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Reads and removes every spilled Entry, along with its bookkeeping
+    pub(crate) fn drain(&mut self) -> Result<Vec<(Entry, EntryState)>, DatabaseError> {
+        let mut entries = Vec::with_capacity(self.records.len());
+        for (key, record) in &self.records {
+            if let Some(entry) = self.read(key)? {
+                entries.push((entry, record.state));
+            };
+        };
+        self.records.clear();
+        self.garbage = 0;
+        Ok(entries)
+    }
+
+    /// Returns an iterator over every spilled Entry, read as they are reached
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Arc<Entry>> + '_ {
+        self.records.keys().filter_map(move |k| self.get(k))
+    }
+
+    /// Flushes the segment file to disk, so that the records referenced by a database file
+    /// about to be saved are durable
+    pub(crate) fn sync(&self) -> Result<(), DatabaseError> {
+        if let Some(file) = &self.file {
+            match file.lock() {
+                Ok(segment) => segment.file.sync_data()?,
+                Err(_) => return Err(DatabaseError::UnableToGetLock),
+            };
+        };
+        Ok(())
+    }
+
+    /// Returns a description of every record that cannot be read, along with its key
+    pub(crate) fn verify(&self) -> Vec<(Option<Field>, String)> {
+        let mut problems = Vec::new();
+        if !self.records.is_empty() && self.file.is_none() {
+            problems.push((None, format!("{} entries spilled but no segment is attached", self.records.len())));
+            return problems
+        };
+
+        for key in self.records.keys() {
+            if let Err(e) = self.read(key) {
+                problems.push((Some(key.clone()), format!("unable to read spilled entry: {}", e)));
+            };
+        };
+        problems
+    }
+}
+
+/// Removes the spill segment of the named Table of the database at the supplied path, if any
+pub(crate) fn remove_segment(database: &Path, table: &str) -> Result<(), DatabaseError> {
+    let path = segment_path(database, table);
+    match std::fs::remove_file(&path) {
+        Ok(_) => {
+            debug!("Removed spill segment {:?}", path);
+            Ok(())
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::ops::AddAssign;
+use std::time::{Duration, SystemTime};
+
+use crate::structs::Field;
+
+/// Usage and limits of a single Table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    /// Number of Entries within the Table
+    pub entries: usize,
+    /// Approximate in-memory size of the Entries in bytes
+    pub approx_bytes: usize,
+    /// Maximum number of Entries, if limited
+    pub max_entries: Option<usize>,
+    /// Number of Entries evicted to stay within capacity since the Table was loaded
+    pub evicted: u64,
+    /// Number of Entries spilled to disk; see TableBuilder::spill_after
+    pub spilled: usize,
+    /// Bytes of the spill segment held by superseded records
+    pub spill_garbage_bytes: u64,
+    /// Number of values stored in side files; see TableBuilder::externalize_above
+    pub externalized: usize,
+    /// Number of distinct String values shared between fields; see
+    /// TableBuilder::deduplicate_above
+    pub deduplicated: usize,
+    /// Approximate in-memory size of the prefix index in bytes; see
+    /// TableBuilder::prefix_index
+    pub prefix_index_bytes: usize,
+    /// Distribution of the approximate in-memory sizes of the Entries held in memory
+    pub entry_sizes: EntrySizeStats,
+    /// When the Table was created, if recorded
+    pub created_at: Option<SystemTime>,
+    /// When an Entry of the Table was last inserted, updated or deleted, if ever
+    pub last_modified_at: Option<SystemTime>,
+}
+
+/// Upper bounds in bytes of the buckets of EntrySizeStats::buckets; Entries larger than the
+/// last bound are counted in the final bucket
+pub const ENTRY_SIZE_BUCKETS: [usize; 7] = [128, 512, 2048, 8192, 32768, 131072, 524288];
+
+/// Number of Entries listed in EntrySizeStats::largest
+pub const LARGEST_ENTRIES: usize = 5;
+
+/// Approximate in-memory sizes of the Entries of a Table, as returned in
+/// TableStats::entry_sizes; see Entry::approx_size.  Entries spilled to disk are not
+/// counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntrySizeStats {
+    /// Number of Entries no larger than each bound of ENTRY_SIZE_BUCKETS, exclusive of the
+    /// smaller bounds, followed by the number larger than every bound
+    pub buckets: [u64; ENTRY_SIZE_BUCKETS.len() + 1],
+    /// Primary fields and sizes of up to LARGEST_ENTRIES of the largest Entries, largest
+    /// first
+    pub largest: Vec<(Field, usize)>,
+}
+
+impl EntrySizeStats {
+    /// Records the size of an Entry
+    pub(crate) fn record(&mut self, key: &Field, size: usize) {
+        let bucket = ENTRY_SIZE_BUCKETS.iter().position(|bound| size <= *bound).unwrap_or(ENTRY_SIZE_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.keep_largest(key, size);
+    }
+
+    /// Adds the sizes recorded by other, such as those of another shard of a Table
+    pub(crate) fn merge(&mut self, other: &EntrySizeStats) {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets) {
+            *bucket += count;
+        };
+        for (key, size) in &other.largest {
+            self.keep_largest(key, *size);
+        };
+    }
+
+    /// Adds the Entry to largest if it is among the LARGEST_ENTRIES largest; ties are
+    /// ordered by primary field
+    fn keep_largest(&mut self, key: &Field, size: usize) {
+        let position = self.largest.partition_point(|(k, s)| *s > size || (*s == size && k < key));
+        if position < LARGEST_ENTRIES {
+            self.largest.insert(position, (key.clone(), size));
+            self.largest.truncate(LARGEST_ENTRIES);
+        };
+    }
+
+    /// Returns the number of Entries recorded
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// Usage and limits of a Database, as returned by DatabaseClient::stats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    /// Stats of each Table, by name
+    pub tables: HashMap<String, TableStats>,
+    /// Approximate in-memory size of all Entries in bytes
+    pub approx_bytes: usize,
+    /// Maximum approximate size of all Entries in bytes, if limited
+    pub max_memory: Option<usize>,
+    /// Size of the database file in bytes above which saves warn, if set; see
+    /// ClientBuilder::size_budget
+    pub size_budget: Option<u64>,
+    /// True if the most recent save wrote more than size_budget bytes
+    pub over_size_budget: bool,
+    /// Bytes written by saves of the database file since the Client was created or opened
+    pub writes: WriteStats,
+    /// Time waited for the database lock since the Client was created or opened, by the
+    /// name of the method of the Client taking it, such as "insert" or "prune"
+    pub lock_waits: HashMap<&'static str, LockWaitStats>,
+}
+
+impl DatabaseStats {
+    /// Returns up to limit methods which waited longest in total for the database lock,
+    /// longest first
+    /// ```
+    /// # use persistent_keystore_rs::Client;
+    /// # use persistent_keystore_rs::prelude::*;
+    /// # use std::path::Path;
+    /// let mut c = Client::new(Path::new("worstlockwaits.db"), None).unwrap();
+    /// c.list_tables().unwrap();
+    /// for (method, waits) in c.stats().unwrap().worst_lock_waits(3) {
+    ///     println!("{} waited {:?} at most, {:?} on average", method, waits.max_wait, waits.mean_wait());
+    /// };
+    /// # std::fs::remove_file("worstlockwaits.db").unwrap();
+    /// ```
+    pub fn worst_lock_waits(&self, limit: usize) -> Vec<(&'static str, LockWaitStats)> {
+        let mut waits: Vec<(&'static str, LockWaitStats)> = self.lock_waits.iter().map(|(m, w)| (*m, *w)).collect();
+        waits.sort_by(|a, b| b.1.total_wait.cmp(&a.1.total_wait).then(a.0.cmp(b.0)));
+        waits.truncate(limit);
+        waits
+    }
+}
+
+/// Upper bounds of the buckets of LockWaitStats::buckets; waits longer than the last bound
+/// are counted in the final bucket
+pub const LOCK_WAIT_BUCKETS: [Duration; 6] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// Time waited to acquire the database lock by one method of a Client, as returned in
+/// DatabaseStats::lock_waits; long waits show latency caused by contention for the lock
+/// rather than by saves, whose durations are in DatabaseStats::writes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockWaitStats {
+    /// Number of times the lock was acquired
+    pub acquisitions: u64,
+    /// Total time waited
+    pub total_wait: Duration,
+    /// Longest single wait
+    pub max_wait: Duration,
+    /// Number of waits no longer than each bound of LOCK_WAIT_BUCKETS, exclusive of the
+    /// smaller bounds, followed by the number longer than every bound
+    pub buckets: [u64; LOCK_WAIT_BUCKETS.len() + 1],
+}
+
+impl LockWaitStats {
+    /// Records a wait
+    pub(crate) fn record(&mut self, wait: Duration) {
+        self.acquisitions += 1;
+        self.total_wait += wait;
+        self.max_wait = self.max_wait.max(wait);
+        let bucket = LOCK_WAIT_BUCKETS.iter().position(|bound| wait <= *bound).unwrap_or(LOCK_WAIT_BUCKETS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    /// Returns the mean time waited, or zero if the lock was never acquired
+    pub fn mean_wait(&self) -> Duration {
+        match self.acquisitions {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total_wait.as_nanos() / n as u128) as u64),
+        }
+    }
+}
+
+/// Bytes produced while saving a database file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveStats {
+    /// Bytes of the serialized database
+    pub serialized_bytes: u64,
+    /// Bytes of the serialized database after compression
+    pub compressed_bytes: u64,
+    /// Bytes written to the database file
+    pub written_bytes: u64,
+    /// Time taken to compress and write the database file
+    pub duration: Duration,
+}
+
+impl AddAssign for SaveStats {
+    fn add_assign(&mut self, other: SaveStats) {
+        self.serialized_bytes += other.serialized_bytes;
+        self.compressed_bytes += other.compressed_bytes;
+        self.written_bytes += other.written_bytes;
+        self.duration += other.duration;
+    }
+}
+
+/// Saves of a database file, as returned in DatabaseStats::writes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of saves, including compactions
+    pub saves: u64,
+    /// Bytes produced by the most recent save, if any
+    pub last_save: Option<SaveStats>,
+    /// Bytes produced by every save
+    pub total: SaveStats,
+}
+
+impl WriteStats {
+    /// Records a save
+    pub(crate) fn record(&mut self, save: SaveStats) {
+        self.saves += 1;
+        self.last_save = Some(save);
+        self.total += save;
+    }
+}
@@ -0,0 +1,670 @@
+use std::time::SystemTime;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::fmt;
+use std::net::IpAddr;
+use serde::de::{EnumAccess, VariantAccess, Visitor};
+use serde_derive::{Serialize, Deserialize};
+
+use crate::structs::*;
+use crate::variants::{Variant, VARIANTS};
+use crate::decimal::Decimal;
+use crate::cidr::Cidr;
+use crate::geo::GeoPoint;
+#[cfg(feature = "zeroize")]
+use crate::secret::Secret;
+
+/// Physical layout used by a Table to store its entries
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableLayout {
+    /// Entries are stored whole, keyed by their primary field
+    Row,
+    /// Values are stored per field in typed columns, with an index from primary field to row
+    Columnar,
+}
+
+/// Typed storage for a single field of a columnar Table; serialized at the fixed variant
+/// indices of FieldType
+#[derive(Clone)]
+enum Column {
+    String(Vec<Option<String>>),
+    I64(Vec<Option<i64>>),
+    I32(Vec<Option<i32>>),
+    U64(Vec<Option<u64>>),
+    U32(Vec<Option<u32>>),
+    Date(Vec<Option<SystemTime>>),
+    Bool(Vec<Option<bool>>),
+    #[cfg(feature = "zeroize")]
+    Secret(Vec<Option<Secret>>),
+    Custom(Vec<Option<(String, Vec<u8>)>>),
+    I128(Vec<Option<i128>>),
+    U128(Vec<Option<u128>>),
+    Decimal(Vec<Option<Decimal>>),
+    IpAddr(Vec<Option<IpAddr>>),
+    Cidr(Vec<Option<Cidr>>),
+    GeoPoint(Vec<Option<GeoPoint>>),
+    Enum(EnumColumn),
+    /// Column of FieldType::Null, which holds no values other than nulls
+    Null(Vec<Option<()>>),
+}
+
+/// Values of a FieldType::Enum field, as indexes into its declared variants
+#[derive(Clone, Serialize, Deserialize)]
+struct EnumColumn {
+    variants: Vec<String>,
+    values: Vec<Option<u32>>,
+}
+
+impl EnumColumn {
+    fn index(&self, value: Option<&Field>) -> Option<u32> {
+        match value {
+            Some(Field::Enum(v)) => self.variants.iter().position(|d| d == v).map(|i| i as u32),
+            _ => None,
+        }
+    }
+}
+
+impl Column {
+    fn new(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::String => Column::String(Vec::new()),
+            FieldType::I64 => Column::I64(Vec::new()),
+            FieldType::I32 => Column::I32(Vec::new()),
+            FieldType::U64 => Column::U64(Vec::new()),
+            FieldType::U32 => Column::U32(Vec::new()),
+            FieldType::Date => Column::Date(Vec::new()),
+            FieldType::Bool => Column::Bool(Vec::new()),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => Column::Secret(Vec::new()),
+            FieldType::Custom => Column::Custom(Vec::new()),
+            FieldType::I128 => Column::I128(Vec::new()),
+            FieldType::U128 => Column::U128(Vec::new()),
+            FieldType::Decimal => Column::Decimal(Vec::new()),
+            FieldType::IpAddr => Column::IpAddr(Vec::new()),
+            FieldType::Cidr => Column::Cidr(Vec::new()),
+            FieldType::GeoPoint => Column::GeoPoint(Vec::new()),
+            FieldType::Enum(variants) => Column::Enum(EnumColumn{
+                variants: variants.clone(),
+                values: Vec::new(),
+            }),
+            FieldType::Null => Column::Null(Vec::new()),
+        }
+    }
+
+    /// Appends a value to the end of the column; values of the wrong type are stored as None
+    fn push(&mut self, value: Option<&Field>) {
+        match self {
+            Column::String(v) => v.push(match value { Some(Field::String(f)) => Some(f.clone()), _ => None }),
+            Column::I64(v) => v.push(match value { Some(Field::I64(f)) => Some(*f), _ => None }),
+            Column::I32(v) => v.push(match value { Some(Field::I32(f)) => Some(*f), _ => None }),
+            Column::U64(v) => v.push(match value { Some(Field::U64(f)) => Some(*f), _ => None }),
+            Column::U32(v) => v.push(match value { Some(Field::U32(f)) => Some(*f), _ => None }),
+            Column::Date(v) => v.push(match value { Some(Field::Date(f)) => Some(*f), _ => None }),
+            Column::Bool(v) => v.push(match value { Some(Field::Bool(f)) => Some(*f), _ => None }),
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => v.push(match value { Some(Field::Secret(f)) => Some(f.clone()), _ => None }),
+            Column::Custom(v) => v.push(match value { Some(Field::Custom{tag, bytes}) => Some((tag.clone(), bytes.clone())), _ => None }),
+            Column::I128(v) => v.push(match value { Some(Field::I128(f)) => Some(*f), _ => None }),
+            Column::U128(v) => v.push(match value { Some(Field::U128(f)) => Some(*f), _ => None }),
+            Column::Decimal(v) => v.push(match value { Some(Field::Decimal(f)) => Some(*f), _ => None }),
+            Column::IpAddr(v) => v.push(match value { Some(Field::IpAddr(f)) => Some(*f), _ => None }),
+            Column::Cidr(v) => v.push(match value { Some(Field::Cidr(f)) => Some(*f), _ => None }),
+            Column::GeoPoint(v) => v.push(match value { Some(Field::GeoPoint(f)) => Some(*f), _ => None }),
+            Column::Enum(c) => c.values.push(c.index(value)),
+            Column::Null(v) => v.push(None),
+        }
+    }
+
+    /// Replaces the value at the supplied row
+    fn set(&mut self, row: usize, value: Option<&Field>) {
+        match self {
+            Column::String(v) => v[row] = match value { Some(Field::String(f)) => Some(f.clone()), _ => None },
+            Column::I64(v) => v[row] = match value { Some(Field::I64(f)) => Some(*f), _ => None },
+            Column::I32(v) => v[row] = match value { Some(Field::I32(f)) => Some(*f), _ => None },
+            Column::U64(v) => v[row] = match value { Some(Field::U64(f)) => Some(*f), _ => None },
+            Column::U32(v) => v[row] = match value { Some(Field::U32(f)) => Some(*f), _ => None },
+            Column::Date(v) => v[row] = match value { Some(Field::Date(f)) => Some(*f), _ => None },
+            Column::Bool(v) => v[row] = match value { Some(Field::Bool(f)) => Some(*f), _ => None },
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => v[row] = match value { Some(Field::Secret(f)) => Some(f.clone()), _ => None },
+            Column::Custom(v) => v[row] = match value { Some(Field::Custom{tag, bytes}) => Some((tag.clone(), bytes.clone())), _ => None },
+            Column::I128(v) => v[row] = match value { Some(Field::I128(f)) => Some(*f), _ => None },
+            Column::U128(v) => v[row] = match value { Some(Field::U128(f)) => Some(*f), _ => None },
+            Column::Decimal(v) => v[row] = match value { Some(Field::Decimal(f)) => Some(*f), _ => None },
+            Column::IpAddr(v) => v[row] = match value { Some(Field::IpAddr(f)) => Some(*f), _ => None },
+            Column::Cidr(v) => v[row] = match value { Some(Field::Cidr(f)) => Some(*f), _ => None },
+            Column::GeoPoint(v) => v[row] = match value { Some(Field::GeoPoint(f)) => Some(*f), _ => None },
+            Column::Enum(c) => c.values[row] = c.index(value),
+            Column::Null(v) => v[row] = None,
+        }
+    }
+
+    fn get(&self, row: usize) -> Option<Field> {
+        match self {
+            Column::String(v) => v[row].clone().map(Field::String),
+            Column::I64(v) => v[row].map(Field::I64),
+            Column::I32(v) => v[row].map(Field::I32),
+            Column::U64(v) => v[row].map(Field::U64),
+            Column::U32(v) => v[row].map(Field::U32),
+            Column::Date(v) => v[row].map(Field::Date),
+            Column::Bool(v) => v[row].map(Field::Bool),
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => v[row].clone().map(Field::Secret),
+            Column::Custom(v) => v[row].clone().map(|(tag, bytes)| Field::Custom{tag, bytes}),
+            Column::I128(v) => v[row].map(Field::I128),
+            Column::U128(v) => v[row].map(Field::U128),
+            Column::Decimal(v) => v[row].map(Field::Decimal),
+            Column::IpAddr(v) => v[row].map(Field::IpAddr),
+            Column::Cidr(v) => v[row].map(Field::Cidr),
+            Column::GeoPoint(v) => v[row].map(Field::GeoPoint),
+            Column::Enum(c) => c.values[row].and_then(|i| c.variants.get(i as usize)).map(|v| Field::Enum(v.clone())),
+            Column::Null(_) => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::String(v) => v.len(),
+            Column::I64(v) => v.len(),
+            Column::I32(v) => v.len(),
+            Column::U64(v) => v.len(),
+            Column::U32(v) => v.len(),
+            Column::Date(v) => v.len(),
+            Column::Bool(v) => v.len(),
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => v.len(),
+            Column::Custom(v) => v.len(),
+            Column::I128(v) => v.len(),
+            Column::U128(v) => v.len(),
+            Column::Decimal(v) => v.len(),
+            Column::IpAddr(v) => v.len(),
+            Column::Cidr(v) => v.len(),
+            Column::GeoPoint(v) => v.len(),
+            Column::Enum(c) => c.values.len(),
+            Column::Null(v) => v.len(),
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        match self {
+            Column::String(v) => v.shrink_to_fit(),
+            Column::I64(v) => v.shrink_to_fit(),
+            Column::I32(v) => v.shrink_to_fit(),
+            Column::U64(v) => v.shrink_to_fit(),
+            Column::U32(v) => v.shrink_to_fit(),
+            Column::Date(v) => v.shrink_to_fit(),
+            Column::Bool(v) => v.shrink_to_fit(),
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => v.shrink_to_fit(),
+            Column::Custom(v) => v.shrink_to_fit(),
+            Column::I128(v) => v.shrink_to_fit(),
+            Column::U128(v) => v.shrink_to_fit(),
+            Column::Decimal(v) => v.shrink_to_fit(),
+            Column::IpAddr(v) => v.shrink_to_fit(),
+            Column::Cidr(v) => v.shrink_to_fit(),
+            Column::GeoPoint(v) => v.shrink_to_fit(),
+            Column::Enum(c) => c.values.shrink_to_fit(),
+            Column::Null(v) => v.shrink_to_fit(),
+        }
+    }
+
+    fn swap_remove(&mut self, row: usize) {
+        match self {
+            Column::String(v) => { v.swap_remove(row); },
+            Column::I64(v) => { v.swap_remove(row); },
+            Column::I32(v) => { v.swap_remove(row); },
+            Column::U64(v) => { v.swap_remove(row); },
+            Column::U32(v) => { v.swap_remove(row); },
+            Column::Date(v) => { v.swap_remove(row); },
+            Column::Bool(v) => { v.swap_remove(row); },
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => { v.swap_remove(row); },
+            Column::Custom(v) => { v.swap_remove(row); },
+            Column::I128(v) => { v.swap_remove(row); },
+            Column::U128(v) => { v.swap_remove(row); },
+            Column::Decimal(v) => { v.swap_remove(row); },
+            Column::IpAddr(v) => { v.swap_remove(row); },
+            Column::Cidr(v) => { v.swap_remove(row); },
+            Column::GeoPoint(v) => { v.swap_remove(row); },
+            Column::Enum(c) => { c.values.swap_remove(row); },
+            Column::Null(v) => { v.swap_remove(row); },
+        }
+    }
+}
+
+impl serde::Serialize for Column {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Column::String(v) => serializer.serialize_newtype_variant("Column", 0, "String", v),
+            Column::I64(v) => serializer.serialize_newtype_variant("Column", 1, "I64", v),
+            Column::I32(v) => serializer.serialize_newtype_variant("Column", 2, "I32", v),
+            Column::U64(v) => serializer.serialize_newtype_variant("Column", 3, "U64", v),
+            Column::U32(v) => serializer.serialize_newtype_variant("Column", 4, "U32", v),
+            Column::Date(v) => serializer.serialize_newtype_variant("Column", 5, "Date", v),
+            Column::Bool(v) => serializer.serialize_newtype_variant("Column", 6, "Bool", v),
+            #[cfg(feature = "zeroize")]
+            Column::Secret(v) => serializer.serialize_newtype_variant("Column", 7, "Secret", v),
+            Column::Custom(v) => serializer.serialize_newtype_variant("Column", 8, "Custom", v),
+            Column::I128(v) => serializer.serialize_newtype_variant("Column", 9, "I128", v),
+            Column::U128(v) => serializer.serialize_newtype_variant("Column", 10, "U128", v),
+            Column::Decimal(v) => serializer.serialize_newtype_variant("Column", 11, "Decimal", v),
+            Column::IpAddr(v) => serializer.serialize_newtype_variant("Column", 12, "IpAddr", v),
+            Column::Cidr(v) => serializer.serialize_newtype_variant("Column", 13, "Cidr", v),
+            Column::GeoPoint(v) => serializer.serialize_newtype_variant("Column", 14, "GeoPoint", v),
+            Column::Enum(c) => serializer.serialize_newtype_variant("Column", 15, "Enum", c),
+            Column::Null(v) => serializer.serialize_newtype_variant("Column", 16, "Null", v),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Column {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("Column", VARIANTS, ColumnVisitor)
+    }
+}
+
+struct ColumnVisitor;
+
+impl<'de> Visitor<'de> for ColumnVisitor {
+    type Value = Column;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a column")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Column, A::Error> {
+        let (variant, value) = data.variant()?;
+        match variant {
+            Variant::String => value.newtype_variant().map(Column::String),
+            Variant::I64 => value.newtype_variant().map(Column::I64),
+            Variant::I32 => value.newtype_variant().map(Column::I32),
+            Variant::U64 => value.newtype_variant().map(Column::U64),
+            Variant::U32 => value.newtype_variant().map(Column::U32),
+            Variant::Date => value.newtype_variant().map(Column::Date),
+            Variant::Bool => value.newtype_variant().map(Column::Bool),
+            #[cfg(feature = "zeroize")]
+            Variant::Secret => value.newtype_variant().map(Column::Secret),
+            #[cfg(not(feature = "zeroize"))]
+            Variant::Secret => Err(crate::variants::secret_unsupported()),
+            Variant::Custom => value.newtype_variant().map(Column::Custom),
+            Variant::I128 => value.newtype_variant().map(Column::I128),
+            Variant::U128 => value.newtype_variant().map(Column::U128),
+            Variant::Decimal => value.newtype_variant().map(Column::Decimal),
+            Variant::IpAddr => value.newtype_variant().map(Column::IpAddr),
+            Variant::Cidr => value.newtype_variant().map(Column::Cidr),
+            Variant::GeoPoint => value.newtype_variant().map(Column::GeoPoint),
+            Variant::Enum => value.newtype_variant().map(Column::Enum),
+            Variant::Null => value.newtype_variant().map(Column::Null),
+        }
+    }
+}
+
+/// Columnar storage; one typed Vec per field, with rows addressed through the key index
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ColumnStore {
+    #[serde(serialize_with = "crate::ordered::map")]
+    index: HashMap<Field, usize, EntryHasher>,
+    keys: Vec<Field>,
+    timestamps: Vec<Option<SystemTime>>,
+    #[serde(serialize_with = "crate::ordered::map")]
+    columns: HashMap<String, Column>,
+    /// Rows of each column set to Field::Null, which are stored as missing values
+    nulls: BTreeMap<String, BTreeSet<usize>>,
+    /// Bookkeeping of the Table for each row; rebuilt by Table::rebase after loading
+    #[serde(skip)]
+    states: Vec<EntryState>,
+}
+
+impl ColumnStore {
+    fn new(fields: &HashMap<String, FieldRequirement>) -> Self {
+        let mut columns = HashMap::new();
+        for (k, v) in fields {
+            columns.insert(k.clone(), Column::new(&v.unwrap()));
+        };
+
+        Self{
+            index: HashMap::default(),
+            keys: Vec::new(),
+            timestamps: Vec::new(),
+            columns,
+            nulls: BTreeMap::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Gives every row a state, as rows read from disk have none until rebased
+    fn fill_states(&mut self) {
+        self.states.resize(self.keys.len(), EntryState::default());
+    }
+
+    /// Returns the value of the column at the row, or Field::Null if it was cleared
+    fn get(&self, name: &String, column: &Column, row: usize) -> Option<Field> {
+        match column.get(row) {
+            Some(v) => Some(v),
+            None if self.nulls.get(name).is_some_and(|n| n.contains(&row)) => Some(Field::Null),
+            None => None,
+        }
+    }
+
+    /// Records whether the value of the named column at the row is Field::Null
+    fn set_null(&mut self, name: &String, row: usize, value: Option<&Field>) {
+        match value {
+            Some(Field::Null) => {
+                self.nulls.entry(name.clone()).or_default().insert(row);
+            },
+            _ => if let Some(n) = self.nulls.get_mut(name) {
+                n.remove(&row);
+                if n.is_empty() {
+                    self.nulls.remove(name);
+                };
+            },
+        };
+    }
+
+    fn materialize(&self, row: usize) -> Entry {
+        let mut fields = HashMap::new();
+        for (k, c) in &self.columns {
+            if let Some(v) = self.get(k, c, row) {
+                fields.insert(k.clone(), v);
+            };
+        };
+
+        Entry{
+            primary_field: self.keys[row].clone(),
+            fields,
+            last_timestamp: self.timestamps[row],
+        }
+    }
+
+    fn insert(&mut self, entry: Entry, state: EntryState) -> bool {
+        self.fill_states();
+        match self.index.get(&entry.primary_field) {
+            Some(row) => {
+                let row = *row;
+                for (k, c) in self.columns.iter_mut() {
+                    c.set(row, entry.fields.get(k));
+                };
+                let names: Vec<String> = self.columns.keys().cloned().collect();
+                for k in &names {
+                    self.set_null(k, row, entry.fields.get(k));
+                };
+                self.timestamps[row] = entry.last_timestamp;
+                self.states[row] = state;
+                return true
+            },
+            None => {
+                let row = self.keys.len();
+                for (k, c) in self.columns.iter_mut() {
+                    c.push(entry.fields.get(k));
+                };
+                let names: Vec<String> = self.columns.keys().cloned().collect();
+                for k in &names {
+                    self.set_null(k, row, entry.fields.get(k));
+                };
+                self.index.insert(entry.primary_field.clone(), row);
+                self.keys.push(entry.primary_field);
+                self.timestamps.push(entry.last_timestamp);
+                self.states.push(state);
+                return false
+            },
+        }
+    }
+
+    fn remove(&mut self, key: &Field) -> Option<Entry> {
+        self.fill_states();
+        let row = self.index.remove(key)?;
+        let entry = self.materialize(row);
+        for c in self.columns.values_mut() {
+            c.swap_remove(row);
+        };
+        let last = self.keys.len() - 1;
+        for n in self.nulls.values_mut() {
+            n.remove(&row);
+            if n.remove(&last) {
+                n.insert(row);
+            };
+        };
+        self.nulls.retain(|_, n| !n.is_empty());
+        self.keys.swap_remove(row);
+        self.timestamps.swap_remove(row);
+        self.states.swap_remove(row);
+
+        if row < self.keys.len() {
+            self.index.insert(self.keys[row].clone(), row);
+        };
+        Some(entry)
+    }
+}
+
+/// Entry of row storage, held with the bookkeeping of the Table for it; serialized as the
+/// Entry alone, the bookkeeping being rebuilt by Table::rebase after loading
+#[derive(Clone)]
+pub(crate) struct Resident {
+    entry: Arc<Entry>,
+    state: EntryState,
+}
+
+impl serde::Serialize for Resident {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&*self.entry, serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Resident {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entry: Entry = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Resident{
+            entry: Arc::new(entry),
+            state: EntryState::default(),
+        })
+    }
+}
+
+/// Storage backing a Table, selected by its TableLayout
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum TableStorage {
+    Row(#[serde(serialize_with = "crate::ordered::map")] HashMap<Field, Resident, EntryHasher>),
+    Columnar(ColumnStore),
+}
+
+impl TableStorage {
+    pub(crate) fn new(layout: TableLayout, fields: &HashMap<String, FieldRequirement>) -> Self {
+        match layout {
+            TableLayout::Row => TableStorage::Row(HashMap::default()),
+            TableLayout::Columnar => TableStorage::Columnar(ColumnStore::new(fields)),
+        }
+    }
+
+    pub(crate) fn layout(&self) -> TableLayout {
+        match self {
+            TableStorage::Row(_) => TableLayout::Row,
+            TableStorage::Columnar(_) => TableLayout::Columnar,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            TableStorage::Row(m) => m.len(),
+            TableStorage::Columnar(c) => c.keys.len(),
+        }
+    }
+
+    pub(crate) fn contains_key(&self, key: &Field) -> bool {
+        match self {
+            TableStorage::Row(m) => m.contains_key(key),
+            TableStorage::Columnar(c) => c.index.contains_key(key),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &Field) -> Option<Arc<Entry>> {
+        match self {
+            TableStorage::Row(m) => m.get(key).map(|r| r.entry.clone()),
+            TableStorage::Columnar(c) => c.index.get(key).map(|row| Arc::new(c.materialize(*row))),
+        }
+    }
+
+    /// Returns the bookkeeping of the Table for the entry, if it is held
+    pub(crate) fn state(&self, key: &Field) -> Option<EntryState> {
+        match self {
+            TableStorage::Row(m) => m.get(key).map(|r| r.state),
+            TableStorage::Columnar(c) => Some(c.states.get(*c.index.get(key)?).copied().unwrap_or_default()),
+        }
+    }
+
+    /// Returns the bookkeeping of the Table for the entry for modification, if it is held
+    pub(crate) fn state_mut(&mut self, key: &Field) -> Option<&mut EntryState> {
+        match self {
+            TableStorage::Row(m) => m.get_mut(key).map(|r| &mut r.state),
+            TableStorage::Columnar(c) => {
+                let row = *c.index.get(key)?;
+                c.fill_states();
+                c.states.get_mut(row)
+            },
+        }
+    }
+
+    pub(crate) fn last_timestamp(&self, key: &Field) -> Option<SystemTime> {
+        match self {
+            TableStorage::Row(m) => m.get(key)?.entry.last_timestamp,
+            TableStorage::Columnar(c) => c.timestamps[*c.index.get(key)?],
+        }
+    }
+
+    /// Sets the last_timestamp of the entry in place, returning false if it is not held
+    pub(crate) fn touch(&mut self, key: &Field, wall: SystemTime) -> bool {
+        match self {
+            TableStorage::Row(m) => match m.get_mut(key) {
+                Some(resident) => {
+                    Arc::make_mut(&mut resident.entry).last_timestamp = Some(wall);
+                    true
+                },
+                None => false,
+            },
+            TableStorage::Columnar(c) => match c.index.get(key) {
+                Some(row) => {
+                    c.timestamps[*row] = Some(wall);
+                    true
+                },
+                None => false,
+            },
+        }
+    }
+
+    /// Inserts or replaces the entry along with its bookkeeping, returning true if it
+    /// replaced an existing one
+    pub(crate) fn insert(&mut self, entry: Entry, state: EntryState) -> bool {
+        match self {
+            TableStorage::Row(m) => m.insert(entry.primary_field.clone(), Resident{
+                entry: Arc::new(entry),
+                state,
+            }).is_some(),
+            TableStorage::Columnar(c) => c.insert(entry, state),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &Field) -> Option<Arc<Entry>> {
+        match self {
+            TableStorage::Row(m) => m.remove(key).map(|r| r.entry),
+            TableStorage::Columnar(c) => c.remove(key).map(Arc::new),
+        }
+    }
+
+    /// Releases capacity left unused by removed entries
+    pub(crate) fn shrink_to_fit(&mut self) {
+        match self {
+            TableStorage::Row(m) => m.shrink_to_fit(),
+            TableStorage::Columnar(c) => {
+                c.index.shrink_to_fit();
+                c.keys.shrink_to_fit();
+                c.timestamps.shrink_to_fit();
+                c.states.shrink_to_fit();
+                c.columns.values_mut().for_each(Column::shrink_to_fit);
+            },
+        }
+    }
+
+    pub(crate) fn keys(&self) -> Vec<Field> {
+        match self {
+            TableStorage::Row(m) => m.keys().cloned().collect(),
+            TableStorage::Columnar(c) => c.keys.clone(),
+        }
+    }
+
+    pub(crate) fn values(&self) -> Vec<Arc<Entry>> {
+        match self {
+            TableStorage::Row(m) => m.values().map(|r| r.entry.clone()).collect(),
+            TableStorage::Columnar(c) => (0..c.keys.len()).map(|row| Arc::new(c.materialize(row))).collect(),
+        }
+    }
+
+    /// Returns a description of every inconsistency between the entries and the structures
+    /// used to locate them, along with the key involved if any
+    pub(crate) fn verify(&self) -> Vec<(Option<Field>, String)> {
+        let mut problems = Vec::new();
+        match self {
+            TableStorage::Row(m) => {
+                for (k, v) in m {
+                    if *k != v.entry.primary_field {
+                        problems.push((Some(k.clone()), format!("stored under key {} but has primary field {}", k, v.entry.primary_field)));
+                    };
+                };
+            },
+            TableStorage::Columnar(c) => {
+                if c.index.len() != c.keys.len() {
+                    problems.push((None, format!("index has {} keys but {} rows are stored", c.index.len(), c.keys.len())));
+                };
+                if c.timestamps.len() != c.keys.len() {
+                    problems.push((None, format!("{} timestamps stored for {} rows", c.timestamps.len(), c.keys.len())));
+                };
+                for (name, column) in &c.columns {
+                    if column.len() != c.keys.len() {
+                        problems.push((None, format!("column {} has {} values for {} rows", name, column.len(), c.keys.len())));
+                    };
+                };
+                for (name, rows) in &c.nulls {
+                    if rows.last().is_some_and(|r| *r >= c.keys.len()) {
+                        problems.push((None, format!("column {} has nulls beyond its {} rows", name, c.keys.len())));
+                    };
+                };
+                for (row, k) in c.keys.iter().enumerate() {
+                    if c.index.get(k) != Some(&row) {
+                        problems.push((Some(k.clone()), format!("row {} is not indexed", row)));
+                    };
+                };
+            },
+        };
+        problems
+    }
+
+    /// Returns an iterator over every entry; columnar entries are materialized as they are reached
+    pub(crate) fn iter(&self) -> Box<dyn Iterator<Item = Arc<Entry>> + '_> {
+        match self {
+            TableStorage::Row(m) => Box::new(m.values().map(|r| r.entry.clone())),
+            TableStorage::Columnar(c) => Box::new((0..c.keys.len()).map(move |row| Arc::new(c.materialize(row)))),
+        }
+    }
+
+    /// Returns the primary field and value of the supplied field for every entry that has it set
+    pub(crate) fn project(&self, field: &String) -> Vec<(Field, Field)> {
+        let mut results = Vec::new();
+        match self {
+            TableStorage::Row(m) => {
+                for (k, v) in m {
+                    if let Some(value) = v.entry.fields.get(field) {
+                        results.push((k.clone(), value.clone()));
+                    };
+                };
+            },
+            TableStorage::Columnar(c) => {
+                if let Some(column) = c.columns.get(field) {
+                    for (row, k) in c.keys.iter().enumerate() {
+                        if let Some(value) = c.get(field, column, row) {
+                            results.push((k.clone(), value));
+                        };
+                    };
+                };
+            },
+        };
+        results
+    }
+}
@@ -1,12 +1,65 @@
-use std::time::{SystemTime, Duration};
-use std::collections::HashMap;
+use std::time::{SystemTime, Duration, Instant};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
 use serde_derive::{Serialize, Deserialize};
 use std::fmt;
+use std::sync::Arc;
+use std::path::Path;
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::ops::ControlFlow;
+use tracing::error;
 
 use crate::errors::*;
+use crate::storage::*;
+use crate::spill::*;
+use crate::blob::*;
+use crate::intern::*;
+use crate::bloom::*;
+use crate::prefix::*;
+use crate::index::*;
+use crate::clock::Timestamp;
+use crate::format::*;
+use crate::condition::*;
+use crate::filter::Filter;
+use crate::expiration::*;
+use crate::stats::*;
+use crate::cancellation::*;
+use crate::plan::*;
+use crate::verify::*;
+use crate::merge::*;
+use crate::diff::*;
+use crate::system::*;
+use crate::redact;
+use crate::names::{NamePolicy, RESERVED_PREFIX};
+use crate::idempotency::IdempotencyLog;
+use crate::lazy::{self, Slot};
+use crate::decimal::Decimal;
+use crate::cidr::Cidr;
+use crate::geo::GeoPoint;
+#[cfg(feature = "zeroize")]
+use crate::secret::Secret;
 
-#[derive(Hash, PartialEq, Eq, Clone, Serialize, Deserialize, Debug)]
+/// Hasher used for the entries of every Table; FxHash, enabled by the `fxhash` feature.
+/// 
+/// This hasher is not DoS-resistant and should only be used for trusted keys.
+#[cfg(feature = "fxhash")]
+pub type EntryHasher = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
+
+/// Hasher used for the entries of every Table; aHash, enabled by the `ahash` feature.
+#[cfg(all(feature = "ahash", not(feature = "fxhash")))]
+pub type EntryHasher = ahash::RandomState;
+
+/// Hasher used for the entries of every Table.
+/// 
+/// Defaults to the DoS-resistant SipHash of the standard library; the `fxhash` or `ahash`
+/// features swap in a faster hasher for trusted workloads.
+#[cfg(not(any(feature = "fxhash", feature = "ahash")))]
+pub type EntryHasher = std::collections::hash_map::RandomState;
+
+/// Value of a field of an Entry.  Serialized at fixed variant indices, so that database
+/// files are portable between builds with and without the `zeroize` feature.
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub enum Field {
     String(String),
     I64(i64),
@@ -15,12 +68,35 @@ pub enum Field {
     U32(u32),
     Date(SystemTime),
     Bool(bool),
-    NotImplemented,
+    /// Sensitive string zeroized when dropped; requires the `zeroize` feature
+    #[cfg(feature = "zeroize")]
+    Secret(Secret),
+    /// Value of an application-defined type, such as an IP network or decimal, as bytes
+    /// tagged with the name of the type; validated, displayed and parsed by the
+    /// CustomCodec registered for the tag.  See register_codec.
+    Custom {
+        tag: String,
+        bytes: Vec<u8>,
+    },
+    I128(i128),
+    U128(u128),
+    /// Fixed-point decimal number; see Decimal
+    Decimal(Decimal),
+    IpAddr(IpAddr),
+    /// Block of IP addresses; see Cidr and Condition::InSubnet
+    Cidr(Cidr),
+    /// Latitude and longitude; see GeoPoint and Condition::WithinRadius
+    GeoPoint(GeoPoint),
+    /// One of the variants declared by a FieldType::Enum, by name
+    Enum(String),
+    /// Value of an optional field that has been cleared, as opposed to one never set.
+    /// Patching a field with Null clears it; see DatabaseClient::patch.
+    Null,
 }
 
 impl Field {
     pub fn get_type(&self) -> FieldType {
-        let t = match self {
+        match self {
             Field::String(_) => FieldType::String,
             Field::I32(_) => FieldType::I32,
             Field::I64(_) => FieldType::I64,
@@ -28,11 +104,123 @@ impl Field {
             Field::U32(_) => FieldType::U32,
             Field::Date(_) => FieldType::Date,
             Field::Bool(_) => FieldType::Bool,
-            Field::NotImplemented => FieldType::None,
+            #[cfg(feature = "zeroize")]
+            Field::Secret(_) => FieldType::Secret,
+            Field::Custom{..} => FieldType::Custom,
+            Field::I128(_) => FieldType::I128,
+            Field::U128(_) => FieldType::U128,
+            Field::Decimal(_) => FieldType::Decimal,
+            Field::IpAddr(_) => FieldType::IpAddr,
+            Field::Cidr(_) => FieldType::Cidr,
+            Field::GeoPoint(_) => FieldType::GeoPoint,
+            Field::Enum(v) => FieldType::Enum(vec![v.clone()]),
+            Field::Null => FieldType::Null,
+        }
+    }
+
+    /// Returns a Field of the custom type with the supplied tag, holding the bytes.
+    /// If no CustomCodec is registered for the tag, or it finds the bytes invalid,
+    /// DatabaseError::InvalidFormat is returned; see register_codec.
+    pub fn custom(tag: &str, bytes: Vec<u8>) -> Result<Field, DatabaseError> {
+        crate::custom::validate(tag, &bytes)?;
+        Ok(Field::Custom{
+            tag: tag.to_string(),
+            bytes,
+        })
+    }
+
+    /// Parses a textual value into a Field of the supplied FieldType; the reverse of
+    /// Display.  Dates are expected in RFC3339 format, or as signed seconds since the Unix
+    /// epoch for dates RFC3339 cannot represent, decimals as digits with an optional
+    /// sign and decimal point, such as `-12.50`, address blocks as `10.0.0.0/8`, points as
+    /// `lat,lon` in degrees, and custom values as `tag:value`.
+    /// If the value cannot be parsed, DatabaseError::InvalidFormat is returned.
+    /// ```
+    /// use persistent_keystore_rs::{Field, FieldType};
+    /// let count = Field::parse(FieldType::I64, "42").unwrap();
+    /// assert_eq!(count, Field::I64(42));
+    /// let date = Field::parse(FieldType::Date, "2018-02-14T00:28:07Z").unwrap();
+    /// assert_eq!(format!("{}", date), "2018-02-14T00:28:07Z".to_string());
+    ///
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// for date in [UNIX_EPOCH - Duration::new(1, 5), UNIX_EPOCH + Duration::from_secs(253_402_300_800)] {
+    ///     assert_eq!(Field::parse(FieldType::Date, &Field::Date(date).to_string()).unwrap(), Field::Date(date));
+    /// };
+    /// ```
+    pub fn parse(field_type: FieldType, value: &str) -> Result<Field, DatabaseError> {
+        let invalid = |_| DatabaseError::InvalidFormat(format!("{:?} {}", field_type, value));
+        let f = match &field_type {
+            FieldType::String => Field::String(value.to_string()),
+            FieldType::I64 => Field::I64(value.trim().parse().map_err(invalid)?),
+            FieldType::I32 => Field::I32(value.trim().parse().map_err(invalid)?),
+            FieldType::U64 => Field::U64(value.trim().parse().map_err(invalid)?),
+            FieldType::U32 => Field::U32(value.trim().parse().map_err(invalid)?),
+            FieldType::Date => Field::Date(parse_date(value)?),
+            FieldType::Bool => Field::Bool(value.trim().parse().map_err(|_| DatabaseError::InvalidFormat(format!("{:?} {}", field_type, value)))?),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => Field::Secret(Secret::new(value.to_string())),
+            FieldType::Custom => crate::custom::parse(value)?,
+            FieldType::I128 => Field::I128(value.trim().parse().map_err(invalid)?),
+            FieldType::U128 => Field::U128(value.trim().parse().map_err(invalid)?),
+            FieldType::Decimal => Field::Decimal(value.parse()?),
+            FieldType::IpAddr => Field::IpAddr(value.trim().parse().map_err(|_| DatabaseError::InvalidFormat(format!("{:?} {}", field_type, value)))?),
+            FieldType::Cidr => Field::Cidr(value.parse()?),
+            FieldType::GeoPoint => Field::GeoPoint(value.parse()?),
+            FieldType::Enum(variants) => match variants.iter().any(|v| v == value) {
+                true => Field::Enum(value.to_string()),
+                false => return Err(DatabaseError::InvalidFormat(format!("{:?} {}", field_type, value))),
+            },
+            FieldType::Null => match value.trim() {
+                "null" => Field::Null,
+                _ => return Err(DatabaseError::InvalidFormat(format!("{:?} {}", field_type, value))),
+            },
         };
-        t
+        Ok(f)
+    }
+
+    /// Returns the number of bytes the Field holds on the heap
+    fn heap_size(&self) -> usize {
+        match self {
+            Field::String(v) => v.len(),
+            #[cfg(feature = "zeroize")]
+            Field::Secret(v) => v.expose().len(),
+            Field::Custom{tag, bytes} => tag.len() + bytes.len(),
+            Field::Enum(v) => v.len(),
+            _ => 0,
+        }
     }
 
+    /// Compares two Fields of the same type; None is returned if the types differ, or
+    /// custom values have different tags
+    /// ```
+    /// use persistent_keystore_rs::Field;
+    /// use std::cmp::Ordering;
+    /// assert_eq!(Field::I64(1).compare(&Field::I64(2)), Some(Ordering::Less));
+    /// assert_eq!(Field::I64(1).compare(&Field::U64(2)), None);
+    /// ```
+    pub fn compare(&self, other: &Field) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Field::String(a), Field::String(b)) => Some(a.cmp(b)),
+            (Field::I64(a), Field::I64(b)) => Some(a.cmp(b)),
+            (Field::I32(a), Field::I32(b)) => Some(a.cmp(b)),
+            (Field::U64(a), Field::U64(b)) => Some(a.cmp(b)),
+            (Field::U32(a), Field::U32(b)) => Some(a.cmp(b)),
+            (Field::Date(a), Field::Date(b)) => Some(a.cmp(b)),
+            (Field::Bool(a), Field::Bool(b)) => Some(a.cmp(b)),
+            #[cfg(feature = "zeroize")]
+            (Field::Secret(a), Field::Secret(b)) => Some(a.cmp(b)),
+            (Field::Custom{tag: a, bytes: x}, Field::Custom{tag: b, bytes: y}) if a == b => Some(x.cmp(y)),
+            (Field::I128(a), Field::I128(b)) => Some(a.cmp(b)),
+            (Field::U128(a), Field::U128(b)) => Some(a.cmp(b)),
+            (Field::Decimal(a), Field::Decimal(b)) => Some(a.cmp(b)),
+            (Field::IpAddr(a), Field::IpAddr(b)) => Some(a.cmp(b)),
+            (Field::Cidr(a), Field::Cidr(b)) => Some(a.cmp(b)),
+            (Field::GeoPoint(a), Field::GeoPoint(b)) => Some(a.cmp(b)),
+            (Field::Enum(a), Field::Enum(b)) => Some(a.cmp(b)),
+            (Field::Null, Field::Null) => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Field {
@@ -43,15 +231,26 @@ impl fmt::Display for Field {
             Field::I64(v) => format!("{}", v),
             Field::U64(v) => format!("{}", v),
             Field::U32(v) => format!("{}", v),
-            Field::Date(v) => format!("{:?}", v),
+            Field::Date(v) => format_date(*v),
             Field::Bool(v) => format!("{}", v),
-            Field::NotImplemented => format!("NotImplemented"),
+            #[cfg(feature = "zeroize")]
+            Field::Secret(v) => format!("{}", v),
+            Field::Custom{tag, bytes} => crate::custom::display(tag, bytes),
+            Field::I128(v) => format!("{}", v),
+            Field::U128(v) => format!("{}", v),
+            Field::Decimal(v) => format!("{}", v),
+            Field::IpAddr(v) => format!("{}", v),
+            Field::Cidr(v) => format!("{}", v),
+            Field::GeoPoint(v) => format!("{}", v),
+            Field::Enum(v) => v.clone(),
+            Field::Null => "null".to_string(),
         };
         write!(f, "{}", msg)
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+/// Type of a Field.  Serialized at fixed variant indices, as Field.
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum FieldType {
     String,
     I64,
@@ -60,10 +259,58 @@ pub enum FieldType {
     U32,
     Date,
     Bool,
-    None,
+    #[cfg(feature = "zeroize")]
+    Secret,
+    /// Field::Custom of any tag
+    Custom,
+    I128,
+    U128,
+    Decimal,
+    IpAddr,
+    Cidr,
+    GeoPoint,
+    /// Field::Enum of one of the declared variants; values are compared by name, and stored
+    /// as the index of their variant by columnar Tables
+    Enum(Vec<String>),
+    /// Type of Field::Null; optional fields of any type may be set to Field::Null, but no
+    /// field can be declared of this type
+    Null,
+}
+
+impl FieldType {
+    /// Returns DatabaseError::InvalidFormat if the FieldType cannot be declared by a Table;
+    /// Null, an Enum without variants, or an Enum with a variant declared twice
+    fn check_declaration(&self) -> Result<(), DatabaseError> {
+        if *self == FieldType::Null {
+            return Err(DatabaseError::InvalidFormat("fields cannot be declared Null".to_string()))
+        };
+        if let FieldType::Enum(variants) = self {
+            if variants.is_empty() {
+                return Err(DatabaseError::InvalidFormat("Enum declares no variants".to_string()))
+            };
+            if let Some((i, v)) = variants.iter().enumerate().find(|(i, v)| variants[..*i].contains(v)) {
+                return Err(DatabaseError::InvalidFormat(format!("Enum declares {} twice, at {}", v, i)))
+            };
+        };
+        Ok(())
+    }
+
+    /// Returns Ok if the Field is a value of the FieldType.  Enum values that are not
+    /// declared variants are DatabaseError::InvalidFormat, and values of another type
+    /// DatabaseError::MismatchedFieldType.
+    fn check_value(&self, field: &Field) -> Result<(), DatabaseError> {
+        match (self, field) {
+            (FieldType::Enum(variants), Field::Enum(v)) => match variants.contains(v) {
+                true => Ok(()),
+                false => Err(DatabaseError::InvalidFormat(format!("Enum {} is not one of {}", v, variants.join(", ")))),
+            },
+            (t, f) if *t == f.get_type() => Ok(()),
+            _ => Err(DatabaseError::MismatchedFieldType),
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FieldRequirement {
     Required(FieldType),
     Optional(FieldType)
@@ -78,23 +325,149 @@ impl FieldRequirement {
     }
 }
 
+/// Maximum number of entries evaluated at a time when choosing Entries to evict
+const EVICTION_CHUNK_SIZE: usize = 1000;
+
+/// Changes deleting Entries from a Table makes to the Database; built by
+/// Database::plan_delete so that every change is validated before any is made
+#[derive(Default)]
+struct DeletePlan {
+    /// Table the delete was requested for
+    table: String,
+    /// Primary Fields of the Entries deleted from each Table, including by Cascade
+    deleted: HashMap<String, HashSet<Field>>,
+    /// Entries updated by SetMissing, with every deleted reference removed
+    updated: Vec<(String, Entry)>,
+    /// Requested keys whose delete is refused
+    blocked: HashSet<Field>,
+    /// First reason a requested key's delete is refused
+    error: Option<DatabaseError>,
+}
+
+impl DeletePlan {
+    /// Returns true if the plan deletes the Entry from the Table
+    fn deletes(&self, table: &str, key: &Field) -> bool {
+        self.deleted.get(table).is_some_and(|k| k.contains(key))
+    }
+
+    /// Refuses the delete of the requested key
+    fn block(&mut self, key: Field, error: DatabaseError) {
+        self.blocked.insert(key);
+        if self.error.is_none() {
+            self.error = Some(error);
+        };
+    }
+}
+
 /// Database; a collection of Tables
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Database {
     pub sync_interval: Option<Duration>,
-    tables: HashMap<String, Table>,
+    /// Metadata set by Database::set_meta.  It is kept here rather than beside FileInfo in
+    /// the header of the file: the header is rebuilt by every save to record what wrote
+    /// the file, and is outside the database lock and write sequence, so metadata held
+    /// there could not be changed atomically with the Tables, flushed, reloaded or
+    /// snapshotted with them.
+    meta: BTreeMap<String, String>,
+    idempotency: IdempotencyLog,
+    #[serde(serialize_with = "crate::ordered::map")]
+    tables: HashMap<String, Slot>,
+    schema_history: Vec<SchemaChange>,
+    #[serde(skip)]
+    segmented: bool,
+    #[serde(skip)]
+    case_insensitive: bool,
 }
 
-impl Default for Database {
-    fn default() -> Self {
-        Self{
-            sync_interval: None,
-            tables: HashMap::new(),
+impl Database {
+    /// Returns the number of Entries of every Table
+    pub(crate) fn entry_count(&self) -> usize {
+        self.tables.values().map(|t| t.len()).sum()
+    }
+
+    /// Deserializes a Database from the standard or segmented format; the Tables of the
+    /// segmented format are read on first access
+    pub(crate) fn deserialize(uncompressed: &[u8]) -> Result<Database, DatabaseError> {
+        if !lazy::is_segmented(uncompressed) {
+            return Ok(bincode::deserialize(uncompressed)?)
+        };
+        let mut segmented = lazy::deserialize(uncompressed)?;
+        Ok(Database{
+            tables: segmented.take_tables(),
+            sync_interval: segmented.sync_interval,
+            meta: segmented.meta,
+            idempotency: segmented.idempotency,
+            schema_history: segmented.schema_history,
+            segmented: true,
+            case_insensitive: false,
+        })
+    }
+
+    /// Serializes the Database in the segmented format if it was read from it or
+    /// Database::set_segmented was called, otherwise in the standard format
+    pub(crate) fn serialize(&self) -> Result<Vec<u8>, DatabaseError> {
+        match self.segmented {
+            true => lazy::serialize(self.sync_interval, &self.meta, &self.idempotency, &self.tables, &self.schema_history),
+            false => Ok(bincode::serialize(self)?),
         }
     }
-}
 
-impl Database {
+    /// Sets whether the Database is saved in the segmented format; see
+    /// ClientBuilder::segmented
+    pub(crate) fn set_segmented(&mut self, segmented: bool) {
+        self.segmented = segmented;
+    }
+
+    /// Sets whether Tables are found by names differing from theirs only in case; see
+    /// ClientBuilder::case_insensitive_tables
+    pub(crate) fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    /// Returns true if Tables are found by names differing from theirs only in case
+    pub(crate) fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// Returns the name the named Table is stored under; the supplied name unless Tables
+    /// are found case-insensitively and the Table is stored under a name differing only in
+    /// case.  Of several such names the first in order is returned.
+    pub(crate) fn table_name<'a>(&self, table: &'a String) -> Cow<'a, str> {
+        if !self.case_insensitive || self.tables.contains_key(table) {
+            return Cow::Borrowed(table)
+        };
+        match self.tables.keys().filter(|name| same_name(name, table)).min() {
+            Some(name) => Cow::Owned(name.clone()),
+            None => Cow::Borrowed(table),
+        }
+    }
+
+    /// Returns false if the named Table is held as its segment, not yet read; see
+    /// ClientBuilder::segmented
+    pub(crate) fn is_loaded(&self, table: &String) -> bool {
+        self.tables.get(&*self.table_name(table)).is_none_or(Slot::is_loaded)
+    }
+
+    /// Returns true if the Database has the same Tables as other and shares every one of
+    /// them with it, so that neither has modified or replaced a Table since one was cloned
+    /// from the other
+    pub(crate) fn shares_tables(&self, other: &Database) -> bool {
+        self.tables.len() == other.tables.len() && self.tables.iter().all(|(name, slot)| {
+            other.tables.get(name).is_some_and(|o| slot.shares(o))
+        })
+    }
+
+    /// Replaces the Table of the same name, or adds it if there is none, without the checks
+    /// of create_table; used to take the Leases held in the database file
+    pub(crate) fn replace_table(&mut self, table: Table) {
+        self.tables.insert(table.name.clone(), Slot::from(table));
+    }
+
+    /// Removes the named Table from the Database, returning it
+    pub(crate) fn take_table(&mut self, table: &str) -> Result<Option<Table>, DatabaseError> {
+        self.tables.remove(table).map(Slot::into_table).transpose()
+    }
+
     /// Sets the Sync Duration of the Database.
     /// 
     /// Note this is currently only utilized by the Client
@@ -122,6 +495,37 @@ impl Database {
         self.sync_interval = None
     }
 
+    /// Sets a metadata value of the Database, such as the version of the application that
+    /// wrote it, returning the previous value of the key.  Metadata is saved with the
+    /// Database, ahead of its Tables, rather than in the header of the file, so reading it
+    /// decompresses the file; it is intended for a handful of small values.
+    /// ```
+    /// use persistent_keystore_rs::Database;
+    ///
+    /// let mut database = Database::default();
+    /// database.set_meta("AppVersion".to_string(), "1.2.0".to_string());
+    /// assert_eq!(database.get_meta("AppVersion"), Some(&"1.2.0".to_string()));
+    /// ```
+    pub fn set_meta(&mut self, key: String, value: String) -> Option<String> {
+        self.meta.insert(key, value)
+    }
+
+    /// Returns a metadata value of the Database; see Database::set_meta
+    pub fn get_meta(&self, key: &str) -> Option<&String> {
+        self.meta.get(key)
+    }
+
+    /// Removes a metadata value of the Database, returning it; see Database::set_meta
+    pub fn remove_meta(&mut self, key: &str) -> Option<String> {
+        self.meta.remove(key)
+    }
+
+    /// Returns the log of the idempotency keys of recent writes; see
+    /// DatabaseClient::insert_idempotent
+    pub(crate) fn idempotency(&mut self) -> &mut IdempotencyLog {
+        &mut self.idempotency
+    }
+
     /// Returns a mutable reference to a Table within the Database
     /// ```
     /// # use persistent_keystore_rs::{Table, FieldType};
@@ -140,12 +544,78 @@ impl Database {
     /// let table = database.get_table(&"MyTable".to_string()).unwrap();
     /// ```
     pub fn get_table(&mut self, table: &String) -> Result<&mut Table, DatabaseError> {
-        match self.tables.get_mut(table) {
-            Some(t) => return Ok(t),
+        if is_system_table(table) {
+            return Err(DatabaseError::ReadOnlyTable(table.clone()))
+        };
+        let name = self.table_name(table);
+        match self.tables.get_mut(&*name) {
+            Some(t) => return t.get_mut(),
+            None => return Err(DatabaseError::TableDoesNotExist(table.clone()))
+        };
+    }
+
+    /// Returns a reference to a Table within the Database
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::Database;
+    /// # use std::time::Duration;
+    /// 
+    /// # let table1 = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let mut database = Database::default();
+    /// # database.create_table(table1).unwrap();
+    /// let table = database.get_table_ref(&"MyTable".to_string()).unwrap();
+    /// ```
+    pub fn get_table_ref(&self, table: &String) -> Result<&Table, DatabaseError> {
+        match self.tables.get(&*self.table_name(table)) {
+            Some(t) => return t.get(),
             None => return Err(DatabaseError::TableDoesNotExist(table.clone()))
         };
     }
 
+    /// Returns a Table within the Database for reading, or the system table with the
+    /// supplied name built as of now; see SYSTEM_TABLES
+    /// ```
+    /// use persistent_keystore_rs::{Database, Field, TABLES_TABLE};
+    /// # use persistent_keystore_rs::{Table, FieldType, SystemClock, Clock};
+    /// # let table1 = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let mut database = Database::default();
+    /// # database.create_table(table1).unwrap();
+    /// let tables = database.read_table(&TABLES_TABLE.to_string(), SystemClock.timestamp()).unwrap();
+    /// let entry = tables.get(&Field::String("MyTable".to_string())).unwrap();
+    /// assert_eq!(entry.fields["Fields"], Field::String("Count:I64".to_string()));
+    /// ```
+    pub fn read_table(&self, table: &String, now: Timestamp) -> Result<Cow<'_, Table>, DatabaseError> {
+        if let Some(t) = self.tables.get(&*self.table_name(table)) {
+            return Ok(Cow::Borrowed(t.get()?))
+        };
+        match system_table(self, table, now) {
+            Some(t) => Ok(Cow::Owned(t?)),
+            None => Err(DatabaseError::TableDoesNotExist(table.clone())),
+        }
+    }
+
+    /// Returns the Tables created and dropped through the Client, oldest first
+    pub fn schema_history(&self) -> &[SchemaChange] {
+        &self.schema_history
+    }
+
+    /// Records the creation or removal of a Table in the schema history
+    pub(crate) fn record_schema_change(&mut self, table: String, kind: SchemaChangeKind, at: SystemTime) {
+        self.schema_history.push(SchemaChange{
+            table,
+            kind,
+            at,
+        });
+    }
+
     /// Creates a Table within the Database
     /// ```
     /// use persistent_keystore_rs::{Database, Table, FieldType};
@@ -162,8 +632,33 @@ impl Database {
     /// let mut database = Database::default();
     /// database.create_table(table).unwrap();
     /// ```
-    pub fn create_table(&mut self, table: Table) -> Result<(), DatabaseError> {
-        self.tables.insert(table.name.clone(), table);
+    pub fn create_table(&mut self, mut table: Table) -> Result<(), DatabaseError> {
+        if is_system_table(&table.name) {
+            return Err(DatabaseError::TableExists(table.name))
+        };
+        for reference in table.references.values_mut() {
+            reference.table = match self.case_insensitive && same_name(&reference.table, &table.name) {
+                true => table.name.clone(),
+                false => self.table_name(&reference.table).into_owned(),
+            };
+        };
+        for (field, reference) in &table.references {
+            let primary_field = if reference.table == table.name {
+                table.primary_field.clone()
+            } else {
+                match self.tables.get(&reference.table) {
+                    Some(t) => t.primary_field(),
+                    None => return Err(DatabaseError::TableDoesNotExist(reference.table.clone())),
+                }
+            };
+
+            if table.fields[field].unwrap() != primary_field {
+                return Err(DatabaseError::MismatchedFieldType)
+            };
+        };
+
+        table.created_at.get_or_insert_with(SystemTime::now);
+        self.tables.insert(table.name.clone(), Slot::from(table));
         Ok(())
     }
 
@@ -185,12 +680,616 @@ impl Database {
     /// database.drop_table(&"MyTable".to_string()).unwrap();
     /// ```
     pub fn drop_table(&mut self, table: &String) -> Result<(), DatabaseError> {
-        match self.tables.remove(table) {
-            Some(_) => return Ok(()),
-            None => return Err(DatabaseError::TableDoesNotExist(table.clone())),
+        self.check_drop_table(table)?;
+        let name = self.table_name(table);
+        self.tables.remove(&*name);
+        Ok(())
+    }
+
+    /// Returns the Table if it can be dropped, or the error drop_table would return
+    pub(crate) fn check_drop_table(&self, table: &String) -> Result<&Table, DatabaseError> {
+        if is_system_table(table) {
+            return Err(DatabaseError::ReadOnlyTable(table.clone()))
+        };
+        let name = self.table_name(table);
+        let table = &*name;
+        for (name, t) in &self.tables {
+            if name != table && t.references().values().any(|r| r.table == table) {
+                return Err(DatabaseError::ReferenceViolation(format!("table {} is referenced by table {}", table, name)))
+            };
+        };
+
+        match self.tables.get(table) {
+            Some(t) => t.get(),
+            None => Err(DatabaseError::TableDoesNotExist(table.to_string())),
+        }
+    }
+
+    /// Checks every Table and the references between them, returning a report of every
+    /// inconsistency found
+    /// ```
+    /// use persistent_keystore_rs::Database;
+    /// let database = Database::default();
+    /// assert!(database.verify().is_ok());
+    /// ```
+    pub fn verify(&self) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        for (name, slot) in &self.tables {
+            let table = match slot.get() {
+                Ok(t) => t,
+                Err(e) => {
+                    report.problems.push(VerifyProblem{
+                        table: Some(name.clone()),
+                        key: None,
+                        message: format!("unable to read segment: {}", e),
+                    });
+                    continue
+                },
+            };
+            if *name != table.name {
+                report.problems.push(VerifyProblem{
+                    table: Some(name.clone()),
+                    key: None,
+                    message: format!("stored under name {} but named {}", name, table.name),
+                });
+            };
+
+            table.verify(&mut report);
+            if table.references.is_empty() {
+                continue
+            };
+
+            for entry in table.iter_entries() {
+                if let Err(e) = self.check_references(name, &entry) {
+                    report.problems.push(VerifyProblem{
+                        table: Some(name.clone()),
+                        key: Some(entry.primary_field.clone()),
+                        message: format!("{}", e),
+                    });
+                };
+            };
+        };
+        report
+    }
+
+    /// Validates that every reference held by the Entry points to an existing Entry.
+    /// If a referenced Entry does not exist, DatabaseError::ReferenceViolation is returned.
+    pub(crate) fn check_references(&self, table: &String, entry: &Entry) -> Result<(), DatabaseError> {
+        let name = self.table_name(table);
+        let table = &*name;
+        let t = match self.tables.get(table) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        for (field, reference) in t.references() {
+            if let Some(key) = entry.fields.get(field).filter(|k| **k != Field::Null) {
+                if reference.table == *table && *key == entry.primary_field {
+                    continue
+                };
+
+                let exists = match self.tables.get(&reference.table) {
+                    Some(r) => r.get()?.contains(key),
+                    None => false,
+                };
+
+                if !exists {
+                    return Err(DatabaseError::ReferenceViolation(format!("{} {} does not exist in table {}", field, key, reference.table)))
+                };
+            };
+        };
+        Ok(())
+    }
+
+    /// Deletes the Entries with the supplied primary Fields from the Table, applying the
+    /// OnDelete behavior of every reference to them, and returns the number of Entries
+    /// deleted from the Table.  Keys that do not exist are ignored.
+    /// If a reference restricts the delete, DatabaseError::ReferenceViolation is returned,
+    /// and if an Entry would be left invalid by removing its reference, the error of its
+    /// validation; either way nothing is changed.
+    pub(crate) fn delete_entries(&mut self, table: &String, keys: Vec<Field>, now: Timestamp) -> Result<u64, DatabaseError> {
+        let plan = self.plan_delete(table, keys)?;
+        if let Some(e) = plan.error {
+            return Err(e)
+        };
+        self.apply_delete(plan, now)
+    }
+
+    /// Deletes the Entries with the supplied primary Fields from the Table as delete_entries
+    /// does, except that Entries whose delete is refused are kept rather than failing the
+    /// delete of the others; returns the number of Entries deleted from the Table
+    pub(crate) fn delete_unrestricted(&mut self, table: &String, keys: Vec<Field>, now: Timestamp) -> Result<u64, DatabaseError> {
+        let keys = self.deletable_keys(table, keys)?;
+        self.delete_entries(table, keys, now)
+    }
+
+    /// Returns the supplied primary Fields of the Table except those whose delete
+    /// delete_entries would refuse
+    pub(crate) fn deletable_keys(&self, table: &String, keys: Vec<Field>) -> Result<Vec<Field>, DatabaseError> {
+        let mut keys = keys;
+        loop {
+            let plan = self.plan_delete(table, keys.clone())?;
+            if plan.blocked.is_empty() {
+                return Ok(keys)
+            };
+            keys.retain(|k| !plan.blocked.contains(k));
+        };
+    }
+
+    /// Returns the changes deleting the Entries with the supplied primary Fields from the
+    /// Table would make, without making them
+    fn plan_delete(&self, table: &String, keys: Vec<Field>) -> Result<DeletePlan, DatabaseError> {
+        if is_system_table(table) {
+            return Err(DatabaseError::ReadOnlyTable(table.clone()))
+        };
+        let name = self.table_name(table);
+        let table = &*name;
+        let t = match self.tables.get(table) {
+            Some(t) => t.get()?,
+            None => return Err(DatabaseError::TableDoesNotExist(table.to_string())),
+        };
+
+        let mut plan = DeletePlan{
+            table: table.to_string(),
+            ..Default::default()
+        };
+        let keys: HashSet<Field> = keys.into_iter().filter(|k| t.contains(k)).collect();
+        // Supplied key whose delete led to the delete of each Entry
+        let mut roots: HashMap<(String, Field), Field> = keys.iter().map(|k| ((table.to_string(), k.clone()), k.clone())).collect();
+        plan.deleted.insert(table.to_string(), keys.clone());
+        let mut frontier: HashMap<String, HashSet<Field>> = HashMap::new();
+        frontier.insert(table.to_string(), keys);
+
+        let mut restricted = Vec::new();
+        let mut set_missing: HashMap<(String, Field), (Vec<String>, Field)> = HashMap::new();
+        while !frontier.is_empty() {
+            let mut next: HashMap<String, HashSet<Field>> = HashMap::new();
+            for (target, deleted) in &frontier {
+                for (name, referencing) in &self.tables {
+                    for (field, reference) in referencing.references() {
+                        if &reference.table != target {
+                            continue
+                        };
+
+                        for entry in referencing.get()?.iter_entries() {
+                            let root = match entry.fields.get(field) {
+                                Some(v) if deleted.contains(v) => roots.get(&(target.clone(), v.clone())).cloned().unwrap_or_else(|| v.clone()),
+                                _ => continue,
+                            };
+
+                            let key = entry.primary_field.clone();
+                            match reference.on_delete {
+                                OnDelete::Restrict => restricted.push((name.clone(), key, field.clone(), root)),
+                                OnDelete::SetMissing => set_missing.entry((name.clone(), key)).or_insert_with(|| (Vec::new(), root)).0.push(field.clone()),
+                                OnDelete::Cascade => {
+                                    if plan.deleted.entry(name.clone()).or_default().insert(key.clone()) {
+                                        roots.insert((name.clone(), key.clone()), root);
+                                        next.entry(name.clone()).or_default().insert(key);
+                                    };
+                                },
+                            };
+                        };
+                    };
+                };
+            };
+            frontier = next;
+        };
+
+        for (name, key, field, root) in restricted {
+            if !plan.deletes(&name, &key) {
+                plan.block(root, DatabaseError::ReferenceViolation(format!("{} {} in table {} references a deleted entry", field, key, name)));
+            };
+        };
+
+        for ((name, key), (fields, root)) in set_missing {
+            if plan.deletes(&name, &key) {
+                continue
+            };
+
+            if let Some(t) = self.tables.get(&name) {
+                let t = t.get()?;
+                if let Ok(entry) = t.get(&key) {
+                    let mut entry = (*entry).clone();
+                    for field in fields {
+                        entry.fields.remove(&field);
+                    };
+                    match t.validate_entry(&entry) {
+                        Ok(()) => plan.updated.push((name, entry)),
+                        Err(e) => plan.block(root, e),
+                    };
+                };
+            };
+        };
+        Ok(plan)
+    }
+
+    /// Makes the changes of a DeletePlan, returning the number of Entries deleted from the
+    /// Table it was planned for
+    fn apply_delete(&mut self, plan: DeletePlan, now: Timestamp) -> Result<u64, DatabaseError> {
+        for (name, entry) in plan.updated {
+            if let Some(t) = self.tables.get_mut(&name) {
+                t.get_mut()?.update_at(entry, now)?;
+            };
+        };
+
+        let mut deleted = 0;
+        for (name, keys) in plan.deleted {
+            if let Some(t) = self.tables.get_mut(&name) {
+                let t = t.get_mut()?;
+                for key in keys {
+                    if t.delete(key).is_ok() {
+                        t.last_modified_at = Some(now.wall);
+                        if name == plan.table {
+                            deleted += 1;
+                        };
+                    };
+                };
+            };
+        };
+        Ok(deleted)
+    }
+
+    /// Returns the least recently updated Entries of the Table other than keep that can be
+    /// evicted, oldest first, once enough returns true for their number and approximate
+    /// size; or None if it never does.  Entries whose delete is refused, such as by a
+    /// restricting reference, cannot be evicted.
+    fn eviction_candidates<F>(&self, table: &String, keep: &Field, mut enough: F) -> Result<Option<Vec<Field>>, DatabaseError>
+        where F: FnMut(usize, usize) -> bool {
+        let t = self.get_table_ref(table)?;
+        let mut candidates = Vec::new();
+        let mut size = 0;
+        let until = t.update_sequence();
+        let mut cursor = Some(0);
+        while let Some(after) = cursor {
+            let (keys, next) = t.keys_updated_between(after, until, EVICTION_CHUNK_SIZE);
+            for key in keys.into_iter().filter(|k| k != keep) {
+                if self.plan_delete(table, vec![key.clone()])?.error.is_some() {
+                    continue
+                };
+                size += t.footprint(&key);
+                candidates.push(key);
+                if enough(candidates.len(), size) {
+                    return Ok(Some(candidates))
+                };
+            };
+            cursor = next;
+        };
+        Ok(None)
+    }
+
+    /// Evicts the Entries of the Table, as delete_entries deletes them
+    fn evict(&mut self, table: &String, keys: Vec<Field>, now: Timestamp) -> Result<(), DatabaseError> {
+        let evicted = self.delete_entries(table, keys, now)?;
+        self.get_table(table)?.evicted += evicted;
+        Ok(())
+    }
+
+    /// Makes room for an Entry with the supplied primary Field in a Table limited by
+    /// max_entries with CapacityPolicy::EvictOldest, evicting its least recently updated
+    /// Entries along with the changes deleting them makes to the Entries referencing them.
+    /// Entries whose delete a reference restricts are kept; if too few others can be
+    /// evicted, DatabaseError::CapacityExceeded is returned and nothing is evicted.
+    pub(crate) fn make_room(&mut self, table: &String, key: &Field, now: Timestamp) -> Result<(), DatabaseError> {
+        let t = match self.get_table_ref(table) {
+            Ok(t) => t,
+            Err(DatabaseError::TableDoesNotExist(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let max_entries = match t.max_entries {
+            Some(m) if t.capacity_policy == CapacityPolicy::EvictOldest && !t.contains(key) => m,
+            _ => return Ok(()),
+        };
+        let excess = (t.len() + 1).saturating_sub(max_entries);
+        if excess == 0 {
+            return Ok(())
+        };
+
+        match self.eviction_candidates(table, key, |count, _| count >= excess)? {
+            Some(keys) => self.evict(table, keys, now),
+            None => Err(DatabaseError::CapacityExceeded(format!("table {} is limited to {} entries", table, max_entries))),
+        }
+    }
+
+    /// Returns the approximate in-memory size of the Entries of every Table
+    pub fn approx_size(&self) -> usize {
+        self.tables.values().map(|t| t.approx_size()).sum()
+    }
+
+    /// Makes room within max_bytes for the supplied Entry to be written to the Table,
+    /// evicting the least recently updated Entries of that Table if the policy allows, as
+    /// make_room evicts them.
+    /// If there is not enough room, DatabaseError::CapacityExceeded is returned and nothing
+    /// is evicted.
+    pub(crate) fn reserve(&mut self, table: &String, entry: &Entry, max_bytes: usize, policy: CapacityPolicy, now: Timestamp) -> Result<(), DatabaseError> {
+        let existing = match self.get_table_ref(table) {
+            Ok(t) => t.entry_size(&entry.primary_field).unwrap_or(0),
+            Err(DatabaseError::TableDoesNotExist(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let kept = self.approx_size().saturating_sub(existing) + entry.approx_size();
+        let fits = |freed: usize| kept.saturating_sub(freed) <= max_bytes;
+        if fits(0) {
+            return Ok(())
+        };
+
+        let candidates = match policy {
+            CapacityPolicy::Reject => None,
+            CapacityPolicy::EvictOldest => self.eviction_candidates(table, &entry.primary_field, |_, freed| fits(freed))?,
+        };
+        match candidates {
+            Some(keys) => self.evict(table, keys, now),
+            None => Err(DatabaseError::CapacityExceeded(format!("database is limited to {} bytes", max_bytes))),
         }
     }
 
+    /// Merges the Tables and Entries of remote into the Database using last-writer-wins;
+    /// see Database::merge_with and last_writer_wins.
+    /// ```
+    /// # use persistent_keystore_rs::{Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::{Database, Timestamp};
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let mut local = Database::default();
+    /// let mut remote = Database::default();
+    /// # remote.create_table(table).unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # remote.get_table(&"MyTable".to_string()).unwrap().insert(entry).unwrap();
+    /// let report = local.merge(remote, Timestamp::now()).unwrap();
+    /// assert_eq!(report.tables_created, vec!["MyTable".to_string()]);
+    /// ```
+    pub fn merge(&mut self, remote: Database, now: Timestamp) -> Result<MergeReport, DatabaseError> {
+        self.merge_with(remote, now, last_writer_wins)
+    }
+
+    /// Merges the Tables and Entries of remote into the Database, calling resolver with the
+    /// local and remote Entry for each primary Field held by both with differing fields.
+    ///
+    /// Tables only held by remote are copied in full.  Tables held by both must have the
+    /// same primary field and fields, otherwise DatabaseError::SchemaMismatch is returned
+    /// before anything is merged; the settings of the local Table are kept.  Entries only
+    /// held by remote are inserted.  Remote Entries keep their last_timestamp.  Deletes
+    /// are not recorded, so an Entry deleted from one database is restored from the other.
+    ///
+    /// Tables whose names begin with RESERVED_PREFIX, such as LEASE_TABLE, are not merged.
+    ///
+    /// Each Entry written is checked as by Table::insert_or_update, and the references of
+    /// the merged Entries must exist.  If any Entry is rejected, or the resolver returns
+    /// Resolution::Merged with a different primary Field, in which case
+    /// DatabaseError::InvalidPrimaryKey is returned, nothing is merged.
+    /// ```
+    /// # use persistent_keystore_rs::{Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::{Database, Resolution, Timestamp};
+    /// # let table = Table::new()
+    /// #    .name("MyTable".to_string())
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field("Count".to_string(), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let mut local = Database::default();
+    /// # let mut remote = Database::default();
+    /// # local.create_table(table.clone()).unwrap();
+    /// # remote.create_table(table).unwrap();
+    /// # for (database, count) in [(&mut local, 1), (&mut remote, 2)] {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #        .build().unwrap();
+    /// #     database.get_table(&"MyTable".to_string()).unwrap().insert(entry).unwrap();
+    /// # };
+    /// let report = local.merge_with(remote, Timestamp::now(), |local, remote| {
+    ///     let mut merged = local.clone();
+    ///     merged.last_timestamp = None;
+    ///     if let (Some(Field::I64(l)), Some(Field::I64(r))) = (local.fields.get("Count"), remote.fields.get("Count")) {
+    ///         merged.fields.insert("Count".to_string(), Field::I64(l + r));
+    ///     };
+    ///     Resolution::Merged(merged)
+    /// }).unwrap();
+    /// # assert_eq!(report.conflicts.len(), 1);
+    /// let entry = local.get_table(&"MyTable".to_string()).unwrap().get(&Field::String("MyEntry".to_string())).unwrap();
+    /// assert_eq!(entry.fields["Count"], Field::I64(3));
+    /// ```
+    pub fn merge_with<F>(&mut self, remote: Database, now: Timestamp, resolver: F) -> Result<MergeReport, DatabaseError>
+        where F: FnMut(&Entry, &Entry) -> Resolution {
+        let plan = self.plan_merge(&remote, resolver)?;
+        self.apply_merge(plan, now, None, |_, _, _| Ok(()))
+    }
+
+    /// Returns the Tables to create and Entries to write to merge remote into the Database,
+    /// calling resolver for each conflict, without changing the Database; see merge_with.
+    /// Tables whose names begin with RESERVED_PREFIX, such as LEASE_TABLE, belong to the
+    /// database they are held by and are not merged.
+    pub(crate) fn plan_merge<F>(&self, remote: &Database, mut resolver: F) -> Result<MergePlan, DatabaseError>
+        where F: FnMut(&Entry, &Entry) -> Resolution {
+        let mut names: Vec<&String> = remote.tables.keys().filter(|name| !name.starts_with(RESERVED_PREFIX)).collect();
+        names.sort();
+        for name in &names {
+            if let Some(local_table) = self.tables.get(*name) {
+                let (local_table, remote_table) = (local_table.get()?, remote.tables[*name].get()?);
+                if local_table.primary_field != remote_table.primary_field || local_table.fields != remote_table.fields {
+                    return Err(DatabaseError::SchemaMismatch(format!("table {} has different fields in each database", name)))
+                };
+            };
+        };
+
+        let mut plan = MergePlan::default();
+        for name in names {
+            let remote_table = remote.tables[name].get()?;
+            let local_table = match self.tables.get(name) {
+                Some(t) => t.get()?,
+                None => {
+                    plan.tables.push(remote_table.empty_copy()?);
+                    for remote_entry in remote_table.iter_entries() {
+                        plan.writes.push((name.clone(), remote_entry.as_ref().clone()));
+                    };
+                    plan.report.tables_created.push(name.clone());
+                    continue
+                },
+            };
+
+            for remote_entry in remote_table.iter_entries() {
+                let local_entry = match local_table.lookup(&remote_entry.primary_field) {
+                    Some(e) => e,
+                    None => {
+                        plan.writes.push((name.clone(), remote_entry.as_ref().clone()));
+                        plan.report.inserted += 1;
+                        continue
+                    },
+                };
+
+                if local_entry.fields == remote_entry.fields {
+                    plan.report.unchanged += 1;
+                    continue
+                };
+
+                let resolution = resolver(&local_entry, &remote_entry);
+                match &resolution {
+                    Resolution::KeepLocal => {},
+                    Resolution::KeepRemote => plan.writes.push((name.clone(), remote_entry.as_ref().clone())),
+                    Resolution::Merged(merged) => {
+                        if merged.primary_field != remote_entry.primary_field {
+                            return Err(DatabaseError::InvalidPrimaryKey)
+                        };
+                        plan.writes.push((name.clone(), merged.clone()));
+                    },
+                };
+                plan.report.conflicts.push(MergeConflict{
+                    table: name.clone(),
+                    key: remote_entry.primary_field.clone(),
+                    local: local_entry,
+                    remote: remote_entry,
+                    resolution,
+                });
+            };
+        };
+        Ok(plan)
+    }
+
+    /// Makes the changes of the plan to a copy of the Database and replaces the Database
+    /// with the copy once every change is made, so that a merge is made in full or not at
+    /// all.  Each Entry is checked against the schema of its Table, room is made for it
+    /// within max_entries as by make_room, and it is checked by check, such as to reserve
+    /// memory for it, before it is written as of its last_timestamp; the
+    /// references of every Entry written are checked once all are written, as they may
+    /// refer to one another.  The files of created Tables are attached beside the database
+    /// file at path, if supplied.
+    pub(crate) fn apply_merge<C>(&mut self, plan: MergePlan, now: Timestamp, path: Option<&Path>, mut check: C) -> Result<MergeReport, DatabaseError>
+        where C: FnMut(&mut Database, &String, &Entry) -> Result<(), DatabaseError> {
+        let mut merged = self.clone();
+        for mut table in plan.tables {
+            if let Some(path) = path {
+                table.attach_files(path)?;
+            };
+            merged.tables.insert(table.name.clone(), Slot::from(table));
+        };
+        for (table, entry) in &plan.writes {
+            merged.get_table_ref(table)?.check_write(entry, WriteKind::Update)?;
+            merged.make_room(table, &entry.primary_field, now)?;
+            check(&mut merged, table, entry)?;
+            merged.get_table(table)?.write_at(entry.clone(), WriteKind::Update, merged_timestamp(entry, now))?;
+        };
+        for (table, entry) in &plan.writes {
+            merged.check_references(table, entry)?;
+        };
+        *self = merged;
+        Ok(plan.report)
+    }
+
+    /// Returns the Tables and Entries added, removed and changed between the Database and
+    /// another; see Client::diff_files
+    pub fn diff(&self, other: &Database) -> Result<DatabaseDiff, DatabaseError> {
+        let mut diff = DatabaseDiff::default();
+        let mut names: Vec<&String> = self.tables.keys().chain(other.tables.keys().filter(|k| !self.tables.contains_key(*k))).collect();
+        names.sort_unstable();
+        for name in names {
+            let (before, after) = match (self.tables.get(name), other.tables.get(name)) {
+                (Some(before), Some(after)) => (before.get()?, after.get()?),
+                (Some(_), None) => {
+                    diff.tables_removed.push(name.clone());
+                    continue
+                },
+                (None, _) => {
+                    diff.tables_added.push(name.clone());
+                    continue
+                },
+            };
+
+            let mut table = TableDiff{
+                name: name.clone(),
+                schema_changed: before.primary_field != after.primary_field || before.fields != after.fields,
+                ..TableDiff::default()
+            };
+            for entry in before.iter_entries() {
+                match after.lookup(&entry.primary_field) {
+                    Some(changed) if changed.fields != entry.fields => table.changed.push(EntryChange{
+                        key: entry.primary_field.clone(),
+                        fields: changed_fields(&entry, &changed),
+                        before: entry,
+                        after: changed,
+                    }),
+                    Some(_) => {},
+                    None => table.removed.push(entry),
+                };
+            };
+            table.added = after.iter_entries().filter(|e| before.lookup(&e.primary_field).is_none()).collect();
+            table.added.sort_unstable_by(|a, b| a.primary_field.cmp(&b.primary_field));
+            table.removed.sort_unstable_by(|a, b| a.primary_field.cmp(&b.primary_field));
+            table.changed.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+            if !table.is_empty() {
+                diff.tables_changed.push(table);
+            };
+        };
+        Ok(diff)
+    }
+
+    /// Attaches every Table that spills or externalizes values to its files alongside the
+    /// database file at the supplied path; see TableBuilder::spill_after and
+    /// TableBuilder::externalize_above.  Tables not yet read from the segmented format are
+    /// attached once read.
+    pub(crate) fn attach_files(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        for table in self.tables.values_mut() {
+            table.attach_files(path)?;
+        };
+        Ok(())
+    }
+
+    /// Releases the memory left unused by Entries removed from every Table read so far
+    pub(crate) fn shrink_to_fit(&mut self) {
+        for table in self.tables.values_mut().filter_map(Slot::loaded_mut) {
+            table.shrink_to_fit();
+        };
+    }
+
+    /// Removes the files of externalized values no longer referenced by any Table read so
+    /// far, returning the number removed; the files of other Tables are left in place
+    pub(crate) fn collect_blobs(&self) -> Result<u64, DatabaseError> {
+        let mut removed = 0;
+        for table in self.tables.values().filter_map(Slot::loaded) {
+            removed += table.collect_blobs()?;
+        };
+        Ok(removed)
+    }
+
+    /// Flushes the spill segment of every Table read so far to disk
+    pub(crate) fn sync_spill(&self) -> Result<(), DatabaseError> {
+        for table in self.tables.values().filter_map(Slot::loaded) {
+            table.sync_spill()?;
+        };
+        Ok(())
+    }
+
+    /// Records the monotonic update time of every Entry in every Table; see Table::rebase.
+    /// Tables not yet read from the segmented format are rebased to now once read.
+    pub fn rebase(&mut self, now: Timestamp) {
+        for table in self.tables.values_mut() {
+            table.rebase(now);
+        };
+    }
+
     /// Returns a Vec of Table names that are stored within the Database
     /// ```
     /// # use persistent_keystore_rs::{Table, FieldType};
@@ -219,7 +1318,7 @@ impl Database {
     /// # assert!(tables.contains(&"MyTable".to_string()));
     /// # assert!(tables.contains(&"MySecondTable".to_string()));
     /// ```
-    pub fn list_tables(&mut self) -> Vec<String> {
+    pub fn list_tables(&self) -> Vec<String> {
         let mut results = Vec::new();
         for k in self.tables.keys() {
             results.push(k.clone());
@@ -228,9 +1327,28 @@ impl Database {
     }
 }
 
+/// Returns true if two Table names differ only in case
+fn same_name(a: &str, b: &str) -> bool {
+    a.chars().flat_map(char::to_lowercase).eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// Returns the Timestamp recording the last_timestamp of a merged Entry, with the monotonic
+/// time the same age before now
+fn merged_timestamp(entry: &Entry, now: Timestamp) -> Timestamp {
+    let wall = entry.last_timestamp.unwrap_or(now.wall);
+    let age = now.wall.duration_since(wall).unwrap_or(Duration::ZERO);
+    Timestamp{
+        wall,
+        monotonic: now.monotonic.checked_sub(age).unwrap_or(now.monotonic),
+    }
+}
+
 /// Builder Pattern for creating a new Table
 pub struct TableBuilder {
     table: Table,
+    primary_field: Option<FieldType>,
+    layout: TableLayout,
+    name_policy: NamePolicy,
 }
 
 impl TableBuilder {
@@ -253,10 +1371,8 @@ impl TableBuilder {
     ///     .primary_field(FieldType::String).unwrap();
     /// ```
     pub fn primary_field(mut self, priary_key: FieldType) -> Result<Self, DatabaseError> {
-        if let FieldType::None = priary_key {
-            return Err(DatabaseError::UnsupportedFieldType)
-        };
-        self.table.primary_field = priary_key;
+        priary_key.check_declaration()?;
+        self.primary_field = Some(priary_key);
         Ok(self)
     }
 
@@ -269,10 +1385,7 @@ impl TableBuilder {
     ///     .add_field("Count".to_string(), FieldType::I64).unwrap();
     /// ```
     pub fn add_field(mut self, key: String, field_type: FieldType) -> Result<Self, DatabaseError> {
-        if let FieldType::None = field_type {
-            return Err(DatabaseError::UnsupportedFieldType)
-        };
-
+        field_type.check_declaration()?;
         self.table.fields.insert(key, FieldRequirement::Required(field_type));
         Ok(self)
     }
@@ -287,10 +1400,7 @@ impl TableBuilder {
     ///     .add_optional_field("Notes".to_string(), FieldType::String).unwrap();
     /// ```
     pub fn add_optional_field(mut self, key: String, field_type: FieldType) -> Result<Self, DatabaseError> {
-        if let FieldType::None = field_type {
-            return Err(DatabaseError::UnsupportedFieldType)
-        };
-
+        field_type.check_declaration()?;
         self.table.fields.insert(key, FieldRequirement::Optional(field_type));
         Ok(self)
     }
@@ -316,7 +1426,309 @@ impl TableBuilder {
         self
     }
 
-    /// Validates the Table is properly configured and returns the Table object.
+    /// Add a calendar based ExpirationSchedule to the Table.  Entries last updated before
+    /// the most recent scheduled time are removed by prune, in addition to any expiration
+    /// Duration.
+    /// If the schedule is invalid, DatabaseError::InvalidFormat is returned.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, ExpirationSchedule};
+    /// 
+    /// let table = Table::new()
+    /// #     .name("MyTable".to_string())
+    /// #     .primary_field(FieldType::String).unwrap()
+    /// #     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .add_expiration_schedule(ExpirationSchedule::EndOfDay).unwrap();
+    /// ```
+    pub fn add_expiration_schedule(mut self, schedule: ExpirationSchedule) -> Result<Self, DatabaseError> {
+        schedule.validate()?;
+        self.table.expire_schedule = Some(schedule);
+        Ok(self)
+    }
+
+    /// Declares the field as a reference to the primary field of another Table, or of this
+    /// Table.  Entries may only be written if the referenced Entry exists, and deleting a
+    /// referenced Entry through a Client applies the OnDelete behavior, as does its removal
+    /// by prune or eviction, which keep Entries whose delete is restricted.
+    /// 
+    /// The field must already be added to the Table, and must be optional to use
+    /// OnDelete::SetMissing; otherwise DatabaseError::ReferenceViolation is returned.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, OnDelete};
+    /// 
+    /// let table = Table::new()
+    ///     .name("Orders".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Customer".to_string(), FieldType::String).unwrap()
+    ///     .add_reference("Customer".to_string(), "Customers".to_string(), OnDelete::Cascade).unwrap()
+    ///     .build().unwrap();
+    /// ```
+    pub fn add_reference(mut self, field: String, table: String, on_delete: OnDelete) -> Result<Self, DatabaseError> {
+        match self.table.fields.get(&field) {
+            Some(FieldRequirement::Required(_)) if on_delete == OnDelete::SetMissing => {
+                return Err(DatabaseError::ReferenceViolation(format!("field {} must be optional to be set missing on delete", field)))
+            },
+            Some(_) => {},
+            None => return Err(DatabaseError::ReferenceViolation(format!("field {} is not part of the table", field))),
+        };
+
+        self.table.references.insert(field, Reference{
+            table,
+            on_delete,
+        });
+        Ok(self)
+    }
+
+    /// Adds a secondary index over the tuple of fields, in order.  Table::filter and the
+    /// queries of a Client use it when the Conditions include Condition::Equals on the
+    /// leading fields of the index, reading only the matching Entries rather than scanning
+    /// the Table; see Table::explain.
+    ///
+    /// The index is kept in memory only and rebuilt when the Table is loaded.  If a field
+    /// is not part of the Table, DatabaseError::UnsupportedField is returned, and if no
+    /// fields are supplied, DatabaseError::TableMustContainFields.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    ///
+    /// let table = Table::new()
+    ///     .name("Tickets".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Tenant".to_string(), FieldType::String).unwrap()
+    ///     .add_field("Status".to_string(), FieldType::String).unwrap()
+    ///     .add_index(vec!["Tenant".to_string(), "Status".to_string()]).unwrap()
+    ///     .build().unwrap();
+    /// ```
+    pub fn add_index(mut self, fields: Vec<String>) -> Result<Self, DatabaseError> {
+        if fields.is_empty() {
+            return Err(DatabaseError::TableMustContainFields)
+        };
+        if let Some(field) = fields.iter().find(|f| !self.table.fields.contains_key(*f)) {
+            return Err(DatabaseError::UnsupportedField(field.clone()))
+        };
+
+        self.table.indexes.push(fields);
+        Ok(self)
+    }
+
+    /// Marks the field as sensitive; its value is masked in the Debug output of every
+    /// Entry, so that secrets do not reach logs.  Fields are masked by name, in Entries of
+    /// any Table, from when the Table is built or loaded until the process exits.
+    /// If the field is not part of the Table, DatabaseError::UnsupportedField is returned.
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field, FieldType, Table};
+    ///
+    /// let table = Table::new()
+    ///     .name("Credentials".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Password".to_string(), FieldType::String).unwrap()
+    ///     .sensitive_field("Password".to_string()).unwrap()
+    ///     .build().unwrap();
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("admin".to_string())).unwrap()
+    ///     .add_field("Password".to_string(), Field::String("hunter2".to_string())).unwrap()
+    ///     .build().unwrap();
+    /// assert!(!format!("{:?}", entry).contains("hunter2"));
+    /// ```
+    pub fn sensitive_field(mut self, key: String) -> Result<Self, DatabaseError> {
+        if !self.table.fields.contains_key(&key) {
+            return Err(DatabaseError::UnsupportedField(key))
+        };
+
+        self.table.sensitive_fields.insert(key);
+        Ok(self)
+    }
+
+    /// Makes the Table append-only; updating an Entry keeps the previous version, which
+    /// can be read back with Table::history, rather than overwriting it.  Reads return the
+    /// latest version.  Previous versions are kept according to the HistoryRetention and
+    /// are removed along with the Entry when it is deleted or expires.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, HistoryRetention};
+    /// use std::time::Duration;
+    /// 
+    /// let table = Table::new()
+    /// #     .name("MyTable".to_string())
+    /// #     .primary_field(FieldType::String).unwrap()
+    /// #     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .append_only(HistoryRetention{
+    ///         max_versions: Some(10),
+    ///         max_age: Some(Duration::from_secs(86400 * 30)),
+    ///     });
+    /// ```
+    pub fn append_only(mut self, retention: HistoryRetention) -> Self {
+        self.table.history_retention = Some(retention);
+        self
+    }
+
+    /// Limits the number of Entries within the Table.  Once the limit is reached, inserting
+    /// a new Entry either fails with DatabaseError::CapacityExceeded or evicts the least
+    /// recently updated Entry, depending on the CapacityPolicy.  Writes through a Client
+    /// evict as delete does, skipping Entries whose delete a reference restricts.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, CapacityPolicy};
+    /// 
+    /// let table = Table::new()
+    /// #     .name("MyTable".to_string())
+    /// #     .primary_field(FieldType::String).unwrap()
+    /// #     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .max_entries(10000, CapacityPolicy::EvictOldest);
+    /// ```
+    pub fn max_entries(mut self, max_entries: usize, policy: CapacityPolicy) -> Self {
+        self.table.max_entries = Some(max_entries);
+        self.table.capacity_policy = policy;
+        self
+    }
+
+    /// Keeps at most approximately max_resident_bytes of Entries in memory, spilling the
+    /// least recently updated Entries to a segment file stored alongside the database and
+    /// reading them back on demand.  Writing a spilled Entry brings it back into memory.
+    ///
+    /// Tables are only spilled when held by a Client; the segment is named after the
+    /// database file and a hash of the table name, e.g. `my.db.1f2e3d4c5b6a7980.spill`.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    ///
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .spill_after(64 * 1024 * 1024)
+    ///     .build().unwrap();
+    /// ```
+    pub fn spill_after(mut self, max_resident_bytes: usize) -> Self {
+        self.table.spill_after = Some(max_resident_bytes);
+        self
+    }
+
+    /// Stores String values longer than threshold bytes in side files rather than with their
+    /// Entries, so that large values are written once rather than on every save.  Values
+    /// are read back from their file whenever the Entry is read.
+    ///
+    /// Values are only externalized when the Table is held by a Client; the files are kept
+    /// in a directory named after the database file and a hash of the table name, e.g.
+    /// `my.db.1f2e3d4c5b6a7980.blobs`.  Files of values that have been replaced or deleted
+    /// are removed by DatabaseClient::compact.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    ///
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Document".to_string(), FieldType::String).unwrap()
+    ///     .externalize_above(1024 * 1024)
+    ///     .build().unwrap();
+    /// ```
+    pub fn externalize_above(mut self, threshold: usize) -> Self {
+        self.table.externalize_above = Some(threshold);
+        self
+    }
+
+    /// Stores each distinct String value longer than min_len bytes once, however many
+    /// Entries hold it, reducing memory and file size for tables with many repeated values
+    /// such as URLs or user agents.  Values are restored whenever the Entry is read.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    ///
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("UserAgent".to_string(), FieldType::String).unwrap()
+    ///     .deduplicate_above(32)
+    ///     .build().unwrap();
+    /// ```
+    pub fn deduplicate_above(mut self, min_len: usize) -> Self {
+        self.table.deduplicate_above = Some(min_len);
+        self
+    }
+
+    /// Maintains a bloom filter over the primary Fields of the Table, so that looking up a
+    /// key that does not exist usually returns without searching the Entries or reading the
+    /// spill segment.  false_positive_rate is the fraction of such lookups that still search,
+    /// e.g. 0.01.
+    ///
+    /// The filter is kept in memory only and rebuilt when the Table is loaded.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, Field};
+    ///
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .bloom_filter(0.01)
+    ///     .build().unwrap();
+    /// assert!(!table.exists(&Field::String("Missing".to_string())));
+    /// ```
+    pub fn bloom_filter(mut self, false_positive_rate: f64) -> Self {
+        self.table.bloom_filter = Some(false_positive_rate);
+        self
+    }
+
+    /// Maintains a radix tree of the primary Fields of a Table keyed by FieldType::String,
+    /// storing the leading bytes shared by several keys once; suited to long, similar keys
+    /// such as paths or URLs.  Table::scan_prefix uses it to find the Entries whose key
+    /// starts with a prefix without visiting the rest of the Table.
+    ///
+    /// The index is kept in memory only and rebuilt when the Table is loaded.  If the
+    /// primary field is not FieldType::String, build returns DatabaseError::InvalidPrimaryKey.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType};
+    ///
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .prefix_index()
+    ///     .build().unwrap();
+    /// ```
+    pub fn prefix_index(mut self) -> Self {
+        self.table.prefix_index = true;
+        self
+    }
+
+    /// Stores the Table in a columnar layout; values are kept per field in typed columns
+    /// rather than per Entry.  This reduces memory for wide numeric tables and speeds up
+    /// projections, at the cost of materializing an Entry on every read.
+    /// ```
+    /// use persistent_keystore_rs::{Table, FieldType, TableLayout};
+    /// 
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I64).unwrap()
+    ///     .columnar()
+    ///     .build().unwrap();
+    /// # assert_eq!(table.layout(), TableLayout::Columnar);
+    /// ```
+    pub fn columnar(mut self) -> Self {
+        self.layout = TableLayout::Columnar;
+        self
+    }
+
+    /// Sets the NamePolicy the names of the Table and its fields are checked against by
+    /// build, in place of NamePolicy::default
+    /// ```
+    /// use persistent_keystore_rs::{NameCharset, NamePolicy, Table, FieldType};
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    ///
+    /// let policy = NamePolicy{
+    ///     charset: NameCharset::Identifier,
+    ///     ..Default::default()
+    /// };
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Last Seen".to_string(), FieldType::Date).unwrap()
+    ///     .name_policy(policy)
+    ///     .build();
+    /// assert!(matches!(table, Err(DatabaseError::InvalidName(_))));
+    /// ```
+    pub fn name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = policy;
+        self
+    }
+
+    /// Validates the Table is properly configured and returns the Table object.  The names
+    /// of the Table and its fields are checked against its NamePolicy, failing with
+    /// DatabaseError::InvalidName; see TableBuilder::name_policy.
     /// ```
     /// use persistent_keystore_rs::{Table, FieldType};
     /// use std::time::Duration;
@@ -329,29 +1741,144 @@ impl TableBuilder {
     ///     .add_expiration(Duration::from_secs(60))
     ///     .build();
     /// ```
-    pub fn build(self) -> Result<Table, DatabaseError> {
-        if let FieldType::None = self.table.primary_field {
-            return Err(DatabaseError::TableMissingPrimaryKey)
+    pub fn build(mut self) -> Result<Table, DatabaseError> {
+        let primary_field = match self.primary_field {
+            Some(primary_field) => primary_field,
+            None => return Err(DatabaseError::TableMissingPrimaryKey),
+        };
+        self.table.primary_field = primary_field;
 
-        } else if self.table.name.len() == 0 {
+        if self.table.name.is_empty() {
             return Err(DatabaseError::TableNameNotSet)
 
-        } else if self.table.fields.len() == 0 {
+        } else if self.table.fields.is_empty() {
             return Err(DatabaseError::TableMustContainFields)
+
+        } else if self.table.prefix_index && self.table.primary_field != FieldType::String {
+            return Err(DatabaseError::InvalidPrimaryKey)
+        };
+        self.name_policy.check_table_name(&self.table.name)?;
+        let mut fields: Vec<&String> = self.table.fields.keys().collect();
+        fields.sort_unstable();
+        for field in fields {
+            self.name_policy.check_field_name(field)?;
         };
 
-        Ok(self.table)
+        let mut table = self.table;
+        table.entries = TableStorage::new(self.layout, &table.fields);
+        table.rebuild_bloom();
+        table.rebuild_prefixes();
+        table.rebuild_indexes();
+        redact::register(&table.sensitive_fields);
+        Ok(table)
     }
 }
 
+/// How a query locates the Entries it evaluates; see Table::access_for
+enum Access<'a> {
+    Key(&'a Field),
+    KeyPrefix(&'a PrefixIndex, &'a str),
+    Index(&'a CompoundIndex, Vec<Field>),
+    FullScan,
+}
+
 /// Table is a collection of Entry objects that meet a specified format criteria
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Table {
     pub name: String,
     pub primary_field: FieldType,
+    #[serde(serialize_with = "crate::ordered::map")]
     pub fields: HashMap<String, FieldRequirement>,
-    entries: HashMap<Field, Entry>,
+    entries: TableStorage,
     pub expire_after: Option<Duration>,
+    pub expire_schedule: Option<ExpirationSchedule>,
+    pub max_entries: Option<usize>,
+    pub capacity_policy: CapacityPolicy,
+    #[serde(serialize_with = "crate::ordered::map")]
+    pub references: HashMap<String, Reference>,
+    pub history_retention: Option<HistoryRetention>,
+    #[serde(serialize_with = "crate::ordered::map")]
+    history: HashMap<Field, Vec<Arc<Entry>>, EntryHasher>,
+    pub spill_after: Option<usize>,
+    spilled: SpillSegment,
+    pub externalize_above: Option<usize>,
+    blobs: BlobStore,
+    pub deduplicate_above: Option<usize>,
+    strings: StringPool,
+    pub bloom_filter: Option<f64>,
+    #[serde(skip)]
+    bloom: Option<BloomFilter>,
+    pub prefix_index: bool,
+    #[serde(skip)]
+    prefixes: Option<PrefixIndex>,
+    pub indexes: Vec<Vec<String>>,
+    #[serde(skip)]
+    compound: Vec<CompoundIndex>,
+    #[serde(serialize_with = "crate::ordered::set")]
+    pub sensitive_fields: HashSet<String>,
+    pub created_at: Option<SystemTime>,
+    pub last_modified_at: Option<SystemTime>,
+    #[serde(skip)]
+    history_size: usize,
+    /// Primary Fields of the Entries held in memory by update sequence, least recent first;
+    /// the rest of the bookkeeping of each is held with it in entries
+    #[serde(skip)]
+    order: BTreeMap<u64, Field>,
+    /// Primary Fields of the spilled Entries by update sequence; the rest of the bookkeeping
+    /// of each is held with its spill record
+    #[serde(skip)]
+    spilled_order: BTreeMap<u64, Field>,
+    #[serde(skip)]
+    sequence: u64,
+    #[serde(skip)]
+    size: usize,
+    #[serde(skip)]
+    evicted: u64,
+}
+
+/// Limits on the previous versions kept by an append-only Table; unbounded by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRetention {
+    /// Maximum number of previous versions kept per Entry
+    pub max_versions: Option<usize>,
+    /// Previous versions last updated longer ago than this are removed by prune
+    pub max_age: Option<Duration>,
+}
+
+/// Behavior applied to referencing Entries when the Entry they reference is deleted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnDelete {
+    /// The delete fails with DatabaseError::ReferenceViolation
+    Restrict,
+    /// Referencing Entries are deleted as well
+    Cascade,
+    /// The reference is removed from referencing Entries
+    SetMissing,
+}
+
+/// Reference from a field to the primary field of a Table
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    pub table: String,
+    pub on_delete: OnDelete,
+}
+
+/// Action taken when a Table or Database reaches a configured capacity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapacityPolicy {
+    /// The write fails with DatabaseError::CapacityExceeded
+    Reject,
+    /// The least recently updated Entries of the Table are removed to make room
+    EvictOldest,
+}
+
+/// In-memory bookkeeping of a Table for an Entry, held with the Entry in its storage or
+/// spill record; rebuilt by Table::rebase after loading
+#[derive(Clone, Copy, Default)]
+pub(crate) struct EntryState {
+    monotonic: Option<Instant>,
+    sequence: u64,
+    size: usize,
 }
 
 impl Table {
@@ -364,11 +1891,42 @@ impl Table {
         TableBuilder{
             table: Table{
                 name: String::new(),
-                primary_field: FieldType::None,
+                // Replaced by build with the FieldType set by primary_field
+                primary_field: FieldType::String,
                 fields: HashMap::new(),
-                entries: HashMap::new(),
+                entries: TableStorage::new(TableLayout::Row, &HashMap::new()),
                 expire_after: None,
-            }
+                expire_schedule: None,
+                max_entries: None,
+                capacity_policy: CapacityPolicy::Reject,
+                references: HashMap::new(),
+                history_retention: None,
+                history: HashMap::default(),
+                spill_after: None,
+                spilled: SpillSegment::default(),
+                externalize_above: None,
+                blobs: BlobStore::default(),
+                deduplicate_above: None,
+                strings: StringPool::default(),
+                bloom_filter: None,
+                bloom: None,
+                prefix_index: false,
+                prefixes: None,
+                indexes: Vec::new(),
+                compound: Vec::new(),
+                sensitive_fields: HashSet::new(),
+                created_at: None,
+                last_modified_at: None,
+                history_size: 0,
+                order: BTreeMap::new(),
+                spilled_order: BTreeMap::new(),
+                sequence: 0,
+                size: 0,
+                evicted: 0,
+            },
+            primary_field: None,
+            layout: TableLayout::Row,
+            name_policy: NamePolicy::default(),
         }
     }
 
@@ -391,13 +1949,202 @@ impl Table {
     /// # table.insert(entry).unwrap();
     /// let result = table.get(&Field::String("MyFirstEntry".to_string())).unwrap();
     /// ```
-    pub fn get(&self, key: &Field) -> Result<&Entry, DatabaseError> {
-        match self.entries.get_key_value(key) {
-            Some((_, v)) => return Ok(v),
+    pub fn get(&self, key: &Field) -> Result<Arc<Entry>, DatabaseError> {
+        match self.lookup(key) {
+            Some(v) => return Ok(v),
             None => return Err(DatabaseError::EntryDoesNotExists),
         }
     }
 
+    /// Returns the Entry with the supplied primary Field, whether in memory or spilled
+    pub(crate) fn lookup(&self, key: &Field) -> Option<Arc<Entry>> {
+        if !self.might_contain(key) {
+            return None
+        };
+        let entry = match self.entries.get(key) {
+            Some(v) => v,
+            None => self.spilled.get(key)?,
+        };
+        Some(self.blobs.resolve(self.strings.resolve(entry)))
+    }
+
+    /// Returns true if the Entry exists, whether in memory or spilled
+    pub(crate) fn contains(&self, key: &Field) -> bool {
+        self.might_contain(key) && (self.entries.contains_key(key) || self.spilled.contains_key(key))
+    }
+
+    /// Returns false if the Entry with the supplied primary Field certainly does not exist,
+    /// as answered by the bloom filter of the Table; always true without one
+    pub fn might_contain(&self, key: &Field) -> bool {
+        self.bloom.as_ref().is_none_or(|b| b.might_contain(key))
+    }
+
+    /// Returns true if an Entry with the supplied primary Field exists within the Table
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// assert!(table.exists(&Field::String("MyFirstEntry".to_string())));
+    /// ```
+    pub fn exists(&self, key: &Field) -> bool {
+        self.contains(key)
+    }
+
+    /// Rebuilds the bloom filter from the primary Fields of the Table, if configured
+    fn rebuild_bloom(&mut self) {
+        let mut bloom = match self.bloom_filter {
+            Some(rate) => BloomFilter::new(self.len(), rate),
+            None => {
+                self.bloom = None;
+                return
+            },
+        };
+        for key in self.entries.keys().iter().chain(self.spilled.keys()) {
+            bloom.insert(key);
+        };
+        self.bloom = Some(bloom);
+    }
+
+    /// Rebuilds the prefix index from the primary Fields of the Table, if configured
+    fn rebuild_prefixes(&mut self) {
+        if !self.prefix_index {
+            self.prefixes = None;
+            return
+        };
+        let mut prefixes = PrefixIndex::default();
+        for key in self.entries.keys().iter().chain(self.spilled.keys()) {
+            if let Field::String(k) = key {
+                prefixes.insert(k);
+            };
+        };
+        self.prefixes = Some(prefixes);
+    }
+
+    /// Rebuilds the compound indexes from the Entries of the Table
+    fn rebuild_indexes(&mut self) {
+        let mut compound: Vec<CompoundIndex> = self.indexes.iter()
+            .map(|fields| CompoundIndex::new(fields.clone()))
+            .collect();
+        if !compound.is_empty() {
+            for entry in self.iter_entries() {
+                for index in compound.iter_mut() {
+                    index.insert(&entry);
+                };
+            };
+        };
+        self.compound = compound;
+    }
+
+    /// Replaces the previous version of an Entry in the compound indexes with the current
+    fn reindex(&mut self, previous: Option<&Entry>, current: Option<&Entry>) {
+        for index in self.compound.iter_mut() {
+            if let Some(p) = previous {
+                index.remove(p);
+            };
+            if let Some(c) = current {
+                index.insert(c);
+            };
+        };
+    }
+
+    /// Returns how the Entries meeting the required Conditions are located: a PRIMARY_FIELD_KEY
+    /// Condition of Equals, or of StartsWith with a prefix index, is preferred to the
+    /// compound index chosen by index_for
+    fn access_for<'a>(&'a self, required: &'a HashMap<String, Condition>) -> Access<'a> {
+        match (required.get(PRIMARY_FIELD_KEY), &self.prefixes) {
+            (Some(Condition::Equals(key)), _) => return Access::Key(key),
+            (Some(Condition::StartsWith(prefix)), Some(prefixes)) => return Access::KeyPrefix(prefixes, prefix),
+            _ => {},
+        };
+        match self.index_for(required) {
+            Some((index, values)) => Access::Index(index, values),
+            None => Access::FullScan,
+        }
+    }
+
+    /// Returns the compound index covering the longest leading run of Equals Conditions,
+    /// along with the values of that run
+    fn index_for(&self, conditions: &HashMap<String, Condition>) -> Option<(&CompoundIndex, Vec<Field>)> {
+        self.compound.iter()
+            .map(|index| (index, index.covered(conditions)))
+            .filter(|(_, values)| !values.is_empty())
+            .max_by_key(|(_, values)| values.len())
+    }
+
+    /// Returns the persisted last_timestamp of the Entry, whether in memory or spilled
+    fn stored_timestamp(&self, key: &Field) -> Option<SystemTime> {
+        match self.entries.last_timestamp(key) {
+            Some(t) => Some(t),
+            None => self.spilled.last_timestamp(key),
+        }
+    }
+
+    /// Removes the Entry from memory or the spill segment along with its bookkeeping,
+    /// returning true if it existed
+    fn remove_entry(&mut self, key: &Field) -> bool {
+        if !self.compound.is_empty() {
+            if let Some(previous) = self.lookup(key) {
+                self.reindex(Some(&previous), None);
+            };
+        };
+        self.untrack(key);
+        self.blobs.remove(key);
+        self.strings.remove(key);
+        let removed = self.entries.remove(key).is_some() || self.spilled.remove(key);
+        if let (Some(prefixes), Field::String(k)) = (self.prefixes.as_mut(), key) {
+            prefixes.remove(k);
+        };
+        if let Some(bloom) = self.bloom.as_mut().filter(|_| removed) {
+            bloom.remove();
+            if bloom.is_stale() {
+                self.rebuild_bloom();
+            };
+        };
+        removed
+    }
+
+    /// Adds a primary Field that did not exist to the bloom filter and prefix index, if
+    /// configured
+    fn add_key(&mut self, key: &Field) {
+        if let (Some(prefixes), Field::String(k)) = (self.prefixes.as_mut(), key) {
+            prefixes.insert(k);
+        };
+        if self.bloom.as_ref().is_some_and(|b| b.is_stale()) {
+            self.rebuild_bloom();
+        };
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.insert(key);
+        };
+    }
+
+    /// Returns an iterator over every Entry; spilled Entries and externalized values are
+    /// read as they are reached
+    pub(crate) fn iter_entries(&self) -> impl Iterator<Item = Arc<Entry>> + '_ {
+        self.entries.iter()
+            .chain(self.spilled.iter())
+            .map(|e| self.blobs.resolve(self.strings.resolve(e)))
+    }
+
+    /// Moves the values of the Entry beyond externalize_above to side files, and those
+    /// beyond deduplicate_above to the shared pool
+    fn externalize(&mut self, entry: &mut Entry) -> Result<(), DatabaseError> {
+        if let Some(threshold) = self.externalize_above {
+            self.blobs.externalize(entry, threshold)?;
+        };
+        if let Some(min_len) = self.deduplicate_above {
+            self.strings.intern(entry, min_len);
+        };
+        Ok(())
+    }
+
     /// Inserts the provided entry into the Table
     /// If the primary Field exists, DatabaseError::EntryExists is returned.
     /// ```
@@ -416,21 +2163,45 @@ impl Table {
     ///    .build().unwrap();
     /// table.insert(entry).unwrap();
     /// ```
-    pub fn insert(&mut self, mut entry: Entry) -> Result<(), DatabaseError> {
+    pub fn insert(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.insert_at(entry, Timestamp::now())
+    }
+
+    /// Inserts the provided entry into the Table, recording timestamp as the time it was
+    /// last updated.
+    /// If the primary Field exists, DatabaseError::EntryExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, Timestamp};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let entry = Entry::new()
+    ///    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///    .build().unwrap();
+    /// table.insert_at(entry, Timestamp::now()).unwrap();
+    /// ```
+    pub fn insert_at(&mut self, mut entry: Entry, timestamp: Timestamp) -> Result<(), DatabaseError> {
         self.validate_field_types(&entry)?;
         self.validate_required_fields(&entry)?;
-        entry.last_timestamp = Some(SystemTime::now());
+        entry.last_timestamp = Some(timestamp.wall);
 
-        match self.get(&entry.primary_field) {
-            Ok(_) => return Err(DatabaseError::EntryExists),
-            Err(_) => {
-                
-                match self.entries.insert(entry.primary_field.clone(), entry) {
-                    Some(_) => {},
-                    None => {}
-                }
-            }
-        }
+        if self.contains(&entry.primary_field) {
+            return Err(DatabaseError::EntryExists)
+        };
+
+        self.ensure_capacity(&entry.primary_field, timestamp.wall)?;
+        let indexed = (!self.compound.is_empty()).then(|| entry.clone());
+        self.externalize(&mut entry)?;
+        let state = self.track(&entry, Some(timestamp.monotonic));
+        self.add_key(&entry.primary_field);
+        self.entries.insert(entry, state);
+        self.reindex(None, indexed.as_ref());
+        self.spill_cold();
+        self.last_modified_at = Some(timestamp.wall);
         Ok(())
     }
 
@@ -488,27 +2259,584 @@ impl Table {
     /// 
     /// table.update(entry).unwrap();
     /// ```
-    pub fn update(&mut self, mut entry: Entry) -> Result<(), DatabaseError> {
-        self.validate_field_types(&entry)?;
-        self.validate_required_fields(&entry)?;
-        entry.last_timestamp = Some(SystemTime::now());
-
-        match self.entries.insert(entry.primary_field.clone(), entry) {
-            Some(_) => {},
-            None => {}
-        }
-        Ok(())
+    pub fn update(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.update_at(entry, Timestamp::now())
     }
 
-    /// Validates that all required fields are provided and that no fields are provided
-    /// that are not configured in the table.
-    fn validate_required_fields(&self, entry: &Entry) -> Result<(), DatabaseError> {
-        let mut fields: Vec<&String> = Vec::new();
-        for k in self.fields.keys() {
+    /// Updates the provided entry within the Table, recording timestamp as the time it was
+    /// last updated.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, Timestamp};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let entry = Entry::new()
+    ///    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///    .build().unwrap();
+    /// table.update_at(entry, Timestamp::now()).unwrap();
+    /// ```
+    pub fn update_at(&mut self, mut entry: Entry, timestamp: Timestamp) -> Result<(), DatabaseError> {
+        self.validate_entry(&entry)?;
+        entry.last_timestamp = Some(timestamp.wall);
+
+        self.ensure_capacity(&entry.primary_field, timestamp.wall)?;
+        let previous = match self.history_retention.is_some() || !self.compound.is_empty() {
+            true => self.lookup(&entry.primary_field),
+            false => None,
+        };
+        if let Some(p) = previous.as_ref().filter(|_| self.history_retention.is_some()) {
+            self.append_history(p.clone());
+        };
+
+        let added = !self.contains(&entry.primary_field);
+        let indexed = (!self.compound.is_empty()).then(|| entry.clone());
+        self.externalize(&mut entry)?;
+        let state = self.track(&entry, Some(timestamp.monotonic));
+        self.spilled.remove(&entry.primary_field);
+        if added {
+            self.add_key(&entry.primary_field);
+        };
+        self.entries.insert(entry, state);
+        self.reindex(previous.as_deref(), indexed.as_ref());
+        self.spill_cold();
+        self.last_modified_at = Some(timestamp.wall);
+        Ok(())
+    }
+
+    /// Returns the Entry with the supplied primary Field with the supplied fields set and
+    /// its other fields kept, without writing it; optional fields set to Field::Null are
+    /// cleared.  The result is validated when written with update_at.
+    /// If the Entry does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, Timestamp};
+    /// use std::collections::HashMap;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_optional_field(String::from("Notes"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .add_field("Notes".to_string(), Field::String("draft".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let key = Field::String("MyFirstEntry".to_string());
+    /// let patched = table.patched(&key, HashMap::from([("Notes".to_string(), Field::Null)])).unwrap();
+    /// assert_eq!(patched.fields["Count"], Field::I64(1));
+    /// assert_eq!(patched.fields["Notes"], Field::Null);
+    /// table.update_at(patched, Timestamp::now()).unwrap();
+    /// ```
+    pub fn patched(&self, key: &Field, fields: HashMap<String, Field>) -> Result<Entry, DatabaseError> {
+        let mut entry = match self.lookup(key) {
+            Some(e) => Entry::clone(&e),
+            None => return Err(DatabaseError::EntryDoesNotExists),
+        };
+        entry.fields.extend(fields);
+        Ok(entry)
+    }
+
+    /// Returns the Entry with the supplied primary Field without the named fields, without
+    /// writing it.  The result is validated when written with update_at, so removing a
+    /// required field fails there with DatabaseError::MissingRequiredField.
+    /// If a name is not a field of the Table, DatabaseError::UnsupportedField is returned;
+    /// if the Entry does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, Timestamp};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_optional_field(String::from("Notes"), FieldType::String).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .add_field("Notes".to_string(), Field::String("draft".to_string())).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let key = Field::String("MyFirstEntry".to_string());
+    /// let unset = table.unset(&key, &["Notes".to_string()]).unwrap();
+    /// assert!(!unset.fields.contains_key("Notes"));
+    /// table.update_at(unset, Timestamp::now()).unwrap();
+    /// ```
+    pub fn unset(&self, key: &Field, fields: &[String]) -> Result<Entry, DatabaseError> {
+        if let Some(f) = fields.iter().find(|f| !self.fields.contains_key(*f)) {
+            return Err(DatabaseError::UnsupportedField(f.clone()))
+        };
+        let mut entry = match self.lookup(key) {
+            Some(e) => Entry::clone(&e),
+            None => return Err(DatabaseError::EntryDoesNotExists),
+        };
+        for f in fields {
+            entry.fields.remove(f);
+        };
+        Ok(entry)
+    }
+
+    /// Sets the last_timestamp of the Entry with the supplied primary Field to the supplied
+    /// time, making it the most recently updated, without changing its fields; returns
+    /// false if the Entry does not exist.  Cheaper than update_at, as the Entry is neither
+    /// validated nor reindexed, and no previous version is kept by append-only Tables.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{Entry, Field, Timestamp};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// assert!(table.touch_at(&Field::String("MyFirstEntry".to_string()), Timestamp::now()).unwrap());
+    /// assert!(!table.touch_at(&Field::String("Missing".to_string()), Timestamp::now()).unwrap());
+    /// ```
+    pub fn touch_at(&mut self, key: &Field, timestamp: Timestamp) -> Result<bool, DatabaseError> {
+        if self.entries.touch(key, timestamp.wall) {
+            self.retrack(key, Some(timestamp.monotonic));
+            return Ok(true)
+        };
+        match self.spilled.get(key) {
+            Some(entry) => {
+                let mut entry = Arc::unwrap_or_clone(entry);
+                entry.last_timestamp = Some(timestamp.wall);
+                let state = self.spilled.state(key).unwrap_or_default();
+                self.spilled.write(&entry, state)?;
+                self.retrack(key, Some(timestamp.monotonic));
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Keeps the previous version of an Entry, dropping the oldest versions beyond
+    /// max_versions
+    fn append_history(&mut self, previous: Arc<Entry>) {
+        let max_versions = self.history_retention.and_then(|r| r.max_versions);
+        self.history_size += previous.approx_size();
+        let versions = self.history.entry(previous.primary_field.clone()).or_default();
+        versions.push(previous);
+
+        if let Some(max) = max_versions {
+            while versions.len() > max {
+                self.history_size -= versions.remove(0).approx_size();
+            };
+        };
+    }
+
+    /// Removes every previous version of the Entry, returning their approximate size
+    fn remove_history(&mut self, key: &Field) -> usize {
+        match self.history.remove(key) {
+            Some(versions) => {
+                let size = versions.iter().map(|v| v.approx_size()).sum();
+                self.history_size -= size;
+                size
+            },
+            None => 0,
+        }
+    }
+
+    /// Returns every version of the Entry matching the primary Field, oldest first, ending
+    /// with the current version.  Tables that are not append-only only return the current
+    /// version.
+    /// If the primary Field does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, FieldType};
+    /// use persistent_keystore_rs::{Field, HistoryRetention};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .append_only(HistoryRetention::default())
+    /// #    .build().unwrap();
+    /// # for count in 0..3 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(count)).unwrap()
+    /// #        .build().unwrap();
+    /// #     table.insert_or_update(entry).unwrap();
+    /// # };
+    /// let versions = table.history(&Field::String("MyFirstEntry".to_string())).unwrap();
+    /// assert_eq!(versions.len(), 3);
+    /// assert_eq!(versions[2].fields["Count"], Field::I64(2));
+    /// ```
+    pub fn history(&self, key: &Field) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let current = self.get(key)?;
+        let mut versions = match self.history.get(key) {
+            Some(v) => v.clone(),
+            None => Vec::new(),
+        };
+        versions.push(current);
+        Ok(versions)
+    }
+
+    /// Makes room for the Entry with the supplied primary Field according to max_entries,
+    /// as of now
+    fn ensure_capacity(&mut self, key: &Field, now: SystemTime) -> Result<(), DatabaseError> {
+        let max_entries = match self.max_entries {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+
+        if self.contains(key) {
+            return Ok(())
+        };
+
+        while self.len() >= max_entries {
+            match self.capacity_policy {
+                CapacityPolicy::Reject => {
+                    return Err(DatabaseError::CapacityExceeded(format!("table {} is limited to {} entries", self.name, max_entries)))
+                },
+                CapacityPolicy::EvictOldest => {
+                    if !self.evict_oldest(key, now) {
+                        return Err(DatabaseError::CapacityExceeded(format!("table {} is limited to {} entries", self.name, max_entries)))
+                    };
+                },
+            };
+        };
+        Ok(())
+    }
+
+    /// Removes the least recently updated Entry other than keep as of now, returning false
+    /// if there is no other Entry to remove
+    fn evict_oldest(&mut self, keep: &Field, now: SystemTime) -> bool {
+        let mut oldest: Option<(u64, Field)> = None;
+        for order in [&self.order, &self.spilled_order] {
+            if let Some((sequence, key)) = order.iter().find(|(_, k)| *k != keep) {
+                if oldest.as_ref().is_none_or(|(s, _)| sequence < s) {
+                    oldest = Some((*sequence, key.clone()));
+                };
+            };
+        };
+
+        let Some((_, key)) = oldest else {
+            return false
+        };
+        self.remove_history(&key);
+        self.remove_entry(&key);
+        self.evicted += 1;
+        self.last_modified_at = Some(now);
+        true
+    }
+
+    /// Returns the bookkeeping of the Entry, whether in memory or spilled
+    fn state(&self, key: &Field) -> Option<EntryState> {
+        self.entries.state(key).or_else(|| self.spilled.state(key))
+    }
+
+    /// Returns the bookkeeping of the Entry for modification, whether in memory or spilled
+    fn state_mut(&mut self, key: &Field) -> Option<&mut EntryState> {
+        match self.entries.contains_key(key) {
+            true => self.entries.state_mut(key),
+            false => self.spilled.state_mut(key),
+        }
+    }
+
+    /// Records the Entry as the most recently updated, replacing any previous state, and
+    /// returns its state to be stored with it
+    fn track(&mut self, entry: &Entry, monotonic: Option<Instant>) -> EntryState {
+        self.untrack(&entry.primary_field);
+        self.sequence += 1;
+        let size = entry.approx_size();
+        self.size += size;
+        self.order.insert(self.sequence, entry.primary_field.clone());
+        EntryState{
+            monotonic,
+            sequence: self.sequence,
+            size,
+        }
+    }
+
+    /// Records the Entry, in memory or spilled, as the most recently updated, keeping its
+    /// size
+    fn retrack(&mut self, key: &Field, monotonic: Option<Instant>) {
+        let (state, order) = match self.entries.contains_key(key) {
+            true => (self.entries.state_mut(key), &mut self.order),
+            false => (self.spilled.state_mut(key), &mut self.spilled_order),
+        };
+        if let Some(state) = state {
+            order.remove(&state.sequence);
+            self.sequence += 1;
+            state.sequence = self.sequence;
+            state.monotonic = monotonic;
+            order.insert(self.sequence, key.clone());
+        };
+    }
+
+    /// Removes the Entry from the update order and clears its state, returning its
+    /// approximate size
+    fn untrack(&mut self, key: &Field) -> usize {
+        let state = match self.state_mut(key) {
+            Some(state) => std::mem::take(state),
+            None => return 0,
+        };
+        self.order.remove(&state.sequence);
+        self.spilled_order.remove(&state.sequence);
+        self.size -= state.size;
+        state.size
+    }
+
+    /// Moves the least recently updated Entries to the spill segment until the Entries in
+    /// memory fit within spill_after.  Entries that cannot be written remain in memory.
+    fn spill_cold(&mut self) {
+        let limit = match self.spill_after {
+            Some(l) if self.spilled.is_attached() => l,
+            _ => return,
+        };
+
+        while self.size > limit {
+            let (sequence, key) = match self.order.iter().next() {
+                Some((s, k)) => (*s, k.clone()),
+                None => break,
+            };
+            let entry = match self.entries.get(&key) {
+                Some(e) => e,
+                None => break,
+            };
+
+            let state = self.entries.state(&key).unwrap_or_default();
+            if let Err(e) = self.spilled.write(&entry, EntryState{size: 0, ..state}) {
+                error!("Unable to spill entry {} of table {}: {}", key, self.name, e);
+                break
+            };
+            self.entries.remove(&key);
+            self.order.remove(&sequence);
+            self.spilled_order.insert(sequence, key.clone());
+            self.size -= state.size;
+        };
+    }
+
+    /// Attaches the spill segment of the Table to the segment file at the supplied path and
+    /// spills Entries beyond spill_after.  Entries spilled to the segment of another
+    /// database, such as a Table merged from another file, are first read back into memory.
+    pub(crate) fn attach_spill(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        if self.spilled.is_attached() {
+            if self.spilled.path().as_deref() == Some(path) {
+                return Ok(())
+            };
+
+            for (entry, mut state) in self.spilled.drain()? {
+                if self.spilled_order.remove(&state.sequence).is_some() {
+                    state.size = entry.approx_size();
+                    self.size += state.size;
+                    self.order.insert(state.sequence, entry.primary_field.clone());
+                };
+                self.entries.insert(entry, state);
+            };
+        };
+
+        self.spilled.attach(path)?;
+        self.spill_cold();
+        Ok(())
+    }
+
+    /// Stores the externalized values of the Table in the supplied directory.  Values stored
+    /// in the directory of another database, such as a Table merged from another file, are
+    /// copied.
+    pub(crate) fn attach_blobs(&mut self, dir: &Path) -> Result<(), DatabaseError> {
+        match self.blobs.dir() {
+            Some(d) if d == dir => Ok(()),
+            Some(_) => self.blobs.copy_to(dir),
+            None => self.blobs.attach(dir),
+        }
+    }
+
+    /// Releases the memory left unused by Entries removed from the Table
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.history.shrink_to_fit();
+    }
+
+    /// Removes the files of externalized values no longer referenced by the Table,
+    /// returning the number removed
+    pub(crate) fn collect_blobs(&self) -> Result<u64, DatabaseError> {
+        self.blobs.collect()
+    }
+
+    /// Flushes the spill segment of the Table to disk
+    pub(crate) fn sync_spill(&self) -> Result<(), DatabaseError> {
+        self.spilled.sync()
+    }
+
+    /// Returns the approximate in-memory size of the Entry with the supplied primary Field
+    pub(crate) fn entry_size(&self, key: &Field) -> Option<usize> {
+        self.state(key).map(|s| s.size)
+    }
+
+    /// Returns the approximate in-memory size of the Entry with the supplied primary Field
+    /// and its previous versions, or 0 if it does not exist
+    pub(crate) fn footprint(&self, key: &Field) -> usize {
+        let history: usize = match self.history.get(key) {
+            Some(versions) => versions.iter().map(|v| v.approx_size()).sum(),
+            None => 0,
+        };
+        self.entry_size(key).unwrap_or(0) + history
+    }
+
+    /// Returns the approximate in-memory size of the Entries within the Table
+    pub fn approx_size(&self) -> usize {
+        self.size + self.history_size
+    }
+
+    /// Returns the current usage and limits of the Table
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let stats = table.stats();
+    /// assert_eq!(stats.entries, 1);
+    /// ```
+    pub fn stats(&self) -> TableStats {
+        TableStats{
+            entries: self.len(),
+            approx_bytes: self.size + self.history_size,
+            max_entries: self.max_entries,
+            evicted: self.evicted,
+            spilled: self.spilled.len(),
+            spill_garbage_bytes: self.spilled.garbage(),
+            externalized: self.blobs.len(),
+            deduplicated: self.strings.len(),
+            prefix_index_bytes: self.prefixes.as_ref().map_or(0, |p| p.approx_size()),
+            entry_sizes: self.entry_sizes(),
+            created_at: self.created_at,
+            last_modified_at: self.last_modified_at,
+        }
+    }
+
+    /// Returns the distribution of the approximate sizes of the Entries held in memory
+    fn entry_sizes(&self) -> EntrySizeStats {
+        let mut sizes = EntrySizeStats::default();
+        for key in self.order.values() {
+            if let Some(state) = self.entries.state(key) {
+                sizes.record(key, state.size);
+            };
+        };
+        sizes
+    }
+
+    /// Checks the Entries of the Table against its schema and the structures used to locate
+    /// and account for them, adding every inconsistency found to the report
+    pub(crate) fn verify(&self, report: &mut VerifyReport) {
+        let mut problem = |key: Option<Field>, message: String| {
+            report.problems.push(VerifyProblem{
+                table: Some(self.name.clone()),
+                key,
+                message,
+            });
+        };
+
+        let storage = self.entries.verify().into_iter()
+            .chain(self.spilled.verify())
+            .chain(self.blobs.verify())
+            .chain(self.strings.verify());
+        for (key, message) in storage {
+            problem(key, message);
+        };
+        if let Some(prefixes) = self.prefixes.as_ref().filter(|p| p.len() != self.len()) {
+            problem(None, format!("prefix index holds {} keys for {} entries", prefixes.len(), self.len()));
+        };
+
+        let mut size = 0;
+        let mut entries = 0;
+        for entry in self.iter_entries() {
+            entries += 1;
+            let key = Some(entry.primary_field.clone());
+            if let Err(e) = self.validate_field_types(&entry) {
+                problem(key.clone(), format!("{}", e));
+            };
+            if let Err(e) = self.validate_required_fields(&entry) {
+                problem(key.clone(), format!("{}", e));
+            };
+            for index in self.compound.iter().filter(|i| !i.contains(&entry)) {
+                problem(key.clone(), format!("entry is missing from index on ({})", index.fields().join(", ")));
+            };
+
+            let order = match self.spilled.contains_key(&entry.primary_field) {
+                true => &self.spilled_order,
+                false => &self.order,
+            };
+            let state = self.state(&entry.primary_field).unwrap_or_default();
+            size += state.size;
+            if order.get(&state.sequence) != Some(&entry.primary_field) {
+                problem(key, format!("missing from update order"));
+            };
+        };
+
+        let tracked = self.order.len() + self.spilled_order.len();
+        if tracked != entries {
+            problem(None, format!("{} entries stored but {} tracked", entries, tracked));
+        };
+        if size != self.size {
+            problem(None, format!("approximate size is {} but entries total {}", self.size, size));
+        };
+
+        for key in self.history.keys() {
+            if !self.contains(key) {
+                problem(Some(key.clone()), format!("history kept for a missing entry"));
+            };
+        };
+        report.entries_checked += entries;
+        report.tables_checked += 1;
+    }
+
+    /// Validates the fields of the Entry as update_at does, without writing it
+    pub(crate) fn validate_entry(&self, entry: &Entry) -> Result<(), DatabaseError> {
+        self.validate_field_types(entry)?;
+        self.validate_required_fields(entry)
+    }
+
+    /// Returns the error the write of the Entry would fail with, if any, without making it
+    /// or evicting anything to make room for it; so that a write that will fail can be
+    /// refused before Entries of this or another Table are evicted for it
+    pub(crate) fn check_write(&self, entry: &Entry, kind: WriteKind) -> Result<(), DatabaseError> {
+        self.validate_entry(entry)?;
+        let exists = self.contains(&entry.primary_field);
+        if kind == WriteKind::Insert && exists {
+            return Err(DatabaseError::EntryExists)
+        };
+        match self.max_entries {
+            Some(max) if !exists && self.capacity_policy == CapacityPolicy::Reject && self.len() >= max => {
+                Err(DatabaseError::CapacityExceeded(format!("table {} is limited to {} entries", self.name, max)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes the write of the supplied kind; see insert_at and update_at
+    pub(crate) fn write_at(&mut self, entry: Entry, kind: WriteKind, timestamp: Timestamp) -> Result<(), DatabaseError> {
+        match kind {
+            WriteKind::Insert => self.insert_at(entry, timestamp),
+            WriteKind::Update => self.update_at(entry, timestamp),
+        }
+    }
+
+    /// Validates that all required fields are provided and that no fields are provided
+    /// that are not configured in the table.
+    fn validate_required_fields(&self, entry: &Entry) -> Result<(), DatabaseError> {
+        let mut fields: Vec<&String> = Vec::new();
+        for k in self.fields.keys() {
             fields.push(k);
         };
 
-        for (k, _) in &entry.fields {
+        for k in entry.fields.keys() {
             if self.fields.contains_key(k) {
                 match fields.iter().position(|r| r == &k) {
                     Some(index) => {
@@ -522,34 +2850,31 @@ impl Table {
         }
 
         for f in fields {
-            match self.fields.get_key_value(f) {
-                Some((_, requirement)) => {
-                    match requirement {
-                        FieldRequirement::Required(_) => return Err(DatabaseError::MissingRequiredField(f.clone())),
-                        FieldRequirement::Optional(_) => {},
-                    }
-                },
-                None => {}
-            }
+            if let Some(FieldRequirement::Required(_)) = self.fields.get(f) {
+                return Err(DatabaseError::MissingRequiredField(f.clone()))
+            };
         };
 
         Ok(())
     }
 
     /// Validates that the fields provided within the entry, matches the field types
-    /// of the Entry with the field types specified in the table.
+    /// of the Entry with the field types specified in the table.  Custom values are
+    /// validated by the codecs of their tags, and Enum values by the declared variants.
+    /// Only optional fields may be Field::Null.
     fn validate_field_types(&self, entry: &Entry) -> Result<(), DatabaseError> {
-        if self.primary_field != entry.primary_field.get_type() {
-            return Err(DatabaseError::MismatchedFieldType)
+        self.primary_field.check_value(&entry.primary_field)?;
+        for field in std::iter::once(&entry.primary_field).chain(entry.fields.values()) {
+            if let Field::Custom{tag, bytes} = field {
+                crate::custom::validate(tag, bytes)?;
+            };
         };
 
         for (k, v) in &entry.fields {
             match self.fields.get_key_value(k) {
-                Some((_, value)) => {
-                    if value.clone().unwrap() != v.get_type() {
-                        return Err(DatabaseError::MismatchedFieldType)
-                    }
-                },
+                Some((_, FieldRequirement::Optional(_))) if *v == Field::Null => {},
+                Some((_, FieldRequirement::Required(_))) if *v == Field::Null => return Err(DatabaseError::MissingRequiredField(k.clone())),
+                Some((_, value)) => value.unwrap().check_value(v)?,
                 None => {},
             }
         }
@@ -576,12 +2901,316 @@ impl Table {
     /// table.delete(Field::String("MyFirstEntry".to_string())).unwrap();
     /// ```
     pub fn delete(&mut self, primary_field: Field) -> Result<(), DatabaseError> {
-        match self.entries.remove_entry(&primary_field) {
-            Some(_) => return Ok(()),
-            None => return Err(DatabaseError::EntryDoesNotExists),
+        match self.remove_entry(&primary_field) {
+            true => {
+                self.remove_history(&primary_field);
+                return Ok(())
+            },
+            false => return Err(DatabaseError::EntryDoesNotExists),
+        }
+    }
+
+    /// Returns the primary Field of every Entry within the Table
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// # use std::time::{Duration, SystemTime};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let keys = table.keys();
+    /// # assert_eq!(keys, vec![Field::String("MyFirstEntry".to_string())]);
+    /// ```
+    pub fn keys(&self) -> Vec<Field> {
+        let mut keys = self.entries.keys();
+        keys.extend(self.spilled.keys().cloned());
+        keys
+    }
+
+    /// Returns the update sequence of the most recently updated Entry; Entries updated
+    /// later are numbered after it
+    pub(crate) fn update_sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns up to limit primary Fields of the Entries, in memory or spilled, last updated
+    /// after the update sequence after and no later than until, least recently updated
+    /// first, with the sequence to resume from or None once every such Entry is returned.
+    /// Entries updated in between are renumbered past until, so a cursor resumed after the
+    /// Table changed neither repeats nor skips the Entries left unchanged.
+    pub(crate) fn keys_updated_between(&self, after: u64, until: u64, limit: usize) -> (Vec<Field>, Option<u64>) {
+        if after >= until {
+            return (Vec::new(), None)
+        };
+        let mut updated: Vec<(u64, &Field)> = self.order.range(after + 1..=until).take(limit)
+            .chain(self.spilled_order.range(after + 1..=until).take(limit))
+            .map(|(s, k)| (*s, k))
+            .collect();
+        updated.sort_unstable_by_key(|(s, _)| *s);
+        updated.truncate(limit);
+        let next = match updated.len() < limit {
+            true => None,
+            false => updated.last().map(|(s, _)| *s),
+        };
+        (updated.into_iter().map(|(_, k)| k.clone()).collect(), next)
+    }
+
+    /// Returns how long ago the Entry with the supplied primary Field was last updated.
+    /// 
+    /// Entries updated within the current process are measured with monotonic time and are
+    /// unaffected by changes to the wall clock.  Otherwise the persisted last_timestamp is
+    /// used, and an Entry whose last_timestamp is in the future is treated as just updated.
+    /// Returns None if the Entry does not exist or has never been timestamped.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::Timestamp;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let age = table.age(&Field::String("MyFirstEntry".to_string()), Timestamp::now());
+    /// # assert!(age.is_some());
+    /// ```
+    pub fn age(&self, key: &Field, now: Timestamp) -> Option<Duration> {
+        if let Some(EntryState{monotonic: Some(updated), ..}) = self.state(key) {
+            return Some(now.monotonic.saturating_duration_since(updated))
+        };
+
+        let last_timestamp = self.stored_timestamp(key)?;
+        match now.wall.duration_since(last_timestamp) {
+            Ok(age) => Some(age),
+            Err(_) => Some(Duration::ZERO),
         }
     }
 
+    /// Returns how long until the Entry with the supplied primary Field expires, as measured
+    /// by prune; zero if it has expired but not yet been pruned.  Returns None if the Table
+    /// does not expire Entries.
+    /// If the Entry does not exist, DatabaseError::EntryDoesNotExists is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::Timestamp;
+    /// use std::time::Duration;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_expiration(Duration::from_secs(60))
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # let now = Timestamp::now();
+    /// # table.insert_at(entry, now).unwrap();
+    /// let ttl = table.time_to_live(&Field::String("MyFirstEntry".to_string()), now).unwrap();
+    /// assert_eq!(ttl, Some(Duration::from_secs(60)));
+    /// ```
+    pub fn time_to_live(&self, key: &Field, now: Timestamp) -> Result<Option<Duration>, DatabaseError> {
+        if !self.contains(key) {
+            return Err(DatabaseError::EntryDoesNotExists)
+        };
+
+        let mut ttl = match (self.expire_after, self.age(key, now)) {
+            (Some(expire_after), Some(age)) => Some(expire_after.saturating_sub(age)),
+            _ => None,
+        };
+
+        if let (Some(schedule), Some(last_timestamp)) = (&self.expire_schedule, self.stored_timestamp(key)) {
+            if let Some(boundary) = schedule.next_boundary(last_timestamp) {
+                let remaining = boundary.duration_since(now.wall).unwrap_or(Duration::ZERO);
+                ttl = Some(ttl.map_or(remaining, |t| t.min(remaining)));
+            };
+        };
+        Ok(ttl)
+    }
+
+    /// Records the monotonic update time of every Entry from its persisted last_timestamp,
+    /// relative to now; used after a Table is loaded from disk so that later wall clock
+    /// changes do not affect expiration.  Entry sizes and update order are rebuilt as well.
+    pub fn rebase(&mut self, now: Timestamp) {
+        redact::register(&self.sensitive_fields);
+        let mut entries = Vec::new();
+        let resident = self.entries.values().into_iter().map(|e| (e.primary_field.clone(), Some(e)));
+        let spilled = self.spilled.keys().map(|k| (k.clone(), None));
+        for (key, entry) in resident.chain(spilled) {
+            let updated = match self.age(&key, now) {
+                Some(age) => {
+                    match now.monotonic.checked_sub(age) {
+                        Some(i) => Some(i),
+                        None => Some(now.monotonic),
+                    }
+                },
+                None => None,
+            };
+            entries.push((updated, key, entry));
+        };
+
+        entries.sort_by_key(|(updated, _, _)| *updated);
+        for (updated, key, entry) in entries {
+            match entry {
+                Some(e) => {
+                    let state = self.track(&e, updated);
+                    if let Some(s) = self.entries.state_mut(&key) {
+                        *s = state;
+                    };
+                },
+                None => self.retrack(&key, updated),
+            };
+        };
+
+        self.history_size = self.history.values()
+            .flat_map(|versions| versions.iter())
+            .map(|v| v.approx_size())
+            .sum();
+        self.strings.rebuild();
+        self.rebuild_bloom();
+        self.rebuild_prefixes();
+        self.rebuild_indexes();
+    }
+
+    /// Removes the entries matching the supplied primary Fields that have expired as of
+    /// now, returning the number of entries removed.  Age is measured as by Table::age;
+    /// an ExpirationSchedule is compared against the wall clock last_timestamp.
+    /// 
+    /// Each entry is re-evaluated at the time of removal, so an entry updated after its key
+    /// was collected is kept.  Keys that no longer exist are ignored.  If anything is
+    /// removed, last_modified_at is set to now.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::Timestamp;
+    /// use std::time::{Duration, SystemTime};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .add_expiration(Duration::from_secs(60))
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .build().unwrap();
+    /// # let now = Timestamp::now();
+    /// # table.insert_at(entry, now).unwrap();
+    /// let keys = table.keys();
+    /// let later = Timestamp{
+    ///     wall: now.wall + Duration::from_secs(120),
+    ///     monotonic: now.monotonic + Duration::from_secs(120),
+    /// };
+    /// let removed = table.prune_keys(&keys, later);
+    /// # assert_eq!(removed, 1);
+    /// ```
+    pub fn prune_keys(&mut self, keys: &[Field], now: Timestamp) -> u64 {
+        let boundary = match &self.expire_schedule {
+            Some(s) => s.last_boundary(now.wall),
+            None => None,
+        };
+
+        let history_cutoff = match self.history_retention.and_then(|r| r.max_age) {
+            Some(max_age) => now.wall.checked_sub(max_age),
+            None => None,
+        };
+
+        if self.expire_after.is_none() && boundary.is_none() && history_cutoff.is_none() {
+            return 0
+        };
+
+        self.prune_histories(keys, now);
+        let mut removed = 0;
+        for key in keys {
+            if self.is_expired(key, now, boundary) {
+                self.remove_entry(key);
+                self.remove_history(key);
+                self.last_modified_at = Some(now.wall);
+                removed += 1;
+            }
+        };
+        removed
+    }
+
+    /// Removes the previous versions of the Entries with the supplied primary Fields that
+    /// are older than the max_age of the HistoryRetention of the Table; returns true if any
+    /// were removed
+    pub(crate) fn prune_histories(&mut self, keys: &[Field], now: Timestamp) -> bool {
+        let history_cutoff = match self.history_retention.and_then(|r| r.max_age) {
+            Some(max_age) => now.wall.checked_sub(max_age),
+            None => None,
+        };
+
+        let mut pruned = false;
+        if let Some(cutoff) = history_cutoff {
+            for key in keys {
+                if self.prune_history(key, cutoff) {
+                    self.last_modified_at = Some(now.wall);
+                    pruned = true;
+                };
+            };
+        };
+        pruned
+    }
+
+    /// Returns the supplied primary Fields of the Entries that prune_keys would remove as of
+    /// now, without removing them
+    pub(crate) fn expired_keys(&self, keys: &[Field], now: Timestamp) -> Vec<Field> {
+        let boundary = match &self.expire_schedule {
+            Some(s) => s.last_boundary(now.wall),
+            None => None,
+        };
+        keys.iter().filter(|k| self.is_expired(k, now, boundary)).cloned().collect()
+    }
+
+    /// Returns true if the Entry with the supplied primary Field is older than the
+    /// expiration of the Table, or was last updated before the boundary of its schedule
+    fn is_expired(&self, key: &Field, now: Timestamp, boundary: Option<SystemTime>) -> bool {
+        let mut expired = match (self.expire_after, self.age(key, now)) {
+            (Some(expire_after), Some(age)) => age > expire_after,
+            _ => false,
+        };
+
+        if let Some(b) = boundary {
+            if let Some(last_timestamp) = self.stored_timestamp(key) {
+                expired = expired || last_timestamp < b;
+            };
+        };
+        expired
+    }
+
+    /// Removes the previous versions of the Entry last updated before cutoff, returning true
+    /// if any was removed
+    fn prune_history(&mut self, key: &Field, cutoff: SystemTime) -> bool {
+        let mut freed = 0;
+        if let Some(versions) = self.history.get_mut(key) {
+            versions.retain(|v| {
+                let keep = match v.last_timestamp {
+                    Some(t) => t >= cutoff,
+                    None => false,
+                };
+                if !keep {
+                    freed += v.approx_size();
+                };
+                keep
+            });
+
+            if versions.is_empty() {
+                self.history.remove(key);
+            };
+        };
+        self.history_size -= freed;
+        freed > 0
+    }
+
     /// Returns all Entries from the Table
     /// ```
     /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
@@ -601,33 +3230,545 @@ impl Table {
     /// #    .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
     /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
     /// #    .build().unwrap();
-    /// # table.insert(entry2).unwrap();
-    /// # let entry3 = Entry::new()
-    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
-    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// # table.insert(entry2).unwrap();
+    /// # let entry3 = Entry::new()
+    /// #    .set_primary_field(Field::String("MyThirdEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(SystemTime::now())).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry3).unwrap();
+    /// let results = table.scan().unwrap();
+    /// # assert_eq!(results.len(), 3);
+    /// ```
+    pub fn scan(&self) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        Ok(self.iter_entries().collect())
+    }
+
+    /// Returns the Entries last updated longer ago than older_than, as measured by age,
+    /// oldest first; Entries of the same age are ordered by primary Field.  Independent of
+    /// the expiration of the Table, so that stale Entries can be refreshed before they
+    /// expire, or in Tables that never expire them.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::Timestamp;
+    /// use std::time::Duration;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let now = Timestamp::now();
+    /// assert!(table.stale(Duration::from_secs(3600), now).unwrap().is_empty());
+    /// # let later = Timestamp{ wall: now.wall + Duration::from_secs(7200), monotonic: now.monotonic + Duration::from_secs(7200) };
+    /// # assert_eq!(table.stale(Duration::from_secs(3600), later).unwrap().len(), 1);
+    /// ```
+    pub fn stale(&self, older_than: Duration, now: Timestamp) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let mut stale: Vec<(Duration, Field)> = self.keys().into_iter()
+            .filter_map(|key| match self.age(&key, now) {
+                Some(age) if age > older_than => Some((age, key)),
+                _ => None,
+            })
+            .collect();
+        stale.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(stale.into_iter().filter_map(|(_, key)| self.lookup(&key)).collect())
+    }
+
+    /// Returns the Entries that will expire within the supplied duration, with the time
+    /// left until each expires as returned by time_to_live, soonest first; Entries expiring
+    /// at the same time are ordered by primary Field.  Entries already expired but not yet
+    /// pruned are returned with no time left.  Tables without an expiration return none.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::Timestamp;
+    /// use std::time::Duration;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .add_expiration(Duration::from_secs(3600))
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let now = Timestamp::now();
+    /// assert!(table.expiring(Duration::from_secs(60), now).unwrap().is_empty());
+    /// let expiring = table.expiring(Duration::from_secs(7200), now).unwrap();
+    /// assert!(expiring[0].1 <= Duration::from_secs(3600));
+    /// ```
+    pub fn expiring(&self, within: Duration, now: Timestamp) -> Result<Vec<(Arc<Entry>, Duration)>, DatabaseError> {
+        if self.expire_after.is_none() && self.expire_schedule.is_none() {
+            return Ok(Vec::new())
+        };
+        let mut expiring = Vec::new();
+        for key in self.keys() {
+            if let Some(ttl) = self.time_to_live(&key, now)?.filter(|ttl| *ttl <= within) {
+                expiring.push((ttl, key));
+            };
+        };
+        expiring.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(expiring.into_iter().filter_map(|(ttl, key)| self.lookup(&key).map(|e| (e, ttl))).collect())
+    }
+
+    /// Returns the Entries whose String primary Field starts with prefix, ordered by
+    /// primary Field.  Tables built with TableBuilder::prefix_index visit only the matching
+    /// Entries; others are scanned in full.
+    /// If the primary field is not FieldType::String, DatabaseError::InvalidPrimaryKey is
+    /// returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .prefix_index()
+    /// #    .build().unwrap();
+    /// for path in ["/usr/bin/ls", "/usr/lib/libc.so", "/etc/hosts"] {
+    ///     let entry = Entry::new()
+    ///         .set_primary_field(Field::String(path.to_string())).unwrap()
+    ///         .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///         .build().unwrap();
+    ///     table.insert(entry).unwrap();
+    /// };
+    /// let results = table.scan_prefix("/usr/").unwrap();
+    /// assert_eq!(results.len(), 2);
+    /// assert_eq!(results[0].primary_field, Field::String("/usr/bin/ls".to_string()));
+    /// ```
+    pub fn scan_prefix(&self, prefix: &str) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        if self.primary_field != FieldType::String {
+            return Err(DatabaseError::InvalidPrimaryKey)
+        };
+
+        if let Some(prefixes) = &self.prefixes {
+            let entries = prefixes.keys_with_prefix(prefix).into_iter()
+                .filter_map(|k| self.lookup(&Field::String(k)))
+                .collect();
+            return Ok(entries)
+        };
+
+        let mut entries: Vec<(String, Arc<Entry>)> = self.iter_entries()
+            .filter_map(|e| match &e.primary_field {
+                Field::String(k) if k.starts_with(prefix) => Some((k.clone(), e.clone())),
+                _ => None,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries.into_iter().map(|(_, e)| e).collect())
+    }
+
+    /// Returns all Entries that meet every supplied Condition, with time based
+    /// Conditions evaluated relative to now
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType, Condition};
+    /// # use std::collections::HashMap;
+    /// # use std::time::{Duration, SystemTime};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("TimeStamp"), FieldType::Date).unwrap()
+    /// #    .build().unwrap();
+    /// # let now = SystemTime::now();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("TimeStamp".to_string(), Field::Date(now - Duration::from_secs(3600))).unwrap()
+    /// #    .build().unwrap();
+    /// # table.insert(entry).unwrap();
+    /// let mut conditions = HashMap::new();
+    /// conditions.insert("TimeStamp".to_string(), Condition::OlderThan(Duration::from_secs(60)));
+    /// let results = table.filter(&conditions, now).unwrap();
+    /// # assert_eq!(results.len(), 1);
+    /// ```
+    pub fn filter(&self, conditions: &HashMap<String, Condition>, now: SystemTime) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.filter_cancellable(conditions, now, &CancellationToken::new())
+    }
+
+    /// Returns all Entries that meet every supplied Condition, as Table::filter.
+    /// If the token is cancelled before the Table has been filtered,
+    /// DatabaseError::Cancelled is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::CancellationToken;
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// use std::collections::HashMap;
+    /// use std::time::SystemTime;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let token = CancellationToken::new();
+    /// token.cancel();
+    /// let result = table.filter_cancellable(&HashMap::new(), SystemTime::now(), &token);
+    /// assert!(matches!(result, Err(DatabaseError::Cancelled)));
+    /// ```
+    pub fn filter_cancellable(&self, conditions: &HashMap<String, Condition>, now: SystemTime, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let mut results = Vec::new();
+        self.for_each_match(conditions, now, token, |entry| {
+            results.push(entry);
+            Ok(())
+        })?;
+        Ok(results)
+    }
+
+    /// Returns up to limit Entries that meet every supplied Condition, as
+    /// Table::filter_cancellable, and true if more Entries meet them.  Reading stops at the
+    /// first Entry beyond the limit, so a small limit is answered without a full scan.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// use persistent_keystore_rs::CancellationToken;
+    /// use std::collections::HashMap;
+    /// use std::time::SystemTime;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # for i in 0..5 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(i)).unwrap()
+    /// #        .build().unwrap();
+    /// #     table.insert(entry).unwrap();
+    /// # };
+    /// let token = CancellationToken::new();
+    /// let (results, more) = table.filter_limit(&HashMap::new(), SystemTime::now(), 3, &token).unwrap();
+    /// assert_eq!((results.len(), more), (3, true));
+    /// let (results, more) = table.filter_limit(&HashMap::new(), SystemTime::now(), 5, &token).unwrap();
+    /// assert_eq!((results.len(), more), (5, false));
+    /// ```
+    pub fn filter_limit(&self, conditions: &HashMap<String, Condition>, now: SystemTime, limit: usize, token: &CancellationToken) -> Result<(Vec<Arc<Entry>>, bool), DatabaseError> {
+        let mut results = Vec::new();
+        let mut more = false;
+        self.for_each_candidate(conditions, |entry| matches_all(entry, conditions, now), token, |entry| {
+            if results.len() == limit {
+                more = true;
+                return Ok(ControlFlow::Break(()))
+            };
+            results.push(entry);
+            Ok(ControlFlow::Continue(()))
+        })?;
+        Ok((results, more))
+    }
+
+    /// Calls visit with every Entry that meets every supplied Condition, as
+    /// Table::filter_cancellable, stopping at the first error returned by visit
+    pub(crate) fn for_each_match<F>(&self, conditions: &HashMap<String, Condition>, now: SystemTime, token: &CancellationToken, mut visit: F) -> Result<(), DatabaseError>
+    where
+        F: FnMut(Arc<Entry>) -> Result<(), DatabaseError>,
+    {
+        self.for_each_candidate(conditions, |entry| matches_all(entry, conditions, now), token, |entry| {
+            visit(entry).map(ControlFlow::Continue)
+        })
+    }
+
+    /// Calls visit with every Entry for which matches returns true, reading only the
+    /// Entries of an index covering the required Conditions, if any, until visit breaks
+    fn for_each_candidate<M, F>(&self, required: &HashMap<String, Condition>, matches: M, token: &CancellationToken, mut visit: F) -> Result<(), DatabaseError>
+    where
+        M: Fn(&Entry) -> bool,
+        F: FnMut(Arc<Entry>) -> Result<ControlFlow<()>, DatabaseError>,
+    {
+        token.check()?;
+        let entries: Box<dyn Iterator<Item = Arc<Entry>>> = match self.access_for(required) {
+            Access::Key(key) => Box::new(self.lookup(key).into_iter()),
+            Access::KeyPrefix(prefixes, prefix) => Box::new(prefixes.keys_with_prefix(prefix).into_iter().filter_map(|k| self.lookup(&Field::String(k)))),
+            Access::Index(index, values) => Box::new(index.get(&values).into_iter().filter_map(|k| self.lookup(&k))),
+            Access::FullScan => Box::new(self.iter_entries()),
+        };
+
+        for (n, i) in entries.enumerate() {
+            if n % CANCELLATION_CHECK_INTERVAL == 0 {
+                token.check()?;
+            };
+
+            if matches(&i) && visit(i)?.is_break() {
+                break
+            };
+        };
+        Ok(())
+    }
+
+    /// Returns all Entries that match the Filter, with time based Conditions evaluated
+    /// relative to now.
+    /// If the token is cancelled before the Table has been filtered,
+    /// DatabaseError::Cancelled is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, FieldType};
+    /// use persistent_keystore_rs::{CancellationToken, Condition, Field, Filter};
+    /// use std::time::SystemTime;
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// # for i in 0..10 {
+    /// #     let entry = Entry::new()
+    /// #        .set_primary_field(Field::String(format!("Entry{}", i))).unwrap()
+    /// #        .add_field("Count".to_string(), Field::I64(i)).unwrap()
+    /// #        .build().unwrap();
+    /// #     table.insert(entry).unwrap();
+    /// # };
+    /// let filter = Filter::field("Count", Condition::LessThan(Field::I64(2)))
+    ///     .or(Filter::field("Count", Condition::GreaterThan(Field::I64(8))));
+    /// let results = table.query_filter(&filter, SystemTime::now(), &CancellationToken::new()).unwrap();
+    /// assert_eq!(results.len(), 3);
+    /// ```
+    pub fn query_filter(&self, filter: &Filter, now: SystemTime, token: &CancellationToken) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        let mut results = Vec::new();
+        self.for_each_candidate(&filter.required(), |entry| filter.matches(entry, now), token, |entry| {
+            results.push(entry);
+            Ok(ControlFlow::Continue(()))
+        })?;
+        Ok(results)
+    }
+
+    /// Returns the QueryPlan describing how Table::filter evaluates the supplied Conditions
+    /// ```
+    /// # use persistent_keystore_rs::{Table, FieldType};
+    /// use persistent_keystore_rs::{AccessPath, Condition, Field};
+    /// use std::collections::HashMap;
+    /// # let table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .build().unwrap();
+    /// let mut conditions = HashMap::new();
+    /// conditions.insert("Count".to_string(), Condition::GreaterThan(Field::I64(1)));
+    /// let plan = table.explain(&conditions);
+    /// assert_eq!(plan.access, AccessPath::FullScan);
+    /// assert_eq!(plan.residual.len(), 1);
+    /// ```
+    pub fn explain(&self, conditions: &HashMap<String, Condition>) -> QueryPlan {
+        let mut residual: Vec<(String, Condition)> = conditions.iter()
+            .map(|(k, c)| (k.clone(), c.clone()))
+            .collect();
+        residual.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let (index, values) = match self.access_for(conditions) {
+            Access::Index(index, values) => (index, values),
+            Access::FullScan => {
+                return QueryPlan{
+                    table: self.name.clone(),
+                    access: AccessPath::FullScan,
+                    pushed_down: Vec::new(),
+                    residual,
+                    estimated_rows_scanned: self.len(),
+                }
+            },
+            Access::Key(key) => {
+                let pushed_down = residual.iter().position(|(k, _)| k == PRIMARY_FIELD_KEY).map(|i| residual.remove(i));
+                return QueryPlan{
+                    table: self.name.clone(),
+                    access: AccessPath::PrimaryKey,
+                    pushed_down: pushed_down.into_iter().collect(),
+                    residual,
+                    estimated_rows_scanned: self.lookup(key).map_or(0, |_| 1),
+                }
+            },
+            Access::KeyPrefix(prefixes, prefix) => {
+                let pushed_down = residual.iter().position(|(k, _)| k == PRIMARY_FIELD_KEY).map(|i| residual.remove(i));
+                return QueryPlan{
+                    table: self.name.clone(),
+                    access: AccessPath::PrimaryKeyPrefix(prefix.to_string()),
+                    pushed_down: pushed_down.into_iter().collect(),
+                    residual,
+                    estimated_rows_scanned: prefixes.keys_with_prefix(prefix).len(),
+                }
+            },
+        };
+
+        let used = &index.fields()[..values.len()];
+        let pushed_down = used.iter()
+            .zip(values.iter())
+            .map(|(k, v)| (k.clone(), Condition::Equals(v.clone())))
+            .collect();
+        residual.retain(|(k, _)| !used.contains(k));
+        QueryPlan{
+            table: self.name.clone(),
+            access: AccessPath::Index(used.to_vec()),
+            pushed_down,
+            residual,
+            estimated_rows_scanned: index.get(&values).len(),
+        }
+    }
+
+    /// Returns the primary Field and the value of the supplied field for every Entry
+    /// that has the field set.  Columnar tables read a single column to answer this.
+    /// If the field is not part of the Table, DatabaseError::UnsupportedField is returned.
+    /// ```
+    /// # use persistent_keystore_rs::{Table, Entry, Field, FieldType};
+    /// # let mut table = Table::new()
+    /// #    .name(String::from("MyTable"))
+    /// #    .primary_field(FieldType::String).unwrap()
+    /// #    .add_field(String::from("Count"), FieldType::I64).unwrap()
+    /// #    .columnar()
+    /// #    .build().unwrap();
+    /// # let entry = Entry::new()
+    /// #    .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    /// #    .add_field("Count".to_string(), Field::I64(3)).unwrap()
     /// #    .build().unwrap();
-    /// # table.insert(entry3).unwrap();
-    /// let results = table.scan().unwrap();
-    /// # assert_eq!(results.len(), 3);
+    /// # table.insert(entry).unwrap();
+    /// let counts = table.project(&"Count".to_string()).unwrap();
+    /// # assert_eq!(counts, vec![(Field::String("MyFirstEntry".to_string()), Field::I64(3))]);
     /// ```
-    pub fn scan(&self) -> Result<Vec<Entry>, DatabaseError> {
-        let mut results = Vec::new();
-        for (_, v) in &self.entries {
-            results.push(v.clone())
+    pub fn project(&self, field: &String) -> Result<Vec<(Field, Field)>, DatabaseError> {
+        if !self.fields.contains_key(field) {
+            return Err(DatabaseError::UnsupportedField(field.clone()))
+        };
+        let mut results = self.entries.project(field);
+        for entry in self.spilled.iter() {
+            if let Some(value) = entry.fields.get(field) {
+                results.push((entry.primary_field.clone(), value.clone()));
+            };
         };
+        results.extend(self.blobs.project(field));
+        results.extend(self.strings.project(field));
         Ok(results)
     }
+
+    /// Returns the number of Entries within the Table
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.spilled.len()
+    }
+
+    /// Returns true if the Table contains no Entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the TableLayout used to store the Entries of the Table
+    pub fn layout(&self) -> TableLayout {
+        self.entries.layout()
+    }
+
+    /// Attaches the Table, if it spills or externalizes values, to its files alongside the
+    /// database file at the supplied path; see Database::attach_files
+    pub(crate) fn attach_files(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        if self.externalize_above.is_some() || self.blobs.len() > 0 {
+            self.attach_blobs(&blob_dir(path, &self.name))?;
+        };
+        if self.spill_after.is_some() || self.spilled.len() > 0 {
+            self.attach_spill(&segment_path(path, &self.name))?;
+        };
+        Ok(())
+    }
+
+    /// Returns a Table with the name, schema and settings of the Table but none of its
+    /// Entries or history
+    pub(crate) fn empty_copy(&self) -> Result<Table, DatabaseError> {
+        let mut builder = Table::new();
+        builder.table.name = self.name.clone();
+        builder.table.fields = self.fields.clone();
+        builder.table.expire_after = self.expire_after;
+        builder.table.expire_schedule = self.expire_schedule.clone();
+        builder.table.max_entries = self.max_entries;
+        builder.table.capacity_policy = self.capacity_policy;
+        builder.table.references = self.references.clone();
+        builder.table.history_retention = self.history_retention;
+        builder.table.spill_after = self.spill_after;
+        builder.table.externalize_above = self.externalize_above;
+        builder.table.deduplicate_above = self.deduplicate_above;
+        builder.table.bloom_filter = self.bloom_filter;
+        builder.table.prefix_index = self.prefix_index;
+        builder.table.indexes = self.indexes.clone();
+        builder.table.sensitive_fields = self.sensitive_fields.clone();
+        builder.primary_field = Some(self.primary_field.clone());
+        builder.layout = self.layout();
+        builder.name_policy = NamePolicy::unrestricted();
+        builder.build()
+    }
+}
+
+/// Kind of a write of an Entry to a Table; see Table::check_write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum WriteKind {
+    /// Table::insert_at; the Entry must not exist
+    Insert,
+    /// Table::update_at; the Entry is inserted or replaced
+    Update,
+}
+
+/// Outcome of DatabaseClient::delete_many_limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeleteBatch {
+    /// Number of entries deleted from the table
+    pub deleted: u64,
+    /// True if entries meeting the criteria remain, so another call would delete more
+    pub more: bool,
+}
+
+/// Outcome of DatabaseClient::insert_or_update
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    /// No Entry with the primary Field existed, or it had expired
+    Inserted,
+    /// The Entry replaced the previous version
+    Updated{previous: Arc<Entry>},
+}
+
+impl UpsertOutcome {
+    /// Returns true if the Entry was inserted rather than updated
+    pub fn is_inserted(&self) -> bool {
+        matches!(self, UpsertOutcome::Inserted)
+    }
 }
 
 /// Entry represents all items that are contained within a Table
-#[derive(Clone, Serialize, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Entry {
     pub primary_field: Field,
+    #[serde(serialize_with = "crate::ordered::map")]
     pub fields: HashMap<String, Field>,
     pub last_timestamp: Option<SystemTime>,
 }
 
+/// Defines the accessors of Entry for the named Fields of a variant holding a Copy value;
+/// see Entry::get_string
+macro_rules! typed_accessors {
+    ($get:ident, $try_get:ident, $t:ty, $variant:ident) => {
+        #[doc = concat!("Returns the value of the named Field::", stringify!($variant), "; None if the Entry has no such Field or it is of another FieldType")]
+        pub fn $get(&self, name: &str) -> Option<$t> {
+            self.$try_get(name).ok().flatten()
+        }
+
+        #[doc = concat!("Returns the value of the named Field::", stringify!($variant), "; Ok(None) if the Entry has no such Field, and DatabaseError::TypeMismatch if it is of another FieldType")]
+        pub fn $try_get(&self, name: &str) -> Result<Option<$t>, DatabaseError> {
+            self.typed_field(name, FieldType::$variant, |f| match f {
+                Field::$variant(v) => Some(*v),
+                _ => None,
+            })
+        }
+    };
+}
+
 impl Entry {
+    /// Returns the approximate in-memory size of the Entry in bytes, as counted against
+    /// the capacity limits of the Table and database and in TableStats::entry_sizes
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// let small = Entry::new()
+    ///     .set_primary_field(Field::String("small".to_string())).unwrap()
+    ///     .add_field("Notes".to_string(), Field::String("a".to_string())).unwrap()
+    ///     .build().unwrap();
+    /// let large = Entry::new()
+    ///     .set_primary_field(Field::String("large".to_string())).unwrap()
+    ///     .add_field("Notes".to_string(), Field::String("a".repeat(1000))).unwrap()
+    ///     .build().unwrap();
+    /// assert!(large.approx_size() >= small.approx_size() + 999);
+    /// ```
+    pub fn approx_size(&self) -> usize {
+        let mut size = std::mem::size_of::<Entry>() + self.primary_field.heap_size();
+        for (k, v) in &self.fields {
+            size += std::mem::size_of::<(String, Field)>() + k.len() + v.heap_size();
+        };
+        size
+    }
+
     /// Returns an EntryBuilder Instance that will be used to create a new entry
     /// ```
     /// use persistent_keystore_rs::Entry;
@@ -635,11 +3776,32 @@ impl Entry {
     /// ```
     pub fn new() -> EntryBuilder {
         EntryBuilder{
-            entry: Entry{
-                primary_field: Field::NotImplemented,
-                fields: HashMap::new(),
-                last_timestamp: None,
-            }
+            primary_field: None,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Returns an EntryBuilder holding the primary Field and fields of the Entry, so that a
+    /// modified copy can be built without adding every unchanged field again.  The
+    /// last_timestamp is not kept; it is set when the Entry is written.
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(1)).unwrap()
+    ///     .add_field("Notes".to_string(), Field::String("First".to_string())).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// let updated = entry.to_builder()
+    ///     .add_field("Count".to_string(), Field::I64(2)).unwrap()
+    ///     .build().unwrap();
+    /// assert_eq!(updated.get_i64("Count"), Some(2));
+    /// assert_eq!(updated.get_string("Notes"), Some("First"));
+    /// ```
+    pub fn to_builder(&self) -> EntryBuilder {
+        EntryBuilder{
+            primary_field: Some(self.primary_field.clone()),
+            fields: self.fields.clone(),
         }
     }
 
@@ -668,27 +3830,87 @@ impl Entry {
         };
         None
     }
+
+    /// Returns the named Field read by value; Ok(None) if the Entry has no such Field, and
+    /// DatabaseError::TypeMismatch if it is not of FieldType expected
+    fn typed_field<'a, T>(&'a self, name: &str, expected: FieldType, value: impl FnOnce(&'a Field) -> Option<T>) -> Result<Option<T>, DatabaseError> {
+        let field = match self.fields.get(name) {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        match value(field) {
+            Some(v) => Ok(Some(v)),
+            None => Err(DatabaseError::TypeMismatch(format!("field {} is {:?}, not {:?}", name, field.get_type(), expected))),
+        }
+    }
+
+    /// Returns the value of the named Field::String; None if the Entry has no such Field or
+    /// it is of another FieldType.  See try_get_string to tell the two apart.
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("Notes".to_string(), Field::String("First".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(3)).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// assert_eq!(entry.get_string("Notes"), Some("First"));
+    /// assert_eq!(entry.get_i64("Count"), Some(3));
+    /// assert_eq!(entry.get_string("Count"), None);
+    /// ```
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        self.try_get_string(name).ok().flatten()
+    }
+
+    /// Returns the value of the named Field::String; Ok(None) if the Entry has no such
+    /// Field, and DatabaseError::TypeMismatch if it is of another FieldType
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field};
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(3)).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// assert_eq!(entry.try_get_string("Notes").unwrap(), None);
+    /// assert!(matches!(entry.try_get_string("Count"), Err(DatabaseError::TypeMismatch(_))));
+    /// ```
+    pub fn try_get_string(&self, name: &str) -> Result<Option<&str>, DatabaseError> {
+        self.typed_field(name, FieldType::String, |f| match f {
+            Field::String(v) => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    typed_accessors!(get_i64, try_get_i64, i64, I64);
+    typed_accessors!(get_i32, try_get_i32, i32, I32);
+    typed_accessors!(get_u64, try_get_u64, u64, U64);
+    typed_accessors!(get_u32, try_get_u32, u32, U32);
+    typed_accessors!(get_date, try_get_date, SystemTime, Date);
+    typed_accessors!(get_bool, try_get_bool, bool, Bool);
 }
 
 impl fmt::Display for Entry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let msg = match &self.primary_field {
-            Field::String(v) => format!("{}", v),
-            Field::I32(v) => format!("{}", v),
-            Field::I64(v) => format!("{}", v),
-            Field::U64(v) => format!("{}", v),
-            Field::U32(v) => format!("{}", v),
-            Field::Date(v) => format!("{:?}", v),
-            Field::Bool(v) => format!("{}", v),
-            Field::NotImplemented => format!("NotImplemented"),
-        };
-        write!(f, "{}", msg)
+        write!(f, "{}", self.primary_field)
+    }
+}
+
+/// Masks the values of sensitive fields; see TableBuilder::sensitive_field
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("primary_field", &self.primary_field)
+            .field("fields", &redact::RedactedFields(&self.fields))
+            .field("last_timestamp", &self.last_timestamp)
+            .finish()
     }
 }
 
 /// Builder Pattern for creating new Entry items to be inserted into a Table
 pub struct EntryBuilder {
-    entry: Entry,
+    primary_field: Option<Field>,
+    fields: HashMap<String, Field>,
 }
 
 impl EntryBuilder {
@@ -699,10 +3921,7 @@ impl EntryBuilder {
     ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap();
     /// ```
     pub fn set_primary_field(mut self, field: Field) -> Result<Self, DatabaseError> {
-        if let FieldType::None = field.get_type() {
-            return Err(DatabaseError::InvalidPrimaryKey)
-        };
-        self.entry.primary_field = field;
+        self.primary_field = Some(field);
         Ok(self)
     }
 
@@ -714,15 +3933,11 @@ impl EntryBuilder {
     ///     .add_field("Count".to_string(), Field::I32(0)).unwrap();
     /// ```
     pub fn add_field(mut self, key: String, field: Field) -> Result<Self, DatabaseError> {
-        if let FieldType::None = field.get_type() {
-            return Err(DatabaseError::UnsupportedFieldType)
-        };
-
-        if key.len() == 0 {
+        if key.is_empty() {
             return Err(DatabaseError::InvalidPrimaryKey)
         }
 
-        self.entry.fields.insert(key, field);
+        self.fields.insert(key, field);
         Ok(self)
     }
 
@@ -736,19 +3951,55 @@ impl EntryBuilder {
     ///     .build().unwrap();
     /// ```
     pub fn build(self) -> Result<Entry, DatabaseError> {
-        if let FieldType::None = self.entry.primary_field.get_type() {
-            return Err(DatabaseError::InvalidPrimaryKey)
+        let primary_field = match self.primary_field {
+            Some(primary_field) => primary_field,
+            None => return Err(DatabaseError::InvalidPrimaryKey),
         };
 
-        if self.entry.fields.len() == 0 {
+        if self.fields.is_empty() {
             return Err(DatabaseError::EntryMustContainFields)
         }
 
-        Ok(self.entry)
+        Ok(Entry{
+            primary_field,
+            fields: self.fields,
+            last_timestamp: None,
+        })
+    }
+
+    /// Validates the Entry as build, then against the schema of the Table, as an insert into
+    /// it would; so that an Entry that does not fit the Table is rejected where it is built
+    /// rather than when it is written.  Checks made against the contents of the Table, such
+    /// as its capacity and references, are still made when it is written.
+    /// ```
+    /// use persistent_keystore_rs::{Entry, Field, FieldType, Table};
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// let table = Table::new()
+    ///     .name("MyTable".to_string())
+    ///     .primary_field(FieldType::String).unwrap()
+    ///     .add_field("Count".to_string(), FieldType::I32).unwrap()
+    ///     .build().unwrap();
+    ///
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MyFirstEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I32(3)).unwrap()
+    ///     .build_for(&table).unwrap();
+    ///
+    /// let entry = Entry::new()
+    ///     .set_primary_field(Field::String("MySecondEntry".to_string())).unwrap()
+    ///     .add_field("Count".to_string(), Field::I64(3)).unwrap()
+    ///     .build_for(&table);
+    /// assert!(matches!(entry, Err(DatabaseError::MismatchedFieldType)));
+    /// ```
+    pub fn build_for(self, table: &Table) -> Result<Entry, DatabaseError> {
+        let entry = self.build()?;
+        table.validate_field_types(&entry)?;
+        table.validate_required_fields(&entry)?;
+        Ok(entry)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(loom)))]
 mod tests {
     use super::*;
 
@@ -760,7 +4011,7 @@ mod tests {
             .add_field("OptionalKey".to_string(), Field::String("My second entry".to_string())).unwrap()
             .build().unwrap();
 
-        if let None = entry.get_field("FirstKey".to_string()) {
+        if entry.get_field("FirstKey".to_string()).is_none() {
             panic!("Expected value, received none")
         }
     }
@@ -777,4 +4028,551 @@ mod tests {
             panic!("Expected None, received {}", s)
         }
     }
+
+    #[test]
+    fn field_display_parse_round_trip() {
+        let fields = vec![
+            Field::String("My String".to_string()),
+            Field::I64(-12312312),
+            Field::I32(-123),
+            Field::U64(12312312),
+            Field::U32(123),
+            Field::Date(SystemTime::UNIX_EPOCH + Duration::from_millis(1518568087250)),
+            Field::Bool(true),
+        ];
+
+        for field in fields {
+            let parsed = Field::parse(field.get_type(), &format!("{}", field)).unwrap();
+            assert_eq!(parsed, field);
+        };
+
+        if let Ok(f) = Field::parse(FieldType::I64, "not a number") {
+            panic!("Expected InvalidFormat, received {}", f)
+        };
+    }
+
+
+    #[test]
+    fn sensitive_fields_redacted() {
+        let entry = Entry::new()
+            .set_primary_field(Field::String("Account".to_string())).unwrap()
+            .add_field("RedactedToken".to_string(), Field::String("s3cr3t-token".to_string())).unwrap()
+            .add_field("RedactedOwner".to_string(), Field::String("visible-owner".to_string())).unwrap()
+            .build().unwrap();
+        assert!(format!("{:?}", entry).contains("s3cr3t-token"));
+
+        if Table::new()
+            .name("Redacted".to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_field("RedactedToken".to_string(), FieldType::String).unwrap()
+            .sensitive_field("Missing".to_string()).is_ok() {
+            panic!("Expected UnsupportedField for an undefined sensitive field")
+        };
+
+        let mut table = Table::new()
+            .name("Redacted".to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_field("RedactedToken".to_string(), FieldType::String).unwrap()
+            .add_field("RedactedOwner".to_string(), FieldType::String).unwrap()
+            .sensitive_field("RedactedToken".to_string()).unwrap()
+            .build().unwrap();
+        table.insert(entry.clone()).unwrap();
+
+        for debug in [format!("{:?}", entry), format!("{:?}", table.get(&entry.primary_field).unwrap())] {
+            assert!(!debug.contains("s3cr3t-token"), "{}", debug);
+            assert!(debug.contains(crate::redact::REDACTED));
+            assert!(debug.contains("visible-owner"));
+        };
+        assert_eq!(entry.fields["RedactedToken"], Field::String("s3cr3t-token".to_string()));
+    }
+
+
+    #[test]
+    fn builders_require_primary_field() {
+        let entry = Entry::new()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build();
+        assert!(matches!(entry, Err(DatabaseError::InvalidPrimaryKey)));
+
+        let table = Table::new()
+            .name("MyTable".to_string())
+            .add_field("FirstKey".to_string(), FieldType::I64).unwrap()
+            .build();
+        assert!(matches!(table, Err(DatabaseError::TableMissingPrimaryKey)));
+
+        let table = Table::new()
+            .name("MyTable".to_string())
+            .primary_field(FieldType::U64).unwrap()
+            .add_field("FirstKey".to_string(), FieldType::I64).unwrap()
+            .build().unwrap();
+        assert_eq!(table.primary_field, FieldType::U64);
+    }
+
+
+    #[test]
+    fn typed_accessors() {
+        let date = std::time::UNIX_EPOCH + Duration::from_secs(1518568087);
+        let entry = Entry::new()
+            .set_primary_field(Field::String("MyEntry".to_string())).unwrap()
+            .add_field("String".to_string(), Field::String("value".to_string())).unwrap()
+            .add_field("I64".to_string(), Field::I64(-64)).unwrap()
+            .add_field("I32".to_string(), Field::I32(-32)).unwrap()
+            .add_field("U64".to_string(), Field::U64(64)).unwrap()
+            .add_field("U32".to_string(), Field::U32(32)).unwrap()
+            .add_field("Date".to_string(), Field::Date(date)).unwrap()
+            .add_field("Bool".to_string(), Field::Bool(true)).unwrap()
+            .build().unwrap();
+
+        assert_eq!(entry.get_string("String"), Some("value"));
+        assert_eq!(entry.get_i64("I64"), Some(-64));
+        assert_eq!(entry.get_i32("I32"), Some(-32));
+        assert_eq!(entry.get_u64("U64"), Some(64));
+        assert_eq!(entry.get_u32("U32"), Some(32));
+        assert_eq!(entry.get_date("Date"), Some(date));
+        assert_eq!(entry.get_bool("Bool"), Some(true));
+
+        assert_eq!(entry.get_i64("I32"), None);
+        assert_eq!(entry.get_bool("Missing"), None);
+        assert_eq!(entry.try_get_u64("Missing").unwrap(), None);
+        match entry.try_get_date("String") {
+            Err(DatabaseError::TypeMismatch(m)) => assert_eq!(m, "field String is String, not Date"),
+            other => panic!("Expected a type mismatch, received {:?}", other),
+        };
+    }
+
+
+    #[test]
+    fn build_for_table() {
+        let table = Table::new()
+            .name("MyTable".to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_field("FirstKey".to_string(), FieldType::I64).unwrap()
+            .add_optional_field("Notes".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+        let entry = || Entry::new().set_primary_field(Field::String("MyEntry".to_string())).unwrap();
+
+        let built = entry()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build_for(&table).unwrap();
+        assert_eq!(built.get_i64("FirstKey"), Some(1));
+
+        let missing = entry()
+            .add_field("Notes".to_string(), Field::String("notes".to_string())).unwrap()
+            .build_for(&table);
+        assert!(matches!(missing, Err(DatabaseError::MissingRequiredField(f)) if f == "FirstKey"));
+
+        let unsupported = entry()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .add_field("Other".to_string(), Field::I64(1)).unwrap()
+            .build_for(&table);
+        assert!(matches!(unsupported, Err(DatabaseError::UnsupportedField(f)) if f == "Other"));
+
+        let primary = Entry::new()
+            .set_primary_field(Field::U64(1)).unwrap()
+            .add_field("FirstKey".to_string(), Field::I64(1)).unwrap()
+            .build_for(&table);
+        assert!(matches!(primary, Err(DatabaseError::MismatchedFieldType)));
+    }
+
+
+    #[test]
+    fn field_variant_indices() {
+        let index = |bytes: Vec<u8>| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        assert_eq!(index(bincode::serialize(&Field::String("x".to_string())).unwrap()), 0);
+        assert_eq!(index(bincode::serialize(&Field::Bool(true)).unwrap()), 6);
+        assert_eq!(index(bincode::serialize(&FieldType::Bool).unwrap()), 6);
+
+        let custom = Field::Custom{tag: "raw".to_string(), bytes: vec![1, 2]};
+        let serialized = bincode::serialize(&custom).unwrap();
+        assert_eq!(index(serialized.clone()), 8);
+        assert_eq!(bincode::deserialize::<Field>(&serialized).unwrap(), custom);
+        assert_eq!(index(bincode::serialize(&FieldType::Custom).unwrap()), 8);
+        assert_eq!(bincode::deserialize::<FieldType>(&bincode::serialize(&FieldType::Custom).unwrap()).unwrap(), FieldType::Custom);
+
+        let json = serde_json::to_string(&custom).unwrap();
+        assert_eq!(json, r#"{"Custom":{"tag":"raw","bytes":[1,2]}}"#);
+        assert_eq!(serde_json::from_str::<Field>(&json).unwrap(), custom);
+        assert_eq!(serde_json::from_str::<Field>(r#"{"I64":-1}"#).unwrap(), Field::I64(-1));
+        assert_eq!(serde_json::from_str::<FieldType>(r#""Custom""#).unwrap(), FieldType::Custom);
+
+        #[cfg(not(feature = "zeroize"))]
+        assert!(bincode::deserialize::<FieldType>(&7u32.to_le_bytes()).is_err());
+    }
+
+
+    #[test]
+    fn decimal_fields() {
+        let d = |v: &str| v.parse::<Decimal>().unwrap();
+        assert_eq!(d("12.50"), d("12.5"));
+        assert_eq!(d("12.50").scale(), 1);
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d("+7.").to_string(), "7");
+        assert_eq!(d(".5").to_string(), "0.5");
+        assert_eq!(d("-0").to_string(), "0");
+        for invalid in ["", ".", "-", "1e5", "1.2.3", "12a", "0.00000000000000000000000000001"] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{}", invalid);
+        };
+
+        let mut values = [d("10"), d("-1.5"), d("9.99"), d("-1.25"), d("0"), Decimal::new(i128::MAX, 0).unwrap(), Decimal::new(i128::MIN, 28).unwrap()];
+        values.sort();
+        let sorted: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(sorted[..6], ["-17014118346.0469231731687303715884105728", "-1.5", "-1.25", "0", "9.99", "10"]);
+        assert_eq!(sorted[6], i128::MAX.to_string());
+        assert_eq!(Decimal::new(i128::MAX, 0).unwrap().cmp(&d("0.5")), std::cmp::Ordering::Greater);
+        assert_eq!(d("-0.5").cmp(&Decimal::new(i128::MIN, 0).unwrap()), std::cmp::Ordering::Greater);
+
+        let amount = Field::Decimal(d("1234.5678"));
+        assert_eq!(Field::parse(FieldType::Decimal, &amount.to_string()).unwrap(), amount);
+        assert_eq!(amount.compare(&Field::Decimal(d("1234.56781"))), Some(std::cmp::Ordering::Less));
+        assert_eq!(amount.compare(&Field::I64(1234)), None);
+        let serialized = bincode::serialize(&amount).unwrap();
+        assert_eq!(u32::from_le_bytes(serialized[..4].try_into().unwrap()), 11);
+        assert_eq!(bincode::deserialize::<Field>(&serialized).unwrap(), amount);
+        let unnormalized = bincode::serialize(&(11u32, 12500i128, 3u32)).unwrap();
+        assert_eq!(bincode::deserialize::<Field>(&unnormalized).unwrap(), Field::Decimal(d("12.5")));
+        assert!(bincode::deserialize::<Field>(&bincode::serialize(&(11u32, 1i128, 29u32)).unwrap()).is_err());
+
+        let big = Field::U128(u128::MAX);
+        assert_eq!(Field::parse(FieldType::U128, &big.to_string()).unwrap(), big);
+        assert_eq!(Field::I128(i128::MIN).compare(&Field::I128(-1)), Some(std::cmp::Ordering::Less));
+        assert!(Field::parse(FieldType::I128, "170141183460469231731687303715884105728").is_err());
+        assert_eq!(serde_json::to_string(&Field::U128(u128::MAX).flat()).unwrap(), u128::MAX.to_string());
+        assert_eq!(serde_json::to_string(&amount.flat()).unwrap(), r#""1234.5678""#);
+    }
+
+
+    #[test]
+    fn network_fields() {
+        let ip = |v: &str| v.parse::<IpAddr>().unwrap();
+        let cidr = |v: &str| v.parse::<Cidr>().unwrap();
+        assert_eq!(cidr("10.1.2.3/8"), cidr("10.0.0.0/8"));
+        assert_eq!(cidr("10.1.2.3").to_string(), "10.1.2.3/32");
+        assert_eq!(cidr("2001:db8:ffff::1/32").to_string(), "2001:db8::/32");
+        for invalid in ["10.0.0.0/33", "::/129", "10.0.0/8", "10.0.0.0/", "/8", "10.0.0.0/-1"] {
+            assert!(invalid.parse::<Cidr>().is_err(), "{}", invalid);
+        };
+
+        assert!(cidr("0.0.0.0/0").contains(&ip("255.255.255.255")));
+        assert!(!cidr("0.0.0.0/0").contains(&ip("::1")));
+        assert!(cidr("::/0").contains(&ip("::ffff:10.0.0.1")));
+        assert!(!cidr("::/0").contains(&ip("10.0.0.1")));
+        assert!(cidr("10.0.0.0/8").contains_cidr(&cidr("10.20.0.0/16")));
+        assert!(!cidr("10.20.0.0/16").contains_cidr(&cidr("10.0.0.0/8")));
+
+        let now = std::time::SystemTime::now();
+        let subnet = Condition::InSubnet(cidr("192.168.0.0/16"));
+        assert!(subnet.matches(Some(&Field::IpAddr(ip("192.168.4.2"))), now));
+        assert!(!subnet.matches(Some(&Field::IpAddr(ip("192.169.0.1"))), now));
+        assert!(subnet.matches(Some(&Field::Cidr(cidr("192.168.4.0/24"))), now));
+        assert!(!subnet.matches(Some(&Field::String("192.168.4.2".to_string())), now));
+        assert!(!subnet.matches(None, now));
+        assert_eq!(subnet.to_string(), "in 192.168.0.0/16");
+
+        let address = Field::IpAddr(ip("fe80::1"));
+        assert_eq!(Field::parse(FieldType::IpAddr, &address.to_string()).unwrap(), address);
+        assert!(Field::parse(FieldType::IpAddr, "10.0.0.256").is_err());
+        let block = Field::Cidr(cidr("172.16.0.0/12"));
+        assert_eq!(Field::parse(FieldType::Cidr, "172.16.0.0/12").unwrap(), block);
+        for (field, index) in [(&address, 12), (&block, 13)] {
+            let serialized = bincode::serialize(field).unwrap();
+            assert_eq!(u32::from_le_bytes(serialized[..4].try_into().unwrap()), index);
+            assert_eq!(&bincode::deserialize::<Field>(&serialized).unwrap(), field);
+        };
+        assert_eq!(serde_json::to_string(&block.flat()).unwrap(), r#""172.16.0.0/12""#);
+    }
+
+
+    #[test]
+    fn geo_point_fields() {
+        let point = |v: &str| v.parse::<GeoPoint>().unwrap();
+        assert_eq!(point("-33.8688, 151.2093").to_string(), "-33.8688,151.2093");
+        assert_eq!(point("-0.00000004,0").to_string(), "0,0");
+        assert_eq!(point("90,-180").to_string(), "90,-180");
+        assert_eq!(point("1.23456789,0"), point("1.2345679,0"));
+        for invalid in ["", "1", "1,", "90.1,0", "0,180.5", "NaN,0", "a,b"] {
+            assert!(invalid.parse::<GeoPoint>().is_err(), "{}", invalid);
+        };
+
+        assert_eq!(point("10,20").distance_to(&point("10,20")), 0.0);
+        let quarter = point("0,0").distance_to(&point("0,90"));
+        assert!((quarter - std::f64::consts::FRAC_PI_2 * crate::EARTH_RADIUS_METERS).abs() < 1.0);
+        let antipode = point("0,0").distance_to(&point("0,180"));
+        assert!((antipode - std::f64::consts::PI * crate::EARTH_RADIUS_METERS).abs() < 1.0);
+
+        let now = std::time::SystemTime::now();
+        let near = Condition::WithinRadius{center: point("40.7128,-74.006"), meters: 5_000};
+        assert!(near.matches(Some(&Field::GeoPoint(point("40.73,-73.99"))), now));
+        assert!(!near.matches(Some(&Field::GeoPoint(point("40.65,-73.78"))), now));
+        assert!(!near.matches(Some(&Field::String("40.7128,-74.006".to_string())), now));
+        assert_eq!(near.to_string(), "within 5000m of 40.7128,-74.006");
+
+        let field = Field::GeoPoint(point("51.5074,-0.1278"));
+        assert_eq!(Field::parse(FieldType::GeoPoint, &field.to_string()).unwrap(), field);
+        let serialized = bincode::serialize(&field).unwrap();
+        assert_eq!(u32::from_le_bytes(serialized[..4].try_into().unwrap()), 14);
+        assert_eq!(bincode::deserialize::<Field>(&serialized).unwrap(), field);
+        assert!(bincode::deserialize::<Field>(&bincode::serialize(&(14u32, 900_000_001i32, 0i32)).unwrap()).is_err());
+    }
+
+
+    #[test]
+    fn enum_field_declarations() {
+        let status = FieldType::Enum(vec!["active".to_string(), "suspended".to_string()]);
+        assert!(Table::new().name("T".to_string()).primary_field(FieldType::Enum(Vec::new())).is_err());
+        let duplicate = FieldType::Enum(vec!["a".to_string(), "b".to_string(), "a".to_string()]);
+        match Table::new().name("T".to_string()).add_field("Status".to_string(), duplicate) {
+            Err(DatabaseError::InvalidFormat(e)) => assert_eq!(e, "Enum declares a twice, at 2"),
+            _ => panic!("expected InvalidFormat"),
+        };
+
+        assert_eq!(Field::parse(status.clone(), "active").unwrap(), Field::Enum("active".to_string()));
+        assert!(Field::parse(status.clone(), "actve").is_err());
+        assert!(status.check_value(&Field::Enum("suspended".to_string())).is_ok());
+        assert!(matches!(status.check_value(&Field::Enum("actve".to_string())), Err(DatabaseError::InvalidFormat(_))));
+        assert!(matches!(status.check_value(&Field::String("active".to_string())), Err(DatabaseError::MismatchedFieldType)));
+
+        let serialized = bincode::serialize(&status).unwrap();
+        assert_eq!(u32::from_le_bytes(serialized[..4].try_into().unwrap()), 15);
+        assert_eq!(bincode::deserialize::<FieldType>(&serialized).unwrap(), status);
+        assert_eq!(serde_json::to_string(&status).unwrap(), r#"{"Enum":["active","suspended"]}"#);
+        assert_eq!(serde_json::from_str::<FieldType>(r#"{"Enum":["active","suspended"]}"#).unwrap(), status);
+        assert_eq!(serde_json::from_str::<FieldType>(r#""Date""#).unwrap(), FieldType::Date);
+        let field = Field::Enum("active".to_string());
+        assert_eq!(bincode::deserialize::<Field>(&bincode::serialize(&field).unwrap()).unwrap(), field);
+    }
+
+    #[test]
+    fn null_fields() {
+        assert!(matches!(Table::new().name("T".to_string()).add_optional_field("Notes".to_string(), FieldType::Null), Err(DatabaseError::InvalidFormat(_))));
+        assert_eq!(Field::parse(FieldType::Null, "null").unwrap(), Field::Null);
+        assert_eq!(Field::Null.to_string(), "null");
+        assert_eq!(Field::Null.get_type(), FieldType::Null);
+
+        let serialized = bincode::serialize(&Field::Null).unwrap();
+        assert_eq!(serialized.len(), 4);
+        assert_eq!(u32::from_le_bytes(serialized[..4].try_into().unwrap()), 16);
+        assert_eq!(bincode::deserialize::<Field>(&serialized).unwrap(), Field::Null);
+        assert_eq!(serde_json::to_string(&Field::Null).unwrap(), r#""Null""#);
+        assert_eq!(serde_json::from_str::<Field>(r#""Null""#).unwrap(), Field::Null);
+    }
+
+    #[test]
+    fn display_rows() {
+        let table = Table::new()
+            .name("Hosts".to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_optional_field("Zone".to_string(), FieldType::String).unwrap()
+            .add_field("Port".to_string(), FieldType::U32).unwrap()
+            .add_optional_field("Alias".to_string(), FieldType::String).unwrap()
+            .add_field("Address".to_string(), FieldType::IpAddr).unwrap()
+            .build().unwrap();
+        assert_eq!(table.display_columns(), ["Address", "Port", "Alias", "Zone"]);
+
+        let entry = Entry::new()
+            .set_primary_field(Field::String("web".to_string())).unwrap()
+            .add_field("Port".to_string(), Field::U32(443)).unwrap()
+            .add_field("Address".to_string(), Field::IpAddr("10.0.0.1".parse().unwrap())).unwrap()
+            .add_field("Zone".to_string(), Field::Null).unwrap()
+            .build().unwrap();
+        let row = entry.to_display_row(&table);
+        assert_eq!(row.labels(), ["primary_field", "Address", "Port", "Alias", "Zone"]);
+        assert_eq!(row.cells(), ["web", "10.0.0.1", "443", "", "null"]);
+        assert_eq!(row.columns.iter().map(|c| c.required).collect::<Vec<bool>>(), [true, true, true, false, false]);
+        assert_eq!(row.columns[3].value, None);
+        assert_eq!(row.to_string(), "primary_field: web, Address: 10.0.0.1, Port: 443, Zone: null");
+    }
+
+    #[test]
+    fn filter_trees() {
+        let active = || Filter::equals("Status", Field::String("active".to_string()));
+        let tier = |t: &str| Filter::equals("Tier", Field::String(t.to_string()));
+        let filter = active().and(tier("gold").or(tier("silver"))).and(!Filter::field("Age", Condition::LessThan(Field::I64(18))));
+        assert_eq!(filter.to_string(), "Status = active AND (Tier = gold OR Tier = silver) AND NOT Age < 18");
+        assert_eq!((!tier("gold").or(tier("silver"))).to_string(), "NOT (Tier = gold OR Tier = silver)");
+        assert_eq!(!!active(), active());
+        assert_eq!(Filter::And(Vec::new()).to_string(), "TRUE");
+
+        let required = filter.required();
+        assert_eq!(required.len(), 1);
+        assert_eq!(required["Status"], Condition::Equals(Field::String("active".to_string())));
+        assert!(tier("gold").or(active()).required().is_empty());
+
+        let conditions = HashMap::from([
+            ("Tier".to_string(), Condition::Equals(Field::String("gold".to_string()))),
+            ("Status".to_string(), Condition::Equals(Field::String("active".to_string()))),
+        ]);
+        assert_eq!(Filter::from(conditions), Filter::And(vec![active(), tier("gold")]));
+
+        let now = SystemTime::now();
+        let entry = |status: &str, tier: Option<&str>, age: i64| {
+            let mut entry = Entry::new()
+                .set_primary_field(Field::String("u".to_string())).unwrap()
+                .add_field("Status".to_string(), Field::String(status.to_string())).unwrap()
+                .add_field("Age".to_string(), Field::I64(age)).unwrap();
+            if let Some(tier) = tier {
+                entry = entry.add_field("Tier".to_string(), Field::String(tier.to_string())).unwrap();
+            };
+            entry.build().unwrap()
+        };
+        assert!(filter.matches(&entry("active", Some("silver"), 30), now));
+        assert!(!filter.matches(&entry("active", Some("bronze"), 30), now));
+        assert!(!filter.matches(&entry("active", None, 30), now));
+        assert!(!filter.matches(&entry("active", Some("gold"), 12), now));
+        assert!(!filter.matches(&entry("closed", Some("gold"), 30), now));
+        assert!(Filter::And(Vec::new()).matches(&entry("closed", None, 0), now));
+        assert!(!Filter::Or(Vec::new()).matches(&entry("closed", None, 0), now));
+    }
+
+    #[test]
+    fn starts_with_condition() {
+        let now = SystemTime::now();
+        let usr = Condition::StartsWith("/usr/".to_string());
+        assert!(usr.matches(Some(&Field::String("/usr/bin/ls".to_string())), now));
+        assert!(!usr.matches(Some(&Field::String("/etc/hosts".to_string())), now));
+        assert!(!usr.matches(Some(&Field::Enum("/usr/".to_string())), now));
+        assert!(!usr.matches(None, now));
+        assert_eq!(usr.to_string(), "starts with /usr/");
+
+        let entry = Entry::new()
+            .set_primary_field(Field::String("/usr/bin/ls".to_string())).unwrap()
+            .add_field("Path".to_string(), Field::String("/etc/hosts".to_string())).unwrap()
+            .build().unwrap();
+        assert!(Filter::field(crate::PRIMARY_FIELD_KEY, usr.clone()).matches(&entry, now));
+        assert!(!Filter::field("Path", usr).matches(&entry, now));
+    }
+
+    #[test]
+    fn entry_to_builder() {
+        let table = Table::new()
+            .name("Users".to_string())
+            .primary_field(FieldType::String).unwrap()
+            .add_field("Name".to_string(), FieldType::String).unwrap()
+            .add_field("Logins".to_string(), FieldType::U64).unwrap()
+            .add_optional_field("Email".to_string(), FieldType::String).unwrap()
+            .build().unwrap();
+        let mut entry = Entry::new()
+            .set_primary_field(Field::String("u1".to_string())).unwrap()
+            .add_field("Name".to_string(), Field::String("Ada".to_string())).unwrap()
+            .add_field("Logins".to_string(), Field::U64(3)).unwrap()
+            .add_field("Email".to_string(), Field::String("ada@example.com".to_string())).unwrap()
+            .build().unwrap();
+        entry.last_timestamp = Some(SystemTime::now());
+
+        let rebuilt = entry.to_builder().build_for(&table).unwrap();
+        assert_eq!(rebuilt.fields, entry.fields);
+        assert_eq!(rebuilt.primary_field, entry.primary_field);
+        assert_eq!(rebuilt.last_timestamp, None);
+
+        let updated = entry.to_builder()
+            .add_field("Logins".to_string(), Field::U64(4)).unwrap()
+            .build_for(&table).unwrap();
+        assert_eq!(updated.get_u64("Logins"), Some(4));
+        assert_eq!(updated.get_string("Name"), Some("Ada"));
+        assert_eq!(updated.get_string("Email"), Some("ada@example.com"));
+
+        let renamed = entry.to_builder()
+            .set_primary_field(Field::String("u2".to_string())).unwrap()
+            .build().unwrap();
+        assert_eq!(renamed.primary_field, Field::String("u2".to_string()));
+        assert_eq!(entry.primary_field, Field::String("u1".to_string()));
+        assert!(entry.to_builder().add_field("Logins".to_string(), Field::I64(4)).unwrap().build_for(&table).is_err());
+    }
+
+
+    #[test]
+    fn keys_updated_between_resumes() {
+        let mut table = Table::new()
+            .name("Cursor".to_string())
+            .primary_field(FieldType::I64).unwrap()
+            .add_field("Count".to_string(), FieldType::I64).unwrap()
+            .build().unwrap();
+        let entry = |key: i64, count: i64| Entry::new()
+            .set_primary_field(Field::I64(key)).unwrap()
+            .add_field("Count".to_string(), Field::I64(count)).unwrap()
+            .build().unwrap();
+        for key in 0..5 {
+            table.insert(entry(key, 0)).unwrap();
+        };
+        let until = table.update_sequence();
+
+        let (first, cursor) = table.keys_updated_between(0, until, 2);
+        assert_eq!(first, vec![Field::I64(0), Field::I64(1)]);
+
+        // Entries updated or inserted after the cursor began are not visited
+        table.update(entry(3, 1)).unwrap();
+        table.insert(entry(5, 0)).unwrap();
+        let (second, cursor) = table.keys_updated_between(cursor.unwrap(), until, 2);
+        assert_eq!(second, vec![Field::I64(2), Field::I64(4)]);
+        assert_eq!(table.keys_updated_between(cursor.unwrap(), until, 2), (Vec::new(), None));
+    }
+
+
+    #[test]
+    fn database_clone_shares_tables() {
+        let mut database = Database::default();
+        for name in ["Written", "Unchanged"] {
+            let table = Table::new()
+                .name(name.to_string())
+                .primary_field(FieldType::I64).unwrap()
+                .add_field("Count".to_string(), FieldType::I64).unwrap()
+                .build().unwrap();
+            database.create_table(table).unwrap();
+        };
+        let entry = Entry::new()
+            .set_primary_field(Field::I64(1)).unwrap()
+            .add_field("Count".to_string(), Field::I64(1)).unwrap()
+            .build().unwrap();
+
+        let snapshot = database.clone();
+        let table = |d: &Database, name: &str| d.get_table_ref(&name.to_string()).unwrap() as *const Table;
+        assert_eq!(table(&database, "Written"), table(&snapshot, "Written"));
+        database.get_table(&"Written".to_string()).unwrap().insert(entry).unwrap();
+
+        assert_ne!(table(&database, "Written"), table(&snapshot, "Written"));
+        assert_eq!(table(&database, "Unchanged"), table(&snapshot, "Unchanged"));
+        assert_eq!(database.get_table_ref(&"Written".to_string()).unwrap().len(), 1);
+        assert_eq!(snapshot.get_table_ref(&"Written".to_string()).unwrap().len(), 0);
+    }
+
+
+    #[test]
+    fn bookkeeping_follows_spilled_entries() {
+        let path = std::env::temp_dir().join("BookkeepingSpill.db");
+        let entry = |key: i64| Entry::new()
+            .set_primary_field(Field::I64(key)).unwrap()
+            .add_field("Count".to_string(), Field::I64(key)).unwrap()
+            .build().unwrap();
+        let mut table = Table::new()
+            .name("BookkeepingSpill".to_string())
+            .primary_field(FieldType::I64).unwrap()
+            .add_field("Count".to_string(), FieldType::I64).unwrap()
+            .spill_after(entry(0).approx_size())
+            .build().unwrap();
+        let _ = std::fs::remove_file(segment_path(&path, "BookkeepingSpill"));
+        table.attach_spill(&segment_path(&path, "BookkeepingSpill")).unwrap();
+        for key in 0..4 {
+            table.insert(entry(key)).unwrap();
+        };
+        assert_eq!(table.stats().spilled, 3);
+        let order = |t: &Table| t.keys_updated_between(0, t.update_sequence(), 10).0;
+        let consistent = |t: &Table| {
+            let mut report = VerifyReport::default();
+            t.verify(&mut report);
+            assert!(report.is_ok(), "{:?}", report.problems);
+        };
+
+        assert!(table.touch_at(&Field::I64(0), Timestamp::now()).unwrap());
+        table.delete(Field::I64(1)).unwrap();
+        assert_eq!(order(&table), vec![Field::I64(2), Field::I64(3), Field::I64(0)]);
+        assert_eq!(table.entry_size(&Field::I64(0)), Some(0));
+        assert_eq!(table.entry_size(&Field::I64(3)), Some(entry(3).approx_size()));
+        consistent(&table);
+
+        let mut loaded: Table = bincode::deserialize(&bincode::serialize(&table).unwrap()).unwrap();
+        loaded.attach_spill(&segment_path(&path, "BookkeepingSpill")).unwrap();
+        loaded.rebase(Timestamp::now());
+        assert_eq!(loaded.stats().approx_bytes, table.stats().approx_bytes);
+        consistent(&loaded);
+        std::fs::remove_file(segment_path(&path, "BookkeepingSpill")).unwrap();
+    }
 }
\ No newline at end of file
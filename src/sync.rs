@@ -0,0 +1,32 @@
+//! Synchronization primitives shared between clones of a Client, and between the threads
+//! using them.
+//!
+//! Built with `RUSTFLAGS="--cfg loom"` these are replaced by loom's model checked
+//! equivalents, so the locking of the Client can be explored under every interleaving
+//! by `loom::model`.  Clients built so can only be used inside a model; the background
+//! threads of the Client and Container sleep between runs, use std channels to be
+//! stopped and are not modelled.  Tables read from their segment on first use are loaded
+//! through a std OnceLock, as loom has no equivalent, and the log level is a plain std
+//! atomic.
+#[cfg(not(loom))]
+pub(crate) use std::sync::{atomic, mpsc, Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{atomic, mpsc, Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Declares a static shared by every Client of the process; under loom it is initialized
+/// on first use within each execution of a model, as loom primitives cannot be created
+/// in constant expressions
+macro_rules! shared_static {
+    ($(#[$attr:meta])* static $name:ident: $t:ty = $init:expr;) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        static $name: $t = $init;
+        #[cfg(loom)]
+        loom::lazy_static! {
+            $(#[$attr])*
+            static ref $name: $t = $init;
+        }
+    };
+}
+pub(crate) use shared_static;
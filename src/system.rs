@@ -0,0 +1,161 @@
+use std::time::SystemTime;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::structs::*;
+use crate::clock::Timestamp;
+use crate::errors::*;
+use crate::names::NamePolicy;
+
+/// Name of the read-only system table describing every Table of the database, one Entry per
+/// Table keyed by its name
+pub const TABLES_TABLE: &str = "__tables";
+/// Name of the read-only system table holding the TableStats of every Table of the
+/// database, one Entry per Table keyed by its name
+pub const STATS_TABLE: &str = "__stats";
+/// Name of the read-only system table listing the Tables created and dropped through the
+/// Client, one Entry per change keyed by its sequence number
+pub const SCHEMA_HISTORY_TABLE: &str = "__schema_history";
+
+/// Names of the read-only system tables
+pub const SYSTEM_TABLES: [&str; 3] = [TABLES_TABLE, STATS_TABLE, SCHEMA_HISTORY_TABLE];
+
+/// Returns true if the name is that of a read-only system table
+pub fn is_system_table(name: &str) -> bool {
+    SYSTEM_TABLES.contains(&name)
+}
+
+/// Kind of a SchemaChange
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SchemaChangeKind {
+    Created,
+    Dropped,
+}
+
+/// Creation or removal of a Table, recorded in the schema history of a Database
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub table: String,
+    pub kind: SchemaChangeKind,
+    pub at: SystemTime,
+}
+
+/// Returns the Fields of a Table as `name:Type` sorted by name, with `?` after the Type of
+/// optional fields
+fn describe_fields(table: &Table) -> String {
+    let mut fields: Vec<String> = table.fields.iter()
+        .map(|(name, requirement)| match requirement {
+            FieldRequirement::Required(t) => format!("{}:{:?}", name, t),
+            FieldRequirement::Optional(t) => format!("{}:{:?}?", name, t),
+        })
+        .collect();
+    fields.sort();
+    fields.join(",")
+}
+
+fn u64_field(value: usize) -> Field {
+    Field::U64(value as u64)
+}
+
+/// Builds the system table with the supplied name from the Database, or returns None if
+/// the name is not that of a system table
+pub(crate) fn system_table(database: &Database, name: &str, now: Timestamp) -> Option<Result<Table, DatabaseError>> {
+    let result = match name {
+        TABLES_TABLE => tables_table(database, now),
+        STATS_TABLE => stats_table(database, now),
+        SCHEMA_HISTORY_TABLE => schema_history_table(database, now),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn tables_table(database: &Database, now: Timestamp) -> Result<Table, DatabaseError> {
+    let mut table = Table::new()
+        .name(TABLES_TABLE.to_string())
+        .name_policy(NamePolicy::unrestricted())
+        .primary_field(FieldType::String)?
+        .add_field("PrimaryField".to_string(), FieldType::String)?
+        .add_field("Fields".to_string(), FieldType::String)?
+        .add_optional_field("ExpireAfter".to_string(), FieldType::U64)?
+        .add_optional_field("MaxEntries".to_string(), FieldType::U64)?
+        .add_optional_field("CreatedAt".to_string(), FieldType::Date)?
+        .add_optional_field("LastModifiedAt".to_string(), FieldType::Date)?
+        .build()?;
+
+    for name in database.list_tables() {
+        let t = database.get_table_ref(&name)?;
+        let mut entry = Entry::new()
+            .set_primary_field(Field::String(name.clone()))?
+            .add_field("PrimaryField".to_string(), Field::String(format!("{:?}", t.primary_field)))?
+            .add_field("Fields".to_string(), Field::String(describe_fields(t)))?;
+        if let Some(d) = t.expire_after {
+            entry = entry.add_field("ExpireAfter".to_string(), Field::U64(d.as_secs()))?;
+        };
+        if let Some(m) = t.max_entries {
+            entry = entry.add_field("MaxEntries".to_string(), u64_field(m))?;
+        };
+        if let Some(at) = t.created_at {
+            entry = entry.add_field("CreatedAt".to_string(), Field::Date(at))?;
+        };
+        if let Some(at) = t.last_modified_at {
+            entry = entry.add_field("LastModifiedAt".to_string(), Field::Date(at))?;
+        };
+        table.insert_at(entry.build()?, now)?;
+    };
+    Ok(table)
+}
+
+fn stats_table(database: &Database, now: Timestamp) -> Result<Table, DatabaseError> {
+    let mut table = Table::new()
+        .name(STATS_TABLE.to_string())
+        .name_policy(NamePolicy::unrestricted())
+        .primary_field(FieldType::String)?
+        .add_field("Entries".to_string(), FieldType::U64)?
+        .add_field("ApproxBytes".to_string(), FieldType::U64)?
+        .add_field("Evicted".to_string(), FieldType::U64)?
+        .add_field("Spilled".to_string(), FieldType::U64)?
+        .add_field("Externalized".to_string(), FieldType::U64)?
+        .add_field("Deduplicated".to_string(), FieldType::U64)?
+        .build()?;
+
+    for name in database.list_tables() {
+        let stats = database.get_table_ref(&name)?.stats();
+        let entry = Entry::new()
+            .set_primary_field(Field::String(name))?
+            .add_field("Entries".to_string(), u64_field(stats.entries))?
+            .add_field("ApproxBytes".to_string(), u64_field(stats.approx_bytes))?
+            .add_field("Evicted".to_string(), Field::U64(stats.evicted))?
+            .add_field("Spilled".to_string(), u64_field(stats.spilled))?
+            .add_field("Externalized".to_string(), u64_field(stats.externalized))?
+            .add_field("Deduplicated".to_string(), u64_field(stats.deduplicated))?
+            .build()?;
+        table.insert_at(entry, now)?;
+    };
+    Ok(table)
+}
+
+fn schema_history_table(database: &Database, now: Timestamp) -> Result<Table, DatabaseError> {
+    let mut table = Table::new()
+        .name(SCHEMA_HISTORY_TABLE.to_string())
+        .name_policy(NamePolicy::unrestricted())
+        .primary_field(FieldType::U64)?
+        .add_field("Table".to_string(), FieldType::String)?
+        .add_field("Change".to_string(), FieldType::String)?
+        .add_field("At".to_string(), FieldType::Date)?
+        .build()?;
+
+    for (sequence, change) in database.schema_history().iter().enumerate() {
+        let kind = match change.kind {
+            SchemaChangeKind::Created => "created",
+            SchemaChangeKind::Dropped => "dropped",
+        };
+        let entry = Entry::new()
+            .set_primary_field(u64_field(sequence))?
+            .add_field("Table".to_string(), Field::String(change.table.clone()))?
+            .add_field("Change".to_string(), Field::String(kind.to_string()))?
+            .add_field("At".to_string(), Field::Date(change.at))?
+            .build()?;
+        table.insert_at(entry, now)?;
+    };
+    Ok(table)
+}
+
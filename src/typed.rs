@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tracing::{error, trace};
+
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+use crate::system::TABLES_TABLE;
+
+/// Rust type of the primary field of a Table, fixed at compile time by TypedTable
+pub trait Key: Into<Field> {
+    /// FieldType of the Fields the type converts into
+    const FIELD_TYPE: FieldType;
+}
+
+macro_rules! key {
+    ($t:ty, $variant:ident) => {
+        impl From<$t> for Field {
+            fn from(value: $t) -> Self {
+                Field::$variant(value)
+            }
+        }
+
+        impl Key for $t {
+            const FIELD_TYPE: FieldType = FieldType::$variant;
+        }
+    };
+}
+
+key!(String, String);
+key!(i64, I64);
+key!(i32, I32);
+key!(u64, U64);
+key!(u32, U32);
+key!(SystemTime, Date);
+key!(bool, Bool);
+
+/// Table whose primary field has the Rust type K, so that passing a key of the wrong type
+/// is a compile error rather than DatabaseError::MismatchedFieldType.
+///
+/// The FieldType of the Table is checked against K once, when the TypedTable is created.
+/// Entries are still built with Entry::new, and their primary fields checked as they are
+/// written.
+/// ```
+/// use persistent_keystore_rs::{Client, Entry, Field, FieldType, Table, TypedTable};
+/// # use persistent_keystore_rs::prelude::*;
+/// # use std::path::Path;
+/// let mut c = Client::new(Path::new("typedtable.db"), None).unwrap();
+/// # let table = Table::new()
+/// #    .name("Users".to_string())
+/// #    .primary_field(FieldType::U64).unwrap()
+/// #    .add_field("Name".to_string(), FieldType::String).unwrap()
+/// #    .build().unwrap();
+/// # c.create_table(table).unwrap();
+/// let mut users = TypedTable::<u64>::new(c, "Users".to_string()).unwrap();
+/// let entry = Entry::new()
+///     .set_primary_field(Field::from(42u64)).unwrap()
+///     .add_field("Name".to_string(), Field::String("Alice".to_string())).unwrap()
+///     .build().unwrap();
+/// users.insert(entry).unwrap();
+/// assert_eq!(users.get(42).unwrap().fields["Name"], Field::String("Alice".to_string()));
+/// # std::fs::remove_file("typedtable.db").unwrap();
+/// ```
+/// Keys of another type do not compile:
+/// ```compile_fail
+/// # use persistent_keystore_rs::{Client, TypedTable};
+/// # use std::path::Path;
+/// # let c = Client::new(Path::new("typedtablekey.db"), None).unwrap();
+/// let mut users = TypedTable::<u64>::new(c, "Users".to_string()).unwrap();
+/// users.get("Alice".to_string());
+/// ```
+pub struct TypedTable<K: Key> {
+    client: Box<dyn DatabaseClient>,
+    table: String,
+    key: PhantomData<fn(K)>,
+}
+
+impl<K: Key> TypedTable<K> {
+    /// Returns a TypedTable for the named Table of the client.
+    /// If the Table does not exist, DatabaseError::TableDoesNotExist is returned, and if
+    /// its primary field is not of FieldType K::FIELD_TYPE, DatabaseError::SchemaMismatch.
+    pub fn new(mut client: Box<dyn DatabaseClient>, table: String) -> Result<Self, DatabaseError> {
+        trace!("Creating TypedTable on table {}", table);
+        let schema = match client.get(TABLES_TABLE.to_string(), Field::String(table.clone())) {
+            Ok(schema) => schema,
+            Err(DatabaseError::EntryDoesNotExists) => {
+                error!("Table {} does not exist", table);
+                return Err(DatabaseError::TableDoesNotExist(table))
+            },
+            Err(e) => return Err(e),
+        };
+
+        let expected = format!("{:?}", K::FIELD_TYPE);
+        match schema.fields.get("PrimaryField") {
+            Some(Field::String(primary_field)) if *primary_field == expected => {},
+            primary_field => {
+                error!("Table {} does not have a primary field of {}", table, expected);
+                return Err(DatabaseError::SchemaMismatch(format!("table {} has primary field {:?}, not {}", table, primary_field, expected)))
+            },
+        };
+
+        Ok(TypedTable{
+            client,
+            table,
+            key: PhantomData,
+        })
+    }
+
+    /// Returns the name of the Table
+    pub fn table(&self) -> &String {
+        &self.table
+    }
+
+    /// Returns the client of the Table
+    pub fn client(&mut self) -> &mut Box<dyn DatabaseClient> {
+        &mut self.client
+    }
+
+    /// Returns the client of the Table
+    pub fn into_inner(self) -> Box<dyn DatabaseClient> {
+        self.client
+    }
+
+    /// See DatabaseClient::get
+    pub fn get(&mut self, key: K) -> Result<Arc<Entry>, DatabaseError> {
+        self.client.get(self.table.clone(), key.into())
+    }
+
+    /// See DatabaseClient::exists
+    pub fn exists(&mut self, key: K) -> Result<bool, DatabaseError> {
+        self.client.exists(self.table.clone(), key.into())
+    }
+
+    /// See DatabaseClient::insert
+    pub fn insert(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.client.insert(self.table.clone(), entry)
+    }
+
+    /// See DatabaseClient::insert_or_update
+    pub fn insert_or_update(&mut self, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        self.client.insert_or_update(self.table.clone(), entry)
+    }
+
+    /// See DatabaseClient::update
+    pub fn update(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.client.update(self.table.clone(), entry)
+    }
+
+    /// See DatabaseClient::delete
+    pub fn delete(&mut self, key: K) -> Result<(), DatabaseError> {
+        self.client.delete(self.table.clone(), key.into())
+    }
+
+    /// See DatabaseClient::scan
+    pub fn scan(&mut self) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.client.scan(self.table.clone())
+    }
+}
@@ -0,0 +1,222 @@
+//! Serialization of Field and FieldType, and the columns of columnar Tables, at fixed
+//! variant indices.  Derived serialization
+//! numbers variants by position, so a variant behind a feature, such as Field::Secret,
+//! would shift the index of every variant after it between builds with and without the
+//! feature; fixing the indices keeps database files portable between such builds.
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::SerializeStructVariant;
+
+use crate::structs::{Field, FieldType};
+
+/// Variants of Field and FieldType, and of the columns of columnar Tables, in the order of
+/// their serialized indices; variants are only ever appended
+#[derive(serde_derive::Deserialize)]
+pub(crate) enum Variant {
+    String,
+    I64,
+    I32,
+    U64,
+    U32,
+    Date,
+    Bool,
+    Secret,
+    Custom,
+    I128,
+    U128,
+    Decimal,
+    IpAddr,
+    Cidr,
+    GeoPoint,
+    Enum,
+    Null,
+}
+
+pub(crate) const VARIANTS: &[&str] = &["String", "I64", "I32", "U64", "U32", "Date", "Bool", "Secret", "Custom", "I128", "U128", "Decimal", "IpAddr", "Cidr", "GeoPoint", "Enum", "Null"];
+
+const CUSTOM_FIELDS: &[&str] = &["tag", "bytes"];
+
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn secret_unsupported<E: de::Error>() -> E {
+    E::custom("Secret requires the zeroize feature")
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Field::String(v) => serializer.serialize_newtype_variant("Field", 0, "String", v),
+            Field::I64(v) => serializer.serialize_newtype_variant("Field", 1, "I64", v),
+            Field::I32(v) => serializer.serialize_newtype_variant("Field", 2, "I32", v),
+            Field::U64(v) => serializer.serialize_newtype_variant("Field", 3, "U64", v),
+            Field::U32(v) => serializer.serialize_newtype_variant("Field", 4, "U32", v),
+            Field::Date(v) => serializer.serialize_newtype_variant("Field", 5, "Date", v),
+            Field::Bool(v) => serializer.serialize_newtype_variant("Field", 6, "Bool", v),
+            #[cfg(feature = "zeroize")]
+            Field::Secret(v) => serializer.serialize_newtype_variant("Field", 7, "Secret", v),
+            Field::Custom{tag, bytes} => {
+                let mut variant = serializer.serialize_struct_variant("Field", 8, "Custom", 2)?;
+                variant.serialize_field("tag", tag)?;
+                variant.serialize_field("bytes", bytes)?;
+                variant.end()
+            },
+            Field::I128(v) => serializer.serialize_newtype_variant("Field", 9, "I128", v),
+            Field::U128(v) => serializer.serialize_newtype_variant("Field", 10, "U128", v),
+            Field::Decimal(v) => serializer.serialize_newtype_variant("Field", 11, "Decimal", v),
+            Field::IpAddr(v) => serializer.serialize_newtype_variant("Field", 12, "IpAddr", v),
+            Field::Cidr(v) => serializer.serialize_newtype_variant("Field", 13, "Cidr", v),
+            Field::GeoPoint(v) => serializer.serialize_newtype_variant("Field", 14, "GeoPoint", v),
+            Field::Enum(v) => serializer.serialize_newtype_variant("Field", 15, "Enum", v),
+            Field::Null => serializer.serialize_unit_variant("Field", 16, "Null"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("Field", VARIANTS, FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a Field")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Field, A::Error> {
+        let (variant, value) = data.variant()?;
+        match variant {
+            Variant::String => value.newtype_variant().map(Field::String),
+            Variant::I64 => value.newtype_variant().map(Field::I64),
+            Variant::I32 => value.newtype_variant().map(Field::I32),
+            Variant::U64 => value.newtype_variant().map(Field::U64),
+            Variant::U32 => value.newtype_variant().map(Field::U32),
+            Variant::Date => value.newtype_variant().map(Field::Date),
+            Variant::Bool => value.newtype_variant().map(Field::Bool),
+            #[cfg(feature = "zeroize")]
+            Variant::Secret => value.newtype_variant().map(Field::Secret),
+            #[cfg(not(feature = "zeroize"))]
+            Variant::Secret => Err(secret_unsupported()),
+            Variant::Custom => value.struct_variant(CUSTOM_FIELDS, CustomVisitor),
+            Variant::I128 => value.newtype_variant().map(Field::I128),
+            Variant::U128 => value.newtype_variant().map(Field::U128),
+            Variant::Decimal => value.newtype_variant().map(Field::Decimal),
+            Variant::IpAddr => value.newtype_variant().map(Field::IpAddr),
+            Variant::Cidr => value.newtype_variant().map(Field::Cidr),
+            Variant::GeoPoint => value.newtype_variant().map(Field::GeoPoint),
+            Variant::Enum => value.newtype_variant().map(Field::Enum),
+            Variant::Null => value.unit_variant().map(|_| Field::Null),
+        }
+    }
+}
+
+/// Reads the tag and bytes of Field::Custom, in order or by name
+struct CustomVisitor;
+
+impl<'de> Visitor<'de> for CustomVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the tag and bytes of a custom Field")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Field, A::Error> {
+        let tag = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let bytes = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Field::Custom{tag, bytes})
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Field, A::Error> {
+        let (mut tag, mut bytes) = (None, None);
+        while let Some(name) = map.next_key::<String>()? {
+            match name.as_str() {
+                "tag" => tag = Some(map.next_value()?),
+                "bytes" => bytes = Some(map.next_value()?),
+                _ => return Err(de::Error::unknown_field(&name, CUSTOM_FIELDS)),
+            };
+        };
+        Ok(Field::Custom{
+            tag: tag.ok_or_else(|| de::Error::missing_field("tag"))?,
+            bytes: bytes.ok_or_else(|| de::Error::missing_field("bytes"))?,
+        })
+    }
+}
+
+impl Serialize for FieldType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let FieldType::Enum(variants) = self {
+            return serializer.serialize_newtype_variant("FieldType", 15, "Enum", variants)
+        };
+        let (index, name) = match self {
+            FieldType::String => (0, "String"),
+            FieldType::I64 => (1, "I64"),
+            FieldType::I32 => (2, "I32"),
+            FieldType::U64 => (3, "U64"),
+            FieldType::U32 => (4, "U32"),
+            FieldType::Date => (5, "Date"),
+            FieldType::Bool => (6, "Bool"),
+            #[cfg(feature = "zeroize")]
+            FieldType::Secret => (7, "Secret"),
+            FieldType::Custom => (8, "Custom"),
+            FieldType::I128 => (9, "I128"),
+            FieldType::U128 => (10, "U128"),
+            FieldType::Decimal => (11, "Decimal"),
+            FieldType::IpAddr => (12, "IpAddr"),
+            FieldType::Cidr => (13, "Cidr"),
+            FieldType::GeoPoint => (14, "GeoPoint"),
+            FieldType::Enum(_) => unreachable!("Enum is serialized with its variants"),
+            FieldType::Null => (16, "Null"),
+        };
+        serializer.serialize_unit_variant("FieldType", index, name)
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("FieldType", VARIANTS, FieldTypeVisitor)
+    }
+}
+
+struct FieldTypeVisitor;
+
+impl<'de> Visitor<'de> for FieldTypeVisitor {
+    type Value = FieldType;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a FieldType")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<FieldType, A::Error> {
+        let (variant, value) = data.variant()?;
+        if let Variant::Enum = variant {
+            return value.newtype_variant().map(FieldType::Enum)
+        };
+        value.unit_variant()?;
+        match variant {
+            Variant::String => Ok(FieldType::String),
+            Variant::I64 => Ok(FieldType::I64),
+            Variant::I32 => Ok(FieldType::I32),
+            Variant::U64 => Ok(FieldType::U64),
+            Variant::U32 => Ok(FieldType::U32),
+            Variant::Date => Ok(FieldType::Date),
+            Variant::Bool => Ok(FieldType::Bool),
+            #[cfg(feature = "zeroize")]
+            Variant::Secret => Ok(FieldType::Secret),
+            #[cfg(not(feature = "zeroize"))]
+            Variant::Secret => Err(secret_unsupported()),
+            Variant::Custom => Ok(FieldType::Custom),
+            Variant::I128 => Ok(FieldType::I128),
+            Variant::U128 => Ok(FieldType::U128),
+            Variant::Decimal => Ok(FieldType::Decimal),
+            Variant::IpAddr => Ok(FieldType::IpAddr),
+            Variant::Cidr => Ok(FieldType::Cidr),
+            Variant::GeoPoint => Ok(FieldType::GeoPoint),
+            Variant::Enum => unreachable!("Enum is read with its variants"),
+            Variant::Null => Ok(FieldType::Null),
+        }
+    }
+}
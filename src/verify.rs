@@ -0,0 +1,41 @@
+use std::fmt;
+
+use crate::structs::*;
+
+/// Inconsistency found by DatabaseClient::verify
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyProblem {
+    /// Table the problem was found in; None for problems with the database file
+    pub table: Option<String>,
+    /// Primary Field of the Entry the problem was found in, if specific to one Entry
+    pub key: Option<Field>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.table, &self.key) {
+            (Some(t), Some(k)) => write!(f, "table {} entry {}: {}", t, k, self.message),
+            (Some(t), None) => write!(f, "table {}: {}", t, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Result of DatabaseClient::verify
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of Tables checked
+    pub tables_checked: usize,
+    /// Number of Entries checked
+    pub entries_checked: usize,
+    /// Every inconsistency found; empty if the database is consistent
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    /// Returns true if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use axum_core::extract::{FromRef, FromRequestParts};
+use axum_core::response::{IntoResponse, Response};
+use http::StatusCode;
+use http::request::Parts;
+use tracing::error;
+
+use crate::{Client, ClientBuilder};
+use crate::structs::*;
+use crate::prelude::*;
+use crate::errors::*;
+
+/// Cloneable handle to a Client for use as the state of a web application; enabled by the
+/// `web` feature.
+///
+/// Clones share the same database, so each request handler may use its own clone.
+/// ```
+/// use persistent_keystore_rs::{Client, SharedClient};
+/// use persistent_keystore_rs::prelude::*;
+/// # use std::path::Path;
+/// let state = SharedClient::build(Client::builder(Path::new("sharedclient.db"))).unwrap();
+/// let mut handler_state = state.clone();
+/// assert!(handler_state.list_tables().unwrap().is_empty());
+/// # std::fs::remove_file("sharedclient.db").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SharedClient {
+    client: Client,
+}
+
+impl SharedClient {
+    /// Creates the database configured by the builder; see ClientBuilder::build
+    pub fn build(builder: ClientBuilder) -> Result<Self, DatabaseError> {
+        Ok(SharedClient{
+            client: builder.build_client()?,
+        })
+    }
+
+    /// Opens the database configured by the builder; see ClientBuilder::open
+    pub fn open(builder: ClientBuilder) -> Result<Self, DatabaseError> {
+        Ok(SharedClient{
+            client: builder.open_client()?,
+        })
+    }
+}
+
+impl Deref for SharedClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl DerefMut for SharedClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+}
+
+/// Table known at compile time, for use with TableHandle
+pub trait NamedTable {
+    /// Name of the Table
+    const NAME: &'static str;
+}
+
+/// Handle to a single Table of a SharedClient; enabled by the `web` feature.
+///
+/// Used as an axum extractor in the handlers of an application whose state contains a
+/// SharedClient; if the Table does not exist the request is rejected with
+/// DatabaseError::TableDoesNotExist.
+/// ```
+/// use persistent_keystore_rs::{Client, NamedTable, SharedClient, TableHandle};
+/// # use persistent_keystore_rs::{FieldType, Table};
+/// # use persistent_keystore_rs::prelude::*;
+/// # use std::path::Path;
+///
+/// struct Users;
+///
+/// impl NamedTable for Users {
+///     const NAME: &'static str = "Users";
+/// }
+///
+/// let mut state = SharedClient::build(Client::builder(Path::new("tablehandle.db"))).unwrap();
+/// # let table = Table::new()
+/// #    .name(String::from("Users"))
+/// #    .primary_field(FieldType::String).unwrap()
+/// #    .add_field(String::from("Name"), FieldType::String).unwrap()
+/// #    .build().unwrap();
+/// # state.create_table(table).unwrap();
+/// let mut users = TableHandle::<Users>::new(&state).unwrap();
+/// assert!(users.scan().unwrap().is_empty());
+/// # std::fs::remove_file("tablehandle.db").unwrap();
+/// ```
+pub struct TableHandle<T: NamedTable> {
+    client: Client,
+    table: PhantomData<fn() -> T>,
+}
+
+impl<T: NamedTable> TableHandle<T> {
+    /// Returns a handle to the Table of the client.
+    /// If the Table does not exist, DatabaseError::TableDoesNotExist is returned.
+    pub fn new(client: &SharedClient) -> Result<Self, DatabaseError> {
+        let mut client = client.client.clone();
+        if !client.list_tables()?.iter().any(|t| t == T::NAME) {
+            error!("Table {} does not exist", T::NAME);
+            return Err(DatabaseError::TableDoesNotExist(T::NAME.to_string()))
+        };
+
+        Ok(TableHandle{
+            client,
+            table: PhantomData,
+        })
+    }
+
+    /// Returns the name of the Table
+    pub fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    /// See DatabaseClient::get
+    pub fn get(&mut self, primary_field: Field) -> Result<Arc<Entry>, DatabaseError> {
+        self.client.get(T::NAME.to_string(), primary_field)
+    }
+
+    /// See DatabaseClient::insert
+    pub fn insert(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.client.insert(T::NAME.to_string(), entry)
+    }
+
+    /// See DatabaseClient::insert_or_update
+    pub fn insert_or_update(&mut self, entry: Entry) -> Result<UpsertOutcome, DatabaseError> {
+        self.client.insert_or_update(T::NAME.to_string(), entry)
+    }
+
+    /// See DatabaseClient::update
+    pub fn update(&mut self, entry: Entry) -> Result<(), DatabaseError> {
+        self.client.update(T::NAME.to_string(), entry)
+    }
+
+    /// See DatabaseClient::delete
+    pub fn delete(&mut self, primary_field: Field) -> Result<(), DatabaseError> {
+        self.client.delete(T::NAME.to_string(), primary_field)
+    }
+
+    /// See DatabaseClient::scan
+    pub fn scan(&mut self) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.client.scan(T::NAME.to_string())
+    }
+
+    /// See DatabaseClient::query
+    pub fn query(&mut self, criteria: HashMap<String, Field>) -> Result<Vec<Arc<Entry>>, DatabaseError> {
+        self.client.query(T::NAME.to_string(), criteria)
+    }
+}
+
+impl<S, T> FromRequestParts<S> for TableHandle<T>
+where
+    SharedClient: FromRef<S>,
+    S: Send + Sync,
+    T: NamedTable,
+{
+    type Rejection = DatabaseError;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        TableHandle::new(&SharedClient::from_ref(state))
+    }
+}
+
+impl DatabaseError {
+    /// Returns the HTTP status code best describing the error; enabled by the `web` feature.
+    ///
+    /// Missing tables and entries are 404, existing ones 409, and invalid tables, entries
+    /// and values 400.  Errors of the database itself are 500, or 503 if the operation may
+    /// succeed when retried.
+    /// ```
+    /// use persistent_keystore_rs::errors::DatabaseError;
+    /// assert_eq!(DatabaseError::EntryDoesNotExists.status_code(), 404);
+    /// ```
+    pub fn status_code(&self) -> u16 {
+        let status = match self {
+            DatabaseError::TableDoesNotExist(_) |
+            DatabaseError::EntryDoesNotExists |
+            DatabaseError::DatabaseDoesNotExist(_) => StatusCode::NOT_FOUND,
+            DatabaseError::TableExists(_) |
+            DatabaseError::EntryExists |
+            DatabaseError::DatabaseExistsError |
+            DatabaseError::ReferenceViolation(_) |
+            DatabaseError::UnsavedChanges(_) => StatusCode::CONFLICT,
+            DatabaseError::TableMissingPrimaryKey |
+            DatabaseError::TableNameNotSet |
+            DatabaseError::TableMustContainFields |
+            DatabaseError::EntryMustContainFields |
+            DatabaseError::UnsupportedField(_) |
+            DatabaseError::MissingRequiredField(_) |
+            DatabaseError::MismatchedFieldType |
+            DatabaseError::UnsupportedFieldType |
+            DatabaseError::InvalidPrimaryKey |
+            DatabaseError::InvalidFormat(_) |
+            DatabaseError::SchemaMismatch(_) |
+            DatabaseError::TypeMismatch(_) |
+            DatabaseError::InvalidName(_) |
+            DatabaseError::UnknownSequence(_) => StatusCode::BAD_REQUEST,
+            DatabaseError::ReadOnlyTable(_) |
+            DatabaseError::AccessDenied(_) => StatusCode::FORBIDDEN,
+            DatabaseError::CapacityExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+            DatabaseError::UnableToGetLock |
+            DatabaseError::WouldBlock |
+            DatabaseError::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+            DatabaseError::EmptyDatabaseFile(_) |
+            DatabaseError::DatabaseIoError(_) |
+            DatabaseError::DatabaseSerializationError(_) |
+            DatabaseError::DatabaseDecompressionError(_) |
+            DatabaseError::DatabaseCompressionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        status.as_u16()
+    }
+}
+
+impl IntoResponse for DatabaseError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, self.to_string()).into_response()
+    }
+}
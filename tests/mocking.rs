@@ -1,3 +1,5 @@
+// Written in the explicit style of the crate; see the lint allowances of src/lib.rs
+#![allow(clippy::needless_return)]
 #[cfg(all(test, feature = "mocks"))]
 use persistent_keystore_rs::MockDatabaseClient;
 #[cfg(all(test, feature = "mocks"))]
@@ -10,6 +12,8 @@ use persistent_keystore_rs::{Field, Entry};
 use persistent_keystore_rs::errors::DatabaseError;
 #[allow(unused_imports)]
 use std::collections::HashMap;
+#[allow(unused_imports)]
+use std::sync::Arc;
 
 #[test]
 fn test_mock_importable() {
@@ -48,11 +52,11 @@ fn test_mock_get_item() {
         mockdb
             .expect_get()
             .with(predicate::eq("MyTable".to_string()), predicate::eq(Field::String("MyField".to_string())))
-            .returning(|_x, y| return Ok(Entry{
+            .returning(|_x, y| return Ok(Arc::new(Entry{
                 primary_field: y,
                 fields: HashMap::new(),
                 last_timestamp: None,
-            })
+            }))
         );
 
         let results = mockdb.get("MyTable".to_string(), Field::String("MyField".to_string()));
@@ -63,10 +67,38 @@ fn test_mock_get_item() {
                     primary_field: Field::String("MyField".to_string()),
                     fields: HashMap::new(),
                     last_timestamp: None,
-                }, r)
+                }, *r)
             },
             Err(e) => panic!("No error expected, received {}", e),
         };
 
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_mock_modify() {
+    #[cfg(all(test, feature = "mocks"))]
+    {
+        let mut mockdb = MockDatabaseClient::new();
+        mockdb
+            .expect_modify()
+            .with(predicate::eq("MyTable".to_string()), predicate::eq(Field::String("MyField".to_string())), predicate::always())
+            .returning(|_x, y, modify| {
+                let mut entry = Entry{
+                    primary_field: y,
+                    fields: HashMap::new(),
+                    last_timestamp: None,
+                };
+                modify(&mut entry);
+                match entry.fields.contains_key("Count") {
+                    true => Ok(()),
+                    false => Err(DatabaseError::MissingRequiredField("Count".to_string())),
+                }
+            }
+        );
+
+        let mut modify = |e: &mut Entry| {
+            e.fields.insert("Count".to_string(), Field::I64(1));
+        };
+        mockdb.modify("MyTable".to_string(), Field::String("MyField".to_string()), &mut modify).unwrap();
+    }
+}